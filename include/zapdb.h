@@ -0,0 +1,53 @@
+/*
+ * C API for zapdb. Build the library with
+ *
+ *     cargo rustc --release --features ffi --crate-type cdylib
+ *
+ * (or --crate-type staticlib) and link against libzapdb.
+ *
+ * Functions returning int return 0 on success and -1 on failure; functions
+ * returning a pointer return NULL on failure. zapdb_last_error() then
+ * describes the failure. Queries use the JSON protocol described in
+ * src/protocol.rs, and rows are returned as JSON objects.
+ */
+#ifndef ZAPDB_H
+#define ZAPDB_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct ZapDb ZapDb;
+typedef struct ZapDbResult ZapDbResult;
+
+/* Valid until the next failing call on the same thread. */
+const char *zapdb_last_error(void);
+
+/* key points to 32 bytes. */
+ZapDb *zapdb_open(const uint8_t *key, const char *wal_path);
+/* Frees db. save_path may be NULL to skip the final snapshot. */
+int zapdb_close(ZapDb *db, const char *save_path);
+int zapdb_load(ZapDb *db, const char *path);
+int zapdb_save(ZapDb *db, const char *path);
+
+/* columns_json: [{"name": "id", "type": "integer", "constraints": ["unique"]}] */
+int zapdb_create_table(ZapDb *db, const char *table, const char *columns_json);
+/* row_json: {"id": 1, "name": "Alice"} */
+int zapdb_insert(ZapDb *db, const char *table, const char *row_json);
+/* Returns the number of rows deleted, or -1. */
+int64_t zapdb_delete(ZapDb *db, const char *table, const char *query_json);
+
+ZapDbResult *zapdb_query(ZapDb *db, const char *table, const char *query_json);
+size_t zapdb_result_count(const ZapDbResult *result);
+/* Valid until the result is freed; NULL if index is out of range. */
+const char *zapdb_result_row(const ZapDbResult *result, size_t index);
+void zapdb_result_free(ZapDbResult *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ZAPDB_H */
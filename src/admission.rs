@@ -0,0 +1,141 @@
+//! Write admission, configured with `DatabaseConfig::admission`.
+//!
+//! Every write takes a permit before it is logged, and holds it until it has
+//! been applied. A write that finds no free permit joins the queue if there
+//! is room and fails with `Busy::QueueFull` otherwise, so a burst of writers
+//! gets a quick error to back off on rather than piling up behind the table
+//! lock. Per-table rates are token buckets refilled continuously. WAL replay
+//! is never turned away.
+
+use crate::{AdmissionConfig, Database, Instant, WriteError};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Semaphore, SemaphorePermit, TryAcquireError};
+
+/// Why a write was turned away. Retrying later may succeed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Busy {
+    QueueFull { limit: usize },
+    /// The table has used up its writes for now; one will be available after
+    /// `retry_after`.
+    RateLimited { table: String, retry_after: Duration },
+}
+
+impl fmt::Display for Busy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Busy::QueueFull { limit } => write!(f, "Too many writes waiting (limit {})", limit),
+            Busy::RateLimited { table, retry_after } => {
+                write!(f, "Writes to table {} are rate limited; retry in {:?}", table, retry_after)
+            }
+        }
+    }
+}
+
+impl Error for Busy {}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub(crate) struct Admission {
+    permits: Semaphore,
+    queued: AtomicUsize,
+    max_queued: usize,
+    rates: HashMap<String, u32>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+// A place in the queue, given up when dropped.
+struct Queued<'a>(&'a AtomicUsize);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Admission {
+    pub(crate) fn new(config: &AdmissionConfig) -> Self {
+        Admission {
+            permits: Semaphore::new(config.max_concurrent_writes.max(1)),
+            queued: AtomicUsize::new(0),
+            max_queued: config.max_queued_writes,
+            rates: config.table_rates.clone(),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn admit(&self, tables: &[&str]) -> Result<SemaphorePermit<'_>, Busy> {
+        let (permit, queued) = match self.permits.try_acquire() {
+            Ok(permit) => (Some(permit), None),
+            Err(TryAcquireError::NoPermits) => {
+                if self.queued.fetch_add(1, Ordering::SeqCst) >= self.max_queued {
+                    self.queued.fetch_sub(1, Ordering::SeqCst);
+                    return Err(Busy::QueueFull { limit: self.max_queued });
+                }
+                (None, Some(Queued(&self.queued)))
+            }
+            Err(TryAcquireError::Closed) => unreachable!("the semaphore is never closed"),
+        };
+        self.take_tokens(tables)?;
+        match permit {
+            Some(permit) => Ok(permit),
+            None => {
+                let permit = self.permits.acquire().await.expect("the semaphore is never closed");
+                drop(queued);
+                Ok(permit)
+            }
+        }
+    }
+
+    // Takes a token for each rate-limited table, or none if any is out.
+    fn take_tokens(&self, tables: &[&str]) -> Result<(), Busy> {
+        let limited: Vec<(&str, f64)> = tables
+            .iter()
+            .filter_map(|t| self.rates.get(*t).map(|rate| (*t, *rate as f64)))
+            .collect();
+        if limited.is_empty() {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        for &(table, rate) in &limited {
+            let bucket = buckets.entry(table.to_string()).or_insert(Bucket {
+                tokens: rate,
+                updated: now,
+            });
+            let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+            bucket.updated = now;
+            if bucket.tokens < 1.0 {
+                return Err(Busy::RateLimited {
+                    table: table.to_string(),
+                    retry_after: Duration::try_from_secs_f64((1.0 - bucket.tokens) / rate).unwrap_or(Duration::MAX),
+                });
+            }
+        }
+        for (table, _) in limited {
+            if let Some(bucket) = buckets.get_mut(table) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Waits for a turn to write to `tables`, up to the operation timeout.
+    /// The write must hold the returned permit until it is done.
+    pub(crate) async fn admit(&self, tables: &[&str]) -> Result<Option<SemaphorePermit<'_>>, WriteError> {
+        match &self.admission {
+            Some(admission) => Ok(Some(self.timed(admission.admit(tables)).await??)),
+            None => Ok(None),
+        }
+    }
+}
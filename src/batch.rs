@@ -0,0 +1,120 @@
+//! Write batches: many writes applied together for throughput.
+//!
+//! A `WriteBatch` queues inserts, updates and deletes until it is flushed.
+//! Flushing goes through admission once, logs every write with a single WAL
+//! append, applies them all under one hold of the table lock, and brings
+//! indexes and Merkle trees up to date once per table at the end rather
+//! than after each write. Unlike a transaction, a batch isn't atomic: each
+//! write succeeds or fails on its own and the rest still apply.
+
+use crate::update_expr::RowUpdate;
+use crate::watch::Changes;
+use crate::{Database, OnConflict, Operation, Query, UpdateFn, Value, WriteError};
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+
+#[derive(Clone, Default)]
+pub struct WriteBatch {
+    operations: Vec<(Operation, Option<UpdateFn>)>,
+}
+
+impl WriteBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, table_name: &str, row: HashMap<String, Value>) {
+        let table_name = table_name.to_string();
+        self.operations.push((Operation::Insert { table_name, row }, None));
+    }
+
+    pub fn update(&mut self, table_name: &str, query: Query, update_fn: UpdateFn) {
+        let table_name = table_name.to_string();
+        self.operations.push((Operation::Update { table_name, query }, Some(update_fn)));
+    }
+
+    pub fn delete(&mut self, table_name: &str, query: Query) {
+        let table_name = table_name.to_string();
+        self.operations.push((Operation::Delete { table_name, query }, None));
+    }
+
+    pub fn insert_on_conflict(&mut self, table_name: &str, row: HashMap<String, Value>, on_conflict: OnConflict) {
+        let table_name = table_name.to_string();
+        self.operations.push((Operation::InsertOnConflict { table_name, row, on_conflict }, None));
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+impl Database {
+    /// Applies and empties `batch`. Returns each write's result in the
+    /// order they were queued: the rows it changed, or why it failed. Fails
+    /// as a whole, with nothing written, only if the batch couldn't get its
+    /// turn or couldn't be logged.
+    pub async fn flush(&self, batch: &mut WriteBatch) -> Result<Vec<Result<usize, WriteError>>, WriteError> {
+        self.check_open()?;
+        if batch.is_empty() {
+            return Ok(Vec::new());
+        }
+        let touched: Vec<String> = batch.operations.iter().map(|(op, _)| op.table_name().to_string()).collect();
+        let touched: Vec<&str> = touched.iter().map(String::as_str).collect();
+        let _admitted = self.admit(&touched).await?;
+        let mut wal_writer = self.timed(self.wal_writer.write()).await?;
+        let mut tables = self.timed(self.tables.write()).await?;
+        // Writes the session's role isn't allowed are neither logged nor
+        // applied.
+        let denied: Vec<Option<String>> = batch.operations.iter().map(|(op, _)| self.check_operation(op).err()).collect();
+        let entries: Vec<_> = batch
+            .operations
+            .iter()
+            .zip(&denied)
+            .filter(|((op, _), denied)| denied.is_none() && !tables.get(op.table_name()).is_some_and(|t| t.ephemeral))
+            .map(|((op, _), _)| op.wal_entry())
+            .collect();
+        wal_writer.log_all(&entries).map_err(|e| e.to_string())?;
+        drop(wal_writer);
+
+        // Writes leave maintenance to the end while this is raised. The table
+        // lock is held throughout, so nothing else sees it raised.
+        self.deferred_maintenance.fetch_add(1, Ordering::SeqCst);
+        let mut changes = Changes::new();
+        let results = std::mem::take(&mut batch.operations)
+            .into_iter()
+            .zip(denied)
+            .map(|((op, update_fn), denied)| {
+                if let Some(reason) = denied {
+                    return Err(reason.into());
+                }
+                match op {
+                    Operation::Insert { table_name, row } => self
+                        .insert_internal(&mut tables, &table_name, row, &mut changes)
+                        .map(|_| 1),
+                    Operation::Update { table_name, query } => {
+                        let update = RowUpdate::Fn(update_fn.expect("updates are queued with a function"));
+                        Ok(self.update_internal(&mut tables, &table_name, &query, update, &mut changes)?)
+                    }
+                    Operation::Delete { table_name, query } => {
+                        Ok(self.delete_internal(&mut tables, &table_name, &query, &mut changes)?)
+                    }
+                    Operation::InsertOnConflict { table_name, row, on_conflict } => self
+                        .insert_on_conflict_internal(&mut tables, &table_name, row, on_conflict, &mut changes)
+                        .map(|outcome| outcome.inserted()),
+                }
+            })
+            .collect();
+        for name in &touched {
+            if let Some(table) = tables.get_mut(*name) {
+                table.finish_maintenance();
+            }
+        }
+        self.deferred_maintenance.fetch_sub(1, Ordering::SeqCst);
+        self.finish_write(&mut tables, &touched, changes);
+        Ok(results)
+    }
+}
@@ -0,0 +1,343 @@
+use argon2::Argon2;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use zapdb::{to_row, Column, Constraint, DataType, Database, DatabaseConfig, Query, Row};
+
+const HELP: &str = "\
+Commands:
+  .tables                          List tables and their row counts
+  .schema [table]                  Show column definitions
+  .create <table> <col:type[:constraint...]>...
+                                   Create a table. Constraints: unique, not_null,
+                                   fk=<table>.<column>
+  .index <table> <column>          Create an index
+  .insert <table> <json object>    Insert a row, e.g. {\"id\": 1, \"name\": \"Alice\"}
+  .select <table> [json query]     Query a table (defaults to \"match_all\")
+  .delete <table> <json query>     Delete matching rows
+  .save                            Save the database file
+  .backup <path>                   Write a snapshot to another file
+  .verify                          Check Merkle integrity of every table
+  .help                            Show this help
+  .quit                            Save and exit
+
+Queries use the JSON query protocol, e.g.
+  {\"condition\": {\"column\": \"age\", \"op\": \"gte\", \"value\": 25}}";
+
+#[tokio::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("Usage: {} <database.zap>", args[0]);
+        std::process::exit(1);
+    }
+    let db_path = args[1].clone();
+    let wal_path = Path::new(&db_path).with_extension("wal");
+
+    let key = match read_key(&db_path) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let db = match Database::open(key, &wal_path.to_string_lossy(), DatabaseConfig::default()) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to open {}: {}", db_path, e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = db.load(&db_path).await {
+        eprintln!("Failed to open {}: {}", db_path, e);
+        std::process::exit(1);
+    }
+    println!("zapdb shell. Type .help for commands.");
+
+    let stdin = io::stdin();
+    loop {
+        print!("zapdb> ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("{}", e);
+                break;
+            }
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == ".quit" || line == ".exit" {
+            break;
+        }
+        if let Err(e) = run_command(&db, &db_path, line).await {
+            println!("Error: {}", e);
+        }
+    }
+
+    if let Err(e) = db.close(Some(&db_path)).await {
+        eprintln!("Failed to save {}: {}", db_path, e);
+        std::process::exit(1);
+    }
+}
+
+// The key comes from ZAPDB_KEY (64 hex characters) or is derived with
+// Argon2id from a password, read without echo, and a random salt kept next to
+// the database in a `.salt` file.
+fn read_key(db_path: &str) -> Result<[u8; 32], String> {
+    if let Ok(hex) = env::var("ZAPDB_KEY") {
+        return parse_hex_key(&hex);
+    }
+    let password = match env::var("ZAPDB_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => rpassword::prompt_password("Password: ").map_err(|e| e.to_string())?,
+    };
+    let salt_path = Path::new(db_path).with_extension("salt");
+    let salt = match fs::read(&salt_path) {
+        Ok(salt) => salt,
+        // Databases made before salts were derived their key this way.
+        Err(e) if e.kind() == io::ErrorKind::NotFound && Path::new(db_path).exists() => {
+            eprintln!("Warning: {} has no salt file, so its key is derived without one", db_path);
+            return Ok(blake3::derive_key("zapdb-cli password key v1", password.as_bytes()));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let salt = rand::random::<[u8; 16]>().to_vec();
+            fs::write(&salt_path, &salt).map_err(|e| format!("Failed to write {}: {}", salt_path.display(), e))?;
+            salt
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", salt_path.display(), e)),
+    };
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), &salt, &mut key)
+        .map_err(|e| e.to_string())?;
+    Ok(key)
+}
+
+fn parse_hex_key(hex: &str) -> Result<[u8; 32], String> {
+    let hex = hex.trim();
+    // Checking every byte first also keeps the slicing below on char
+    // boundaries.
+    if hex.len() != 64 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("ZAPDB_KEY must be 64 hex characters".to_string());
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).expect("checked to be hex");
+    }
+    Ok(key)
+}
+
+async fn run_command(db: &Database, db_path: &str, line: &str) -> Result<(), String> {
+    if !line.starts_with('.') {
+        return Err("Unknown input. Commands start with '.', see .help".to_string());
+    }
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    match command {
+        ".help" => println!("{}", HELP),
+        ".tables" => {
+            let tables = db.tables.read().await;
+            let mut names: Vec<&String> = tables.keys().collect();
+            names.sort();
+            for name in names {
+                let table = &tables[name];
+                let suffix = if table.is_ephemeral() { ", ephemeral" } else { "" };
+                println!("{} ({} rows{})", name, table.row_count(), suffix);
+            }
+        }
+        ".schema" => {
+            let names = if rest.is_empty() {
+                db.table_names().await
+            } else {
+                vec![rest.to_string()]
+            };
+            for name in names {
+                let columns = db
+                    .table_columns(&name)
+                    .await
+                    .ok_or_else(|| format!("Table {} not found", name))?;
+                println!("{}", name);
+                for column in columns {
+                    println!("  {}", describe_column(&column));
+                }
+            }
+        }
+        ".create" => {
+            let mut parts = rest.split_whitespace();
+            let table = parts.next().ok_or("Usage: .create <table> <col:type>...")?;
+            let columns = parts.map(parse_column).collect::<Result<Vec<_>, _>>()?;
+            if columns.is_empty() {
+                return Err("A table needs at least one column".to_string());
+            }
+            db.create_table(table.to_string(), columns).await?;
+        }
+        ".index" => {
+            let mut parts = rest.split_whitespace();
+            match (parts.next(), parts.next()) {
+                (Some(table), Some(column)) => db.create_index(table, column).await?,
+                _ => return Err("Usage: .index <table> <column>".to_string()),
+            }
+        }
+        ".insert" => {
+            let (table, json) = split_table(rest, ".insert <table> <json object>")?;
+            let value: serde_json::Value =
+                serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+            let columns = db
+                .table_columns(table)
+                .await
+                .ok_or_else(|| format!("Table {} not found", table))?;
+            let row = to_row(&value, &columns)?;
+            db.insert(table, row).await?;
+        }
+        ".select" => {
+            let (table, json) = split_table(rest, ".select <table> [json query]")?;
+            let query = if json.is_empty() {
+                Query::MatchAll
+            } else {
+                db.query_from_json(table, json).await?
+            };
+            let (rows, elapsed) = db.select(table, &query).await?;
+            let columns = db.table_columns(table).await.unwrap_or_default();
+            print_rows(&columns, &rows);
+            println!("{} row(s) in {:?}", rows.len(), elapsed);
+        }
+        ".delete" => {
+            let (table, json) = split_table(rest, ".delete <table> <json query>")?;
+            let query = db.query_from_json(table, json).await?;
+            let deleted = db.delete(table, &query).await?;
+            println!("Deleted {} row(s)", deleted);
+        }
+        ".save" => db.save(db_path).await.map_err(|e| e.to_string())?,
+        ".backup" => {
+            if rest.is_empty() {
+                return Err("Usage: .backup <path>".to_string());
+            }
+            db.backup(rest).await.map_err(|e| e.to_string())?;
+            println!("Backup written to {}", rest);
+        }
+        ".verify" => {
+            if db.verify_integrity().await {
+                println!("ok");
+            } else {
+                println!("Integrity check FAILED");
+            }
+        }
+        other => return Err(format!("Unknown command {}, see .help", other)),
+    }
+    Ok(())
+}
+
+fn split_table<'a>(rest: &'a str, usage: &str) -> Result<(&'a str, &'a str), String> {
+    if rest.is_empty() {
+        return Err(format!("Usage: {}", usage));
+    }
+    let (table, json) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    Ok((table, json.trim()))
+}
+
+fn parse_column(spec: &str) -> Result<Column, String> {
+    let mut parts = spec.split(':');
+    let name = parts.next().filter(|n| !n.is_empty());
+    let data_type = parts.next();
+    let (name, data_type) = match (name, data_type) {
+        (Some(name), Some(data_type)) => (name, data_type.parse::<DataType>()?),
+        _ => return Err(format!("Invalid column {}, expected name:type", spec)),
+    };
+    let constraints = parts
+        .map(|constraint| match constraint.to_ascii_lowercase().as_str() {
+            "unique" => Ok(Constraint::Unique),
+            "not_null" => Ok(Constraint::NotNull),
+            other => match other.strip_prefix("fk=").and_then(|fk| fk.split_once('.')) {
+                Some((table, column)) => Ok(Constraint::ForeignKey {
+                    table: table.to_string(),
+                    column: column.to_string(),
+                }),
+                None => Err(format!("Unknown constraint {}", constraint)),
+            },
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    Ok(Column::new(name.to_string(), data_type, constraints))
+}
+
+fn describe_column(column: &Column) -> String {
+    let mut description = format!("{} {:?}", column.name, column.data_type);
+    for constraint in &column.constraints {
+        match constraint {
+            Constraint::NotNull => description.push_str(" NOT NULL"),
+            Constraint::Unique => description.push_str(" UNIQUE"),
+            Constraint::ForeignKey { table, column } => {
+                description.push_str(&format!(" REFERENCES {}({})", table, column))
+            }
+        }
+    }
+    description
+}
+
+fn print_rows(columns: &[Column], rows: &[Row]) {
+    // Schema columns first, then anything else (join results, aggregates).
+    let mut headers: Vec<String> = columns
+        .iter()
+        .map(|c| c.name.clone())
+        .filter(|name| rows.iter().any(|r| r.contains_key(name)))
+        .collect();
+    let mut extra: Vec<String> = rows
+        .iter()
+        .flat_map(|r| r.keys())
+        .filter(|k| !headers.contains(k))
+        .cloned()
+        .collect();
+    extra.sort();
+    extra.dedup();
+    headers.extend(extra);
+    if headers.is_empty() {
+        return;
+    }
+
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            headers
+                .iter()
+                .map(|h| row.get(h).map(|v| v.to_string()).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain(std::iter::once(h.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let separator: String = widths
+        .iter()
+        .map(|w| "-".repeat(w + 2))
+        .collect::<Vec<_>>()
+        .join("+");
+    let format_line = |values: &[String]| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!(" {:<width$} ", v, width = w))
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    println!("{}", format_line(&headers));
+    println!("{}", separator);
+    for row in &cells {
+        println!("{}", format_line(row));
+    }
+}
@@ -0,0 +1,163 @@
+//! Encrypted bundles of selected tables, for moving data between databases.
+//!
+//! A bundle holds a manifest, then the tables in the snapshot layout (see
+//! `recovery`), compressed and encrypted with the recipient's key like a
+//! snapshot. The manifest is JSON and lists each table with its row count
+//! and a BLAKE3 hash of its columns and rows, which `import_bundle` checks
+//! before creating anything. Rows keep their ids within the bundle but get
+//! new ones when imported.
+
+use crate::codec::invalid;
+use crate::recovery::{self, put_frame, take_frame, RecoveryReport};
+use crate::{begin_transaction, seal_snapshot, unpack_snapshot, Codec, Database, Query, Table};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub created_at: DateTime<Utc>,
+    pub tables: Vec<BundledTable>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BundledTable {
+    pub name: String,
+    pub rows: usize,
+    /// Hex-encoded BLAKE3 hash of the table's columns and rows.
+    pub hash: String,
+}
+
+// Hashes the columns and the rows' Merkle leaves, which don't depend on the
+// codec the bundle was written with.
+fn table_hash(table: &Table) -> io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&Codec::Bincode.encode(&table.columns)?);
+    for leaf in table.fresh_merkle_leaves() {
+        hasher.update(&leaf);
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+impl Database {
+    /// Writes `tables` to `path` as a bundle encrypted with `recipient_key`,
+    /// keeping only the rows matching the table's query in `filters`, if it
+    /// has one. Returns the bundle's manifest.
+    pub async fn export_bundle(
+        &self,
+        tables: &[&str],
+        filters: &HashMap<String, Query>,
+        path: &str,
+        recipient_key: &[u8; 32],
+    ) -> io::Result<BundleManifest> {
+        self.check_open_io()?;
+        let view = self.read_tables().await?;
+        let mut selected = Vec::new();
+        for name in tables {
+            let table = view
+                .get(name)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Table {} not found", name)))?;
+            self.check_exportable(name)
+                .map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e))?;
+            let mut bundled = Table::new(name.to_string(), table.columns.clone(), false);
+            bundled.next_row_id = table.next_row_id;
+            let positions = match filters.get(*name) {
+                Some(query) => {
+                    self.check_query(query, &table.columns).map_err(io::Error::other)?;
+                    let optimized_query = self.query_planner.optimize(query.clone(), table);
+                    self.execute_query(table, &optimized_query).map_err(io::Error::other)?
+                }
+                None => (0..table.data.len()).collect(),
+            };
+            for i in positions {
+                bundled.data.push_back(Arc::clone(&table.data[i]));
+                bundled.row_ids.push_back(table.row_ids[i]);
+            }
+            selected.push(bundled);
+        }
+        drop(view);
+
+        let manifest = BundleManifest {
+            created_at: Utc::now(),
+            tables: selected
+                .iter()
+                .map(|table| {
+                    Ok(BundledTable {
+                        name: table.name.clone(),
+                        rows: table.data.len(),
+                        hash: table_hash(table)?,
+                    })
+                })
+                .collect::<io::Result<_>>()?,
+        };
+        let mut encoded = Vec::new();
+        put_frame(&mut encoded, &serde_json::to_vec(&manifest)?);
+        let named: Vec<(&str, &Table)> = selected.iter().map(|t| (t.name.as_str(), t)).collect();
+        encoded.extend(recovery::encode_snapshot(
+            self.config.codec,
+            &named,
+            &HashMap::new(),
+            &HashMap::new(),
+            &HashMap::new(),
+        )?);
+        self.storage.write(path, &seal_snapshot(recipient_key, self.config.cipher, &encoded)?)?;
+        Ok(manifest)
+    }
+
+    /// Creates the tables in the bundle at `path`, decrypted with `key`, and
+    /// inserts their rows in one transaction. Fails before creating anything
+    /// if the bundle doesn't match its manifest or one of its tables already
+    /// exists.
+    pub async fn import_bundle(&self, path: &str, key: &[u8; 32]) -> io::Result<BundleManifest> {
+        self.check_open_io()?;
+        let buffer = self
+            .storage
+            .read(path)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path)))?;
+        let mut report = RecoveryReport::default();
+        let decompressed = unpack_snapshot(key, &buffer, &mut report, false)?;
+        let mut bytes = &decompressed[..];
+        let manifest: BundleManifest = serde_json::from_slice(take_frame(&mut bytes).map_err(invalid)?)?;
+        let contents = recovery::decode_snapshot(bytes, &mut report)?;
+        report.into_result()?;
+
+        if contents.tables.len() != manifest.tables.len() {
+            return Err(invalid(format!(
+                "Bundle holds {} tables but its manifest lists {}",
+                contents.tables.len(),
+                manifest.tables.len()
+            )));
+        }
+        let existing = self.table_names().await;
+        for listed in &manifest.tables {
+            let table = contents
+                .tables
+                .get(&listed.name)
+                .ok_or_else(|| invalid(format!("Table {} is missing from the bundle", listed.name)))?;
+            if table.data.len() != listed.rows || table_hash(table)? != listed.hash {
+                return Err(invalid(format!("Table {} doesn't match the manifest", listed.name)));
+            }
+            if existing.contains(&listed.name) {
+                return Err(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    format!("Table {} already exists", listed.name),
+                ));
+            }
+        }
+
+        let mut transaction = begin_transaction();
+        for listed in &manifest.tables {
+            let table = &contents.tables[&listed.name];
+            self.create_table(listed.name.clone(), table.columns.clone())
+                .await
+                .map_err(io::Error::other)?;
+            for row in &table.data {
+                transaction.insert(listed.name.clone(), (**row).clone().into_map());
+            }
+        }
+        self.commit(transaction).await.map_err(io::Error::other)?;
+        Ok(manifest)
+    }
+}
@@ -0,0 +1,249 @@
+//! Change data capture: publishing a table's changes to external systems.
+//!
+//! `Database::attach_sink` follows a table's change stream, the same one
+//! `watch` delivers, and hands committed changes to a `ChangeSink` in commit
+//! order, a batch at a time. A batch is retried until the sink accepts it,
+//! and only then is the sink's position advanced in the `_cdc_checkpoints`
+//! table. Delivery is at-least-once: a batch the sink received but reported
+//! as failed is sent again, so every change carries its position for
+//! consumers to drop repeats. Positions carry on from the checkpoint after a
+//! restart, but changes wait for delivery in memory, and any not yet
+//! delivered when the process stops are not sent.
+//!
+//! Kafka and NATS sinks are built in behind the `kafka` and `nats` features.
+
+use crate::watch::WatchEvent;
+use crate::{row_to_json, Column, Condition, Constraint, DataType, Database, Operator, Query, RowId, Value};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+
+/// The table sink positions are checkpointed in.
+pub const CHECKPOINT_TABLE: &str = "_cdc_checkpoints";
+
+/// A committed change, as handed to a sink.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeRecord {
+    /// Counts the sink's changes from 1, across restarts.
+    pub position: u64,
+    pub table: String,
+    pub row_id: RowId,
+    pub event: WatchEvent,
+    /// When the sink picked the change up.
+    pub timestamp: DateTime<Utc>,
+}
+
+impl ChangeRecord {
+    /// The change as JSON, with the row before and after it:
+    /// `{"position", "table", "row_id", "operation", "before", "after", "timestamp"}`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let (operation, before, after) = match &self.event {
+            WatchEvent::Insert(row) => ("insert", None, Some(row)),
+            WatchEvent::Update { old, new } => ("update", Some(old), Some(new)),
+            WatchEvent::Delete(row) => ("delete", Some(row), None),
+        };
+        json!({
+            "position": self.position,
+            "table": self.table,
+            "row_id": self.row_id,
+            "operation": operation,
+            "before": before.map(row_to_json),
+            "after": after.map(row_to_json),
+            "timestamp": self.timestamp.to_rfc3339(),
+        })
+    }
+}
+
+pub type SinkFuture<'a> = Pin<Box<dyn Future<Output = Result<(), String>> + Send + 'a>>;
+
+/// Somewhere changes are published to.
+pub trait ChangeSink: Send + 'static {
+    /// Publishes `changes`, in order, resolving once the destination has
+    /// accepted all of them. On an error the whole batch is sent again.
+    fn publish<'a>(&'a mut self, changes: &'a [ChangeRecord]) -> SinkFuture<'a>;
+}
+
+#[derive(Clone, Debug)]
+pub struct SinkOptions {
+    /// The most changes handed to the sink at once.
+    pub batch_size: usize,
+    /// How long to wait before sending a failed batch again.
+    pub retry_delay: Duration,
+}
+
+impl Default for SinkOptions {
+    fn default() -> Self {
+        SinkOptions {
+            batch_size: 100,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SinkStatus {
+    /// The position of the last change the sink accepted.
+    pub position: u64,
+    /// Changes accepted since the sink was attached.
+    pub delivered: u64,
+    pub failures: u64,
+    pub last_error: Option<String>,
+}
+
+struct AttachedSink {
+    handle: AbortHandle,
+    status: Arc<Mutex<SinkStatus>>,
+}
+
+#[derive(Default)]
+pub(crate) struct Sinks {
+    attached: Mutex<HashMap<String, AttachedSink>>,
+}
+
+fn sink_query(name: &str) -> Query {
+    Query::Condition(Condition {
+        column: "sink".to_string(),
+        operator: Operator::Eq,
+        value: Value::String(name.to_string()),
+    })
+}
+
+impl Database {
+    /// Publishes every change committed to `table_name` from now on to
+    /// `sink`, until the sink is detached or the database closes. The
+    /// changes are numbered on from the last position checkpointed under
+    /// `name`. Attaching a name again replaces the previous sink.
+    pub async fn attach_sink<S: ChangeSink>(
+        self: &Arc<Self>,
+        name: &str,
+        table_name: &str,
+        mut sink: S,
+        options: SinkOptions,
+    ) -> Result<(), String> {
+        if options.batch_size == 0 {
+            return Err("Sink batch size must be at least 1".to_string());
+        }
+        // Stop the previous sink first so the two don't share positions.
+        self.detach_sink(name);
+        // Subscribe before reading the checkpoint so no change falls between.
+        let mut watcher = self.watch(table_name, Query::MatchAll).await?;
+        let position = self.sink_checkpoint(name, table_name).await?;
+        let status = Arc::new(Mutex::new(SinkStatus {
+            position,
+            ..Default::default()
+        }));
+
+        let db: Weak<Database> = Arc::downgrade(self);
+        let task_status = Arc::clone(&status);
+        let sink_name = name.to_string();
+        let table = table_name.to_string();
+        let handle = self.spawn_background(async move {
+            let mut position = position;
+            while let Some(first) = watcher.recv_with_row_id().await {
+                let mut batch = vec![first];
+                while batch.len() < options.batch_size {
+                    match watcher.try_recv_with_row_id() {
+                        Some(change) => batch.push(change),
+                        None => break,
+                    }
+                }
+                let timestamp = Utc::now();
+                let records: Vec<ChangeRecord> = batch
+                    .into_iter()
+                    .zip(position + 1..)
+                    .map(|((row_id, event), position)| ChangeRecord {
+                        position,
+                        table: table.clone(),
+                        row_id,
+                        event,
+                        timestamp,
+                    })
+                    .collect();
+
+                while let Err(e) = sink.publish(&records).await {
+                    {
+                        let mut status = task_status.lock().unwrap();
+                        status.failures += 1;
+                        status.last_error = Some(e);
+                    }
+                    tokio::time::sleep(options.retry_delay).await;
+                    if db.upgrade().is_none_or(|db| db.is_closed()) {
+                        return;
+                    }
+                }
+                position += records.len() as u64;
+
+                let Some(db) = db.upgrade() else { return };
+                let delta = Value::Integer(records.len() as i64);
+                let checkpointed = db.increment(CHECKPOINT_TABLE, &sink_query(&sink_name), "position", delta).await;
+                let mut status = task_status.lock().unwrap();
+                status.position = position;
+                status.delivered += records.len() as u64;
+                if let Err(e) = checkpointed {
+                    status.failures += 1;
+                    status.last_error = Some(format!("Failed to checkpoint: {}", e));
+                }
+            }
+        });
+        let previous = self
+            .sinks
+            .attached
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), AttachedSink { handle, status });
+        if let Some(previous) = previous {
+            previous.handle.abort();
+        }
+        Ok(())
+    }
+
+    /// Stops publishing to a sink. Its checkpoint is kept. Returns false if
+    /// there was no sink by that name.
+    pub fn detach_sink(&self, name: &str) -> bool {
+        match self.sinks.attached.lock().unwrap().remove(name) {
+            Some(sink) => {
+                sink.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn sink_status(&self, name: &str) -> Option<SinkStatus> {
+        let attached = self.sinks.attached.lock().unwrap();
+        attached.get(name).map(|sink| sink.status.lock().unwrap().clone())
+    }
+
+    // The last position checkpointed for `name`, creating the checkpoint
+    // table and the sink's row the first time.
+    async fn sink_checkpoint(&self, name: &str, table_name: &str) -> Result<u64, String> {
+        if !self.table_names().await.iter().any(|t| t == CHECKPOINT_TABLE) {
+            let columns = vec![
+                Column::new("sink".to_string(), DataType::String, vec![Constraint::Unique]),
+                Column::new("table".to_string(), DataType::String, vec![]),
+                Column::new("position".to_string(), DataType::Integer, vec![]),
+            ];
+            // Another sink may have created it in the meantime.
+            if let Err(e) = self.create_table(CHECKPOINT_TABLE.to_string(), columns).await {
+                if !self.table_names().await.iter().any(|t| t == CHECKPOINT_TABLE) {
+                    return Err(e);
+                }
+            }
+        }
+        if let Some(row) = self.first(CHECKPOINT_TABLE, &sink_query(name)).await? {
+            return Ok(row.get_i64("position").unwrap_or(0) as u64);
+        }
+        let row = HashMap::from([
+            ("sink".to_string(), Value::String(name.to_string())),
+            ("table".to_string(), Value::String(table_name.to_string())),
+            ("position".to_string(), Value::Integer(0)),
+        ]);
+        self.insert(CHECKPOINT_TABLE, row).await.map_err(|e| e.to_string())?;
+        Ok(0)
+    }
+}
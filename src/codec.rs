@@ -0,0 +1,95 @@
+//! Encoding of snapshots and WAL entries, per `Codec`.
+//!
+//! Snapshots and WALs start with a header naming the format version and the
+//! codec that wrote them, so a database reads files in any codec, and a new
+//! `DatabaseConfig::codec` takes effect as files are rewritten: the WAL on
+//! the next `save`, snapshots when they are next written. Files from before
+//! headers existed are read as bincode.
+//!
+//! Versions 2 and 3 only change the snapshot layout: version 2 frames each
+//! table and row separately (see `recovery`), and version 3 adds row ids.
+//! WALs read the same in every version.
+
+use crate::Codec;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io;
+
+const MAGIC: &[u8; 4] = b"ZAPC";
+pub(crate) const FORMAT_VERSION: u8 = 3;
+
+impl Codec {
+    fn id(self) -> u8 {
+        match self {
+            Codec::Bincode => 0,
+            Codec::Postcard => 1,
+            Codec::MessagePack => 2,
+        }
+    }
+
+    pub(crate) fn header(self) -> Vec<u8> {
+        let mut header = MAGIC.to_vec();
+        header.extend([FORMAT_VERSION, self.id()]);
+        header
+    }
+
+    /// Strips a header from the start of `bytes` and returns the codec it
+    /// names, or `None` if `bytes` doesn't start with one.
+    pub(crate) fn read_header(bytes: &mut &[u8]) -> io::Result<Option<Codec>> {
+        Ok(Codec::read_versioned_header(bytes)?.map(|(codec, _)| codec))
+    }
+
+    /// Like `read_header`, also returning the format version.
+    pub(crate) fn read_versioned_header(bytes: &mut &[u8]) -> io::Result<Option<(Codec, u8)>> {
+        let Some(rest) = bytes.strip_prefix(MAGIC) else {
+            return Ok(None);
+        };
+        let (version, codec) = match rest {
+            [version @ 1..=FORMAT_VERSION, id, ..] => match id {
+                0 => (*version, Codec::Bincode),
+                1 => (*version, Codec::Postcard),
+                2 => (*version, Codec::MessagePack),
+                _ => return Err(invalid(format!("Unknown codec {}", id))),
+            },
+            [version, _, ..] => return Err(invalid(format!("Unsupported format version {}", version))),
+            _ => return Err(invalid("Truncated header".to_string())),
+        };
+        *bytes = &rest[2..];
+        Ok(Some((codec, version)))
+    }
+
+    pub(crate) fn encode<T: Serialize + ?Sized>(self, value: &T) -> io::Result<Vec<u8>> {
+        match self {
+            Codec::Bincode => bincode::serialize(value).map_err(io::Error::other),
+            Codec::Postcard => postcard::to_stdvec(value).map_err(io::Error::other),
+            Codec::MessagePack => rmp_serde::to_vec(value).map_err(io::Error::other),
+        }
+    }
+
+    /// Decodes one value from the start of `bytes` and advances past it.
+    pub(crate) fn decode<T: DeserializeOwned>(self, bytes: &mut &[u8]) -> io::Result<T> {
+        match self {
+            Codec::Bincode => {
+                // Same options as `bincode::deserialize_from`, plus a limit so
+                // a corrupt length fails instead of allocating it.
+                use bincode::Options;
+                bincode::DefaultOptions::new()
+                    .with_fixint_encoding()
+                    .allow_trailing_bytes()
+                    .with_limit(bytes.len() as u64)
+                    .deserialize_from(bytes)
+                    .map_err(|e| invalid(e.to_string()))
+            }
+            Codec::Postcard => {
+                let (value, rest) = postcard::take_from_bytes(bytes).map_err(|e| invalid(e.to_string()))?;
+                *bytes = rest;
+                Ok(value)
+            }
+            Codec::MessagePack => rmp_serde::from_read(bytes).map_err(|e| invalid(e.to_string())),
+        }
+    }
+}
+
+pub(crate) fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,55 @@
+//! Converting written values to their column's type, per `Coercion`.
+
+use crate::{Coercion, Column, DataType, Value};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Integers of at most this magnitude convert to f64 exactly.
+const MAX_EXACT_FLOAT: i64 = 1 << 53;
+
+impl Value {
+    /// Converts the value to `data_type` if `coercion` allows it, otherwise
+    /// returns it unchanged.
+    pub fn coerce(self, data_type: &DataType, coercion: Coercion) -> Value {
+        let lossy = match coercion {
+            Coercion::Strict => return self,
+            Coercion::Lossless => false,
+            Coercion::Lossy => true,
+        };
+        let converted = match (&self, data_type) {
+            (Value::Integer(i), DataType::Float) if lossy || i.abs() <= MAX_EXACT_FLOAT => {
+                Some(Value::Float(*i as f64))
+            }
+            (Value::Float(f), DataType::Integer)
+                if f.is_finite() && (lossy || f.fract() == 0.0) && f.abs() < i64::MAX as f64 =>
+            {
+                Some(Value::Integer(f.trunc() as i64))
+            }
+            (Value::String(s), DataType::Uuid) => Uuid::parse_str(s).ok().map(Value::Uuid),
+            (Value::String(s), DataType::DateTime) => DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|dt| Value::DateTime(dt.with_timezone(&Utc))),
+            (Value::String(s), DataType::Integer) if lossy => s.trim().parse().ok().map(Value::Integer),
+            (Value::String(s), DataType::Float) if lossy => s.trim().parse().ok().map(Value::Float),
+            (Value::String(s), DataType::Boolean) if lossy => s.trim().parse().ok().map(Value::Boolean),
+            (
+                Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::DateTime(_) | Value::Uuid(_),
+                DataType::String,
+            ) if lossy => Some(Value::String(self.to_string())),
+            _ => None,
+        };
+        converted.unwrap_or(self)
+    }
+}
+
+pub(crate) fn coerce_row(row: &mut HashMap<String, Value>, columns: &[Column], coercion: Coercion) {
+    if coercion == Coercion::Strict {
+        return;
+    }
+    for column in columns {
+        if let Some(value) = row.remove(&column.name) {
+            row.insert(column.name.clone(), value.coerce(&column.data_type, coercion));
+        }
+    }
+}
@@ -0,0 +1,253 @@
+//! Value indexes, hashed or ordered.
+//!
+//! A hash index finds the rows holding a value; range conditions still read
+//! every key. An ordered index keeps its keys sorted, so `Gt`, `Gte`, `Lt`
+//! and `Lte` read only the keys in range, as does a pair of them on the same
+//! column under one `And`, which is how a range with both ends is written.
+//!
+//! `Value`'s own ordering counts values it can't compare, such as nulls and
+//! values of different types, as equal, so ordered keys sort by type first,
+//! numbers together, then by value. Keys found in a range are still checked
+//! against the conditions, which is where a float bound finds the integers
+//! equal to it and NaN matches nothing.
+
+use crate::row_ids::{IdList, RowId};
+use crate::{compare_int_float, Condition, Database, Operator, Query, Table, Value};
+use imbl::{HashMap, OrdMap};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::ops::Bound;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexKind {
+    /// Finds rows by value.
+    #[default]
+    Hash,
+    /// Finds rows by value or by range of values.
+    Ordered,
+}
+
+// Persistent maps, so published copies of a table share all but the
+// entries a write changed.
+#[derive(Clone)]
+pub(crate) enum ColumnIndex {
+    Hash(HashMap<Value, IdList>),
+    Ordered(OrdMap<OrderedKey, IdList>),
+}
+
+/// A value in an ordered index.
+#[derive(Clone, Debug)]
+pub(crate) struct OrderedKey(Value);
+
+// Orders values of different types by type, numbers together.
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Integer(_) | Value::Float(_) => 0,
+        Value::String(_) => 1,
+        Value::Boolean(_) => 2,
+        Value::DateTime(_) => 3,
+        Value::Uuid(_) => 4,
+        Value::Json(_) => 5,
+        Value::Null => 6,
+        Value::Param(_) => 7,
+    }
+}
+
+impl Ord for OrderedKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b) = (&self.0, &other.0);
+        type_rank(a).cmp(&type_rank(b)).then_with(|| match (a, b) {
+            // NaN sorts after every other number, and an integer before the
+            // float equal to it.
+            (Value::Float(x), Value::Float(y)) => x.partial_cmp(y).unwrap_or_else(|| x.is_nan().cmp(&y.is_nan())),
+            (Value::Integer(i), Value::Float(f)) => compare_int_float(*i, *f).unwrap_or(Ordering::Less).then(Ordering::Less),
+            (Value::Float(f), Value::Integer(i)) => {
+                compare_int_float(*i, *f).unwrap_or(Ordering::Less).then(Ordering::Less).reverse()
+            }
+            (Value::Json(x), Value::Json(y)) => x.to_string().cmp(&y.to_string()),
+            (Value::Param(x), Value::Param(y)) => x.cmp(y),
+            _ => a.partial_cmp(b).unwrap_or(Ordering::Equal),
+        })
+    }
+}
+
+impl PartialOrd for OrderedKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for OrderedKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OrderedKey {}
+
+/// Whether `operator` bounds a range of values.
+pub(crate) fn is_bound(operator: &Operator) -> bool {
+    matches!(operator, Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte)
+}
+
+impl ColumnIndex {
+    pub(crate) fn new(kind: IndexKind) -> Self {
+        match kind {
+            IndexKind::Hash => ColumnIndex::Hash(HashMap::new()),
+            IndexKind::Ordered => ColumnIndex::Ordered(OrdMap::new()),
+        }
+    }
+
+    pub(crate) fn kind(&self) -> IndexKind {
+        match self {
+            ColumnIndex::Hash(_) => IndexKind::Hash,
+            ColumnIndex::Ordered(_) => IndexKind::Ordered,
+        }
+    }
+
+    pub(crate) fn insert(&mut self, value: Value, id: RowId) {
+        match self {
+            ColumnIndex::Hash(index) => index.entry(value).or_default().push_back(id),
+            ColumnIndex::Ordered(index) => index.entry(OrderedKey(value)).or_default().push_back(id),
+        }
+    }
+
+    /// Takes the `removed` ids out of the entry for `value`.
+    pub(crate) fn remove(&mut self, value: &Value, removed: &HashSet<RowId>) {
+        match self {
+            ColumnIndex::Hash(index) => {
+                if let Some(ids) = index.get_mut(value) {
+                    ids.retain(|id| !removed.contains(id));
+                    if ids.is_empty() {
+                        index.remove(value);
+                    }
+                }
+            }
+            ColumnIndex::Ordered(index) => {
+                let key = OrderedKey(value.clone());
+                if let Some(ids) = index.get_mut(&key) {
+                    ids.retain(|id| !removed.contains(id));
+                    if ids.is_empty() {
+                        index.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn get(&self, value: &Value) -> Option<&IdList> {
+        match self {
+            ColumnIndex::Hash(index) => index.get(value),
+            ColumnIndex::Ordered(index) => index.get(&OrderedKey(value.clone())),
+        }
+    }
+
+    /// How many different values the index holds.
+    pub(crate) fn distinct_values(&self) -> usize {
+        match self {
+            ColumnIndex::Hash(index) => index.len(),
+            ColumnIndex::Ordered(index) => index.len(),
+        }
+    }
+
+    /// Calls `f` with every value in the index and its ids.
+    pub(crate) fn for_each(&self, mut f: impl FnMut(&Value, &IdList)) {
+        match self {
+            ColumnIndex::Hash(index) => index.iter().for_each(|(value, ids)| f(value, ids)),
+            ColumnIndex::Ordered(index) => index.iter().for_each(|(key, ids)| f(&key.0, ids)),
+        }
+    }
+
+    /// Calls `f` with the ids of the values matching every one of `bounds`,
+    /// range conditions on the indexed column.
+    pub(crate) fn range(&self, bounds: &[&Condition], mut f: impl FnMut(&IdList)) {
+        let matches = |value: &Value| bounds.iter().all(|c| c.operator.apply(value, &c.value));
+        let ColumnIndex::Ordered(index) = self else {
+            return self.for_each(|value, ids| {
+                if matches(value) {
+                    f(ids)
+                }
+            });
+        };
+        // Keys equal to a bound are read whatever the operator and left to
+        // `matches`, which also sees to the other numeric type.
+        let lowest = |value: &Value| value.index_keys().into_iter().map(OrderedKey).min();
+        let highest = |value: &Value| value.index_keys().into_iter().map(OrderedKey).max();
+        let lower = bounds.iter().find(|c| matches!(c.operator, Operator::Gt | Operator::Gte));
+        let upper = bounds.iter().find(|c| matches!(c.operator, Operator::Lt | Operator::Lte));
+        let rank = match (lower, upper) {
+            (Some(l), Some(u)) if type_rank(&l.value) != type_rank(&u.value) => return,
+            (Some(c), _) | (_, Some(c)) => type_rank(&c.value),
+            (None, None) => return,
+        };
+        let start = lower.and_then(|c| lowest(&c.value)).map_or(Bound::Unbounded, Bound::Included);
+        let end = upper.and_then(|c| highest(&c.value)).map_or(Bound::Unbounded, Bound::Included);
+        if let (Bound::Included(start), Bound::Included(end)) = (&start, &end) {
+            if start > end {
+                return;
+            }
+        }
+        let mut visit = |(key, ids): (&OrderedKey, &IdList)| {
+            if matches(&key.0) {
+                f(ids)
+            }
+        };
+        // An open end stops at the last key of the bound's type.
+        match (&start, &end) {
+            (Bound::Unbounded, _) => index
+                .range((start, end))
+                .rev()
+                .take_while(|(key, _)| type_rank(&key.0) == rank)
+                .for_each(&mut visit),
+            _ => index
+                .range((start, end))
+                .take_while(|(key, _)| type_rank(&key.0) == rank)
+                .for_each(&mut visit),
+        }
+    }
+}
+
+impl Database {
+    /// Indexes `column_name` with an index of `kind`, replacing any index
+    /// already on the column. `create_index` makes hash indexes.
+    pub async fn create_index_with_kind(&self, table_name: &str, column_name: &str, kind: IndexKind) -> Result<(), String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+
+        if !table.columns.iter().any(|c| c.name == column_name) {
+            return Err(format!("Column {} not found", column_name));
+        }
+
+        table.build_index(column_name, kind);
+        self.publish(&tables, &[table_name]);
+        Ok(())
+    }
+
+    // When `queries`, under an `And`, start with a bound on a column with an
+    // ordered index, the rows within every bound they put on that column,
+    // and the column.
+    pub(crate) fn range_scan<'a>(&self, table: &Table, queries: &'a [Query]) -> Option<(Vec<usize>, &'a str)> {
+        let Query::Condition(first) = queries.first()? else {
+            return None;
+        };
+        let index = table
+            .indexes
+            .get(&first.column)
+            .filter(|index| index.kind() == IndexKind::Ordered && is_bound(&first.operator) && !table.indexes_stale)?;
+        let bounds: Vec<&Condition> = queries
+            .iter()
+            .filter_map(|q| match q {
+                Query::Condition(c) if c.column == first.column && is_bound(&c.operator) => Some(c),
+                _ => None,
+            })
+            .collect();
+        table.index_last_used.insert(first.column.clone(), self.memory.tick());
+        let mut rows = Vec::new();
+        index.range(&bounds, |ids| rows.extend(table.positions(ids)));
+        rows.sort_unstable();
+        Some((rows, first.column.as_str()))
+    }
+}
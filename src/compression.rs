@@ -0,0 +1,135 @@
+//! Compression of large String and Json values.
+//!
+//! A compressed column deflates each value of at least a set size on its
+//! own, and rows store the compressed bytes behind a one-byte header giving
+//! the value's type. Values that don't shrink are stored as they are. Rows
+//! still hand out `&Value`: a value is inflated the first time it is read,
+//! and the inflated copy is kept with the compressed one from then on.
+//! Copies of rows, like those `select` returns, start out compressed again,
+//! so it is mostly conditions on the column itself that leave stored values
+//! inflated. Enabling compression again compresses them afresh. It suits
+//! columns that are written far more often than they are filtered on, such
+//! as log messages.
+//!
+//! Like dictionary encoding, compression is a choice of in-memory layout: it
+//! isn't written to the WAL or to snapshots and has to be enabled again
+//! after `load`. A column is either dictionary-encoded or compressed, never
+//! both; enabling one turns the other off.
+
+use crate::{Database, DataType, Table, Value};
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::io::{Read, Write};
+use std::mem::size_of;
+use std::sync::{Arc, OnceLock};
+
+const STRING: u8 = 0;
+const JSON: u8 = 1;
+
+pub(crate) struct Compressed {
+    // The type header, then the deflated value.
+    bytes: Box<[u8]>,
+    inflated: OnceLock<Value>,
+}
+
+impl Compressed {
+    /// Compresses `value` if it is a String or Json value of at least
+    /// `min_bytes` that shrinks, and otherwise hands it back.
+    pub(crate) fn compress(value: Value, min_bytes: usize) -> Result<Compressed, Value> {
+        let (kind, raw) = match &value {
+            Value::String(s) if s.len() >= min_bytes => (STRING, s.as_bytes().to_vec()),
+            Value::Json(json) => match serde_json::to_vec(json) {
+                Ok(raw) if raw.len() >= min_bytes => (JSON, raw),
+                _ => return Err(value),
+            },
+            _ => return Err(value),
+        };
+        let mut encoder = DeflateEncoder::new(vec![kind], flate2::Compression::default());
+        if encoder.write_all(&raw).is_err() {
+            return Err(value);
+        }
+        match encoder.finish() {
+            Ok(bytes) if bytes.len() < raw.len() => Ok(Compressed {
+                bytes: bytes.into_boxed_slice(),
+                inflated: OnceLock::new(),
+            }),
+            _ => Err(value),
+        }
+    }
+
+    pub(crate) fn value(&self) -> &Value {
+        self.inflated.get_or_init(|| inflate(&self.bytes))
+    }
+
+    pub(crate) fn into_value(self) -> Value {
+        match self.inflated.into_inner() {
+            Some(value) => value,
+            None => inflate(&self.bytes),
+        }
+    }
+
+    pub(crate) fn estimated_size(&self) -> usize {
+        size_of::<Compressed>() + self.bytes.len() + self.inflated.get().map_or(0, Value::estimated_size)
+    }
+}
+
+// Only bytes written by `compress` get here, so they always inflate.
+fn inflate(bytes: &[u8]) -> Value {
+    let mut raw = Vec::new();
+    if DeflateDecoder::new(&bytes[1..]).read_to_end(&mut raw).is_err() {
+        return Value::Null;
+    }
+    match bytes[0] {
+        STRING => String::from_utf8(raw).map_or(Value::Null, Value::String),
+        _ => serde_json::from_slice(&raw).map_or(Value::Null, Value::Json),
+    }
+}
+
+// Copies are left to inflate on their own, so they stay small.
+impl Clone for Compressed {
+    fn clone(&self) -> Self {
+        Compressed {
+            bytes: self.bytes.clone(),
+            inflated: OnceLock::new(),
+        }
+    }
+}
+
+impl Table {
+    // Re-lays every row out against a schema compressing `column`. Row
+    // positions are unchanged, so indexes stay valid.
+    fn compress_column(&mut self, column: &str, min_bytes: usize) {
+        self.schema = Arc::new(self.schema.with_compression(column, min_bytes));
+        for row in &mut self.data {
+            *row = Arc::new(row.conform(&self.schema));
+        }
+        self.data_bytes = self.data.iter().map(|r| r.estimated_size()).sum();
+    }
+}
+
+impl Database {
+    /// Stores values of `column` in `table_name` of at least `min_bytes`
+    /// compressed. The column must be a String or Json column. Calling it
+    /// again compresses every value afresh.
+    pub async fn enable_compression(&self, table_name: &str, column_name: &str, min_bytes: usize) -> Result<(), String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let column = table
+            .columns
+            .iter()
+            .find(|c| c.name == column_name)
+            .ok_or_else(|| format!("Column {} not found", column_name))?;
+        if !matches!(column.data_type, DataType::String | DataType::Json) {
+            return Err(format!(
+                "Column {} must be a String or Json column to be compressed",
+                column_name
+            ));
+        }
+        table.compress_column(column_name, min_bytes);
+        self.publish(&tables, &[table_name]);
+        Ok(())
+    }
+}
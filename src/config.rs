@@ -0,0 +1,214 @@
+use crate::StorageBackend;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Options fixed when a `Database` is opened. Build one with struct update
+/// syntax, e.g. `DatabaseConfig { memory_limit: Some(64 << 20), ..Default::default() }`.
+#[derive(Clone, Debug, Default)]
+pub struct DatabaseConfig {
+    /// Budget in bytes for table data and indexes, as estimated by
+    /// `Database::memory_stats`. `None` means unlimited.
+    pub memory_limit: Option<usize>,
+    /// How many committed writes `Database::undo` can reverse. 0, the default,
+    /// disables undo.
+    pub undo_depth: usize,
+    /// Which conversions writes may apply to values that don't match their
+    /// column's type.
+    pub coercion: Coercion,
+    /// What inserts and updates do with columns the table doesn't declare.
+    pub unknown_columns: UnknownColumns,
+    /// Where the WAL and snapshots are kept. `None` means files.
+    pub storage: Option<Arc<dyn StorageBackend>>,
+    /// How new snapshots and WALs are encoded. Existing files are read with
+    /// whichever codec wrote them.
+    pub codec: Codec,
+    /// How new snapshots are encrypted. Existing ones are read with
+    /// whichever cipher wrote them.
+    pub cipher: Cipher,
+    /// How new snapshots are compressed. Existing ones are read however
+    /// they were written.
+    pub snapshot_compression: SnapshotCompression,
+    /// Limits on individual tables, by table name. Inserts that would break
+    /// them fail with `WriteError::Limit`.
+    pub table_limits: HashMap<String, TableLimits>,
+    /// Bounds on concurrent and queued writes. `None` admits every write.
+    pub admission: Option<AdmissionConfig>,
+    /// How long a write may wait for its turn before failing with
+    /// `WriteError::Timeout`. `None` waits indefinitely.
+    pub operation_timeout: Option<Duration>,
+    /// A directory `save` copies the WAL to, as a numbered segment, before
+    /// emptying it, for `Database::rebuild_from_wal`. `None` keeps no
+    /// archive.
+    pub wal_archive: Option<String>,
+    /// Caps on the rows a single select returns, against a query that
+    /// matches far more than meant to. Selects beyond them fail with
+    /// `ResultLimitError`, or are cut short.
+    pub result_limits: ResultLimits,
+    /// Budget in bytes for the hash table a join builds. Joins with both
+    /// sides over it sort them in spill files instead. `None` means
+    /// unlimited.
+    pub join_memory_limit: Option<usize>,
+    /// The directory joins over `join_memory_limit` spill to. Spill files
+    /// are encrypted, but reveal how big the joins are, so joins only spill
+    /// to a directory chosen here. `None` hashes them in memory regardless.
+    pub spill_dir: Option<String>,
+    /// When a table is analyzed again without being asked, so the planner's
+    /// statistics don't go stale. `None` leaves it to `Database::analyze`.
+    pub auto_analyze: Option<AutoAnalyze>,
+    /// Columns each role may not read or write, by role name, for sessions
+    /// acting as that role. Sessions without a role, or with one not listed,
+    /// may read and write every column.
+    pub roles: HashMap<String, RolePermissions>,
+}
+
+/// The columns one role may not read or write, by table.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RolePermissions {
+    /// Columns left out of the rows the role reads. Queries that filter,
+    /// sort, group or aggregate on them fail, as do writes to them.
+    pub hidden: HashMap<String, HashSet<String>>,
+    /// Columns the role can read but not write.
+    pub read_only: HashMap<String, HashSet<String>>,
+}
+
+impl RolePermissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn hide(mut self, table: &str, columns: &[&str]) -> Self {
+        self.hidden.entry(table.to_string()).or_default().extend(columns.iter().map(|c| c.to_string()));
+        self
+    }
+
+    pub fn read_only(mut self, table: &str, columns: &[&str]) -> Self {
+        self.read_only.entry(table.to_string()).or_default().extend(columns.iter().map(|c| c.to_string()));
+        self
+    }
+}
+
+/// How many rows a table must have changed since its statistics were last
+/// collected for a write to collect them again: `min_changes` plus
+/// `fraction` of its rows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AutoAnalyze {
+    pub min_changes: usize,
+    pub fraction: f64,
+}
+
+impl Default for AutoAnalyze {
+    fn default() -> Self {
+        AutoAnalyze {
+            min_changes: 50,
+            fraction: 0.1,
+        }
+    }
+}
+
+/// Caps on one table's size. `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TableLimits {
+    pub max_rows: Option<usize>,
+    /// Estimated bytes of row data, as counted by `Database::memory_stats`.
+    /// Indexes don't count.
+    pub max_bytes: Option<usize>,
+    /// Estimated bytes of a single row.
+    pub max_row_bytes: Option<usize>,
+}
+
+/// Caps on the rows one select returns. `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResultLimits {
+    pub max_rows: Option<usize>,
+    /// Estimated bytes of the rows returned, as counted by
+    /// `Row::estimated_size`.
+    pub max_bytes: Option<usize>,
+    /// Return the first rows that fit instead of failing.
+    pub truncate: bool,
+}
+
+/// How many writes may run and wait at once, and how often each table may
+/// be written. Writes turned away fail with `WriteError::Busy` and can be
+/// retried.
+#[derive(Clone, Debug, Default)]
+pub struct AdmissionConfig {
+    /// Writes running at once, including those waiting for the table lock.
+    /// Further writes queue for a turn.
+    pub max_concurrent_writes: usize,
+    /// Writes allowed to queue; beyond that writes fail with
+    /// `Busy::QueueFull`.
+    pub max_queued_writes: usize,
+    /// Writes per second allowed on each named table, with bursts of up to
+    /// a second's worth. Beyond that writes fail with `Busy::RateLimited`.
+    pub table_rates: HashMap<String, u32>,
+}
+
+/// How values are converted to a column's type on insert and update. Values
+/// that can't be converted are left alone and rejected as before.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Coercion {
+    /// No conversions.
+    #[default]
+    Strict,
+    /// Conversions that keep the exact value: integers to floats (up to
+    /// 2^53), whole floats to integers, and RFC 3339 or UUID strings to
+    /// datetimes and UUIDs.
+    Lossless,
+    /// Also rounds integers to floats, truncates floats to integers, parses
+    /// numbers and booleans from strings, and formats scalars as strings.
+    Lossy,
+}
+
+/// What happens to values written to columns that aren't in the table's
+/// schema.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnknownColumns {
+    /// They are stored along with the rest of the row.
+    #[default]
+    Keep,
+    /// The write fails.
+    Reject,
+    /// They are dropped and the rest of the row is written.
+    Strip,
+}
+
+/// The serialization format of snapshots and WAL entries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    #[default]
+    Bincode,
+    /// Varint-encoded, so usually smaller than bincode.
+    Postcard,
+    /// Self-describing, so readable by other tools.
+    MessagePack,
+}
+
+/// How snapshots are compressed before they are encrypted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SnapshotCompression {
+    #[default]
+    Gzip,
+    /// zstd at `level`, from 1 to 22.
+    Zstd { level: i32 },
+    /// zstd at `level` with a dictionary of up to `dictionary_bytes`, trained
+    /// from up to `samples` of the snapshot's rows and stored in its header.
+    /// Suits many small, similar rows; the dictionary counts against the
+    /// snapshot's size, so keep it small next to the data.
+    ZstdDictionary {
+        level: i32,
+        samples: usize,
+        dictionary_bytes: usize,
+    },
+}
+
+/// The authenticated cipher snapshots are encrypted with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cipher {
+    /// Fastest where the CPU has AES instructions.
+    #[default]
+    Aes256Gcm,
+    /// Fast in software, for CPUs without AES instructions.
+    ChaCha20Poly1305,
+}
@@ -0,0 +1,160 @@
+//! What an insert does when it would break a unique constraint.
+//!
+//! `OnConflict::Error` is the plain insert. `Ignore` leaves the table as it
+//! is when the row clashes with a stored row on any unique column, and
+//! `Replace` deletes every stored row it clashes with before inserting it,
+//! so the row takes over each value it shares. Only unique constraints are
+//! resolved this way; the row still fails on any other constraint, and a
+//! replace that fails puts the deleted rows back.
+//!
+//! Inserts that resolve conflicts are logged with their mode, so replaying
+//! the WAL resolves them the same way.
+
+use crate::watch::{Changes, WatchEvent};
+use crate::{coercion, schema, Constraint, Database, OperationKind, Table, Value, WalEntry, WriteError};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// What an insert does with a row that breaks a unique constraint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnConflict {
+    /// Fail, as `insert` does.
+    #[default]
+    Error,
+    /// Leave the row out and succeed.
+    Ignore,
+    /// Delete the rows it clashes with, then insert it.
+    Replace,
+}
+
+/// What `insert_on_conflict` did with the row.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertOutcome {
+    Inserted,
+    /// Left out under `OnConflict::Ignore`.
+    Ignored,
+    /// Inserted under `OnConflict::Replace`, after deleting this many rows.
+    Replaced(usize),
+}
+
+impl InsertOutcome {
+    /// Rows inserted: 0 or 1.
+    pub fn inserted(&self) -> usize {
+        match self {
+            InsertOutcome::Ignored => 0,
+            _ => 1,
+        }
+    }
+}
+
+// Positions of the rows `row` clashes with on a unique column, in order.
+fn conflicts(table: &Table, row: &HashMap<String, Value>) -> Vec<usize> {
+    let mut positions = BTreeSet::new();
+    for column in table.columns.iter().filter(|c| c.constraints.contains(&Constraint::Unique)) {
+        if let Some(value) = row.get(&column.name) {
+            positions.extend(
+                table
+                    .data
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, r)| r.get(&column.name) == Some(value))
+                    .map(|(i, _)| i),
+            );
+        }
+    }
+    positions.into_iter().collect()
+}
+
+impl Database {
+    /// Inserts `row` into `table_name`, resolving a clash on a unique
+    /// column as `on_conflict` says.
+    pub async fn insert_on_conflict(
+        &self,
+        table_name: &str,
+        row: HashMap<String, Value>,
+        on_conflict: OnConflict,
+    ) -> Result<InsertOutcome, WriteError> {
+        self.check_row_writable(table_name, &row)?;
+        self.measured(table_name, OperationKind::Insert, async {
+            let _admitted = self.admit(&[table_name]).await?;
+            self.apply_insert_on_conflict(table_name, row, on_conflict).await
+        })
+        .await
+    }
+
+    // `insert_on_conflict` without admission, for WAL replay.
+    pub(crate) async fn apply_insert_on_conflict(
+        &self,
+        table_name: &str,
+        row: HashMap<String, Value>,
+        on_conflict: OnConflict,
+    ) -> Result<InsertOutcome, WriteError> {
+        if on_conflict == OnConflict::Error {
+            self.apply_insert(table_name, row).await?;
+            return Ok(InsertOutcome::Inserted);
+        }
+        self.check_open()?;
+        let wal_entry = WalEntry::InsertOnConflict {
+            table_name: table_name.to_string(),
+            row: row.clone(),
+            on_conflict,
+        };
+        let mut tables = self.log_and_lock(table_name, &wal_entry).await?;
+        let mut changes = Changes::new();
+        let outcome = self.insert_on_conflict_internal(&mut tables, table_name, row, on_conflict, &mut changes)?;
+        self.finish_write(&mut tables, &[table_name], changes);
+        Ok(outcome)
+    }
+
+    pub(crate) fn insert_on_conflict_internal(
+        &self,
+        tables: &mut HashMap<String, Table>,
+        table_name: &str,
+        row: HashMap<String, Value>,
+        on_conflict: OnConflict,
+        changes: &mut Changes,
+    ) -> Result<InsertOutcome, WriteError> {
+        if on_conflict == OnConflict::Error {
+            self.insert_internal(tables, table_name, row, changes)?;
+            return Ok(InsertOutcome::Inserted);
+        }
+        self.check_writable(table_name)?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        // Compared as the insert will store it.
+        let mut checked = row.clone();
+        schema::check_row(&mut checked, &table.columns, self.config.unknown_columns)?;
+        coercion::coerce_row(&mut checked, &table.columns, self.config.coercion);
+        let positions = conflicts(table, &checked);
+        if positions.is_empty() {
+            self.insert_internal(tables, table_name, row, changes)?;
+            return Ok(InsertOutcome::Inserted);
+        }
+        if on_conflict == OnConflict::Ignore {
+            return Ok(InsertOutcome::Ignored);
+        }
+
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let removed = table.remove_rows(&positions);
+        let recorded = changes.len();
+        if self.tracking_changes() {
+            for (id, row) in &removed {
+                changes.push((table_name.to_string(), *id, WatchEvent::Delete((**row).clone())));
+            }
+        }
+        if let Err(e) = self.insert_internal(tables, table_name, row, changes) {
+            changes.truncate(recorded);
+            if let Some(table) = tables.get_mut(table_name) {
+                for (id, row) in removed {
+                    table.restore_row(id, row);
+                }
+                table.maintain(self.maintenance_deferred());
+            }
+            return Err(e);
+        }
+        Ok(InsertOutcome::Replaced(removed.len()))
+    }
+}
@@ -0,0 +1,231 @@
+//! Dependencies between tables, and dropping and renaming tables.
+//!
+//! A table's dependents are the foreign keys that refer to it and the
+//! views, materialized views and rollups over it, whether as their base
+//! table or as the target of a join. They are worked out from those
+//! definitions whenever they're needed, so the graph can't drift from
+//! them.
+//!
+//! `drop_table` and `rename_table` take `OnDependents`. `Restrict` fails
+//! if the table has dependents, naming every one. `Cascade` carries the
+//! change through: dropping a table removes the foreign keys that refer to
+//! it and drops the views, materialized views and rollups over it, along
+//! with whatever depends on those in turn, and renaming points them all at
+//! the new name. A table's foreign keys to itself never block it.
+//!
+//! Both are logged to the WAL with their policy, so replay makes the same
+//! changes. Watches, row history and per-table limits are kept by name and
+//! don't follow a rename.
+
+use crate::{Constraint, Database, Query, Table, WalEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// What `drop_table` and `rename_table` do about a table's dependents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OnDependents {
+    /// Fail, listing them.
+    #[default]
+    Restrict,
+    /// Drop or update them too.
+    Cascade,
+}
+
+/// Something that depends on a table.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dependent {
+    /// A foreign key on `table.column` that refers to it.
+    ForeignKey { table: String, column: String },
+    View(String),
+    MaterializedView(String),
+    Rollup(String),
+}
+
+impl fmt::Display for Dependent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dependent::ForeignKey { table, column } => write!(f, "foreign key {}.{}", table, column),
+            Dependent::View(name) => write!(f, "view {}", name),
+            Dependent::MaterializedView(name) => write!(f, "materialized view {}", name),
+            Dependent::Rollup(name) => write!(f, "rollup {}", name),
+        }
+    }
+}
+
+pub(crate) fn rename_joined_table(query: &mut Query, old: &str, new: &str) {
+    if let Query::Join(join) = query {
+        if join.target_table == old {
+            join.target_table = new.to_string();
+        }
+    }
+}
+
+fn references(constraint: &Constraint, table_name: &str) -> bool {
+    matches!(constraint, Constraint::ForeignKey { table, .. } if table == table_name)
+}
+
+// Points the foreign keys of `table` that refer to `old` at `new`, or
+// removes them if there's no `new`.
+fn retarget_foreign_keys(table: &mut Table, old: &str, new: Option<&str>) {
+    for column in &mut table.columns {
+        match new {
+            Some(new) => {
+                for constraint in &mut column.constraints {
+                    if let Constraint::ForeignKey { table, .. } = constraint {
+                        if table == old {
+                            *table = new.to_string();
+                        }
+                    }
+                }
+            }
+            None => column.constraints.retain(|c| !references(c, old)),
+        }
+    }
+}
+
+impl Database {
+    /// What depends directly on `table_name`, in order.
+    pub async fn dependents(&self, table_name: &str) -> Vec<Dependent> {
+        self.dependents_of(self.read_tables_untimed().await.values(), table_name)
+    }
+
+    fn dependents_of<'a>(&self, tables: impl Iterator<Item = &'a Table>, table_name: &str) -> Vec<Dependent> {
+        let mut dependents: Vec<Dependent> = tables
+            .filter(|table| table.name != table_name)
+            .flat_map(|table| {
+                table
+                    .columns
+                    .iter()
+                    .filter(|c| c.constraints.iter().any(|c| references(c, table_name)))
+                    .map(|c| Dependent::ForeignKey {
+                        table: table.name.clone(),
+                        column: c.name.clone(),
+                    })
+            })
+            .collect();
+        dependents.sort();
+        dependents.extend(self.named_views.depending_on(table_name).into_iter().map(Dependent::View));
+        dependents.extend(self.materialized.depending_on(table_name).into_iter().map(Dependent::MaterializedView));
+        dependents.extend(self.rollups.depending_on(table_name).into_iter().map(Dependent::Rollup));
+        dependents
+    }
+
+    // Fails for anything but a plain table, and under `Restrict` for a
+    // table with dependents.
+    fn check_droppable(
+        &self,
+        tables: &HashMap<String, Table>,
+        table_name: &str,
+        on_dependents: OnDependents,
+    ) -> Result<Vec<Dependent>, String> {
+        if self.materialized.contains(table_name) {
+            return Err(format!("{} is a materialized view; use drop_materialized_view", table_name));
+        }
+        if self.rollups.contains(table_name) {
+            return Err(format!("{} is a rollup; use drop_rollup", table_name));
+        }
+        if !tables.contains_key(table_name) {
+            return Err(format!("Table {} not found", table_name));
+        }
+        let dependents = self.dependents_of(tables.values(), table_name);
+        if on_dependents == OnDependents::Restrict && !dependents.is_empty() {
+            let names: Vec<String> = dependents.iter().map(Dependent::to_string).collect();
+            return Err(format!("Table {} is used by {}", table_name, names.join(", ")));
+        }
+        Ok(dependents)
+    }
+
+    /// Drops `table_name` and its rows. Returns what was dropped or changed
+    /// with it under `Cascade`, including dependents of dependents.
+    pub async fn drop_table(&self, table_name: &str, on_dependents: OnDependents) -> Result<Vec<Dependent>, String> {
+        self.check_open()?;
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        self.check_droppable(&tables, table_name, on_dependents)?;
+        if !tables[table_name].ephemeral {
+            let wal_entry = WalEntry::DropTable {
+                name: table_name.to_string(),
+                on_dependents,
+            };
+            wal_writer.log(&wal_entry).map_err(|e| e.to_string())?;
+        }
+        drop(wal_writer);
+
+        let mut dropped = Vec::new();
+        let mut altered = Vec::new();
+        let mut pending = vec![table_name.to_string()];
+        while let Some(name) = pending.pop() {
+            for dependent in self.dependents_of(tables.values(), &name) {
+                match &dependent {
+                    Dependent::ForeignKey { table, .. } => {
+                        if let Some(table) = tables.get_mut(table) {
+                            retarget_foreign_keys(table, &name, None);
+                        }
+                        altered.push(table.clone());
+                    }
+                    Dependent::View(view) => self.named_views.remove(view),
+                    Dependent::MaterializedView(view) => {
+                        self.materialized.remove(view);
+                        pending.push(view.clone());
+                    }
+                    Dependent::Rollup(rollup) => {
+                        self.rollups.remove(rollup);
+                        pending.push(rollup.clone());
+                    }
+                }
+                dropped.push(dependent);
+            }
+            tables.remove(&name);
+        }
+        altered.retain(|name| tables.contains_key(name));
+        let altered: Vec<&str> = altered.iter().map(String::as_str).collect();
+        self.publish(&tables, &altered);
+        Ok(dropped)
+    }
+
+    /// Renames `table_name` to `new_name`. Returns the dependents that were
+    /// pointed at the new name under `Cascade`.
+    pub async fn rename_table(
+        &self,
+        table_name: &str,
+        new_name: &str,
+        on_dependents: OnDependents,
+    ) -> Result<Vec<Dependent>, String> {
+        self.check_open()?;
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let dependents = self.check_droppable(&tables, table_name, on_dependents)?;
+        if tables.contains_key(new_name) || self.named_views.contains(new_name) {
+            return Err(format!("Table {} already exists", new_name));
+        }
+        if !tables[table_name].ephemeral {
+            let wal_entry = WalEntry::RenameTable {
+                name: table_name.to_string(),
+                new_name: new_name.to_string(),
+                on_dependents,
+            };
+            wal_writer.log(&wal_entry).map_err(|e| e.to_string())?;
+        }
+        drop(wal_writer);
+
+        let mut table = tables.remove(table_name).ok_or_else(|| format!("Table {} not found", table_name))?;
+        table.name = new_name.to_string();
+        retarget_foreign_keys(&mut table, table_name, Some(new_name));
+        tables.insert(new_name.to_string(), table);
+        let mut changed = vec![new_name];
+        for dependent in &dependents {
+            if let Dependent::ForeignKey { table, .. } = dependent {
+                if let Some(child) = tables.get_mut(table) {
+                    retarget_foreign_keys(child, table_name, Some(new_name));
+                }
+                changed.push(table);
+            }
+        }
+        self.named_views.rename_table(table_name, new_name);
+        self.materialized.rename_table(table_name, new_name);
+        self.rollups.rename_table(table_name, new_name);
+        self.publish(&tables, &changed);
+        Ok(dependents)
+    }
+}
@@ -0,0 +1,119 @@
+//! Dictionary encoding for low-cardinality string columns.
+//!
+//! An encoded column keeps each distinct string once in a dictionary attached
+//! to the table's `RowSchema`, and rows store a `u32` code instead of their own
+//! copy. Rows still hand out `&Value`, serialize exactly as before, and compare
+//! by code for equality scans.
+//!
+//! Like indexes, encoding is a choice of in-memory layout: it isn't written to
+//! the WAL or to snapshots and has to be enabled again after `load`.
+//! Dictionaries only grow; strings that are no longer used stay until the
+//! encoding is enabled again, which rebuilds it from the current rows.
+
+use crate::{Database, DataType, Value};
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::fmt;
+use std::mem::size_of;
+
+pub(crate) struct Dictionary {
+    values: boxcar::Vec<Value>,
+    codes: DashMap<String, u32>,
+}
+
+impl Dictionary {
+    pub(crate) fn new() -> Self {
+        Dictionary {
+            values: boxcar::Vec::new(),
+            codes: DashMap::new(),
+        }
+    }
+
+    pub(crate) fn encode(&self, value: String) -> u32 {
+        if let Some(code) = self.codes.get(&value) {
+            return *code;
+        }
+        match self.codes.entry(value) {
+            Entry::Occupied(entry) => *entry.get(),
+            Entry::Vacant(entry) => {
+                let code = self.values.push(Value::String(entry.key().clone())) as u32;
+                *entry.insert(code)
+            }
+        }
+    }
+
+    pub(crate) fn code(&self, value: &str) -> Option<u32> {
+        self.codes.get(value).map(|code| *code)
+    }
+
+    pub(crate) fn value(&self, code: u32) -> &Value {
+        &self.values[code as usize]
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.values.count()
+    }
+
+    // The string is held twice: once as the value and once as the lookup key.
+    pub(crate) fn estimated_size(&self) -> usize {
+        self.values
+            .iter()
+            .map(|(_, value)| value.estimated_size() * 2 + size_of::<u32>())
+            .sum()
+    }
+}
+
+// Dictionaries are shared by identity: two are only equal if they're the same
+// one, which is what code comparisons need.
+impl PartialEq for Dictionary {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Dictionary {}
+
+impl fmt::Debug for Dictionary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Dictionary({} values)", self.len())
+    }
+}
+
+impl Database {
+    /// Stores `column` of `table_name` dictionary-encoded. The column must be a
+    /// String column. Calling it again rebuilds the dictionary, dropping strings
+    /// no row uses any more.
+    pub async fn enable_dictionary_encoding(
+        &self,
+        table_name: &str,
+        column_name: &str,
+    ) -> Result<(), String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let column = table
+            .columns
+            .iter()
+            .find(|c| c.name == column_name)
+            .ok_or_else(|| format!("Column {} not found", column_name))?;
+        if column.data_type != DataType::String {
+            return Err(format!(
+                "Column {} must be a String column to be dictionary-encoded",
+                column_name
+            ));
+        }
+        table.encode_column(column_name);
+        self.publish(&tables, &[table_name]);
+        Ok(())
+    }
+
+    /// Number of distinct strings in the column's dictionary, or `None` if the
+    /// column isn't encoded.
+    pub async fn dictionary_size(&self, table_name: &str, column_name: &str) -> Option<usize> {
+        let tables = self.read_tables_untimed().await;
+        let table = tables.get(table_name)?;
+        table.schema.dictionary(column_name).map(Dictionary::len)
+    }
+}
@@ -0,0 +1,178 @@
+//! Comparing snapshots.
+//!
+//! `Database::diff` compares two snapshots, and `diff_current` a snapshot
+//! with the database as it is now, table by table. Rows are matched by
+//! their ids, which snapshots keep, so an updated row is reported as changed
+//! rather than removed and added again. Tables with the same columns and the
+//! same Merkle root hold the same rows under the same ids, and are passed
+//! over without comparing their rows. Ephemeral tables, materialized views
+//! and rollups among them, aren't saved in snapshots and are left out.
+
+use crate::recovery::{self, RecoveryReport};
+use crate::{unpack_snapshot, Blake3Hasher, Database, Row, RowId, Table};
+use rs_merkle::MerkleTree;
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::sync::Arc;
+
+/// How a table differs between two snapshots.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableDiff {
+    pub added: Vec<(RowId, Row)>,
+    pub removed: Vec<(RowId, Row)>,
+    /// `(id, before, after)` for each row whose values changed.
+    pub changed: Vec<(RowId, Row, Row)>,
+    pub columns_changed: bool,
+}
+
+impl TableDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() && !self.columns_changed
+    }
+}
+
+/// The differences between two snapshots, from the first to the second.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SnapshotDiff {
+    pub added_tables: Vec<String>,
+    pub removed_tables: Vec<String>,
+    /// Every table that differs, by name. The rows of added and removed
+    /// tables are all reported as added or removed.
+    pub tables: BTreeMap<String, TableDiff>,
+    /// Tables in both snapshots that were found identical.
+    pub unchanged_tables: usize,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+}
+
+impl Table {
+    // The root of the table's Merkle tree, built over leaves rehashed if
+    // writes left them stale.
+    pub(crate) fn merkle_root(&self) -> Option<[u8; 32]> {
+        let leaves = match self.merkle_stale {
+            true => self.fresh_merkle_leaves(),
+            false => self.merkle_leaves.iter().copied().collect(),
+        };
+        MerkleTree::<Blake3Hasher>::from_leaves(&leaves).root()
+    }
+}
+
+fn rows(table: Option<&Table>) -> impl Iterator<Item = (RowId, &Arc<Row>)> {
+    table
+        .into_iter()
+        .flat_map(|table| table.row_ids.iter().copied().zip(table.data.iter()))
+}
+
+fn diff_table(before: Option<&Table>, after: Option<&Table>) -> TableDiff {
+    let mut diff = TableDiff::default();
+    if let (Some(before), Some(after)) = (before, after) {
+        diff.columns_changed = before.columns != after.columns;
+    }
+
+    // Both sides are in id order.
+    let (mut b, mut a) = (rows(before).peekable(), rows(after).peekable());
+    loop {
+        match (b.peek(), a.peek()) {
+            (Some((before_id, _)), Some((after_id, _))) if before_id == after_id => {
+                let (id, old) = b.next().unwrap();
+                let (_, new) = a.next().unwrap();
+                if old != new {
+                    diff.changed.push((id, (**old).clone(), (**new).clone()));
+                }
+            }
+            (Some((before_id, _)), Some((after_id, _))) if before_id > after_id => {
+                let (id, row) = a.next().unwrap();
+                diff.added.push((id, (**row).clone()));
+            }
+            (Some(_), _) => {
+                let (id, row) = b.next().unwrap();
+                diff.removed.push((id, (**row).clone()));
+            }
+            (None, Some(_)) => {
+                let (id, row) = a.next().unwrap();
+                diff.added.push((id, (**row).clone()));
+            }
+            (None, None) => break,
+        }
+    }
+    diff
+}
+
+fn diff_snapshots(before: &HashMap<String, Table>, after: &HashMap<&str, &Table>) -> SnapshotDiff {
+    let mut diff = SnapshotDiff::default();
+    for (name, old) in before {
+        match after.get(name.as_str()) {
+            Some(new) if old.columns == new.columns && old.merkle_root() == new.merkle_root() => {
+                diff.unchanged_tables += 1;
+            }
+            Some(new) => {
+                let table = diff_table(Some(old), Some(new));
+                if table.is_empty() {
+                    diff.unchanged_tables += 1;
+                } else {
+                    diff.tables.insert(name.clone(), table);
+                }
+            }
+            None => {
+                diff.removed_tables.push(name.clone());
+                diff.tables.insert(name.clone(), diff_table(Some(old), None));
+            }
+        }
+    }
+    for (name, new) in after {
+        if !before.contains_key(*name) {
+            diff.added_tables.push(name.to_string());
+            diff.tables.insert(name.to_string(), diff_table(None, Some(new)));
+        }
+    }
+    diff.added_tables.sort();
+    diff.removed_tables.sort();
+    diff
+}
+
+impl Database {
+    /// Compares the snapshot at `path_a` with the one at `path_b`, both
+    /// encrypted with this database's key, as described in the module docs.
+    pub async fn diff(&self, path_a: &str, path_b: &str) -> io::Result<SnapshotDiff> {
+        self.check_open_io()?;
+        let before = self.read_snapshot_tables(path_a)?;
+        let after = self.read_snapshot_tables(path_b)?;
+        let after: HashMap<&str, &Table> = after.iter().map(|(name, table)| (name.as_str(), table)).collect();
+        Ok(diff_snapshots(&before, &after))
+    }
+
+    /// Compares the snapshot at `path` with the database's current tables,
+    /// such as to check what has changed since a backup.
+    pub async fn diff_current(&self, path: &str) -> io::Result<SnapshotDiff> {
+        self.check_open_io()?;
+        let before = self.read_snapshot_tables(path)?;
+        let tables = self.read_tables().await?;
+        let after: HashMap<&str, &Table> = tables
+            .values()
+            .filter(|table| !table.ephemeral)
+            .map(|table| (table.name.as_str(), table))
+            .collect();
+        Ok(diff_snapshots(&before, &after))
+    }
+
+    // The tables of the snapshot at `path`, without loading them.
+    fn read_snapshot_tables(&self, path: &str) -> io::Result<HashMap<String, Table>> {
+        let buffer = self
+            .storage
+            .read(path)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path)))?;
+        let mut report = RecoveryReport::default();
+        let decompressed = unpack_snapshot(&self.key, &buffer, &mut report, false)?;
+        let mut tables = recovery::decode_snapshot(&decompressed, &mut report)?.tables;
+        report.into_result()?;
+        for table in tables.values_mut() {
+            table.adopt_schema();
+            table.assign_row_ids();
+        }
+        Ok(tables)
+    }
+}
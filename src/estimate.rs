@@ -0,0 +1,164 @@
+//! Estimating what a select would return without running it.
+//!
+//! `Database::estimate` reckons how many rows a query would return, and how
+//! big they are, from the table's statistics and indexes, so a caller can
+//! warn before an expensive select or export. An equality on an indexed
+//! column is counted from the index; other conditions use the planner's
+//! estimates, with the conditions of an `And` taken as independent and
+//! those of an `Or` as overlapping by chance. A traversal is taken to reach
+//! every row, and an aggregate returns a row per group.
+//!
+//! A join is estimated as joining on a key: each row matches the rows of
+//! the other table sharing its value, from the number of different values
+//! the columns have, which is taken from an index or the statistics, or
+//! otherwise assumed to be one per row. Sizes are in-memory sizes, as
+//! `memory_stats` counts them, at each table's average row size.
+
+use crate::sample::SampleSize;
+use crate::{Database, JoinType, Operator, Query, Table, Value};
+use std::mem::size_of;
+
+// Used for comparisons of expressions, which have no statistics.
+const COMPARE_SELECTIVITY: f64 = 1.0 / 3.0;
+
+/// What a query is expected to return, from `Database::estimate`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Estimate {
+    pub rows: f64,
+    /// The rows' estimated size in memory.
+    pub bytes: f64,
+}
+
+fn average_row_size(table: &Table) -> f64 {
+    match table.data.len() {
+        0 => 0.0,
+        rows => table.data_bytes as f64 / rows as f64,
+    }
+}
+
+// How many different values `column` has, if an index or the statistics
+// say.
+fn distinct_values(table: &Table, column: &str) -> Option<f64> {
+    if let Some(index) = table.indexes.get(column).filter(|_| !table.indexes_stale) {
+        return Some(index.distinct_values() as f64);
+    }
+    let stats = table.stats.as_ref()?;
+    let column = stats.columns.get(column)?;
+    // Scaled to the table's current size.
+    let scale = table.data.len() as f64 / stats.row_count.max(1) as f64;
+    Some((column.distinct_count as f64 * scale).max(1.0))
+}
+
+impl Database {
+    /// The rows `query` is expected to return from `table_name`, and their
+    /// size, without running it; see the module docs.
+    pub async fn estimate(&self, table_name: &str, query: &Query) -> Result<Estimate, String> {
+        self.check_open()?;
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        self.check_query(query, &table.columns)?;
+        self.check_readable(table_name, query)?;
+        let row_count = table.data.len() as f64;
+        let estimate = match query {
+            Query::Join(join) => {
+                let target = tables
+                    .get(&join.target_table)
+                    .ok_or_else(|| format!("Table {} not found", join.target_table))?;
+                let target_count = target.data.len() as f64;
+                let (left, right) = &join.on_condition;
+                let distinct = distinct_values(table, left)
+                    .unwrap_or(row_count)
+                    .max(distinct_values(target, right).unwrap_or(target_count))
+                    .max(1.0);
+                let matched = row_count * target_count / distinct;
+                let rows = match join.join_type {
+                    JoinType::Inner => matched,
+                    JoinType::Left => matched.max(row_count),
+                    JoinType::Right => matched.max(target_count),
+                };
+                Estimate {
+                    rows,
+                    bytes: rows * (average_row_size(table) + average_row_size(target)),
+                }
+            }
+            Query::Aggregate(aggregate) => {
+                let matching = match &aggregate.filter {
+                    Some(filter) => self.fraction(table, filter) * row_count,
+                    None => row_count,
+                };
+                let groups: f64 = aggregate
+                    .group_by
+                    .iter()
+                    .map(|column| distinct_values(table, column).unwrap_or(row_count))
+                    .product();
+                let rows = if aggregate.group_by.is_empty() { 1.0 } else { groups.min(matching).ceil() };
+                Estimate {
+                    rows,
+                    bytes: rows * ((aggregate.group_by.len() + 1) * size_of::<Value>()) as f64,
+                }
+            }
+            _ => {
+                let rows = self.fraction(table, query) * row_count;
+                Estimate {
+                    rows,
+                    bytes: rows * average_row_size(table),
+                }
+            }
+        };
+        Ok(estimate)
+    }
+
+    // The fraction of the rows of `table` expected to match `query`.
+    fn fraction(&self, table: &Table, query: &Query) -> f64 {
+        let row_count = table.data.len() as f64;
+        match query {
+            Query::MatchAll | Query::Traverse(_) => 1.0,
+            Query::Condition(condition) => {
+                let index = table.indexes.get(&condition.column).filter(|_| !table.indexes_stale);
+                match index {
+                    Some(index) if matches!(condition.operator, Operator::Eq) && row_count > 0.0 => {
+                        let ids: usize = condition
+                            .value
+                            .index_keys()
+                            .iter()
+                            .filter_map(|key| index.get(key))
+                            .map(|ids| ids.len())
+                            .sum();
+                        ids as f64 / row_count
+                    }
+                    _ => self.query_planner.selectivity(table, condition),
+                }
+            }
+            Query::And(queries) => queries.iter().map(|q| self.fraction(table, q)).product(),
+            Query::Or(queries) => 1.0 - queries.iter().map(|q| 1.0 - self.fraction(table, q)).product::<f64>(),
+            Query::Compare(_) => COMPARE_SELECTIVITY,
+            Query::Sample(sample) => {
+                let fraction = self.fraction(table, &sample.query);
+                match sample.size {
+                    SampleSize::Fraction(share) => fraction * share.clamp(0.0, 1.0),
+                    SampleSize::Rows(rows) if row_count > 0.0 => fraction.min(rows as f64 / row_count),
+                    SampleSize::Rows(_) => 0.0,
+                }
+            }
+            // Each value the subquery selects matches its share of `column`'s
+            // distinct values.
+            Query::InSubquery {
+                column,
+                table: other,
+                subquery,
+                select_column,
+            } => self
+                .with_published_table(other, |other| {
+                    let other_count = other.data.len() as f64;
+                    let values = (self.fraction(other, subquery) * other_count)
+                        .min(distinct_values(other, select_column).unwrap_or(other_count));
+                    let distinct = distinct_values(table, column).unwrap_or(row_count).max(1.0);
+                    Ok((values / distinct).min(1.0))
+                })
+                .unwrap_or(0.0),
+            Query::Join(_) | Query::Aggregate(_) => 1.0,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Reporting how a join will run.
+//!
+//! `Database::explain_join` plans a join as `select` would and reports the
+//! plan: which table the join loops over, and how it finds the matching
+//! rows of the other, the probed table. When the probed table has an index
+//! on its join column the planner can look each key up in it instead of
+//! reading the table, and the plan names the index. Joins planned to hash
+//! may still spill to a sort-merge join at run time, under
+//! `DatabaseConfig::join_memory_limit`.
+
+use crate::optimizer::JoinStrategy;
+use crate::{Database, Join, Query};
+
+/// How a join finds the rows of the probed table that match a row of the
+/// driving table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinMethod {
+    /// Compares against every row of the probed table.
+    NestedLoop,
+    /// Looks the key up in a hash table built from the probed table.
+    Hash,
+    /// Looks the key up in the probed table's index on its join column.
+    IndexLookup,
+}
+
+/// A join's plan, from `Database::explain_join`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct JoinExplanation {
+    /// The table the join loops over.
+    pub driving_table: String,
+    pub probed_table: String,
+    pub method: JoinMethod,
+    /// The probed table's column whose index is looked up, for
+    /// `IndexLookup`.
+    pub index: Option<String>,
+    /// The rows of each table taking part once the filter has been pushed
+    /// down: the queried table's, then the target table's.
+    pub rows: (usize, usize),
+}
+
+impl Database {
+    /// The plan `select` would use for `join` on `table_name`, with `filter`
+    /// applied to the joined rows.
+    pub async fn explain_join(
+        &self,
+        table_name: &str,
+        join: &Join,
+        filter: Option<&Query>,
+    ) -> Result<JoinExplanation, String> {
+        self.check_open()?;
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let left = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let right = tables
+            .get(&join.target_table)
+            .ok_or_else(|| format!("Table {} not found", join.target_table))?;
+        let pushdown = self.query_planner.push_down(filter, left, right, join);
+        let rows = (
+            self.execute_query(left, &pushdown.left)?.len(),
+            self.execute_query(right, &pushdown.right)?.len(),
+        );
+        let plan = self.query_planner.plan_join((left, rows.0), (right, rows.1), join);
+        let (driving, probed, probed_column) = if plan.drive_left {
+            (left, right, &join.on_condition.1)
+        } else {
+            (right, left, &join.on_condition.0)
+        };
+        let method = match plan.strategy {
+            JoinStrategy::NestedLoop => JoinMethod::NestedLoop,
+            JoinStrategy::Hash => JoinMethod::Hash,
+            JoinStrategy::IndexLookup => JoinMethod::IndexLookup,
+        };
+        Ok(JoinExplanation {
+            driving_table: driving.name.clone(),
+            probed_table: probed.name.clone(),
+            method,
+            index: (method == JoinMethod::IndexLookup).then(|| probed_column.clone()),
+            rows,
+        })
+    }
+}
@@ -0,0 +1,301 @@
+//! C API, enabled with the `ffi` feature. `include/zapdb.h` declares it.
+//!
+//! A `ZapDb` handle owns a database and the runtime its calls block on, so a
+//! handle must not be used from inside another tokio runtime. Queries use the
+//! JSON protocol, and rows come back as JSON objects.
+//!
+//! Functions returning `int` return 0 on success and -1 on failure. Functions
+//! returning a pointer return NULL on failure. After a failure,
+//! `zapdb_last_error` describes it. Strings passed in are UTF-8 and
+//! NUL-terminated. Strings handed out stay owned by the handle or result they
+//! came from.
+
+use crate::{row_to_json, to_row, Column, Constraint, DataType, Database};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::ptr;
+use tokio::runtime::Runtime;
+
+pub struct ZapDb {
+    runtime: Runtime,
+    db: Database,
+}
+
+pub struct ZapDbResult {
+    rows: Vec<CString>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl Into<String>) {
+    let message = CString::new(message.into().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+unsafe fn str_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, String> {
+    if s.is_null() {
+        return Err(format!("{} is NULL", name));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| format!("{} is not valid UTF-8", name))
+}
+
+unsafe fn handle<'a>(db: *mut ZapDb) -> Result<&'a ZapDb, String> {
+    db.as_ref().ok_or_else(|| "Database handle is NULL".to_string())
+}
+
+// Runs `f`, recording its error for `zapdb_last_error`.
+fn guard<T>(f: impl FnOnce() -> Result<T, String>) -> Option<T> {
+    match f() {
+        Ok(value) => Some(value),
+        Err(e) => {
+            set_error(e);
+            None
+        }
+    }
+}
+
+fn status(result: Option<()>) -> c_int {
+    match result {
+        Some(()) => 0,
+        None => -1,
+    }
+}
+
+/// The message for the last failed call on this thread, or NULL. Valid until
+/// the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn zapdb_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Opens a database with a 32-byte encryption key and a WAL path.
+///
+/// # Safety
+/// `key` must point to 32 readable bytes and `wal_path` to a NUL-terminated
+/// string.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_open(key: *const u8, wal_path: *const c_char) -> *mut ZapDb {
+    let opened = guard(|| {
+        if key.is_null() {
+            return Err("key is NULL".to_string());
+        }
+        let mut key_bytes = [0u8; 32];
+        key_bytes.copy_from_slice(std::slice::from_raw_parts(key, 32));
+        let wal_path = str_arg(wal_path, "wal_path")?;
+        let runtime = Runtime::new().map_err(|e| e.to_string())?;
+        let db = {
+            let _guard = runtime.enter();
+            Database::new(key_bytes, wal_path)
+        };
+        Ok(ZapDb { runtime, db })
+    });
+    opened.map_or(ptr::null_mut(), |db| Box::into_raw(Box::new(db)))
+}
+
+/// Closes the database, writing a final snapshot to `save_path` unless it is
+/// NULL, and frees the handle, even if closing fails.
+///
+/// # Safety
+/// `db` must come from `zapdb_open` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_close(db: *mut ZapDb, save_path: *const c_char) -> c_int {
+    if db.is_null() {
+        return 0;
+    }
+    let db = Box::from_raw(db);
+    status(guard(|| {
+        let save_path = if save_path.is_null() {
+            None
+        } else {
+            Some(str_arg(save_path, "save_path")?)
+        };
+        db.runtime
+            .block_on(db.db.close(save_path))
+            .map_err(|e| e.to_string())
+    }))
+}
+
+/// Loads a snapshot (if `path` exists) and replays the WAL.
+///
+/// # Safety
+/// `db` must be a live handle and `path` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_load(db: *mut ZapDb, path: *const c_char) -> c_int {
+    status(guard(|| {
+        let db = handle(db)?;
+        let path = str_arg(path, "path")?;
+        db.runtime.block_on(db.db.load(path)).map_err(|e| e.to_string())
+    }))
+}
+
+/// Writes a snapshot to `path` and truncates the WAL.
+///
+/// # Safety
+/// `db` must be a live handle and `path` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_save(db: *mut ZapDb, path: *const c_char) -> c_int {
+    status(guard(|| {
+        let db = handle(db)?;
+        let path = str_arg(path, "path")?;
+        db.runtime.block_on(db.db.save(path)).map_err(|e| e.to_string())
+    }))
+}
+
+/// Creates a table. `columns_json` is an array of
+/// `{"name": "id", "type": "integer", "constraints": ["not_null", "unique"]}`;
+/// `constraints` is optional.
+///
+/// # Safety
+/// `db` must be a live handle and the strings NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_create_table(
+    db: *mut ZapDb,
+    table: *const c_char,
+    columns_json: *const c_char,
+) -> c_int {
+    status(guard(|| {
+        let db = handle(db)?;
+        let table = str_arg(table, "table")?;
+        let columns = parse_columns(str_arg(columns_json, "columns_json")?)?;
+        db.runtime
+            .block_on(db.db.create_table(table.to_string(), columns))
+            .map(|_| ())
+    }))
+}
+
+/// Inserts a row given as a JSON object of column names to values.
+///
+/// # Safety
+/// `db` must be a live handle and the strings NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_insert(db: *mut ZapDb, table: *const c_char, row_json: *const c_char) -> c_int {
+    status(guard(|| {
+        let db = handle(db)?;
+        let table = str_arg(table, "table")?;
+        let json: serde_json::Value = serde_json::from_str(str_arg(row_json, "row_json")?)
+            .map_err(|e| format!("Invalid JSON: {}", e))?;
+        db.runtime.block_on(async {
+            let columns = db
+                .db
+                .table_columns(table)
+                .await
+                .ok_or_else(|| format!("Table {} not found", table))?;
+            db.db.insert(table, to_row(&json, &columns)?).await?;
+            Ok(())
+        })
+    }))
+}
+
+/// Deletes the rows matching a JSON protocol query and returns how many were
+/// deleted, or -1.
+///
+/// # Safety
+/// `db` must be a live handle and the strings NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_delete(db: *mut ZapDb, table: *const c_char, query_json: *const c_char) -> i64 {
+    let deleted = guard(|| {
+        let db = handle(db)?;
+        let table = str_arg(table, "table")?;
+        let query_json = str_arg(query_json, "query_json")?;
+        db.runtime.block_on(async {
+            let query = db.db.query_from_json(table, query_json).await?;
+            Ok(db.db.delete(table, &query).await?)
+        })
+    });
+    deleted.map_or(-1, |count| count as i64)
+}
+
+/// Runs a JSON protocol query. Free the result with `zapdb_result_free`.
+///
+/// # Safety
+/// `db` must be a live handle and the strings NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_query(
+    db: *mut ZapDb,
+    table: *const c_char,
+    query_json: *const c_char,
+) -> *mut ZapDbResult {
+    let result = guard(|| {
+        let db = handle(db)?;
+        let table = str_arg(table, "table")?;
+        let query_json = str_arg(query_json, "query_json")?;
+        let rows = db.runtime.block_on(async {
+            let query = db.db.query_from_json(table, query_json).await?;
+            db.db.select_shared(table, &query).await.map(|(rows, _)| rows)
+        })?;
+        let rows = rows
+            .iter()
+            .map(|row| CString::new(row_to_json(row).to_string()).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ZapDbResult { rows })
+    });
+    result.map_or(ptr::null_mut(), |result| Box::into_raw(Box::new(result)))
+}
+
+/// The number of rows in a result.
+///
+/// # Safety
+/// `result` must come from `zapdb_query` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_result_count(result: *const ZapDbResult) -> usize {
+    result.as_ref().map_or(0, |r| r.rows.len())
+}
+
+/// Row `index` of a result as a JSON object, or NULL if out of range. Valid
+/// until the result is freed.
+///
+/// # Safety
+/// `result` must come from `zapdb_query` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_result_row(result: *const ZapDbResult, index: usize) -> *const c_char {
+    result
+        .as_ref()
+        .and_then(|r| r.rows.get(index))
+        .map_or(ptr::null(), |row| row.as_ptr())
+}
+
+/// # Safety
+/// `result` must come from `zapdb_query` and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn zapdb_result_free(result: *mut ZapDbResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+fn parse_columns(json: &str) -> Result<Vec<Column>, String> {
+    let parsed: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    let items = parsed
+        .as_array()
+        .ok_or_else(|| "columns_json must be an array".to_string())?;
+    items
+        .iter()
+        .map(|item| {
+            let name = item
+                .get("name")
+                .and_then(|n| n.as_str())
+                .ok_or_else(|| "Column is missing a name".to_string())?;
+            let data_type: DataType = item
+                .get("type")
+                .and_then(|t| t.as_str())
+                .ok_or_else(|| format!("Column {} is missing a type", name))?
+                .parse()?;
+            let constraints = match item.get("constraints").and_then(|c| c.as_array()) {
+                Some(constraints) => constraints
+                    .iter()
+                    .map(|c| match c.as_str() {
+                        Some("not_null") => Ok(Constraint::NotNull),
+                        Some("unique") => Ok(Constraint::Unique),
+                        _ => Err(format!("Unknown constraint {} on column {}", c, name)),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => Vec::new(),
+            };
+            Ok(Column::new(name.to_string(), data_type, constraints))
+        })
+        .collect()
+}
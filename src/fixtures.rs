@@ -0,0 +1,140 @@
+//! Loading fixtures: rows for several tables from one JSON document.
+//!
+//! ```json
+//! {
+//!   "users": [{"$name": "alice", "id": 1, "name": "Alice"}],
+//!   "posts": [{"id": 10, "author_id": {"$ref": "users.alice.id"}, "title": "Hi"}]
+//! }
+//! ```
+//!
+//! Each table maps to a list of rows. A row may be named with `$name`, and
+//! another row can use `{"$ref": "<table>.<name>.<column>"}` for that row's
+//! value in the column, as written in the document. Tables are loaded after
+//! the tables they refer to, by reference or foreign key, and rows in the
+//! order they are listed. Values are converted like JSON query values.
+
+use crate::{begin_transaction, Constraint, Database, Value, WriteError};
+use serde_json::{Map, Value as Json};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Read;
+
+const NAME: &str = "$name";
+const REF: &str = "$ref";
+
+type Fixtures = BTreeMap<String, Vec<Map<String, Json>>>;
+
+// The `(table, name, column)` a value refers to, if it is a reference.
+fn reference(json: &Json) -> Result<Option<(&str, &str, &str)>, String> {
+    let Some(target) = json.as_object().filter(|o| o.len() == 1).and_then(|o| o.get(REF)) else {
+        return Ok(None);
+    };
+    let target = target
+        .as_str()
+        .ok_or_else(|| format!("{} must be a string, not {}", REF, target))?;
+    let mut parts = target.splitn(3, '.');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(table), Some(name), Some(column)) => Ok(Some((table, name, column))),
+        _ => Err(format!("Reference {} is not <table>.<name>.<column>", target)),
+    }
+}
+
+// Follows references from `json` to a plain value.
+fn resolve<'a>(fixtures: &'a Fixtures, mut json: &'a Json) -> Result<&'a Json, String> {
+    let mut seen = BTreeSet::new();
+    while let Some((table, name, column)) = reference(json)? {
+        if !seen.insert((table, name, column)) {
+            return Err(format!("Reference {}.{}.{} refers back to itself", table, name, column));
+        }
+        let row = fixtures
+            .get(table)
+            .and_then(|rows| rows.iter().find(|row| row.get(NAME).and_then(Json::as_str) == Some(name)))
+            .ok_or_else(|| format!("Fixture {}.{} not found", table, name))?;
+        json = row
+            .get(column)
+            .ok_or_else(|| format!("Fixture {}.{} has no column {}", table, name, column))?;
+    }
+    Ok(json)
+}
+
+// Orders the tables so each comes after the ones it depends on.
+fn load_order<'a>(dependencies: &BTreeMap<&'a str, BTreeSet<&'a str>>) -> Result<Vec<&'a str>, String> {
+    let mut order = Vec::new();
+    while order.len() < dependencies.len() {
+        let ready: Vec<&str> = dependencies
+            .iter()
+            .filter(|(table, needs)| !order.contains(*table) && needs.iter().all(|n| order.contains(n)))
+            .map(|(table, _)| *table)
+            .collect();
+        if ready.is_empty() {
+            let rest: Vec<&str> = dependencies
+                .keys()
+                .filter(|table| !order.contains(*table))
+                .copied()
+                .collect();
+            return Err(format!("Fixtures depend on each other in a cycle: {}", rest.join(", ")));
+        }
+        order.extend(ready);
+    }
+    Ok(order)
+}
+
+impl Database {
+    /// Inserts the fixtures read from `reader`, described in the module docs,
+    /// in one transaction, and returns how many rows were inserted. The
+    /// tables must already exist.
+    pub async fn load_fixtures(&self, reader: impl Read) -> Result<usize, WriteError> {
+        let fixtures: Fixtures =
+            serde_json::from_reader(reader).map_err(|e| format!("Invalid fixtures: {}", e))?;
+
+        let tables = self.read_tables().await?;
+        let mut columns = HashMap::new();
+        let mut dependencies: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (table_name, rows) in &fixtures {
+            let table = tables
+                .get(table_name)
+                .ok_or_else(|| format!("Table {} not found", table_name))?;
+            columns.insert(table_name.as_str(), &table.columns);
+            let needs = dependencies.entry(table_name).or_default();
+            for column in &table.columns {
+                for constraint in &column.constraints {
+                    if let Constraint::ForeignKey { table: target, .. } = constraint {
+                        if let Some((target, _)) = fixtures.get_key_value(target) {
+                            needs.insert(target);
+                        }
+                    }
+                }
+            }
+            for value in rows.iter().flat_map(|row| row.values()) {
+                if let Some((target, _, _)) = reference(value)? {
+                    if let Some((target, _)) = fixtures.get_key_value(target) {
+                        needs.insert(target);
+                    }
+                }
+            }
+            needs.remove(table_name.as_str());
+        }
+
+        let mut transaction = begin_transaction();
+        let mut inserted = 0;
+        for table_name in load_order(&dependencies)? {
+            let columns = columns[table_name];
+            for fixture in &fixtures[table_name] {
+                let mut row = HashMap::new();
+                for (column, json) in fixture.iter().filter(|(column, _)| *column != NAME) {
+                    let json = resolve(&fixtures, json)?;
+                    let value = match columns.iter().find(|c| c.name == *column) {
+                        Some(c) => Value::from_json(json, &c.data_type)
+                            .map_err(|e| format!("{}.{}: {}", table_name, column, e))?,
+                        None => crate::protocol::infer_value(json),
+                    };
+                    row.insert(column.clone(), value);
+                }
+                transaction.insert(table_name.to_string(), row);
+                inserted += 1;
+            }
+        }
+        drop(tables);
+        self.commit(transaction).await?;
+        Ok(inserted)
+    }
+}
@@ -0,0 +1,254 @@
+//! Arrow Flight service, enabled with the `server` feature.
+//!
+//! Tables move as Arrow record batches, converted as described in
+//! `record_batch`:
+//!
+//! - `do_get` streams the rows selected by a ticket. A ticket is JSON:
+//!   `{"table": "users"}` for the whole table, or
+//!   `{"table": "users", "query": <query>}` with a JSON protocol query.
+//! - `do_put` inserts batches into the table named by the descriptor path
+//!   (`["users"]`). Each batch is committed as one transaction, and a
+//!   `PutResult` whose metadata is the number of rows inserted so far follows
+//!   each one.
+//! - `list_flights`, `get_flight_info` and `get_schema` describe tables. A
+//!   descriptor is either a path naming a table or a command holding a
+//!   ticket.
+
+use crate::record_batch::{arrow_schema, batch_to_rows, rows_to_batch};
+use crate::{begin_transaction, Column, Database, WriteError};
+use arrow::ipc::writer::IpcWriteOptions;
+use arrow_flight::decode::FlightRecordBatchStream;
+use arrow_flight::encode::FlightDataEncoderBuilder;
+use arrow_flight::error::FlightError;
+use arrow_flight::flight_descriptor::DescriptorType;
+use arrow_flight::flight_service_server::{FlightService, FlightServiceServer};
+use arrow_flight::{
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightEndpoint, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PollInfo, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tonic::{Request, Response, Status, Streaming};
+
+// Rows per record batch sent by `do_get`.
+const BATCH_ROWS: usize = 64 * 1024;
+
+pub struct ZapFlightService {
+    db: Arc<Database>,
+}
+
+impl Database {
+    /// The Flight service for this database, to add to a tonic server.
+    pub fn flight_service(self: &Arc<Self>) -> FlightServiceServer<ZapFlightService> {
+        FlightServiceServer::new(ZapFlightService { db: self.clone() })
+    }
+
+    /// Serves the Flight service on `addr` until the server fails.
+    pub async fn serve_flight(self: &Arc<Self>, addr: SocketAddr) -> Result<(), String> {
+        tonic::transport::Server::builder()
+            .add_service(self.flight_service())
+            .serve(addr)
+            .await
+            .map_err(|e| e.to_string())
+    }
+}
+
+// A parsed ticket: the table and the JSON query to run on it.
+struct Selection {
+    table: String,
+    query: String,
+}
+
+impl Selection {
+    fn parse(ticket: &[u8]) -> Result<Self, Status> {
+        let json: serde_json::Value = serde_json::from_slice(ticket)
+            .map_err(|e| Status::invalid_argument(format!("Invalid ticket: {}", e)))?;
+        let table = json
+            .get("table")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| Status::invalid_argument("Ticket has no table"))?;
+        Ok(Selection {
+            table: table.to_string(),
+            query: json
+                .get("query")
+                .map_or_else(|| "\"match_all\"".to_string(), |q| q.to_string()),
+        })
+    }
+
+    fn from_descriptor(descriptor: &FlightDescriptor) -> Result<Self, Status> {
+        match descriptor.r#type() {
+            DescriptorType::Path => match descriptor.path.as_slice() {
+                [table] => Ok(Selection {
+                    table: table.clone(),
+                    query: "\"match_all\"".to_string(),
+                }),
+                _ => Err(Status::invalid_argument("Descriptor path must be a table name")),
+            },
+            DescriptorType::Cmd => Selection::parse(&descriptor.cmd),
+            DescriptorType::Unknown => Err(Status::invalid_argument("Unknown descriptor type")),
+        }
+    }
+
+    fn ticket(&self) -> Ticket {
+        let query: serde_json::Value = serde_json::from_str(&self.query).unwrap_or_default();
+        Ticket::new(serde_json::json!({ "table": self.table, "query": query }).to_string())
+    }
+}
+
+impl ZapFlightService {
+    async fn columns(&self, table: &str) -> Result<Vec<Column>, Status> {
+        self.db
+            .table_columns(table)
+            .await
+            .ok_or_else(|| Status::not_found(format!("Table {} not found", table)))
+    }
+
+    async fn flight_info(&self, selection: Selection, descriptor: FlightDescriptor) -> Result<FlightInfo, Status> {
+        let columns = self.columns(&selection.table).await?;
+        let query = self
+            .db
+            .query_from_json(&selection.table, &selection.query)
+            .await
+            .map_err(Status::invalid_argument)?;
+        let count = self
+            .db
+            .count(&selection.table, &query)
+            .await
+            .map_err(Status::internal)?;
+        FlightInfo::new()
+            .try_with_schema(&arrow_schema(&columns))
+            .map_err(|e| Status::internal(e.to_string()))
+            .map(|info| {
+                info.with_descriptor(descriptor)
+                    .with_endpoint(FlightEndpoint::new().with_ticket(selection.ticket()))
+                    .with_total_records(count as i64)
+            })
+    }
+}
+
+#[tonic::async_trait]
+impl FlightService for ZapFlightService {
+    type HandshakeStream = BoxStream<'static, Result<HandshakeResponse, Status>>;
+    type ListFlightsStream = BoxStream<'static, Result<FlightInfo, Status>>;
+    type DoGetStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoPutStream = BoxStream<'static, Result<PutResult, Status>>;
+    type DoExchangeStream = BoxStream<'static, Result<FlightData, Status>>;
+    type DoActionStream = BoxStream<'static, Result<arrow_flight::Result, Status>>;
+    type ListActionsStream = BoxStream<'static, Result<ActionType, Status>>;
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented("Handshake is not supported"))
+    }
+
+    async fn list_flights(&self, _request: Request<Criteria>) -> Result<Response<Self::ListFlightsStream>, Status> {
+        let mut infos = Vec::new();
+        for table in self.db.table_names().await {
+            let descriptor = FlightDescriptor::new_path(vec![table.clone()]);
+            let selection = Selection::from_descriptor(&descriptor)?;
+            // A table dropped since `table_names` is skipped.
+            if let Ok(info) = self.flight_info(selection, descriptor).await {
+                infos.push(Ok(info));
+            }
+        }
+        Ok(Response::new(stream::iter(infos).boxed()))
+    }
+
+    async fn get_flight_info(&self, request: Request<FlightDescriptor>) -> Result<Response<FlightInfo>, Status> {
+        let descriptor = request.into_inner();
+        let selection = Selection::from_descriptor(&descriptor)?;
+        Ok(Response::new(self.flight_info(selection, descriptor).await?))
+    }
+
+    async fn poll_flight_info(&self, _request: Request<FlightDescriptor>) -> Result<Response<PollInfo>, Status> {
+        Err(Status::unimplemented("Use get_flight_info"))
+    }
+
+    async fn get_schema(&self, request: Request<FlightDescriptor>) -> Result<Response<SchemaResult>, Status> {
+        let selection = Selection::from_descriptor(&request.into_inner())?;
+        let schema = arrow_schema(&self.columns(&selection.table).await?);
+        let result = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+            .try_into()
+            .map_err(|e: arrow::error::ArrowError| Status::internal(e.to_string()))?;
+        Ok(Response::new(result))
+    }
+
+    async fn do_get(&self, request: Request<Ticket>) -> Result<Response<Self::DoGetStream>, Status> {
+        let selection = Selection::parse(&request.into_inner().ticket)?;
+        let columns = self.columns(&selection.table).await?;
+        let query = self
+            .db
+            .query_from_json(&selection.table, &selection.query)
+            .await
+            .map_err(Status::invalid_argument)?;
+        let (rows, _) = self
+            .db
+            .select_shared(&selection.table, &query)
+            .await
+            .map_err(Status::internal)?;
+        let schema = Arc::new(arrow_schema(&columns));
+        let batches = stream::iter((0..rows.len()).step_by(BATCH_ROWS).map(move |start| {
+            let end = (start + BATCH_ROWS).min(rows.len());
+            rows_to_batch(&columns, &rows[start..end]).map_err(FlightError::from)
+        }));
+        let data = FlightDataEncoderBuilder::new()
+            .with_schema(schema)
+            .build(batches)
+            .map_err(Status::from);
+        Ok(Response::new(data.boxed()))
+    }
+
+    async fn do_put(&self, request: Request<Streaming<FlightData>>) -> Result<Response<Self::DoPutStream>, Status> {
+        let mut data = request.into_inner();
+        let first = data
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("No data"))?;
+        let descriptor = first
+            .flight_descriptor
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("The first message has no descriptor"))?;
+        let table = Selection::from_descriptor(&descriptor)?.table;
+        let columns = self.columns(&table).await?;
+
+        let data = stream::once(async { Ok(first) }).chain(data.map_err(FlightError::from));
+        let mut batches = FlightRecordBatchStream::new_from_flight_data(data);
+        let mut results = Vec::new();
+        let mut inserted = 0;
+        while let Some(batch) = batches.try_next().await? {
+            let rows = batch_to_rows(&batch, &columns).map_err(Status::invalid_argument)?;
+            inserted += rows.len();
+            let mut transaction = begin_transaction();
+            for row in rows {
+                transaction.insert(table.clone(), row);
+            }
+            self.db.commit(transaction).await.map_err(|e| match e {
+                WriteError::Busy(busy) => Status::unavailable(busy.to_string()),
+                e => Status::failed_precondition(e.to_string()),
+            })?;
+            results.push(Ok(PutResult {
+                app_metadata: inserted.to_string().into(),
+            }));
+        }
+        Ok(Response::new(stream::iter(results).boxed()))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("Exchange is not supported"))
+    }
+
+    async fn do_action(&self, _request: Request<Action>) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("There are no actions"))
+    }
+
+    async fn list_actions(&self, _request: Request<Empty>) -> Result<Response<Self::ListActionsStream>, Status> {
+        Ok(Response::new(stream::empty().boxed()))
+    }
+}
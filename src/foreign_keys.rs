@@ -0,0 +1,106 @@
+//! Indexes for foreign key checks, and listing a table's indexes.
+//!
+//! Every column a foreign key refers to is indexed, so checking a child
+//! row is a lookup rather than a scan of the parent table. The index is
+//! built when the child table is created, or when tables are loaded, and
+//! kept up to date by writes like any other. A column that already has an
+//! index, such as the one built for a unique column on load, keeps it.
+//!
+//! Checks count as uses of the index, so it is only evicted under memory
+//! pressure if foreign keys aren't being checked either. Without it, or
+//! while it is stale, checks scan the parent table as before.
+
+use crate::{Constraint, Database, IndexKind, Table, Value};
+use std::collections::HashMap;
+
+/// An index on one column, as listed by `Database::indexes`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndexInfo {
+    pub column: String,
+    /// Estimated bytes.
+    pub bytes: usize,
+    /// Whether the column is unique.
+    pub unique: bool,
+    /// `(table, column)` of each foreign key that refers to the column.
+    pub referenced_by: Vec<(String, String)>,
+}
+
+// `(table, column, referenced table, referenced column)` of every foreign
+// key.
+fn foreign_keys<'a>(tables: impl Iterator<Item = &'a Table>) -> Vec<(&'a str, &'a str, &'a str, &'a str)> {
+    tables
+        .flat_map(|table| {
+            table.columns.iter().flat_map(move |column| {
+                column.constraints.iter().filter_map(move |constraint| match constraint {
+                    Constraint::ForeignKey { table: target, column: target_column } => {
+                        Some((table.name.as_str(), column.name.as_str(), target.as_str(), target_column.as_str()))
+                    }
+                    _ => None,
+                })
+            })
+        })
+        .collect()
+}
+
+/// Indexes every column a foreign key refers to that isn't indexed yet.
+pub(crate) fn index_referenced_columns(tables: &mut HashMap<String, Table>) {
+    let referenced: Vec<(String, String)> = foreign_keys(tables.values())
+        .into_iter()
+        .map(|(_, _, table, column)| (table.to_string(), column.to_string()))
+        .collect();
+    for (table, column) in referenced {
+        if let Some(table) = tables.get_mut(&table) {
+            if !table.indexes.contains_key(&column) {
+                table.build_index(&column, IndexKind::Hash);
+            }
+        }
+    }
+}
+
+impl Table {
+    // Whether any row holds `value` in `column`, for foreign key checks.
+    pub(crate) fn has_value(&self, column: &str, value: &Value, now: u64) -> bool {
+        match self.indexes.get(column).filter(|_| !self.indexes_stale) {
+            Some(index) => {
+                self.index_last_used.insert(column.to_string(), now);
+                index.get(value).is_some_and(|ids| !ids.is_empty())
+            }
+            None => self.data.iter().any(|r| r.get(column) == Some(value)),
+        }
+    }
+}
+
+impl Database {
+    /// The value indexes on `table_name`, by column, or `None` if there's
+    /// no such table. Text indexes aren't listed.
+    pub async fn indexes(&self, table_name: &str) -> Option<Vec<IndexInfo>> {
+        let tables = self.read_tables_untimed().await;
+        let table = tables.get(table_name)?;
+        let mut indexes: Vec<IndexInfo> = table
+            .indexes
+            .keys()
+            .map(|column| IndexInfo {
+                column: column.clone(),
+                bytes: table.index_sizes.get(column).copied().unwrap_or(0),
+                unique: table
+                    .columns
+                    .iter()
+                    .any(|c| &c.name == column && c.constraints.contains(&Constraint::Unique)),
+                referenced_by: Vec::new(),
+            })
+            .collect();
+        for (child, child_column, target, target_column) in foreign_keys(tables.values()) {
+            if target != table_name {
+                continue;
+            }
+            if let Some(index) = indexes.iter_mut().find(|i| i.column == target_column) {
+                index.referenced_by.push((child.to_string(), child_column.to_string()));
+            }
+        }
+        for index in &mut indexes {
+            index.referenced_by.sort();
+        }
+        indexes.sort_by(|a, b| a.column.cmp(&b.column));
+        Some(indexes)
+    }
+}
@@ -0,0 +1,48 @@
+//! Forking a database into an isolated copy, for tests and trial
+//! migrations against real data.
+//!
+//! `Database::fork` copies everything loaded from the snapshot and WAL, and
+//! written since: tables, views, materialized views, rollups, sequences,
+//! functions and tenants, as they stand between two writes. Writes replace
+//! rows rather than change them, so the fork shares its rows with the
+//! original, as `select_shared` does; indexes and the rest are copied.
+//!
+//! The fork keeps its WAL and snapshots in a `MemoryStorage` of its own, so
+//! nothing it does reaches the original or its files, and it is gone once
+//! dropped. Watches, change sinks, scheduled jobs, row history, undo and
+//! the lock taken by `open` aren't carried over.
+
+use crate::{Database, DatabaseConfig, MemoryStorage};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+impl Database {
+    /// An isolated, in-memory copy of the database, as described in the
+    /// module docs.
+    pub async fn fork(&self) -> Result<Database, String> {
+        self.check_open()?;
+        // Writes take the WAL lock first, so holding it keeps them all out.
+        let _wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tables = self.timed(self.tables.read()).await.map_err(|e| e.to_string())?.clone();
+        for table in tables.values_mut() {
+            table.finish_maintenance();
+        }
+
+        let config = DatabaseConfig {
+            storage: Some(Arc::new(MemoryStorage::new())),
+            wal_archive: None,
+            ..self.config.clone()
+        };
+        let mut fork = Database::with_config(self.key, &self.wal_path, config);
+        fork.named_views.replace(self.named_views.all());
+        fork.sequences.replace(self.sequences.all());
+        fork.materialized = self.materialized.fork();
+        fork.rollups = self.rollups.fork();
+        fork.functions = self.functions.fork();
+        fork.tenants = self.tenants.fork();
+        fork.session = std::sync::RwLock::new(self.session.read().unwrap().clone());
+        fork.publish_all(&tables);
+        fork.tables = Arc::new(RwLock::new(tables));
+        Ok(fork)
+    }
+}
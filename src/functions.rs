@@ -0,0 +1,272 @@
+//! Expressions and user-defined scalar functions.
+//!
+//! Functions are registered on a database with a signature and can then be
+//! called from expressions: in `Query::Compare` filters and in
+//! `Database::project`. Every expression is checked against the registered
+//! signatures and the table's columns before the query runs, so a misspelt
+//! column, a wrong number of arguments or an argument of the wrong type is an
+//! error rather than an empty result.
+//!
+//! At run time a missing column evaluates to `Null`. If a function returns an
+//! error while filtering, the row doesn't match; while projecting, the error
+//! is returned.
+
+use crate::text_index::check_regex;
+use crate::{Column, Condition, Database, DataType, Operator, Query, Row, RowSchema, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Expr {
+    Column(String),
+    Literal(Value),
+    Call { function: String, args: Vec<Expr> },
+}
+
+impl Expr {
+    pub fn column(name: &str) -> Self {
+        Expr::Column(name.to_string())
+    }
+
+    pub fn call(function: &str, args: Vec<Expr>) -> Self {
+        Expr::Call {
+            function: function.to_string(),
+            args,
+        }
+    }
+}
+
+/// A filter comparing two expressions, e.g. `score(title, body) > 0.5`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Comparison {
+    pub left: Expr,
+    pub operator: Operator,
+    pub right: Expr,
+}
+
+type FunctionBody = Arc<dyn Fn(&[Value]) -> Result<Value, String> + Send + Sync>;
+
+#[derive(Clone)]
+struct ScalarFunction {
+    params: Vec<DataType>,
+    returns: DataType,
+    body: FunctionBody,
+}
+
+#[derive(Default)]
+pub(crate) struct Functions {
+    functions: RwLock<HashMap<String, ScalarFunction>>,
+}
+
+impl Functions {
+    // A copy for `Database::fork`.
+    pub(crate) fn fork(&self) -> Self {
+        Functions {
+            functions: RwLock::new(self.functions.read().unwrap().clone()),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<ScalarFunction> {
+        self.functions.read().unwrap().get(name).cloned()
+    }
+}
+
+impl Database {
+    /// Registers `body` as the scalar function `name`, taking arguments of
+    /// the types in `params` and returning a `returns`. Arguments may also be
+    /// `Null`. Registering a name again replaces the function.
+    pub fn register_function<F>(&self, name: &str, params: Vec<DataType>, returns: DataType, body: F)
+    where
+        F: Fn(&[Value]) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        self.functions.functions.write().unwrap().insert(
+            name.to_string(),
+            ScalarFunction {
+                params,
+                returns,
+                body: Arc::new(body),
+            },
+        );
+    }
+
+    pub fn unregister_function(&self, name: &str) -> bool {
+        self.functions.functions.write().unwrap().remove(name).is_some()
+    }
+
+    /// Runs `query` on `table_name` and returns, for each matching row, a row
+    /// with one column per `(name, expression)` pair. The expressions of a
+    /// join read the columns of the joined rows.
+    pub async fn project(
+        &self,
+        table_name: &str,
+        query: &Query,
+        columns: &[(&str, Expr)],
+    ) -> Result<Vec<Row>, String> {
+        {
+            let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+            let table = tables
+                .get(table_name)
+                .ok_or_else(|| format!("Table {} not found", table_name))?;
+            let joined = match query {
+                Query::Join(join) => tables.get(&join.target_table).map(|target| join.joined_columns(table, target)),
+                _ => None,
+            };
+            for (_, expr) in columns {
+                self.expr_type(expr, joined.as_deref().unwrap_or(&table.columns))?;
+                self.check_expr_readable(table_name, expr)?;
+            }
+        }
+        let (rows, _) = self.select_shared(table_name, query).await?;
+        let schema = Arc::new(RowSchema::new(columns.iter().map(|(name, _)| name.to_string())));
+        rows.iter()
+            .map(|row| {
+                let mut projected = Row::with_schema(schema.clone());
+                for (name, expr) in columns {
+                    projected.insert(name.to_string(), self.evaluate_expr(row, expr)?);
+                }
+                Ok(projected)
+            })
+            .collect()
+    }
+
+    /// Checks every comparison in `query` against `columns` and the
+    /// registered functions.
+    pub(crate) fn check_query(&self, query: &Query, columns: &[Column]) -> Result<(), String> {
+        match query {
+            Query::Compare(comparison) => {
+                let left = self.expr_type(&comparison.left, columns)?;
+                let right = self.expr_type(&comparison.right, columns)?;
+                match (left, right) {
+                    (Some(left), Some(right)) if !comparable(&left, &right) => {
+                        Err(format!("Cannot compare {:?} with {:?}", left, right))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().try_for_each(|q| self.check_query(q, columns))
+            }
+            Query::Aggregate(aggregate) => {
+                if let Some(missing) = aggregate.group_by.iter().find(|g| !columns.iter().any(|c| c.name == **g)) {
+                    return Err(format!("Column {} not found", missing));
+                }
+                match &aggregate.filter {
+                    Some(filter) => self.check_query(filter, columns),
+                    None => Ok(()),
+                }
+            }
+            Query::Condition(Condition {
+                operator: Operator::Regex,
+                value: Value::String(pattern),
+                ..
+            }) => check_regex(pattern),
+            Query::Condition(Condition {
+                value: Value::Param(i),
+                ..
+            }) => Err(format!("Parameter ${} is not bound; run the query with execute_prepared", i)),
+            Query::Traverse(traverse) => self.check_traverse(traverse, columns),
+            Query::Sample(sample) => self.check_sample(sample, columns),
+            Query::InSubquery {
+                table,
+                subquery,
+                select_column,
+                ..
+            } => self.check_subquery(table, subquery, select_column),
+            _ => Ok(()),
+        }
+    }
+
+    // The type `expr` evaluates to, or `None` for a `Null` literal.
+    fn expr_type(&self, expr: &Expr, columns: &[Column]) -> Result<Option<DataType>, String> {
+        match expr {
+            Expr::Column(name) => columns
+                .iter()
+                .find(|c| c.name == *name)
+                .map(|c| Some(c.data_type.clone()))
+                .ok_or_else(|| format!("Column {} not found", name)),
+            Expr::Literal(value) => Ok(value_type(value)),
+            Expr::Call { function, args } => {
+                let signature = self
+                    .functions
+                    .get(function)
+                    .ok_or_else(|| format!("Function {} not found", function))?;
+                if args.len() != signature.params.len() {
+                    return Err(format!(
+                        "Function {} takes {} arguments, got {}",
+                        function,
+                        signature.params.len(),
+                        args.len()
+                    ));
+                }
+                for (i, (arg, param)) in args.iter().zip(&signature.params).enumerate() {
+                    if let Some(arg_type) = self.expr_type(arg, columns)? {
+                        if arg_type != *param {
+                            return Err(format!(
+                                "Argument {} of {} must be {:?}, got {:?}",
+                                i + 1,
+                                function,
+                                param,
+                                arg_type
+                            ));
+                        }
+                    }
+                }
+                Ok(Some(signature.returns))
+            }
+        }
+    }
+
+    pub(crate) fn evaluate_expr(&self, row: &Row, expr: &Expr) -> Result<Value, String> {
+        match expr {
+            Expr::Column(name) => Ok(row.get(name).cloned().unwrap_or(Value::Null)),
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Call { function, args } => {
+                let signature = self
+                    .functions
+                    .get(function)
+                    .ok_or_else(|| format!("Function {} not found", function))?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.evaluate_expr(row, arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let result = (signature.body)(&args)?;
+                match value_type(&result) {
+                    Some(result_type) if result_type != signature.returns => Err(format!(
+                        "Function {} returned {:?}, expected {:?}",
+                        function, result_type, signature.returns
+                    )),
+                    _ => Ok(result),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn evaluate_comparison(&self, row: &Row, comparison: &Comparison) -> bool {
+        match (
+            self.evaluate_expr(row, &comparison.left),
+            self.evaluate_expr(row, &comparison.right),
+        ) {
+            (Ok(left), Ok(right)) => comparison.operator.apply(&left, &right),
+            _ => false,
+        }
+    }
+}
+
+pub(crate) fn value_type(value: &Value) -> Option<DataType> {
+    match value {
+        Value::Integer(_) => Some(DataType::Integer),
+        Value::String(_) => Some(DataType::String),
+        Value::Float(_) => Some(DataType::Float),
+        Value::Boolean(_) => Some(DataType::Boolean),
+        Value::DateTime(_) => Some(DataType::DateTime),
+        Value::Uuid(_) => Some(DataType::Uuid),
+        Value::Json(_) => Some(DataType::Json),
+        Value::Null | Value::Param(_) => None,
+    }
+}
+
+fn comparable(left: &DataType, right: &DataType) -> bool {
+    let numeric = |t: &DataType| matches!(t, DataType::Integer | DataType::Float);
+    left == right || (numeric(left) && numeric(right))
+}
@@ -0,0 +1,158 @@
+//! GROUP BY for aggregate queries.
+//!
+//! An `AggregateQuery` with `group_by` columns returns a row for each
+//! combination of their values among the rows it aggregates, holding those
+//! values under the columns' names and the aggregate under `result`.
+//! Groups come in the order of their first rows. Rows without a value in a
+//! group column are grouped under `Null`. A group's `Min` and `Max` skip
+//! nulls, and are `Null` rather than an error for a group without values.
+//! Aggregates without `group_by` are unchanged.
+
+use crate::hll::HyperLogLog;
+use crate::{AggregateFunction, AggregateQuery, Database, Query, Row, RowSchema, Table, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const RESULT_COLUMN: &str = "result";
+
+static NULL: Value = Value::Null;
+
+impl AggregateQuery {
+    /// `function` over `column`, or over `*` to `Count` rows, of every row.
+    pub fn new(function: AggregateFunction, column: &str) -> Self {
+        AggregateQuery {
+            function,
+            column: column.to_string(),
+            filter: None,
+            group_by: Vec::new(),
+        }
+    }
+
+    /// Aggregates only the rows matching `filter`.
+    pub fn filtered(mut self, filter: Query) -> Self {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Aggregates each combination of the values of `columns` separately.
+    pub fn grouped_by(mut self, columns: &[&str]) -> Self {
+        self.group_by = columns.iter().map(|c| c.to_string()).collect();
+        self
+    }
+}
+
+/// The value `row` gives an aggregate of `column`. `*` gives every row a
+/// `Null`, so `Count` of it counts rows.
+pub(crate) fn aggregated<'a>(row: &'a Row, column: &str) -> Option<&'a Value> {
+    match column {
+        "*" => Some(&NULL),
+        _ => row.get(column),
+    }
+}
+
+/// `function` over `values`, or `None` for the `Min` or `Max` of no values.
+pub(crate) fn aggregate_values(function: &AggregateFunction, values: Vec<&Value>) -> Option<Value> {
+    match function {
+        AggregateFunction::Count => Some(Value::Integer(values.len() as i64)),
+        AggregateFunction::Sum => {
+            let mut sum = 0.0;
+            for value in values {
+                match value {
+                    Value::Integer(i) => sum += *i as f64,
+                    Value::Float(f) => sum += *f,
+                    _ => {}
+                }
+            }
+            Some(Value::Float(sum))
+        }
+        AggregateFunction::Avg => {
+            let mut sum = 0.0;
+            let mut count = 0;
+            for value in values {
+                match value {
+                    Value::Integer(i) => {
+                        sum += *i as f64;
+                        count += 1;
+                    }
+                    Value::Float(f) => {
+                        sum += *f;
+                        count += 1;
+                    }
+                    _ => {}
+                }
+            }
+            if count == 0 {
+                Some(Value::Float(0.0))
+            } else {
+                Some(Value::Float(sum / count as f64))
+            }
+        }
+        AggregateFunction::Min => values.into_iter().min().cloned(),
+        AggregateFunction::Max => values.into_iter().max().cloned(),
+        AggregateFunction::CountDistinct => {
+            let distinct: std::collections::HashSet<&Value> = values.into_iter().filter(|v| **v != Value::Null).collect();
+            Some(Value::Integer(distinct.len() as i64))
+        }
+        AggregateFunction::ApproxCountDistinct => {
+            let mut hll = HyperLogLog::new();
+            values.into_iter().filter(|v| **v != Value::Null).for_each(|v| hll.add(v));
+            Some(Value::Integer(hll.estimate().round() as i64))
+        }
+    }
+}
+
+// A group's values for `function`. Nulls compare equal to everything, so
+// they are left out of a group's `Min` and `Max` rather than win or lose at
+// random.
+fn group_values<'a>(function: &AggregateFunction, values: Vec<&'a Value>) -> Vec<&'a Value> {
+    match function {
+        AggregateFunction::Min | AggregateFunction::Max => values.into_iter().filter(|v| **v != Value::Null).collect(),
+        _ => values,
+    }
+}
+
+impl Database {
+    pub(crate) fn execute_grouped_aggregate(&self, table: &Table, aggregate: &AggregateQuery) -> Result<Vec<Row>, String> {
+        if aggregate.group_by.iter().any(|c| c == RESULT_COLUMN) {
+            return Err(format!("Can't group by a column named {}", RESULT_COLUMN));
+        }
+        let positions = match &aggregate.filter {
+            Some(filter) => self.execute_query(table, filter)?,
+            None => (0..table.data.len()).collect(),
+        };
+        let mut groups: Vec<(Vec<Value>, Vec<&Value>)> = Vec::new();
+        let mut group_of: HashMap<Vec<Value>, usize> = HashMap::new();
+        for i in positions {
+            let row = &table.data[i];
+            let key: Vec<Value> = aggregate
+                .group_by
+                .iter()
+                .map(|column| row.get(column).cloned().unwrap_or(Value::Null))
+                .collect();
+            let group = *group_of.entry(key.clone()).or_insert_with(|| {
+                groups.push((key, Vec::new()));
+                groups.len() - 1
+            });
+            if let Some(value) = aggregated(row, &aggregate.column) {
+                groups[group].1.push(value);
+            }
+        }
+
+        let names = aggregate.group_by.iter().cloned().chain([RESULT_COLUMN.to_string()]);
+        let schema = Arc::new(RowSchema::new(names));
+        let rows = groups
+            .into_iter()
+            .map(|(key, values)| {
+                let mut row = Row::with_schema(schema.clone());
+                for (column, value) in aggregate.group_by.iter().zip(key) {
+                    row.insert(column.clone(), value);
+                }
+                let result = aggregate_values(&aggregate.function, group_values(&aggregate.function, values))
+                    .unwrap_or(Value::Null);
+                row.insert(RESULT_COLUMN.to_string(), result);
+                row
+            })
+            .collect();
+        Ok(rows)
+    }
+}
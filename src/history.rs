@@ -0,0 +1,117 @@
+//! Row change history for audited tables.
+//!
+//! Once history is enabled for a table, every committed insert, update and
+//! delete is recorded against the row's primary key, with the row as it was
+//! before and after. An update that changes the key is recorded under both
+//! keys. History is kept in memory only and starts empty after a restart.
+
+use crate::watch::{Changes, WatchEvent};
+use crate::{Database, Row, Table, Value};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryOperation {
+    Insert,
+    Update,
+    Delete,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistoryEntry {
+    pub operation: HistoryOperation,
+    pub timestamp: DateTime<Utc>,
+    pub before: Option<Row>,
+    pub after: Option<Row>,
+}
+
+#[derive(Default)]
+pub(crate) struct History {
+    tables: Mutex<HashMap<String, HashMap<Value, Vec<HistoryEntry>>>>,
+}
+
+impl History {
+    pub(crate) fn active(&self) -> bool {
+        !self.tables.lock().unwrap().is_empty()
+    }
+}
+
+impl Database {
+    /// Starts recording the change history of every row in `table_name`.
+    /// Enabling it again keeps what was already recorded.
+    pub async fn enable_history(&self, table_name: &str) -> Result<(), String> {
+        self.check_open()?;
+        // Take the write lock so no write is half-recorded.
+        let tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        if !tables.contains_key(table_name) {
+            return Err(format!("Table {} not found", table_name));
+        }
+        self.history
+            .tables
+            .lock()
+            .unwrap()
+            .entry(table_name.to_string())
+            .or_default();
+        Ok(())
+    }
+
+    /// Stops recording history for `table_name` and discards what was recorded.
+    pub fn disable_history(&self, table_name: &str) -> bool {
+        self.history.tables.lock().unwrap().remove(table_name).is_some()
+    }
+
+    /// The changes made to the row with primary key `key`, oldest first,
+    /// without the columns hidden from the session's role.
+    pub fn history(&self, table_name: &str, key: &Value) -> Result<Vec<HistoryEntry>, String> {
+        let tables = self.history.tables.lock().unwrap();
+        let rows = tables
+            .get(table_name)
+            .ok_or_else(|| format!("History is not enabled for table {}", table_name))?;
+        let mut entries = rows.get(key).cloned().unwrap_or_default();
+        let hidden = self.hidden_joined(table_name, None);
+        if !hidden.is_empty() {
+            for row in entries.iter_mut().flat_map(|e| e.before.iter_mut().chain(e.after.iter_mut())) {
+                for name in &hidden {
+                    row.remove(name);
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    pub(crate) fn record_history(&self, tables: &HashMap<String, Table>, changes: &Changes) {
+        let mut history = self.history.tables.lock().unwrap();
+        if history.is_empty() {
+            return;
+        }
+        let timestamp = Utc::now();
+        for (table_name, _, event) in changes {
+            let (Some(rows), Some(table)) = (history.get_mut(table_name), tables.get(table_name)) else {
+                continue;
+            };
+            let Some(pk) = table.primary_key().map(|c| c.name.as_str()) else {
+                continue;
+            };
+            let (operation, before, after) = match event {
+                WatchEvent::Insert(row) => (HistoryOperation::Insert, None, Some(row)),
+                WatchEvent::Update { old, new } => (HistoryOperation::Update, Some(old), Some(new)),
+                WatchEvent::Delete(row) => (HistoryOperation::Delete, Some(row), None),
+            };
+            let entry = HistoryEntry {
+                operation,
+                timestamp,
+                before: before.cloned(),
+                after: after.cloned(),
+            };
+            let old_key = before.and_then(|row| row.get(pk));
+            let new_key = after.and_then(|row| row.get(pk));
+            if let Some(key) = old_key {
+                rows.entry(key.clone()).or_default().push(entry.clone());
+            }
+            if let Some(key) = new_key.filter(|key| old_key != Some(*key)) {
+                rows.entry(key.clone()).or_default().push(entry);
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+//! HyperLogLog, for `AggregateFunction::ApproxCountDistinct`.
+//!
+//! Each value is hashed to 64 bits; the top `PRECISION` bits pick a register
+//! and the register keeps the longest run of leading zeros seen in the rest.
+//! The estimate uses a fixed 16 KiB however many values there are, with a
+//! standard error of about 0.8%. Small counts fall back to linear counting,
+//! which is close to exact.
+
+use crate::Value;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+
+pub(crate) struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub(crate) fn new() -> Self {
+        HyperLogLog {
+            registers: vec![0; REGISTERS],
+        }
+    }
+
+    pub(crate) fn add(&mut self, value: &Value) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // A marker bit bounds the run for hashes whose remaining bits are 0.
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        if rank > self.registers[register] {
+            self.registers[register] = rank;
+        }
+    }
+
+    pub(crate) fn estimate(&self) -> f64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|r| 2f64.powi(-(*r as i32))).sum();
+        let estimate = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|r| **r == 0).count();
+        if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Streaming imports.
+//!
+//! `Database::import` reads rows from a stream and writes them in batches,
+//! each with one WAL append and one hold of the table lock. What happens to
+//! a row that can't be inserted is up to `OnRowError`. A row whose foreign
+//! key refers to a row of the same table that hasn't been imported yet, as
+//! when children come before their parents, is held back until the parent
+//! is imported; rows still waiting when the stream ends are tried last.
+
+use crate::{begin_transaction, Condition, Constraint, Database, Operator, Query, Value, WriteBatch, WriteError};
+use futures_core::Stream;
+use std::collections::{HashMap, HashSet};
+use std::future::poll_fn;
+use std::pin::pin;
+use std::sync::Arc;
+
+/// What `import` does with a row that can't be inserted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnRowError {
+    /// Stop the import and return the error. The batch the row was in is not
+    /// written; earlier batches are.
+    #[default]
+    Fail,
+    /// Leave the row out and carry on.
+    Skip,
+    /// Leave the row out, carry on, and return it with its error.
+    Collect,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportProgress {
+    /// Rows taken from the stream so far.
+    pub read: usize,
+    pub inserted: usize,
+    pub failed: usize,
+    /// Rows held back waiting for the row they refer to.
+    pub waiting: usize,
+}
+
+/// A row left out of an import under `OnRowError::Collect`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RowError {
+    /// The row's position in the stream, from 0.
+    pub index: usize,
+    pub row: HashMap<String, Value>,
+    pub error: WriteError,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub failed: usize,
+    /// The rows left out, under `OnRowError::Collect`.
+    pub errors: Vec<RowError>,
+}
+
+type ProgressFn = Arc<dyn Fn(&ImportProgress) + Send + Sync>;
+
+#[derive(Clone)]
+pub struct ImportOptions {
+    /// The most rows written at once.
+    pub batch_size: usize,
+    pub on_error: OnRowError,
+    /// Called after each batch is written.
+    pub progress: Option<ProgressFn>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            batch_size: 1000,
+            on_error: OnRowError::Fail,
+            progress: None,
+        }
+    }
+}
+
+impl ImportOptions {
+    pub fn on_progress(mut self, f: impl Fn(&ImportProgress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(f));
+        self
+    }
+}
+
+type Pending = (usize, HashMap<String, Value>);
+
+// Tracks a table's foreign keys to itself, to hold back rows whose parent
+// hasn't been imported yet.
+struct SelfReferences {
+    /// `(column, referenced column)` pairs.
+    keys: Vec<(String, String)>,
+    /// Values of each referenced column known to exist.
+    present: HashMap<String, HashSet<Value>>,
+    /// Rows waiting for a referenced value, by `(referenced column, value)`.
+    waiting: HashMap<(String, Value), Vec<Pending>>,
+    waiting_rows: usize,
+}
+
+impl SelfReferences {
+    // The referenced value `row` is missing, if any.
+    async fn missing(&mut self, db: &Database, table_name: &str, row: &HashMap<String, Value>) -> Option<(String, Value)> {
+        for (column, target) in &self.keys {
+            let value = match row.get(column) {
+                Some(Value::Null) | None => continue,
+                Some(value) => value,
+            };
+            let present = self.present.entry(target.clone()).or_default();
+            if present.contains(value) {
+                continue;
+            }
+            let query = Query::Condition(Condition {
+                column: target.clone(),
+                operator: Operator::Eq,
+                value: value.clone(),
+            });
+            if db.exists(table_name, &query).await.unwrap_or(false) {
+                present.insert(value.clone());
+            } else {
+                return Some((target.clone(), value.clone()));
+            }
+        }
+        None
+    }
+
+    // Records `row` as imported and returns the rows that were waiting for it.
+    fn imported(&mut self, row: &HashMap<String, Value>) -> Vec<Pending> {
+        let mut released = Vec::new();
+        for (_, target) in &self.keys {
+            if let Some(value) = row.get(target) {
+                self.present.entry(target.clone()).or_default().insert(value.clone());
+                if let Some(rows) = self.waiting.remove(&(target.clone(), value.clone())) {
+                    released.extend(rows);
+                }
+            }
+        }
+        self.waiting_rows -= released.len();
+        released
+    }
+}
+
+impl Database {
+    /// Inserts the rows of `rows` into `table_name`, as described in the
+    /// module docs. Fails as a whole if a batch couldn't be written at all,
+    /// such as when admission turns it away; batches before it are kept.
+    pub async fn import<S>(&self, table_name: &str, rows: S, options: ImportOptions) -> Result<ImportReport, WriteError>
+    where
+        S: Stream<Item = HashMap<String, Value>>,
+    {
+        self.check_open()?;
+        if options.batch_size == 0 {
+            return Err("Import batch size must be at least 1".to_string().into());
+        }
+        let keys = match self.read_tables().await?.get(table_name) {
+            Some(table) => table
+                .columns
+                .iter()
+                .flat_map(|c| c.constraints.iter().map(move |constraint| (c, constraint)))
+                .filter_map(|(c, constraint)| match constraint {
+                    Constraint::ForeignKey { table, column } if table == table_name => {
+                        Some((c.name.clone(), column.clone()))
+                    }
+                    _ => None,
+                })
+                .collect(),
+            None => return Err(format!("Table {} not found", table_name).into()),
+        };
+        let mut references = SelfReferences {
+            keys,
+            present: HashMap::new(),
+            waiting: HashMap::new(),
+            waiting_rows: 0,
+        };
+
+        let mut rows = pin!(rows);
+        let mut progress = ImportProgress::default();
+        let mut report = ImportReport::default();
+        let mut ready: Vec<Pending> = Vec::new();
+        let mut ended = false;
+        while !ended || !ready.is_empty() || references.waiting_rows > 0 {
+            if !ended {
+                match poll_fn(|cx| rows.as_mut().poll_next(cx)).await {
+                    Some(row) => {
+                        let index = progress.read;
+                        progress.read += 1;
+                        match references.missing(self, table_name, &row).await {
+                            Some(awaited) => {
+                                references.waiting.entry(awaited).or_default().push((index, row));
+                                references.waiting_rows += 1;
+                            }
+                            None => ready.push((index, row)),
+                        }
+                    }
+                    None => ended = true,
+                }
+            } else if ready.is_empty() {
+                // Nothing will release these now; let them fail on their own.
+                ready.extend(references.waiting.drain().flat_map(|(_, rows)| rows));
+                ready.sort_by_key(|(index, _)| *index);
+                references.waiting_rows = 0;
+            }
+            if ready.len() >= options.batch_size || (ended && !ready.is_empty()) {
+                let batch: Vec<Pending> = ready.drain(..ready.len().min(options.batch_size)).collect();
+                for (index, row, result) in self.import_batch(table_name, batch, options.on_error).await? {
+                    match result {
+                        Ok(()) => {
+                            report.inserted += 1;
+                            ready.extend(references.imported(&row));
+                        }
+                        Err(error) if options.on_error == OnRowError::Fail => return Err(error),
+                        Err(error) => {
+                            report.failed += 1;
+                            if options.on_error == OnRowError::Collect {
+                                report.errors.push(RowError { index, row, error });
+                            }
+                        }
+                    }
+                }
+                progress.inserted = report.inserted;
+                progress.failed = report.failed;
+                progress.waiting = references.waiting_rows;
+                if let Some(callback) = &options.progress {
+                    callback(&progress);
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    // Writes one batch and returns each row with its result. Under
+    // `OnRowError::Fail` the batch is a transaction, so either every row
+    // is written or the first error is returned alone.
+    async fn import_batch(
+        &self,
+        table_name: &str,
+        batch: Vec<Pending>,
+        on_error: OnRowError,
+    ) -> Result<Vec<(usize, HashMap<String, Value>, Result<(), WriteError>)>, WriteError> {
+        if on_error == OnRowError::Fail {
+            let mut transaction = begin_transaction();
+            for (_, row) in &batch {
+                transaction.insert(table_name.to_string(), row.clone());
+            }
+            self.commit(transaction).await?;
+            return Ok(batch.into_iter().map(|(index, row)| (index, row, Ok(()))).collect());
+        }
+        let mut write_batch = WriteBatch::new();
+        for (_, row) in &batch {
+            write_batch.insert(table_name, row.clone());
+        }
+        let results = self.flush(&mut write_batch).await?;
+        Ok(batch
+            .into_iter()
+            .zip(results)
+            .map(|((index, row), result)| (index, row, result.map(|_| ())))
+            .collect())
+    }
+}
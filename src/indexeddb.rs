@@ -0,0 +1,160 @@
+//! Storage in the browser's IndexedDB, for wasm32.
+//!
+//! IndexedDB is asynchronous and its handles can't leave the thread that
+//! made them, while a `StorageBackend` is synchronous and shared. So
+//! `IndexedDbStorage` reads every object into memory when it is opened,
+//! serves reads and writes from there, and remembers which objects changed;
+//! `flush` writes those back in one transaction. Call it after `save` and
+//! after writes that must survive a reload. `sync` can't wait for IndexedDB,
+//! so it does nothing.
+
+use crate::StorageBackend;
+use js_sys::{Array, Function, Promise, Uint8Array};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::sync::Mutex;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbFactory, IdbRequest, IdbTransactionMode};
+
+const STORE: &str = "objects";
+
+#[derive(Debug)]
+pub struct IndexedDbStorage {
+    name: String,
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+    dirty: Mutex<HashSet<String>>,
+}
+
+impl IndexedDbStorage {
+    /// Opens the IndexedDB database `name`, creating it if needed, and reads
+    /// everything stored in it.
+    pub async fn open(name: &str) -> io::Result<Self> {
+        let db = open_database(name).await?;
+        let transaction = db.transaction_with_str(STORE).map_err(js_error)?;
+        let store = transaction.object_store(STORE).map_err(js_error)?;
+        let keys: Array = finish(&store.get_all_keys().map_err(js_error)?).await?.unchecked_into();
+        let values: Array = finish(&store.get_all().map_err(js_error)?).await?.unchecked_into();
+        let objects = keys
+            .iter()
+            .zip(values.iter())
+            .filter_map(|(key, value)| Some((key.as_string()?, Uint8Array::new(&value).to_vec())))
+            .collect();
+        db.close();
+        Ok(Self {
+            name: name.to_string(),
+            objects: Mutex::new(objects),
+            dirty: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Writes the objects changed since the last flush to IndexedDB.
+    pub async fn flush(&self) -> io::Result<()> {
+        let names: Vec<String> = self.dirty.lock().unwrap().drain().collect();
+        if names.is_empty() {
+            return Ok(());
+        }
+        let changed: Vec<(String, Vec<u8>)> = {
+            let objects = self.objects.lock().unwrap();
+            names.iter().filter_map(|name| Some((name.clone(), objects.get(name)?.clone()))).collect()
+        };
+        let result = self.put_all(&changed).await;
+        if result.is_err() {
+            self.dirty.lock().unwrap().extend(names);
+        }
+        result
+    }
+
+    async fn put_all(&self, objects: &[(String, Vec<u8>)]) -> io::Result<()> {
+        let db = open_database(&self.name).await?;
+        let transaction = db
+            .transaction_with_str_and_mode(STORE, IdbTransactionMode::Readwrite)
+            .map_err(js_error)?;
+        let store = transaction.object_store(STORE).map_err(js_error)?;
+        for (name, data) in objects {
+            store
+                .put_with_key(&Uint8Array::from(&data[..]), &JsValue::from_str(name))
+                .map_err(js_error)?;
+        }
+        let result = settle(|ok, err| {
+            transaction.set_oncomplete(Some(ok));
+            transaction.set_onerror(Some(err));
+        })
+        .await;
+        db.close();
+        result
+    }
+
+    fn changed(&self, name: &str) {
+        self.dirty.lock().unwrap().insert(name.to_string());
+    }
+}
+
+impl StorageBackend for IndexedDbStorage {
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.objects.lock().unwrap().get(name).cloned())
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.objects.lock().unwrap().insert(name.to_string(), data.to_vec());
+        self.changed(name);
+        Ok(())
+    }
+
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.objects
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .extend_from_slice(data);
+        self.changed(name);
+        Ok(())
+    }
+
+    fn sync(&self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn open_database(name: &str) -> io::Result<IdbDatabase> {
+    // `indexedDB` is a global of windows and workers alike.
+    let factory: IdbFactory = js_sys::Reflect::get(&js_sys::global(), &JsValue::from_str("indexedDB"))
+        .and_then(JsCast::dyn_into)
+        .map_err(|_| io::Error::new(io::ErrorKind::Unsupported, "IndexedDB isn't available"))?;
+    let request = factory.open_with_u32(name, 1).map_err(js_error)?;
+    let upgrading = request.clone();
+    let on_upgrade = Closure::once_into_js(move || {
+        if let Ok(db) = upgrading.result().and_then(JsCast::dyn_into::<IdbDatabase>) {
+            let _ = db.create_object_store(STORE);
+        }
+    });
+    request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+    Ok(finish(&request).await?.unchecked_into())
+}
+
+// The result of `request`, once it has one.
+async fn finish(request: &IdbRequest) -> io::Result<JsValue> {
+    settle(|ok, err| {
+        request.set_onsuccess(Some(ok));
+        request.set_onerror(Some(err));
+    })
+    .await?;
+    request.result().map_err(js_error)
+}
+
+// Waits for one of the two callbacks `listen` registers to be called.
+async fn settle(listen: impl FnOnce(&Function, &Function)) -> io::Result<()> {
+    let mut listen = Some(listen);
+    let promise = Promise::new(&mut |resolve, reject| {
+        if let Some(listen) = listen.take() {
+            listen(&resolve, &reject);
+        }
+    });
+    JsFuture::from(promise).await.map(drop).map_err(js_error)
+}
+
+fn js_error(error: JsValue) -> io::Error {
+    io::Error::other(format!("IndexedDB: {:?}", error))
+}
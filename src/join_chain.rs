@@ -0,0 +1,124 @@
+//! Joining a table to several others in one select.
+//!
+//! `Database::select_joins` joins the table queried to the target of a
+//! chain's first join, then the rows joined so far to the target of each
+//! join after it, as users to posts to comments. A join after the first
+//! names the column of the rows so far it joins on as those rows name it,
+//! `posts.id` once posts have been joined `Prefixed`, and its `columns`
+//! only names the columns of its own target; the rows so far keep theirs.
+//! Each join keeps its type: a `Left` join keeps the rows so far that match
+//! nothing in its target, padded with nulls, and a `Right` join the rows of
+//! its target that match nothing so far.
+//!
+//! The chain's filter is run on the rows of the last join, by the names
+//! they give their columns.
+
+use crate::join_columns::{joined_columns, joined_name};
+use crate::{Database, Instant, Join, Query, Row, Table};
+use std::time::Duration;
+
+/// A table's joins to the targets of `joins`, in turn:
+///
+/// ```ignore
+/// JoinChain::new()
+///     .join(Join::new(JoinType::Inner, "posts", ("id", "user_id")).prefixed())
+///     .join(Join::new(JoinType::Inner, "comments", ("posts.id", "post_id")).prefixed())
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct JoinChain {
+    pub joins: Vec<Join>,
+    /// Keeps the joined rows matching it.
+    pub filter: Option<Query>,
+}
+
+impl JoinChain {
+    pub fn new() -> Self {
+        JoinChain::default()
+    }
+
+    pub fn join(mut self, join: Join) -> Self {
+        self.joins.push(join);
+        self
+    }
+
+    pub fn filter(mut self, filter: Query) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+}
+
+impl Database {
+    /// The rows of `table_name` joined by each join of `chain` in turn, as
+    /// described in the module docs.
+    pub async fn select_joins(&self, table_name: &str, chain: &JoinChain) -> Result<(Vec<Row>, Duration), String> {
+        let start = Instant::now();
+        let mut rows = self.join_chain_rows(table_name, chain).await?;
+        self.limit_results(table_name, &mut rows)?;
+        Ok((rows, start.elapsed()))
+    }
+
+    // `select_joins` without `DatabaseConfig::result_limits`.
+    pub(crate) async fn join_chain_rows(&self, table_name: &str, chain: &JoinChain) -> Result<Vec<Row>, String> {
+        self.check_open()?;
+        let Some((first, rest)) = chain.joins.split_first() else {
+            return Err("A join chain needs at least one join".to_string());
+        };
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let target_of = |join: &Join| {
+            tables
+                .get(&join.target_table)
+                .ok_or_else(|| format!("Table {} not found", join.target_table))
+        };
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let target = target_of(first)?;
+        first.check_columns(table_name)?;
+        self.check_readable(table_name, &Query::Join(first.clone()))?;
+        let (left_prefix, right_prefix) = first.prefixes(table_name).unzip();
+        let mut hidden = self.hidden_joined(table_name, left_prefix);
+        hidden.extend(self.hidden_joined(&first.target_table, right_prefix));
+        let mut columns = first.joined_columns(table, target);
+        let mut rows = self.execute_join_query(table, target, first, None)?;
+
+        for join in rest {
+            let target = target_of(join)?;
+            self.check_column_readable(&join.target_table, &join.on_condition.1)?;
+            let (on, _) = &join.on_condition;
+            if hidden.contains(on) || !columns.iter().any(|c| &c.name == on) {
+                return Err(format!("Column {} not found", on));
+            }
+            let prefix = join.prefixes(table_name).map(|(_, target)| target);
+            if let Some(prefix) = prefix {
+                if let Some(column) = target
+                    .columns
+                    .iter()
+                    .map(|c| joined_name(Some(prefix), &c.name))
+                    .find(|name| columns.iter().any(|c| &c.name == name))
+                {
+                    return Err(format!("Column {} is joined twice; give the tables aliases", column));
+                }
+            }
+            hidden.extend(self.hidden_joined(&join.target_table, prefix));
+            let mut so_far = Table::new(String::new(), columns, true);
+            for row in rows {
+                so_far.push_row(row);
+            }
+            columns = joined_columns(&so_far, target, (None, prefix));
+            rows = self.join_tables(&so_far, target, join, None, (None, prefix))?;
+        }
+
+        if !hidden.is_empty() {
+            for row in &mut rows {
+                for name in &hidden {
+                    row.remove(name);
+                }
+            }
+        }
+        if let Some(filter) = &chain.filter {
+            self.check_filter_names(filter, &hidden)?;
+            self.retain_matching(&mut rows, filter)?;
+        }
+        Ok(rows)
+    }
+}
@@ -0,0 +1,99 @@
+//! How joined rows name their columns.
+//!
+//! By default a joined row holds every column under its own name, so a
+//! column both tables have, such as `id`, holds the target table's value.
+//! `JoinColumns::Prefixed` names each column after its table instead, as
+//! `users.id` and `posts.id`, and `Aliased` after the given aliases, which
+//! a join of a table with itself needs. Filters on the join, on views over
+//! it and in `Database::project` use the names the rows have; the parts of
+//! a filter that only read one table are still run on it before the join.
+
+use crate::{Column, Join, JoinType, Table};
+use serde::{Deserialize, Serialize};
+
+/// How the rows of a join name their columns.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JoinColumns {
+    /// Each column under its own name, the target table's winning where
+    /// both tables have one.
+    #[default]
+    Merge,
+    /// Each column as `table.column`.
+    Prefixed,
+    /// Each column as `alias.column`, with the aliases of the table queried
+    /// and of the target table.
+    Aliased(String, String),
+}
+
+impl Join {
+    pub fn new(join_type: JoinType, target_table: &str, on_condition: (&str, &str)) -> Self {
+        Join {
+            join_type,
+            target_table: target_table.to_string(),
+            on_condition: (on_condition.0.to_string(), on_condition.1.to_string()),
+            columns: JoinColumns::Merge,
+        }
+    }
+
+    pub fn prefixed(mut self) -> Self {
+        self.columns = JoinColumns::Prefixed;
+        self
+    }
+
+    pub fn aliased(mut self, table: &str, target_table: &str) -> Self {
+        self.columns = JoinColumns::Aliased(table.to_string(), target_table.to_string());
+        self
+    }
+
+    /// What the columns of `table_name`, the table queried, and of the
+    /// target table are prefixed with, if anything.
+    pub(crate) fn prefixes<'a>(&'a self, table_name: &'a str) -> Option<(&'a str, &'a str)> {
+        match &self.columns {
+            JoinColumns::Merge => None,
+            JoinColumns::Prefixed => Some((table_name, &self.target_table)),
+            JoinColumns::Aliased(table, target) => Some((table, target)),
+        }
+    }
+
+    /// Fails if the columns of the two tables would get the same names.
+    pub(crate) fn check_columns(&self, table_name: &str) -> Result<(), String> {
+        match self.prefixes(table_name) {
+            Some((table, target)) if table == target => {
+                Err(format!("Both sides of the join are named {}; give them aliases", table))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The columns of the joined rows of `left` and `right`, in order. A
+    /// merged column both tables have is listed once, as the left's.
+    pub(crate) fn joined_columns(&self, left: &Table, right: &Table) -> Vec<Column> {
+        joined_columns(left, right, self.prefixes(&left.name).unzip())
+    }
+}
+
+/// `Join::joined_columns` with the given prefixes for each table.
+pub(crate) fn joined_columns(
+    left: &Table,
+    right: &Table,
+    (left_prefix, right_prefix): (Option<&str>, Option<&str>),
+) -> Vec<Column> {
+    let mut columns: Vec<Column> = Vec::new();
+    for (table, prefix) in [(left, left_prefix), (right, right_prefix)] {
+        for column in &table.columns {
+            let name = joined_name(prefix, &column.name);
+            if !columns.iter().any(|c| c.name == name) {
+                columns.push(Column { name, ..column.clone() });
+            }
+        }
+    }
+    columns
+}
+
+/// The name `column` has in a joined row, given its table's prefix.
+pub(crate) fn joined_name(prefix: Option<&str>, column: &str) -> String {
+    match prefix {
+        Some(prefix) => format!("{}.{}", prefix, column),
+        None => column.to_string(),
+    }
+}
@@ -0,0 +1,214 @@
+//! A change sink that produces to a Kafka topic.
+//!
+//! Speaks the Kafka wire protocol directly: each batch is one Produce
+//! request (version 3, record batch format 2) sent with `acks=all`, so it
+//! counts as accepted once every in-sync replica has it. Requests go to the
+//! configured broker, which must lead the target partition; there is no
+//! metadata lookup. Records are keyed by table and row id.
+
+use crate::cdc::{ChangeRecord, ChangeSink, SinkFuture};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const PRODUCE: i16 = 0;
+const PRODUCE_VERSION: i16 = 3;
+const ACKS_ALL: i16 = -1;
+
+pub struct KafkaSink {
+    address: String,
+    topic: String,
+    partition: i32,
+    timeout: Duration,
+    correlation_id: i32,
+    connection: Option<TcpStream>,
+}
+
+impl KafkaSink {
+    /// A sink producing to partition 0 of `topic` on the broker at `address`
+    /// (`host:port`). It connects on first use, and again after an error.
+    pub fn new(address: &str, topic: &str) -> Self {
+        KafkaSink {
+            address: address.to_string(),
+            topic: topic.to_string(),
+            partition: 0,
+            timeout: Duration::from_secs(30),
+            correlation_id: 0,
+            connection: None,
+        }
+    }
+
+    pub fn partition(mut self, partition: i32) -> Self {
+        self.partition = partition;
+        self
+    }
+
+    /// How long the broker may wait for replicas before failing a batch.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn send(&mut self, changes: &[ChangeRecord]) -> Result<(), String> {
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => TcpStream::connect(&self.address)
+                .await
+                .map_err(|e| format!("Failed to connect to Kafka at {}: {}", self.address, e))?,
+        };
+        self.correlation_id = self.correlation_id.wrapping_add(1);
+        let request = self.produce_request(changes);
+        connection.write_all(&request).await.map_err(|e| e.to_string())?;
+
+        let size = connection.read_i32().await.map_err(|e| e.to_string())?;
+        let mut response = vec![0; size.max(0) as usize];
+        connection.read_exact(&mut response).await.map_err(|e| e.to_string())?;
+        self.check_response(&response)?;
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    fn produce_request(&self, changes: &[ChangeRecord]) -> Vec<u8> {
+        let batch = record_batch(changes);
+        let mut body = Vec::new();
+        body.extend_from_slice(&PRODUCE.to_be_bytes());
+        body.extend_from_slice(&PRODUCE_VERSION.to_be_bytes());
+        body.extend_from_slice(&self.correlation_id.to_be_bytes());
+        put_string(&mut body, "zapdb");
+        // No transactional id.
+        body.extend_from_slice(&(-1i16).to_be_bytes());
+        body.extend_from_slice(&ACKS_ALL.to_be_bytes());
+        body.extend_from_slice(&(self.timeout.as_millis().min(i32::MAX as u128) as i32).to_be_bytes());
+        body.extend_from_slice(&1i32.to_be_bytes());
+        put_string(&mut body, &self.topic);
+        body.extend_from_slice(&1i32.to_be_bytes());
+        body.extend_from_slice(&self.partition.to_be_bytes());
+        body.extend_from_slice(&(batch.len() as i32).to_be_bytes());
+        body.extend_from_slice(&batch);
+
+        let mut request = (body.len() as i32).to_be_bytes().to_vec();
+        request.extend_from_slice(&body);
+        request
+    }
+
+    fn check_response(&self, response: &[u8]) -> Result<(), String> {
+        let mut reader = Reader(response);
+        if reader.i32()? != self.correlation_id {
+            return Err("Kafka answered a different request".to_string());
+        }
+        for _ in 0..reader.i32()? {
+            let topic = reader.string()?;
+            for _ in 0..reader.i32()? {
+                let partition = reader.i32()?;
+                let error_code = reader.i16()?;
+                reader.take(16)?;
+                if error_code != 0 {
+                    return Err(format!(
+                        "Kafka rejected the batch for {}/{} with error code {}",
+                        topic, partition, error_code
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ChangeSink for KafkaSink {
+    fn publish<'a>(&'a mut self, changes: &'a [ChangeRecord]) -> SinkFuture<'a> {
+        Box::pin(self.send(changes))
+    }
+}
+
+fn record_batch(changes: &[ChangeRecord]) -> Vec<u8> {
+    let timestamp = changes.first().map_or(0, |c| c.timestamp.timestamp_millis());
+    let mut records = Vec::new();
+    for (offset_delta, change) in changes.iter().enumerate() {
+        let key = format!("{}:{}", change.table, change.row_id);
+        let value = change.to_json().to_string();
+        let mut record = vec![0];
+        put_varint(&mut record, 0);
+        put_varint(&mut record, offset_delta as i64);
+        put_varint(&mut record, key.len() as i64);
+        record.extend_from_slice(key.as_bytes());
+        put_varint(&mut record, value.len() as i64);
+        record.extend_from_slice(value.as_bytes());
+        put_varint(&mut record, 0);
+        put_varint(&mut records, record.len() as i64);
+        records.extend_from_slice(&record);
+    }
+
+    // Everything after the CRC, which covers it.
+    let mut checked = Vec::new();
+    checked.extend_from_slice(&0i16.to_be_bytes());
+    checked.extend_from_slice(&(changes.len() as i32 - 1).to_be_bytes());
+    checked.extend_from_slice(&timestamp.to_be_bytes());
+    checked.extend_from_slice(&timestamp.to_be_bytes());
+    checked.extend_from_slice(&(-1i64).to_be_bytes());
+    checked.extend_from_slice(&(-1i16).to_be_bytes());
+    checked.extend_from_slice(&(-1i32).to_be_bytes());
+    checked.extend_from_slice(&(changes.len() as i32).to_be_bytes());
+    checked.extend_from_slice(&records);
+
+    let mut batch = Vec::new();
+    batch.extend_from_slice(&0i64.to_be_bytes());
+    // The batch length counts from the leader epoch on.
+    batch.extend_from_slice(&((4 + 1 + 4 + checked.len()) as i32).to_be_bytes());
+    batch.extend_from_slice(&(-1i32).to_be_bytes());
+    batch.push(2);
+    batch.extend_from_slice(&crc32c(&checked).to_be_bytes());
+    batch.extend_from_slice(&checked);
+    batch
+}
+
+fn put_string(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend_from_slice(&(s.len() as i16).to_be_bytes());
+    buffer.extend_from_slice(s.as_bytes());
+}
+
+// A zigzag-encoded variable-length integer, as record fields use.
+fn put_varint(buffer: &mut Vec<u8>, value: i64) {
+    let mut v = ((value << 1) ^ (value >> 63)) as u64;
+    while v >= 0x80 {
+        buffer.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buffer.push(v as u8);
+}
+
+fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0x82F6_3B78 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err("Truncated response from Kafka".to_string());
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn i16(&mut self) -> Result<i16, String> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.i16()?.max(0) as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+}
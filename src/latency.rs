@@ -0,0 +1,184 @@
+//! Latency histograms per table and kind of operation.
+//!
+//! Every select, insert, update and delete that succeeds is timed and
+//! counted in a histogram for its table and kind, from the call to its
+//! return, so time spent waiting for admission or for the table's lock is
+//! included. Recording is a few relaxed atomic adds; the histograms of a
+//! table are made by its first timed operation.
+//!
+//! Buckets are logarithmic, four to each power of two nanoseconds, so a
+//! percentile is within about 12% of the true latency. Throughput counts
+//! from a histogram's first operation, or from `reset_latency_stats`.
+
+use crate::{Database, Instant};
+use dashmap::DashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const BUCKETS: usize = 252;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum OperationKind {
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+const KINDS: [OperationKind; 4] = [
+    OperationKind::Select,
+    OperationKind::Insert,
+    OperationKind::Update,
+    OperationKind::Delete,
+];
+
+/// The latencies of one kind of operation on one table, from
+/// `Database::latency_stats`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperationLatency {
+    pub table: String,
+    pub kind: OperationKind,
+    pub count: u64,
+    /// Operations per second since the first one was timed.
+    pub throughput: f64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+struct Histogram {
+    started: Instant,
+    buckets: Vec<AtomicU64>,
+    max_nanos: AtomicU64,
+}
+
+// The bucket holding `nanos`: below 4 one each, then four to each power of
+// two.
+fn bucket(nanos: u64) -> usize {
+    if nanos < 4 {
+        return nanos as usize;
+    }
+    let exp = 63 - nanos.leading_zeros() as usize;
+    (exp - 1) * 4 + ((nanos >> (exp - 2)) & 3) as usize
+}
+
+// The middle of the range `bucket` holds.
+fn bucket_middle(bucket: usize) -> u64 {
+    if bucket < 4 {
+        return bucket as u64;
+    }
+    let shift = bucket / 4 - 1;
+    let lower = (4 + bucket as u64 % 4) << shift;
+    lower + (1 << shift) / 2
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram {
+            started: Instant::now(),
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, elapsed: Duration) {
+        let nanos = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[bucket(nanos)].fetch_add(1, Ordering::Relaxed);
+        self.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+
+    fn stats(&self, table: &str, kind: OperationKind) -> Option<OperationLatency> {
+        let counts: Vec<u64> = self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).collect();
+        let count: u64 = counts.iter().sum();
+        if count == 0 {
+            return None;
+        }
+        let max = self.max_nanos.load(Ordering::Relaxed);
+        let percentile = |q: f64| {
+            let rank = ((count as f64 * q).ceil() as u64).max(1);
+            let mut seen = 0;
+            for (i, n) in counts.iter().enumerate() {
+                seen += n;
+                if seen >= rank {
+                    return Duration::from_nanos(bucket_middle(i).min(max));
+                }
+            }
+            Duration::from_nanos(max)
+        };
+        Some(OperationLatency {
+            table: table.to_string(),
+            kind,
+            count,
+            throughput: count as f64 / self.started.elapsed().as_secs_f64(),
+            p50: percentile(0.50),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: Duration::from_nanos(max),
+        })
+    }
+}
+
+/// A histogram for each kind of operation, by table.
+#[derive(Default)]
+pub(crate) struct Latencies {
+    tables: DashMap<String, Arc<[Histogram; 4]>>,
+}
+
+impl Latencies {
+    fn record(&self, table_name: &str, kind: OperationKind, elapsed: Duration) {
+        let histograms = match self.tables.get(table_name) {
+            Some(histograms) => Arc::clone(&histograms),
+            None => Arc::clone(
+                &self
+                    .tables
+                    .entry(table_name.to_string())
+                    .or_insert_with(|| Arc::new(KINDS.map(|_| Histogram::new()))),
+            ),
+        };
+        histograms[kind as usize].record(elapsed);
+    }
+}
+
+impl Database {
+    /// Runs `operation` on `table_name`, timing it if it succeeds.
+    pub(crate) async fn measured<T, E>(
+        &self,
+        table_name: &str,
+        kind: OperationKind,
+        operation: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let start = Instant::now();
+        let result = operation.await;
+        if result.is_ok() {
+            self.latencies.record(table_name, kind, start.elapsed());
+        }
+        result
+    }
+
+    /// The latencies of every kind of operation timed on each table, by
+    /// table then kind; see the module docs.
+    pub fn latency_stats(&self) -> Vec<OperationLatency> {
+        let mut stats: Vec<OperationLatency> = self
+            .latencies
+            .tables
+            .iter()
+            .flat_map(|entry| {
+                let (table, histograms) = entry.pair();
+                KINDS
+                    .iter()
+                    .filter_map(|&kind| histograms[kind as usize].stats(table, kind))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        stats.sort_by(|a, b| (&a.table, a.kind).cmp(&(&b.table, b.kind)));
+        stats
+    }
+
+    /// Forgets every operation timed so far.
+    pub fn reset_latency_stats(&self) {
+        self.latencies.tables.clear();
+    }
+}
@@ -1,35 +1,210 @@
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
-use std::time::{Instant, Duration};
-use std::fs::File;
-use std::io::{self, Write, Read, BufWriter};
+use std::time::Duration;
+// std's clock panics on wasm32, where time comes from JavaScript.
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+use std::io;
 use serde::{Serialize, Deserialize};
-use std::error::Error;
 use std::fmt;
-use flate2::write::GzEncoder;
-use flate2::read::GzDecoder;
-use flate2::Compression;
 use tokio::sync::RwLock;
-use std::sync::Arc;
-use aes_gcm::{
-    aead::{Aead, KeyInit},
-    Aes256Gcm, Nonce,
-};
-use crate::optimizer::QueryPlanner;
-
+use tokio::task::{AbortHandle, JoinHandle};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::admission::Admission;
+use crate::cdc::Sinks;
+use crate::functions::Functions;
+use crate::group_by::aggregate_values;
+use crate::history::History;
+use crate::join_columns::{joined_columns, joined_name};
+use crate::latency::Latencies;
+use crate::lock::LockFile;
+use crate::materialized::MaterializedViews;
+use crate::memory::MemoryTracker;
+use crate::optimizer::{JoinStrategy, OrStrategy, QueryPlanner};
+use crate::partitions::Partitions;
+use crate::rollups::Rollups;
+use crate::scheduler::Scheduler;
+use crate::sequences::Sequences;
+use crate::snapshot::Snapshot;
+use crate::tenants::Tenants;
+use crate::text_index::TrigramIndex;
+use crate::undo::UndoLog;
+use crate::update_expr::RowUpdate;
+use crate::views::NamedViews;
+use crate::watch::{Changes, Watchers};
+use arc_swap::ArcSwap;
+
+mod admission;
+mod batch;
+mod bundle;
+mod cdc;
+mod codec;
+mod coercion;
+mod column_index;
+mod compression;
+mod config;
+mod conflict;
+mod dependencies;
+mod dictionary;
+mod diff;
+mod estimate;
+mod explain;
+mod fixtures;
+mod foreign_keys;
+mod fork;
+mod functions;
+mod group_by;
+mod history;
+mod hll;
+mod import;
+mod join_chain;
+mod join_columns;
+mod latency;
+mod limits;
+mod lock;
+mod materialized;
+mod memory;
 mod optimizer;
-
+mod pagination;
+mod partitions;
+mod permissions;
+mod pool;
+mod prepared;
+mod protocol;
+mod query_builder;
+mod query_macro;
+mod recovery;
+mod replication;
+mod row;
+mod rollups;
+mod row_ids;
+mod sample;
+mod savepoint;
+mod scheduler;
+mod schema;
+mod schema_file;
+mod sealed;
+mod select_options;
+mod sequences;
+mod session;
+mod snapshot;
+mod snapshot_compression;
+mod snapshot_roots;
+mod sort_merge;
+mod sql;
+mod stats;
+mod storage;
+mod subquery;
+mod tenants;
+mod text_index;
+mod time_series;
+mod timeout;
+mod top_k;
+mod traverse;
+mod typed;
+mod undo;
+mod update_expr;
+mod verify;
+mod views;
+mod wal_archive;
+mod watch;
+
+pub use admission::Busy;
+pub use batch::WriteBatch;
+pub use bundle::{BundleManifest, BundledTable};
+pub use cdc::{ChangeRecord, ChangeSink, SinkFuture, SinkOptions, SinkStatus, CHECKPOINT_TABLE};
+pub use column_index::IndexKind;
+pub use config::{
+    AdmissionConfig, AutoAnalyze, Cipher, Codec, Coercion, DatabaseConfig, ResultLimits, RolePermissions,
+    SnapshotCompression, TableLimits, UnknownColumns,
+};
+pub use conflict::{InsertOutcome, OnConflict};
+pub use dependencies::{Dependent, OnDependents};
+pub use diff::{SnapshotDiff, TableDiff};
+pub use estimate::Estimate;
+pub use explain::{JoinExplanation, JoinMethod};
+pub use foreign_keys::IndexInfo;
+pub use functions::{Comparison, Expr};
+pub use history::{HistoryEntry, HistoryOperation};
+pub use import::{ImportOptions, ImportProgress, ImportReport, OnRowError, RowError};
+pub use join_chain::JoinChain;
+pub use join_columns::JoinColumns;
+pub use latency::{OperationKind, OperationLatency};
+pub use limits::{ConstraintError, LimitError, ResultLimitError, SelectError, WriteError};
+pub use lock::OpenError;
+pub use memory::MemoryStats;
+pub use pagination::Page;
+pub use partitions::{Partition, Partitioning, Period};
+pub use pool::{
+    create_pool, create_pool_with_config, create_pool_with_options, create_pool_with_session, ConnectionError,
+    DbConnectionManager, Pool, PoolConfig, PoolMetrics, PooledConnection,
+};
+pub use prepared::PreparedQuery;
+pub use protocol::Params;
+pub use query_builder::{col, ColumnRef, QueryBuilder};
+pub use recovery::{RecoveryReport, Skipped};
+pub use replication::Replication;
+pub use rollups::{GroupBy, Measure, Rollup};
+pub use row::{FromValue, Row, RowSchema};
+pub use row_ids::RowId;
+pub use sample::{Sample, SampleSize};
+pub use scheduler::{CronSchedule, Job, JobStatus, Schedule};
+pub use schema_file::{SchemaChange, SchemaDefinition, TableDefinition};
+pub use select_options::SelectOptions;
+pub use session::SessionContext;
+pub use snapshot_roots::{SnapshotError, TableRoots};
+pub use sql::SqlOutput;
+pub use stats::{ColumnStats, TableStats};
+pub use storage::{FileStorage, MemoryStorage, StorageBackend};
+pub use tenants::{Tenant, TenantConfig};
+pub use time_series::{time_bucket, Fill, TimeSeries};
+pub use timeout::Timeout;
+pub use top_k::SortOrder;
+pub use traverse::Traverse;
+pub use typed::{from_row, row_to_json, to_row};
+pub use update_expr::UpdateExpr;
+pub use views::View;
+pub use wal_archive::WalRebuildReport;
+pub use watch::{WatchEvent, Watcher};
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(target_arch = "wasm32")]
+mod indexeddb;
+#[cfg(target_arch = "wasm32")]
+pub use indexeddb::IndexedDbStorage;
+#[cfg(any(feature = "datafusion", feature = "server"))]
+mod record_batch;
+#[cfg(feature = "datafusion")]
+mod table_provider;
+#[cfg(feature = "datafusion")]
+pub use table_provider::ZapTable;
+#[cfg(feature = "server")]
+mod flight;
+#[cfg(feature = "server")]
+pub use flight::ZapFlightService;
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "kafka")]
+pub use kafka::KafkaSink;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "nats")]
+pub use nats::NatsSink;
 #[cfg(feature = "sharding")]
 pub mod network;
 #[cfg(feature = "sharding")]
 pub mod sharding;
 
 
-use rand::{rngs::OsRng, RngCore};
-use rs_merkle::{MerkleTree, Hasher as MerkleHasher};
+use rs_merkle::Hasher as MerkleHasher;
+use imbl::Vector;
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
-use serde_json;
 
 #[derive(Clone)]
 struct Blake3Hasher;
@@ -81,6 +256,24 @@ pub enum DataType {
     Json,
 }
 
+impl std::str::FromStr for DataType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "integer" | "int" | "bigint" => Ok(DataType::Integer),
+            "string" | "text" | "varchar" => Ok(DataType::String),
+            "float" | "real" | "double" => Ok(DataType::Float),
+            "boolean" | "bool" => Ok(DataType::Boolean),
+            "datetime" | "timestamp" => Ok(DataType::DateTime),
+            "uuid" => Ok(DataType::Uuid),
+            "json" => Ok(DataType::Json),
+            other => Err(format!("Unknown data type {}", other)),
+        }
+    }
+}
+
+use column_index::{is_bound, ColumnIndex};
 use dashmap::DashMap;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -98,6 +291,44 @@ pub enum Operation {
         table_name: String,
         query: Query,
     },
+    InsertOnConflict {
+        table_name: String,
+        row: HashMap<String, Value>,
+        on_conflict: OnConflict,
+    },
+}
+
+impl Operation {
+    fn table_name(&self) -> &str {
+        match self {
+            Operation::Insert { table_name, .. }
+            | Operation::Update { table_name, .. }
+            | Operation::Delete { table_name, .. }
+            | Operation::InsertOnConflict { table_name, .. } => table_name,
+        }
+    }
+
+    fn wal_entry(&self) -> WalEntry {
+        match self {
+            Operation::Insert { table_name, row } => WalEntry::Insert {
+                table_name: table_name.clone(),
+                row: row.clone(),
+            },
+            Operation::Update { table_name, query } => WalEntry::Update {
+                table_name: table_name.clone(),
+                query: query.clone(),
+            },
+            Operation::Delete { table_name, query } => WalEntry::Delete {
+                table_name: table_name.clone(),
+                query: query.clone(),
+            },
+            Operation::InsertOnConflict { table_name, row, on_conflict } => WalEntry::InsertOnConflict {
+                table_name: table_name.clone(),
+                row: row.clone(),
+                on_conflict: *on_conflict,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -119,11 +350,75 @@ pub enum WalEntry {
         table_name: String,
         query: Query,
     },
+    CreateView {
+        name: String,
+        table: String,
+        query: Query,
+    },
+    DropView {
+        name: String,
+    },
+    /// An exact row replacement written by `undo` and `redo`.
+    Replace {
+        table_name: String,
+        old: Option<HashMap<String, Value>>,
+        new: Option<HashMap<String, Value>>,
+    },
+    CreateSequence {
+        name: String,
+        start: i64,
+        increment: i64,
+    },
+    DropSequence {
+        name: String,
+    },
+    /// A value handed out by `next_val`.
+    SequenceValue {
+        name: String,
+        value: i64,
+    },
+    UpdateExpr {
+        table_name: String,
+        query: Query,
+        expr: UpdateExpr,
+    },
+    PartitionTable {
+        table_name: String,
+        partitioning: Partitioning,
+    },
+    DropPartition {
+        table_name: String,
+        key: Value,
+    },
+    InsertOnConflict {
+        table_name: String,
+        row: HashMap<String, Value>,
+        on_conflict: OnConflict,
+    },
+    DropTable {
+        name: String,
+        on_dependents: OnDependents,
+    },
+    RenameTable {
+        name: String,
+        new_name: String,
+        on_dependents: OnDependents,
+    },
+    CreateTenant {
+        name: String,
+        config: TenantConfig,
+    },
+    SuspendTenant {
+        name: String,
+        suspended: bool,
+    },
 }
 
-#[derive(Clone)]
+pub type UpdateFn = fn(&mut HashMap<String, Value>);
+
+#[derive(Clone, Default)]
 pub struct Transaction {
-    operations: Vec<(Operation, Option<fn(&mut HashMap<String, Value>)>)>,
+    operations: Vec<(Operation, Option<UpdateFn>)>,
 }
 
 impl Transaction {
@@ -142,7 +437,7 @@ impl Transaction {
         &mut self,
         table_name: String,
         query: Query,
-        update_fn: fn(&mut HashMap<String, Value>),
+        update_fn: UpdateFn,
     ) {
         self.operations.push((
             Operation::Update {
@@ -157,41 +452,241 @@ impl Transaction {
         self.operations
             .push((Operation::Delete { table_name, query }, None));
     }
+
+    pub fn insert_on_conflict(&mut self, table_name: String, row: HashMap<String, Value>, on_conflict: OnConflict) {
+        self.operations
+            .push((Operation::InsertOnConflict { table_name, row, on_conflict }, None));
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Table {
     name: String,
     columns: Vec<Column>,
-    data: Vec<HashMap<String, Value>>,
+    data: Vector<Arc<Row>>,
+    // The id of each row in `data`, in ascending order.
+    #[serde(skip)]
+    row_ids: Vector<RowId>,
+    #[serde(skip)]
+    next_row_id: RowId,
     #[serde(skip)]
-    indexes: HashMap<String, DashMap<Value, Vec<usize>>>,
+    schema: Arc<RowSchema>,
     #[serde(skip)]
-    merkle_tree: Option<MerkleTree<Blake3Hasher>>,
+    // Row ids by value.
+    indexes: HashMap<String, ColumnIndex>,
+    // The Merkle tree's leaves, one for each row in `data`. Writes update
+    // the leaves of the rows they change; the tree above them is only built
+    // when its root is asked for.
+    #[serde(skip)]
+    merkle_leaves: Vector<[u8; 32]>,
+    #[serde(skip)]
+    ephemeral: bool,
+    // Set when the indexes or Merkle leaves no longer reflect `data` and are
+    // left to be rebuilt: the leaves of a new table, and while maintenance is
+    // deferred, the indexes after every row was replaced at once.
+    #[serde(skip)]
+    indexes_stale: bool,
+    #[serde(skip, default = "stale")]
+    merkle_stale: bool,
+    #[serde(skip)]
+    data_bytes: usize,
+    #[serde(skip)]
+    index_sizes: HashMap<String, usize>,
+    // Logical time each index last answered a query, for eviction.
+    #[serde(skip)]
+    index_last_used: DashMap<String, u64>,
+    #[serde(skip)]
+    stats: Option<Arc<TableStats>>,
+    // Rows changed since `stats` was collected, for `auto_analyze`.
+    #[serde(skip)]
+    changed_since_analyze: usize,
+    // Changes with the indexes and statistics, for the plan cache.
+    #[serde(skip, default = "optimizer::next_epoch")]
+    plan_epoch: u64,
+    #[serde(skip)]
+    partitions: Option<Partitions>,
+    #[serde(skip)]
+    text_indexes: HashMap<String, TrigramIndex>,
+}
+
+fn stale() -> bool {
+    true
 }
 
 impl Table {
-    fn build_merkle_tree(&mut self) {
-        let mut leaves = Vec::new();
-        for row in &self.data {
-            let encoded_row = bincode::serialize(&row).unwrap();
-            leaves.push(Blake3Hasher::hash(&encoded_row));
+    fn new(name: String, columns: Vec<Column>, ephemeral: bool) -> Self {
+        Table {
+            name,
+            schema: Arc::new(RowSchema::from_columns(&columns)),
+            columns,
+            data: Vector::new(),
+            row_ids: Vector::new(),
+            next_row_id: 0,
+            indexes: HashMap::new(),
+            merkle_leaves: Vector::new(),
+            ephemeral,
+            indexes_stale: false,
+            merkle_stale: true,
+            data_bytes: 0,
+            index_sizes: HashMap::new(),
+            index_last_used: DashMap::new(),
+            stats: None,
+            changed_since_analyze: 0,
+            plan_epoch: optimizer::next_epoch(),
+            partitions: None,
+            text_indexes: HashMap::new(),
         }
-        self.merkle_tree = Some(MerkleTree::<Blake3Hasher>::from_leaves(&leaves));
     }
 
-    pub fn verify_integrity(&self) -> bool {
-        if let Some(tree) = &self.merkle_tree {
-            let mut leaves = Vec::new();
-            for row in &self.data {
-                let encoded_row = bincode::serialize(&row).unwrap();
-                leaves.push(Blake3Hasher::hash(&encoded_row));
+    // Deserialized rows each carry their own schema; re-lay them out against
+    // the table's shared one.
+    fn adopt_schema(&mut self) {
+        self.schema = Arc::new(RowSchema::from_columns(&self.columns));
+        for row in &mut self.data {
+            *row = Arc::new(row.conform(&self.schema));
+        }
+        self.data_bytes = self.data.iter().map(|r| r.estimated_size()).sum();
+    }
+
+    fn build_index(&mut self, column: &str, kind: IndexKind) {
+        if self.indexes.get(column).map(|index| index.kind()) != Some(kind) {
+            self.plan_epoch = optimizer::next_epoch();
+        }
+        let mut index = ColumnIndex::new(kind);
+        let mut size = 0;
+        for (row, id) in self.data.iter().zip(&self.row_ids) {
+            if let Some(value) = row.get(column) {
+                size += memory::index_entry_size(value);
+                index.insert(value.clone(), *id);
             }
-            let new_tree = MerkleTree::<Blake3Hasher>::from_leaves(&leaves);
-            tree.root() == new_tree.root()
-        } else {
-            true
         }
+        self.indexes.insert(column.to_string(), index);
+        self.index_sizes.insert(column.to_string(), size);
+    }
+
+    // Returns the estimated bytes freed.
+    fn drop_index(&mut self, column: &str) -> usize {
+        self.indexes.remove(column);
+        self.plan_epoch = optimizer::next_epoch();
+        self.index_last_used.remove(column);
+        self.index_sizes.remove(column).unwrap_or(0)
+    }
+
+    // Re-lays every row out against a schema with a fresh dictionary for
+    // `column`. Row positions are unchanged, so indexes stay valid.
+    fn encode_column(&mut self, column: &str) {
+        self.schema = Arc::new(self.schema.with_dictionary(column));
+        for row in &mut self.data {
+            *row = Arc::new(row.conform(&self.schema));
+        }
+        self.data_bytes = self.data.iter().map(|r| r.estimated_size()).sum();
+    }
+
+    fn dictionary_bytes(&self) -> usize {
+        self.schema.dictionary_bytes()
+    }
+
+    fn index_bytes(&self) -> usize {
+        self.index_sizes.values().sum::<usize>() + self.text_index_bytes()
+    }
+
+    fn index_last_used(&self, column: &str) -> u64 {
+        self.index_last_used.get(column).map_or(0, |t| *t)
+    }
+
+    fn rebuild_indexes(&mut self) {
+        let columns: Vec<(String, IndexKind)> = self.indexes.iter().map(|(c, i)| (c.clone(), i.kind())).collect();
+        for (column, kind) in columns {
+            self.build_index(&column, kind);
+        }
+        let columns: Vec<String> = self.text_indexes.keys().cloned().collect();
+        for column in columns {
+            self.build_text_index(&column);
+        }
+        if self.indexes_stale {
+            self.plan_epoch = optimizer::next_epoch();
+        }
+        self.indexes_stale = false;
+    }
+
+    // Brings the Merkle leaves, and indexes marked stale, up to date after a
+    // write, unless maintenance is deferred, in which case the work is left
+    // for `finish_maintenance`. Writes keep the indexes and the leaves of the
+    // rows they change up to date themselves.
+    fn maintain(&mut self, deferred: bool) {
+        if deferred {
+            self.merkle_stale = true;
+            return;
+        }
+        self.finish_maintenance();
+    }
+
+    fn finish_maintenance(&mut self) {
+        if self.indexes_stale {
+            self.rebuild_indexes();
+        }
+        if self.merkle_stale {
+            self.build_merkle_leaves();
+        }
+    }
+
+    fn build_merkle_leaves(&mut self) {
+        self.merkle_leaves = self.fresh_merkle_leaves().into();
+        self.merkle_stale = false;
+    }
+
+    // Leaves hash each row's id and a fixed encoding of the row, so they
+    // don't depend on the configured codec.
+    fn merkle_leaf(id: RowId, row: &Row) -> [u8; 32] {
+        let mut leaf = id.to_le_bytes().to_vec();
+        // Encoding reads every value, and a compressed value read in a stored
+        // row stays inflated; a copy inflates its own.
+        let encoded = match row.has_compressed() {
+            true => Codec::Bincode.encode(&row.clone()),
+            false => Codec::Bincode.encode(row),
+        };
+        leaf.extend(encoded.unwrap());
+        Blake3Hasher::hash(&leaf)
+    }
+
+    // The leaves for the rows as they are, whatever writes left behind.
+    fn fresh_merkle_leaves(&self) -> Vec<[u8; 32]> {
+        self.data
+            .iter()
+            .zip(&self.row_ids)
+            .map(|(row, id)| Table::merkle_leaf(*id, row))
+            .collect()
+    }
+
+    /// The first Unique column identifies a row for `Database::get`, falling back
+    /// to the first declared column when no column is unique.
+    pub fn primary_key(&self) -> Option<&Column> {
+        self.columns
+            .iter()
+            .find(|c| c.constraints.contains(&Constraint::Unique))
+            .or_else(|| self.columns.first())
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    pub fn row_count(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_ephemeral(&self) -> bool {
+        self.ephemeral
+    }
+
+    /// Whether the rows still hash to the Merkle leaves writes left, when
+    /// the leaves are up to date.
+    pub fn verify_integrity(&self) -> bool {
+        self.merkle_stale || self.merkle_leaves.iter().eq(self.fresh_merkle_leaves().iter())
     }
 }
 
@@ -206,6 +701,23 @@ pub enum Value {
     Uuid(Uuid),
     Json(serde_json::Value),
     Null,
+    Param(usize),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::DateTime(dt) => write!(f, "{}", dt.to_rfc3339()),
+            Value::Uuid(u) => write!(f, "{}", u),
+            Value::Json(j) => write!(f, "{}", j),
+            Value::Null => write!(f, "NULL"),
+            Value::Param(i) => write!(f, "${}", i),
+        }
+    }
 }
 
 impl PartialEq for Value {
@@ -219,11 +731,13 @@ impl PartialEq for Value {
             (Value::Uuid(a), Value::Uuid(b)) => a == b,
             (Value::Json(a), Value::Json(b)) => a == b,
             (Value::Null, Value::Null) => true,
+            (Value::Param(a), Value::Param(b)) => a == b,
             _ => false,
         }
     }
 }
 
+#[allow(clippy::non_canonical_partial_ord_impl)]
 impl PartialOrd for Value {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         match (self, other) {
@@ -233,11 +747,59 @@ impl PartialOrd for Value {
             (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
             (Value::DateTime(a), Value::DateTime(b)) => a.partial_cmp(b),
             (Value::Uuid(a), Value::Uuid(b)) => a.partial_cmp(b),
+            (Value::Integer(a), Value::Float(b)) => compare_int_float(*a, *b),
+            (Value::Float(a), Value::Integer(b)) => compare_int_float(*b, *a).map(|o| o.reverse()),
             _ => None,
         }
     }
 }
 
+// Compares without converting `i` to f64, which would round large integers.
+fn compare_int_float(i: i64, f: f64) -> Option<std::cmp::Ordering> {
+    use std::cmp::Ordering;
+    if f.is_nan() {
+        return None;
+    }
+    // i64::MIN is -2^63 and i64::MAX rounds up to 2^63 as a float.
+    if f >= i64::MAX as f64 {
+        return Some(Ordering::Less);
+    }
+    if f < i64::MIN as f64 {
+        return Some(Ordering::Greater);
+    }
+    let whole = f.floor() as i64;
+    match i.cmp(&whole) {
+        Ordering::Equal if f > whole as f64 => Some(Ordering::Less),
+        ordering => Some(ordering),
+    }
+}
+
+impl Value {
+    /// Equality as used by query conditions: like `==`, except that integers
+    /// and floats compare by numeric value.
+    pub(crate) fn matches(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Integer(_), Value::Float(_)) | (Value::Float(_), Value::Integer(_)) => {
+                self.partial_cmp(other) == Some(std::cmp::Ordering::Equal)
+            }
+            _ => self == other,
+        }
+    }
+
+    // The index keys that may hold values matching `self`: itself, plus the
+    // equal value of the other numeric type.
+    fn index_keys(&self) -> Vec<Value> {
+        let twin = match self {
+            Value::Integer(i) if (*i as f64) as i128 == *i as i128 => Some(Value::Float(*i as f64)),
+            Value::Float(f) if f.fract() == 0.0 && self.matches(&Value::Integer(*f as i64)) => {
+                Some(Value::Integer(*f as i64))
+            }
+            _ => None,
+        };
+        std::iter::once(self.clone()).chain(twin).collect()
+    }
+}
+
 impl Ord for Value {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.partial_cmp(other).unwrap_or(std::cmp::Ordering::Equal)
@@ -245,6 +807,10 @@ impl Ord for Value {
 }
 
 
+/// Integers and floats compare by numeric value, exactly, so `Integer(5)`
+/// equals `Float(5.0)` and `Integer(30)` is greater than `Float(29.5)`. NaN is
+/// neither equal to, less than nor greater than any integer. Other values only
+/// compare with values of the same type.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Operator {
     Eq,
@@ -253,6 +819,47 @@ pub enum Operator {
     Gte,
     Lt,
     Lte,
+    /// Matches a string against a SQL LIKE pattern, ignoring case: `%` matches
+    /// any run of characters, `_` any one, and `\` escapes the next.
+    ILike,
+    /// Like `ILike`, but minding case.
+    Like,
+    /// Matches a string that `Like` doesn't. Values that aren't strings
+    /// match neither.
+    NotLike,
+    /// Matches a string containing a match of a regular expression, in the
+    /// syntax of the `regex` crate. Anchor it with `^` and `$` to match the
+    /// whole string.
+    Regex,
+}
+
+impl Operator {
+    pub(crate) fn apply(&self, left: &Value, right: &Value) -> bool {
+        match self {
+            Operator::Eq => left.matches(right),
+            Operator::NotEq => !left.matches(right),
+            Operator::Gt => left > right,
+            Operator::Gte => left >= right,
+            Operator::Lt => left < right,
+            Operator::Lte => left <= right,
+            Operator::ILike => match (left, right) {
+                (Value::String(text), Value::String(pattern)) => text_index::ilike(pattern, text),
+                _ => false,
+            },
+            Operator::Like => match (left, right) {
+                (Value::String(text), Value::String(pattern)) => text_index::like(pattern, text),
+                _ => false,
+            },
+            Operator::NotLike => match (left, right) {
+                (Value::String(text), Value::String(pattern)) => !text_index::like(pattern, text),
+                _ => false,
+            },
+            Operator::Regex => match (left, right) {
+                (Value::String(text), Value::String(pattern)) => text_index::regex_matches(pattern, text),
+                _ => false,
+            },
+        }
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -269,11 +876,16 @@ pub enum JoinType {
     Right,
 }
 
+/// A join with another table. Build one with `Join::new`, as joins may gain
+/// options.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct Join {
     pub join_type: JoinType,
     pub target_table: String,
     pub on_condition: (String, String),
+    #[serde(default)]
+    pub columns: JoinColumns,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -283,13 +895,26 @@ pub enum AggregateFunction {
     Avg,
     Min,
     Max,
+    /// The number of different non-null values.
+    CountDistinct,
+    /// `CountDistinct` estimated with HyperLogLog, in constant memory, to
+    /// within about 1%.
+    ApproxCountDistinct,
 }
 
+/// An aggregate over a table's rows. Build one with `AggregateQuery::new`,
+/// as joins are built with `Join::new`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[non_exhaustive]
 pub struct AggregateQuery {
     pub function: AggregateFunction,
+    /// The column aggregated, or `*` to `Count` rows.
     pub column: String,
     pub filter: Option<Box<Query>>,
+    /// Columns whose combinations of values are aggregated separately, one
+    /// row each.
+    #[serde(default)]
+    pub group_by: Vec<String>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -300,6 +925,20 @@ pub enum Query {
     Or(Vec<Query>),
     Join(Join),
     Aggregate(AggregateQuery),
+    /// Compares expressions, which may call registered functions.
+    Compare(Comparison),
+    /// Rows reachable from a starting set, with their depth.
+    Traverse(Traverse),
+    /// A reproducible random subset of the rows matching a query.
+    Sample(Sample),
+    /// Rows whose `column` equals a value of `select_column` in the rows of
+    /// `table` matching `subquery`.
+    InSubquery {
+        column: String,
+        table: String,
+        subquery: Box<Query>,
+        select_column: String,
+    },
 }
 
 impl Eq for Value {}
@@ -321,28 +960,122 @@ impl Hash for Value {
                 s.hash(state);
             }
             Value::Null => 0.hash(state),
+            Value::Param(i) => i.hash(state),
         }
     }
 }
 
 pub struct WalWriter {
-    writer: BufWriter<File>,
+    storage: Arc<dyn StorageBackend>,
+    path: String,
+    // The codec of the WAL as it is, and the one it is rewritten with.
+    codec: Codec,
+    new_codec: Codec,
+    replicas: Vec<replication::Subscriber>,
 }
 
 impl WalWriter {
     pub fn new(path: &str) -> io::Result<Self> {
-        let file = File::options().append(true).create(true).open(path)?;
+        Self::with_storage(Arc::new(FileStorage::new()), path, Codec::default())
+    }
+
+    /// Opens the WAL at `path`, appending in the codec it was written with,
+    /// or starting it with `codec` if it is empty.
+    pub fn with_storage(storage: Arc<dyn StorageBackend>, path: &str, codec: Codec) -> io::Result<Self> {
+        let existing = match storage.read(path)? {
+            Some(bytes) if !bytes.is_empty() => Some(Codec::read_header(&mut &bytes[..])?.unwrap_or_default()),
+            _ => None,
+        };
+        if existing.is_none() {
+            storage.append(path, &codec.header())?;
+        }
         Ok(Self {
-            writer: BufWriter::new(file),
+            storage,
+            path: path.to_string(),
+            codec: existing.unwrap_or(codec),
+            new_codec: codec,
+            replicas: Vec::new(),
         })
     }
 
     pub fn log(&mut self, entry: &WalEntry) -> io::Result<()> {
-        let encoded: Vec<u8> = bincode::serialize(entry).unwrap();
-        self.writer.write_all(&encoded)?;
-        self.writer.flush()?;
+        let encoded = self.codec.encode(entry)?;
+        self.storage.append(&self.path, &encoded)?;
+        self.forward([entry]);
         Ok(())
     }
+
+    /// Logs `entries` with a single append.
+    pub fn log_all<'a>(&mut self, entries: impl IntoIterator<Item = &'a WalEntry>) -> io::Result<()> {
+        let entries: Vec<&WalEntry> = entries.into_iter().collect();
+        let mut encoded = Vec::new();
+        for entry in &entries {
+            encoded.extend(self.codec.encode(entry)?);
+        }
+        self.storage.append(&self.path, &encoded)?;
+        self.forward(entries);
+        Ok(())
+    }
+
+    /// Empties the WAL, switching it to the configured codec.
+    pub fn truncate(&mut self) -> io::Result<()> {
+        self.storage.write(&self.path, &self.new_codec.header())?;
+        self.codec = self.new_codec;
+        Ok(())
+    }
+
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.storage.sync(&self.path)
+    }
+
+    /// `sync`, run on the blocking thread pool.
+    pub(crate) async fn sync_unblocked(&mut self) -> io::Result<()> {
+        let path = self.path.clone();
+        storage::unblock(&self.storage, move |storage| storage.sync(&path)).await
+    }
+}
+
+/// The entries of a WAL with their byte offsets, up to and including the
+/// first that can't be decoded.
+pub(crate) struct WalEntries<'a> {
+    wal: &'a [u8],
+    rest: &'a [u8],
+    codec: Codec,
+}
+
+impl<'a> WalEntries<'a> {
+    pub(crate) fn new(wal: &'a [u8]) -> Self {
+        WalEntries {
+            wal,
+            rest: wal,
+            codec: Codec::default(),
+        }
+    }
+}
+
+impl Iterator for WalEntries<'_> {
+    type Item = (usize, io::Result<WalEntry>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.rest.is_empty() {
+            let offset = self.wal.len() - self.rest.len();
+            // Databases opening an empty WAL at the same time each write a
+            // header, so one can appear between entries.
+            let entry = match Codec::read_header(&mut self.rest) {
+                Ok(Some(codec)) => {
+                    self.codec = codec;
+                    continue;
+                }
+                Ok(None) => self.codec.decode(&mut self.rest),
+                Err(e) => Err(e),
+            };
+            if entry.is_err() {
+                self.rest = &[];
+            }
+            return Some((offset, entry));
+        }
+        None
+    }
 }
 
 #[cfg(feature = "sharding")]
@@ -351,29 +1084,146 @@ use crate::network::NetworkManager;
 use crate::sharding::ShardManager;
 
 pub struct Database {
+    /// The authoritative tables, locked by writers. Queries read published
+    /// snapshots instead, so changes made directly through this lock are only
+    /// visible to them after the next write to the same table.
     pub tables: Arc<RwLock<HashMap<String, Table>>>,
+    snapshot: ArcSwap<Snapshot>,
     key: [u8; 32],
     wal_writer: Arc<RwLock<WalWriter>>,
     wal_path: String,
+    storage: Arc<dyn StorageBackend>,
+    config: DatabaseConfig,
     query_planner: QueryPlanner,
+    closed: AtomicBool,
+    background_tasks: Mutex<Vec<JoinHandle<()>>>,
+    deferred_maintenance: AtomicUsize,
+    // Set when deferred maintenance ended without the table lock, so the
+    // next write has to finish it.
+    maintenance_pending: AtomicBool,
+    memory: MemoryTracker,
+    latencies: Latencies,
+    watchers: Watchers,
+    materialized: MaterializedViews,
+    rollups: Rollups,
+    named_views: NamedViews,
+    sequences: Sequences,
+    history: History,
+    undo_log: UndoLog,
+    functions: Functions,
+    scheduler: Scheduler,
+    sinks: Sinks,
+    lock_file: Mutex<Option<LockFile>>,
+    admission: Option<Admission>,
+    tenants: Tenants,
+    session: std::sync::RwLock<SessionContext>,
     #[cfg(feature = "sharding")]
     shard_manager: Option<ShardManager>,
     #[cfg(feature = "sharding")]
     network_manager: Option<NetworkManager>,
 }
 
+/// Compresses and encrypts an encoded snapshot.
+fn seal_snapshot(key: &[u8; 32], cipher: Cipher, encoded: &[u8]) -> io::Result<Vec<u8>> {
+    let compressed = snapshot_compression::compress(SnapshotCompression::Gzip, encoded, &[])?;
+    sealed::seal(key, cipher, &compressed)
+}
+
+/// Decrypts a snapshot written by `seal_snapshot`, leaving it compressed.
+fn unseal_snapshot(key: &[u8; 32], buffer: &[u8]) -> io::Result<Vec<u8>> {
+    sealed::open(key, buffer)
+}
+
+/// Decrypts and decompresses a snapshot. Unless `recover` is set, a snapshot
+/// that doesn't decompress fails; otherwise what did is returned and the rest
+/// is added to `report`.
+fn unpack_snapshot(key: &[u8; 32], buffer: &[u8], report: &mut RecoveryReport, recover: bool) -> io::Result<Vec<u8>> {
+    let decrypted_data = unseal_snapshot(key, buffer)?;
+    snapshot_compression::decompress(&decrypted_data, report, recover)
+}
+
 pub fn begin_transaction() -> Transaction {
     Transaction::new()
 }
 
+// Ends `with_deferred_maintenance`'s mode if its future panics or is dropped.
+// Without the table lock to hand, the finishing is left to the next write.
+struct DeferredMaintenance<'a> {
+    db: &'a Database,
+}
+
+impl Drop for DeferredMaintenance<'_> {
+    fn drop(&mut self) {
+        match self.db.tables.try_write() {
+            Ok(mut tables) => self.db.end_deferred_maintenance(&mut tables),
+            Err(_) => {
+                self.db.maintenance_pending.store(true, Ordering::SeqCst);
+                self.db.deferred_maintenance.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
 impl Database {
     pub fn new(key: [u8; 32], wal_path: &str) -> Self {
+        Self::with_config(key, wal_path, DatabaseConfig::default())
+    }
+
+    /// A database whose WAL and snapshots live in a fresh `MemoryStorage`,
+    /// reachable through `storage()`. Nothing is written to disk.
+    pub fn in_memory(key: [u8; 32]) -> Self {
+        let config = DatabaseConfig {
+            storage: Some(Arc::new(MemoryStorage::new())),
+            ..Default::default()
+        };
+        Self::with_config(key, "zapdb.wal", config)
+    }
+
+    pub fn storage(&self) -> Arc<dyn StorageBackend> {
+        Arc::clone(&self.storage)
+    }
+
+    pub fn with_config(key: [u8; 32], wal_path: &str, config: DatabaseConfig) -> Self {
+        let undo_log = UndoLog::new(config.undo_depth);
+        let storage: Arc<dyn StorageBackend> = match &config.storage {
+            Some(storage) => Arc::clone(storage),
+            // There are no files on wasm32.
+            #[cfg(target_arch = "wasm32")]
+            None => Arc::new(MemoryStorage::new()),
+            #[cfg(not(target_arch = "wasm32"))]
+            None => Arc::new(FileStorage::new()),
+        };
         Self {
             tables: Arc::new(RwLock::new(HashMap::new())),
+            snapshot: ArcSwap::default(),
             key,
-            wal_writer: Arc::new(RwLock::new(WalWriter::new(wal_path).unwrap())),
+            wal_writer: Arc::new(RwLock::new(
+                WalWriter::with_storage(Arc::clone(&storage), wal_path, config.codec).unwrap(),
+            )),
+            storage,
             wal_path: wal_path.to_string(),
+            admission: config.admission.as_ref().map(Admission::new),
+            config,
             query_planner: QueryPlanner::new(),
+            closed: AtomicBool::new(false),
+            background_tasks: Mutex::new(Vec::new()),
+            deferred_maintenance: AtomicUsize::new(0),
+            maintenance_pending: AtomicBool::new(false),
+            memory: MemoryTracker::default(),
+            latencies: Latencies::default(),
+            watchers: Watchers::default(),
+            materialized: MaterializedViews::default(),
+            rollups: Rollups::default(),
+            named_views: NamedViews::default(),
+            sequences: Sequences::default(),
+            history: History::default(),
+            undo_log,
+            functions: Functions::default(),
+            scheduler: Scheduler::default(),
+            sinks: Sinks::default(),
+            lock_file: Mutex::new(None),
+            tenants: Tenants::default(),
+            session: Default::default(),
             #[cfg(feature = "sharding")]
             shard_manager: None,
             #[cfg(feature = "sharding")]
@@ -382,6 +1232,63 @@ impl Database {
     }
 
 
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn check_open(&self) -> Result<(), String> {
+        if self.is_closed() {
+            Err("Database is closed".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_open_io(&self) -> io::Result<()> {
+        self.check_open().map_err(io::Error::other)
+    }
+
+    /// Spawns a task that lives as long as the database; `close` aborts it.
+    pub fn spawn_background<F>(&self, task: F) -> AbortHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task);
+        let abort_handle = handle.abort_handle();
+        let mut tasks = self.background_tasks.lock().unwrap();
+        tasks.retain(|t| !t.is_finished());
+        tasks.push(handle);
+        abort_handle
+    }
+
+    /// Stops background tasks, optionally writes a final snapshot to `save_path`,
+    /// then flushes and fsyncs the WAL. Every operation afterwards fails with
+    /// "Database is closed", and the lock taken by `open` is released.
+    /// Closing twice is a no-op.
+    pub async fn close(&self, save_path: Option<&str>) -> io::Result<()> {
+        if self.is_closed() {
+            return Ok(());
+        }
+        for task in self.background_tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+        if let Some(path) = save_path {
+            self.save(path).await?;
+        }
+        let mut wal_writer = self.timed(self.wal_writer.write()).await?;
+        wal_writer.sync_unblocked().await?;
+        self.closed.store(true, Ordering::SeqCst);
+        self.watchers.close();
+        self.lock_file.lock().unwrap().take();
+        Ok(())
+    }
+
+    /// Flushes and fsyncs the WAL.
+    pub async fn sync_wal(&self) -> io::Result<()> {
+        self.check_open_io()?;
+        self.timed(self.wal_writer.write()).await?.sync_unblocked().await
+    }
+
     #[cfg(feature = "sharding")]
     pub async fn enable_sharding(
         &mut self,
@@ -400,143 +1307,302 @@ impl Database {
         }
     }
 
-    pub async fn commit(&self, transaction: Transaction) -> Result<(), String> {
-        let mut wal_writer = self.wal_writer.write().await;
+    pub async fn commit(&self, transaction: Transaction) -> Result<(), WriteError> {
+        self.check_open()?;
         for (op, _) in &transaction.operations {
-            let wal_entry = match op {
-                Operation::Insert { table_name, row } => WalEntry::Insert {
-                    table_name: table_name.clone(),
-                    row: row.clone(),
-                },
-                Operation::Update { table_name, query } => WalEntry::Update {
-                    table_name: table_name.clone(),
-                    query: query.clone(),
-                },
-                Operation::Delete { table_name, query } => WalEntry::Delete {
-                    table_name: table_name.clone(),
-                    query: query.clone(),
-                },
-            };
-            wal_writer.log(&wal_entry).map_err(|e| e.to_string())?;
+            self.check_operation(op)?;
+        }
+        let touched: Vec<&str> = transaction.operations.iter().map(|(op, _)| op.table_name()).collect();
+        let _admitted = self.admit(&touched).await?;
+        let mut wal_writer = self.timed(self.wal_writer.write()).await?;
+        let mut tables = self.timed(self.tables.write()).await?;
+        for (op, _) in &transaction.operations {
+            if tables.get(op.table_name()).is_some_and(|t| t.ephemeral) {
+                continue;
+            }
+            wal_writer.log(&op.wal_entry()).map_err(|e| e.to_string())?;
         }
+        drop(wal_writer);
+
+        let changed: Vec<String> = touched.iter().map(|t| t.to_string()).collect();
 
-        let mut tables = self.tables.write().await;
         let original_tables = tables.clone();
+        let mut changes = Changes::new();
 
         for (op, update_fn) in transaction.operations {
             let result = match op {
                 Operation::Insert { table_name, row } => {
-                    self.insert_internal(&mut tables, &table_name, row)
+                    self.insert_internal(&mut tables, &table_name, row, &mut changes)
                 }
                 Operation::Update { table_name, query } => self
-                    .update_internal(&mut tables, &table_name, &query, update_fn.unwrap())
+                    .update_internal(&mut tables, &table_name, &query, RowUpdate::Fn(update_fn.unwrap()), &mut changes)
                     .map(|_| ()),
                 Operation::Delete { table_name, query } => self
-                    .delete_internal(&mut tables, &table_name, &query)
+                    .delete_internal(&mut tables, &table_name, &query, &mut changes)
+                    .map(|_| ())
+                    .map_err(WriteError::from),
+                Operation::InsertOnConflict { table_name, row, on_conflict } => self
+                    .insert_on_conflict_internal(&mut tables, &table_name, row, on_conflict, &mut changes)
                     .map(|_| ()),
             };
-            if result.is_err() {
+            if let Err(e) = result {
                 *tables = original_tables;
-                return Err(result.unwrap_err());
+                return Err(e);
             }
         }
+        let changed: Vec<&str> = changed.iter().map(String::as_str).collect();
+        self.finish_write(&mut tables, &changed, changes);
         Ok(())
     }
 
-    pub fn rollback(&self, _transaction: Transaction) {
-        // No-op for now, as commit will handle rollback on failure.
-        // This can be expanded later if needed.
+    /// Runs `f` with per-write index and Merkle maintenance suspended, then
+    /// rebuilds each modified table once. Meant for bulk loads. Queries inside
+    /// `f` stay correct (stale indexes fall back to scans), but
+    /// `verify_integrity` is only meaningful after `f` returns. The mode applies
+    /// to every writer on this database while it is active.
+    ///
+    /// The mode also ends if `f` panics or the returned future is dropped
+    /// before it completes.
+    pub async fn with_deferred_maintenance<F, T>(&self, f: F) -> T
+    where
+        F: AsyncFnOnce(&Self) -> T,
+    {
+        self.deferred_maintenance.fetch_add(1, Ordering::SeqCst);
+        let deferred = DeferredMaintenance { db: self };
+        let result = f(self).await;
+        let mut tables = self.tables.write().await;
+        std::mem::forget(deferred);
+        self.end_deferred_maintenance(&mut tables);
+        result
     }
 
-    pub async fn save(&self, path: &str) -> io::Result<()> {
-        let start = Instant::now();
-        let tables = self.tables.read().await;
-        let encoded: Vec<u8> =
-            bincode::serialize(&*tables).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
-
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(&encoded)?;
-        let compressed_data = encoder.finish()?;
+    // Leaves the deferred mode, finishing and publishing first if this is the
+    // last caller in it, so queries never see a snapshot that predates the
+    // writes made while it was on. Call with the write lock held.
+    fn end_deferred_maintenance(&self, tables: &mut HashMap<String, Table>) {
+        if self.deferred_maintenance.load(Ordering::SeqCst) == 1 {
+            self.finish_deferred(tables);
+        }
+        self.deferred_maintenance.fetch_sub(1, Ordering::SeqCst);
+    }
 
-        let cipher = Aes256Gcm::new((&self.key).into());
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
+    fn finish_deferred(&self, tables: &mut HashMap<String, Table>) {
+        for table in tables.values_mut() {
+            table.finish_maintenance();
+        }
+        self.publish_all(tables);
+        self.maintenance_pending.store(false, Ordering::SeqCst);
+    }
 
-        let ciphertext = cipher
-            .encrypt(nonce, compressed_data.as_slice())
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    // Queries read through the lock while this is true, since the published
+    // snapshot may be missing writes.
+    fn maintenance_deferred(&self) -> bool {
+        self.deferred_maintenance.load(Ordering::SeqCst) > 0 || self.maintenance_pending.load(Ordering::SeqCst)
+    }
 
-        let mut file = File::create(path)?;
-        file.write_all(&nonce)?;
-        file.write_all(&ciphertext)?;
+    // Writes only record their changes when something consumes them.
+    fn tracking_changes(&self) -> bool {
+        self.watchers.active() || self.materialized.active() || self.rollups.active() || self.history.active()
+            || self.undo_log.enabled()
+    }
 
-        // Truncate the WAL file
-        File::create(&self.wal_path)?;
+    // Runs after a successful write, with the tables still locked.
+    fn finish_write(&self, tables: &mut HashMap<String, Table>, changed: &[&str], changes: Changes) {
+        if self.maintenance_pending.load(Ordering::SeqCst) && self.deferred_maintenance.load(Ordering::SeqCst) == 0 {
+            self.finish_deferred(tables);
+        }
+        self.undo_log.record(&changes);
+        self.publish_changes(tables, changed, changes);
+    }
 
-        println!("Database saved in {:?}", start.elapsed());
-        Ok(())
+    fn publish_changes(&self, tables: &mut HashMap<String, Table>, changed: &[&str], changes: Changes) {
+        self.record_history(tables, &changes);
+        self.auto_analyze(tables, changed);
+        let mut views = self.maintain_views(tables, &changes);
+        views.extend(self.maintain_rollups(tables, &changes));
+        let mut changed = changed.to_vec();
+        changed.extend(views.iter().map(String::as_str));
+        self.publish(tables, &changed);
+        self.notify(changes);
     }
 
-    pub async fn load(&self, path: &str) -> io::Result<()> {
+    pub fn rollback(&self, _transaction: Transaction) {
+        // No-op for now, as commit will handle rollback on failure.
+        // This can be expanded later if needed.
+    }
+
+    pub async fn save(&self, path: &str) -> io::Result<()> {
+        self.check_open_io()?;
         let start = Instant::now();
-        if let Ok(mut file) = File::open(path) {
-            let mut buffer = Vec::new();
-            file.read_to_end(&mut buffer)?;
-
-            let cipher = Aes256Gcm::new((&self.key).into());
-            let nonce = Nonce::from_slice(&buffer[..12]);
-            let ciphertext = &buffer[12..];
-
-            let decrypted_data = cipher
-                .decrypt(nonce, ciphertext)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
-
-            let mut decoder = GzDecoder::new(&decrypted_data[..]);
-            let mut decompressed_data = Vec::new();
-            decoder.read_to_end(&mut decompressed_data)?;
-
-            let tables: HashMap<String, Table> = bincode::deserialize(&decompressed_data)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-
-            let mut self_tables = self.tables.write().await;
-            *self_tables = tables;
-            for table in self_tables.values_mut() {
-                table.indexes = HashMap::new();
-                for col in &table.columns {
-                    if col.constraints.contains(&Constraint::Unique) {
-                        let index = DashMap::new();
-                        for (i, row) in table.data.iter().enumerate() {
-                            if let Some(value) = row.get(&col.name) {
-                                index.entry(value.clone()).or_insert_with(Vec::new).push(i);
-                            }
-                        }
-                        table.indexes.insert(col.name.clone(), index);
-                    }
+        // Holding the WAL lock keeps writes out until the WAL is truncated,
+        // so none is logged after the snapshot and then lost. Once the
+        // snapshot is being written, the save runs to completion.
+        let mut wal_writer = self.timed(self.wal_writer.write()).await?;
+        self.write_snapshot(path).await?;
+
+        if let Some(dir) = self.config.wal_archive.clone() {
+            let wal_path = self.wal_path.clone();
+            storage::unblock(&self.storage, move |storage| match storage.read(&wal_path)? {
+                Some(wal) => wal_archive::archive(&dir, &wal),
+                None => Ok(()),
+            })
+            .await?;
+        }
+        wal_writer.truncate()?;
+
+        println!("Database saved in {:?}", start.elapsed());
+        Ok(())
+    }
+
+    /// Writes a snapshot to `path` without truncating the WAL, so the live
+    /// database keeps its own recovery log. Restore with `load`.
+    pub async fn backup(&self, path: &str) -> io::Result<()> {
+        self.check_open_io()?;
+        // A backup changes nothing, so it can give up at any point.
+        self.timed(self.write_snapshot(path)).await?.map(|_| ())
+    }
+
+    // Returns the Merkle roots of the tables written.
+    async fn write_snapshot(&self, path: &str) -> io::Result<TableRoots> {
+        let (encoded, samples, roots) = {
+            let tables = self.timed(self.tables.read()).await?;
+            let persistent: Vec<(&str, &Table)> = tables
+                .iter()
+                .filter(|(_, t)| !t.ephemeral)
+                .map(|(name, t)| (name.as_str(), t))
+                .collect();
+            let encoded = recovery::encode_snapshot(
+                self.config.codec,
+                &persistent,
+                &self.named_views.all(),
+                &self.sequences.all(),
+                &self.tenants.all(),
+            )?;
+            let samples = match self.config.snapshot_compression {
+                SnapshotCompression::ZstdDictionary { samples, .. } => {
+                    snapshot_compression::samples(self.config.codec, &persistent, samples)?
                 }
-                table.build_merkle_tree();
-            }
+                _ => Vec::new(),
+            };
+            (encoded, samples, snapshot_roots::roots(persistent.into_iter()))
+        };
+        // Compressing and encrypting are slow for a big snapshot too.
+        let (key, cipher, compression, path) =
+            (self.key, self.config.cipher, self.config.snapshot_compression, path.to_string());
+        storage::unblock(&self.storage, move |storage| {
+            let compressed = snapshot_compression::compress(compression, &encoded, &samples)?;
+            storage.write(&path, &sealed::seal(&key, cipher, &compressed)?)
+        })
+        .await?;
+        Ok(roots)
+    }
+
+    pub async fn load(&self, path: &str) -> io::Result<()> {
+        let mut report = RecoveryReport::default();
+        self.load_reporting(path, &mut report, false).await?;
+        report.into_result()
+    }
+
+    // Loads the snapshot at `path` and replays the WAL. Whatever can't be
+    // decoded is added to `report`; unless `recover` is set, nothing is
+    // loaded if anything is.
+    async fn load_reporting(&self, path: &str, report: &mut RecoveryReport, recover: bool) -> io::Result<()> {
+        self.check_open_io()?;
+        let start = Instant::now();
+        let snapshot = self.timed(self.read_snapshot(path, report, recover)).await??;
+        let wal = self.timed(self.read_wal()).await??;
+        // Nothing has changed yet; from here the load runs to completion.
+        if let Some(contents) = snapshot {
+            self.install_snapshot(contents).await;
         }
 
-        self.replay_wal().await?;
+        self.replay_wal(wal, report, recover).await?;
 
         println!("Database loaded in {:?}", start.elapsed());
         Ok(())
     }
 
-    async fn replay_wal(&self) -> io::Result<()> {
-        let mut file = match File::open(&self.wal_path) {
-            Ok(f) => f,
-            Err(_) => return Ok(()),
+    // Reads and decodes the snapshot at `path`, if there is one. Whatever
+    // can't be decoded is added to `report`; unless `recover` is set,
+    // nothing is returned if anything is.
+    async fn read_snapshot(
+        &self,
+        path: &str,
+        report: &mut RecoveryReport,
+        recover: bool,
+    ) -> io::Result<Option<recovery::SnapshotContents>> {
+        let snapshot_path = path.to_string();
+        let Some(buffer) = storage::unblock(&self.storage, move |storage| storage.read(&snapshot_path)).await? else {
+            return Ok(None);
+        };
+        let decompressed_data = unpack_snapshot(&self.key, &buffer, report, recover)?;
+        let mut contents = recovery::decode_snapshot(&decompressed_data, report)?;
+        if !recover && !report.is_clean() {
+            return Ok(None);
+        }
+        for table in contents.tables.values_mut() {
+            table.adopt_schema();
+            table.assign_row_ids();
+        }
+        Ok(Some(contents))
+    }
+
+    // Replaces every table but the ephemeral ones with a snapshot's.
+    async fn install_snapshot(&self, contents: recovery::SnapshotContents) {
+        self.named_views.replace(contents.views);
+        self.sequences.replace(contents.sequences);
+        self.tenants.replace(contents.tenants);
+
+        let mut self_tables = self.tables.write().await;
+        let ephemeral: Vec<(String, Table)> = self_tables
+            .drain()
+            .filter(|(_, t)| t.ephemeral)
+            .collect();
+        *self_tables = contents.tables;
+        for table in self_tables.values_mut() {
+            table.indexes = HashMap::new();
+            let unique: Vec<String> = table
+                .columns
+                .iter()
+                .filter(|c| c.constraints.contains(&Constraint::Unique))
+                .map(|c| c.name.clone())
+                .collect();
+            for column in unique {
+                table.build_index(&column, IndexKind::Hash);
+            }
+            table.build_merkle_leaves();
+        }
+        foreign_keys::index_referenced_columns(&mut self_tables);
+        for (name, table) in ephemeral {
+            self_tables.entry(name).or_insert(table);
+        }
+        self.refresh_views(&mut self_tables);
+        self.refresh_rollups(&mut self_tables);
+        self.publish_all(&self_tables);
+    }
+
+    async fn read_wal(&self) -> io::Result<Option<Vec<u8>>> {
+        let wal_path = self.wal_path.clone();
+        storage::unblock(&self.storage, move |storage| storage.read(&wal_path)).await
+    }
+
+    async fn replay_wal(&self, wal: Option<Vec<u8>>, report: &mut RecoveryReport, recover: bool) -> io::Result<()> {
+        let Some(buffer) = wal else {
+            return Ok(());
         };
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer)?;
 
-        let mut cursor = io::Cursor::new(buffer);
-        while cursor.position() < cursor.get_ref().len() as u64 {
-            let entry: WalEntry = bincode::deserialize_from(&mut cursor)
-                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-            self.apply_wal_entry(entry).await;
+        for (offset, entry) in WalEntries::new(&buffer) {
+            match entry {
+                Ok(entry) => self.apply_wal_entry(entry).await,
+                Err(e) if recover => {
+                    report.skipped.push(Skipped::Wal {
+                        offset,
+                        error: e.to_string(),
+                    });
+                    break;
+                }
+                Err(e) => return Err(e),
+            }
         }
 
         Ok(())
@@ -545,16 +1611,58 @@ impl Database {
     async fn apply_wal_entry(&self, entry: WalEntry) {
         match entry {
             WalEntry::CreateTable { name, columns } => {
-                let _ = self.create_table(name, columns).await;
+                let _ = self.create_table_internal(name, columns, false, false).await;
             }
             WalEntry::Insert { table_name, row } => {
-                let _ = self.insert(&table_name, row).await;
+                let _ = self.apply_insert(&table_name, row).await;
             }
             WalEntry::Update { .. } => {
                 // Not implemented due to non-serializable update_fn
             }
             WalEntry::Delete { table_name, query } => {
-                let _ = self.delete(&table_name, &query).await;
+                let _ = self.apply_delete(&table_name, &query).await;
+            }
+            WalEntry::CreateView { name, table, query } => {
+                let _ = self.create_view(&name, &table, query).await;
+            }
+            WalEntry::DropView { name } => {
+                let _ = self.drop_view(&name).await;
+            }
+            WalEntry::Replace { table_name, old, new } => {
+                let _ = self.replay_replace(&table_name, old, new).await;
+            }
+            WalEntry::CreateSequence { name, start, increment } => {
+                let _ = self.create_sequence(&name, start, increment).await;
+            }
+            WalEntry::DropSequence { name } => {
+                let _ = self.drop_sequence(&name).await;
+            }
+            WalEntry::SequenceValue { name, value } => {
+                self.replay_sequence_value(&name, value);
+            }
+            WalEntry::UpdateExpr { table_name, query, expr } => {
+                let _ = self.apply_update_expr(&table_name, &query, &expr).await;
+            }
+            WalEntry::PartitionTable { table_name, partitioning } => {
+                let _ = self.partition_table(&table_name, partitioning).await;
+            }
+            WalEntry::DropPartition { table_name, key } => {
+                let _ = self.apply_drop_partition(&table_name, &key).await;
+            }
+            WalEntry::InsertOnConflict { table_name, row, on_conflict } => {
+                let _ = self.apply_insert_on_conflict(&table_name, row, on_conflict).await;
+            }
+            WalEntry::DropTable { name, on_dependents } => {
+                let _ = self.drop_table(&name, on_dependents).await;
+            }
+            WalEntry::RenameTable { name, new_name, on_dependents } => {
+                let _ = self.rename_table(&name, &new_name, on_dependents).await;
+            }
+            WalEntry::CreateTenant { name, config } => {
+                let _ = self.create_tenant(&name, config).await;
+            }
+            WalEntry::SuspendTenant { name, suspended } => {
+                let _ = self.set_suspended(&name, suspended).await;
             }
         }
     }
@@ -563,65 +1671,85 @@ impl Database {
         name: String,
         columns: Vec<Column>,
     ) -> Result<Duration, String> {
-        let start = Instant::now();
-        let wal_entry = WalEntry::CreateTable {
-            name: name.clone(),
-            columns: columns.clone(),
-        };
-        self.wal_writer
-            .write()
-            .await
-            .log(&wal_entry)
-            .map_err(|e| e.to_string())?;
+        self.create_table_internal(name, columns, false, true).await
+    }
 
-        let mut tables = self.tables.write().await;
-        if tables.contains_key(&name) {
+    /// Creates a scratch table that is never written to the WAL and is left out
+    /// of `save`. It survives `load`, but is lost when the process exits.
+    pub async fn create_ephemeral_table(
+        &self,
+        name: String,
+        columns: Vec<Column>,
+    ) -> Result<Duration, String> {
+        self.create_table_internal(name, columns, true, true).await
+    }
+
+    async fn create_table_internal(
+        &self,
+        name: String,
+        columns: Vec<Column>,
+        ephemeral: bool,
+        check: bool,
+    ) -> Result<Duration, String> {
+        self.check_open()?;
+        let start = Instant::now();
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        if tables.contains_key(&name) || self.named_views.contains(&name) {
             return Err(format!("Table {} already exists", name));
         }
-        tables.insert(
-            name.clone(),
-            Table {
-                name,
-                columns,
-                data: Vec::new(),
-                indexes: HashMap::new(),
-                merkle_tree: None,
-            },
-        );
+        if check {
+            schema::check_schema(&tables, &name, &columns)?;
+        }
+        if !ephemeral {
+            let wal_entry = WalEntry::CreateTable {
+                name: name.clone(),
+                columns: columns.clone(),
+            };
+            wal_writer.log(&wal_entry).map_err(|e| e.to_string())?;
+        }
+        drop(wal_writer);
+        tables.insert(name.clone(), Table::new(name.clone(), columns, ephemeral));
+        foreign_keys::index_referenced_columns(&mut tables);
+        self.publish(&tables, &[&name]);
         Ok(start.elapsed())
     }
 
     pub async fn create_index(&self, table_name: &str, column_name: &str) -> Result<(), String> {
-        let mut tables = self.tables.write().await;
-        let table = tables
-            .get_mut(table_name)
-            .ok_or_else(|| format!("Table {} not found", table_name))?;
-
-        if !table.columns.iter().any(|c| c.name == column_name) {
-            return Err(format!("Column {} not found", column_name));
-        }
+        self.create_index_with_kind(table_name, column_name, IndexKind::Hash).await
+    }
 
-        let index = DashMap::new();
-        for (i, row) in table.data.iter().enumerate() {
-            if let Some(value) = row.get(column_name) {
-                index.entry(value.clone()).or_insert_with(Vec::new).push(i);
-            }
+    async fn log_wal(&self, table_name: &str, wal_entry: &WalEntry) -> Result<(), String> {
+        let ephemeral = self
+            .timed(self.tables.read())
+            .await
+            .map_err(|e| e.to_string())?
+            .get(table_name)
+            .is_some_and(|t| t.ephemeral);
+        if ephemeral {
+            return Ok(());
         }
-
-        table.indexes.insert(column_name.to_string(), index);
-        Ok(())
+        self.timed(self.wal_writer.write())
+            .await
+            .map_err(|e| e.to_string())?
+            .log(wal_entry)
+            .map_err(|e| e.to_string())
     }
 
     fn insert_internal(
         &self,
         tables: &mut HashMap<String, Table>,
         table_name: &str,
-        row: HashMap<String, Value>,
-    ) -> Result<(), String> {
+        mut row: HashMap<String, Value>,
+        changes: &mut Changes,
+    ) -> Result<(), WriteError> {
+        self.check_writable(table_name)?;
         // First, check all constraints
         let table = tables
             .get(table_name)
             .ok_or_else(|| format!("Table {} not found", table_name))?;
+        schema::check_row(&mut row, &table.columns, self.config.unknown_columns)?;
+        coercion::coerce_row(&mut row, &table.columns, self.config.coercion);
 
         for col in &table.columns {
             let value = row.get(&col.name);
@@ -630,21 +1758,21 @@ impl Database {
                 match constraint {
                     Constraint::NotNull => {
                         if value.is_none() || value == Some(&Value::Null) {
-                            return Err(format!("Column {} cannot be null", col.name));
+                            return Err(ConstraintError::new(table_name, &col.name, constraint, value).into());
                         }
                     }
                     Constraint::Unique => {
                         if let Some(val) = value {
                             if table.data.iter().any(|r| r.get(&col.name) == Some(val)) {
-                                return Err(format!("Column {} must be unique", col.name));
+                                return Err(ConstraintError::new(table_name, &col.name, constraint, value).into());
                             }
                         }
                     }
                     Constraint::ForeignKey { table: fk_table, column: fk_column } => {
                         if let Some(val) = value {
                             let foreign_table = tables.get(fk_table).ok_or_else(|| format!("Foreign key table {} not found", fk_table))?;
-                            if !foreign_table.data.iter().any(|r| r.get(fk_column) == Some(val)) {
-                                return Err(format!("Foreign key violation on column {}", col.name));
+                            if !foreign_table.has_value(fk_column, val, self.memory.tick()) {
+                                return Err(ConstraintError::new(table_name, &col.name, constraint, value).into());
                             }
                         }
                     }
@@ -652,44 +1780,52 @@ impl Database {
             }
 
             if let Some(value) = value {
-                let type_matches = match (&col.data_type, value) {
-                    (DataType::Integer, Value::Integer(_)) => true,
-                    (DataType::String, Value::String(_)) => true,
-                    (DataType::Float, Value::Float(_)) => true,
-                    (DataType::Boolean, Value::Boolean(_)) => true,
-                    (DataType::DateTime, Value::DateTime(_)) => true,
-                    (DataType::Uuid, Value::Uuid(_)) => true,
-                    (DataType::Json, Value::Json(_)) => true,
-                    (_, Value::Null) => true,
-                    _ => false,
-                };
+                let type_matches = matches!(
+                    (&col.data_type, value),
+                    (DataType::Integer, Value::Integer(_))
+                        | (DataType::String, Value::String(_))
+                        | (DataType::Float, Value::Float(_))
+                        | (DataType::Boolean, Value::Boolean(_))
+                        | (DataType::DateTime, Value::DateTime(_))
+                        | (DataType::Uuid, Value::Uuid(_))
+                        | (DataType::Json, Value::Json(_))
+                        | (_, Value::Null)
+                );
                 if !type_matches {
                     return Err(format!(
                         "Invalid data type for column {}: expected {:?}, got {:?}",
                         col.name, col.data_type, value
-                    ));
+                    ).into());
                 }
             } else if !col.constraints.contains(&Constraint::NotNull) {
                 // Allow missing columns if they are nullable
             } else {
-                return Err(format!("Missing column: {}", col.name));
+                return Err(format!("Missing column: {}", col.name).into());
             }
         }
 
         // If all constraints are satisfied, perform the insertion
+        let row = Row::from_map(&table.schema, row);
+        self.check_limits(table, &row)?;
+        let row_size = row.estimated_size();
+        let index_size: usize = table
+            .indexes
+            .keys()
+            .filter_map(|col_name| row.get(col_name))
+            .map(memory::index_entry_size)
+            .sum();
+        self.reserve_memory(tables, row_size + index_size)?;
+
         let table = tables
             .get_mut(table_name)
             .ok_or_else(|| format!("Table {} not found", table_name))?;
 
-        let new_index = table.data.len();
-        for (col_name, index) in &table.indexes {
-            if let Some(value) = row.get(col_name) {
-                index.entry(value.clone()).or_insert_with(Vec::new).push(new_index);
-            }
+        let event = self.tracking_changes().then(|| WatchEvent::Insert(row.clone()));
+        let id = table.push_row(row);
+        if let Some(event) = event {
+            changes.push((table_name.to_string(), id, event));
         }
-
-        table.data.push(row);
-        table.build_merkle_tree();
+        table.maintain(self.maintenance_deferred());
         Ok(())
     }
 
@@ -697,127 +1833,403 @@ impl Database {
         &self,
         table_name: &str,
         row: HashMap<String, Value>,
-    ) -> Result<Duration, String> {
-        let start = Instant::now();
-
+    ) -> Result<Duration, WriteError> {
+        self.check_row_writable(table_name, &row)?;
+        self.measured(table_name, OperationKind::Insert, async {
+            let _admitted = self.admit(&[table_name]).await?;
+            self.apply_insert(table_name, row).await
+        })
+        .await
+    }
 
+    // `insert` without admission, for WAL replay.
+    async fn apply_insert(&self, table_name: &str, row: HashMap<String, Value>) -> Result<Duration, WriteError> {
+        self.check_open()?;
+        let start = Instant::now();
 
         let wal_entry = WalEntry::Insert {
             table_name: table_name.to_string(),
             row: row.clone(),
         };
-        self.wal_writer
-            .write()
-            .await
-            .log(&wal_entry)
-            .map_err(|e| e.to_string())?;
-
-        let mut tables = self.tables.write().await;
-        self.insert_internal(&mut tables, table_name, row)?;
+        let mut tables = self.log_and_lock(table_name, &wal_entry).await?;
+        let mut changes = Changes::new();
+        self.insert_internal(&mut tables, table_name, row, &mut changes)?;
+        self.finish_write(&mut tables, &[table_name], changes);
         Ok(start.elapsed())
     }
 
+    /// Rows matching `query`, in the order they were inserted, whichever
+    /// way the query is run. Joins follow the order of the preserved
+    /// table, then of the other, and traversals go level by level, each in
+    /// insertion order.
     pub async fn select(
         &self,
         table_name: &str,
         query: &Query,
-    ) -> Result<(Vec<HashMap<String, Value>>, Duration), String> {
+    ) -> Result<(Vec<Row>, Duration), String> {
         let start = Instant::now();
-        let tables = self.tables.read().await;
+        let (results, _) = self.select_shared(table_name, query).await?;
+        let results = results.into_iter().map(Arc::unwrap_or_clone).collect();
+        Ok((results, start.elapsed()))
+    }
+
+    /// Like `select`, but hands out shared references to the stored rows instead
+    /// of deep-cloning them. Rows are copy-on-write, so later updates don't affect
+    /// references that have already been returned.
+    pub async fn select_shared(
+        &self,
+        table_name: &str,
+        query: &Query,
+    ) -> Result<(Vec<Arc<Row>>, Duration), String> {
+        self.measured(table_name, OperationKind::Select, async {
+            let (mut results, elapsed) = self.select_rows(table_name, query).await?;
+            self.limit_results(table_name, &mut results)?;
+            Ok::<_, String>((results, elapsed))
+        })
+        .await
+    }
+
+    // `select_shared` without `DatabaseConfig::result_limits`.
+    pub(crate) async fn select_rows(
+        &self,
+        table_name: &str,
+        query: &Query,
+    ) -> Result<(Vec<Arc<Row>>, Duration), String> {
+        self.check_open()?;
+        match self.resolve_view(table_name, query)? {
+            Some(view) => self.select_filtered(&view.table, &view.query, view.filter.as_ref()).await,
+            None => self.select_filtered(table_name, query, None).await,
+        }
+    }
+
+    // Runs `query` on the table `table_name`, keeping only the rows of a join
+    // or traversal that match `filter`.
+    pub(crate) async fn select_filtered(
+        &self,
+        table_name: &str,
+        query: &Query,
+        filter: Option<&Query>,
+    ) -> Result<(Vec<Arc<Row>>, Duration), String> {
+        let start = Instant::now();
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
         let table = tables
             .get(table_name)
             .ok_or_else(|| format!("Table {} not found", table_name))?;
-
-
+        self.check_query(query, &table.columns)?;
+        self.check_readable(table_name, query)?;
+        if let Some(filter) = filter {
+            self.check_filter_readable(table_name, query, filter)?;
+        }
 
         let optimized_query = self.query_planner.optimize(query.clone(), table);
 
-        let results = match &optimized_query {
+        let mut results: Vec<Arc<Row>> = match &optimized_query {
             Query::Join(join) => {
                 let target_table = tables
                     .get(&join.target_table)
                     .ok_or_else(|| format!("Table {} not found", join.target_table))?;
-                self.execute_join_query(table, target_table, join)
+                join.check_columns(table_name)?;
+                self.execute_join_query(table, target_table, join, filter)?
+                    .into_iter()
+                    .map(Arc::new)
+                    .collect()
+            }
+            Query::Traverse(traverse) => {
+                let mut rows = self.execute_traverse(table, traverse)?;
+                if let Some(filter) = filter {
+                    self.retain_matching(&mut rows, filter)?;
+                }
+                rows.into_iter().map(Arc::new).collect()
             }
+            Query::Aggregate(aggregate_query) if !aggregate_query.group_by.is_empty() => self
+                .execute_grouped_aggregate(table, aggregate_query)?
+                .into_iter()
+                .map(Arc::new)
+                .collect(),
             Query::Aggregate(aggregate_query) => {
                 let result = self.execute_aggregate_query(table, aggregate_query)?;
-                let mut row = HashMap::new();
+                let mut row = Row::new();
                 row.insert("result".to_string(), result);
-                vec![row]
+                vec![Arc::new(row)]
             }
             _ => self
-                .execute_query(table, &optimized_query)
+                .execute_query(table, &optimized_query)?
                 .into_iter()
-                .map(|i| table.data[i].clone())
+                .map(|i| Arc::clone(&table.data[i]))
                 .collect(),
         };
+        self.hide_columns(table_name, query, &mut results);
 
         Ok((results, start.elapsed()))
     }
 
+    pub async fn get(
+        &self,
+        table_name: &str,
+        key: &Value,
+    ) -> Result<Option<Row>, String> {
+        let pk = {
+            let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+            let table = tables
+                .get(table_name)
+                .ok_or_else(|| format!("Table {} not found", table_name))?;
+            table
+                .primary_key()
+                .map(|c| c.name.clone())
+                .ok_or_else(|| format!("Table {} has no columns", table_name))?
+        };
+        let query = Query::Condition(Condition {
+            column: pk,
+            operator: Operator::Eq,
+            value: key.clone(),
+        });
+        self.first(table_name, &query).await
+    }
+
+    pub async fn first(
+        &self,
+        table_name: &str,
+        query: &Query,
+    ) -> Result<Option<Row>, String> {
+        self.check_open()?;
+        if matches!(query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_))
+            || self.named_views.contains(table_name)
+        {
+            let (results, _) = self.select_rows(table_name, query).await?;
+            return Ok(results.into_iter().next().map(Arc::unwrap_or_clone));
+        }
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        self.check_query(query, &table.columns)?;
+        self.check_readable(table_name, query)?;
+        let optimized_query = self.query_planner.optimize(query.clone(), table);
+        Ok(self
+            .execute_query(table, &optimized_query)?
+            .into_iter()
+            .min()
+            .map(|i| {
+                let mut row = (*table.data[i]).clone();
+                self.hide_row_columns(table_name, query, &mut row);
+                row
+            }))
+    }
+
+    pub async fn exists(&self, table_name: &str, query: &Query) -> Result<bool, String> {
+        Ok(self.count(table_name, query).await? > 0)
+    }
+
+    pub async fn count(&self, table_name: &str, query: &Query) -> Result<usize, String> {
+        self.check_open()?;
+        if matches!(query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_))
+            || self.named_views.contains(table_name)
+        {
+            let (results, _) = self.select_rows(table_name, query).await?;
+            return Ok(results.len());
+        }
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        self.check_query(query, &table.columns)?;
+        self.check_readable(table_name, query)?;
+        let optimized_query = self.query_planner.optimize(query.clone(), table);
+        Ok(self.execute_query(table, &optimized_query)?.len())
+    }
+
+    // Joins the tables, keeping the joined rows that match `filter`. Parts
+    // of the filter that only read one table are run on it first.
     fn execute_join_query(
         &self,
         left_table: &Table,
         right_table: &Table,
         join: &Join,
-    ) -> Vec<HashMap<String, Value>> {
+        filter: Option<&Query>,
+    ) -> Result<Vec<Row>, String> {
+        let prefixes = join.prefixes(&left_table.name).unzip();
+        self.join_tables(left_table, right_table, join, filter, prefixes)
+    }
+
+    // `execute_join_query` with the given prefixes for the columns of each
+    // table.
+    pub(crate) fn join_tables(
+        &self,
+        left_table: &Table,
+        right_table: &Table,
+        join: &Join,
+        filter: Option<&Query>,
+        (left_prefix, right_prefix): (Option<&str>, Option<&str>),
+    ) -> Result<Vec<Row>, String> {
         let mut results = Vec::new();
-        let (left_col, right_col) = &join.on_condition;
+        let pushdown = self.query_planner.push_down(filter, left_table, right_table, join);
+        let mut left_rows = self.execute_query(left_table, &pushdown.left)?;
+        let mut right_rows = self.execute_query(right_table, &pushdown.right)?;
+        left_rows.sort_unstable();
+        right_rows.sort_unstable();
+
+        // Every joined row shares one schema covering both tables.
+        let schema = Arc::new(RowSchema::new(
+            joined_columns(left_table, right_table, (left_prefix, right_prefix))
+                .into_iter()
+                .map(|c| c.name),
+        ));
+        let merge = |first: &Row, second: &Row| {
+            let mut merged_row = Row::with_schema(schema.clone());
+            merged_row.extend(first.iter().map(|(k, v)| (joined_name(left_prefix, k), v.clone())));
+            merged_row.extend(second.iter().map(|(k, v)| (joined_name(right_prefix, k), v.clone())));
+            merged_row
+        };
+        let pad = |row: &Row, prefix: Option<&str>, other: &Table, other_prefix: Option<&str>| {
+            let mut merged_row = Row::with_schema(schema.clone());
+            merged_row.extend(row.iter().map(|(k, v)| (joined_name(prefix, k), v.clone())));
+            for col in &other.columns {
+                merged_row.insert(joined_name(other_prefix, &col.name), Value::Null);
+            }
+            merged_row
+        };
 
+        // Matching (left, right) positions, put back in the order of the
+        // preserved side whichever side the plan looped over.
+        let mut pairs = self.join_pairs((left_table, &left_rows), (right_table, &right_rows), join);
         match join.join_type {
             JoinType::Inner => {
-                for left_row in &left_table.data {
-                    for right_row in &right_table.data {
-                        if left_row.get(left_col) == right_row.get(right_col) {
-                            let mut merged_row = left_row.clone();
-                            merged_row.extend(right_row.clone());
-                            results.push(merged_row);
-                        }
-                    }
+                pairs.sort_unstable();
+                for (l, r) in pairs {
+                    results.push(merge(&left_table.data[l], &right_table.data[r]));
                 }
             }
             JoinType::Left => {
-                for left_row in &left_table.data {
+                pairs.sort_unstable();
+                let mut pairs = pairs.into_iter().peekable();
+                for &l in &left_rows {
+                    let left_row = &left_table.data[l];
                     let mut found_match = false;
-                    for right_row in &right_table.data {
-                        if left_row.get(left_col) == right_row.get(right_col) {
-                            let mut merged_row = left_row.clone();
-                            merged_row.extend(right_row.clone());
-                            results.push(merged_row);
-                            found_match = true;
-                        }
+                    while let Some((_, r)) = pairs.next_if(|(pl, _)| *pl == l) {
+                        results.push(merge(left_row, &right_table.data[r]));
+                        found_match = true;
                     }
                     if !found_match {
-                        let mut merged_row = left_row.clone();
-                        for col in &right_table.columns {
-                            merged_row.insert(col.name.clone(), Value::Null);
-                        }
-                        results.push(merged_row);
+                        results.push(pad(left_row, left_prefix, right_table, right_prefix));
                     }
                 }
             }
             JoinType::Right => {
-                for right_row in &right_table.data {
+                pairs.sort_unstable_by_key(|(l, r)| (*r, *l));
+                let mut pairs = pairs.into_iter().peekable();
+                for &r in &right_rows {
+                    let right_row = &right_table.data[r];
                     let mut found_match = false;
-                    for left_row in &left_table.data {
-                        if left_row.get(left_col) == right_row.get(right_col) {
-                            let mut merged_row = left_row.clone();
-                            merged_row.extend(right_row.clone());
-                            results.push(merged_row);
-                            found_match = true;
-                        }
+                    while let Some((l, _)) = pairs.next_if(|(_, pr)| *pr == r) {
+                        results.push(merge(&left_table.data[l], right_row));
+                        found_match = true;
                     }
                     if !found_match {
-                        let mut merged_row = right_row.clone();
-                        for col in &left_table.columns {
-                            merged_row.insert(col.name.clone(), Value::Null);
+                        results.push(pad(right_row, right_prefix, left_table, left_prefix));
+                    }
+                }
+            }
+        }
+        if let Some(residual) = &pushdown.residual {
+            self.retain_matching(&mut results, residual)?;
+        }
+        Ok(results)
+    }
+
+    // Positions of the rows that join, out of the given positions of each
+    // table, as (left, right) pairs in no particular order. Rows missing the
+    // join column match each other.
+    fn join_pairs(
+        &self,
+        (left_table, left_rows): (&Table, &[usize]),
+        (right_table, right_rows): (&Table, &[usize]),
+        join: &Join,
+    ) -> Vec<(usize, usize)> {
+        let plan = self.query_planner.plan_join(
+            (left_table, left_rows.len()),
+            (right_table, right_rows.len()),
+            join,
+        );
+        let (left_col, right_col) = &join.on_condition;
+        if let Some(budget) = self.config.join_memory_limit {
+            let too_big = |table, rows, column| sort_merge::hash_table_size(table, rows, column) > budget;
+            if plan.strategy == JoinStrategy::Hash
+                && too_big(left_table, left_rows, left_col)
+                && too_big(right_table, right_rows, right_col)
+            {
+                // Hashes in memory after all if a spill file can't be written.
+                if let Ok(pairs) = self.sort_merge_pairs((left_table, left_rows), (right_table, right_rows), join, budget) {
+                    return pairs;
+                }
+            }
+        }
+        let ((driving, driving_rows, driving_col), (other, other_rows, other_col)) = if plan.drive_left {
+            ((left_table, left_rows, left_col), (right_table, right_rows, right_col))
+        } else {
+            ((right_table, right_rows, right_col), (left_table, left_rows, left_col))
+        };
+
+        let mut pairs = Vec::new();
+        let mut push = |d: usize, o: usize| pairs.push(if plan.drive_left { (d, o) } else { (o, d) });
+        match plan.strategy {
+            JoinStrategy::NestedLoop => {
+                for &d in driving_rows {
+                    for &o in other_rows {
+                        if driving.data[d].get(driving_col) == other.data[o].get(other_col) {
+                            push(d, o);
+                        }
+                    }
+                }
+            }
+            JoinStrategy::Hash => {
+                let mut positions: HashMap<Option<&Value>, Vec<usize>> = HashMap::new();
+                for &o in other_rows {
+                    positions.entry(other.data[o].get(other_col)).or_default().push(o);
+                }
+                for &d in driving_rows {
+                    for &o in positions.get(&driving.data[d].get(driving_col)).into_iter().flatten() {
+                        push(d, o);
+                    }
+                }
+            }
+            JoinStrategy::IndexLookup => {
+                let index = &other.indexes[other_col];
+                other.index_last_used.insert(other_col.clone(), self.memory.tick());
+                // The index covers every row, so unless they all take part,
+                // as when the other side isn't filtered, its hits are checked
+                // against the rows that do. It leaves out rows missing the
+                // column, which are only looked for if a key is missing too.
+                let every_row = other_rows.len() == other.data.len();
+                let mut taking_part = Vec::new();
+                if !every_row {
+                    taking_part = vec![false; other.data.len()];
+                    other_rows.iter().for_each(|&o| taking_part[o] = true);
+                }
+                let mut missing: Option<Vec<usize>> = None;
+                for &d in driving_rows {
+                    match driving.data[d].get(driving_col) {
+                        Some(key) => {
+                            for o in index.get(key).iter().flat_map(|ids| other.positions(*ids)) {
+                                if every_row || taking_part[o] {
+                                    push(d, o);
+                                }
+                            }
                         }
-                        results.push(merged_row);
+                        None => missing
+                            .get_or_insert_with(|| {
+                                other_rows
+                                    .iter()
+                                    .copied()
+                                    .filter(|&o| other.data[o].get(other_col).is_none())
+                                    .collect()
+                            })
+                            .iter()
+                            .for_each(|&o| push(d, o)),
                     }
                 }
             }
         }
-        results
+        pairs
     }
 
     pub async fn aggregate(
@@ -825,11 +2237,15 @@ impl Database {
         table_name: &str,
         aggregate_query: &AggregateQuery,
     ) -> Result<(Value, Duration), String> {
+        self.check_open()?;
         let start = Instant::now();
-        let tables = self.tables.read().await;
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
         let table = tables
             .get(table_name)
             .ok_or_else(|| format!("Table {} not found", table_name))?;
+        if let Some(filter) = &aggregate_query.filter {
+            self.check_query(filter, &table.columns)?;
+        }
 
         let result = self.execute_aggregate_query(table, aggregate_query)?;
         Ok((result, start.elapsed()))
@@ -840,190 +2256,236 @@ impl Database {
         table: &Table,
         aggregate_query: &AggregateQuery,
     ) -> Result<Value, String> {
-        let rows_to_aggregate: Vec<&HashMap<String, Value>> =
+        let rows_to_aggregate: Vec<&Row> =
             if let Some(filter) = &aggregate_query.filter {
-                self.execute_query(table, filter)
+                self.execute_query(table, filter)?
                     .into_iter()
-                    .map(|i| &table.data[i])
+                    .map(|i| &*table.data[i])
                     .collect()
             } else {
-                table.data.iter().collect()
+                table.data.iter().map(|row| &**row).collect()
             };
 
         let values: Vec<&Value> = rows_to_aggregate
             .iter()
-            .filter_map(|row| row.get(&aggregate_query.column))
+            .filter_map(|row| group_by::aggregated(row, &aggregate_query.column))
             .collect();
-
-        match aggregate_query.function {
-            AggregateFunction::Count => Ok(Value::Integer(values.len() as i64)),
-            AggregateFunction::Sum => {
-                let mut sum = 0.0;
-                for value in values {
-                    match value {
-                        Value::Integer(i) => sum += *i as f64,
-                        Value::Float(f) => sum += *f,
-                        _ => {}
-                    }
-                }
-                Ok(Value::Float(sum))
-            }
-            AggregateFunction::Avg => {
-                let mut sum = 0.0;
-                let mut count = 0;
-                for value in values {
-                    match value {
-                        Value::Integer(i) => {
-                            sum += *i as f64;
-                            count += 1;
-                        }
-                        Value::Float(f) => {
-                            sum += *f;
-                            count += 1;
-                        }
-                        _ => {}
-                    }
-                }
-                if count == 0 {
-                    Ok(Value::Float(0.0))
-                } else {
-                    Ok(Value::Float(sum / count as f64))
-                }
-            }
-            AggregateFunction::Min => {
-                values.into_iter().min().map(|v| v.clone()).ok_or_else(|| "No values to aggregate".to_string())
-            }
-            AggregateFunction::Max => {
-                values.into_iter().max().map(|v| v.clone()).ok_or_else(|| "No values to aggregate".to_string())
-            }
-        }
+        aggregate_values(&aggregate_query.function, values).ok_or_else(|| "No values to aggregate".to_string())
     }
 
-    fn execute_query(&self, table: &Table, query: &Query) -> Vec<usize> {
-        match query {
+    // Positions of the rows matching `query`, in ascending order. Only a
+    // subquery can fail.
+    fn execute_query(&self, table: &Table, query: &Query) -> Result<Vec<usize>, String> {
+        Ok(match query {
             Query::Aggregate(_) => {
                 // This should be handled in the `select` function
                 // but we need to satisfy the compiler for now.
                 vec![]
             }
-            Query::Join(_) => {
+            Query::Join(_) | Query::Traverse(_) => {
                 // This should be handled in the `select` function
                 // but we need to satisfy the compiler for now.
                 vec![]
             }
             Query::MatchAll => (0..table.data.len()).collect(),
             Query::Condition(condition) => {
-                if let Some(index) = table.indexes.get(&condition.column) {
-                    let mut results = Vec::new();
-                    match condition.operator {
-                        Operator::Eq => {
-                            if let Some(indices) = index.get(&condition.value) {
-                                results.extend(indices.value().clone());
-                            }
-                        }
-                        Operator::NotEq => {
-                            for item in index.iter() {
-                                if *item.key() != condition.value {
-                                    results.extend(item.value().clone());
-                                }
-                            }
+                let rows = self.execute_condition(table, condition);
+                self.query_planner
+                    .observe(table, condition, table.data.len(), rows.len());
+                rows
+            }
+            Query::And(queries) => {
+                if queries.is_empty() {
+                    return Ok((0..table.data.len()).collect());
+                }
+                // Conditions after the first are checked on the rows left
+                // rather than run over the whole table.
+                let (mut final_result, scanned) = match self.range_scan(table, queries) {
+                    Some((rows, column)) => (rows, Some(column)),
+                    None => (self.execute_query(table, &queries[0])?, None),
+                };
+                for query in &queries[1..] {
+                    match query {
+                        Query::Condition(condition)
+                            if scanned == Some(condition.column.as_str()) && is_bound(&condition.operator) => {}
+                        Query::Condition(condition) => {
+                            let candidates = final_result.len();
+                            final_result.retain(|i| self.evaluate_condition(&table.data[*i], condition));
+                            self.query_planner
+                                .observe(table, condition, candidates, final_result.len());
                         }
-                        Operator::Gt => {
-                            for item in index.iter() {
-                                if *item.key() > condition.value {
-                                    results.extend(item.value().clone());
-                                }
-                            }
+                        _ => {
+                            let other_set: std::collections::HashSet<usize> =
+                                self.execute_query(table, query)?.into_iter().collect();
+                            final_result.retain(|item| other_set.contains(item));
                         }
-                        Operator::Gte => {
-                            for item in index.iter() {
-                                if *item.key() >= condition.value {
-                                    results.extend(item.value().clone());
-                                }
+                    }
+                }
+                final_result
+            }
+            Query::Or(queries) => {
+                // Rows come back once each, in table order.
+                let mut final_result = match self.query_planner.plan_or(queries, table) {
+                    OrStrategy::IndexUnion => {
+                        let mut keys: HashMap<&str, Vec<Value>> = HashMap::new();
+                        for q in queries {
+                            if let Query::Condition(condition) = q {
+                                keys.entry(condition.column.as_str())
+                                    .or_default()
+                                    .extend(condition.value.index_keys());
                             }
                         }
-                        Operator::Lt => {
-                            for item in index.iter() {
-                                if *item.key() < condition.value {
-                                    results.extend(item.value().clone());
+                        let mut results = Vec::new();
+                        for (column, keys) in keys {
+                            let index = &table.indexes[column];
+                            table.index_last_used.insert(column.to_string(), self.memory.tick());
+                            for key in keys {
+                                if let Some(ids) = index.get(&key) {
+                                    results.extend(table.positions(ids));
                                 }
                             }
                         }
-                        Operator::Lte => {
-                            for item in index.iter() {
-                                if *item.key() <= condition.value {
-                                    results.extend(item.value().clone());
-                                }
-                            }
+                        results
+                    }
+                    // Scanned branches only read columns, so matching them
+                    // can't fail.
+                    OrStrategy::Scan => {
+                        return Ok((0..table.data.len())
+                            .filter(|i| {
+                                queries.iter().any(|q| self.matches_query(&table.data[*i], q).is_ok_and(|m| m))
+                            })
+                            .collect());
+                    }
+                    OrStrategy::Branches => {
+                        let mut rows = Vec::new();
+                        for q in queries {
+                            rows.extend(self.execute_query(table, q)?);
                         }
+                        rows
                     }
-                    results
-                } else {
-                    (0..table.data.len())
-                        .filter(|i| self.evaluate_condition(&table.data[*i], condition))
-                        .collect()
-                }
-            }
-            Query::And(queries) => {
-                if queries.is_empty() {
-                    return (0..table.data.len()).collect();
-                }
-                let mut result_sets: Vec<Vec<usize>> = queries
-                    .iter()
-                    .map(|q| self.execute_query(table, q))
-                    .collect();
-
-                let mut final_result = result_sets[0].clone();
-                for i in 1..result_sets.len() {
-                    let other_set: std::collections::HashSet<usize> = result_sets[i].iter().cloned().collect();
-                    final_result.retain(|item| other_set.contains(item));
-                }
+                };
+                final_result.sort_unstable();
+                final_result.dedup();
                 final_result
             }
-            Query::Or(queries) => {
-                let mut final_result = std::collections::HashSet::new();
-                for q in queries {
-                    let result_set = self.execute_query(table, q);
-                    final_result.extend(result_set);
-                }
-                final_result.into_iter().collect()
-            }
-        }
+            Query::Compare(comparison) => (0..table.data.len())
+                .filter(|i| self.evaluate_comparison(&table.data[*i], comparison))
+                .collect(),
+            Query::Sample(sample) => sample.pick(self.execute_query(table, &sample.query)?),
+            Query::InSubquery {
+                column,
+                table: other,
+                subquery,
+                select_column,
+            } => self.rows_in(table, column, &self.subquery_values(other, subquery, select_column)?),
+        })
     }
 
-    fn evaluate_condition(&self, row: &HashMap<String, Value>, condition: &Condition) -> bool {
-        if let Some(value) = row.get(&condition.column) {
+    fn execute_condition(&self, table: &Table, condition: &Condition) -> Vec<usize> {
+        if let Some(results) = self.search_text_index(table, condition) {
+            return results;
+        }
+        let index = table
+            .indexes
+            .get(&condition.column)
+            .filter(|_| !table.indexes_stale);
+        if let Some(index) = index {
+            table
+                .index_last_used
+                .insert(condition.column.clone(), self.memory.tick());
+            let mut results = Vec::new();
             match condition.operator {
-                Operator::Eq => value == &condition.value,
-                Operator::NotEq => value != &condition.value,
-                Operator::Gt => value > &condition.value,
-                Operator::Gte => value >= &condition.value,
-                Operator::Lt => value < &condition.value,
-                Operator::Lte => value <= &condition.value,
+                Operator::Eq => {
+                    for key in condition.value.index_keys() {
+                        if let Some(ids) = index.get(&key) {
+                            results.extend(table.positions(ids));
+                        }
+                    }
+                }
+                Operator::Gt | Operator::Gte | Operator::Lt | Operator::Lte => {
+                    index.range(&[condition], |ids| results.extend(table.positions(ids)));
+                }
+                Operator::NotEq | Operator::ILike | Operator::Like | Operator::NotLike | Operator::Regex => {
+                    index.for_each(|value, ids| {
+                        if condition.operator.apply(value, &condition.value) {
+                            results.extend(table.positions(ids));
+                        }
+                    });
+                }
             }
+            // Index entries come in no particular order.
+            results.sort_unstable();
+            results
+        } else if let Some(dictionary) = table
+            .schema
+            .dictionary(&condition.column)
+            .filter(|_| matches!(condition.operator, Operator::Eq | Operator::NotEq))
+        {
+            // Compare codes rather than strings; rows that aren't
+            // encoded (nulls, or rows with a schema of their own) fall
+            // back to comparing values.
+            let wanted = match &condition.value {
+                Value::String(s) => dictionary.code(s),
+                _ => None,
+            };
+            let eq = matches!(condition.operator, Operator::Eq);
+            (0..table.data.len())
+                .filter(|i| {
+                    let row = &table.data[*i];
+                    match row.code(&condition.column, dictionary) {
+                        Some(code) => (Some(code) == wanted) == eq,
+                        None => self.evaluate_condition(row, condition),
+                    }
+                })
+                .collect()
+        } else if let Some(partitions) = table.pruned_partitions(condition) {
+            let mut results: Vec<usize> = partitions
+                .into_iter()
+                .flat_map(|ids| table.positions(ids))
+                .filter(|i| self.evaluate_condition(&table.data[*i], condition))
+                .collect();
+            results.sort_unstable();
+            results
         } else {
-            false
+            (0..table.data.len())
+                .filter(|i| self.evaluate_condition(&table.data[*i], condition))
+                .collect()
         }
     }
 
+    fn evaluate_condition(&self, row: &Row, condition: &Condition) -> bool {
+        row.get(&condition.column)
+            .is_some_and(|value| condition.operator.apply(value, &condition.value))
+    }
+
     fn update_internal(
         &self,
         tables: &mut HashMap<String, Table>,
         table_name: &str,
         query: &Query,
-        update_fn: fn(&mut HashMap<String, Value>),
-    ) -> Result<usize, String> {
+        update: RowUpdate,
+        changes: &mut Changes,
+    ) -> Result<usize, WriteError> {
+        self.check_writable(table_name)?;
         // First, check all constraints
         let table = tables
             .get(table_name)
             .ok_or_else(|| format!("Table {} not found", table_name))?;
+        self.check_query(query, &table.columns)?;
 
-        let indices_to_update = self.execute_query(table, query);
+        let indices_to_update = self.execute_query(table, query)?;
         let updated_count = indices_to_update.len();
+        let mut updated_rows = Vec::with_capacity(updated_count);
 
         for index in &indices_to_update {
-            let mut updated_row = table.data[*index].clone();
-            update_fn(&mut updated_row);
+            let mut updated_row = table.data[*index].to_map();
+            match update {
+                // Only functions run for the caller; expressions are replayed too.
+                RowUpdate::Fn(_) => self.guarded_update(table_name, &mut updated_row, |row| update.apply(row))?,
+                RowUpdate::Expr(_) => update.apply(&mut updated_row)?,
+            }
+            schema::check_row(&mut updated_row, &table.columns, self.config.unknown_columns)?;
+            coercion::coerce_row(&mut updated_row, &table.columns, self.config.coercion);
 
             for col in &table.columns {
                 let value = updated_row.get(&col.name);
@@ -1032,48 +2494,61 @@ impl Database {
                     match constraint {
                         Constraint::NotNull => {
                             if value.is_none() || value == Some(&Value::Null) {
-                                return Err(format!("Column {} cannot be null", col.name));
+                                return Err(ConstraintError::new(table_name, &col.name, constraint, value).into());
                             }
                         }
                         Constraint::Unique => {
                             if let Some(val) = value {
                                 if table.data.iter().enumerate().any(|(i, r)| i != *index && r.get(&col.name) == Some(val)) {
-                                    return Err(format!("Column {} must be unique", col.name));
+                                    return Err(ConstraintError::new(table_name, &col.name, constraint, value).into());
                                 }
                             }
                         }
                         Constraint::ForeignKey { table: fk_table, column: fk_column } => {
                             if let Some(val) = value {
                                 let foreign_table = tables.get(fk_table).ok_or_else(|| format!("Foreign key table {} not found", fk_table))?;
-                                if !foreign_table.data.iter().any(|r| r.get(fk_column) == Some(val)) {
-                                    return Err(format!("Foreign key violation on column {}", col.name));
+                                if !foreign_table.has_value(fk_column, val, self.memory.tick()) {
+                                    return Err(ConstraintError::new(table_name, &col.name, constraint, value).into());
                                 }
                             }
                         }
                     }
                 }
             }
+            updated_rows.push(updated_row);
         }
 
         // If all constraints are satisfied, perform the update
+        let updated_rows: Vec<Row> = updated_rows
+            .into_iter()
+            .map(|row| Row::from_map(&table.schema, row))
+            .collect();
+        let old_size: usize = indices_to_update
+            .iter()
+            .map(|i| table.data[*i].estimated_size())
+            .sum();
+        let new_size: usize = updated_rows.iter().map(Row::estimated_size).sum();
+        self.reserve_memory(tables, new_size.saturating_sub(old_size))?;
+
         let table = tables
             .get_mut(table_name)
             .ok_or_else(|| format!("Table {} not found", table_name))?;
 
-        for index in indices_to_update {
-            update_fn(&mut table.data[index]);
+        let watching = self.tracking_changes();
+        for (index, updated_row) in indices_to_update.into_iter().zip(updated_rows) {
+            let new = watching.then(|| updated_row.clone());
+            let old = table.replace_row(index, updated_row);
+            if let Some(new) = new {
+                let event = WatchEvent::Update {
+                    old: (*old).clone(),
+                    new,
+                };
+                changes.push((table_name.to_string(), table.row_ids[index], event));
+            }
         }
 
         if updated_count > 0 {
-            for (col_name, index) in &table.indexes {
-                index.clear();
-                for (i, row) in table.data.iter().enumerate() {
-                    if let Some(value) = row.get(col_name) {
-                        index.entry(value.clone()).or_insert_with(Vec::new).push(i);
-                    }
-                }
-            }
-            table.build_merkle_tree();
+            table.maintain(self.maintenance_deferred());
         }
 
         Ok(updated_count)
@@ -1083,20 +2558,23 @@ impl Database {
         &self,
         table_name: &str,
         query: &Query,
-        update_fn: fn(&mut HashMap<String, Value>),
-    ) -> Result<usize, String> {
-        let wal_entry = WalEntry::Update {
-            table_name: table_name.to_string(),
-            query: query.clone(),
-        };
-        self.wal_writer
-            .write()
-            .await
-            .log(&wal_entry)
-            .map_err(|e| e.to_string())?;
-
-        let mut tables = self.tables.write().await;
-        self.update_internal(&mut tables, table_name, query, update_fn)
+        update_fn: UpdateFn,
+    ) -> Result<usize, WriteError> {
+        self.check_open()?;
+        self.check_readable(table_name, query)?;
+        self.measured(table_name, OperationKind::Update, async {
+            let _admitted = self.admit(&[table_name]).await?;
+            let wal_entry = WalEntry::Update {
+                table_name: table_name.to_string(),
+                query: query.clone(),
+            };
+            let mut tables = self.log_and_lock(table_name, &wal_entry).await?;
+            let mut changes = Changes::new();
+            let updated = self.update_internal(&mut tables, table_name, query, RowUpdate::Fn(update_fn), &mut changes)?;
+            self.finish_write(&mut tables, &[table_name], changes);
+            Ok::<_, WriteError>(updated)
+        })
+        .await
     }
 
     fn delete_internal(
@@ -1104,57 +2582,70 @@ impl Database {
         tables: &mut HashMap<String, Table>,
         table_name: &str,
         query: &Query,
+        changes: &mut Changes,
     ) -> Result<usize, String> {
+        self.check_writable(table_name)?;
         let table = tables
             .get_mut(table_name)
             .ok_or_else(|| format!("Table {} not found", table_name))?;
+        self.check_query(query, &table.columns)?;
 
-        let indices_to_delete = self.execute_query(table, query);
+        let indices_to_delete = self.execute_query(table, query)?;
         let deleted_count = indices_to_delete.len();
 
-        let indices_to_delete_set: std::collections::HashSet<usize> =
-            indices_to_delete.into_iter().collect();
-
-        let mut new_data = Vec::new();
-        for (i, row) in table.data.iter().enumerate() {
-            if !indices_to_delete_set.contains(&i) {
-                new_data.push(row.clone());
+        let removed = table.remove_rows(&indices_to_delete);
+        if self.tracking_changes() {
+            for (id, row) in removed {
+                changes.push((table_name.to_string(), id, WatchEvent::Delete((*row).clone())));
             }
         }
-        table.data = new_data;
 
         if deleted_count > 0 {
-            for (col_name, index) in &table.indexes {
-                index.clear();
-                for (i, row) in table.data.iter().enumerate() {
-                    if let Some(value) = row.get(col_name) {
-                        index.entry(value.clone()).or_insert_with(Vec::new).push(i);
-                    }
-                }
-            }
-            table.build_merkle_tree();
+            table.maintain(self.maintenance_deferred());
         }
 
         Ok(deleted_count)
     }
 
-    pub async fn delete(&self, table_name: &str, query: &Query) -> Result<usize, String> {
+    pub async fn delete(&self, table_name: &str, query: &Query) -> Result<usize, WriteError> {
+        self.check_readable(table_name, query)?;
+        self.measured(table_name, OperationKind::Delete, async {
+            let _admitted = self.admit(&[table_name]).await?;
+            self.apply_delete(table_name, query).await
+        })
+        .await
+    }
+
+    // `delete` without admission, for WAL replay.
+    async fn apply_delete(&self, table_name: &str, query: &Query) -> Result<usize, WriteError> {
+        self.check_open()?;
         let wal_entry = WalEntry::Delete {
             table_name: table_name.to_string(),
             query: query.clone(),
         };
-        self.wal_writer
-            .write()
-            .await
-            .log(&wal_entry)
-            .map_err(|e| e.to_string())?;
+        let mut tables = self.log_and_lock(table_name, &wal_entry).await?;
+        let mut changes = Changes::new();
+        let deleted = self.delete_internal(&mut tables, table_name, query, &mut changes)?;
+        self.finish_write(&mut tables, &[table_name], changes);
+        Ok(deleted)
+    }
 
-        let mut tables = self.tables.write().await;
-        self.delete_internal(&mut tables, table_name, query)
+    pub async fn table_names(&self) -> Vec<String> {
+        let tables = self.read_tables_untimed().await;
+        let mut names: Vec<String> = tables.values().map(|t| t.name.clone()).collect();
+        names.sort();
+        names
+    }
+
+    pub async fn table_columns(&self, table_name: &str) -> Option<Vec<Column>> {
+        self.read_tables_untimed()
+            .await
+            .get(table_name)
+            .map(|t| t.columns.clone())
     }
 
     pub async fn verify_integrity(&self) -> bool {
-        let tables = self.tables.read().await;
+        let tables = self.read_tables_untimed().await;
         for table in tables.values() {
             if !table.verify_integrity() {
                 return false;
@@ -1163,53 +2654,3 @@ impl Database {
         true
     }
 }
-
-#[derive(Debug)]
-pub struct ConnectionError(String);
-
-impl fmt::Display for ConnectionError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
-    }
-}
-
-impl Error for ConnectionError {}
-
-pub struct DbConnectionManager {
-    key: [u8; 32],
-    wal_path: String,
-}
-
-impl DbConnectionManager {
-    pub fn new(key: [u8; 32], wal_path: &str) -> Self {
-        DbConnectionManager {
-            key,
-            wal_path: wal_path.to_string(),
-        }
-    }
-}
-
-impl r2d2::ManageConnection for DbConnectionManager {
-    type Connection = Database;
-    type Error = ConnectionError;
-
-    fn connect(&self) -> Result<Self::Connection, Self::Error> {
-        Ok(Database::new(self.key, &self.wal_path))
-    }
-
-    fn is_valid(&self, _conn: &mut Self::Connection) -> Result<(), Self::Error> {
-        Ok(())
-    }
-
-    fn has_broken(&self, _conn: &mut Self::Connection) -> bool {
-        false
-    }
-}
-
-pub type Pool = r2d2::Pool<DbConnectionManager>;
-pub type PooledConnection = r2d2::PooledConnection<DbConnectionManager>;
-
-pub fn create_pool(key: [u8; 32], wal_path: &str) -> Result<Pool, r2d2::Error> {
-    let manager = DbConnectionManager::new(key, wal_path);
-    r2d2::Pool::new(manager)
-}
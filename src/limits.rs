@@ -0,0 +1,287 @@
+//! Per-table limits, set with `DatabaseConfig::table_limits` or per tenant,
+//! and the errors writes return. Also the caps on what a select returns,
+//! set with `DatabaseConfig::result_limits`.
+
+use crate::{Busy, Constraint, Database, Row, Table, TableLimits, Timeout, Value};
+use std::borrow::Borrow;
+use std::error::Error;
+use std::fmt;
+
+/// A `TableLimits` cap an insert would have broken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LimitError {
+    TooManyRows { table: String, limit: usize },
+    TooManyBytes { table: String, limit: usize },
+    RowTooLarge { table: String, size: usize, limit: usize },
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LimitError::TooManyRows { table, limit } => {
+                write!(f, "Table {} is limited to {} rows", table, limit)
+            }
+            LimitError::TooManyBytes { table, limit } => {
+                write!(f, "Table {} is limited to {} bytes", table, limit)
+            }
+            LimitError::RowTooLarge { table, size, limit } => write!(
+                f,
+                "Row of {} bytes exceeds the limit of {} bytes for table {}",
+                size, limit, table
+            ),
+        }
+    }
+}
+
+impl Error for LimitError {}
+
+/// A column constraint a write would have broken, with the value that
+/// broke it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstraintError {
+    pub table: String,
+    pub column: String,
+    /// For a foreign key, this names the table and column the value
+    /// wasn't found in.
+    pub constraint: Constraint,
+    /// `Null` when a value was required but missing.
+    pub value: Value,
+}
+
+impl ConstraintError {
+    pub(crate) fn new(table: &str, column: &str, constraint: &Constraint, value: Option<&Value>) -> Self {
+        ConstraintError {
+            table: table.to_string(),
+            column: column.to_string(),
+            constraint: constraint.clone(),
+            value: value.cloned().unwrap_or(Value::Null),
+        }
+    }
+}
+
+impl fmt::Display for ConstraintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.constraint {
+            Constraint::NotNull => write!(f, "Column {} cannot be null", self.column),
+            Constraint::Unique => write!(f, "Column {} must be unique", self.column),
+            Constraint::ForeignKey { .. } => write!(f, "Foreign key violation on column {}", self.column),
+        }
+    }
+}
+
+impl Error for ConstraintError {}
+
+/// Why a write failed. Converts to and from `String`, so it works with `?`
+/// alongside the rest of the API.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WriteError {
+    Limit(LimitError),
+    Constraint(Box<ConstraintError>),
+    /// Turned away by the admission controller before anything was written.
+    Busy(Busy),
+    /// Gave up waiting for its turn before anything was written.
+    Timeout(Timeout),
+    /// Any other failure, such as a value of the wrong type.
+    Rejected(String),
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WriteError::Limit(e) => write!(f, "{}", e),
+            WriteError::Constraint(e) => write!(f, "{}", e),
+            WriteError::Busy(e) => write!(f, "{}", e),
+            WriteError::Timeout(e) => write!(f, "{}", e),
+            WriteError::Rejected(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for WriteError {}
+
+impl WriteError {
+    /// Whether the same write may succeed if tried again later.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, WriteError::Busy(_) | WriteError::Timeout(_))
+    }
+}
+
+impl From<Busy> for WriteError {
+    fn from(e: Busy) -> Self {
+        WriteError::Busy(e)
+    }
+}
+
+impl From<Timeout> for WriteError {
+    fn from(e: Timeout) -> Self {
+        WriteError::Timeout(e)
+    }
+}
+
+impl From<LimitError> for WriteError {
+    fn from(e: LimitError) -> Self {
+        WriteError::Limit(e)
+    }
+}
+
+impl From<ConstraintError> for WriteError {
+    fn from(e: ConstraintError) -> Self {
+        WriteError::Constraint(Box::new(e))
+    }
+}
+
+impl From<String> for WriteError {
+    fn from(message: String) -> Self {
+        WriteError::Rejected(message)
+    }
+}
+
+impl From<WriteError> for String {
+    fn from(e: WriteError) -> Self {
+        e.to_string()
+    }
+}
+
+/// A `ResultLimits` cap a select went over.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResultLimitError {
+    TooManyRows { table: String, limit: usize },
+    TooManyBytes { table: String, limit: usize },
+}
+
+impl fmt::Display for ResultLimitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResultLimitError::TooManyRows { table, limit } => {
+                write!(f, "Query on {} returns more than {} rows", table, limit)
+            }
+            ResultLimitError::TooManyBytes { table, limit } => {
+                write!(f, "Query on {} returns more than {} bytes", table, limit)
+            }
+        }
+    }
+}
+
+impl Error for ResultLimitError {}
+
+impl From<ResultLimitError> for String {
+    fn from(e: ResultLimitError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Why `select_with` failed. Converts to and from `String`, like
+/// `WriteError`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SelectError {
+    Limit(ResultLimitError),
+    /// Any other failure, such as a missing table or column.
+    Failed(String),
+}
+
+impl fmt::Display for SelectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectError::Limit(e) => write!(f, "{}", e),
+            SelectError::Failed(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl Error for SelectError {}
+
+impl From<ResultLimitError> for SelectError {
+    fn from(e: ResultLimitError) -> Self {
+        SelectError::Limit(e)
+    }
+}
+
+impl From<String> for SelectError {
+    fn from(message: String) -> Self {
+        SelectError::Failed(message)
+    }
+}
+
+impl From<SelectError> for String {
+    fn from(e: SelectError) -> Self {
+        e.to_string()
+    }
+}
+
+impl Database {
+    /// Holds the rows of a select on `table_name` to
+    /// `DatabaseConfig::result_limits`, failing or cutting them short.
+    pub(crate) fn limit_results<R: Borrow<Row>>(&self, table_name: &str, rows: &mut Vec<R>) -> Result<(), ResultLimitError> {
+        let limits = self.config.result_limits;
+        let mut keep = rows.len();
+        let mut exceeded = None;
+        if let Some(limit) = limits.max_rows.filter(|&limit| rows.len() > limit) {
+            keep = limit;
+            exceeded = Some(ResultLimitError::TooManyRows {
+                table: table_name.to_string(),
+                limit,
+            });
+        }
+        if let Some(limit) = limits.max_bytes {
+            let mut bytes = 0;
+            let over = rows[..keep].iter().position(|row| {
+                bytes += row.borrow().estimated_size();
+                bytes > limit
+            });
+            if let Some(fits) = over {
+                keep = fits;
+                exceeded = Some(ResultLimitError::TooManyBytes {
+                    table: table_name.to_string(),
+                    limit,
+                });
+            }
+        }
+        match exceeded {
+            Some(e) if !limits.truncate => Err(e),
+            _ => {
+                rows.truncate(keep);
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn check_limits(&self, table: &Table, row: &Row) -> Result<(), LimitError> {
+        if let Some(limits) = self.config.table_limits.get(&table.name) {
+            check(limits, table, row)?;
+        }
+        if let Some(limits) = self.tenants.limits_for(&table.name) {
+            check(&limits, table, row)?;
+        }
+        Ok(())
+    }
+}
+
+fn check(limits: &TableLimits, table: &Table, row: &Row) -> Result<(), LimitError> {
+    if let Some(limit) = limits.max_rows {
+        if table.data.len() >= limit {
+            return Err(LimitError::TooManyRows {
+                table: table.name.clone(),
+                limit,
+            });
+        }
+    }
+    let size = row.estimated_size();
+    if let Some(limit) = limits.max_row_bytes {
+        if size > limit {
+            return Err(LimitError::RowTooLarge {
+                table: table.name.clone(),
+                size,
+                limit,
+            });
+        }
+    }
+    if let Some(limit) = limits.max_bytes {
+        if table.data_bytes + table.dictionary_bytes() + size > limit {
+            return Err(LimitError::TooManyBytes {
+                table: table.name.clone(),
+                limit,
+            });
+        }
+    }
+    Ok(())
+}
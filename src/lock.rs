@@ -0,0 +1,79 @@
+//! Advisory locks, taken by `Database::open`.
+//!
+//! The lock is an OS file lock on a file next to the WAL
+//! (`<wal path>.lock`), held for as long as the database is open and
+//! released when it is closed or dropped, or when its process exits, however
+//! it exits. Taking it is a single atomic call, so two openers can't both
+//! get it, and a crashed process never leaves it behind. The file holds the
+//! PID of the owner, for the error a second opener gets; the file itself
+//! stays behind, and its presence means nothing.
+
+use crate::{Database, DatabaseConfig};
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Write};
+
+#[derive(Debug)]
+pub enum OpenError {
+    /// Another database holds the lock, in the process with this PID if it
+    /// has written it yet.
+    InUse { pid: Option<u32> },
+    Io(io::Error),
+}
+
+impl fmt::Display for OpenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OpenError::InUse { pid: Some(pid) } => write!(f, "Database is in use by process {}", pid),
+            OpenError::InUse { pid: None } => write!(f, "Database is in use by another process"),
+            OpenError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for OpenError {}
+
+impl From<io::Error> for OpenError {
+    fn from(e: io::Error) -> Self {
+        OpenError::Io(e)
+    }
+}
+
+// Holding the file holds the lock.
+#[derive(Debug)]
+pub(crate) struct LockFile {
+    _file: File,
+}
+
+impl LockFile {
+    pub(crate) fn acquire(wal_path: &str) -> Result<Self, OpenError> {
+        let path = format!("{}.lock", wal_path);
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let mut owner = String::new();
+                let pid = file.read_to_string(&mut owner).ok().and_then(|_| owner.trim().parse().ok());
+                return Err(OpenError::InUse { pid });
+            }
+            Err(TryLockError::Error(e)) => return Err(e.into()),
+        }
+        file.set_len(0)?;
+        file.write_all(std::process::id().to_string().as_bytes())?;
+        file.sync_all()?;
+        Ok(LockFile { _file: file })
+    }
+}
+
+impl Database {
+    /// Like `with_config`, but first takes the lock file for `wal_path`, so
+    /// that no other database opened this way can use the same WAL until
+    /// this one is closed or dropped.
+    pub fn open(key: [u8; 32], wal_path: &str, config: DatabaseConfig) -> Result<Self, OpenError> {
+        let lock = LockFile::acquire(wal_path)?;
+        let db = Self::with_config(key, wal_path, config);
+        *db.lock_file.lock().unwrap() = Some(lock);
+        Ok(db)
+    }
+}
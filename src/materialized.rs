@@ -0,0 +1,425 @@
+//! Materialized views.
+//!
+//! A materialized view is stored as a table, so it can be selected from and
+//! indexed like any other, but only the database writes to it. Every committed
+//! change to the source table is applied to the view in the same write:
+//! filter views add and remove the affected rows, and aggregate views keep a
+//! running count and sum, and for distinct counts how often each value
+//! occurs, so their approximate counts are exact. Min and max are recomputed only when the current
+//! extreme is removed, join views are recomputed whenever either side
+//! changes, and traversal views whenever their table changes.
+//!
+//! Views live in memory like ephemeral tables: they aren't written to the WAL
+//! or to snapshots, and are recomputed from their sources by `load`.
+//!
+//! A view's query can't contain a subquery, whose results change with writes
+//! to another table the view doesn't follow. Subqueries are the only part of
+//! a query that can fail to run, so maintaining a view can't fail.
+
+use crate::dependencies;
+use crate::subquery::has_subquery;
+use crate::traverse::DEPTH_COLUMN;
+use crate::watch::{Changes, WatchEvent};
+use crate::{
+    AggregateFunction, AggregateQuery, Column, Database, DataType, Query, Row, Table, Value,
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone)]
+struct MaterializedView {
+    source: String,
+    query: Query,
+    aggregate: Option<AggregateState>,
+}
+
+impl MaterializedView {
+    fn depends_on(&self, table_name: &str) -> bool {
+        self.source == table_name
+            || matches!(&self.query, Query::Join(join) if join.target_table == table_name)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct AggregateState {
+    count: i64,
+    sum: f64,
+    numeric: i64,
+    extreme: Option<Value>,
+    // Occurrences of each non-null value, for distinct counts.
+    distinct: HashMap<Value, i64>,
+    // The current min/max was removed; only a full recompute can find the next.
+    pub(crate) stale: bool,
+}
+
+impl AggregateState {
+    pub(crate) fn add(&mut self, function: &AggregateFunction, value: &Value) {
+        self.count += 1;
+        if let Some(number) = numeric(value) {
+            self.sum += number;
+            self.numeric += 1;
+        }
+        if matches!(function, AggregateFunction::CountDistinct | AggregateFunction::ApproxCountDistinct)
+            && *value != Value::Null
+        {
+            *self.distinct.entry(value.clone()).or_insert(0) += 1;
+        }
+        let replaces = match (&self.extreme, function) {
+            (None, _) => true,
+            (Some(extreme), AggregateFunction::Min) => value < extreme,
+            (Some(extreme), AggregateFunction::Max) => value >= extreme,
+            _ => false,
+        };
+        if replaces {
+            self.extreme = Some(value.clone());
+        }
+    }
+
+    pub(crate) fn remove(&mut self, function: &AggregateFunction, value: &Value) {
+        self.count -= 1;
+        if let Some(number) = numeric(value) {
+            self.sum -= number;
+            self.numeric -= 1;
+        }
+        if let Some(occurrences) = self.distinct.get_mut(value) {
+            *occurrences -= 1;
+            if *occurrences == 0 {
+                self.distinct.remove(value);
+            }
+        }
+        if matches!(function, AggregateFunction::Min | AggregateFunction::Max)
+            && self.extreme.as_ref() == Some(value)
+        {
+            self.stale = true;
+        }
+    }
+
+    pub(crate) fn result(&self, function: &AggregateFunction) -> Value {
+        match function {
+            AggregateFunction::Count => Value::Integer(self.count),
+            AggregateFunction::Sum => Value::Float(self.sum),
+            AggregateFunction::Avg if self.numeric == 0 => Value::Float(0.0),
+            AggregateFunction::Avg => Value::Float(self.sum / self.numeric as f64),
+            AggregateFunction::Min | AggregateFunction::Max => {
+                self.extreme.clone().unwrap_or(Value::Null)
+            }
+            AggregateFunction::CountDistinct | AggregateFunction::ApproxCountDistinct => {
+                Value::Integer(self.distinct.len() as i64)
+            }
+        }
+    }
+}
+
+fn numeric(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct MaterializedViews {
+    views: Mutex<HashMap<String, MaterializedView>>,
+}
+
+impl MaterializedViews {
+    // A copy for `Database::fork`.
+    pub(crate) fn fork(&self) -> Self {
+        MaterializedViews {
+            views: Mutex::new(self.views.lock().unwrap().clone()),
+        }
+    }
+
+    pub(crate) fn active(&self) -> bool {
+        !self.views.lock().unwrap().is_empty()
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.views.lock().unwrap().contains_key(name)
+    }
+
+    pub(crate) fn depending_on(&self, table_name: &str) -> Vec<String> {
+        let views = self.views.lock().unwrap();
+        let mut names: Vec<String> = views
+            .iter()
+            .filter(|(_, view)| view.depends_on(table_name))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        self.views.lock().unwrap().remove(name);
+    }
+
+    pub(crate) fn rename_table(&self, old: &str, new: &str) {
+        for view in self.views.lock().unwrap().values_mut() {
+            if view.source == old {
+                view.source = new.to_string();
+            }
+            dependencies::rename_joined_table(&mut view.query, old, new);
+        }
+    }
+}
+
+impl Database {
+    /// Creates a table named `name` holding the result of `query` over
+    /// `source_table`, kept up to date as the source changes. The view can be
+    /// queried and indexed, but not written to directly.
+    pub async fn create_materialized_view(
+        &self,
+        name: &str,
+        source_table: &str,
+        query: Query,
+    ) -> Result<(), String> {
+        self.check_open()?;
+        self.check_exportable(source_table)?;
+        if let Query::Join(join) = &query {
+            self.check_exportable(&join.target_table)?;
+        }
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        if tables.contains_key(name) || self.named_views.contains(name) {
+            return Err(format!("Table {} already exists", name));
+        }
+        let source = tables
+            .get(source_table)
+            .ok_or_else(|| format!("Table {} not found", source_table))?;
+        if has_subquery(&query) {
+            return Err("Queries with subqueries can't be materialized".to_string());
+        }
+        let columns = match &query {
+            Query::Aggregate(aggregate) if !aggregate.group_by.is_empty() => {
+                return Err("Grouped aggregates can't be materialized; use a rollup".to_string());
+            }
+            Query::Aggregate(aggregate) => {
+                let column = source
+                    .columns
+                    .iter()
+                    .find(|c| c.name == aggregate.column)
+                    .ok_or_else(|| format!("Column {} not found", aggregate.column))?;
+                let data_type = match aggregate.function {
+                    AggregateFunction::Count
+                    | AggregateFunction::CountDistinct
+                    | AggregateFunction::ApproxCountDistinct => DataType::Integer,
+                    AggregateFunction::Sum | AggregateFunction::Avg => DataType::Float,
+                    AggregateFunction::Min | AggregateFunction::Max => column.data_type.clone(),
+                };
+                vec![Column::new("result".to_string(), data_type, vec![])]
+            }
+            Query::Join(join) => {
+                let target = tables
+                    .get(&join.target_table)
+                    .ok_or_else(|| format!("Table {} not found", join.target_table))?;
+                join.check_columns(source_table)?;
+                unconstrained(&join.joined_columns(source, target))
+            }
+            Query::Traverse(traverse) => {
+                self.check_traverse(traverse, &source.columns)?;
+                let mut columns = unconstrained(&source.columns);
+                columns.push(Column::new(DEPTH_COLUMN.to_string(), DataType::Integer, vec![]));
+                columns
+            }
+            _ => unconstrained(&source.columns),
+        };
+
+        let mut view = MaterializedView {
+            source: source_table.to_string(),
+            query,
+            aggregate: None,
+        };
+        let mut table = Table::new(name.to_string(), columns, true);
+        self.recompute_view(&mut view, &mut table, &tables);
+        tables.insert(name.to_string(), table);
+        self.materialized.views.lock().unwrap().insert(name.to_string(), view);
+        self.publish(&tables, &[name]);
+        Ok(())
+    }
+
+    /// Recomputes a view from its source from scratch.
+    pub async fn refresh_materialized_view(&self, name: &str) -> Result<(), String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let mut views = self.materialized.views.lock().unwrap();
+        let view = views
+            .get_mut(name)
+            .ok_or_else(|| format!("Materialized view {} not found", name))?;
+        let mut table = tables
+            .remove(name)
+            .ok_or_else(|| format!("Table {} not found", name))?;
+        self.recompute_view(view, &mut table, &tables);
+        tables.insert(name.to_string(), table);
+        drop(views);
+        self.publish(&tables, &[name]);
+        Ok(())
+    }
+
+    pub async fn drop_materialized_view(&self, name: &str) -> Result<(), String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        if self.materialized.views.lock().unwrap().remove(name).is_none() {
+            return Err(format!("Materialized view {} not found", name));
+        }
+        tables.remove(name);
+        self.publish(&tables, &[]);
+        Ok(())
+    }
+
+    pub(crate) fn check_writable(&self, table_name: &str) -> Result<(), String> {
+        if self.materialized.contains(table_name) {
+            Err(format!("{} is a materialized view and can't be written to", table_name))
+        } else if self.rollups.contains(table_name) {
+            Err(format!("{} is a rollup and can't be written to", table_name))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Applies committed `changes` to the views over the changed tables and
+    /// returns the names of the views that were updated.
+    pub(crate) fn maintain_views(
+        &self,
+        tables: &mut HashMap<String, Table>,
+        changes: &Changes,
+    ) -> Vec<String> {
+        let mut views = self.materialized.views.lock().unwrap();
+        let mut updated = Vec::new();
+        for (name, view) in views.iter_mut() {
+            if !changes.iter().any(|(table, _, _)| view.depends_on(table)) {
+                continue;
+            }
+            let Some(mut table) = tables.remove(name) else {
+                continue;
+            };
+            let source_changes = changes.iter().filter(|(t, _, _)| *t == view.source);
+            let recompute = match &view.query {
+                // A sample can change when any row does.
+                Query::Join(_) | Query::Traverse(_) | Query::Sample(_) => true,
+                Query::Aggregate(aggregate) => {
+                    for (_, _, event) in source_changes {
+                        self.apply_to_aggregate(view.aggregate.get_or_insert_default(), aggregate, event);
+                    }
+                    view.aggregate.as_ref().is_none_or(|state| state.stale)
+                }
+                query => {
+                    for (_, _, event) in source_changes {
+                        self.apply_to_rows(&mut table, query, event);
+                    }
+                    false
+                }
+            };
+            if recompute {
+                self.recompute_view(view, &mut table, tables);
+            } else {
+                if let (Query::Aggregate(aggregate), Some(state)) = (&view.query, &view.aggregate) {
+                    let mut row = Row::with_schema(table.schema.clone());
+                    row.insert("result".to_string(), state.result(&aggregate.function));
+                    table.set_rows(vec![Arc::new(row)]);
+                }
+                table.maintain(self.maintenance_deferred());
+            }
+            tables.insert(name.clone(), table);
+            updated.push(name.clone());
+        }
+        updated
+    }
+
+    /// Recomputes every view, for after the source tables were replaced.
+    pub(crate) fn refresh_views(&self, tables: &mut HashMap<String, Table>) {
+        let mut views = self.materialized.views.lock().unwrap();
+        for (name, view) in views.iter_mut() {
+            if let Some(mut table) = tables.remove(name) {
+                self.recompute_view(view, &mut table, tables);
+                tables.insert(name.clone(), table);
+            }
+        }
+    }
+
+    fn apply_to_rows(&self, table: &mut Table, query: &Query, event: &WatchEvent) {
+        let (removed, added) = match event {
+            WatchEvent::Insert(row) => (None, Some(row)),
+            WatchEvent::Delete(row) => (Some(row), None),
+            WatchEvent::Update { old, new } => (Some(old), Some(new)),
+        };
+        let matching = |row: &Row| self.matches_query(row, query).is_ok_and(|m| m);
+        if let Some(row) = removed.filter(|row| matching(row)) {
+            if let Some(position) = table.data.iter().position(|r| **r == *row) {
+                table.remove_rows(&[position]);
+            }
+        }
+        if let Some(row) = added.filter(|row| matching(row)) {
+            table.push_row(row.conform(&table.schema));
+        }
+    }
+
+    fn apply_to_aggregate(&self, state: &mut AggregateState, aggregate: &AggregateQuery, event: &WatchEvent) {
+        let matching = |row: &Row| {
+            aggregate
+                .filter
+                .as_deref()
+                .is_none_or(|filter| self.matches_query(row, filter).is_ok_and(|m| m))
+        };
+        let (removed, added) = match event {
+            WatchEvent::Insert(row) => (None, Some(row)),
+            WatchEvent::Delete(row) => (Some(row), None),
+            WatchEvent::Update { old, new } => (Some(old), Some(new)),
+        };
+        if let Some(value) = removed.filter(|r| matching(r)).and_then(|r| r.get(&aggregate.column)) {
+            state.remove(&aggregate.function, value);
+        }
+        if let Some(value) = added.filter(|r| matching(r)).and_then(|r| r.get(&aggregate.column)) {
+            state.add(&aggregate.function, value);
+        }
+    }
+
+    fn recompute_view(
+        &self,
+        view: &mut MaterializedView,
+        table: &mut Table,
+        tables: &HashMap<String, Table>,
+    ) {
+        let rows = match (tables.get(&view.source), &view.query) {
+            (None, _) => Vec::new(),
+            (Some(source), Query::Join(join)) => match tables.get(&join.target_table) {
+                Some(target) => self.execute_join_query(source, target, join, None).unwrap_or_default(),
+                None => Vec::new(),
+            },
+            (Some(source), Query::Traverse(traverse)) => self.execute_traverse(source, traverse).unwrap_or_default(),
+            (Some(source), Query::Aggregate(aggregate)) => {
+                let mut state = AggregateState::default();
+                let rows: Vec<usize> = match &aggregate.filter {
+                    Some(filter) => self.execute_query(source, filter).unwrap_or_default(),
+                    None => (0..source.data.len()).collect(),
+                };
+                for i in rows {
+                    if let Some(value) = source.data[i].get(&aggregate.column) {
+                        state.add(&aggregate.function, value);
+                    }
+                }
+                let mut row = Row::new();
+                row.insert("result".to_string(), state.result(&aggregate.function));
+                view.aggregate = Some(state);
+                vec![row]
+            }
+            (Some(source), query) => {
+                let mut rows = self.execute_query(source, query).unwrap_or_default();
+                rows.sort_unstable();
+                rows.into_iter().map(|i| (*source.data[i]).clone()).collect()
+            }
+        };
+        let rows = rows
+            .into_iter()
+            .map(|row| Arc::new(row.conform(&table.schema)))
+            .collect();
+        table.set_rows(rows);
+        table.maintain(self.maintenance_deferred());
+    }
+}
+
+fn unconstrained(columns: &[Column]) -> Vec<Column> {
+    columns
+        .iter()
+        .map(|c| Column::new(c.name.clone(), c.data_type.clone(), vec![]))
+        .collect()
+}
@@ -0,0 +1,163 @@
+//! Memory accounting and eviction.
+//!
+//! Sizes are estimates: the in-memory size of each value plus its heap
+//! allocations, without allocator overhead. Tables keep a running total of
+//! their row data and of each index, so checking the budget on a write doesn't
+//! walk the data.
+//!
+//! When a write would take the database over `DatabaseConfig::memory_limit`,
+//! the least recently used indexes are dropped first; queries on those columns
+//! fall back to scans. If that isn't enough the write fails, so the limit is
+//! never exceeded by growth.
+//!
+//! The published copies readers use share their rows, indexes and Merkle
+//! leaves with the tables, so they aren't counted again. An evicted index is
+//! freed once its table is republished without it and queries still reading
+//! the older copy finish.
+
+use crate::{Database, Table, Value};
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+impl Value {
+    /// Estimated bytes used by the value, including heap allocations.
+    pub fn estimated_size(&self) -> usize {
+        let heap = match self {
+            Value::String(s) => s.capacity(),
+            Value::Json(j) => json_size(j),
+            _ => 0,
+        };
+        size_of::<Value>() + heap
+    }
+}
+
+fn json_size(json: &serde_json::Value) -> usize {
+    match json {
+        serde_json::Value::String(s) => s.capacity(),
+        serde_json::Value::Array(items) => items
+            .iter()
+            .map(|item| size_of::<serde_json::Value>() + json_size(item))
+            .sum(),
+        serde_json::Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| k.capacity() + size_of::<serde_json::Value>() + json_size(v))
+            .sum(),
+        _ => 0,
+    }
+}
+
+// Each index entry is the key plus one position.
+pub(crate) fn index_entry_size(value: &Value) -> usize {
+    value.estimated_size() + size_of::<usize>()
+}
+
+/// A snapshot of memory use and of what the budget has cost so far.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryStats {
+    pub limit: Option<usize>,
+    pub data_bytes: usize,
+    pub index_bytes: usize,
+    /// Indexes dropped to stay within the limit since the database was opened.
+    pub evicted_indexes: usize,
+    /// Writes refused because nothing was left to evict.
+    pub rejected_writes: usize,
+    /// Joins sorted in spill files because both sides were over
+    /// `DatabaseConfig::join_memory_limit`.
+    pub spilled_joins: usize,
+}
+
+impl MemoryStats {
+    pub fn used_bytes(&self) -> usize {
+        self.data_bytes + self.index_bytes
+    }
+
+    /// Fraction of the limit in use, or 0.0 without a limit.
+    pub fn pressure(&self) -> f64 {
+        match self.limit {
+            Some(0) => 1.0,
+            Some(limit) => self.used_bytes() as f64 / limit as f64,
+            None => 0.0,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct MemoryTracker {
+    clock: AtomicU64,
+    evicted_indexes: AtomicUsize,
+    rejected_writes: AtomicUsize,
+    pub(crate) spilled_joins: AtomicUsize,
+}
+
+impl MemoryTracker {
+    pub(crate) fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed) + 1
+    }
+}
+
+impl Database {
+    pub async fn memory_stats(&self) -> MemoryStats {
+        let tables = self.tables.read().await;
+        MemoryStats {
+            limit: self.config.memory_limit,
+            data_bytes: tables.values().map(|t| t.data_bytes + t.dictionary_bytes()).sum(),
+            index_bytes: tables.values().map(Table::index_bytes).sum(),
+            evicted_indexes: self.memory.evicted_indexes.load(Ordering::Relaxed),
+            rejected_writes: self.memory.rejected_writes.load(Ordering::Relaxed),
+            spilled_joins: self.memory.spilled_joins.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Makes room for `additional` bytes, evicting the coldest indexes if the
+    /// limit would otherwise be exceeded.
+    pub(crate) fn reserve_memory(
+        &self,
+        tables: &mut HashMap<String, Table>,
+        additional: usize,
+    ) -> Result<(), String> {
+        let limit = match self.config.memory_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let mut used: usize = tables
+            .values()
+            .map(|t| t.data_bytes + t.dictionary_bytes() + t.index_bytes())
+            .sum();
+        if used + additional <= limit {
+            return Ok(());
+        }
+
+        // `(last used, table, column, whether it's a text index)`.
+        let mut candidates: Vec<(u64, String, String, bool)> = tables
+            .values()
+            .flat_map(|t| {
+                let indexes = t
+                    .indexes
+                    .keys()
+                    .map(move |column| (t.index_last_used(column), t.name.clone(), column.clone(), false));
+                let text_indexes = t
+                    .text_indexes
+                    .iter()
+                    .map(move |(column, index)| (index.last_used(), t.name.clone(), column.clone(), true));
+                indexes.chain(text_indexes)
+            })
+            .collect();
+        candidates.sort();
+        for (_, table_name, column, text) in candidates {
+            if used + additional <= limit {
+                break;
+            }
+            if let Some(table) = tables.get_mut(&table_name) {
+                used -= if text { table.drop_text_index(&column) } else { table.drop_index(&column) };
+                self.memory.evicted_indexes.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        if used + additional > limit {
+            self.memory.rejected_writes.fetch_add(1, Ordering::Relaxed);
+            return Err(format!("Memory limit of {} bytes exceeded", limit));
+        }
+        Ok(())
+    }
+}
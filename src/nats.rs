@@ -0,0 +1,88 @@
+//! A change sink that publishes to a NATS subject.
+//!
+//! Speaks the NATS text protocol directly over TCP. Each change is published
+//! as one JSON message; a batch counts as accepted once the server answers
+//! the PING sent after it, which it does only after processing everything
+//! before. Point the subject at a JetStream stream to keep the messages.
+
+use crate::cdc::{ChangeRecord, ChangeSink, SinkFuture};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+pub struct NatsSink {
+    address: String,
+    subject: String,
+    connection: Option<BufReader<TcpStream>>,
+}
+
+impl NatsSink {
+    /// A sink publishing to `subject` on the server at `address`
+    /// (`host:port`). It connects on first use, and again after an error.
+    pub fn new(address: &str, subject: &str) -> Self {
+        NatsSink {
+            address: address.to_string(),
+            subject: subject.to_string(),
+            connection: None,
+        }
+    }
+
+    async fn connect(&self) -> Result<BufReader<TcpStream>, String> {
+        let stream = TcpStream::connect(&self.address)
+            .await
+            .map_err(|e| format!("Failed to connect to NATS at {}: {}", self.address, e))?;
+        let mut connection = BufReader::new(stream);
+        let info = read_line(&mut connection).await?;
+        if !info.starts_with("INFO") {
+            return Err(format!("Unexpected greeting from NATS: {}", info));
+        }
+        connection
+            .get_mut()
+            .write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false,\"lang\":\"rust\",\"name\":\"zapdb\"}\r\n")
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(connection)
+    }
+
+    async fn send(&mut self, changes: &[ChangeRecord]) -> Result<(), String> {
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => self.connect().await?,
+        };
+        let mut buffer = Vec::new();
+        for change in changes {
+            let payload = change.to_json().to_string();
+            buffer.extend_from_slice(format!("PUB {} {}\r\n", self.subject, payload.len()).as_bytes());
+            buffer.extend_from_slice(payload.as_bytes());
+            buffer.extend_from_slice(b"\r\n");
+        }
+        buffer.extend_from_slice(b"PING\r\n");
+        connection.get_mut().write_all(&buffer).await.map_err(|e| e.to_string())?;
+        loop {
+            let line = read_line(&mut connection).await?;
+            if line == "PONG" {
+                break;
+            } else if line == "PING" {
+                connection.get_mut().write_all(b"PONG\r\n").await.map_err(|e| e.to_string())?;
+            } else if let Some(error) = line.strip_prefix("-ERR") {
+                return Err(format!("NATS rejected the batch:{}", error));
+            }
+        }
+        self.connection = Some(connection);
+        Ok(())
+    }
+}
+
+async fn read_line(connection: &mut BufReader<TcpStream>) -> Result<String, String> {
+    let mut line = String::new();
+    match connection.read_line(&mut line).await {
+        Ok(0) => Err("NATS closed the connection".to_string()),
+        Ok(_) => Ok(line.trim_end().to_string()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+impl ChangeSink for NatsSink {
+    fn publish<'a>(&'a mut self, changes: &'a [ChangeRecord]) -> SinkFuture<'a> {
+        Box::pin(self.send(changes))
+    }
+}
@@ -1,39 +1,429 @@
-use crate::{Query, Table};
-use std::collections::HashMap;
+use crate::column_index::is_bound;
+use crate::{Comparison, Condition, Expr, IndexKind, Join, JoinType, Operator, Query, Table, Value};
+use dashmap::DashMap;
+use std::mem::{self, Discriminant};
+use std::sync::atomic::{AtomicU64, Ordering};
 
-pub struct QueryPlanner {}
+// Plans are cached by the shape of the query, its structure with the values
+// left out, so queries that only differ in their values share a plan. A
+// table takes a new epoch whenever its indexes or statistics change, which
+// retires the plans made for it.
+const MAX_CACHED_PLANS: usize = 1024;
+
+static EPOCH: AtomicU64 = AtomicU64::new(1);
+
+// Each time a condition runs, the rows it matched are compared with the
+// estimate, and the correction for its table, column and operator moves
+// this far towards the error.
+const FEEDBACK_WEIGHT: f64 = 0.2;
+// Corrections stay within a factor of about a thousand either way.
+const MAX_LOG_CORRECTION: f64 = 7.0;
+
+type CorrectionKey = (String, String, Discriminant<Operator>);
+
+#[derive(Clone, Copy, Default)]
+struct Correction {
+    /// Running average of ln(actual rows / estimated rows).
+    log_factor: f64,
+    /// `log_factor` when cached plans were last retired for it.
+    planned_with: f64,
+}
+
+pub(crate) fn next_epoch() -> u64 {
+    EPOCH.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum PlanKey {
+    And {
+        epoch: u64,
+        feedback: u64,
+        shape: String,
+    },
+    // Row counts are rounded to a power of two.
+    Join {
+        left: (u64, u32),
+        right: (u64, u32),
+        join: String,
+    },
+}
+
+#[derive(Clone)]
+enum CachedPlan {
+    /// The order to run an `And`'s subqueries in.
+    And(Vec<usize>),
+    Join(JoinPlan),
+}
+
+/// How a join finds the rows of one side that match a row of the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum JoinStrategy {
+    /// Compare against every row of the other side.
+    NestedLoop,
+    /// Look the key up in the other side's index on the join column.
+    IndexLookup,
+    /// Look the key up in a hash table built from the other side.
+    Hash,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct JoinPlan {
+    /// Whether the loop runs over the rows of the table being queried, or
+    /// over the join's target table.
+    pub(crate) drive_left: bool,
+    pub(crate) strategy: JoinStrategy,
+}
+
+/// How an `Or` finds its rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum OrStrategy {
+    /// Every branch is an equality on an indexed column: look all the
+    /// values up in the indexes.
+    IndexUnion,
+    /// Some branch has to read every row anyway: check all the branches on
+    /// each row in one pass.
+    Scan,
+    /// Run each branch on its own.
+    Branches,
+}
+
+/// A filter on a join's rows, split into what can run on each table before
+/// the join and what has to run on the joined rows.
+pub(crate) struct Pushdown {
+    pub(crate) left: Query,
+    pub(crate) right: Query,
+    pub(crate) residual: Option<Query>,
+}
+
+pub struct QueryPlanner {
+    cache: DashMap<PlanKey, CachedPlan>,
+    corrections: DashMap<CorrectionKey, Correction>,
+    // Bumped when a correction has moved enough to change plans.
+    feedback_version: AtomicU64,
+}
 
 impl QueryPlanner {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            cache: DashMap::new(),
+            corrections: DashMap::new(),
+            feedback_version: AtomicU64::new(0),
+        }
+    }
+
+    fn correction_key(table: &Table, condition: &Condition) -> CorrectionKey {
+        (table.name.clone(), condition.column.clone(), mem::discriminant(&condition.operator))
+    }
+
+    /// Estimated fraction of the table's rows matching `condition`, from its
+    /// statistics corrected by how earlier estimates turned out.
+    pub(crate) fn selectivity(&self, table: &Table, condition: &Condition) -> f64 {
+        let estimate = table.selectivity(condition);
+        match self.corrections.get(&Self::correction_key(table, condition)) {
+            Some(correction) => (estimate * correction.log_factor.exp()).min(1.0),
+            None => estimate,
+        }
+    }
+
+    /// Records that `condition`, run on `candidates` rows of the table,
+    /// matched `matched` of them.
+    pub(crate) fn observe(&self, table: &Table, condition: &Condition, candidates: usize, matched: usize) {
+        if candidates == 0 {
+            return;
+        }
+        let estimated = table.selectivity(condition) * candidates as f64;
+        let error = ((matched as f64 + 1.0) / (estimated + 1.0)).ln();
+        let mut correction = self.corrections.entry(Self::correction_key(table, condition)).or_default();
+        correction.log_factor = (correction.log_factor + FEEDBACK_WEIGHT * (error - correction.log_factor))
+            .clamp(-MAX_LOG_CORRECTION, MAX_LOG_CORRECTION);
+        if (correction.log_factor - correction.planned_with).abs() > std::f64::consts::LN_2 {
+            correction.planned_with = correction.log_factor;
+            self.feedback_version.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn cached(&self, key: &PlanKey) -> Option<CachedPlan> {
+        self.cache.get(key).map(|plan| plan.clone())
+    }
+
+    fn remember(&self, key: PlanKey, plan: CachedPlan) {
+        if self.cache.len() >= MAX_CACHED_PLANS {
+            self.cache.clear();
+        }
+        self.cache.insert(key, plan);
     }
 
     pub fn optimize(&self, query: Query, table: &Table) -> Query {
         self.optimize_query(query, table)
     }
 
+    /// Changes whenever the plans cached for `table` are retired.
+    pub(crate) fn plan_version(&self, table: &Table) -> (u64, u64) {
+        (table.plan_epoch, self.feedback_version.load(Ordering::Relaxed))
+    }
+
+    pub(crate) fn plan_or(&self, queries: &[Query], table: &Table) -> OrStrategy {
+        let indexed = |column: &str| table.indexes.contains_key(column) && !table.indexes_stale;
+        let index_eq =
+            |q: &Query| matches!(q, Query::Condition(c) if matches!(c.operator, Operator::Eq) && indexed(&c.column));
+        let scans = |q: &Query| match q {
+            Query::Condition(c) => !indexed(&c.column),
+            Query::Compare(_) => true,
+            _ => false,
+        };
+        if queries.iter().all(index_eq) {
+            OrStrategy::IndexUnion
+        } else if queries.iter().any(scans) && queries.iter().all(|q| query_columns(q, &mut Vec::new())) {
+            OrStrategy::Scan
+        } else {
+            OrStrategy::Branches
+        }
+    }
+
+    /// Splits `filter` into the parts that only read one table's columns,
+    /// which can filter that table before the join, and the rest. A table
+    /// whose unmatched rows are padded with nulls can only be filtered
+    /// early in an inner join, since filtering it afterwards also removes
+    /// rows padded on its behalf. The filter reads the joined rows' column
+    /// names, which are translated back for the parts run on one table.
+    pub(crate) fn push_down(&self, filter: Option<&Query>, left: &Table, right: &Table, join: &Join) -> Pushdown {
+        let mut conjuncts = Vec::new();
+        if let Some(filter) = filter {
+            flatten_and(filter, &mut conjuncts);
+        }
+        let (left_prefix, right_prefix) = join.prefixes(&left.name).unzip();
+        // The column of `table` a joined column is, if it's only that table's.
+        let column_of = |table: &Table, other: &Table, prefix: Option<&str>, name: &str| -> Option<String> {
+            let column = match prefix {
+                Some(prefix) => name.strip_prefix(prefix)?.strip_prefix('.')?,
+                None if other.columns.iter().any(|c| c.name == name) => return None,
+                None => name,
+            };
+            table.columns.iter().any(|c| c.name == column).then(|| column.to_string())
+        };
+        let owns = |table: &Table, other: &Table, prefix: Option<&str>, query: &Query, columns: &[&str]| {
+            if !columns.iter().all(|c| column_of(table, other, prefix, c).is_some()) {
+                return None;
+            }
+            Some(rename_columns(query, &|name| column_of(table, other, prefix, name).unwrap_or_default()))
+        };
+        let left_allowed = matches!(join.join_type, JoinType::Inner | JoinType::Left);
+        let right_allowed = matches!(join.join_type, JoinType::Inner | JoinType::Right);
+
+        let (mut on_left, mut on_right, mut residual) = (Vec::new(), Vec::new(), Vec::new());
+        for query in conjuncts {
+            let mut columns = Vec::new();
+            if !query_columns(query, &mut columns) || columns.is_empty() {
+                residual.push(query.clone());
+            } else if let Some(query) = owns(left, right, left_prefix, query, &columns).filter(|_| left_allowed) {
+                on_left.push(query);
+            } else if let Some(query) = owns(right, left, right_prefix, query, &columns).filter(|_| right_allowed) {
+                on_right.push(query);
+            } else {
+                residual.push(query.clone());
+            }
+        }
+        Pushdown {
+            left: self.optimize(Query::And(on_left), left),
+            right: self.optimize(Query::And(on_right), right),
+            residual: (!residual.is_empty()).then_some(Query::And(residual)),
+        }
+    }
+
+    /// Picks the side to loop over and how to find its matches, whichever
+    /// reads the fewest rows. Either choice gives the same rows in the same
+    /// order. `left_rows` and `right_rows` are how many rows of each table
+    /// take part, after any filters pushed down.
+    pub(crate) fn plan_join(
+        &self,
+        (left, left_rows): (&Table, usize),
+        (right, right_rows): (&Table, usize),
+        join: &Join,
+    ) -> JoinPlan {
+        let magnitude = |rows: usize| usize::BITS - rows.leading_zeros();
+        let key = PlanKey::Join {
+            left: (left.plan_epoch, magnitude(left_rows)),
+            right: (right.plan_epoch, magnitude(right_rows)),
+            join: format!("{:?}", join),
+        };
+        if let Some(CachedPlan::Join(plan)) = self.cached(&key) {
+            return plan;
+        }
+        let plan = self.choose_join(left, left_rows, right, right_rows, join);
+        self.remember(key, CachedPlan::Join(plan));
+        plan
+    }
+
+    fn choose_join(&self, left: &Table, left_rows: usize, right: &Table, right_rows: usize, join: &Join) -> JoinPlan {
+        let (left_col, right_col) = &join.on_condition;
+        let plan = |drive_left: bool| {
+            let (driving_rows, other, other_rows, other_col) = if drive_left {
+                (left_rows, right, right_rows, right_col)
+            } else {
+                (right_rows, left, left_rows, left_col)
+            };
+            let (driving_rows, other_rows) = (driving_rows as f64, other_rows as f64);
+            let indexed = other.indexes.contains_key(other_col) && !other.indexes_stale;
+            [
+                (JoinStrategy::NestedLoop, driving_rows * other_rows),
+                (JoinStrategy::Hash, driving_rows + 2.0 * other_rows),
+                (JoinStrategy::IndexLookup, if indexed { driving_rows } else { f64::INFINITY }),
+            ]
+            .into_iter()
+            .map(move |(strategy, cost)| (JoinPlan { drive_left, strategy }, cost))
+        };
+        plan(true)
+            .chain(plan(false))
+            .fold(None, |best: Option<(JoinPlan, f64)>, (plan, cost)| match best {
+                Some((_, best_cost)) if best_cost <= cost => best,
+                _ => Some((plan, cost)),
+            })
+            .map(|(plan, _)| plan)
+            .expect("there is always a plan")
+    }
+
     fn optimize_query(&self, query: Query, table: &Table) -> Query {
         match query {
-            Query::And(mut queries) => {
-                queries.sort_by_key(|q| self.estimate_cost(q, table));
-                Query::And(queries)
+            Query::And(queries) => {
+                let key = PlanKey::And {
+                    epoch: table.plan_epoch,
+                    feedback: self.feedback_version.load(Ordering::Relaxed),
+                    shape: format!("{:?}", queries.iter().map(shape).collect::<Vec<_>>()),
+                };
+                let order = match self.cached(&key) {
+                    Some(CachedPlan::And(order)) => order,
+                    _ => {
+                        // The first subquery picks the candidates the rest
+                        // filter, so the cheapest one that keeps the fewest
+                        // rows goes first.
+                        let costs: Vec<f64> = queries.iter().map(|q| self.estimate_cost(q, table)).collect();
+                        let mut order: Vec<usize> = (0..queries.len()).collect();
+                        order.sort_by(|a, b| costs[*a].partial_cmp(&costs[*b]).unwrap_or(std::cmp::Ordering::Equal));
+                        self.remember(key, CachedPlan::And(order.clone()));
+                        order
+                    }
+                };
+                let mut queries: Vec<Option<Query>> = queries.into_iter().map(Some).collect();
+                Query::And(order.iter().filter_map(|&i| queries[i].take()).collect())
             }
             _ => query,
         }
     }
 
-    fn estimate_cost(&self, query: &Query, table: &Table) -> u64 {
+    // Rows read to run the query, plus the rows it keeps.
+    fn estimate_cost(&self, query: &Query, table: &Table) -> f64 {
+        let row_count = table.data.len() as f64;
         match query {
             Query::Condition(condition) => {
-                if table.indexes.contains_key(&condition.column) {
-                    1 // Low cost for indexed columns
+                let rows = self.selectivity(table, condition) * row_count;
+                let indexed = table.indexes.get(&condition.column).is_some_and(|index| {
+                    matches!(condition.operator, Operator::Eq)
+                        || (index.kind() == IndexKind::Ordered && is_bound(&condition.operator))
+                }) || (table.text_indexed(&condition.column) && matches!(condition.operator, Operator::ILike | Operator::Like));
+                if indexed {
+                    rows * 2.0
+                } else if let Some(partitions) = table.pruned_partitions(condition) {
+                    partitions.iter().map(|ids| ids.len()).sum::<usize>() as f64 + rows
                 } else {
-                    10 // High cost for non-indexed columns
+                    row_count + rows
                 }
             }
             Query::And(queries) => queries.iter().map(|q| self.estimate_cost(q, table)).sum(),
             Query::Or(queries) => queries.iter().map(|q| self.estimate_cost(q, table)).sum(),
-            _ => 100, // Default high cost for other query types
+            _ => row_count * 10.0, // Default high cost for other query types
         }
     }
 }
+
+fn flatten_and<'a>(query: &'a Query, out: &mut Vec<&'a Query>) {
+    match query {
+        Query::And(queries) => queries.iter().for_each(|q| flatten_and(q, out)),
+        Query::MatchAll => {}
+        _ => out.push(query),
+    }
+}
+
+// Collects the columns `query` reads, or returns false if it isn't a plain
+// filter.
+fn query_columns<'a>(query: &'a Query, out: &mut Vec<&'a str>) -> bool {
+    fn expr_columns<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+        match expr {
+            Expr::Column(name) => out.push(name),
+            Expr::Literal(_) => {}
+            Expr::Call { args, .. } => args.iter().for_each(|a| expr_columns(a, out)),
+        }
+    }
+    match query {
+        Query::MatchAll => true,
+        Query::Condition(condition) => {
+            out.push(&condition.column);
+            true
+        }
+        Query::Compare(comparison) => {
+            expr_columns(&comparison.left, out);
+            expr_columns(&comparison.right, out);
+            true
+        }
+        Query::And(queries) | Query::Or(queries) => queries.iter().all(|q| query_columns(q, out)),
+        Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_) | Query::Sample(_) | Query::InSubquery { .. } => {
+            false
+        }
+    }
+}
+
+// `query` with each column renamed by `rename`.
+fn rename_columns(query: &Query, rename: &dyn Fn(&str) -> String) -> Query {
+    fn rename_expr(expr: &Expr, rename: &dyn Fn(&str) -> String) -> Expr {
+        match expr {
+            Expr::Column(name) => Expr::Column(rename(name)),
+            Expr::Literal(value) => Expr::Literal(value.clone()),
+            Expr::Call { function, args } => Expr::Call {
+                function: function.clone(),
+                args: args.iter().map(|a| rename_expr(a, rename)).collect(),
+            },
+        }
+    }
+    match query {
+        Query::Condition(condition) => Query::Condition(Condition {
+            column: rename(&condition.column),
+            ..condition.clone()
+        }),
+        Query::Compare(comparison) => Query::Compare(Comparison {
+            left: rename_expr(&comparison.left, rename),
+            operator: comparison.operator.clone(),
+            right: rename_expr(&comparison.right, rename),
+        }),
+        Query::And(queries) => Query::And(queries.iter().map(|q| rename_columns(q, rename)).collect()),
+        Query::Or(queries) => Query::Or(queries.iter().map(|q| rename_columns(q, rename)).collect()),
+        other => other.clone(),
+    }
+}
+
+// `query` with its values replaced by nulls.
+fn shape(query: &Query) -> Query {
+    fn expr_shape(expr: &Expr) -> Expr {
+        match expr {
+            Expr::Column(name) => Expr::Column(name.clone()),
+            Expr::Literal(_) => Expr::Literal(Value::Null),
+            Expr::Call { function, args } => Expr::Call {
+                function: function.clone(),
+                args: args.iter().map(expr_shape).collect(),
+            },
+        }
+    }
+    match query {
+        Query::Condition(condition) => Query::Condition(Condition {
+            column: condition.column.clone(),
+            operator: condition.operator.clone(),
+            value: Value::Null,
+        }),
+        Query::Compare(comparison) => Query::Compare(Comparison {
+            left: expr_shape(&comparison.left),
+            operator: comparison.operator.clone(),
+            right: expr_shape(&comparison.right),
+        }),
+        Query::And(queries) => Query::And(queries.iter().map(shape).collect()),
+        Query::Or(queries) => Query::Or(queries.iter().map(shape).collect()),
+        other => other.clone(),
+    }
+}
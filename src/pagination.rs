@@ -0,0 +1,64 @@
+use crate::top_k::{top_k, SortOrder};
+use crate::{Database, Query, Row, Value};
+
+#[derive(Clone, Debug)]
+pub struct Page {
+    pub rows: Vec<Row>,
+    /// Pass this back as `after` to fetch the next page; `None` on the last page.
+    pub next_cursor: Option<Value>,
+}
+
+impl Database {
+    /// Keyset pagination: returns up to `page_size` rows matching `query` whose
+    /// `order_column` is strictly greater than `after`, in ascending order.
+    /// `order_column` should be unique, otherwise rows sharing the cursor value
+    /// at a page boundary are skipped.
+    pub async fn select_page(
+        &self,
+        table_name: &str,
+        query: &Query,
+        order_column: &str,
+        after: Option<&Value>,
+        page_size: usize,
+    ) -> Result<Page, String> {
+        self.check_open()?;
+        if matches!(query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_)) {
+            return Err("Pagination is only supported for filter queries".to_string());
+        }
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        if !table.columns.iter().any(|c| c.name == order_column) {
+            return Err(format!("Column {} not found", order_column));
+        }
+        self.check_readable(table_name, query)?;
+        self.check_column_readable(table_name, order_column)?;
+
+        let optimized_query = self.query_planner.optimize(query.clone(), table);
+        let candidates = self
+            .execute_query(table, &optimized_query)?
+            .into_iter()
+            .filter_map(|i| table.data[i].get(order_column).map(|v| (v, i)))
+            .filter(|(v, _)| **v != Value::Null && after.is_none_or(|after| *v > after));
+        // One more than a page, to tell whether there is another.
+        let mut candidates = top_k(candidates, SortOrder::Ascending, page_size.saturating_add(1));
+
+        let has_more = candidates.len() > page_size;
+        candidates.truncate(page_size);
+        let next_cursor = if has_more {
+            candidates.last().map(|(v, _)| (*v).clone())
+        } else {
+            None
+        };
+        let rows = candidates
+            .into_iter()
+            .map(|(_, i)| {
+                let mut row = (*table.data[i]).clone();
+                self.hide_row_columns(table_name, query, &mut row);
+                row
+            })
+            .collect();
+        Ok(Page { rows, next_cursor })
+    }
+}
@@ -0,0 +1,265 @@
+//! Partitioning a table by the value of a column, or by the period a
+//! `DateTime` column falls in.
+//!
+//! A partitioned table is still one table: rows are stored and queried as
+//! before, and partitions come and go as rows with new keys are written and
+//! the last rows with a key are removed. The table keeps the ids of the rows
+//! in each partition, so a condition on the partition column only reads the
+//! partitions it could match, and `drop_partition` removes a whole partition
+//! without evaluating a query against every row. The partitioning is saved
+//! in snapshots and the WAL.
+
+use crate::row_ids::IdList;
+use crate::watch::{Changes, WatchEvent};
+use crate::{Condition, Database, DataType, Operator, Row, RowId, Table, Value, WalEntry, WriteError};
+use chrono::{DateTime, Datelike, Duration, Months, Timelike, Utc};
+use imbl::OrdMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Partitioning {
+    /// A partition for each value of the column.
+    Key { column: String },
+    /// A partition for each period of a `DateTime` column, keyed by the
+    /// period's start.
+    Time { column: String, period: Period },
+}
+
+/// A calendar period in UTC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Period {
+    Hour,
+    Day,
+    Month,
+    Year,
+}
+
+/// One partition of a table, from `Database::partitions`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Partition {
+    /// The partition's value, or the start of its period. `Value::Null` for
+    /// the rows without a value in the partition column.
+    pub key: Value,
+    pub rows: usize,
+}
+
+#[derive(Clone)]
+pub(crate) struct Partitions {
+    pub(crate) by: Partitioning,
+    // Row ids in ascending order, by partition key. `None` holds the rows
+    // without a value in the partition column.
+    rows: OrdMap<Option<Value>, IdList>,
+}
+
+impl Period {
+    pub(crate) fn start(self, at: &DateTime<Utc>) -> DateTime<Utc> {
+        let date = at.date_naive();
+        let start = match self {
+            Period::Hour => date.and_hms_opt(at.hour(), 0, 0),
+            Period::Day => date.and_hms_opt(0, 0, 0),
+            Period::Month => date.with_day(1).and_then(|d| d.and_hms_opt(0, 0, 0)),
+            Period::Year => date.with_ordinal(1).and_then(|d| d.and_hms_opt(0, 0, 0)),
+        };
+        start.expect("the start of a period is a valid time").and_utc()
+    }
+
+    fn end(self, start: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Period::Hour => start.checked_add_signed(Duration::hours(1)),
+            Period::Day => start.checked_add_signed(Duration::days(1)),
+            Period::Month => start.checked_add_months(Months::new(1)),
+            Period::Year => start.checked_add_months(Months::new(12)),
+        }
+    }
+}
+
+impl Partitioning {
+    pub fn column(&self) -> &str {
+        match self {
+            Partitioning::Key { column } | Partitioning::Time { column, .. } => column,
+        }
+    }
+
+    fn key(&self, row: &Row) -> Option<Value> {
+        match (self, row.get(self.column())?) {
+            (_, Value::Null) => None,
+            (Partitioning::Time { period, .. }, Value::DateTime(at)) => Some(Value::DateTime(period.start(at))),
+            (_, value) => Some(value.clone()),
+        }
+    }
+
+    // Whether a row in the partition `key` could match `condition` on the
+    // partition column.
+    fn may_match(&self, key: &Value, condition: &Condition) -> bool {
+        let (Partitioning::Time { period, .. }, Value::DateTime(start), Value::DateTime(value)) =
+            (self, key, &condition.value)
+        else {
+            return match self {
+                Partitioning::Key { .. } => condition.operator.apply(key, &condition.value),
+                Partitioning::Time { .. } => true,
+            };
+        };
+        let before_end = period.end(start).is_none_or(|end| *value < end);
+        match condition.operator {
+            Operator::Eq => start <= value && before_end,
+            Operator::NotEq | Operator::ILike | Operator::Like | Operator::NotLike | Operator::Regex => true,
+            Operator::Lt => start < value,
+            Operator::Lte => start <= value,
+            Operator::Gt | Operator::Gte => before_end,
+        }
+    }
+}
+
+impl Table {
+    pub(crate) fn partitioning(&self) -> Option<&Partitioning> {
+        self.partitions.as_ref().map(|p| &p.by)
+    }
+
+    /// Partitions the table by `by`, or stops partitioning it.
+    pub(crate) fn set_partitioning(&mut self, by: Option<Partitioning>) {
+        self.partitions = by.map(|by| {
+            let mut rows: OrdMap<Option<Value>, IdList> = OrdMap::new();
+            for (row, id) in self.data.iter().zip(&self.row_ids) {
+                rows.entry(by.key(row)).or_default().push_back(*id);
+            }
+            Partitions { by, rows }
+        });
+    }
+
+    pub(crate) fn partition_row(&mut self, id: RowId, row: &Row) {
+        if let Some(partitions) = &mut self.partitions {
+            let ids = partitions.rows.entry(partitions.by.key(row)).or_default();
+            let position = ids.binary_search(&id).unwrap_or_else(|at| at);
+            ids.insert(position, id);
+        }
+    }
+
+    pub(crate) fn unpartition_rows(&mut self, rows: &[(RowId, &Row)]) {
+        let Some(partitions) = &mut self.partitions else {
+            return;
+        };
+        for (id, row) in rows {
+            let key = partitions.by.key(row);
+            if let Some(ids) = partitions.rows.get_mut(&key) {
+                if let Ok(position) = ids.binary_search(id) {
+                    ids.remove(position);
+                }
+                if ids.is_empty() {
+                    partitions.rows.remove(&key);
+                }
+            }
+        }
+    }
+
+    /// The ids in the partitions that may hold rows matching `condition`,
+    /// or `None` if the table isn't partitioned on its column.
+    pub(crate) fn pruned_partitions(&self, condition: &Condition) -> Option<Vec<&IdList>> {
+        let partitions = self
+            .partitions
+            .as_ref()
+            .filter(|p| p.by.column() == condition.column)?;
+        Some(
+            partitions
+                .rows
+                .iter()
+                .filter(|(key, _)| key.as_ref().is_none_or(|key| partitions.by.may_match(key, condition)))
+                .map(|(_, ids)| ids)
+                .collect(),
+        )
+    }
+}
+
+impl Database {
+    /// Partitions the table by `by`, including the rows already in it.
+    /// Replaces any earlier partitioning of the table.
+    pub async fn partition_table(&self, table_name: &str, by: Partitioning) -> Result<(), String> {
+        self.check_open()?;
+        {
+            let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+            let table = tables
+                .get(table_name)
+                .ok_or_else(|| format!("Table {} not found", table_name))?;
+            let column = table
+                .columns
+                .iter()
+                .find(|c| c.name == by.column())
+                .ok_or_else(|| format!("Column {} not found in table {}", by.column(), table_name))?;
+            if matches!(by, Partitioning::Time { .. }) && column.data_type != DataType::DateTime {
+                return Err(format!("Column {} is not a DateTime column", column.name));
+            }
+        }
+        let wal_entry = WalEntry::PartitionTable {
+            table_name: table_name.to_string(),
+            partitioning: by.clone(),
+        };
+        let mut tables = self.log_and_lock(table_name, &wal_entry).await?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        table.set_partitioning(Some(by));
+        table.plan_epoch = crate::optimizer::next_epoch();
+        self.publish(&tables, &[table_name]);
+        Ok(())
+    }
+
+    /// The table's partitions, in key order, with rows without a key first.
+    pub async fn partitions(&self, table_name: &str) -> Result<Vec<Partition>, String> {
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let partitions = table
+            .partitions
+            .as_ref()
+            .ok_or_else(|| format!("Table {} is not partitioned", table_name))?;
+        Ok(partitions
+            .rows
+            .iter()
+            .map(|(key, ids)| Partition {
+                key: key.clone().unwrap_or(Value::Null),
+                rows: ids.len(),
+            })
+            .collect())
+    }
+
+    /// Deletes every row in the partition `key`, as listed by `partitions`,
+    /// and returns how many there were.
+    pub async fn drop_partition(&self, table_name: &str, key: &Value) -> Result<usize, WriteError> {
+        let _admitted = self.admit(&[table_name]).await?;
+        self.apply_drop_partition(table_name, key).await
+    }
+
+    // `drop_partition` without admission, for WAL replay.
+    pub(crate) async fn apply_drop_partition(&self, table_name: &str, key: &Value) -> Result<usize, WriteError> {
+        self.check_open()?;
+        let wal_entry = WalEntry::DropPartition {
+            table_name: table_name.to_string(),
+            key: key.clone(),
+        };
+        let mut tables = self.log_and_lock(table_name, &wal_entry).await?;
+        self.check_writable(table_name)?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let partitions = table
+            .partitions
+            .as_ref()
+            .ok_or_else(|| format!("Table {} is not partitioned", table_name))?;
+        let key = Some(key.clone()).filter(|k| *k != Value::Null);
+        let positions: Vec<usize> = match partitions.rows.get(&key) {
+            Some(ids) => table.positions(ids).collect(),
+            None => return Ok(0),
+        };
+
+        let removed = table.remove_rows(&positions);
+        table.maintain(self.maintenance_deferred());
+        let mut changes = Changes::new();
+        if self.tracking_changes() {
+            for (id, row) in &removed {
+                changes.push((table_name.to_string(), *id, WatchEvent::Delete((**row).clone())));
+            }
+        }
+        self.finish_write(&mut tables, &[table_name], changes);
+        Ok(removed.len())
+    }
+}
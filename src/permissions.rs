@@ -0,0 +1,300 @@
+//! Column permissions for the session's role.
+//!
+//! `DatabaseConfig::roles` lists, for each role, the columns it may not
+//! see and those it may not write. A session acting as the role gets rows
+//! without its hidden columns from every select, including joins, traversals
+//! and projections, and can't filter, sort, group or aggregate on them, so
+//! their values can't be inferred from what a query matches. That includes
+//! the filters of join and traversal views and of SQL joins, which are
+//! checked against the names the hidden columns have in the joined rows.
+//! History is returned without the hidden columns. Writes that set a hidden
+//! or read-only column fail, and `update` hands its function the row without
+//! the hidden columns. Bundles, tenant exports, materialized views and
+//! rollups refuse a role with hidden columns in the tables they copy.
+//!
+//! Permissions are checked where the public methods take their arguments,
+//! not where writes are applied, so WAL replay and replication apply every
+//! change whatever the session. Watches and change sinks see whole rows.
+
+use crate::functions::Expr;
+use crate::join_columns::joined_name;
+use crate::{Database, Operation, Query, Row, RolePermissions, UpdateExpr, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+impl Database {
+    // The session's role and what it may not do, if anything.
+    fn restrictions(&self) -> Option<(String, &RolePermissions)> {
+        if self.config.roles.is_empty() {
+            return None;
+        }
+        let role = self.session.read().unwrap().role.clone()?;
+        let permissions = self.config.roles.get(&role)?;
+        Some((role, permissions))
+    }
+
+    fn hidden_columns(&self, table_name: &str) -> Option<(String, &HashSet<String>)> {
+        let (role, permissions) = self.restrictions()?;
+        permissions.hidden.get(table_name).map(|hidden| (role, hidden))
+    }
+
+    /// Fails if the session's role can't read `column` of `table_name`.
+    pub(crate) fn check_column_readable(&self, table_name: &str, column: &str) -> Result<(), String> {
+        match self.hidden_columns(table_name) {
+            Some((role, hidden)) if hidden.contains(column) => Err(format!(
+                "Column {} of {} is hidden from role {}",
+                column, table_name, role
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if `query`, run on `table_name`, reads a column hidden from the
+    /// session's role.
+    pub(crate) fn check_readable(&self, table_name: &str, query: &Query) -> Result<(), String> {
+        fn expr_columns<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+            match expr {
+                Expr::Column(name) => out.push(name),
+                Expr::Literal(_) => {}
+                Expr::Call { args, .. } => args.iter().for_each(|a| expr_columns(a, out)),
+            }
+        }
+        if self.restrictions().is_none() {
+            return Ok(());
+        }
+        match query {
+            Query::MatchAll => Ok(()),
+            Query::Condition(condition) => self.check_column_readable(table_name, &condition.column),
+            Query::Compare(comparison) => {
+                let mut columns = Vec::new();
+                expr_columns(&comparison.left, &mut columns);
+                expr_columns(&comparison.right, &mut columns);
+                columns.into_iter().try_for_each(|c| self.check_column_readable(table_name, c))
+            }
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter().try_for_each(|q| self.check_readable(table_name, q))
+            }
+            Query::Join(join) => {
+                self.check_column_readable(table_name, &join.on_condition.0)?;
+                self.check_column_readable(&join.target_table, &join.on_condition.1)
+            }
+            Query::Aggregate(aggregate) => {
+                if aggregate.column != "*" {
+                    self.check_column_readable(table_name, &aggregate.column)?;
+                }
+                for column in &aggregate.group_by {
+                    self.check_column_readable(table_name, column)?;
+                }
+                match &aggregate.filter {
+                    Some(filter) => self.check_readable(table_name, filter),
+                    None => Ok(()),
+                }
+            }
+            Query::Traverse(traverse) => {
+                self.check_column_readable(table_name, &traverse.from)?;
+                self.check_column_readable(table_name, &traverse.to)?;
+                self.check_readable(table_name, &traverse.start)
+            }
+            Query::Sample(sample) => self.check_readable(table_name, &sample.query),
+            Query::InSubquery {
+                column,
+                table,
+                subquery,
+                select_column,
+            } => {
+                self.check_column_readable(table_name, column)?;
+                self.check_column_readable(table, select_column)?;
+                self.check_readable(table, subquery)
+            }
+        }
+    }
+
+    /// Fails if `filter`, kept for the rows of the join or traversal `query`
+    /// on `table_name`, reads a column hidden from the session's role, under
+    /// the name it has in those rows.
+    pub(crate) fn check_filter_readable(&self, table_name: &str, query: &Query, filter: &Query) -> Result<(), String> {
+        if self.restrictions().is_none() {
+            return Ok(());
+        }
+        self.check_filter_names(filter, &self.hidden_names(table_name, query))
+    }
+
+    /// Fails if `filter` reads one of the `hidden` columns of the rows it's
+    /// kept for.
+    pub(crate) fn check_filter_names(&self, filter: &Query, hidden: &[String]) -> Result<(), String> {
+        fn expr_names<'a>(expr: &'a Expr, out: &mut Vec<&'a str>) {
+            match expr {
+                Expr::Column(name) => out.push(name),
+                Expr::Literal(_) => {}
+                Expr::Call { args, .. } => args.iter().for_each(|a| expr_names(a, out)),
+            }
+        }
+        let Some((role, _)) = self.restrictions() else {
+            return Ok(());
+        };
+        let mut names = Vec::new();
+        match filter {
+            Query::Condition(condition) => names.push(condition.column.as_str()),
+            Query::Compare(comparison) => {
+                expr_names(&comparison.left, &mut names);
+                expr_names(&comparison.right, &mut names);
+            }
+            Query::InSubquery {
+                column,
+                table,
+                subquery,
+                select_column,
+            } => {
+                self.check_column_readable(table, select_column)?;
+                self.check_readable(table, subquery)?;
+                names.push(column);
+            }
+            Query::And(queries) | Query::Or(queries) => {
+                return queries.iter().try_for_each(|q| self.check_filter_names(q, hidden));
+            }
+            _ => {}
+        }
+        match names.into_iter().find(|name| hidden.iter().any(|h| h == name)) {
+            Some(name) => Err(format!("Column {} is hidden from role {}", name, role)),
+            None => Ok(()),
+        }
+    }
+
+    /// Fails if `expr` reads a column hidden from the session's role.
+    pub(crate) fn check_expr_readable(&self, table_name: &str, expr: &Expr) -> Result<(), String> {
+        match expr {
+            Expr::Column(name) => self.check_column_readable(table_name, name),
+            Expr::Literal(_) => Ok(()),
+            Expr::Call { args, .. } => args.iter().try_for_each(|a| self.check_expr_readable(table_name, a)),
+        }
+    }
+
+    /// Fails if `table_name` has columns hidden from the session's role, for
+    /// exports that copy whole tables.
+    pub(crate) fn check_exportable(&self, table_name: &str) -> Result<(), String> {
+        match self.hidden_columns(table_name) {
+            Some((role, hidden)) if !hidden.is_empty() => Err(format!(
+                "Table {} has columns hidden from role {} and can't be exported",
+                table_name, role
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    // The names the hidden columns have in the rows `query` returns.
+    fn hidden_names(&self, table_name: &str, query: &Query) -> Vec<String> {
+        match query {
+            Query::Join(join) => {
+                let (left, right) = join.prefixes(table_name).unzip();
+                let mut names = self.hidden_joined(table_name, left);
+                names.extend(self.hidden_joined(&join.target_table, right));
+                names
+            }
+            _ => self.hidden_joined(table_name, None),
+        }
+    }
+
+    /// The names the columns of `table_name` hidden from the session's role
+    /// have in joined rows, given the table's prefix.
+    pub(crate) fn hidden_joined(&self, table_name: &str, prefix: Option<&str>) -> Vec<String> {
+        let Some((_, permissions)) = self.restrictions() else {
+            return Vec::new();
+        };
+        permissions
+            .hidden
+            .get(table_name)
+            .into_iter()
+            .flatten()
+            .map(|column| joined_name(prefix, column))
+            .collect()
+    }
+
+    /// Removes the columns hidden from the session's role from `rows`, the
+    /// result of `query` on `table_name`.
+    pub(crate) fn hide_columns(&self, table_name: &str, query: &Query, rows: &mut [Arc<Row>]) {
+        let names = self.hidden_names(table_name, query);
+        if names.is_empty() {
+            return;
+        }
+        for row in rows {
+            if names.iter().any(|name| row.contains_key(name)) {
+                let row = Arc::make_mut(row);
+                for name in &names {
+                    row.remove(name);
+                }
+            }
+        }
+    }
+
+    /// `hide_columns` for a single row.
+    pub(crate) fn hide_row_columns(&self, table_name: &str, query: &Query, row: &mut Row) {
+        for name in self.hidden_names(table_name, query) {
+            row.remove(&name);
+        }
+    }
+
+    /// Fails if the session's role can't write `column` of `table_name`.
+    pub(crate) fn check_column_writable(&self, table_name: &str, column: &str) -> Result<(), String> {
+        let Some((role, permissions)) = self.restrictions() else {
+            return Ok(());
+        };
+        let listed = |columns: &HashMap<String, HashSet<String>>| {
+            columns.get(table_name).is_some_and(|columns| columns.contains(column))
+        };
+        if listed(&permissions.hidden) || listed(&permissions.read_only) {
+            return Err(format!("Column {} of {} can't be written by role {}", column, table_name, role));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_row_writable(&self, table_name: &str, row: &HashMap<String, Value>) -> Result<(), String> {
+        row.keys().try_for_each(|column| self.check_column_writable(table_name, column))
+    }
+
+    pub(crate) fn check_expr_writable(&self, table_name: &str, expr: &UpdateExpr) -> Result<(), String> {
+        match expr {
+            UpdateExpr::Increment { column, .. } => self.check_column_writable(table_name, column),
+            UpdateExpr::Set(values) => values.iter().try_for_each(|(column, _)| self.check_column_writable(table_name, column)),
+        }
+    }
+
+    /// Checks what a queued write in a transaction or batch reads and writes.
+    pub(crate) fn check_operation(&self, operation: &Operation) -> Result<(), String> {
+        match operation {
+            Operation::Insert { table_name, row } | Operation::InsertOnConflict { table_name, row, .. } => {
+                self.check_row_writable(table_name, row)
+            }
+            Operation::Update { table_name, query } | Operation::Delete { table_name, query } => {
+                self.check_readable(table_name, query)
+            }
+        }
+    }
+
+    /// Runs an `update` function on `row` without the columns hidden from
+    /// the session's role, then fails if it changed a column the role can't
+    /// write.
+    pub(crate) fn guarded_update(
+        &self,
+        table_name: &str,
+        row: &mut HashMap<String, Value>,
+        update: impl FnOnce(&mut HashMap<String, Value>) -> Result<(), String>,
+    ) -> Result<(), String> {
+        if self.restrictions().is_none() {
+            return update(row);
+        }
+        let hidden: Vec<String> = self.hidden_columns(table_name).map(|(_, h)| h.iter().cloned().collect()).unwrap_or_default();
+        let hidden_values: Vec<(String, Value)> =
+            hidden.iter().filter_map(|c| row.remove(c).map(|v| (c.clone(), v))).collect();
+        let before = row.clone();
+        update(row)?;
+        let columns: HashSet<&String> = before.keys().chain(row.keys()).collect();
+        for column in columns {
+            if before.get(column) != row.get(column) {
+                self.check_column_writable(table_name, column)?;
+            }
+        }
+        row.extend(hidden_values);
+        Ok(())
+    }
+}
+
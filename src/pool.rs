@@ -0,0 +1,222 @@
+//! Connection pooling with r2d2.
+//!
+//! Each connection is its own `Database` handle on the shared WAL. The pool
+//! caps how many are open, makes `get` wait in line for a free one up to a
+//! timeout, checks a connection on checkout and replaces it if it has been
+//! closed, and closes connections left idle. `Pool::metrics` reports what
+//! the pool is doing, including how long `get` takes.
+
+use crate::{Database, DatabaseConfig, Instant, SessionContext};
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct ConnectionError(String);
+
+impl fmt::Display for ConnectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ConnectionError {}
+
+pub struct DbConnectionManager {
+    key: [u8; 32],
+    wal_path: String,
+    config: DatabaseConfig,
+    session: SessionContext,
+}
+
+impl DbConnectionManager {
+    pub fn new(key: [u8; 32], wal_path: &str) -> Self {
+        Self::with_config(key, wal_path, DatabaseConfig::default())
+    }
+
+    pub fn with_config(key: [u8; 32], wal_path: &str, config: DatabaseConfig) -> Self {
+        Self::with_session(key, wal_path, config, SessionContext::default())
+    }
+
+    /// A manager whose connections start each checkout with `session`.
+    pub fn with_session(key: [u8; 32], wal_path: &str, config: DatabaseConfig, session: SessionContext) -> Self {
+        DbConnectionManager {
+            key,
+            wal_path: wal_path.to_string(),
+            config,
+            session,
+        }
+    }
+}
+
+impl r2d2::ManageConnection for DbConnectionManager {
+    type Connection = Database;
+    type Error = ConnectionError;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        let db = Database::with_config(self.key, &self.wal_path, self.config.clone());
+        db.set_session(self.session.clone());
+        Ok(db)
+    }
+
+    // Runs on each checkout, so a session changed by the last user doesn't
+    // carry over to the next.
+    fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        conn.check_open().map_err(ConnectionError)?;
+        conn.set_session(self.session.clone());
+        Ok(())
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.is_closed()
+    }
+}
+
+pub type PooledConnection = r2d2::PooledConnection<DbConnectionManager>;
+
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    /// The most connections open at once.
+    pub max_connections: u32,
+    /// How long `get` waits for a free connection before failing.
+    pub acquire_timeout: Duration,
+    /// How many idle connections to keep open, or `None` for as many as
+    /// `max_connections`.
+    pub min_idle: Option<u32>,
+    /// How long a connection can stay idle before it is closed, if there are
+    /// more than `min_idle`.
+    pub idle_timeout: Option<Duration>,
+    /// The context each checkout starts with.
+    pub session: SessionContext,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_connections: 10,
+            acquire_timeout: Duration::from_secs(30),
+            min_idle: None,
+            idle_timeout: Some(Duration::from_secs(600)),
+            session: SessionContext::default(),
+        }
+    }
+}
+
+/// A point-in-time view of a pool, from `Pool::metrics`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PoolMetrics {
+    /// Open connections, idle or in use.
+    pub connections: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    /// Callers waiting in `get`.
+    pub waiters: usize,
+    /// Successful `get`s so far.
+    pub acquired: u64,
+    /// `get`s that gave up after `acquire_timeout`.
+    pub timeouts: u64,
+    pub mean_acquire_latency: Duration,
+    pub max_acquire_latency: Duration,
+}
+
+#[derive(Default)]
+struct PoolStats {
+    waiters: AtomicUsize,
+    acquired: AtomicU64,
+    timeouts: AtomicU64,
+    total_nanos: AtomicU64,
+    max_nanos: AtomicU64,
+}
+
+#[derive(Clone)]
+pub struct Pool {
+    inner: r2d2::Pool<DbConnectionManager>,
+    stats: Arc<PoolStats>,
+}
+
+impl Pool {
+    /// Checks out a connection, waiting up to the pool's `acquire_timeout`
+    /// for one to be free.
+    pub fn get(&self) -> Result<PooledConnection, r2d2::Error> {
+        self.stats.waiters.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let result = self.inner.get();
+        self.stats.waiters.fetch_sub(1, Ordering::Relaxed);
+        if result.is_ok() {
+            let nanos = start.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+            self.stats.acquired.fetch_add(1, Ordering::Relaxed);
+            self.stats.total_nanos.fetch_add(nanos, Ordering::Relaxed);
+            self.stats.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+        } else {
+            self.stats.timeouts.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        let state = self.inner.state();
+        let acquired = self.stats.acquired.load(Ordering::Relaxed);
+        let total_nanos = self.stats.total_nanos.load(Ordering::Relaxed);
+        PoolMetrics {
+            connections: state.connections,
+            idle: state.idle_connections,
+            in_use: state.connections - state.idle_connections,
+            waiters: self.stats.waiters.load(Ordering::Relaxed),
+            acquired,
+            timeouts: self.stats.timeouts.load(Ordering::Relaxed),
+            mean_acquire_latency: Duration::from_nanos(total_nanos.checked_div(acquired).unwrap_or(0)),
+            max_acquire_latency: Duration::from_nanos(self.stats.max_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    pub fn max_connections(&self) -> u32 {
+        self.inner.max_size()
+    }
+}
+
+pub fn create_pool(key: [u8; 32], wal_path: &str) -> Result<Pool, r2d2::Error> {
+    create_pool_with_config(key, wal_path, DatabaseConfig::default())
+}
+
+pub fn create_pool_with_config(
+    key: [u8; 32],
+    wal_path: &str,
+    config: DatabaseConfig,
+) -> Result<Pool, r2d2::Error> {
+    create_pool_with_options(key, wal_path, config, PoolConfig::default())
+}
+
+/// A pool whose connections each start a checkout with `session`; change it
+/// for one checkout with `set_session`.
+pub fn create_pool_with_session(
+    key: [u8; 32],
+    wal_path: &str,
+    config: DatabaseConfig,
+    session: SessionContext,
+) -> Result<Pool, r2d2::Error> {
+    create_pool_with_options(key, wal_path, config, PoolConfig { session, ..Default::default() })
+}
+
+/// A pool sized and timed by `pool`. Fails if the first `min_idle`
+/// connections can't be opened within `acquire_timeout`.
+pub fn create_pool_with_options(
+    key: [u8; 32],
+    wal_path: &str,
+    config: DatabaseConfig,
+    pool: PoolConfig,
+) -> Result<Pool, r2d2::Error> {
+    let manager = DbConnectionManager::with_session(key, wal_path, config, pool.session);
+    let inner = r2d2::Pool::builder()
+        .max_size(pool.max_connections)
+        .connection_timeout(pool.acquire_timeout)
+        .min_idle(pool.min_idle)
+        .idle_timeout(pool.idle_timeout)
+        .test_on_check_out(true)
+        .build(manager)?;
+    Ok(Pool {
+        inner,
+        stats: Arc::default(),
+    })
+}
@@ -0,0 +1,195 @@
+//! Prepared queries with bind parameters.
+//!
+//! A query template holds `Value::Param(i)` in place of the values of its
+//! conditions, and of any literal in a comparison. `Database::prepare`
+//! checks the template against the table and plans it once;
+//! `Database::execute_prepared` binds the `i`th value to each `Param(i)` and
+//! runs the plan as it stands, skipping the checks and the planner.
+//!
+//! The plan is made again, and the template checked again, once the table's
+//! indexes or statistics change or the planner's estimates have moved, just
+//! as cached plans are retired. Only filters on tables can be prepared; a
+//! parameter left unbound in a query run any other way is an error.
+
+use crate::text_index::check_regex;
+use crate::{Comparison, Condition, Database, Expr, Instant, Operator, Query, Row, Table, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// A query planned once to be run many times, from `Database::prepare`.
+pub struct PreparedQuery {
+    table: String,
+    query: Query,
+    params: usize,
+    // Parameters that are regex patterns, checked as they are bound.
+    regex_params: Vec<usize>,
+    // The plan, and the planner's `plan_version` it was made at.
+    plan: Mutex<((u64, u64), Query)>,
+}
+
+impl PreparedQuery {
+    pub fn table_name(&self) -> &str {
+        &self.table
+    }
+
+    /// The template, as it was prepared.
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// How many values `execute_prepared` takes: one more than the highest
+    /// parameter in the template.
+    pub fn param_count(&self) -> usize {
+        self.params
+    }
+}
+
+// The highest parameter in `query` plus one, and those used as regexes.
+fn params(query: &Query, count: &mut usize, regex: &mut Vec<usize>) {
+    fn expr_params(expr: &Expr, count: &mut usize) {
+        match expr {
+            Expr::Literal(Value::Param(i)) => *count = (*count).max(i + 1),
+            Expr::Call { args, .. } => args.iter().for_each(|a| expr_params(a, count)),
+            _ => {}
+        }
+    }
+    match query {
+        Query::Condition(condition) => {
+            if let Value::Param(i) = condition.value {
+                *count = (*count).max(i + 1);
+                if matches!(condition.operator, Operator::Regex) {
+                    regex.push(i);
+                }
+            }
+        }
+        Query::Compare(comparison) => {
+            expr_params(&comparison.left, count);
+            expr_params(&comparison.right, count);
+        }
+        Query::And(queries) | Query::Or(queries) => queries.iter().for_each(|q| params(q, count, regex)),
+        _ => {}
+    }
+}
+
+// `query` with each `Param(i)` replaced by `values[i]`.
+pub(crate) fn bind_value(value: &Value, values: &[Value]) -> Value {
+    match value {
+        Value::Param(i) => values[*i].clone(),
+        other => other.clone(),
+    }
+}
+
+pub(crate) fn bind(query: &Query, values: &[Value]) -> Query {
+    fn bind_expr(expr: &Expr, values: &[Value]) -> Expr {
+        match expr {
+            Expr::Column(name) => Expr::Column(name.clone()),
+            Expr::Literal(value) => Expr::Literal(bind_value(value, values)),
+            Expr::Call { function, args } => Expr::Call {
+                function: function.clone(),
+                args: args.iter().map(|a| bind_expr(a, values)).collect(),
+            },
+        }
+    }
+    match query {
+        Query::Condition(condition) => Query::Condition(Condition {
+            column: condition.column.clone(),
+            operator: condition.operator.clone(),
+            value: bind_value(&condition.value, values),
+        }),
+        Query::Compare(comparison) => Query::Compare(Comparison {
+            left: bind_expr(&comparison.left, values),
+            operator: comparison.operator.clone(),
+            right: bind_expr(&comparison.right, values),
+        }),
+        Query::And(queries) => Query::And(queries.iter().map(|q| bind(q, values)).collect()),
+        Query::Or(queries) => Query::Or(queries.iter().map(|q| bind(q, values)).collect()),
+        Query::InSubquery {
+            column,
+            table,
+            subquery,
+            select_column,
+        } => Query::InSubquery {
+            column: column.clone(),
+            table: table.clone(),
+            subquery: Box::new(bind(subquery, values)),
+            select_column: select_column.clone(),
+        },
+        other => other.clone(),
+    }
+}
+
+impl Database {
+    /// Checks and plans `query`, a filter on the table `table_name` with
+    /// parameters, as described in the module docs.
+    pub async fn prepare(&self, table_name: &str, query: Query) -> Result<PreparedQuery, String> {
+        self.check_open()?;
+        if matches!(query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_) | Query::Sample(_)) {
+            return Err("Only filters can be prepared".to_string());
+        }
+        if self.named_views.contains(table_name) {
+            return Err(format!("{} is a view; only queries on tables can be prepared", table_name));
+        }
+        let (mut count, mut regex_params) = (0, Vec::new());
+        params(&query, &mut count, &mut regex_params);
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let plan = self.plan_template(&query, count, table)?;
+        Ok(PreparedQuery {
+            table: table_name.to_string(),
+            query,
+            params: count,
+            regex_params,
+            plan: Mutex::new(plan),
+        })
+    }
+
+    fn plan_template(&self, query: &Query, params: usize, table: &Table) -> Result<((u64, u64), Query), String> {
+        let version = self.query_planner.plan_version(table);
+        self.check_query(&bind(query, &vec![Value::Null; params]), &table.columns)?;
+        Ok((version, self.query_planner.optimize(query.clone(), table)))
+    }
+
+    /// Runs `prepared` with `params` bound to its parameters, in order, and
+    /// returns the rows as `select` does.
+    pub async fn execute_prepared(
+        &self,
+        prepared: &PreparedQuery,
+        params: &[Value],
+    ) -> Result<(Vec<Row>, Duration), String> {
+        let start = Instant::now();
+        self.check_open()?;
+        if params.len() != prepared.params {
+            return Err(format!("Expected {} parameters, got {}", prepared.params, params.len()));
+        }
+        for &i in &prepared.regex_params {
+            if let Value::String(pattern) = &params[i] {
+                check_regex(pattern)?;
+            }
+        }
+
+        self.check_readable(&prepared.table, &prepared.query)?;
+
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(&prepared.table)
+            .ok_or_else(|| format!("Table {} not found", prepared.table))?;
+        let query = {
+            let mut plan = prepared.plan.lock().unwrap();
+            if plan.0 != self.query_planner.plan_version(table) {
+                *plan = self.plan_template(&prepared.query, prepared.params, table)?;
+            }
+            bind(&plan.1, params)
+        };
+
+        let mut results: Vec<Arc<Row>> = self
+            .execute_query(table, &query)?
+            .into_iter()
+            .map(|i| Arc::clone(&table.data[i]))
+            .collect();
+        self.hide_columns(&prepared.table, &query, &mut results);
+        self.limit_results(&prepared.table, &mut results)?;
+        Ok((results.into_iter().map(Arc::unwrap_or_clone).collect(), start.elapsed()))
+    }
+}
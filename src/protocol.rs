@@ -0,0 +1,625 @@
+//! JSON query protocol.
+//!
+//! This is the stable wire format for clients that can't construct `Query`
+//! values directly. Values are plain JSON and are converted using the column's
+//! `DataType`, so `25` is an Integer for an Integer column and a Float for a
+//! Float column. DateTime and Uuid values are strings.
+//!
+//! ```json
+//! "match_all"
+//! {"condition": {"column": "age", "op": "gte", "value": 25}}
+//! {"and": [<query>, ...]}
+//! {"or": [<query>, ...]}
+//! {"join": {"type": "inner", "table": "posts", "on": ["id", "user_id"]}}
+//! {"aggregate": {"function": "count", "column": "id", "filter": <query>, "group_by": ["team"]}}
+//! {"compare": {"left": <expr>, "op": "gt", "right": <expr>}}
+//! {"traverse": {"start": <query>, "from": "id", "to": "manager_id", "max_depth": 3}}
+//! {"sample": {"query": <query>, "rows": 100, "seed": 7}}
+//! {"in_subquery": {"column": "customer_id", "table": "customers", "query": <query>, "select_column": "id"}}
+//! ```
+//!
+//! `op` is one of `eq`, `not_eq`, `gt`, `gte`, `lt`, `lte`, `ilike`, `like`,
+//! `not_like`, `regex`; join `type` is one of `inner`, `left`, `right`;
+//! `function` is one of `count`, `sum`, `avg`, `min`, `max`, `count_distinct`,
+//! `approx_count_distinct`; `count`'s `column` may be `"*"` to count rows.
+//! `filter`, `group_by` and `max_depth` are optional, as is a join's
+//! `columns`, which is `merge`, `prefixed` or `[table_alias, target_alias]`.
+//! A sample takes either `rows` or `fraction`, a number from 0 to 1. An `in_subquery`'s `query` is parsed
+//! against the columns of its `table`, so it can only be parsed by
+//! `Database::query_from_json`. Unknown keys are rejected.
+//!
+//! An `<expr>` is `{"column": "age"}`, `{"value": 25}` or
+//! `{"call": {"function": "score", "args": [<expr>, ...]}}`. A value compared
+//! directly with a column takes the column's type; other values are typed from
+//! the JSON (integer, float, string, boolean or null).
+//!
+//! To build a query from user input, don't write the input into the JSON;
+//! write a template that refers to bind parameters by name, and pass the
+//! values separately to `Query::from_json_with_params` or
+//! `Database::query_from_json_with_params`. A condition takes
+//! `"param": "name"` in place of `"value"`, and `{"param": "name"}` is an
+//! `<expr>`. Parameterized templates may not contain literal values, so a
+//! template that has had input written into it is rejected rather than run.
+
+use crate::functions::value_type;
+use crate::{
+    AggregateFunction, AggregateQuery, Coercion, Column, Comparison, Condition, DataType, Database, Expr,
+    Join, JoinColumns, JoinType, Operator, Query, Sample, SampleSize, Traverse,
+};
+use serde_json::{json, Map, Value as Json};
+use std::collections::HashMap;
+
+/// Bind parameter values, by name.
+pub type Params = HashMap<String, crate::Value>;
+
+impl Query {
+    /// Parses a query in the JSON protocol format, validating column names and
+    /// value types against `columns`. Join targets are not checked; use
+    /// `Database::query_from_json` to validate those too.
+    pub fn from_json(json: &str, columns: &[Column]) -> Result<Query, String> {
+        let parsed: Json = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+        parse_query(&parsed, columns, None, None, "$")
+    }
+
+    /// Parses a parameterized query template, binding `params`. Every
+    /// parameter the template uses must be given.
+    pub fn from_json_with_params(json: &str, columns: &[Column], params: &Params) -> Result<Query, String> {
+        let parsed: Json = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+        parse_query(&parsed, columns, None, Some(params), "$")
+    }
+
+    pub fn to_json(&self) -> Json {
+        match self {
+            Query::MatchAll => json!("match_all"),
+            Query::Condition(condition) => json!({
+                "condition": {
+                    "column": condition.column,
+                    "op": operator_name(&condition.operator),
+                    "value": condition.value.to_json(),
+                }
+            }),
+            Query::And(queries) => json!({ "and": queries.iter().map(Query::to_json).collect::<Vec<_>>() }),
+            Query::Or(queries) => json!({ "or": queries.iter().map(Query::to_json).collect::<Vec<_>>() }),
+            Query::Join(join) => {
+                let mut body = Map::new();
+                body.insert("type".to_string(), json!(join_type_name(&join.join_type)));
+                body.insert("table".to_string(), json!(join.target_table));
+                body.insert("on".to_string(), json!([join.on_condition.0, join.on_condition.1]));
+                match &join.columns {
+                    JoinColumns::Merge => {}
+                    JoinColumns::Prefixed => {
+                        body.insert("columns".to_string(), json!("prefixed"));
+                    }
+                    JoinColumns::Aliased(table, target) => {
+                        body.insert("columns".to_string(), json!([table, target]));
+                    }
+                }
+                json!({ "join": body })
+            }
+            Query::Aggregate(aggregate) => {
+                let mut body = Map::new();
+                body.insert("function".to_string(), json!(function_name(&aggregate.function)));
+                body.insert("column".to_string(), json!(aggregate.column));
+                if let Some(filter) = &aggregate.filter {
+                    body.insert("filter".to_string(), filter.to_json());
+                }
+                if !aggregate.group_by.is_empty() {
+                    body.insert("group_by".to_string(), json!(aggregate.group_by));
+                }
+                json!({ "aggregate": body })
+            }
+            Query::Traverse(traverse) => {
+                let mut body = Map::new();
+                body.insert("start".to_string(), traverse.start.to_json());
+                body.insert("from".to_string(), json!(traverse.from));
+                body.insert("to".to_string(), json!(traverse.to));
+                if let Some(max_depth) = traverse.max_depth {
+                    body.insert("max_depth".to_string(), json!(max_depth));
+                }
+                json!({ "traverse": body })
+            }
+            Query::Sample(sample) => {
+                let mut body = Map::new();
+                body.insert("query".to_string(), sample.query.to_json());
+                match sample.size {
+                    SampleSize::Fraction(fraction) => body.insert("fraction".to_string(), json!(fraction)),
+                    SampleSize::Rows(rows) => body.insert("rows".to_string(), json!(rows)),
+                };
+                body.insert("seed".to_string(), json!(sample.seed));
+                json!({ "sample": body })
+            }
+            Query::InSubquery {
+                column,
+                table,
+                subquery,
+                select_column,
+            } => json!({
+                "in_subquery": {
+                    "column": column,
+                    "table": table,
+                    "query": subquery.to_json(),
+                    "select_column": select_column,
+                }
+            }),
+            Query::Compare(comparison) => json!({
+                "compare": {
+                    "left": expr_to_json(&comparison.left),
+                    "op": operator_name(&comparison.operator),
+                    "right": expr_to_json(&comparison.right),
+                }
+            }),
+        }
+    }
+}
+
+fn expr_to_json(expr: &Expr) -> Json {
+    match expr {
+        Expr::Column(name) => json!({ "column": name }),
+        Expr::Literal(value) => json!({ "value": value.to_json() }),
+        Expr::Call { function, args } => json!({
+            "call": {
+                "function": function,
+                "args": args.iter().map(expr_to_json).collect::<Vec<_>>(),
+            }
+        }),
+    }
+}
+
+impl Database {
+    /// Parses a JSON protocol query against `table_name`, including the
+    /// target table of any join.
+    pub async fn query_from_json(&self, table_name: &str, json: &str) -> Result<Query, String> {
+        self.parse_json_query(table_name, json, None).await
+    }
+
+    /// Like `query_from_json`, for a parameterized template.
+    pub async fn query_from_json_with_params(
+        &self,
+        table_name: &str,
+        json: &str,
+        params: &Params,
+    ) -> Result<Query, String> {
+        self.parse_json_query(table_name, json, Some(params)).await
+    }
+
+    async fn parse_json_query(&self, table_name: &str, json: &str, params: Option<&Params>) -> Result<Query, String> {
+        let parsed: Json = serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let lookup = |name: &str| tables.get(name).map(|t| t.columns.clone());
+        parse_query(&parsed, &table.columns, Some(&lookup), params, "$")
+    }
+}
+
+type TableLookup<'a> = &'a dyn Fn(&str) -> Option<Vec<Column>>;
+
+fn parse_query(
+    json: &Json,
+    columns: &[Column],
+    tables: Option<TableLookup>,
+    params: Option<&Params>,
+    path: &str,
+) -> Result<Query, String> {
+    if json.as_str() == Some("match_all") {
+        return Ok(Query::MatchAll);
+    }
+    let (kind, body) = single_key(json, path)?;
+    let path = format!("{}.{}", path, kind);
+    match kind {
+        "condition" => {
+            let body = object(body, &path, &["column", "op", "value", "param"])?;
+            let column_name = string_field(body, "column", &path)?;
+            let column = find_column(columns, column_name, &path)?;
+            let operator = parse_operator(string_field(body, "op", &path)?, &path)?;
+            let value = match (body.get("value"), body.get("param")) {
+                (Some(value), None) => {
+                    check_literal(params, &format!("{}.value", path))?;
+                    crate::Value::from_json(value, &column.data_type)
+                        .map_err(|e| format!("{}.value: {}", path, e))?
+                }
+                (None, Some(name)) => {
+                    let path = format!("{}.param", path);
+                    let value = bind(params, name, &path)?.coerce(&column.data_type, Coercion::Lossless);
+                    match value_type(&value) {
+                        Some(data_type) if data_type != column.data_type => {
+                            return Err(format!("{}: expected {:?}, got {:?}", path, column.data_type, data_type));
+                        }
+                        _ => value,
+                    }
+                }
+                _ => return Err(format!("{}: expected one of value or param", path)),
+            };
+            Ok(Query::Condition(Condition {
+                column: column_name.to_string(),
+                operator,
+                value,
+            }))
+        }
+        "and" | "or" => {
+            let items = body
+                .as_array()
+                .ok_or_else(|| format!("{}: expected an array", path))?;
+            let queries = items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| parse_query(item, columns, tables, params, &format!("{}[{}]", path, i)))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(if kind == "and" { Query::And(queries) } else { Query::Or(queries) })
+        }
+        "join" => {
+            let body = object(body, &path, &["type", "table", "on", "columns"])?;
+            let join_type = match string_field(body, "type", &path)? {
+                "inner" => JoinType::Inner,
+                "left" => JoinType::Left,
+                "right" => JoinType::Right,
+                other => return Err(format!("{}.type: unknown join type {}", path, other)),
+            };
+            let target_table = string_field(body, "table", &path)?;
+            let on = body
+                .get("on")
+                .and_then(|on| on.as_array())
+                .filter(|on| on.len() == 2)
+                .ok_or_else(|| format!("{}.on: expected [left_column, right_column]", path))?;
+            let left = on[0]
+                .as_str()
+                .ok_or_else(|| format!("{}.on[0]: expected a string", path))?;
+            let right = on[1]
+                .as_str()
+                .ok_or_else(|| format!("{}.on[1]: expected a string", path))?;
+            find_column(columns, left, &format!("{}.on[0]", path))?;
+            if let Some(lookup) = tables {
+                let target_columns = lookup(target_table)
+                    .ok_or_else(|| format!("{}.table: table {} not found", path, target_table))?;
+                find_column(&target_columns, right, &format!("{}.on[1]", path))?;
+            }
+            let join_columns = match body.get("columns") {
+                None => JoinColumns::Merge,
+                Some(naming) => match (naming.as_str(), naming.as_array().map(Vec::as_slice)) {
+                    (Some("merge"), _) => JoinColumns::Merge,
+                    (Some("prefixed"), _) => JoinColumns::Prefixed,
+                    (_, Some([Json::String(table), Json::String(target)])) => {
+                        JoinColumns::Aliased(table.clone(), target.clone())
+                    }
+                    _ => {
+                        return Err(format!(
+                            "{}.columns: expected merge, prefixed or [table_alias, target_alias]",
+                            path
+                        ))
+                    }
+                },
+            };
+            Ok(Query::Join(Join {
+                join_type,
+                target_table: target_table.to_string(),
+                on_condition: (left.to_string(), right.to_string()),
+                columns: join_columns,
+            }))
+        }
+        "aggregate" => {
+            let body = object(body, &path, &["function", "column", "filter", "group_by"])?;
+            let function = match string_field(body, "function", &path)? {
+                "count" => AggregateFunction::Count,
+                "sum" => AggregateFunction::Sum,
+                "avg" => AggregateFunction::Avg,
+                "min" => AggregateFunction::Min,
+                "max" => AggregateFunction::Max,
+                "count_distinct" => AggregateFunction::CountDistinct,
+                "approx_count_distinct" => AggregateFunction::ApproxCountDistinct,
+                other => return Err(format!("{}.function: unknown function {}", path, other)),
+            };
+            let column = string_field(body, "column", &path)?;
+            // `count` of `*` counts rows, as in SQL.
+            if !(matches!(function, AggregateFunction::Count) && column == "*") {
+                find_column(columns, column, &path)?;
+            }
+            let filter = match body.get("filter") {
+                Some(filter) => Some(Box::new(parse_query(
+                    filter,
+                    columns,
+                    tables,
+                    params,
+                    &format!("{}.filter", path),
+                )?)),
+                None => None,
+            };
+            let group_by = match body.get("group_by") {
+                Some(group_by) => group_by
+                    .as_array()
+                    .ok_or_else(|| format!("{}.group_by: expected an array", path))?
+                    .iter()
+                    .enumerate()
+                    .map(|(i, column)| {
+                        let column_path = format!("{}.group_by[{}]", path, i);
+                        let name = column
+                            .as_str()
+                            .ok_or_else(|| format!("{}: expected a string", column_path))?;
+                        find_column(columns, name, &column_path).map(|_| name.to_string())
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => Vec::new(),
+            };
+            Ok(Query::Aggregate(AggregateQuery {
+                function,
+                column: column.to_string(),
+                filter,
+                group_by,
+            }))
+        }
+        "compare" => {
+            let body = object(body, &path, &["left", "op", "right"])?;
+            let operator = parse_operator(string_field(body, "op", &path)?, &path)?;
+            let left = body
+                .get("left")
+                .ok_or_else(|| format!("{}: missing field left", path))?;
+            let right = body
+                .get("right")
+                .ok_or_else(|| format!("{}: missing field right", path))?;
+            let left_path = format!("{}.left", path);
+            let right_path = format!("{}.right", path);
+            // A literal compared with a column is read as the column's type.
+            let left_type = column_type(left, columns, &left_path)?;
+            let right_type = column_type(right, columns, &right_path)?;
+            Ok(Query::Compare(Comparison {
+                left: parse_expr(left, columns, right_type.as_ref(), params, &left_path)?,
+                operator,
+                right: parse_expr(right, columns, left_type.as_ref(), params, &right_path)?,
+            }))
+        }
+        "traverse" => {
+            let body = object(body, &path, &["start", "from", "to", "max_depth"])?;
+            let start = body
+                .get("start")
+                .ok_or_else(|| format!("{}: missing field start", path))?;
+            let start = parse_query(start, columns, tables, params, &format!("{}.start", path))?;
+            let from = string_field(body, "from", &path)?;
+            find_column(columns, from, &format!("{}.from", path))?;
+            let to = string_field(body, "to", &path)?;
+            find_column(columns, to, &format!("{}.to", path))?;
+            let max_depth = match body.get("max_depth") {
+                Some(depth) => Some(
+                    depth
+                        .as_u64()
+                        .ok_or_else(|| format!("{}.max_depth: expected a non-negative integer", path))?
+                        as usize,
+                ),
+                None => None,
+            };
+            Ok(Query::Traverse(Traverse {
+                start: Box::new(start),
+                from: from.to_string(),
+                to: to.to_string(),
+                max_depth,
+            }))
+        }
+        "sample" => {
+            let body = object(body, &path, &["query", "fraction", "rows", "seed"])?;
+            let query = body
+                .get("query")
+                .ok_or_else(|| format!("{}: missing field query", path))?;
+            let query = parse_query(query, columns, tables, params, &format!("{}.query", path))?;
+            let size = match (body.get("fraction"), body.get("rows")) {
+                (Some(fraction), None) => SampleSize::Fraction(
+                    fraction
+                        .as_f64()
+                        .ok_or_else(|| format!("{}.fraction: expected a number", path))?,
+                ),
+                (None, Some(rows)) => SampleSize::Rows(
+                    rows.as_u64()
+                        .ok_or_else(|| format!("{}.rows: expected a non-negative integer", path))?
+                        as usize,
+                ),
+                _ => return Err(format!("{}: expected one of fraction or rows", path)),
+            };
+            let seed = body
+                .get("seed")
+                .ok_or_else(|| format!("{}: missing field seed", path))?
+                .as_u64()
+                .ok_or_else(|| format!("{}.seed: expected a non-negative integer", path))?;
+            Ok(Query::Sample(Sample {
+                query: Box::new(query),
+                size,
+                seed,
+            }))
+        }
+        "in_subquery" => {
+            let body = object(body, &path, &["column", "table", "query", "select_column"])?;
+            let column = string_field(body, "column", &path)?;
+            find_column(columns, column, &format!("{}.column", path))?;
+            let table = string_field(body, "table", &path)?;
+            let lookup = tables.ok_or_else(|| {
+                format!("{}: subqueries need the other table's columns; use Database::query_from_json", path)
+            })?;
+            let other_columns =
+                lookup(table).ok_or_else(|| format!("{}.table: table {} not found", path, table))?;
+            let select_column = string_field(body, "select_column", &path)?;
+            find_column(&other_columns, select_column, &format!("{}.select_column", path))?;
+            let query = body
+                .get("query")
+                .ok_or_else(|| format!("{}: missing field query", path))?;
+            let subquery = parse_query(query, &other_columns, tables, params, &format!("{}.query", path))?;
+            Ok(Query::InSubquery {
+                column: column.to_string(),
+                table: table.to_string(),
+                subquery: Box::new(subquery),
+                select_column: select_column.to_string(),
+            })
+        }
+        other => Err(format!("{}: unknown query type {}", path, other)),
+    }
+}
+
+fn parse_operator(name: &str, path: &str) -> Result<Operator, String> {
+    match name {
+        "eq" => Ok(Operator::Eq),
+        "not_eq" => Ok(Operator::NotEq),
+        "gt" => Ok(Operator::Gt),
+        "gte" => Ok(Operator::Gte),
+        "lt" => Ok(Operator::Lt),
+        "lte" => Ok(Operator::Lte),
+        "ilike" => Ok(Operator::ILike),
+        "like" => Ok(Operator::Like),
+        "not_like" => Ok(Operator::NotLike),
+        "regex" => Ok(Operator::Regex),
+        other => Err(format!("{}.op: unknown operator {}", path, other)),
+    }
+}
+
+fn column_type(json: &Json, columns: &[Column], path: &str) -> Result<Option<DataType>, String> {
+    match json.get("column").and_then(Json::as_str) {
+        Some(name) => Ok(Some(find_column(columns, name, path)?.data_type.clone())),
+        None => Ok(None),
+    }
+}
+
+fn parse_expr(
+    json: &Json,
+    columns: &[Column],
+    literal_type: Option<&DataType>,
+    params: Option<&Params>,
+    path: &str,
+) -> Result<Expr, String> {
+    let (kind, body) = single_key(json, path)?;
+    let path = format!("{}.{}", path, kind);
+    match kind {
+        "column" => {
+            let name = body
+                .as_str()
+                .ok_or_else(|| format!("{}: expected a string", path))?;
+            find_column(columns, name, &path)?;
+            Ok(Expr::Column(name.to_string()))
+        }
+        "value" => {
+            check_literal(params, &path)?;
+            let value = match literal_type {
+                Some(data_type) => crate::Value::from_json(body, data_type).map_err(|e| format!("{}: {}", path, e))?,
+                None => infer_value(body),
+            };
+            Ok(Expr::Literal(value))
+        }
+        "param" => {
+            let value = bind(params, body, &path)?;
+            Ok(Expr::Literal(match literal_type {
+                Some(data_type) => value.coerce(data_type, Coercion::Lossless),
+                None => value,
+            }))
+        }
+        "call" => {
+            let body = object(body, &path, &["function", "args"])?;
+            let function = string_field(body, "function", &path)?;
+            let args = match body.get("args") {
+                Some(args) => args
+                    .as_array()
+                    .ok_or_else(|| format!("{}.args: expected an array", path))?
+                    .iter()
+                    .enumerate()
+                    .map(|(i, arg)| parse_expr(arg, columns, None, params, &format!("{}.args[{}]", path, i)))
+                    .collect::<Result<Vec<_>, _>>()?,
+                None => Vec::new(),
+            };
+            Ok(Expr::call(function, args))
+        }
+        other => Err(format!("{}: unknown expression type {}", path, other)),
+    }
+}
+
+// Literal values are only allowed outside parameterized templates.
+fn check_literal(params: Option<&Params>, path: &str) -> Result<(), String> {
+    match params {
+        Some(_) => Err(format!("{}: literal values aren't allowed in a parameterized query; use a param", path)),
+        None => Ok(()),
+    }
+}
+
+fn bind(params: Option<&Params>, name: &Json, path: &str) -> Result<crate::Value, String> {
+    let name = name
+        .as_str()
+        .ok_or_else(|| format!("{}: expected a parameter name", path))?;
+    params
+        .and_then(|params| params.get(name))
+        .cloned()
+        .ok_or_else(|| format!("{}: missing parameter {}", path, name))
+}
+
+pub(crate) fn infer_value(json: &Json) -> crate::Value {
+    match json {
+        Json::Null => crate::Value::Null,
+        Json::Bool(b) => crate::Value::Boolean(*b),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => crate::Value::Integer(i),
+            None => crate::Value::Float(n.as_f64().unwrap_or(f64::NAN)),
+        },
+        Json::String(s) => crate::Value::String(s.clone()),
+        other => crate::Value::Json(other.clone()),
+    }
+}
+
+fn single_key<'a>(json: &'a Json, path: &str) -> Result<(&'a str, &'a Json), String> {
+    match json.as_object() {
+        Some(map) if map.len() == 1 => {
+            let (key, value) = map.iter().next().unwrap();
+            Ok((key.as_str(), value))
+        }
+        _ => Err(format!("{}: expected \"match_all\" or an object with a single key", path)),
+    }
+}
+
+fn object<'a>(json: &'a Json, path: &str, allowed: &[&str]) -> Result<&'a Map<String, Json>, String> {
+    let map = json
+        .as_object()
+        .ok_or_else(|| format!("{}: expected an object", path))?;
+    if let Some(unknown) = map.keys().find(|k| !allowed.contains(&k.as_str())) {
+        return Err(format!("{}: unknown field {}", path, unknown));
+    }
+    Ok(map)
+}
+
+fn string_field<'a>(map: &'a Map<String, Json>, field: &str, path: &str) -> Result<&'a str, String> {
+    map.get(field)
+        .ok_or_else(|| format!("{}: missing field {}", path, field))?
+        .as_str()
+        .ok_or_else(|| format!("{}.{}: expected a string", path, field))
+}
+
+fn find_column<'a>(columns: &'a [Column], name: &str, path: &str) -> Result<&'a Column, String> {
+    columns
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| format!("{}: column {} not found", path, name))
+}
+
+fn operator_name(operator: &Operator) -> &'static str {
+    match operator {
+        Operator::Eq => "eq",
+        Operator::NotEq => "not_eq",
+        Operator::Gt => "gt",
+        Operator::Gte => "gte",
+        Operator::Lt => "lt",
+        Operator::Lte => "lte",
+        Operator::ILike => "ilike",
+        Operator::Like => "like",
+        Operator::NotLike => "not_like",
+        Operator::Regex => "regex",
+    }
+}
+
+fn join_type_name(join_type: &JoinType) -> &'static str {
+    match join_type {
+        JoinType::Inner => "inner",
+        JoinType::Left => "left",
+        JoinType::Right => "right",
+    }
+}
+
+fn function_name(function: &AggregateFunction) -> &'static str {
+    match function {
+        AggregateFunction::Count => "count",
+        AggregateFunction::Sum => "sum",
+        AggregateFunction::Avg => "avg",
+        AggregateFunction::Min => "min",
+        AggregateFunction::Max => "max",
+        AggregateFunction::CountDistinct => "count_distinct",
+        AggregateFunction::ApproxCountDistinct => "approx_count_distinct",
+    }
+}
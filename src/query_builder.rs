@@ -0,0 +1,219 @@
+//! A fluent builder for queries and the options to run them with.
+//!
+//! `col` starts a condition on a column, and `Query::and` and `Query::or`
+//! combine conditions, flattening nested `And`s and `Or`s as they go.
+//! `QueryBuilder` collects a table, a filter and `SelectOptions`, and
+//! `Database::fetch` runs it like `select_with`:
+//!
+//! ```
+//! use zapdb::{col, QueryBuilder};
+//!
+//! let q = QueryBuilder::table("users")
+//!     .filter(col("age").gte(25))
+//!     .and(col("name").eq("Alice").or(col("name").eq("Bob")))
+//!     .order_by("age")
+//!     .limit(10);
+//! ```
+//!
+//! Values are anything with a `From` conversion into `Value`, as in `query!`.
+
+use crate::top_k::SortOrder;
+use crate::{Condition, Database, Operator, Query, Row, SelectError, SelectOptions, Value};
+use std::time::Duration;
+
+/// A column to compare, made by `col`.
+#[derive(Clone, Debug)]
+pub struct ColumnRef(String);
+
+/// The column named, to build a condition on.
+pub fn col(name: &str) -> ColumnRef {
+    ColumnRef(name.to_string())
+}
+
+impl ColumnRef {
+    fn compare(self, operator: Operator, value: impl Into<Value>) -> Query {
+        Query::Condition(Condition {
+            column: self.0,
+            operator,
+            value: value.into(),
+        })
+    }
+
+    pub fn eq(self, value: impl Into<Value>) -> Query {
+        self.compare(Operator::Eq, value)
+    }
+
+    pub fn ne(self, value: impl Into<Value>) -> Query {
+        self.compare(Operator::NotEq, value)
+    }
+
+    pub fn gt(self, value: impl Into<Value>) -> Query {
+        self.compare(Operator::Gt, value)
+    }
+
+    pub fn gte(self, value: impl Into<Value>) -> Query {
+        self.compare(Operator::Gte, value)
+    }
+
+    pub fn lt(self, value: impl Into<Value>) -> Query {
+        self.compare(Operator::Lt, value)
+    }
+
+    pub fn lte(self, value: impl Into<Value>) -> Query {
+        self.compare(Operator::Lte, value)
+    }
+
+    pub fn like(self, pattern: &str) -> Query {
+        self.compare(Operator::Like, pattern)
+    }
+
+    pub fn not_like(self, pattern: &str) -> Query {
+        self.compare(Operator::NotLike, pattern)
+    }
+
+    pub fn ilike(self, pattern: &str) -> Query {
+        self.compare(Operator::ILike, pattern)
+    }
+
+    pub fn regex(self, pattern: &str) -> Query {
+        self.compare(Operator::Regex, pattern)
+    }
+
+    /// Equal to any of `values`. With none, nothing matches.
+    pub fn is_in<V: Into<Value>>(self, values: impl IntoIterator<Item = V>) -> Query {
+        let conditions: Vec<Query> = values.into_iter().map(|value| self.clone().eq(value)).collect();
+        Query::Or(conditions)
+    }
+
+    /// Between `low` and `high`, inclusive: the `And` of a `Gte` and a `Lte`,
+    /// which an ordered index on the column answers with one range scan.
+    pub fn between(self, low: impl Into<Value>, high: impl Into<Value>) -> Query {
+        Query::And(vec![self.clone().gte(low), self.lte(high)])
+    }
+}
+
+impl Query {
+    /// Rows matching both queries.
+    pub fn and(self, other: Query) -> Query {
+        match (self, other) {
+            (Query::MatchAll, other) | (other, Query::MatchAll) => other,
+            (Query::And(mut left), Query::And(right)) => {
+                left.extend(right);
+                Query::And(left)
+            }
+            (Query::And(mut left), other) => {
+                left.push(other);
+                Query::And(left)
+            }
+            (other, Query::And(mut right)) => {
+                right.insert(0, other);
+                Query::And(right)
+            }
+            (left, right) => Query::And(vec![left, right]),
+        }
+    }
+
+    /// Rows matching either query.
+    pub fn or(self, other: Query) -> Query {
+        match (self, other) {
+            (Query::MatchAll, _) | (_, Query::MatchAll) => Query::MatchAll,
+            (Query::Or(mut left), Query::Or(right)) => {
+                left.extend(right);
+                Query::Or(left)
+            }
+            (Query::Or(mut left), other) => {
+                left.push(other);
+                Query::Or(left)
+            }
+            (other, Query::Or(mut right)) => {
+                right.insert(0, other);
+                Query::Or(right)
+            }
+            (left, right) => Query::Or(vec![left, right]),
+        }
+    }
+}
+
+/// A table, a filter and how to sort, skip, limit and project the rows.
+#[derive(Clone, Debug)]
+pub struct QueryBuilder {
+    table: String,
+    query: Query,
+    options: SelectOptions,
+}
+
+impl QueryBuilder {
+    pub fn table(name: &str) -> Self {
+        QueryBuilder {
+            table: name.to_string(),
+            query: Query::MatchAll,
+            options: SelectOptions::new(),
+        }
+    }
+
+    /// Keeps the rows matching `query` as well as the filter so far.
+    pub fn filter(mut self, query: Query) -> Self {
+        self.query = self.query.and(query);
+        self
+    }
+
+    /// The same as `filter`.
+    pub fn and(self, query: Query) -> Self {
+        self.filter(query)
+    }
+
+    /// Keeps the rows matching either `query` or the filter so far.
+    pub fn or(mut self, query: Query) -> Self {
+        self.query = self.query.or(query);
+        self
+    }
+
+    pub fn order_by(mut self, column: &str) -> Self {
+        self.options = self.options.order_by(column, SortOrder::Ascending);
+        self
+    }
+
+    pub fn order_by_desc(mut self, column: &str) -> Self {
+        self.options = self.options.order_by(column, SortOrder::Descending);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.options = self.options.limit(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.options = self.options.offset(offset);
+        self
+    }
+
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.options = self.options.columns(columns);
+        self
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table
+    }
+
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    pub fn options(&self) -> &SelectOptions {
+        &self.options
+    }
+
+    /// The filter, for the methods that take a `Query`.
+    pub fn build(self) -> Query {
+        self.query
+    }
+}
+
+impl Database {
+    /// The rows `builder` describes, as `select_with` returns them.
+    pub async fn fetch(&self, builder: &QueryBuilder) -> Result<(Vec<Row>, Duration), SelectError> {
+        self.select_with(&builder.table, &builder.query, &builder.options).await
+    }
+}
@@ -0,0 +1,130 @@
+//! The `query!` macro, which builds a `Query` from a Rust-like expression.
+//!
+//! `&&` binds tighter than `||`, and parentheses group. Each comparison is a
+//! column name, an operator and a value, which can be any expression with a
+//! `From` conversion into `Value`. An unknown operator or a value with no
+//! conversion fails to compile; columns and types are still only checked
+//! against the table when the query runs.
+
+use crate::Value;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+macro_rules! impl_into_value {
+    ($ty:ty, $variant:ident) => {
+        impl From<$ty> for Value {
+            fn from(value: $ty) -> Self {
+                Value::$variant(value.into())
+            }
+        }
+    };
+}
+
+impl_into_value!(i64, Integer);
+impl_into_value!(i32, Integer);
+impl_into_value!(f64, Float);
+impl_into_value!(bool, Boolean);
+impl_into_value!(String, String);
+impl_into_value!(&str, String);
+impl_into_value!(DateTime<Utc>, DateTime);
+impl_into_value!(Uuid, Uuid);
+impl_into_value!(serde_json::Value, Json);
+
+/// Builds a `Query` from comparisons joined by `&&` and `||`:
+///
+/// ```
+/// use zapdb::query;
+///
+/// let min_age = 25;
+/// let q = query!((age >= min_age && name == "Alice") || admin == true);
+/// let everything = query!();
+/// ```
+#[macro_export]
+macro_rules! query {
+    () => {
+        $crate::Query::MatchAll
+    };
+    ($($tokens:tt)+) => {
+        $crate::__query_or!([] [] $($tokens)+)
+    };
+}
+
+// Splits on `||` into groups of tokens.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __query_or {
+    ([] [$($current:tt)+]) => {
+        $crate::__query_and!([] [] $($current)+)
+    };
+    ([$([$($group:tt)+])+] [$($current:tt)+]) => {
+        $crate::Query::Or(vec![
+            $($crate::__query_and!([] [] $($group)+),)+
+            $crate::__query_and!([] [] $($current)+),
+        ])
+    };
+    ([$($groups:tt)*] [$($current:tt)+] || $($rest:tt)+) => {
+        $crate::__query_or!([$($groups)* [$($current)+]] [] $($rest)+)
+    };
+    ([$($groups:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__query_or!([$($groups)*] [$($current)* $next] $($rest)*)
+    };
+}
+
+// Splits a group on `&&` into comparisons.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __query_and {
+    ([] [$($current:tt)+]) => {
+        $crate::__query_term!($($current)+)
+    };
+    ([$([$($term:tt)+])+] [$($current:tt)+]) => {
+        $crate::Query::And(vec![
+            $($crate::__query_term!($($term)+),)+
+            $crate::__query_term!($($current)+),
+        ])
+    };
+    ([$($terms:tt)*] [$($current:tt)+] && $($rest:tt)+) => {
+        $crate::__query_and!([$($terms)* [$($current)+]] [] $($rest)+)
+    };
+    ([$($terms:tt)*] [$($current:tt)*] $next:tt $($rest:tt)*) => {
+        $crate::__query_and!([$($terms)*] [$($current)* $next] $($rest)*)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __query_term {
+    (($($inner:tt)+)) => {
+        $crate::query!($($inner)+)
+    };
+    ($column:ident == $($value:tt)+) => {
+        $crate::__query_condition!($column, Eq, $($value)+)
+    };
+    ($column:ident != $($value:tt)+) => {
+        $crate::__query_condition!($column, NotEq, $($value)+)
+    };
+    ($column:ident >= $($value:tt)+) => {
+        $crate::__query_condition!($column, Gte, $($value)+)
+    };
+    ($column:ident <= $($value:tt)+) => {
+        $crate::__query_condition!($column, Lte, $($value)+)
+    };
+    ($column:ident > $($value:tt)+) => {
+        $crate::__query_condition!($column, Gt, $($value)+)
+    };
+    ($column:ident < $($value:tt)+) => {
+        $crate::__query_condition!($column, Lt, $($value)+)
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __query_condition {
+    ($column:ident, $operator:ident, $($value:tt)+) => {
+        $crate::Query::Condition($crate::Condition {
+            column: stringify!($column).to_string(),
+            operator: $crate::Operator::$operator,
+            value: $crate::Value::from($($value)+),
+        })
+    };
+}
@@ -0,0 +1,121 @@
+//! Conversion between rows and Arrow record batches, for the `datafusion` and
+//! `server` features.
+//!
+//! Integers map to `Int64`, floats to `Float64`, booleans to `Boolean` and
+//! date-times to microsecond timestamps in UTC. Strings, UUIDs and JSON all
+//! map to `Utf8`. Every field is nullable.
+
+use crate::{Column, DataType, Row, Value};
+use arrow::array::{ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray};
+use arrow::datatypes::{DataType as ArrowType, Field, Schema, TimeUnit};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use std::sync::Arc;
+
+pub(crate) fn arrow_type(data_type: &DataType) -> ArrowType {
+    match data_type {
+        DataType::Integer => ArrowType::Int64,
+        DataType::Float => ArrowType::Float64,
+        DataType::Boolean => ArrowType::Boolean,
+        DataType::DateTime => ArrowType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        DataType::String | DataType::Uuid | DataType::Json => ArrowType::Utf8,
+    }
+}
+
+pub(crate) fn arrow_schema(columns: &[Column]) -> Schema {
+    Schema::new(
+        columns
+            .iter()
+            .map(|c| Field::new(c.name.clone(), arrow_type(&c.data_type), true))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// One batch holding `rows`, with a column per entry in `columns`. Values of
+/// the wrong type become nulls.
+pub(crate) fn rows_to_batch(columns: &[Column], rows: &[Arc<Row>]) -> Result<RecordBatch, ArrowError> {
+    let arrays: Vec<ArrayRef> = columns
+        .iter()
+        .map(|column| {
+            let values = rows.iter().map(|row| row.get(&column.name));
+            let array: ArrayRef = match column.data_type {
+                DataType::Integer => Arc::new(Int64Array::from_iter(values.map(|v| match v {
+                    Some(Value::Integer(i)) => Some(*i),
+                    _ => None,
+                }))),
+                DataType::Float => Arc::new(Float64Array::from_iter(values.map(|v| match v {
+                    Some(Value::Float(f)) => Some(*f),
+                    Some(Value::Integer(i)) => Some(*i as f64),
+                    _ => None,
+                }))),
+                DataType::Boolean => Arc::new(BooleanArray::from_iter(values.map(|v| match v {
+                    Some(Value::Boolean(b)) => Some(*b),
+                    _ => None,
+                }))),
+                DataType::DateTime => Arc::new(
+                    TimestampMicrosecondArray::from_iter(values.map(|v| match v {
+                        Some(Value::DateTime(dt)) => Some(dt.timestamp_micros()),
+                        _ => None,
+                    }))
+                    .with_timezone("UTC"),
+                ),
+                DataType::String | DataType::Uuid | DataType::Json => {
+                    Arc::new(StringArray::from_iter(values.map(|v| match v {
+                        Some(Value::String(s)) => Some(s.clone()),
+                        Some(Value::Null) | None => None,
+                        Some(other) => Some(other.to_string()),
+                    })))
+                }
+            };
+            array
+        })
+        .collect();
+    RecordBatch::try_new(Arc::new(arrow_schema(columns)), arrays)
+}
+
+/// The rows in `batch`, whose fields must all be columns of the table. Arrays
+/// are cast to the columns' Arrow types first, so an `Int32` field fills an
+/// Integer column. Nulls become `Value::Null`.
+#[cfg(feature = "server")]
+pub(crate) fn batch_to_rows(
+    batch: &RecordBatch,
+    columns: &[Column],
+) -> Result<Vec<std::collections::HashMap<String, Value>>, String> {
+    use arrow::array::Array;
+
+    let mut rows = vec![std::collections::HashMap::new(); batch.num_rows()];
+    for (field, array) in batch.schema().fields().iter().zip(batch.columns()) {
+        let column = columns
+            .iter()
+            .find(|c| c.name == *field.name())
+            .ok_or_else(|| format!("Column {} not found", field.name()))?;
+        let array = arrow::compute::cast(array, &arrow_type(&column.data_type)).map_err(|e| e.to_string())?;
+        for (i, row) in rows.iter_mut().enumerate() {
+            let value = if array.is_null(i) {
+                Value::Null
+            } else {
+                value_at(&array, i, &column.data_type)
+                    .ok_or_else(|| format!("Invalid {:?} in column {}", column.data_type, column.name))?
+            };
+            row.insert(column.name.clone(), value);
+        }
+    }
+    Ok(rows)
+}
+
+#[cfg(feature = "server")]
+fn value_at(array: &ArrayRef, i: usize, data_type: &DataType) -> Option<Value> {
+    let string = || array.as_any().downcast_ref::<StringArray>().map(|a| a.value(i));
+    match data_type {
+        DataType::Integer => Some(Value::Integer(array.as_any().downcast_ref::<Int64Array>()?.value(i))),
+        DataType::Float => Some(Value::Float(array.as_any().downcast_ref::<Float64Array>()?.value(i))),
+        DataType::Boolean => Some(Value::Boolean(array.as_any().downcast_ref::<BooleanArray>()?.value(i))),
+        DataType::DateTime => {
+            let micros = array.as_any().downcast_ref::<TimestampMicrosecondArray>()?.value(i);
+            chrono::DateTime::from_timestamp_micros(micros).map(Value::DateTime)
+        }
+        DataType::String => Some(Value::String(string()?.to_string())),
+        DataType::Uuid => string()?.parse().ok().map(Value::Uuid),
+        DataType::Json => serde_json::from_str(string()?).ok().map(Value::Json),
+    }
+}
@@ -0,0 +1,378 @@
+//! Snapshot layout, and loading what can be read from a damaged snapshot.
+//!
+//! After the header, a snapshot holds the number of tables, then one frame
+//! per table, then optionally the views, the sequences, the tables'
+//! partitionings and the tenants. A table's frame
+//! holds frames for its name, its columns, the next row id, and each of its
+//! rows, which start with the row's id. A frame is a little-endian `u64`
+//! length followed by that many bytes; ids are little-endian `u64`s too.
+//! Version 2 snapshots have no ids, and their rows are numbered as loaded.
+//! Framing each table and row separately lets `load_with_recovery` skip one
+//! that fails to decode and carry on with the next.
+
+use crate::codec::invalid;
+use crate::sequences::Sequence;
+use crate::tenants::TenantState;
+use crate::{Codec, Column, Database, Partitioning, Row, RowId, Table, View};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// Something `load_with_recovery` couldn't read and left out.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Skipped {
+    /// The `position`th table in the snapshot, with its name if that much
+    /// could be read.
+    Table {
+        position: usize,
+        name: Option<String>,
+        error: String,
+    },
+    /// The `position`th row of a table in the snapshot.
+    Row { table: String, position: usize, error: String },
+    Views { error: String },
+    Sequences { error: String },
+    /// The tables' partitionings. The tables are loaded unpartitioned.
+    Partitionings { error: String },
+    Tenants { error: String },
+    /// The rest of the snapshot from byte `offset` of its decompressed
+    /// contents, after a frame that runs past the end.
+    Snapshot { offset: usize, error: String },
+    /// The rest of the WAL from byte `offset`. WAL entries aren't framed, so
+    /// nothing after the first bad one can be read.
+    Wal { offset: usize, error: String },
+}
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Skipped::Table { position, name: Some(name), error } => {
+                write!(f, "Table {} (#{}): {}", name, position, error)
+            }
+            Skipped::Table { position, name: None, error } => write!(f, "Table #{}: {}", position, error),
+            Skipped::Row { table, position, error } => write!(f, "Row {} of table {}: {}", position, table, error),
+            Skipped::Views { error } => write!(f, "Views: {}", error),
+            Skipped::Sequences { error } => write!(f, "Sequences: {}", error),
+            Skipped::Partitionings { error } => write!(f, "Partitionings: {}", error),
+            Skipped::Tenants { error } => write!(f, "Tenants: {}", error),
+            Skipped::Snapshot { offset, error } => write!(f, "Snapshot from byte {}: {}", offset, error),
+            Skipped::Wal { offset, error } => write!(f, "WAL from byte {}: {}", offset, error),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RecoveryReport {
+    pub skipped: Vec<Skipped>,
+}
+
+impl RecoveryReport {
+    /// Whether everything was read.
+    pub fn is_clean(&self) -> bool {
+        self.skipped.is_empty()
+    }
+
+    /// An error for the first thing skipped, for loads that don't recover.
+    pub(crate) fn into_result(self) -> io::Result<()> {
+        match self.skipped.into_iter().next() {
+            Some(skipped) => Err(invalid(skipped.to_string())),
+            None => Ok(()),
+        }
+    }
+}
+
+pub(crate) struct SnapshotContents {
+    pub(crate) tables: HashMap<String, Table>,
+    pub(crate) views: HashMap<String, View>,
+    pub(crate) sequences: HashMap<String, Sequence>,
+    pub(crate) tenants: HashMap<String, TenantState>,
+}
+
+pub(crate) fn put_frame(out: &mut Vec<u8>, frame: &[u8]) {
+    out.extend((frame.len() as u64).to_le_bytes());
+    out.extend(frame);
+}
+
+pub(crate) fn take_frame<'a>(bytes: &mut &'a [u8]) -> Result<&'a [u8], String> {
+    let (len, rest) = bytes
+        .split_first_chunk::<8>()
+        .ok_or_else(|| "Truncated frame length".to_string())?;
+    let len = usize::try_from(u64::from_le_bytes(*len)).unwrap_or(usize::MAX);
+    if len > rest.len() {
+        return Err(format!("Frame of {} bytes runs past the end", len));
+    }
+    let (frame, rest) = rest.split_at(len);
+    *bytes = rest;
+    Ok(frame)
+}
+
+fn decode_frame<T: serde::de::DeserializeOwned>(codec: Codec, mut frame: &[u8]) -> Result<T, String> {
+    codec.decode(&mut frame).map_err(|e| e.to_string())
+}
+
+pub(crate) fn encode_snapshot(
+    codec: Codec,
+    tables: &[(&str, &Table)],
+    views: &HashMap<String, View>,
+    sequences: &HashMap<String, Sequence>,
+    tenants: &HashMap<String, TenantState>,
+) -> io::Result<Vec<u8>> {
+    let mut out = codec.header();
+    out.extend((tables.len() as u64).to_le_bytes());
+    for (name, table) in tables {
+        let mut frame = Vec::new();
+        put_frame(&mut frame, name.as_bytes());
+        put_frame(&mut frame, &codec.encode(&table.columns)?);
+        put_frame(&mut frame, &table.next_row_id.to_le_bytes());
+        for (row, id) in table.data.iter().zip(&table.row_ids) {
+            let mut row_frame = id.to_le_bytes().to_vec();
+            row_frame.extend(codec.encode(row.as_ref())?);
+            put_frame(&mut frame, &row_frame);
+        }
+        put_frame(&mut out, &frame);
+    }
+    // Views, sequences, partitionings and tenants are left out from the
+    // first that is empty along with everything after it.
+    let partitionings: HashMap<&str, &Partitioning> = tables
+        .iter()
+        .filter_map(|(name, table)| Some((*name, table.partitioning()?)))
+        .collect();
+    if !views.is_empty() || !sequences.is_empty() || !partitionings.is_empty() || !tenants.is_empty() {
+        put_frame(&mut out, &codec.encode(views)?);
+    }
+    if !sequences.is_empty() || !partitionings.is_empty() || !tenants.is_empty() {
+        put_frame(&mut out, &codec.encode(sequences)?);
+    }
+    if !partitionings.is_empty() || !tenants.is_empty() {
+        put_frame(&mut out, &codec.encode(&partitionings)?);
+    }
+    if !tenants.is_empty() {
+        put_frame(&mut out, &codec.encode(tenants)?);
+    }
+    Ok(out)
+}
+
+/// Decodes a decompressed snapshot, leaving out and reporting whatever can't
+/// be read.
+pub(crate) fn decode_snapshot(bytes: &[u8], report: &mut RecoveryReport) -> io::Result<SnapshotContents> {
+    read_snapshot(bytes, report, true)
+}
+
+/// Decodes every frame and row of a decompressed snapshot like
+/// `decode_snapshot`, but drops the rows as they are read.
+pub(crate) fn verify_snapshot(bytes: &[u8], report: &mut RecoveryReport) -> io::Result<()> {
+    read_snapshot(bytes, report, false).map(|_| ())
+}
+
+fn read_snapshot(mut bytes: &[u8], report: &mut RecoveryReport, keep_rows: bool) -> io::Result<SnapshotContents> {
+    let mut contents = SnapshotContents {
+        tables: HashMap::new(),
+        views: HashMap::new(),
+        sequences: HashMap::new(),
+        tenants: HashMap::new(),
+    };
+    let total = bytes.len();
+    let (codec, version) = Codec::read_versioned_header(&mut bytes)?.unwrap_or_default();
+    if version < 2 {
+        decode_unframed(codec, bytes, &mut contents, report);
+        return Ok(contents);
+    }
+
+    let count = match bytes.split_first_chunk::<8>() {
+        Some((count, rest)) => {
+            bytes = rest;
+            u64::from_le_bytes(*count)
+        }
+        None => {
+            report.skipped.push(Skipped::Snapshot {
+                offset: total - bytes.len(),
+                error: "Truncated table count".to_string(),
+            });
+            return Ok(contents);
+        }
+    };
+    for position in 0..count as usize {
+        let offset = total - bytes.len();
+        match take_frame(&mut bytes) {
+            Ok(frame) => {
+                if let Some(table) = decode_table(codec, version, frame, position, report, keep_rows) {
+                    contents.tables.insert(table.name.clone(), table);
+                }
+            }
+            Err(error) => {
+                report.skipped.push(Skipped::Snapshot { offset, error });
+                return Ok(contents);
+            }
+        }
+    }
+
+    let offset = total - bytes.len();
+    match (!bytes.is_empty()).then(|| take_frame(&mut bytes)) {
+        Some(Ok(frame)) => match decode_frame(codec, frame) {
+            Ok(views) => contents.views = views,
+            Err(error) => report.skipped.push(Skipped::Views { error }),
+        },
+        Some(Err(error)) => {
+            report.skipped.push(Skipped::Snapshot { offset, error });
+            return Ok(contents);
+        }
+        None => {}
+    }
+    let offset = total - bytes.len();
+    match (!bytes.is_empty()).then(|| take_frame(&mut bytes)) {
+        Some(Ok(frame)) => match decode_frame(codec, frame) {
+            Ok(sequences) => contents.sequences = sequences,
+            Err(error) => report.skipped.push(Skipped::Sequences { error }),
+        },
+        Some(Err(error)) => {
+            report.skipped.push(Skipped::Snapshot { offset, error });
+            return Ok(contents);
+        }
+        None => {}
+    }
+    let offset = total - bytes.len();
+    match (!bytes.is_empty()).then(|| take_frame(&mut bytes)) {
+        Some(Ok(frame)) => match decode_frame::<HashMap<String, Partitioning>>(codec, frame) {
+            Ok(partitionings) => {
+                for (name, partitioning) in partitionings {
+                    if let Some(table) = contents.tables.get_mut(&name) {
+                        table.set_partitioning(Some(partitioning));
+                    }
+                }
+            }
+            Err(error) => report.skipped.push(Skipped::Partitionings { error }),
+        },
+        Some(Err(error)) => {
+            report.skipped.push(Skipped::Snapshot { offset, error });
+            return Ok(contents);
+        }
+        None => {}
+    }
+    let offset = total - bytes.len();
+    match (!bytes.is_empty()).then(|| take_frame(&mut bytes)) {
+        Some(Ok(frame)) => match decode_frame(codec, frame) {
+            Ok(tenants) => contents.tenants = tenants,
+            Err(error) => report.skipped.push(Skipped::Tenants { error }),
+        },
+        Some(Err(error)) => report.skipped.push(Skipped::Snapshot { offset, error }),
+        None => {}
+    }
+    Ok(contents)
+}
+
+fn take_id(bytes: &mut &[u8]) -> Result<RowId, String> {
+    let (id, rest) = bytes
+        .split_first_chunk::<8>()
+        .ok_or_else(|| "Truncated row id".to_string())?;
+    *bytes = rest;
+    Ok(u64::from_le_bytes(*id))
+}
+
+fn decode_table(
+    codec: Codec,
+    version: u8,
+    mut frame: &[u8],
+    position: usize,
+    report: &mut RecoveryReport,
+    keep_rows: bool,
+) -> Option<Table> {
+    let mut skip = |name: Option<String>, error: String| {
+        report.skipped.push(Skipped::Table { position, name, error });
+        None
+    };
+    let name = match take_frame(&mut frame).map(|n| String::from_utf8(n.to_vec())) {
+        Ok(Ok(name)) => name,
+        Ok(Err(e)) => return skip(None, e.to_string()),
+        Err(error) => return skip(None, error),
+    };
+    let columns: Vec<Column> = match take_frame(&mut frame).and_then(|c| decode_frame(codec, c)) {
+        Ok(columns) => columns,
+        Err(error) => return skip(Some(name), error),
+    };
+
+    let next_row_id = match version {
+        2 => None,
+        _ => match take_frame(&mut frame).and_then(|mut id| take_id(&mut id)) {
+            Ok(id) => Some(id),
+            Err(error) => return skip(Some(name), error),
+        },
+    };
+
+    let mut table = Table::new(name, columns, false);
+    let mut position = 0;
+    while !frame.is_empty() {
+        let row = take_frame(&mut frame).and_then(|mut row| {
+            let id = next_row_id.map(|_| take_id(&mut row)).transpose()?;
+            Ok((id, decode_frame::<Row>(codec, row)))
+        });
+        match row {
+            Ok((id, row)) => match row {
+                Ok(row) if keep_rows => {
+                    table.data.push_back(Arc::new(row));
+                    table.row_ids.push_back(id.unwrap_or(position as RowId));
+                }
+                Ok(_) => {}
+                Err(error) => report.skipped.push(Skipped::Row {
+                    table: table.name.clone(),
+                    position,
+                    error,
+                }),
+            },
+            Err(error) => {
+                report.skipped.push(Skipped::Row {
+                    table: table.name.clone(),
+                    position,
+                    error,
+                });
+                break;
+            }
+        }
+        position += 1;
+    }
+    table.next_row_id = next_row_id.unwrap_or(position as RowId);
+    Some(table)
+}
+
+// Snapshots written before version 2 encode all tables as one value, so a
+// failure loses all of them.
+fn decode_unframed(codec: Codec, mut bytes: &[u8], contents: &mut SnapshotContents, report: &mut RecoveryReport) {
+    match codec.decode(&mut bytes) {
+        Ok(tables) => contents.tables = tables,
+        Err(e) => {
+            report.skipped.push(Skipped::Snapshot {
+                offset: 0,
+                error: e.to_string(),
+            });
+            return;
+        }
+    }
+    if !bytes.is_empty() {
+        match codec.decode(&mut bytes) {
+            Ok(views) => contents.views = views,
+            Err(e) => {
+                report.skipped.push(Skipped::Views { error: e.to_string() });
+                return;
+            }
+        }
+    }
+    if !bytes.is_empty() {
+        if let Err(e) = codec.decode(&mut bytes).map(|sequences| contents.sequences = sequences) {
+            report.skipped.push(Skipped::Sequences { error: e.to_string() });
+        }
+    }
+}
+
+impl Database {
+    /// Like `load`, but leaves out the tables, rows, views and sequences in
+    /// the snapshot that can't be decoded, and the rest of the WAL from the
+    /// first entry that can't, instead of failing. The report lists what was
+    /// left out; everything else is loaded and usable. WAL entries that
+    /// depend on something left out are dropped like any other entry that
+    /// fails to replay. A snapshot that can't be decrypted still fails.
+    pub async fn load_with_recovery(&self, path: &str) -> io::Result<RecoveryReport> {
+        let mut report = RecoveryReport::default();
+        self.load_reporting(path, &mut report, true).await?;
+        Ok(report)
+    }
+}
@@ -0,0 +1,134 @@
+//! Replicating one database into another in the same process.
+//!
+//! `Database::replicate_to` copies the tables, views and sequences of the
+//! primary into an empty replica, then forwards every entry the primary
+//! logs to its WAL, in WAL order, to a task that applies them to the replica
+//! as WAL replay would. The replica runs its own write path, so it can keep
+//! indexes, materialized views and rollups the primary doesn't, and serve
+//! reads laid out for them.
+//!
+//! As with replay, updates made with a function (`update`) aren't in the WAL
+//! and so aren't replicated; `update_with` and `increment` are. Neither are
+//! indexes, ephemeral tables or anything else the WAL leaves out. The
+//! replica logs what it applies to a WAL of its own, but the initial copy
+//! only reaches its disk with its next `save`. Writing to the replica
+//! directly makes it diverge from the primary.
+
+use crate::{Database, WalEntry, WalWriter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+
+/// Where the primary's WAL writer sends entries for one replica.
+pub(crate) struct Subscriber {
+    entries: mpsc::UnboundedSender<WalEntry>,
+    sent: Arc<AtomicU64>,
+}
+
+impl WalWriter {
+    /// Sends `entries`, just logged, to every replica still listening.
+    pub(crate) fn forward<'a>(&mut self, entries: impl IntoIterator<Item = &'a WalEntry>) {
+        if self.replicas.is_empty() {
+            return;
+        }
+        for entry in entries {
+            self.replicas.retain(|replica| {
+                replica.sent.fetch_add(1, Ordering::SeqCst);
+                replica.entries.send(entry.clone()).is_ok()
+            });
+        }
+    }
+}
+
+/// A running replication, from `Database::replicate_to`. Dropping it stops
+/// the replication.
+pub struct Replication {
+    sent: Arc<AtomicU64>,
+    applied: watch::Receiver<u64>,
+    task: JoinHandle<()>,
+}
+
+impl Replication {
+    /// The WAL entries the primary has logged that the replica hasn't
+    /// applied yet.
+    pub fn lag(&self) -> u64 {
+        self.sent.load(Ordering::SeqCst).saturating_sub(*self.applied.borrow())
+    }
+
+    /// Waits until the replica has applied everything the primary logged
+    /// before the call, or the replication has ended.
+    pub async fn caught_up(&self) {
+        let target = self.sent.load(Ordering::SeqCst);
+        let mut applied = self.applied.clone();
+        let _ = applied.wait_for(|applied| *applied >= target).await;
+    }
+
+    /// Whether entries are still being applied: false once stopped, or the
+    /// primary is dropped or the replica closed.
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Stops applying entries. The replica keeps what it has applied.
+    pub fn stop(self) {}
+}
+
+impl Drop for Replication {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Database {
+    /// Starts replicating into `replica`, which must have no tables, as
+    /// described in the module docs.
+    pub async fn replicate_to(&self, replica: Arc<Database>) -> Result<Replication, String> {
+        self.check_open()?;
+        replica.check_open()?;
+        if std::ptr::eq(self, &*replica) {
+            return Err("A database can't replicate to itself".to_string());
+        }
+
+        // Writes take the WAL lock first, so holding it keeps them all out
+        // until the replica is subscribed.
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tables = self.timed(self.tables.read()).await.map_err(|e| e.to_string())?.clone();
+        tables.retain(|_, table| !table.ephemeral);
+        for table in tables.values_mut() {
+            table.finish_maintenance();
+        }
+        {
+            let _replica_wal = replica.timed(replica.wal_writer.write()).await.map_err(|e| e.to_string())?;
+            let mut replica_tables = replica.timed(replica.tables.write()).await.map_err(|e| e.to_string())?;
+            if !replica_tables.is_empty() {
+                return Err("The replica must have no tables".to_string());
+            }
+            replica.named_views.replace(self.named_views.all());
+            replica.sequences.replace(self.sequences.all());
+            *replica_tables = tables;
+            replica.publish_all(&replica_tables);
+        }
+
+        let (entries, mut received) = mpsc::unbounded_channel();
+        let sent = Arc::new(AtomicU64::new(0));
+        wal_writer.replicas.push(Subscriber {
+            entries,
+            sent: Arc::clone(&sent),
+        });
+        drop(wal_writer);
+
+        let (applied_tx, applied) = watch::channel(0);
+        let task = tokio::spawn(async move {
+            while let Some(entry) = received.recv().await {
+                if replica.is_closed() {
+                    break;
+                }
+                replica.apply_wal_entry(entry).await;
+                applied_tx.send_modify(|applied| *applied += 1);
+            }
+        });
+        Ok(Replication { sent, applied, task })
+    }
+}
+
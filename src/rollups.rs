@@ -0,0 +1,445 @@
+//! Rollups: tables of aggregates per group, kept up to date on write.
+//!
+//! A rollup groups the rows of a source table by some of its columns, and
+//! by the period a `DateTime` column falls in, and stores one row per group
+//! holding the group's values and its measures. Each group keeps the same
+//! running state as an aggregate materialized view, so a commit only
+//! rewrites the rows of the groups it touched, and reading a rollup never
+//! scans the source. A group is dropped with its last source row. A group
+//! whose minimum or maximum was removed is recomputed from the source rows.
+//!
+//! Like materialized views, rollups are read-only tables that live only in
+//! memory, and `load` recomputes them from their sources. Their filters
+//! can't contain subqueries either, so maintaining them can't fail.
+
+use crate::materialized::AggregateState;
+use crate::subquery::has_subquery;
+use crate::watch::{Changes, WatchEvent};
+use crate::{AggregateFunction, Column, DataType, Database, Period, Query, Row, RowId, Table, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// What a rollup groups rows by.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GroupBy {
+    Column(String),
+    /// The start of the period a `DateTime` column falls in.
+    Period { column: String, period: Period },
+}
+
+impl GroupBy {
+    fn column(&self) -> &str {
+        match self {
+            GroupBy::Column(column) | GroupBy::Period { column, .. } => column,
+        }
+    }
+
+    fn key(&self, row: &Row) -> Value {
+        match (self, row.get(self.column())) {
+            (GroupBy::Period { period, .. }, Some(Value::DateTime(at))) => Value::DateTime(period.start(at)),
+            (_, Some(value)) => value.clone(),
+            (_, None) => Value::Null,
+        }
+    }
+}
+
+/// One aggregate column of a rollup.
+#[derive(Clone, Debug)]
+pub struct Measure {
+    pub name: String,
+    pub function: AggregateFunction,
+    pub column: String,
+}
+
+/// The definition of a rollup over a source table:
+///
+/// ```ignore
+/// Rollup::new("events")
+///     .group_by("kind")
+///     .group_by_period("at", Period::Day)
+///     .measure("events", AggregateFunction::Count, "id")
+///     .measure("revenue", AggregateFunction::Sum, "amount")
+/// ```
+#[derive(Clone, Debug)]
+pub struct Rollup {
+    pub source: String,
+    pub group_by: Vec<GroupBy>,
+    pub measures: Vec<Measure>,
+    /// Only rows matching this are aggregated.
+    pub filter: Option<Query>,
+}
+
+impl Rollup {
+    pub fn new(source: &str) -> Self {
+        Rollup {
+            source: source.to_string(),
+            group_by: Vec::new(),
+            measures: Vec::new(),
+            filter: None,
+        }
+    }
+
+    pub fn group_by(mut self, column: &str) -> Self {
+        self.group_by.push(GroupBy::Column(column.to_string()));
+        self
+    }
+
+    pub fn group_by_period(mut self, column: &str, period: Period) -> Self {
+        self.group_by.push(GroupBy::Period {
+            column: column.to_string(),
+            period,
+        });
+        self
+    }
+
+    /// Adds a column named `name` holding `function` over `column`.
+    pub fn measure(mut self, name: &str, function: AggregateFunction, column: &str) -> Self {
+        self.measures.push(Measure {
+            name: name.to_string(),
+            function,
+            column: column.to_string(),
+        });
+        self
+    }
+
+    pub fn filter(mut self, query: Query) -> Self {
+        self.filter = Some(query);
+        self
+    }
+
+    fn columns(&self, source: &Table) -> Result<Vec<Column>, String> {
+        if self.measures.is_empty() {
+            return Err("A rollup needs at least one measure".to_string());
+        }
+        let source_column = |name: &str| {
+            source
+                .columns
+                .iter()
+                .find(|c| c.name == name)
+                .ok_or_else(|| format!("Column {} not found", name))
+        };
+        let mut columns = Vec::new();
+        for group in &self.group_by {
+            let column = source_column(group.column())?;
+            if matches!(group, GroupBy::Period { .. }) && column.data_type != DataType::DateTime {
+                return Err(format!("Column {} is not a DateTime column", column.name));
+            }
+            columns.push(Column::new(column.name.clone(), column.data_type.clone(), vec![]));
+        }
+        for measure in &self.measures {
+            let column = source_column(&measure.column)?;
+            let data_type = match measure.function {
+                AggregateFunction::Count
+                | AggregateFunction::CountDistinct
+                | AggregateFunction::ApproxCountDistinct => DataType::Integer,
+                AggregateFunction::Sum | AggregateFunction::Avg => DataType::Float,
+                AggregateFunction::Min | AggregateFunction::Max => column.data_type.clone(),
+            };
+            columns.push(Column::new(measure.name.clone(), data_type, vec![]));
+        }
+        let mut names = HashSet::new();
+        if let Some(column) = columns.iter().find(|c| !names.insert(c.name.clone())) {
+            return Err(format!("Rollup column {} appears more than once", column.name));
+        }
+        Ok(columns)
+    }
+}
+
+#[derive(Clone)]
+struct Group {
+    rows: usize,
+    measures: Vec<AggregateState>,
+    row_id: Option<RowId>,
+}
+
+#[derive(Clone)]
+struct RollupState {
+    definition: Rollup,
+    groups: HashMap<Vec<Value>, Group>,
+}
+
+impl RollupState {
+    fn key(&self, row: &Row) -> Vec<Value> {
+        self.definition.group_by.iter().map(|group| group.key(row)).collect()
+    }
+
+    fn add(&mut self, key: Vec<Value>, row: &Row) {
+        let measures = &self.definition.measures;
+        let group = self.groups.entry(key).or_insert_with(|| Group {
+            rows: 0,
+            measures: measures.iter().map(|_| AggregateState::default()).collect(),
+            row_id: None,
+        });
+        group.rows += 1;
+        for (measure, state) in measures.iter().zip(&mut group.measures) {
+            if let Some(value) = row.get(&measure.column) {
+                state.add(&measure.function, value);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[Value], row: &Row) {
+        let Some(group) = self.groups.get_mut(key) else { return };
+        group.rows -= 1;
+        for (measure, state) in self.definition.measures.iter().zip(&mut group.measures) {
+            if let Some(value) = row.get(&measure.column) {
+                state.remove(&measure.function, value);
+            }
+        }
+    }
+
+    // The rollup row for a group.
+    fn row(&self, table: &Table, key: &[Value], group: &Group) -> Row {
+        let mut row = Row::with_schema(table.schema.clone());
+        for (by, value) in self.definition.group_by.iter().zip(key) {
+            row.insert(by.column().to_string(), value.clone());
+        }
+        for (measure, state) in self.definition.measures.iter().zip(&group.measures) {
+            row.insert(measure.name.clone(), state.result(&measure.function));
+        }
+        row
+    }
+
+    // Writes the rows of the groups in `keys` to `table`, dropping the
+    // groups left without source rows.
+    fn write_groups(&mut self, table: &mut Table, keys: &HashSet<Vec<Value>>) {
+        // New groups are appended in key order.
+        let mut keys: Vec<&Vec<Value>> = keys.iter().collect();
+        keys.sort();
+        for key in keys {
+            let Some(group) = self.groups.get(key) else { continue };
+            let position = group.row_id.and_then(|id| table.position_of(id));
+            if group.rows == 0 {
+                self.groups.remove(key);
+                if let Some(position) = position {
+                    table.remove_rows(&[position]);
+                }
+                continue;
+            }
+            let row = self.row(table, key, group);
+            match position {
+                Some(position) => {
+                    table.replace_row(position, row);
+                }
+                None => {
+                    let id = table.push_row(row);
+                    if let Some(group) = self.groups.get_mut(key) {
+                        group.row_id = Some(id);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Rollups {
+    rollups: Mutex<HashMap<String, RollupState>>,
+}
+
+impl Rollups {
+    // A copy for `Database::fork`.
+    pub(crate) fn fork(&self) -> Self {
+        Rollups {
+            rollups: Mutex::new(self.rollups.lock().unwrap().clone()),
+        }
+    }
+
+    pub(crate) fn active(&self) -> bool {
+        !self.rollups.lock().unwrap().is_empty()
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.rollups.lock().unwrap().contains_key(name)
+    }
+
+    pub(crate) fn depending_on(&self, table_name: &str) -> Vec<String> {
+        let rollups = self.rollups.lock().unwrap();
+        let mut names: Vec<String> = rollups
+            .iter()
+            .filter(|(_, state)| state.definition.source == table_name)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        self.rollups.lock().unwrap().remove(name);
+    }
+
+    pub(crate) fn rename_table(&self, old: &str, new: &str) {
+        for state in self.rollups.lock().unwrap().values_mut() {
+            if state.definition.source == old {
+                state.definition.source = new.to_string();
+            }
+        }
+    }
+}
+
+impl Database {
+    /// Creates a table named `name` holding `rollup` over its source table,
+    /// kept up to date as the source changes. The rollup can be queried and
+    /// indexed, but not written to directly.
+    pub async fn create_rollup(&self, name: &str, rollup: Rollup) -> Result<(), String> {
+        self.check_open()?;
+        self.check_exportable(&rollup.source)?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        if tables.contains_key(name) || self.named_views.contains(name) {
+            return Err(format!("Table {} already exists", name));
+        }
+        let source = tables
+            .get(&rollup.source)
+            .ok_or_else(|| format!("Table {} not found", rollup.source))?;
+        if rollup.filter.as_ref().is_some_and(has_subquery) {
+            return Err("Rollup filters can't contain subqueries".to_string());
+        }
+        let mut table = Table::new(name.to_string(), rollup.columns(source)?, true);
+        let mut state = RollupState {
+            definition: rollup,
+            groups: HashMap::new(),
+        };
+        self.recompute_rollup(&mut state, &mut table, &tables);
+        tables.insert(name.to_string(), table);
+        self.rollups.rollups.lock().unwrap().insert(name.to_string(), state);
+        self.publish(&tables, &[name]);
+        Ok(())
+    }
+
+    pub async fn drop_rollup(&self, name: &str) -> Result<(), String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        if self.rollups.rollups.lock().unwrap().remove(name).is_none() {
+            return Err(format!("Rollup {} not found", name));
+        }
+        tables.remove(name);
+        self.publish(&tables, &[]);
+        Ok(())
+    }
+
+    /// Applies committed `changes` to the rollups over the changed tables
+    /// and returns the names of the rollups that were updated.
+    pub(crate) fn maintain_rollups(&self, tables: &mut HashMap<String, Table>, changes: &Changes) -> Vec<String> {
+        let mut rollups = self.rollups.rollups.lock().unwrap();
+        let mut updated = Vec::new();
+        for (name, state) in rollups.iter_mut() {
+            if !changes.iter().any(|(table, _, _)| *table == state.definition.source) {
+                continue;
+            }
+            let Some(mut table) = tables.remove(name) else {
+                continue;
+            };
+            let source = state.definition.source.clone();
+            let mut touched = HashSet::new();
+            for (_, _, event) in changes.iter().filter(|(t, _, _)| *t == source) {
+                let (removed, added) = match event {
+                    WatchEvent::Insert(row) => (None, Some(row)),
+                    WatchEvent::Delete(row) => (Some(row), None),
+                    WatchEvent::Update { old, new } => (Some(old), Some(new)),
+                };
+                if let Some(row) = removed.filter(|row| self.rollup_matches(state, row)) {
+                    let key = state.key(row);
+                    state.remove(&key, row);
+                    touched.insert(key);
+                }
+                if let Some(row) = added.filter(|row| self.rollup_matches(state, row)) {
+                    let key = state.key(row);
+                    state.add(key.clone(), row);
+                    touched.insert(key);
+                }
+            }
+            let stale: HashSet<Vec<Value>> = touched
+                .iter()
+                .filter(|key| {
+                    state.groups.get(*key).is_some_and(|group| {
+                        group.rows > 0 && group.measures.iter().any(|measure| measure.stale)
+                    })
+                })
+                .cloned()
+                .collect();
+            if !stale.is_empty() {
+                if let Some(source) = tables.get(&source) {
+                    self.recompute_groups(state, source, &stale);
+                }
+            }
+            state.write_groups(&mut table, &touched);
+            table.maintain(self.maintenance_deferred());
+            tables.insert(name.clone(), table);
+            updated.push(name.clone());
+        }
+        updated
+    }
+
+    /// Recomputes every rollup, for after the source tables were replaced.
+    pub(crate) fn refresh_rollups(&self, tables: &mut HashMap<String, Table>) {
+        let mut rollups = self.rollups.rollups.lock().unwrap();
+        for (name, state) in rollups.iter_mut() {
+            if let Some(mut table) = tables.remove(name) {
+                self.recompute_rollup(state, &mut table, tables);
+                tables.insert(name.clone(), table);
+            }
+        }
+    }
+
+    fn rollup_matches(&self, state: &RollupState, row: &Row) -> bool {
+        state
+            .definition
+            .filter
+            .as_ref()
+            .is_none_or(|filter| self.matches_query(row, filter).is_ok_and(|m| m))
+    }
+
+    // Rows of `source` the rollup aggregates, by position.
+    fn rollup_rows(&self, state: &RollupState, source: &Table) -> Vec<usize> {
+        match &state.definition.filter {
+            Some(filter) => self.execute_query(source, filter).unwrap_or_default(),
+            None => (0..source.data.len()).collect(),
+        }
+    }
+
+    fn recompute_rollup(&self, state: &mut RollupState, table: &mut Table, tables: &HashMap<String, Table>) {
+        state.groups.clear();
+        if let Some(source) = tables.get(&state.definition.source) {
+            let mut rows = self.rollup_rows(state, source);
+            rows.sort_unstable();
+            for i in rows {
+                let key = state.key(&source.data[i]);
+                state.add(key, &source.data[i]);
+            }
+        }
+        let mut keys: Vec<Vec<Value>> = state.groups.keys().cloned().collect();
+        keys.sort();
+        let rows = keys
+            .iter()
+            .map(|key| Arc::new(state.row(table, key, &state.groups[key])))
+            .collect();
+        table.set_rows(rows);
+        for (position, key) in keys.iter().enumerate() {
+            if let Some(group) = state.groups.get_mut(key) {
+                group.row_id = table.row_id(position);
+            }
+        }
+        table.maintain(self.maintenance_deferred());
+    }
+
+    // Rebuilds the running state of the groups in `keys` from the source.
+    fn recompute_groups(&self, state: &mut RollupState, source: &Table, keys: &HashSet<Vec<Value>>) {
+        let mut row_ids = HashMap::new();
+        for key in keys {
+            if let Some(group) = state.groups.remove(key) {
+                row_ids.insert(key.clone(), group.row_id);
+            }
+        }
+        for i in self.rollup_rows(state, source) {
+            let key = state.key(&source.data[i]);
+            if keys.contains(&key) {
+                state.add(key, &source.data[i]);
+            }
+        }
+        for (key, row_id) in row_ids {
+            if let Some(group) = state.groups.get_mut(&key) {
+                group.row_id = row_id;
+            }
+        }
+    }
+}
@@ -0,0 +1,498 @@
+use crate::compression::Compressed;
+use crate::dictionary::Dictionary;
+use crate::{Column, Value};
+use chrono::{DateTime, Utc};
+use serde::de::Deserializer;
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+use std::ops::{Deref, Index};
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+/// Column names shared by every row of a table, so each row only stores its
+/// values. Rows built outside a table get a schema of their own.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RowSchema {
+    names: Vec<String>,
+    positions: HashMap<String, usize>,
+    // Parallel to `names`; set for dictionary-encoded columns.
+    dictionaries: Vec<Option<Arc<Dictionary>>>,
+    // Parallel to `names`; the smallest value compressed, for compressed
+    // columns.
+    compression: Vec<Option<usize>>,
+}
+
+impl RowSchema {
+    pub fn new<I: IntoIterator<Item = String>>(names: I) -> Self {
+        let mut schema = RowSchema::default();
+        for name in names {
+            schema.push(name);
+        }
+        schema
+    }
+
+    pub fn from_columns(columns: &[Column]) -> Self {
+        RowSchema::new(columns.iter().map(|c| c.name.clone()))
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.positions.get(name).copied()
+    }
+
+    /// A copy of the schema with a new, empty dictionary for `column`.
+    pub(crate) fn with_dictionary(&self, column: &str) -> RowSchema {
+        let mut schema = self.clone();
+        let position = schema.push(column.to_string());
+        schema.dictionaries[position] = Some(Arc::new(Dictionary::new()));
+        schema.compression[position] = None;
+        schema
+    }
+
+    /// A copy of the schema compressing values of `column` of at least
+    /// `min_bytes`.
+    pub(crate) fn with_compression(&self, column: &str, min_bytes: usize) -> RowSchema {
+        let mut schema = self.clone();
+        let position = schema.push(column.to_string());
+        schema.dictionaries[position] = None;
+        schema.compression[position] = Some(min_bytes);
+        schema
+    }
+
+    pub(crate) fn dictionary(&self, column: &str) -> Option<&Dictionary> {
+        self.position(column)
+            .and_then(|i| self.dictionaries[i].as_deref())
+    }
+
+    pub(crate) fn dictionary_bytes(&self) -> usize {
+        self.dictionaries
+            .iter()
+            .flatten()
+            .map(|d| d.estimated_size())
+            .sum()
+    }
+
+    fn decode(&self, position: usize, slot: Slot) -> Value {
+        match slot {
+            Slot::Value(value) => value,
+            Slot::Code(code) => self.dictionaries[position]
+                .as_ref()
+                .map_or(Value::Null, |d| d.value(code).clone()),
+            Slot::Compressed(compressed) => (*compressed).into_value(),
+        }
+    }
+
+    fn push(&mut self, name: String) -> usize {
+        if let Some(position) = self.positions.get(&name) {
+            return *position;
+        }
+        let position = self.names.len();
+        self.positions.insert(name.clone(), position);
+        self.names.push(name);
+        self.dictionaries.push(None);
+        self.compression.push(None);
+        position
+    }
+}
+
+#[derive(Clone)]
+enum Slot {
+    Value(Value),
+    // Index into the schema's dictionary for this column.
+    Code(u32),
+    // Boxed so the other slots don't pay for its size.
+    Compressed(Box<Compressed>),
+}
+
+/// A row stored as values ordered by its schema. The API mirrors the parts of
+/// `HashMap<String, Value>` the rest of the crate relies on, and adds typed
+/// getters that return `None` when the column is missing, `Null`, or of
+/// another type.
+///
+/// For compatibility the row still derefs to a `HashMap<String, Value>`,
+/// which is built on first use and kept until the row changes, so prefer the
+/// row's own methods where they exist. Change a row with its own `insert`,
+/// `remove`, `get_mut` and `extend`.
+///
+/// Inserting a column the schema doesn't know about gives the row its own copy
+/// of the schema, so rows keep behaving like maps at the cost of the sharing.
+#[derive(Default)]
+pub struct Row {
+    schema: Arc<RowSchema>,
+    values: Vec<Option<Slot>>,
+    // The row as a map, for `Deref`. Boxed so rows that never build it only
+    // pay for a pointer.
+    #[allow(clippy::box_collection)]
+    map: OnceLock<Box<HashMap<String, Value>>>,
+}
+
+// The map built for `Deref` isn't copied; the copy builds its own if needed.
+impl Clone for Row {
+    fn clone(&self) -> Self {
+        Row {
+            schema: self.schema.clone(),
+            values: self.values.clone(),
+            map: OnceLock::new(),
+        }
+    }
+}
+
+impl Row {
+    pub fn new() -> Self {
+        Row::default()
+    }
+
+    /// An empty row laid out for `schema`.
+    pub fn with_schema(schema: Arc<RowSchema>) -> Self {
+        let values = vec![None; schema.names.len()];
+        Row {
+            schema,
+            values,
+            map: OnceLock::new(),
+        }
+    }
+
+    /// Builds a row laid out for `schema` from a column map.
+    pub fn from_map(schema: &Arc<RowSchema>, map: HashMap<String, Value>) -> Self {
+        let mut row = Row::with_schema(schema.clone());
+        for (name, value) in map {
+            row.insert(name, value);
+        }
+        row
+    }
+
+    pub fn schema(&self) -> &Arc<RowSchema> {
+        &self.schema
+    }
+
+    /// Re-lays the row out for `schema`, sharing it when possible.
+    pub fn conform(&self, schema: &Arc<RowSchema>) -> Row {
+        if Arc::ptr_eq(&self.schema, schema) {
+            return self.clone();
+        }
+        let mut row = Row::with_schema(schema.clone());
+        for (name, value) in self.iter() {
+            row.insert(name.clone(), value.clone());
+        }
+        row
+    }
+
+    fn slot(&self, position: usize) -> Option<&Value> {
+        match self.values[position].as_ref()? {
+            Slot::Value(value) => Some(value),
+            Slot::Code(code) => self.schema.dictionaries[position]
+                .as_ref()
+                .map(|d| d.value(*code)),
+            Slot::Compressed(compressed) => Some(compressed.value()),
+        }
+    }
+
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        self.schema.position(column).and_then(|i| self.slot(i))
+    }
+
+    pub fn get_mut(&mut self, column: &str) -> Option<&mut Value> {
+        let position = self.schema.position(column)?;
+        self.map.take();
+        if let Some(Slot::Code(_) | Slot::Compressed(_)) = self.values[position] {
+            let slot = self.values[position].take()?;
+            self.values[position] = Some(Slot::Value(self.schema.decode(position, slot)));
+        }
+        match self.values[position].as_mut()? {
+            Slot::Value(value) => Some(value),
+            Slot::Code(_) | Slot::Compressed(_) => None,
+        }
+    }
+
+    /// The dictionary code stored for `column`, if it was encoded with
+    /// `dictionary`.
+    pub(crate) fn code(&self, column: &str, dictionary: &Dictionary) -> Option<u32> {
+        let position = self.schema.position(column)?;
+        let own = self.schema.dictionaries[position].as_deref()?;
+        match self.values[position] {
+            Some(Slot::Code(code)) if own == dictionary => Some(code),
+            _ => None,
+        }
+    }
+
+    pub fn contains_key(&self, column: &str) -> bool {
+        self.get(column).is_some()
+    }
+
+    pub fn insert(&mut self, column: String, value: Value) -> Option<Value> {
+        self.map.take();
+        let position = match self.schema.position(&column) {
+            Some(position) => position,
+            None => {
+                let position = Arc::make_mut(&mut self.schema).push(column);
+                self.values.resize(position + 1, None);
+                position
+            }
+        };
+        let slot = match (value, &self.schema.dictionaries[position], self.schema.compression[position]) {
+            (Value::String(s), Some(dictionary), _) => Slot::Code(dictionary.encode(s)),
+            (value, _, Some(min_bytes)) => Compressed::compress(value, min_bytes).map_or_else(Slot::Value, |c| Slot::Compressed(Box::new(c))),
+            (value, _, None) => Slot::Value(value),
+        };
+        self.values[position]
+            .replace(slot)
+            .map(|old| self.schema.decode(position, old))
+    }
+
+    pub fn remove(&mut self, column: &str) -> Option<Value> {
+        let position = self.schema.position(column)?;
+        self.map.take();
+        self.values[position]
+            .take()
+            .map(|old| self.schema.decode(position, old))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.iter().filter(|v| v.is_some()).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.iter().all(|v| v.is_none())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.schema
+            .names
+            .iter()
+            .enumerate()
+            .filter_map(|(i, name)| self.slot(i).map(|v| (name, v)))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.iter().map(|(name, _)| name)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    pub fn to_map(&self) -> HashMap<String, Value> {
+        self.iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    pub fn into_map(self) -> HashMap<String, Value> {
+        self.into_iter().collect()
+    }
+
+    /// Estimated bytes used by the row's values. The schema is shared by the
+    /// table's rows, so it isn't counted, and neither are dictionary strings.
+    pub fn estimated_size(&self) -> usize {
+        size_of::<Row>()
+            + self
+                .values
+                .iter()
+                .map(|slot| match slot {
+                    Some(Slot::Value(value)) => value.estimated_size(),
+                    Some(Slot::Compressed(compressed)) => compressed.estimated_size(),
+                    _ => size_of::<Option<Slot>>(),
+                })
+                .sum::<usize>()
+    }
+
+    pub(crate) fn has_compressed(&self) -> bool {
+        self.values.iter().any(|slot| matches!(slot, Some(Slot::Compressed(_))))
+    }
+
+    pub fn get_i64(&self, column: &str) -> Option<i64> {
+        match self.get(column) {
+            Some(Value::Integer(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn get_f64(&self, column: &str) -> Option<f64> {
+        match self.get(column) {
+            Some(Value::Float(f)) => Some(*f),
+            _ => None,
+        }
+    }
+
+    pub fn get_str(&self, column: &str) -> Option<&str> {
+        match self.get(column) {
+            Some(Value::String(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_bool(&self, column: &str) -> Option<bool> {
+        match self.get(column) {
+            Some(Value::Boolean(b)) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn get_datetime(&self, column: &str) -> Option<DateTime<Utc>> {
+        match self.get(column) {
+            Some(Value::DateTime(dt)) => Some(*dt),
+            _ => None,
+        }
+    }
+
+    pub fn get_uuid(&self, column: &str) -> Option<Uuid> {
+        match self.get(column) {
+            Some(Value::Uuid(u)) => Some(*u),
+            _ => None,
+        }
+    }
+
+    pub fn get_json(&self, column: &str) -> Option<&serde_json::Value> {
+        match self.get(column) {
+            Some(Value::Json(j)) => Some(j),
+            _ => None,
+        }
+    }
+
+    /// Typed access that explains why a value couldn't be read.
+    /// Use `Option<T>` to accept `Null`.
+    pub fn try_get<T: FromValue>(&self, column: &str) -> Result<T, String> {
+        let value = self
+            .get(column)
+            .ok_or_else(|| format!("Column {} not found", column))?;
+        T::from_value(value).map_err(|e| format!("Column {}: {}", column, e))
+    }
+}
+
+impl Deref for Row {
+    type Target = HashMap<String, Value>;
+
+    fn deref(&self) -> &Self::Target {
+        self.map.get_or_init(|| Box::new(self.to_map()))
+    }
+}
+
+impl Index<&str> for Row {
+    type Output = Value;
+
+    /// The value of `column`. Panics if the row has none, as a map does.
+    fn index(&self, column: &str) -> &Value {
+        self.get(column).expect("column not in row")
+    }
+}
+
+impl PartialEq for Row {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(name, value)| other.get(name) == Some(value))
+    }
+}
+
+impl fmt::Debug for Row {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Rows are encoded as maps so the WAL and snapshot formats don't depend on
+// the in-memory layout.
+impl Serialize for Row {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (name, value) in self.iter() {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Row {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        HashMap::<String, Value>::deserialize(deserializer).map(Row::from)
+    }
+}
+
+impl From<HashMap<String, Value>> for Row {
+    fn from(map: HashMap<String, Value>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl From<Row> for HashMap<String, Value> {
+    fn from(row: Row) -> Self {
+        row.into_map()
+    }
+}
+
+impl FromIterator<(String, Value)> for Row {
+    fn from_iter<I: IntoIterator<Item = (String, Value)>>(iter: I) -> Self {
+        let mut row = Row::new();
+        row.extend(iter);
+        row
+    }
+}
+
+impl Extend<(String, Value)> for Row {
+    fn extend<I: IntoIterator<Item = (String, Value)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl IntoIterator for Row {
+    type Item = (String, Value);
+    type IntoIter = std::vec::IntoIter<(String, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let schema = self.schema;
+        schema
+            .names
+            .iter()
+            .zip(self.values)
+            .enumerate()
+            .filter_map(|(i, (name, slot))| slot.map(|slot| (name.clone(), schema.decode(i, slot))))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+pub trait FromValue: Sized {
+    fn from_value(value: &Value) -> Result<Self, String>;
+}
+
+macro_rules! impl_from_value {
+    ($ty:ty, $variant:ident, $name:expr) => {
+        impl FromValue for $ty {
+            fn from_value(value: &Value) -> Result<Self, String> {
+                match value {
+                    Value::$variant(v) => Ok(v.clone()),
+                    other => Err(format!("expected {}, got {:?}", $name, other)),
+                }
+            }
+        }
+    };
+}
+
+impl_from_value!(i64, Integer, "Integer");
+impl_from_value!(f64, Float, "Float");
+impl_from_value!(String, String, "String");
+impl_from_value!(bool, Boolean, "Boolean");
+impl_from_value!(DateTime<Utc>, DateTime, "DateTime");
+impl_from_value!(Uuid, Uuid, "Uuid");
+impl_from_value!(serde_json::Value, Json, "Json");
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Null => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
@@ -0,0 +1,179 @@
+//! Stable row ids.
+//!
+//! Every row is given an id when it is inserted, one more than the last id
+//! the table handed out, and keeps it until it is deleted; updates keep the
+//! id. Rows are stored in id order, so a row's position is found from its
+//! id by binary search. Indexes hold ids rather than positions, so deleting
+//! rows only removes their index entries instead of rebuilding every index.
+//! Snapshots store the ids, so they are kept across restarts too.
+
+use crate::{memory, Row, Table, Value};
+use imbl::Vector;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+pub type RowId = u64;
+
+// Ids in a persistent vector, which copies share until one of them changes.
+pub(crate) type IdList = Vector<RowId>;
+
+impl Table {
+    /// The id of the row at `position`.
+    pub fn row_id(&self, position: usize) -> Option<RowId> {
+        self.row_ids.get(position).copied()
+    }
+
+    pub(crate) fn position_of(&self, id: RowId) -> Option<usize> {
+        self.row_ids.binary_search(&id).ok()
+    }
+
+    /// Positions of the rows with these ids, skipping any that are gone.
+    pub(crate) fn positions<'a>(&'a self, ids: impl IntoIterator<Item = &'a RowId> + 'a) -> impl Iterator<Item = usize> + 'a {
+        ids.into_iter().filter_map(|id| self.position_of(*id))
+    }
+
+    // Numbers rows that were loaded without ids, for snapshots written before
+    // ids were stored.
+    pub(crate) fn assign_row_ids(&mut self) {
+        if self.row_ids.len() != self.data.len() {
+            self.row_ids = (0..self.data.len() as RowId).collect();
+            self.next_row_id = self.data.len() as RowId;
+        }
+    }
+
+    // Indexes are only kept up to date here while they aren't stale;
+    // stale indexes are rebuilt from scratch anyway.
+    fn index_row(&mut self, id: RowId, row: &Row) {
+        if self.indexes_stale {
+            return;
+        }
+        self.text_index_row(id, row);
+        for (column, index) in &mut self.indexes {
+            if let Some(value) = row.get(column) {
+                index.insert(value.clone(), id);
+                *self.index_sizes.entry(column.clone()).or_insert(0) += memory::index_entry_size(value);
+            }
+        }
+    }
+
+    fn unindex_rows(&mut self, rows: &[(RowId, &Row)]) {
+        if self.indexes_stale || rows.is_empty() {
+            return;
+        }
+        for (id, row) in rows {
+            self.text_unindex_row(*id, row);
+        }
+        let removed: HashSet<RowId> = rows.iter().map(|(id, _)| *id).collect();
+        for (column, index) in &mut self.indexes {
+            let mut values: HashSet<&Value> = HashSet::new();
+            let mut freed = 0;
+            for (_, row) in rows {
+                if let Some(value) = row.get(column) {
+                    freed += memory::index_entry_size(value);
+                    values.insert(value);
+                }
+            }
+            for value in values {
+                index.remove(value, &removed);
+            }
+            if let Some(size) = self.index_sizes.get_mut(column) {
+                *size = size.saturating_sub(freed);
+            }
+        }
+    }
+
+    /// Appends `row` under the next id.
+    pub(crate) fn push_row(&mut self, row: Row) -> RowId {
+        let id = self.next_row_id;
+        self.next_row_id += 1;
+        if !self.merkle_stale {
+            self.merkle_leaves.push_back(Table::merkle_leaf(id, &row));
+        }
+        self.index_row(id, &row);
+        self.partition_row(id, &row);
+        self.data_bytes += row.estimated_size();
+        self.data.push_back(Arc::new(row));
+        self.row_ids.push_back(id);
+        self.changed_since_analyze += 1;
+        id
+    }
+
+    /// Puts back a row removed with `remove_rows`, under its old id and so
+    /// in its old place.
+    pub(crate) fn restore_row(&mut self, id: RowId, row: Arc<Row>) {
+        let position = self.row_ids.binary_search(&id).unwrap_or_else(|at| at);
+        if !self.merkle_stale {
+            self.merkle_leaves.insert(position, Table::merkle_leaf(id, &row));
+        }
+        self.index_row(id, &row);
+        self.partition_row(id, &row);
+        self.data_bytes += row.estimated_size();
+        self.data.insert(position, row);
+        self.row_ids.insert(position, id);
+        self.changed_since_analyze += 1;
+    }
+
+    /// Replaces the row at `position`, keeping its id, and returns the old
+    /// row.
+    pub(crate) fn replace_row(&mut self, position: usize, row: Row) -> Arc<Row> {
+        let id = self.row_ids[position];
+        let old = std::mem::replace(&mut self.data[position], Arc::new(row));
+        self.unindex_rows(&[(id, &old)]);
+        self.unpartition_rows(&[(id, &old)]);
+        let new = self.data[position].clone();
+        if !self.merkle_stale {
+            self.merkle_leaves.set(position, Table::merkle_leaf(id, &new));
+        }
+        self.index_row(id, &new);
+        self.partition_row(id, &new);
+        self.data_bytes = self.data_bytes + new.estimated_size() - old.estimated_size();
+        self.changed_since_analyze += 1;
+        old
+    }
+
+    /// Removes the rows at `positions` and returns them with their ids.
+    pub(crate) fn remove_rows(&mut self, positions: &[usize]) -> Vec<(RowId, Arc<Row>)> {
+        if positions.is_empty() {
+            return Vec::new();
+        }
+        let removed: Vec<(RowId, Arc<Row>)> = positions
+            .iter()
+            .map(|&i| (self.row_ids[i], self.data[i].clone()))
+            .collect();
+        let rows: Vec<(RowId, &Row)> = removed.iter().map(|(id, row)| (*id, &**row)).collect();
+        self.unindex_rows(&rows);
+        self.unpartition_rows(&rows);
+
+        let mut kept = vec![true; self.data.len()];
+        positions.iter().for_each(|&i| kept[i] = false);
+        let mut flags = kept.iter();
+        self.data.retain(|_| *flags.next().unwrap());
+        let mut flags = kept.iter();
+        self.row_ids.retain(|_| *flags.next().unwrap());
+        if !self.merkle_stale {
+            let mut flags = kept.iter();
+            self.merkle_leaves.retain(|_| *flags.next().unwrap());
+        }
+        self.data_bytes -= removed.iter().map(|(_, row)| row.estimated_size()).sum::<usize>();
+        self.changed_since_analyze += removed.len();
+        removed
+    }
+
+    /// Replaces every row, giving them new ids, and marks the indexes for
+    /// rebuilding.
+    pub(crate) fn set_rows(&mut self, rows: Vec<Arc<Row>>) {
+        let start = self.next_row_id;
+        self.next_row_id += rows.len() as RowId;
+        self.row_ids = (start..self.next_row_id).collect();
+        self.data_bytes = rows.iter().map(|r| r.estimated_size()).sum();
+        self.changed_since_analyze += rows.len();
+        self.data = rows.into();
+        self.merkle_stale = true;
+        let partitioning = self.partitions.take().map(|p| p.by);
+        self.set_partitioning(partitioning);
+        if !self.indexes.is_empty() && !self.indexes_stale {
+            self.plan_epoch = crate::optimizer::next_epoch();
+            self.indexes_stale = true;
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Random samples of a query's rows.
+//!
+//! A `Sample` runs its query, then keeps a random subset of the matches by
+//! reservoir sampling: the first `n` matches fill the reservoir and each
+//! later one replaces a random entry with decreasing probability, so every
+//! match is equally likely to be kept. The random numbers come from `seed`,
+//! so the same seed over the same rows picks the same sample. The sample is
+//! returned in table order.
+
+use crate::{Column, Database, Query};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sample {
+    pub query: Box<Query>,
+    pub size: SampleSize,
+    pub seed: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SampleSize {
+    /// This fraction of the matching rows, from 0 to 1, rounded to the
+    /// nearest row.
+    Fraction(f64),
+    /// This many of the matching rows, or all of them if there are fewer.
+    Rows(usize),
+}
+
+impl Sample {
+    /// Picks the sample from `positions`, the query's matches, and returns
+    /// it in ascending order.
+    pub(crate) fn pick(&self, positions: Vec<usize>) -> Vec<usize> {
+        let n = match self.size {
+            SampleSize::Fraction(fraction) => (fraction * positions.len() as f64).round() as usize,
+            SampleSize::Rows(n) => n,
+        };
+        if n >= positions.len() {
+            return positions;
+        }
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut reservoir = positions[..n].to_vec();
+        for (i, position) in positions.into_iter().enumerate().skip(n) {
+            let j = rng.gen_range(0..=i);
+            if j < n {
+                reservoir[j] = position;
+            }
+        }
+        reservoir.sort_unstable();
+        reservoir
+    }
+}
+
+impl Database {
+    pub(crate) fn check_sample(&self, sample: &Sample, columns: &[Column]) -> Result<(), String> {
+        if let SampleSize::Fraction(fraction) = sample.size {
+            if !(0.0..=1.0).contains(&fraction) {
+                return Err(format!("A sample fraction must be from 0 to 1, not {}", fraction));
+            }
+        }
+        if matches!(*sample.query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_)) {
+            return Err("Only filter queries can be sampled".to_string());
+        }
+        self.check_query(&sample.query, columns)
+    }
+}
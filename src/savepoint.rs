@@ -0,0 +1,52 @@
+//! Transactions run in a closure, with savepoints.
+//!
+//! `Database::transaction` hands a closure a fresh `Transaction` and commits
+//! it if the closure returns `Ok`, so a transaction can't be left neither
+//! committed nor dropped. A transaction only queues its writes until it is
+//! committed, so a closure that returns `Err` or panics leaves the tables
+//! as they were without anything to undo.
+//!
+//! `Transaction::savepoint` nests a closure inside a transaction: its writes
+//! are discarded if it returns `Err` and kept otherwise, and the enclosing
+//! closure decides what the error means for the rest of the transaction.
+//! Savepoints nest to any depth. As with `commit`, reads inside a
+//! transaction don't see its queued writes.
+
+use crate::{Database, Transaction, WriteError};
+
+impl Transaction {
+    /// Runs `f` on this transaction, discarding the writes it queued if it
+    /// returns `Err`.
+    pub async fn savepoint<T, E>(&mut self, f: impl AsyncFnOnce(&mut Transaction) -> Result<T, E>) -> Result<T, E> {
+        let mark = self.operations.len();
+        let result = f(self).await;
+        if result.is_err() {
+            self.operations.truncate(mark);
+        }
+        result
+    }
+
+    /// How many writes are queued.
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+impl Database {
+    /// Runs `f` on a new transaction and commits it if `f` returns `Ok`, as
+    /// described in the module docs. A failed commit is returned as `f`'s
+    /// error type.
+    pub async fn transaction<T, E>(&self, f: impl AsyncFnOnce(&mut Transaction) -> Result<T, E>) -> Result<T, E>
+    where
+        E: From<WriteError>,
+    {
+        let mut transaction = Transaction::new();
+        let value = f(&mut transaction).await?;
+        self.commit(transaction).await?;
+        Ok(value)
+    }
+}
@@ -0,0 +1,275 @@
+//! Periodic maintenance jobs.
+//!
+//! Jobs run as background tasks of the database: `close` stops them, and they
+//! stop on their own once the last `Arc<Database>` is dropped. Each job runs
+//! to completion before its next run is scheduled, so runs never overlap.
+//! Cron expressions use the five standard fields (minute, hour, day of month,
+//! month, day of week) and are evaluated in UTC.
+
+use crate::Database;
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, Timelike, Utc};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(CronSchedule),
+}
+
+impl Schedule {
+    pub fn every(period: Duration) -> Self {
+        Schedule::Every(period)
+    }
+
+    pub fn cron(expression: &str) -> Result<Self, String> {
+        expression.parse().map(Schedule::Cron)
+    }
+
+    /// When the job should next run, or `None` if it never will (e.g. a cron
+    /// expression for February 30th).
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Schedule::Every(period) => ChronoDuration::from_std(*period).ok().map(|p| after + p),
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl std::str::FromStr for CronSchedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!(
+                "Invalid cron expression {}: expected 5 fields, got {}",
+                expression,
+                fields.len()
+            ));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // Both 0 and 7 mean Sunday.
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("Invalid cron step {}", part))?;
+                if step == 0 {
+                    return Err(format!("Invalid cron step {}", part));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            // "5/15" means every 15 starting at 5.
+            (value, if step > 1 { max } else { value })
+        };
+        if start > end {
+            return Err(format!("Invalid cron range {}", part));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    match value.parse::<u32>() {
+        Ok(v) if (min..=max).contains(&v) => Ok(v),
+        _ => Err(format!("Invalid cron value {}, expected {}-{}", value, min, max)),
+    }
+}
+
+impl CronSchedule {
+    fn matches(&self, time: &DateTime<Utc>) -> bool {
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        // Standard cron: when both day fields are restricted, either may match.
+        let day_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        };
+        self.minutes & (1 << time.minute()) != 0
+            && self.hours & (1 << time.hour()) != 0
+            && self.months & (1 << time.month()) != 0
+            && day_matches
+    }
+
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut time = after
+            .with_second(0)
+            .and_then(|t| t.with_nanosecond(0))?
+            + ChronoDuration::minutes(1);
+        // Every valid expression matches within a leap cycle.
+        for _ in 0..(4 * 366 * 24 * 60) {
+            if self.matches(&time) {
+                return Some(time);
+            }
+            time += ChronoDuration::minutes(1);
+        }
+        None
+    }
+}
+
+type JobFn = Arc<dyn Fn(Arc<Database>) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send>> + Send + Sync>;
+
+#[derive(Clone)]
+pub enum Job {
+    /// `Database::save`: writes a snapshot and truncates the WAL.
+    Save(String),
+    /// `Database::backup`: writes a snapshot and leaves the WAL alone.
+    Backup(String),
+    /// Flushes and fsyncs the WAL.
+    SyncWal,
+    /// Fails the run if any table's Merkle tree doesn't match its rows.
+    VerifyIntegrity,
+    Custom(JobFn),
+}
+
+impl Job {
+    pub fn custom<F, Fut>(f: F) -> Self
+    where
+        F: Fn(Arc<Database>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), String>> + Send + 'static,
+    {
+        Job::Custom(Arc::new(move |db| Box::pin(f(db))))
+    }
+
+    async fn run(&self, db: Arc<Database>) -> Result<(), String> {
+        match self {
+            Job::Save(path) => db.save(path).await.map_err(|e| e.to_string()),
+            Job::Backup(path) => db.backup(path).await.map_err(|e| e.to_string()),
+            Job::SyncWal => db.sync_wal().await.map_err(|e| e.to_string()),
+            Job::VerifyIntegrity => {
+                if db.verify_integrity().await {
+                    Ok(())
+                } else {
+                    Err("Integrity check failed".to_string())
+                }
+            }
+            Job::Custom(f) => f(db).await,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct JobStatus {
+    pub runs: usize,
+    pub failures: usize,
+    pub last_error: Option<String>,
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+struct ScheduledJob {
+    handle: AbortHandle,
+    status: Arc<Mutex<JobStatus>>,
+}
+
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    jobs: Mutex<HashMap<String, ScheduledJob>>,
+}
+
+impl Database {
+    /// Runs `job` on `schedule` until it is cancelled or the database closes.
+    /// Scheduling a name again replaces the previous job.
+    pub fn schedule(self: &Arc<Self>, name: &str, schedule: Schedule, job: Job) -> Result<(), String> {
+        self.check_open()?;
+        let status = Arc::new(Mutex::new(JobStatus::default()));
+        let db: Weak<Database> = Arc::downgrade(self);
+        let task_status = Arc::clone(&status);
+        let handle = self.spawn_background(async move {
+            while let Some(next) = schedule.next_after(Utc::now()) {
+                task_status.lock().unwrap().next_run = Some(next);
+                let delay = (next - Utc::now()).to_std().unwrap_or_default();
+                tokio::time::sleep(delay).await;
+
+                let db = match db.upgrade() {
+                    Some(db) if !db.is_closed() => db,
+                    _ => break,
+                };
+                let result = job.run(db).await;
+                let mut status = task_status.lock().unwrap();
+                status.runs += 1;
+                if let Err(e) = result {
+                    status.failures += 1;
+                    status.last_error = Some(e);
+                }
+            }
+            task_status.lock().unwrap().next_run = None;
+        });
+        let previous = self
+            .scheduler
+            .jobs
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), ScheduledJob { handle, status });
+        if let Some(previous) = previous {
+            previous.handle.abort();
+        }
+        Ok(())
+    }
+
+    /// Stops a scheduled job. Returns false if there was none by that name.
+    pub fn cancel_job(&self, name: &str) -> bool {
+        match self.scheduler.jobs.lock().unwrap().remove(name) {
+            Some(job) => {
+                job.handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn job_status(&self, name: &str) -> Option<JobStatus> {
+        let jobs = self.scheduler.jobs.lock().unwrap();
+        jobs.get(name).map(|job| job.status.lock().unwrap().clone())
+    }
+
+    pub fn job_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.scheduler.jobs.lock().unwrap().keys().cloned().collect();
+        names.sort();
+        names
+    }
+}
@@ -0,0 +1,94 @@
+//! Schema checks for `create_table`, and for the rows written to a table.
+//!
+//! A new table's columns are checked as a whole before anything is logged,
+//! and the error lists every problem found rather than only the first, so a
+//! schema can be fixed in one pass. Tables replayed from the WAL aren't
+//! checked again, so tables created before these checks still load.
+
+use crate::{Column, Constraint, Table, UnknownColumns, Value};
+use std::collections::{HashMap, HashSet};
+
+pub(crate) fn check_schema(tables: &HashMap<String, Table>, name: &str, columns: &[Column]) -> Result<(), String> {
+    let mut problems = Vec::new();
+    if name.is_empty() {
+        problems.push("the table has no name".to_string());
+    }
+
+    let mut seen = HashSet::new();
+    let mut repeated = HashSet::new();
+    for (i, column) in columns.iter().enumerate() {
+        if column.name.is_empty() {
+            problems.push(format!("column #{} has no name", i));
+        } else if !seen.insert(&column.name) && repeated.insert(&column.name) {
+            problems.push(format!("column {} is declared more than once", column.name));
+        }
+    }
+
+    for column in columns {
+        let mut constraints = HashSet::new();
+        for constraint in &column.constraints {
+            if !constraints.insert(constraint) {
+                problems.push(format!("column {} has the same constraint twice", column.name));
+                continue;
+            }
+            let Constraint::ForeignKey { table, column: target } = constraint else {
+                continue;
+            };
+            // A table may refer to its own columns.
+            let target_columns = if table == name {
+                Some(columns)
+            } else {
+                tables.get(table).map(|t| &t.columns[..])
+            };
+            let Some(target_columns) = target_columns else {
+                problems.push(format!("column {} references table {}, which doesn't exist", column.name, table));
+                continue;
+            };
+            match target_columns.iter().find(|c| &c.name == target) {
+                None => problems.push(format!(
+                    "column {} references {}.{}, which doesn't exist",
+                    column.name, table, target
+                )),
+                Some(_) if table == name && target == &column.name => problems.push(format!(
+                    "column {} references itself, so no value could ever be inserted",
+                    column.name
+                )),
+                Some(c) if c.data_type != column.data_type => problems.push(format!(
+                    "column {} is {:?} but references {}.{}, which is {:?}",
+                    column.name, column.data_type, table, target, c.data_type
+                )),
+                Some(_) => {}
+            }
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Invalid schema for table {}: {}", name, problems.join("; ")))
+    }
+}
+
+/// Applies `policy` to the values in `row` for columns not in `columns`.
+pub(crate) fn check_row(
+    row: &mut HashMap<String, Value>,
+    columns: &[Column],
+    policy: UnknownColumns,
+) -> Result<(), String> {
+    let known = |name: &String| columns.iter().any(|c| &c.name == name);
+    match policy {
+        UnknownColumns::Keep => Ok(()),
+        UnknownColumns::Strip => {
+            row.retain(|name, _| known(name));
+            Ok(())
+        }
+        UnknownColumns::Reject => {
+            let mut unknown: Vec<&str> = row.keys().filter(|name| !known(name)).map(String::as_str).collect();
+            if unknown.is_empty() {
+                return Ok(());
+            }
+            unknown.sort_unstable();
+            Err(format!("Unknown columns: {}", unknown.join(", ")))
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Declarative schemas: the tables, their columns and constraints, and the
+//! indexes on them, as a document kept under version control.
+//!
+//! `Database::export_schema` describes the database as it is, and
+//! `SchemaDefinition` serializes with serde, so it can be written as JSON
+//! with `to_json` or as YAML or anything else serde supports.
+//! `Database::diff_schema` lists the changes that would bring the database
+//! in line with a definition, and `Database::apply_schema` makes them.
+//!
+//! Applying creates the tables and indexes that are missing, each table
+//! after the tables its foreign keys refer to. Tables can't be altered, so a
+//! table whose columns differ from its definition shows up in the diff, but
+//! makes `apply_schema` fail before changing anything. Tables left out of the
+//! definition are left alone. Unique columns are always indexed, so their
+//! indexes aren't listed.
+
+use crate::{Column, Constraint, Database, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SchemaDefinition {
+    pub tables: Vec<TableDefinition>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TableDefinition {
+    pub name: String,
+    pub columns: Vec<Column>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub text_indexes: Vec<String>,
+}
+
+impl SchemaDefinition {
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("Invalid schema: {}", e))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("schemas serialize to JSON")
+    }
+}
+
+/// One step from the database towards a definition, from `diff_schema`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SchemaChange {
+    CreateTable { name: String, columns: Vec<Column> },
+    CreateIndex { table: String, column: String },
+    CreateTextIndex { table: String, column: String },
+    /// The table's columns differ from its definition. `apply_schema`
+    /// refuses these.
+    AlterTable { name: String, from: Vec<Column>, to: Vec<Column> },
+}
+
+fn describe_column(column: &Column) -> String {
+    let mut description = format!("{} {:?}", column.name, column.data_type);
+    for constraint in &column.constraints {
+        match constraint {
+            Constraint::NotNull => description.push_str(" not null"),
+            Constraint::Unique => description.push_str(" unique"),
+            Constraint::ForeignKey { table, column } => {
+                description.push_str(&format!(" references {}({})", table, column))
+            }
+        }
+    }
+    description
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaChange::CreateTable { name, columns } => {
+                let columns: Vec<String> = columns.iter().map(describe_column).collect();
+                write!(f, "create table {} ({})", name, columns.join(", "))
+            }
+            SchemaChange::CreateIndex { table, column } => write!(f, "create index on {} ({})", table, column),
+            SchemaChange::CreateTextIndex { table, column } => {
+                write!(f, "create text index on {} ({})", table, column)
+            }
+            SchemaChange::AlterTable { name, from, to } => {
+                let mut steps = Vec::new();
+                for column in from {
+                    match to.iter().find(|c| c.name == column.name) {
+                        None => steps.push(format!("drop column {}", column.name)),
+                        Some(new) if new != column => {
+                            steps.push(format!("change column {} to {}", describe_column(column), describe_column(new)))
+                        }
+                        Some(_) => {}
+                    }
+                }
+                for column in to.iter().filter(|c| !from.iter().any(|old| old.name == c.name)) {
+                    steps.push(format!("add column {}", describe_column(column)));
+                }
+                if steps.is_empty() {
+                    steps.push("reorder columns".to_string());
+                }
+                write!(f, "alter table {}: {}", name, steps.join(", "))
+            }
+        }
+    }
+}
+
+fn unique(column: &Column) -> bool {
+    column.constraints.contains(&Constraint::Unique)
+}
+
+fn sorted(names: impl Iterator<Item = String>) -> Vec<String> {
+    let mut names: Vec<String> = names.collect();
+    names.sort();
+    names
+}
+
+fn describe_table(table: &Table) -> TableDefinition {
+    TableDefinition {
+        name: table.name.clone(),
+        columns: table.columns.clone(),
+        indexes: sorted(
+            table
+                .indexes
+                .keys()
+                .filter(|name| !table.columns.iter().any(|c| &c.name == *name && unique(c)))
+                .cloned(),
+        ),
+        text_indexes: sorted(table.text_indexes.keys().cloned()),
+    }
+}
+
+// Tables in an order that creates each after the other tables its foreign
+// keys refer to, or an error naming those caught in a cycle.
+fn creation_order<'a>(
+    definitions: Vec<&'a TableDefinition>,
+    existing: &HashSet<String>,
+) -> Result<Vec<&'a TableDefinition>, String> {
+    let mut created = existing.clone();
+    let mut pending = definitions;
+    let mut order = Vec::new();
+    while !pending.is_empty() {
+        let (ready, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|definition| {
+            definition.columns.iter().flat_map(|c| &c.constraints).all(|constraint| match constraint {
+                Constraint::ForeignKey { table, .. } => table == &definition.name || created.contains(table),
+                _ => true,
+            })
+        });
+        if ready.is_empty() {
+            let names: Vec<&str> = rest.iter().map(|d| d.name.as_str()).collect();
+            return Err(format!("Foreign keys between {} refer to tables that can't be created first", names.join(", ")));
+        }
+        created.extend(ready.iter().map(|d| d.name.clone()));
+        order.extend(ready);
+        pending = rest;
+    }
+    Ok(order)
+}
+
+impl Database {
+    /// The tables, other than ephemeral ones, as a definition, ordered by
+    /// name.
+    pub async fn export_schema(&self) -> Result<SchemaDefinition, String> {
+        self.check_open()?;
+        let tables = self.timed(self.tables.read()).await.map_err(|e| e.to_string())?;
+        let mut definitions: Vec<TableDefinition> =
+            tables.values().filter(|t| !t.ephemeral).map(describe_table).collect();
+        definitions.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(SchemaDefinition { tables: definitions })
+    }
+
+    /// The changes `apply_schema` would make, in the order it would make
+    /// them, along with any tables it can't bring in line.
+    pub async fn diff_schema(&self, schema: &SchemaDefinition) -> Result<Vec<SchemaChange>, String> {
+        self.check_open()?;
+        let mut names = HashSet::new();
+        if let Some(repeated) = schema.tables.iter().find(|d| !names.insert(&d.name)) {
+            return Err(format!("Table {} is defined more than once", repeated.name));
+        }
+        for definition in &schema.tables {
+            let columns: Vec<&str> = definition.columns.iter().map(|c| c.name.as_str()).collect();
+            for column in definition.indexes.iter().chain(&definition.text_indexes) {
+                if !columns.contains(&column.as_str()) {
+                    return Err(format!("Index on {}: column {} not found", definition.name, column));
+                }
+            }
+        }
+
+        let tables = self.timed(self.tables.read()).await.map_err(|e| e.to_string())?;
+        let mut changes = Vec::new();
+        let mut missing = Vec::new();
+        for definition in &schema.tables {
+            match tables.get(&definition.name) {
+                Some(table) => {
+                    if table.columns != definition.columns {
+                        changes.push(SchemaChange::AlterTable {
+                            name: definition.name.clone(),
+                            from: table.columns.clone(),
+                            to: definition.columns.clone(),
+                        });
+                    }
+                }
+                None => missing.push(definition),
+            }
+        }
+        let existing: HashSet<String> = tables.keys().cloned().collect();
+        for definition in creation_order(missing, &existing)? {
+            changes.push(SchemaChange::CreateTable {
+                name: definition.name.clone(),
+                columns: definition.columns.clone(),
+            });
+        }
+        for definition in &schema.tables {
+            let table = tables.get(&definition.name);
+            for column in &definition.indexes {
+                let unique_column = definition.columns.iter().any(|c| &c.name == column && unique(c));
+                if !unique_column && !table.is_some_and(|t| t.indexes.contains_key(column)) {
+                    changes.push(SchemaChange::CreateIndex {
+                        table: definition.name.clone(),
+                        column: column.clone(),
+                    });
+                }
+            }
+            for column in &definition.text_indexes {
+                if !table.is_some_and(|t| t.text_indexes.contains_key(column)) {
+                    changes.push(SchemaChange::CreateTextIndex {
+                        table: definition.name.clone(),
+                        column: column.clone(),
+                    });
+                }
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Creates the tables and indexes in `schema` that are missing, and
+    /// returns the changes made. Fails without changing anything if a table
+    /// would have to be altered.
+    pub async fn apply_schema(&self, schema: &SchemaDefinition) -> Result<Vec<SchemaChange>, String> {
+        let changes = self.diff_schema(schema).await?;
+        let altered: Vec<String> = changes
+            .iter()
+            .filter(|c| matches!(c, SchemaChange::AlterTable { .. }))
+            .map(|c| c.to_string())
+            .collect();
+        if !altered.is_empty() {
+            return Err(format!("Tables can't be altered: {}", altered.join("; ")));
+        }
+        for change in &changes {
+            match change {
+                SchemaChange::CreateTable { name, columns } => {
+                    self.create_table(name.clone(), columns.clone()).await?;
+                }
+                SchemaChange::CreateIndex { table, column } => self.create_index(table, column).await?,
+                SchemaChange::CreateTextIndex { table, column } => self.create_text_index(table, column).await?,
+                SchemaChange::AlterTable { .. } => {}
+            }
+        }
+        Ok(changes)
+    }
+}
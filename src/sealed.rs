@@ -0,0 +1,224 @@
+//! Encrypting snapshots in independently keyed chunks.
+//!
+//! A compressed snapshot is cut into chunks of `CHUNK_SIZE` bytes, and each
+//! is encrypted with the configured `Cipher` under a key of its own, derived
+//! from the database key with HKDF-SHA256 from a random salt picked for
+//! every save and the chunk's index. The chunks are encrypted and decrypted on as many
+//! threads as there are cores.
+//!
+//! No key ever encrypts more than one chunk, so reusing a nonce can't expose
+//! two plaintexts under the same key, and a nonce mistake is confined to one
+//! chunk of one save. Each chunk's associated data holds the header, its
+//! index and the number of chunks, so chunks that are reordered, dropped or
+//! taken from another snapshot fail to decrypt.
+//!
+//! The layout is the magic bytes, a format version, the cipher, the salt,
+//! the chunk count, then each chunk's length and ciphertext. Version 1 had
+//! no cipher byte and always used AES-256-GCM. Snapshots written before
+//! chunking, a nonce followed by one AES-256-GCM ciphertext under the
+//! database key, are still read.
+
+use crate::Cipher;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use chacha20poly1305::ChaCha20Poly1305;
+use hkdf::Hkdf;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use sha2::Sha256;
+use std::io;
+use std::thread;
+
+const MAGIC: &[u8; 4] = b"ZAPS";
+const VERSION: u8 = 2;
+const SALT_LEN: usize = 32;
+
+/// The plaintext bytes in every chunk but the last.
+pub(crate) const CHUNK_SIZE: usize = 256 * 1024;
+
+// Each chunk has a key of its own, so a fixed nonce is never reused.
+const NONCE: [u8; 12] = [0; 12];
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Cipher {
+    fn id(self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 0,
+            Cipher::ChaCha20Poly1305 => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> io::Result<Cipher> {
+        match id {
+            0 => Ok(Cipher::Aes256Gcm),
+            1 => Ok(Cipher::ChaCha20Poly1305),
+            _ => Err(invalid(&format!("Unknown cipher {}", id))),
+        }
+    }
+
+    fn encrypt(self, key: &[u8; 32], payload: Payload) -> io::Result<Vec<u8>> {
+        let nonce = Nonce::from_slice(&NONCE);
+        match self {
+            Cipher::Aes256Gcm => Aes256Gcm::new(key.into()).encrypt(nonce, payload),
+            Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).encrypt(nonce, payload),
+        }
+        .map_err(|e| io::Error::other(e.to_string()))
+    }
+
+    fn decrypt(self, key: &[u8; 32], payload: Payload) -> io::Result<Vec<u8>> {
+        let nonce = Nonce::from_slice(&NONCE);
+        match self {
+            Cipher::Aes256Gcm => Aes256Gcm::new(key.into()).decrypt(nonce, payload),
+            Cipher::ChaCha20Poly1305 => ChaCha20Poly1305::new(key.into()).decrypt(nonce, payload),
+        }
+        .map_err(|e| invalid(&e.to_string()))
+    }
+}
+
+fn chunk_key(key: &[u8; 32], salt: &[u8], index: usize) -> [u8; 32] {
+    let mut info = b"zapdb snapshot chunk ".to_vec();
+    info.extend((index as u64).to_le_bytes());
+    let mut chunk_key = [0; 32];
+    Hkdf::<Sha256>::new(Some(salt), key)
+        .expand(&info, &mut chunk_key)
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    chunk_key
+}
+
+// The key for block `index` of spill run `run`, derived from a join's key.
+fn spill_key(key: &[u8; 32], run: u64, index: u64) -> [u8; 32] {
+    let mut info = b"zapdb spill block ".to_vec();
+    info.extend(run.to_le_bytes());
+    info.extend(index.to_le_bytes());
+    let mut block_key = [0; 32];
+    Hkdf::<Sha256>::new(None, key)
+        .expand(&info, &mut block_key)
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    block_key
+}
+
+/// Encrypts block `index` of a join's spill run `run` under a key of its own,
+/// derived from the join's `key`, so blocks can't be moved between places.
+pub(crate) fn seal_spill(key: &[u8; 32], cipher: Cipher, run: u64, index: u64, data: &[u8]) -> io::Result<Vec<u8>> {
+    cipher.encrypt(&spill_key(key, run, index), Payload { msg: data, aad: &[] })
+}
+
+/// Decrypts what `seal_spill` wrote.
+pub(crate) fn open_spill(key: &[u8; 32], cipher: Cipher, run: u64, index: u64, data: &[u8]) -> io::Result<Vec<u8>> {
+    cipher.decrypt(&spill_key(key, run, index), Payload { msg: data, aad: &[] })
+}
+
+fn associated_data(header: &[u8], index: usize) -> Vec<u8> {
+    let mut aad = header.to_vec();
+    aad.extend((index as u32).to_le_bytes());
+    aad
+}
+
+// `f` applied to every item, spread over the available cores, in order.
+fn in_parallel<T: Sync, R: Send>(items: &[T], f: impl Fn(usize, &T) -> io::Result<R> + Sync) -> io::Result<Vec<R>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()).min(items.len()).max(1);
+    let per_thread = items.len().div_ceil(threads).max(1);
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(per_thread)
+            .enumerate()
+            .map(|(n, batch)| {
+                let f = &f;
+                scope.spawn(move || {
+                    batch
+                        .iter()
+                        .enumerate()
+                        .map(|(i, item)| f(n * per_thread + i, item))
+                        .collect::<io::Result<Vec<R>>>()
+                })
+            })
+            .collect();
+        let mut results = Vec::with_capacity(items.len());
+        for handle in handles {
+            results.extend(handle.join().map_err(|_| io::Error::other("Snapshot encryption panicked"))??);
+        }
+        Ok(results)
+    })
+}
+
+/// Encrypts `data` in chunks, as described in the module docs.
+pub(crate) fn seal(key: &[u8; 32], cipher: Cipher, data: &[u8]) -> io::Result<Vec<u8>> {
+    let chunks: Vec<&[u8]> = data.chunks(CHUNK_SIZE).collect();
+    let mut header = MAGIC.to_vec();
+    header.extend([VERSION, cipher.id()]);
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    header.extend(salt);
+    header.extend((chunks.len() as u32).to_le_bytes());
+
+    let sealed = in_parallel(&chunks, |index, chunk| {
+        let aad = associated_data(&header, index);
+        cipher.encrypt(&chunk_key(key, &salt, index), Payload { msg: chunk, aad: &aad })
+    })?;
+    let mut out = header;
+    for chunk in sealed {
+        out.extend((chunk.len() as u32).to_le_bytes());
+        out.extend(chunk);
+    }
+    Ok(out)
+}
+
+/// Decrypts what `seal` wrote, or a snapshot from before chunking.
+pub(crate) fn open(key: &[u8; 32], buffer: &[u8]) -> io::Result<Vec<u8>> {
+    if buffer.starts_with(MAGIC) {
+        match open_chunked(key, buffer) {
+            Ok(data) => return Ok(data),
+            // One in 2^32 old snapshots starts with the magic bytes by chance.
+            Err(e) => return open_legacy(key, buffer).map_err(|_| e),
+        }
+    }
+    open_legacy(key, buffer)
+}
+
+fn open_chunked(key: &[u8; 32], buffer: &[u8]) -> io::Result<Vec<u8>> {
+    let (cipher, header_len) = match buffer.get(MAGIC.len()) {
+        Some(1) => (Cipher::Aes256Gcm, MAGIC.len() + 1 + SALT_LEN + 4),
+        Some(&VERSION) => {
+            let id = *buffer.get(MAGIC.len() + 1).ok_or_else(|| invalid("Snapshot is truncated"))?;
+            (Cipher::from_id(id)?, MAGIC.len() + 2 + SALT_LEN + 4)
+        }
+        Some(version) => return Err(invalid(&format!("Unsupported snapshot encryption version {}", version))),
+        None => return Err(invalid("Snapshot is truncated")),
+    };
+    let header = buffer.get(..header_len).ok_or_else(|| invalid("Snapshot is truncated"))?;
+    let salt = &header[header_len - 4 - SALT_LEN..header_len - 4];
+    let count = u32::from_le_bytes(header[header_len - 4..].try_into().unwrap()) as usize;
+
+    let mut chunks = Vec::new();
+    let mut rest = &buffer[header_len..];
+    while !rest.is_empty() {
+        let len = rest.get(..4).ok_or_else(|| invalid("Snapshot is truncated"))?;
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        let chunk = rest.get(4..4 + len).ok_or_else(|| invalid("Snapshot is truncated"))?;
+        chunks.push(chunk);
+        rest = &rest[4 + len..];
+    }
+    if chunks.len() != count {
+        return Err(invalid("Snapshot is truncated"));
+    }
+
+    let opened = in_parallel(&chunks, |index, chunk| {
+        let aad = associated_data(header, index);
+        cipher.decrypt(&chunk_key(key, salt, index), Payload { msg: chunk, aad: &aad })
+    })?;
+    Ok(opened.concat())
+}
+
+fn open_legacy(key: &[u8; 32], buffer: &[u8]) -> io::Result<Vec<u8>> {
+    if buffer.len() < 12 {
+        return Err(invalid("Snapshot is truncated"));
+    }
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = Nonce::from_slice(&buffer[..12]);
+    cipher
+        .decrypt(nonce, &buffer[12..])
+        .map_err(|e| invalid(&e.to_string()))
+}
@@ -0,0 +1,272 @@
+//! ORDER BY, LIMIT, OFFSET and column projection for `select`.
+//!
+//! `Database::select_with` runs a query like `select`, then sorts its rows
+//! on any number of columns, skips `offset` of them and keeps up to `limit`.
+//! With `columns`, only those columns of the rows it returns are cloned,
+//! which saves copying the large values of wide tables; `select_columns` is
+//! a shorthand for that alone.
+//! For a plain filter on a table with both an order and a limit, the scan
+//! keeps only the first `offset + limit` rows in a heap, as `select_top`
+//! does, and clones only the rows it returns. Joins, aggregates, traversals
+//! and views are selected in full first.
+//!
+//! Nulls, and rows without the column, sort after every value, so they come
+//! last in ascending order and first in descending order. Rows that sort
+//! equal keep the order `select` gives them.
+//!
+//! `DatabaseConfig::result_limits` applies to the rows left after the limit,
+//! as projected, and going over it fails with `SelectError::Limit`.
+
+use crate::top_k::{smallest, SortOrder};
+use crate::{Database, Instant, OperationKind, Query, Row, RowSchema, SelectError, Value};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How to sort, skip and limit the rows of a select:
+///
+/// ```ignore
+/// SelectOptions::new().order_by("age", SortOrder::Descending).order_by("name", SortOrder::Ascending).offset(20).limit(10)
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SelectOptions {
+    /// Columns to sort on, the first taking precedence.
+    pub order_by: Vec<(String, SortOrder)>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+    /// The columns to return, in order, or `None` for all of them.
+    pub columns: Option<Vec<String>>,
+}
+
+impl SelectOptions {
+    pub fn new() -> Self {
+        SelectOptions::default()
+    }
+
+    pub fn order_by(mut self, column: &str, order: SortOrder) -> Self {
+        self.order_by.push((column.to_string(), order));
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = Some(columns.iter().map(|c| c.to_string()).collect());
+        self
+    }
+}
+
+// Orders values of different types by type, numbers together, so the
+// order is total.
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Integer(_) | Value::Float(_) => 0,
+        Value::String(_) => 1,
+        Value::Boolean(_) => 2,
+        Value::DateTime(_) => 3,
+        Value::Uuid(_) => 4,
+        Value::Json(_) => 5,
+        Value::Null => 6,
+        Value::Param(_) => 7,
+    }
+}
+
+fn compare_values(a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let (a, b) = (a.unwrap_or(&Value::Null), b.unwrap_or(&Value::Null));
+    a.partial_cmp(b).unwrap_or_else(|| type_rank(a).cmp(&type_rank(b)))
+}
+
+// A row's values in the sort columns, then its place in the results.
+struct SortKey<'a> {
+    values: Vec<Option<&'a Value>>,
+    order_by: &'a [(String, SortOrder)],
+    position: usize,
+}
+
+impl<'a> SortKey<'a> {
+    fn new(row: &'a Row, order_by: &'a [(String, SortOrder)], position: usize) -> Self {
+        SortKey {
+            values: order_by.iter().map(|(column, _)| row.get(column)).collect(),
+            order_by,
+            position,
+        }
+    }
+}
+
+impl Ord for SortKey<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.values
+            .iter()
+            .zip(&other.values)
+            .zip(self.order_by)
+            .map(|((a, b), (_, order))| match order {
+                SortOrder::Ascending => compare_values(*a, *b),
+                SortOrder::Descending => compare_values(*b, *a),
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+            .then(self.position.cmp(&other.position))
+    }
+}
+
+impl PartialOrd for SortKey<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for SortKey<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for SortKey<'_> {}
+
+// The positions, out of `keys`, of the rows `options` selects, in order.
+fn arrange<'a>(keys: impl Iterator<Item = SortKey<'a>>, options: &SelectOptions) -> Vec<usize> {
+    let sorted: Vec<SortKey> = match options.limit {
+        _ if options.order_by.is_empty() => keys.collect(),
+        Some(limit) => smallest(keys, options.offset.saturating_add(limit)),
+        None => {
+            let mut keys: Vec<SortKey> = keys.collect();
+            keys.sort_unstable();
+            keys
+        }
+    };
+    let limit = options.limit.unwrap_or(usize::MAX);
+    sorted.into_iter().skip(options.offset).take(limit).map(|key| key.position).collect()
+}
+
+fn check_columns<'a>(columns: impl Iterator<Item = &'a str> + Clone, options: &SelectOptions) -> Result<(), String> {
+    let sorted = options.order_by.iter().map(|(name, _)| name);
+    let projected = options.columns.iter().flatten();
+    match sorted.chain(projected).find(|name| !columns.clone().any(|c| c == name.as_str())) {
+        Some(name) => Err(format!("Column {} not found", name)),
+        None => Ok(()),
+    }
+}
+
+fn project(row: &Row, schema: &Arc<RowSchema>) -> Row {
+    let mut projected = Row::with_schema(schema.clone());
+    for name in schema.names() {
+        if let Some(value) = row.get(name) {
+            projected.insert(name.clone(), value.clone());
+        }
+    }
+    projected
+}
+
+impl Database {
+    /// Rows matching `query`, sorted, skipped and limited as `options`
+    /// says; see the module docs.
+    pub async fn select_with(
+        &self,
+        table_name: &str,
+        query: &Query,
+        options: &SelectOptions,
+    ) -> Result<(Vec<Row>, Duration), SelectError> {
+        let select = self.select_filtered_with(table_name, query, None, options);
+        self.measured(table_name, OperationKind::Select, select).await
+    }
+
+    // `select_with`, keeping only the rows of a join or traversal that match
+    // `filter`.
+    pub(crate) async fn select_filtered_with(
+        &self,
+        table_name: &str,
+        query: &Query,
+        filter: Option<&Query>,
+        options: &SelectOptions,
+    ) -> Result<(Vec<Row>, Duration), SelectError> {
+        self.check_open()?;
+        let start = Instant::now();
+        let named = options.order_by.iter().map(|(column, _)| column).chain(options.columns.iter().flatten());
+        for column in named {
+            self.check_column_readable(table_name, column)?;
+        }
+        if matches!(query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_))
+            || self.named_views.contains(table_name)
+        {
+            let (rows, _) = match filter {
+                Some(filter) => self.select_filtered(table_name, query, Some(filter)).await?,
+                None => self.select_rows(table_name, query).await?,
+            };
+            let rows = self.arrange_rows(table_name, &rows, options)?;
+            return Ok((rows, start.elapsed()));
+        }
+
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        check_columns(table.columns.iter().map(|c| c.name.as_str()), options)?;
+        self.check_query(query, &table.columns)?;
+        self.check_readable(table_name, query)?;
+        let optimized_query = self.query_planner.optimize(query.clone(), table);
+        let mut matching = self.execute_query(table, &optimized_query)?;
+        matching.sort_unstable();
+        let keys = matching
+            .into_iter()
+            .map(|i| SortKey::new(&table.data[i], &options.order_by, i));
+        let selected = arrange(keys, options).into_iter().map(|i| &*table.data[i]).collect();
+        let mut rows = self.finish_select(table_name, selected, options)?;
+        for row in &mut rows {
+            self.hide_row_columns(table_name, query, row);
+        }
+        Ok((rows, start.elapsed()))
+    }
+
+    /// The columns named of the rows matching `query`, in that order.
+    pub async fn select_columns(
+        &self,
+        table_name: &str,
+        query: &Query,
+        columns: &[&str],
+    ) -> Result<(Vec<Row>, Duration), String> {
+        let options = SelectOptions::new().columns(columns);
+        Ok(self.select_with(table_name, query, &options).await?)
+    }
+
+    // Sorts, skips and limits `rows`, already selected in full, and copies
+    // out what's left.
+    pub(crate) fn arrange_rows<R: Borrow<Row>>(
+        &self,
+        table_name: &str,
+        rows: &[R],
+        options: &SelectOptions,
+    ) -> Result<Vec<Row>, SelectError> {
+        if let Some(row) = rows.first() {
+            check_columns(row.borrow().schema().names().iter().map(String::as_str), options)?;
+        }
+        let keys = rows.iter().enumerate().map(|(i, row)| SortKey::new(row.borrow(), &options.order_by, i));
+        let selected = arrange(keys, options).into_iter().map(|i| rows[i].borrow()).collect();
+        self.finish_select(table_name, selected, options)
+    }
+
+    // Copies out the selected rows, or the columns of them asked for, within
+    // the result limits.
+    fn finish_select(&self, table_name: &str, mut selected: Vec<&Row>, options: &SelectOptions) -> Result<Vec<Row>, SelectError> {
+        match &options.columns {
+            Some(columns) => {
+                let schema = Arc::new(RowSchema::new(columns.iter().cloned()));
+                let mut rows: Vec<Row> = selected.into_iter().map(|row| project(row, &schema)).collect();
+                self.limit_results(table_name, &mut rows)?;
+                Ok(rows)
+            }
+            None => {
+                self.limit_results(table_name, &mut selected)?;
+                Ok(selected.into_iter().cloned().collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Named sequences of integers, for identifiers shared across tables.
+//!
+//! Every value handed out by `next_val` is logged to the WAL before it is
+//! returned, and snapshots store each sequence's next value, so a value is
+//! never handed out twice, even after a crash. Values skipped by failed or
+//! abandoned writes are not reused.
+
+use crate::{Database, WalEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Sequence {
+    next: i64,
+    increment: i64,
+}
+
+#[derive(Default)]
+pub(crate) struct Sequences {
+    sequences: Mutex<HashMap<String, Sequence>>,
+}
+
+impl Sequences {
+    pub(crate) fn all(&self) -> HashMap<String, Sequence> {
+        self.sequences.lock().unwrap().clone()
+    }
+
+    pub(crate) fn replace(&self, sequences: HashMap<String, Sequence>) {
+        *self.sequences.lock().unwrap() = sequences;
+    }
+}
+
+impl Database {
+    /// Creates a sequence whose first value is `start`, with each value
+    /// `increment` more than the last. A negative increment counts down.
+    pub async fn create_sequence(&self, name: &str, start: i64, increment: i64) -> Result<(), String> {
+        self.check_open()?;
+        if increment == 0 {
+            return Err("A sequence's increment can't be 0".to_string());
+        }
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut sequences = self.sequences.sequences.lock().unwrap();
+        if sequences.contains_key(name) {
+            return Err(format!("Sequence {} already exists", name));
+        }
+        wal_writer
+            .log(&WalEntry::CreateSequence {
+                name: name.to_string(),
+                start,
+                increment,
+            })
+            .map_err(|e| e.to_string())?;
+        sequences.insert(name.to_string(), Sequence { next: start, increment });
+        Ok(())
+    }
+
+    pub async fn drop_sequence(&self, name: &str) -> Result<(), String> {
+        self.check_open()?;
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut sequences = self.sequences.sequences.lock().unwrap();
+        if !sequences.contains_key(name) {
+            return Err(format!("Sequence {} not found", name));
+        }
+        wal_writer
+            .log(&WalEntry::DropSequence { name: name.to_string() })
+            .map_err(|e| e.to_string())?;
+        sequences.remove(name);
+        Ok(())
+    }
+
+    /// Hands out the sequence's next value.
+    pub async fn next_val(&self, name: &str) -> Result<i64, String> {
+        self.check_open()?;
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut sequences = self.sequences.sequences.lock().unwrap();
+        let sequence = sequences
+            .get_mut(name)
+            .ok_or_else(|| format!("Sequence {} not found", name))?;
+        let value = sequence.next;
+        let next = value
+            .checked_add(sequence.increment)
+            .ok_or_else(|| format!("Sequence {} is exhausted", name))?;
+        wal_writer
+            .log(&WalEntry::SequenceValue {
+                name: name.to_string(),
+                value,
+            })
+            .map_err(|e| e.to_string())?;
+        sequence.next = next;
+        Ok(value)
+    }
+
+    /// The value `next_val` would hand out next, without using it.
+    pub fn peek_val(&self, name: &str) -> Option<i64> {
+        self.sequences.sequences.lock().unwrap().get(name).map(|s| s.next)
+    }
+
+    pub fn sequence_names(&self) -> Vec<String> {
+        self.sequences.sequences.lock().unwrap().keys().cloned().collect()
+    }
+
+    /// Replays a `WalEntry::SequenceValue`.
+    pub(crate) fn replay_sequence_value(&self, name: &str, value: i64) {
+        if let Some(sequence) = self.sequences.sequences.lock().unwrap().get_mut(name) {
+            if let Some(next) = value.checked_add(sequence.increment) {
+                sequence.next = next;
+            }
+        }
+    }
+}
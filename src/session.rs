@@ -0,0 +1,33 @@
+//! Per-connection session context.
+//!
+//! Each pooled connection is its own `Database` handle, so the context lives
+//! on the handle: it is set from the pool's default when the connection is
+//! created and again each time it is checked out, and can be changed for
+//! the rest of the checkout with `set_session`. The context describes who
+//! is working and how; features that act per session read it from here.
+//!
+//! Tenants aren't part of it. A session's tables would have to be resolved
+//! in every operation that names one, so a connection working for a tenant
+//! uses a `Database::tenant` handle, which is confined to its namespace.
+
+use crate::Database;
+use std::time::Duration;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SessionContext {
+    /// The role the session acts as.
+    pub role: Option<String>,
+    /// How long a single operation may take.
+    pub statement_timeout: Option<Duration>,
+}
+
+impl Database {
+    pub fn session(&self) -> SessionContext {
+        self.session.read().unwrap().clone()
+    }
+
+    /// Replaces the session context for later operations on this handle.
+    pub fn set_session(&self, session: SessionContext) {
+        *self.session.write().unwrap() = session;
+    }
+}
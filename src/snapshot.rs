@@ -0,0 +1,94 @@
+//! Lock-free read path.
+//!
+//! Writers still serialize on `Database::tables`, but after each write they
+//! publish an immutable copy of the tables they changed. Queries load the
+//! published map instead of taking the lock, so readers never wait on writers
+//! or on each other. Unchanged tables are shared between successive snapshots,
+//! and a changed table's rows, indexes and Merkle leaves are persistent
+//! structures, so its copy shares everything but the parts the write touched.
+//!
+//! While maintenance is deferred, per-write publishing is skipped to keep bulk
+//! loads cheap and queries read through the lock until the mode ends.
+
+use crate::{Database, Table, Timeout};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLockReadGuard;
+
+pub(crate) type Snapshot = HashMap<String, Arc<Table>>;
+
+pub(crate) enum TablesView<'a> {
+    Snapshot(Arc<Snapshot>),
+    Locked(RwLockReadGuard<'a, HashMap<String, Table>>),
+}
+
+impl TablesView<'_> {
+    pub(crate) fn get(&self, name: &str) -> Option<&Table> {
+        match self {
+            TablesView::Snapshot(tables) => tables.get(name).map(|t| &**t),
+            TablesView::Locked(tables) => tables.get(name),
+        }
+    }
+
+    pub(crate) fn values(&self) -> Box<dyn Iterator<Item = &Table> + '_> {
+        match self {
+            TablesView::Snapshot(tables) => Box::new(tables.values().map(|t| &**t)),
+            TablesView::Locked(tables) => Box::new(tables.values()),
+        }
+    }
+}
+
+impl Database {
+    /// The tables to read, waiting for the lock for up to the operation
+    /// timeout while maintenance is deferred.
+    pub(crate) async fn read_tables(&self) -> Result<TablesView<'_>, Timeout> {
+        if self.maintenance_deferred() {
+            Ok(TablesView::Locked(self.timed(self.tables.read()).await?))
+        } else {
+            Ok(TablesView::Snapshot(self.snapshot.load_full()))
+        }
+    }
+
+    // `read_tables` for getters with no error to report a timeout through.
+    pub(crate) async fn read_tables_untimed(&self) -> TablesView<'_> {
+        if self.maintenance_deferred() {
+            TablesView::Locked(self.tables.read().await)
+        } else {
+            TablesView::Snapshot(self.snapshot.load_full())
+        }
+    }
+
+    /// Publishes the `changed` tables. Call with the write lock still held.
+    /// Tables that lost an index to memory eviction are republished too, so
+    /// the snapshot doesn't keep the index alive.
+    pub(crate) fn publish(&self, tables: &HashMap<String, Table>, changed: &[&str]) {
+        if self.maintenance_deferred() {
+            return;
+        }
+        let current = self.snapshot.load();
+        let next = tables
+            .iter()
+            .map(|(name, table)| {
+                let published = current.get(name).filter(|published| {
+                    !changed.contains(&name.as_str())
+                        && published.indexes.len() == table.indexes.len()
+                        && published.text_indexes.len() == table.text_indexes.len()
+                });
+                let table = match published {
+                    Some(published) => Arc::clone(published),
+                    None => Arc::new(table.clone()),
+                };
+                (name.clone(), table)
+            })
+            .collect();
+        self.snapshot.store(Arc::new(next));
+    }
+
+    pub(crate) fn publish_all(&self, tables: &HashMap<String, Table>) {
+        let next = tables
+            .iter()
+            .map(|(name, table)| (name.clone(), Arc::new(table.clone())))
+            .collect();
+        self.snapshot.store(Arc::new(next));
+    }
+}
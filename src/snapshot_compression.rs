@@ -0,0 +1,118 @@
+//! Compressing snapshots.
+//!
+//! Snapshots are compressed with gzip unless `DatabaseConfig::
+//! snapshot_compression` picks zstd, optionally with a dictionary. The
+//! dictionary is trained from a sample of the snapshot's own rows, encoded
+//! as they are in the snapshot, and written ahead of the compressed data, so
+//! the snapshot carries everything needed to read it. If training fails, as
+//! it does with too few rows, the snapshot is compressed without one.
+//!
+//! Compressed data starts with gzip's or zstd's magic number, or with
+//! `DICTIONARY` and the dictionary's length, which is how a snapshot is read
+//! whatever the configuration it was written under.
+//!
+//! zstd is C code, which doesn't build for wasm32, so there only gzip is
+//! available: saving with zstd and loading a zstd snapshot both fail.
+
+use crate::recovery::Skipped;
+use crate::{Codec, RecoveryReport, SnapshotCompression, Table};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{self, Read, Write};
+
+const DICTIONARY: &[u8; 4] = b"ZDCT";
+const ZSTD: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Up to `max` rows spread evenly over `tables`, encoded with `codec`, to
+/// train a dictionary on.
+pub(crate) fn samples(codec: Codec, tables: &[(&str, &Table)], max: usize) -> io::Result<Vec<Vec<u8>>> {
+    let total: usize = tables.iter().map(|(_, table)| table.data.len()).sum();
+    if total == 0 || max == 0 {
+        return Ok(Vec::new());
+    }
+    tables
+        .iter()
+        .flat_map(|(_, table)| table.data.iter())
+        .step_by(total.div_ceil(max))
+        .map(|row| codec.encode(row.as_ref()))
+        .collect()
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(unused_variables))]
+pub(crate) fn compress(compression: SnapshotCompression, encoded: &[u8], samples: &[Vec<u8>]) -> io::Result<Vec<u8>> {
+    match compression {
+        SnapshotCompression::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(encoded)?;
+            encoder.finish()
+        }
+        #[cfg(target_arch = "wasm32")]
+        SnapshotCompression::Zstd { .. } | SnapshotCompression::ZstdDictionary { .. } => Err(zstd_unsupported()),
+        #[cfg(not(target_arch = "wasm32"))]
+        SnapshotCompression::Zstd { level } => zstd::encode_all(encoded, level),
+        #[cfg(not(target_arch = "wasm32"))]
+        SnapshotCompression::ZstdDictionary {
+            level,
+            dictionary_bytes,
+            ..
+        } => {
+            let dictionary = match zstd::dict::from_samples(samples, dictionary_bytes) {
+                Ok(dictionary) if !dictionary.is_empty() => dictionary,
+                _ => return zstd::encode_all(encoded, level),
+            };
+            let mut out = DICTIONARY.to_vec();
+            out.extend((dictionary.len() as u32).to_le_bytes());
+            out.extend(&dictionary);
+            let mut encoder = zstd::Encoder::with_dictionary(out, level, &dictionary)?;
+            encoder.write_all(encoded)?;
+            encoder.finish()
+        }
+    }
+}
+
+/// Decompresses a snapshot however it was compressed. Unless `recover` is
+/// set, a snapshot that doesn't decompress fails; otherwise what did is
+/// returned and the rest is added to `report`.
+pub(crate) fn decompress(compressed: &[u8], report: &mut RecoveryReport, recover: bool) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    let result = if let Some(rest) = compressed.strip_prefix(DICTIONARY) {
+        let length = rest
+            .get(..4)
+            .map(|length| u32::from_le_bytes(length.try_into().unwrap()) as usize)
+            .filter(|length| rest.len() >= 4 + length)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Snapshot dictionary is cut short"))?;
+        let (dictionary, data) = rest[4..].split_at(length);
+        zstd_decode(data, dictionary, &mut decompressed)
+    } else if compressed.starts_with(&ZSTD) {
+        zstd_decode(compressed, &[], &mut decompressed)
+    } else {
+        GzDecoder::new(compressed).read_to_end(&mut decompressed)
+    };
+    if let Err(e) = result {
+        if !recover {
+            return Err(e);
+        }
+        report.skipped.push(Skipped::Snapshot {
+            offset: decompressed.len(),
+            error: e.to_string(),
+        });
+    }
+    Ok(decompressed)
+}
+
+// Decodes a zstd frame, with `dictionary` if it isn't empty.
+#[cfg(not(target_arch = "wasm32"))]
+fn zstd_decode(data: &[u8], dictionary: &[u8], out: &mut Vec<u8>) -> io::Result<usize> {
+    zstd::Decoder::with_dictionary(data, dictionary).and_then(|mut d| d.read_to_end(out))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn zstd_decode(_: &[u8], _: &[u8], _: &mut Vec<u8>) -> io::Result<usize> {
+    Err(zstd_unsupported())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn zstd_unsupported() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "zstd snapshots aren't supported on wasm32")
+}
@@ -0,0 +1,125 @@
+//! Checking a snapshot against its tables' Merkle roots before loading it.
+//!
+//! A follower that bootstraps from a leader's snapshot gets the snapshot
+//! over one channel and can get the roots over another. `backup_with_roots`
+//! writes a snapshot and returns the Merkle root of each table in it, taken
+//! under the same lock, and `load_verified` decodes a snapshot and compares
+//! each table's root with the one expected before loading anything. A table
+//! whose rows or ids differ, or that is missing or unexpected, fails the
+//! load with a `SnapshotError` naming it, and the database is left as it
+//! was. An empty table's root is all zeros.
+
+use crate::recovery::RecoveryReport;
+use crate::{Database, Table};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+/// The Merkle root of each table in a snapshot, by table name.
+pub type TableRoots = BTreeMap<String, [u8; 32]>;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The table's rows don't hash to the expected root.
+    RootMismatch {
+        table: String,
+        expected: [u8; 32],
+        actual: [u8; 32],
+    },
+    /// A table with an expected root isn't in the snapshot.
+    MissingTable(String),
+    /// The snapshot holds a table with no expected root.
+    UnexpectedTable(String),
+    Io(io::Error),
+}
+
+fn hex(root: &[u8; 32]) -> String {
+    root.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::RootMismatch { table, expected, actual } => write!(
+                f,
+                "Table {} has Merkle root {}, expected {}",
+                table,
+                hex(actual),
+                hex(expected)
+            ),
+            SnapshotError::MissingTable(table) => write!(f, "Table {} is missing from the snapshot", table),
+            SnapshotError::UnexpectedTable(table) => write!(f, "Table {} has no expected root", table),
+            SnapshotError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+pub(crate) fn roots<'a>(tables: impl Iterator<Item = (&'a str, &'a Table)>) -> TableRoots {
+    tables
+        .map(|(name, table)| (name.to_string(), table.merkle_root().unwrap_or_default()))
+        .collect()
+}
+
+// The first way `tables` differ from `expected`, checking tables in name
+// order.
+fn check_roots<'a>(tables: impl Iterator<Item = (&'a str, &'a Table)>, expected: &TableRoots) -> Result<(), SnapshotError> {
+    let actual = roots(tables);
+    if let Some(table) = expected.keys().find(|name| !actual.contains_key(*name)) {
+        return Err(SnapshotError::MissingTable(table.clone()));
+    }
+    for (table, actual) in actual {
+        match expected.get(&table) {
+            None => return Err(SnapshotError::UnexpectedTable(table)),
+            Some(expected) if *expected != actual => {
+                return Err(SnapshotError::RootMismatch {
+                    table,
+                    expected: *expected,
+                    actual,
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(())
+}
+
+impl Database {
+    /// The Merkle root of each table but the ephemeral ones, as
+    /// `backup_with_roots` reports them.
+    pub async fn table_roots(&self) -> TableRoots {
+        let tables = self.read_tables_untimed().await;
+        roots(tables.values().filter(|t| !t.ephemeral).map(|t| (t.name.as_str(), t)))
+    }
+
+    /// `backup`, returning the Merkle root of each table written.
+    pub async fn backup_with_roots(&self, path: &str) -> io::Result<TableRoots> {
+        self.check_open_io()?;
+        self.write_snapshot(path).await
+    }
+
+    /// `load`, after checking that the tables of the snapshot at `path`
+    /// have the `expected` roots, as described in the module docs.
+    pub async fn load_verified(&self, path: &str, expected: &TableRoots) -> Result<(), SnapshotError> {
+        self.check_open_io()?;
+        let mut report = RecoveryReport::default();
+        let contents = self.timed(self.read_snapshot(path, &mut report, false)).await.map_err(io::Error::from)??;
+        let Some(contents) = contents else {
+            report.into_result()?;
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path)).into());
+        };
+        check_roots(contents.tables.iter().map(|(name, t)| (name.as_str(), t)), expected)?;
+        let wal = self.timed(self.read_wal()).await.map_err(io::Error::from)??;
+        self.install_snapshot(contents).await;
+        self.replay_wal(wal, &mut report, false).await?;
+        Ok(report.into_result()?)
+    }
+}
@@ -0,0 +1,335 @@
+//! External sort-merge joins, for joins too big to hash in memory.
+//!
+//! A hash join keeps a table of every join key of one side. With
+//! `DatabaseConfig::join_memory_limit` set, a join whose sides would both
+//! need a bigger table than that is sorted on disk instead: each side's
+//! keys and row positions are cut into runs that fit the budget, each run
+//! is sorted and written to a spill file under `DatabaseConfig::spill_dir`,
+//! and the runs of both sides are merged back in step. Only the head of
+//! each run and the rows sharing the current key are held at once; the
+//! joined rows themselves are still built in memory. Without a `spill_dir`,
+//! joins are always hashed in memory.
+//!
+//! The keys are column values, so runs are written in blocks sealed under a
+//! key made for the join and never stored, and each block's key is derived
+//! for its place in its run. A block that was changed, moved or cut off
+//! is caught like a file that can't be read. The files still show how many keys each side has and
+//! how long they are. They are written and read on the querying thread, as
+//! the rest of a query runs.
+//!
+//! Keys are compared by an encoding that is equal for exactly the keys the
+//! hash join matches, so both give the same rows. Spill files are removed
+//! once the join is done. If one can't be written or read back, the join
+//! falls back to hashing in memory.
+
+use crate::sealed::{open_spill, seal_spill};
+use crate::{Cipher, Database, Join, Table, Value};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+
+// What a hash table entry costs beyond its key.
+const ENTRY_OVERHEAD: usize = 3 * size_of::<usize>();
+
+// Runs are at least this big, so a tiny budget doesn't open a file per row.
+const MIN_RUN_BYTES: usize = 64 * 1024;
+
+// Records are sealed in blocks of about this many bytes, and each run being
+// merged holds one block.
+const BLOCK_BYTES: usize = 16 * 1024;
+
+/// The estimated size of a hash table on `column` of the `rows` of `table`.
+pub(crate) fn hash_table_size(table: &Table, rows: &[usize], column: &str) -> usize {
+    rows.iter()
+        .map(|&i| table.data[i].get(column).map_or(0, Value::estimated_size) + ENTRY_OVERHEAD)
+        .sum()
+}
+
+// Bytes that are equal exactly when the hash join would match the keys: a
+// missing column matches only another missing column, and values of
+// different types never match.
+fn encode_key(key: Option<&Value>) -> Vec<u8> {
+    let Some(value) = key else { return vec![0] };
+    let mut bytes = Vec::new();
+    match value {
+        Value::Null => bytes.push(1),
+        Value::Integer(i) => {
+            bytes.push(2);
+            bytes.extend(i.to_be_bytes());
+        }
+        Value::Float(f) => {
+            bytes.push(3);
+            bytes.extend(f.to_bits().to_be_bytes());
+        }
+        Value::String(s) => {
+            bytes.push(4);
+            bytes.extend(s.as_bytes());
+        }
+        Value::Boolean(b) => bytes.extend([5, *b as u8]),
+        Value::DateTime(at) => {
+            bytes.push(6);
+            bytes.extend(at.timestamp().to_be_bytes());
+            bytes.extend(at.timestamp_subsec_nanos().to_be_bytes());
+        }
+        Value::Uuid(uuid) => {
+            bytes.push(7);
+            bytes.extend(uuid.as_bytes());
+        }
+        Value::Json(json) => {
+            bytes.push(8);
+            bytes.extend(serde_json::to_string(json).unwrap_or_default().into_bytes());
+        }
+        Value::Param(i) => {
+            bytes.push(9);
+            bytes.extend(i.to_be_bytes());
+        }
+    }
+    bytes
+}
+
+// A spill file, removed when dropped.
+struct SpillFile(PathBuf);
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+// What a join's runs are sealed with: its key and cipher, and the number
+// the next run will take.
+struct Sealing {
+    key: [u8; 32],
+    cipher: Cipher,
+    runs: u64,
+}
+
+// Seals `block`, preceded by whether it is the run's last, as block `index`
+// of `run`, and writes it with its length.
+fn write_block(
+    writer: &mut BufWriter<File>,
+    sealing: &Sealing,
+    run: u64,
+    index: u64,
+    last: bool,
+    block: &[u8],
+) -> io::Result<()> {
+    let mut plain = Vec::with_capacity(block.len() + 1);
+    plain.push(last as u8);
+    plain.extend_from_slice(block);
+    let sealed = seal_spill(&sealing.key, sealing.cipher, run, index, &plain)?;
+    writer.write_all(&(sealed.len() as u32).to_le_bytes())?;
+    writer.write_all(&sealed)
+}
+
+// Writes `run`, sorted, as records of a key length, the key and a position,
+// in sealed blocks.
+fn write_run(run: &mut Vec<(Vec<u8>, usize)>, dir: &Path, sealing: &mut Sealing) -> io::Result<(SpillFile, RunReader)> {
+    run.sort_unstable();
+    let path = dir.join(format!("zapdb-join-{}-{:016x}.spill", std::process::id(), rand::random::<u64>()));
+    let file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+    let spill = SpillFile(path);
+    let number = sealing.runs;
+    sealing.runs += 1;
+    let mut writer = BufWriter::new(file);
+    let (mut block, mut index) = (Vec::with_capacity(BLOCK_BYTES), 0);
+    for (key, position) in run.drain(..) {
+        block.extend((key.len() as u32).to_le_bytes());
+        block.extend(key);
+        block.extend((position as u64).to_le_bytes());
+        if block.len() >= BLOCK_BYTES {
+            write_block(&mut writer, sealing, number, index, false, &block)?;
+            block.clear();
+            index += 1;
+        }
+    }
+    write_block(&mut writer, sealing, number, index, true, &block)?;
+    writer.flush()?;
+    let reader = RunReader {
+        file: BufReader::new(File::open(&spill.0)?),
+        key: sealing.key,
+        cipher: sealing.cipher,
+        run: number,
+        index: 0,
+        block: Vec::new(),
+        at: 0,
+        last: false,
+    };
+    Ok((spill, reader))
+}
+
+// Reads a run back a block at a time.
+struct RunReader {
+    file: BufReader<File>,
+    key: [u8; 32],
+    cipher: Cipher,
+    run: u64,
+    // The next block to read.
+    index: u64,
+    block: Vec<u8>,
+    at: usize,
+    // Whether `block` is the run's last.
+    last: bool,
+}
+
+impl RunReader {
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        let bytes = self
+            .block
+            .get(self.at..self.at + len)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Spill record cut off"))?;
+        self.at += len;
+        Ok(bytes)
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<(Vec<u8>, usize)>> {
+        while self.at == self.block.len() {
+            if self.last {
+                return Ok(None);
+            }
+            let mut len = [0; 4];
+            self.file.read_exact(&mut len)?;
+            let mut sealed = vec![0; u32::from_le_bytes(len) as usize];
+            self.file.read_exact(&mut sealed)?;
+            self.block = open_spill(&self.key, self.cipher, self.run, self.index, &sealed)?;
+            self.index += 1;
+            self.last = self.block.first() == Some(&1);
+            self.at = 1;
+        }
+        let len = u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes"));
+        let key = self.take(len as usize)?.to_vec();
+        let position = u64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes"));
+        Ok(Some((key, position as usize)))
+    }
+}
+
+// One side of the join, read back in key order by merging its runs.
+struct SortedSide {
+    runs: Vec<RunReader>,
+    // The next record of each run not yet read: key, position and run.
+    heap: BinaryHeap<Reverse<(Vec<u8>, usize, usize)>>,
+    head: Option<(Vec<u8>, usize)>,
+    _files: Vec<SpillFile>,
+}
+
+impl SortedSide {
+    fn new(
+        table: &Table,
+        rows: &[usize],
+        column: &str,
+        budget: usize,
+        dir: &Path,
+        sealing: &mut Sealing,
+    ) -> io::Result<Self> {
+        let budget = budget.max(MIN_RUN_BYTES);
+        let (mut files, mut runs) = (Vec::new(), Vec::new());
+        let mut run = Vec::new();
+        let mut bytes = 0;
+        for &i in rows {
+            let key = encode_key(table.data[i].get(column));
+            bytes += key.len() + ENTRY_OVERHEAD;
+            run.push((key, i));
+            if bytes >= budget {
+                let (file, reader) = write_run(&mut run, dir, sealing)?;
+                files.push(file);
+                runs.push(reader);
+                bytes = 0;
+            }
+        }
+        if !run.is_empty() {
+            let (file, reader) = write_run(&mut run, dir, sealing)?;
+            files.push(file);
+            runs.push(reader);
+        }
+
+        let mut heap = BinaryHeap::new();
+        for (n, reader) in runs.iter_mut().enumerate() {
+            if let Some((key, position)) = reader.read_record()? {
+                heap.push(Reverse((key, position, n)));
+            }
+        }
+        let mut side = SortedSide {
+            runs,
+            heap,
+            head: None,
+            _files: files,
+        };
+        side.advance()?;
+        Ok(side)
+    }
+
+    fn advance(&mut self) -> io::Result<()> {
+        self.head = match self.heap.pop() {
+            Some(Reverse((key, position, n))) => {
+                if let Some((next, next_position)) = self.runs[n].read_record()? {
+                    self.heap.push(Reverse((next, next_position, n)));
+                }
+                Some((key, position))
+            }
+            None => None,
+        };
+        Ok(())
+    }
+
+    // The positions of the rows with the head's key, moving past them.
+    fn group(&mut self) -> io::Result<Vec<usize>> {
+        let Some((key, _)) = self.head.clone() else { return Ok(Vec::new()) };
+        let mut positions = Vec::new();
+        while let Some((_, position)) = self.head.as_ref().filter(|(k, _)| *k == key) {
+            positions.push(*position);
+            self.advance()?;
+        }
+        Ok(positions)
+    }
+}
+
+impl Database {
+    /// The pairs `join_pairs` finds, found by sorting both sides in spill
+    /// files holding up to `budget` bytes of keys each. Fails without a
+    /// `spill_dir`.
+    pub(crate) fn sort_merge_pairs(
+        &self,
+        (left_table, left_rows): (&Table, &[usize]),
+        (right_table, right_rows): (&Table, &[usize]),
+        join: &Join,
+        budget: usize,
+    ) -> io::Result<Vec<(usize, usize)>> {
+        let dir = self
+            .config
+            .spill_dir
+            .as_ref()
+            .map(PathBuf::from)
+            .ok_or_else(|| io::Error::other("No spill_dir is configured"))?;
+        let mut key = [0; 32];
+        OsRng.fill_bytes(&mut key);
+        let mut sealing = Sealing {
+            key,
+            cipher: self.config.cipher,
+            runs: 0,
+        };
+        let (left_col, right_col) = &join.on_condition;
+        let mut left = SortedSide::new(left_table, left_rows, left_col, budget, &dir, &mut sealing)?;
+        let mut right = SortedSide::new(right_table, right_rows, right_col, budget, &dir, &mut sealing)?;
+
+        let mut pairs = Vec::new();
+        while let (Some((l, _)), Some((r, _))) = (&left.head, &right.head) {
+            match l.cmp(r) {
+                Ordering::Less => left.advance()?,
+                Ordering::Greater => right.advance()?,
+                Ordering::Equal => {
+                    let (lefts, rights) = (left.group()?, right.group()?);
+                    for &l in &lefts {
+                        pairs.extend(rights.iter().map(|&r| (l, r)));
+                    }
+                }
+            }
+        }
+        self.memory.spilled_joins.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        Ok(pairs)
+    }
+}
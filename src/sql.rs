@@ -0,0 +1,1059 @@
+//! A SQL text interface.
+//!
+//! `Database::execute_sql` parses one statement in a small subset of SQL into
+//! the structures the rest of the API takes, `Column`s, `Query`s,
+//! `SelectOptions` and `UpdateExpr`s, and runs it:
+//!
+//! - `CREATE TABLE t (id INTEGER PRIMARY KEY, name TEXT NOT NULL, owner INTEGER REFERENCES users (id))`
+//! - `INSERT INTO t (id, name) VALUES (1, 'a'), (2, 'b')`
+//! - `SELECT ... FROM t [[INNER | LEFT | RIGHT] JOIN u ON t.a = u.b ...] [WHERE ...]
+//!   [GROUP BY ...] [ORDER BY ... [ASC | DESC]] [LIMIT n] [OFFSET n]`
+//! - `UPDATE t SET a = 1, b = 'x' [WHERE ...]`
+//! - `DELETE FROM t [WHERE ...]`
+//! - `ANALYZE t`, which collects the planner's statistics, as
+//!   `Database::analyze` does
+//!
+//! WHERE compares columns with literals, using `=`, `!=`, `<>`, `<`, `<=`,
+//! `>`, `>=`, `[NOT] LIKE`, `ILIKE`, `REGEXP`, `IN (...)` and `BETWEEN ...
+//! AND ...`, combined with `AND`, `OR` and parentheses. `IN (SELECT c FROM
+//! t [WHERE ...])` is a `Query::InSubquery`. Literals are
+//! converted to their column's type where nothing is lost, so a string can
+//! be compared with or written to a `DateTime`, `Uuid` or `Json` column.
+//!
+//! A SELECT returns columns, or one aggregate, `COUNT`, `SUM`, `AVG`, `MIN`,
+//! `MAX` or `COUNT(DISTINCT ...)`, alongside the columns it is grouped by.
+//! An aggregate is named as written, like `sum(total)`, unless given an
+//! alias. The columns of a join are named after their tables, or the tables'
+//! aliases, as with `JoinColumns::Aliased`: `SELECT u.name, p.title FROM
+//! users u JOIN posts p ON u.id = p.user_id`. Further joins are made to the
+//! rows joined so far, as with `Database::select_joins`, so their `ON`
+//! names those columns by table: `... JOIN comments c ON p.id = c.post_id`.
+//! A multi-row INSERT is one
+//! transaction, and UPDATE is replayed from the WAL like `update_with`.
+//!
+//! Keywords are case-insensitive, and identifiers may be double-quoted.
+//!
+//! Values from users belong in bind parameters, never in the SQL text.
+//! `Database::execute_sql_with_params` takes the values for `?`s, in order,
+//! and `Database::execute_sql_with_named_params` those for `:name`s, binding
+//! them as `Database::execute_prepared` does. A statement with parameters
+//! may not also contain literal values, so one that has had input formatted
+//! into it is rejected rather than run. `execute_sql` refuses parameters.
+
+use crate::prepared::{bind, bind_value};
+use crate::top_k::SortOrder;
+use crate::{
+    AggregateFunction, AggregateQuery, Coercion, Column, Condition, Constraint, Database, DataType, Join, JoinChain, JoinType,
+    Operator, Params, Query, Row, RowSchema, SelectOptions, Transaction, UpdateExpr, Value,
+};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// What a statement run by `execute_sql` returns.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SqlOutput {
+    /// The rows of a SELECT.
+    Rows(Vec<Row>),
+    /// How many rows an INSERT, UPDATE or DELETE wrote.
+    Count(usize),
+    /// A CREATE TABLE or ANALYZE succeeded.
+    Done,
+}
+
+// Words that end a clause, so they aren't read as an alias.
+const RESERVED: &[&str] = &[
+    "AND", "AS", "ASC", "BETWEEN", "BY", "DESC", "FROM", "GROUP", "ILIKE", "IN", "INNER", "JOIN", "LEFT", "LIKE",
+    "LIMIT", "NOT", "OFFSET", "ON", "OR", "ORDER", "OUTER", "REGEXP", "RIGHT", "SET", "VALUES", "WHERE",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    // Identifiers and keywords, as written.
+    Word(String),
+    // A double-quoted identifier.
+    Quoted(String),
+    String(String),
+    Number(String),
+    // A named parameter, `:name`.
+    Param(String),
+    Symbol(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) | Token::Number(word) => write!(f, "{}", word),
+            Token::Quoted(name) => write!(f, "\"{}\"", name),
+            Token::String(s) => write!(f, "'{}'", s),
+            Token::Param(name) => write!(f, ":{}", name),
+            Token::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &["!=", "<>", "<=", ">=", "(", ")", ",", ".", ";", "*", "=", "<", ">", "-", "?"];
+
+// The tokens of `sql` with their byte offsets.
+fn tokenize(sql: &str) -> Result<Vec<(usize, Token)>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = sql;
+    loop {
+        rest = rest.trim_start();
+        if rest.starts_with("--") {
+            rest = rest.find('\n').map_or("", |end| &rest[end..]);
+            continue;
+        }
+        let at = sql.len() - rest.len();
+        let Some(c) = rest.chars().next() else { break };
+        let (token, len) = if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            (Token::Word(rest[..len].to_string()), len)
+        } else if c.is_ascii_digit() {
+            let len = rest
+                .find(|c: char| !c.is_ascii_digit() && c != '.' && c != 'e' && c != 'E')
+                .unwrap_or(rest.len());
+            (Token::Number(rest[..len].to_string()), len)
+        } else if c == '\'' || c == '"' {
+            let (text, len) = quoted(rest, c).ok_or_else(|| format!("Unterminated quote at position {}", at))?;
+            (if c == '\'' { Token::String(text) } else { Token::Quoted(text) }, len)
+        } else if c == ':' && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            let len = rest[1..]
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .map_or(rest.len(), |len| len + 1);
+            (Token::Param(rest[1..len].to_string()), len)
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|s| rest.starts_with(**s))
+                .ok_or_else(|| format!("Unexpected character {:?} at position {}", c, at))?;
+            (Token::Symbol(symbol), symbol.len())
+        };
+        tokens.push((at, token));
+        rest = &rest[len..];
+    }
+    Ok(tokens)
+}
+
+// The text between the quotes starting `text`, where a doubled quote stands
+// for one, and the length taken.
+fn quoted(text: &str, quote: char) -> Option<(String, usize)> {
+    let mut unquoted = String::new();
+    let mut chars = text.char_indices().skip(1).peekable();
+    while let Some((i, c)) = chars.next() {
+        if c != quote {
+            unquoted.push(c);
+        } else if chars.next_if(|(_, next)| *next == quote).is_some() {
+            unquoted.push(quote);
+        } else {
+            return Some((unquoted, i + 1));
+        }
+    }
+    None
+}
+
+enum Statement {
+    CreateTable {
+        name: String,
+        columns: Vec<Column>,
+    },
+    Insert {
+        table: String,
+        columns: Option<Vec<String>>,
+        rows: Vec<Vec<Value>>,
+    },
+    Select(Select),
+    Update {
+        table: String,
+        values: Vec<(String, Value)>,
+        filter: Option<Query>,
+    },
+    Delete {
+        table: String,
+        filter: Option<Query>,
+    },
+    Analyze {
+        table: String,
+    },
+}
+
+enum SelectItem {
+    Column { name: String, alias: Option<String> },
+    Aggregate { function: AggregateFunction, column: String, name: String },
+}
+
+struct JoinClause {
+    join_type: JoinType,
+    table: String,
+    alias: Option<String>,
+    on: (String, String),
+}
+
+struct Select {
+    // `None` for `*`.
+    items: Option<Vec<SelectItem>>,
+    table: String,
+    alias: Option<String>,
+    joins: Vec<JoinClause>,
+    filter: Option<Query>,
+    group_by: Vec<String>,
+    order_by: Vec<(String, SortOrder)>,
+    limit: Option<usize>,
+    offset: usize,
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    // Whether the statement is run with parameters, which rules out
+    // literals, or without, which rules out parameters.
+    parameterized: bool,
+    // How many `?`s have been read, and the `:name`s in the order first read.
+    positional: usize,
+    names: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next).map(|(_, token)| token)
+    }
+
+    fn error(&self, expected: &str) -> String {
+        match self.tokens.get(self.next) {
+            Some((at, token)) => format!("Expected {} at position {}, found {}", expected, at, token),
+            None => format!("Expected {} at the end of the statement", expected),
+        }
+    }
+
+    fn advance(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.next += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), String> {
+        if self.keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.error(keyword))
+        }
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(s)) if *s == symbol);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn expect_symbol(&mut self, symbol: &str) -> Result<(), String> {
+        if self.symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("'{}'", symbol)))
+        }
+    }
+
+    // Parses `item` one or more times, separated by commas.
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+        let mut items = vec![item(self)?];
+        while self.symbol(",") {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn identifier(&mut self) -> Result<String, String> {
+        match self.peek() {
+            Some(Token::Word(word)) if !RESERVED.iter().any(|r| word.eq_ignore_ascii_case(r)) => {
+                let word = word.clone();
+                self.next += 1;
+                Ok(word)
+            }
+            Some(Token::Quoted(name)) => {
+                let name = name.clone();
+                self.next += 1;
+                Ok(name)
+            }
+            _ => Err(self.error("a name")),
+        }
+    }
+
+    // A column, which a join names after its table as `table.column`.
+    fn column_name(&mut self) -> Result<String, String> {
+        let name = self.identifier()?;
+        if self.symbol(".") {
+            return Ok(format!("{}.{}", name, self.identifier()?));
+        }
+        Ok(name)
+    }
+
+    // An alias after `AS`, or without it.
+    fn alias(&mut self) -> Result<Option<String>, String> {
+        if self.keyword("AS") {
+            return self.identifier().map(Some);
+        }
+        Ok(self.identifier().ok())
+    }
+
+    fn starts_literal(&self) -> bool {
+        match self.peek() {
+            Some(Token::String(_) | Token::Number(_) | Token::Param(_) | Token::Symbol("-" | "?")) => true,
+            Some(Token::Word(_)) => ["TRUE", "FALSE", "NULL"].iter().any(|k| self.peek_keyword(k)),
+            _ => false,
+        }
+    }
+
+    fn literal(&mut self) -> Result<Value, String> {
+        if matches!(self.peek(), Some(Token::Symbol("?") | Token::Param(_))) {
+            return self.param();
+        }
+        if self.parameterized {
+            return Err(self.error("a parameter; literal values aren't allowed in a statement with parameters"));
+        }
+        let negative = self.symbol("-");
+        let value = match self.peek().cloned() {
+            Some(Token::Number(number)) => {
+                let value = if number.contains(['.', 'e', 'E']) {
+                    number.parse().map(Value::Float).ok()
+                } else {
+                    number.parse().map(Value::Integer).ok()
+                };
+                value.ok_or_else(|| self.error("a number"))?
+            }
+            Some(Token::String(s)) if !negative => Value::String(s),
+            Some(Token::Word(_)) if !negative && self.peek_keyword("TRUE") => Value::Boolean(true),
+            Some(Token::Word(_)) if !negative && self.peek_keyword("FALSE") => Value::Boolean(false),
+            Some(Token::Word(_)) if !negative && self.peek_keyword("NULL") => Value::Null,
+            _ => return Err(self.error("a value")),
+        };
+        self.next += 1;
+        Ok(match value {
+            Value::Integer(i) if negative => Value::Integer(-i),
+            Value::Float(f) if negative => Value::Float(-f),
+            value => value,
+        })
+    }
+
+    // A `?` or `:name`, as `Value::Param`. The `?`s are numbered in order,
+    // and each name takes the number it was first given.
+    fn param(&mut self) -> Result<Value, String> {
+        if !self.parameterized {
+            return Err(self.error("a value; parameters need execute_sql_with_params"));
+        }
+        let param = match self.peek().cloned() {
+            Some(Token::Symbol("?")) if self.names.is_empty() => {
+                self.positional += 1;
+                self.positional - 1
+            }
+            Some(Token::Param(name)) if self.positional == 0 => {
+                match self.names.iter().position(|n| *n == name) {
+                    Some(i) => i,
+                    None => {
+                        self.names.push(name);
+                        self.names.len() - 1
+                    }
+                }
+            }
+            _ => return Err(self.error("parameters of one kind; ? and :name can't be mixed")),
+        };
+        self.next += 1;
+        Ok(Value::Param(param))
+    }
+
+    fn comparison(&mut self) -> Result<Operator, String> {
+        let operator = match self.peek() {
+            Some(Token::Symbol("=")) => Operator::Eq,
+            Some(Token::Symbol("!=" | "<>")) => Operator::NotEq,
+            Some(Token::Symbol("<")) => Operator::Lt,
+            Some(Token::Symbol("<=")) => Operator::Lte,
+            Some(Token::Symbol(">")) => Operator::Gt,
+            Some(Token::Symbol(">=")) => Operator::Gte,
+            _ => return Err(self.error("a comparison")),
+        };
+        self.next += 1;
+        Ok(operator)
+    }
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        let statement = if self.keyword("CREATE") {
+            self.create_table()?
+        } else if self.keyword("INSERT") {
+            self.insert()?
+        } else if self.keyword("SELECT") {
+            Statement::Select(self.select()?)
+        } else if self.keyword("UPDATE") {
+            self.update()?
+        } else if self.keyword("DELETE") {
+            self.delete()?
+        } else if self.keyword("ANALYZE") {
+            Statement::Analyze {
+                table: self.identifier()?,
+            }
+        } else {
+            return Err(self.error("CREATE, INSERT, SELECT, UPDATE, DELETE or ANALYZE"));
+        };
+        self.symbol(";");
+        match self.peek() {
+            Some(_) => Err(self.error("the end of the statement")),
+            None => Ok(statement),
+        }
+    }
+
+    fn create_table(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("TABLE")?;
+        let name = self.identifier()?;
+        self.expect_symbol("(")?;
+        let columns = self.list(Self::column_definition)?;
+        self.expect_symbol(")")?;
+        Ok(Statement::CreateTable { name, columns })
+    }
+
+    fn column_definition(&mut self) -> Result<Column, String> {
+        let name = self.identifier()?;
+        let data_type = match self.advance() {
+            Some(Token::Word(word)) => word.parse::<DataType>()?,
+            _ => {
+                self.next -= 1;
+                return Err(self.error("a type"));
+            }
+        };
+        // A length, as in VARCHAR(255), is ignored.
+        if self.symbol("(") {
+            self.count()?;
+            self.expect_symbol(")")?;
+        }
+        let mut constraints = Vec::new();
+        let mut add = |constraint: Constraint| {
+            if !constraints.contains(&constraint) {
+                constraints.push(constraint);
+            }
+        };
+        loop {
+            if self.keyword("NOT") {
+                self.expect_keyword("NULL")?;
+                add(Constraint::NotNull);
+            } else if self.keyword("UNIQUE") {
+                add(Constraint::Unique);
+            } else if self.keyword("PRIMARY") {
+                self.expect_keyword("KEY")?;
+                add(Constraint::NotNull);
+                add(Constraint::Unique);
+            } else if self.keyword("REFERENCES") {
+                let table = self.identifier()?;
+                self.expect_symbol("(")?;
+                let column = self.identifier()?;
+                self.expect_symbol(")")?;
+                add(Constraint::ForeignKey { table, column });
+            } else {
+                break;
+            }
+        }
+        Ok(Column::new(name, data_type, constraints))
+    }
+
+    fn insert(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("INTO")?;
+        let table = self.identifier()?;
+        let columns = if self.symbol("(") {
+            let columns = self.list(Self::identifier)?;
+            self.expect_symbol(")")?;
+            Some(columns)
+        } else {
+            None
+        };
+        self.expect_keyword("VALUES")?;
+        let rows = self.list(|parser| {
+            parser.expect_symbol("(")?;
+            let values = parser.list(Self::literal)?;
+            parser.expect_symbol(")")?;
+            Ok(values)
+        })?;
+        Ok(Statement::Insert { table, columns, rows })
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, String> {
+        let function = match self.peek() {
+            Some(Token::Word(word)) if self.tokens.get(self.next + 1).is_some_and(|(_, t)| *t == Token::Symbol("(")) => {
+                match word.to_ascii_uppercase().as_str() {
+                    "COUNT" => Some(AggregateFunction::Count),
+                    "SUM" => Some(AggregateFunction::Sum),
+                    "AVG" => Some(AggregateFunction::Avg),
+                    "MIN" => Some(AggregateFunction::Min),
+                    "MAX" => Some(AggregateFunction::Max),
+                    _ => return Err(self.error("a column or an aggregate")),
+                }
+            }
+            _ => None,
+        };
+        let Some(mut function) = function else {
+            let name = self.column_name()?;
+            let alias = self.alias()?;
+            return Ok(SelectItem::Column { name, alias });
+        };
+        let written = format!("{}", self.advance().unwrap_or(Token::Symbol("?"))).to_ascii_lowercase();
+        self.expect_symbol("(")?;
+        let distinct = matches!(function, AggregateFunction::Count) && self.keyword("DISTINCT");
+        if distinct {
+            function = AggregateFunction::CountDistinct;
+        }
+        let column = if matches!(function, AggregateFunction::Count) && self.symbol("*") {
+            "*".to_string()
+        } else {
+            self.column_name()?
+        };
+        self.expect_symbol(")")?;
+        let name = match self.alias()? {
+            Some(alias) => alias,
+            None if distinct => format!("{}(distinct {})", written, column),
+            None => format!("{}({})", written, column),
+        };
+        Ok(SelectItem::Aggregate { function, column, name })
+    }
+
+    fn select(&mut self) -> Result<Select, String> {
+        let items = if self.symbol("*") {
+            None
+        } else {
+            Some(self.list(Self::select_item)?)
+        };
+        self.expect_keyword("FROM")?;
+        let table = self.identifier()?;
+        let alias = self.alias()?;
+
+        let mut joins = Vec::new();
+        loop {
+            let join_type = if self.keyword("INNER") {
+                JoinType::Inner
+            } else if self.keyword("LEFT") {
+                self.keyword("OUTER");
+                JoinType::Left
+            } else if self.keyword("RIGHT") {
+                self.keyword("OUTER");
+                JoinType::Right
+            } else if self.peek_keyword("JOIN") {
+                JoinType::Inner
+            } else {
+                break;
+            };
+            self.expect_keyword("JOIN")?;
+            let table = self.identifier()?;
+            let alias = self.alias()?;
+            self.expect_keyword("ON")?;
+            let left = self.column_name()?;
+            self.expect_symbol("=")?;
+            let right = self.column_name()?;
+            joins.push(JoinClause {
+                join_type,
+                table,
+                alias,
+                on: (left, right),
+            });
+        }
+
+        let filter = if self.keyword("WHERE") { Some(self.or()?) } else { None };
+        let mut group_by = Vec::new();
+        if self.keyword("GROUP") {
+            self.expect_keyword("BY")?;
+            group_by = self.list(Self::column_name)?;
+        }
+        let mut order_by = Vec::new();
+        if self.keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            order_by = self.list(|parser| {
+                let column = parser.column_name()?;
+                let order = if parser.keyword("DESC") {
+                    SortOrder::Descending
+                } else {
+                    parser.keyword("ASC");
+                    SortOrder::Ascending
+                };
+                Ok((column, order))
+            })?;
+        }
+        let limit = if self.keyword("LIMIT") { Some(self.count()?) } else { None };
+        let offset = if self.keyword("OFFSET") { self.count()? } else { 0 };
+        Ok(Select {
+            items,
+            table,
+            alias,
+            joins,
+            filter,
+            group_by,
+            order_by,
+            limit,
+            offset,
+        })
+    }
+
+    fn count(&mut self) -> Result<usize, String> {
+        match self.peek() {
+            Some(Token::Number(number)) => match number.parse() {
+                Ok(count) => {
+                    self.next += 1;
+                    Ok(count)
+                }
+                Err(_) => Err(self.error("a whole number")),
+            },
+            _ => Err(self.error("a whole number")),
+        }
+    }
+
+    fn update(&mut self) -> Result<Statement, String> {
+        let table = self.identifier()?;
+        self.expect_keyword("SET")?;
+        let values = self.list(|parser| {
+            let column = parser.identifier()?;
+            parser.expect_symbol("=")?;
+            Ok((column, parser.literal()?))
+        })?;
+        let filter = if self.keyword("WHERE") { Some(self.or()?) } else { None };
+        Ok(Statement::Update { table, values, filter })
+    }
+
+    fn delete(&mut self) -> Result<Statement, String> {
+        self.expect_keyword("FROM")?;
+        let table = self.identifier()?;
+        let filter = if self.keyword("WHERE") { Some(self.or()?) } else { None };
+        Ok(Statement::Delete { table, filter })
+    }
+
+    fn or(&mut self) -> Result<Query, String> {
+        let mut terms = vec![self.and()?];
+        while self.keyword("OR") {
+            terms.push(self.and()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Query::Or(terms) })
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut terms = vec![self.predicate()?];
+        while self.keyword("AND") {
+            terms.push(self.predicate()?);
+        }
+        Ok(if terms.len() == 1 { terms.remove(0) } else { Query::And(terms) })
+    }
+
+    fn predicate(&mut self) -> Result<Query, String> {
+        if self.symbol("(") {
+            let query = self.or()?;
+            self.expect_symbol(")")?;
+            return Ok(query);
+        }
+        // A literal first, as in `5 < age`, compares the other way round.
+        if self.starts_literal() {
+            let value = self.literal()?;
+            let operator = match self.comparison()? {
+                Operator::Lt => Operator::Gt,
+                Operator::Lte => Operator::Gte,
+                Operator::Gt => Operator::Lt,
+                Operator::Gte => Operator::Lte,
+                operator => operator,
+            };
+            return Ok(condition(self.column_name()?, operator, value));
+        }
+
+        let column = self.column_name()?;
+        let pattern = if self.keyword("NOT") {
+            self.expect_keyword("LIKE")?;
+            Some(Operator::NotLike)
+        } else if self.keyword("LIKE") {
+            Some(Operator::Like)
+        } else if self.keyword("ILIKE") {
+            Some(Operator::ILike)
+        } else if self.keyword("REGEXP") {
+            Some(Operator::Regex)
+        } else {
+            None
+        };
+        if let Some(operator) = pattern {
+            return Ok(condition(column, operator, self.literal()?));
+        }
+        if self.keyword("IN") {
+            self.expect_symbol("(")?;
+            if self.keyword("SELECT") {
+                let select_column = self.column_name()?;
+                self.expect_keyword("FROM")?;
+                let table = self.identifier()?;
+                let subquery = if self.keyword("WHERE") { self.or()? } else { Query::MatchAll };
+                self.expect_symbol(")")?;
+                return Ok(Query::InSubquery {
+                    column,
+                    table,
+                    subquery: Box::new(subquery),
+                    select_column,
+                });
+            }
+            let values = self.list(Self::literal)?;
+            self.expect_symbol(")")?;
+            let mut conditions: Vec<Query> =
+                values.into_iter().map(|value| condition(column.clone(), Operator::Eq, value)).collect();
+            return Ok(if conditions.len() == 1 { conditions.remove(0) } else { Query::Or(conditions) });
+        }
+        if self.keyword("BETWEEN") {
+            let low = self.literal()?;
+            self.expect_keyword("AND")?;
+            let high = self.literal()?;
+            return Ok(Query::And(vec![
+                condition(column.clone(), Operator::Gte, low),
+                condition(column, Operator::Lte, high),
+            ]));
+        }
+        let operator = self.comparison()?;
+        Ok(condition(column, operator, self.literal()?))
+    }
+}
+
+fn condition(column: String, operator: Operator, value: Value) -> Query {
+    Query::Condition(Condition { column, operator, value })
+}
+
+fn parse(sql: &str) -> Result<Statement, String> {
+    parser(sql, false)?.statement()
+}
+
+fn parser(sql: &str, parameterized: bool) -> Result<Parser, String> {
+    Ok(Parser {
+        tokens: tokenize(sql)?,
+        next: 0,
+        parameterized,
+        positional: 0,
+        names: Vec::new(),
+    })
+}
+
+// `statement` with `values` bound to its parameters.
+fn bind_statement(statement: Statement, values: &[Value]) -> Statement {
+    let bind_filter = |filter: Option<Query>| filter.map(|filter| bind(&filter, values));
+    match statement {
+        Statement::Insert { table, columns, rows } => Statement::Insert {
+            table,
+            columns,
+            rows: rows
+                .iter()
+                .map(|row| row.iter().map(|value| bind_value(value, values)).collect())
+                .collect(),
+        },
+        Statement::Select(select) => Statement::Select(Select {
+            filter: bind_filter(select.filter),
+            ..select
+        }),
+        Statement::Update { table, values: set, filter } => Statement::Update {
+            table,
+            values: set.iter().map(|(name, value)| (name.clone(), bind_value(value, values))).collect(),
+            filter: bind_filter(filter),
+        },
+        Statement::Delete { table, filter } => Statement::Delete {
+            table,
+            filter: bind_filter(filter),
+        },
+        other => other,
+    }
+}
+
+// `value` as `column`'s type, if it converts without loss.
+fn convert(value: Value, column: &Column) -> Value {
+    match (value, &column.data_type) {
+        (Value::String(s), DataType::Json) => match serde_json::from_str(&s) {
+            Ok(json) => Value::Json(json),
+            Err(_) => Value::String(s),
+        },
+        (value, data_type) => value.coerce(data_type, Coercion::Lossless),
+    }
+}
+
+
+// The rows with the value of each `(from, to)` pair's `from` column under
+// `to`, in order.
+fn rename(rows: Vec<Row>, names: &[(String, String)]) -> Vec<Row> {
+    let schema = Arc::new(RowSchema::new(names.iter().map(|(_, to)| to.clone())));
+    rows.into_iter()
+        .map(|row| {
+            let mut renamed = Row::with_schema(schema.clone());
+            for (from, to) in names {
+                if let Some(value) = row.get(from) {
+                    renamed.insert(to.clone(), value.clone());
+                }
+            }
+            renamed
+        })
+        .collect()
+}
+
+impl Database {
+    // Converts the values `query` compares columns with to the columns'
+    // types, and a subquery's to its table's. Patterns stay as written.
+    fn convert_query(&self, query: &mut Query, columns: &[Column]) {
+        match query {
+            Query::Condition(condition) => {
+                let pattern = matches!(
+                    condition.operator,
+                    Operator::Like | Operator::NotLike | Operator::ILike | Operator::Regex
+                );
+                if let Some(column) = columns.iter().find(|c| c.name == condition.column).filter(|_| !pattern) {
+                    condition.value = convert(std::mem::replace(&mut condition.value, Value::Null), column);
+                }
+            }
+            Query::And(queries) | Query::Or(queries) => {
+                queries.iter_mut().for_each(|q| self.convert_query(q, columns))
+            }
+            Query::InSubquery { table, subquery, .. } => {
+                let columns = self
+                    .with_published_table(table, |table| Ok(table.columns.clone()))
+                    .unwrap_or_default();
+                self.convert_query(subquery, &columns);
+            }
+            _ => {}
+        }
+    }
+
+    /// Parses and runs one SQL statement, as described in the module docs.
+    pub async fn execute_sql(&self, sql: &str) -> Result<SqlOutput, String> {
+        self.run_statement(parse(sql)?).await
+    }
+
+    /// Runs one SQL statement with `params` bound to its `?`s, in order.
+    pub async fn execute_sql_with_params(&self, sql: &str, params: &[Value]) -> Result<SqlOutput, String> {
+        let mut parser = parser(sql, true)?;
+        let statement = parser.statement()?;
+        if params.len() != parser.positional {
+            return Err(format!("Expected {} parameters, got {}", parser.positional, params.len()));
+        }
+        self.run_statement(bind_statement(statement, params)).await
+    }
+
+    /// Runs one SQL statement with `params` bound to its `:name`s. Every
+    /// parameter the statement uses must be given.
+    pub async fn execute_sql_with_named_params(&self, sql: &str, params: &Params) -> Result<SqlOutput, String> {
+        let mut parser = parser(sql, true)?;
+        let statement = parser.statement()?;
+        let values = parser
+            .names
+            .iter()
+            .map(|name| params.get(name).cloned().ok_or_else(|| format!("Missing parameter {}", name)))
+            .collect::<Result<Vec<_>, String>>()?;
+        self.run_statement(bind_statement(statement, &values)).await
+    }
+
+    async fn run_statement(&self, statement: Statement) -> Result<SqlOutput, String> {
+        match statement {
+            Statement::CreateTable { name, columns } => {
+                self.create_table(name, columns).await?;
+                Ok(SqlOutput::Done)
+            }
+            Statement::Insert { table, columns, rows } => self.execute_insert(&table, columns, rows).await,
+            Statement::Select(select) => self.execute_select(select).await.map(SqlOutput::Rows),
+            Statement::Update { table, values, filter } => {
+                let columns = self.sql_columns(&table).await?;
+                let values = values
+                    .into_iter()
+                    .map(|(name, value)| match columns.iter().find(|c| c.name == name) {
+                        Some(column) => Ok((name, convert(value, column))),
+                        None => Err(format!("Column {} not found", name)),
+                    })
+                    .collect::<Result<_, String>>()?;
+                let mut filter = filter.unwrap_or(Query::MatchAll);
+                self.convert_query(&mut filter, &columns);
+                let updated = self.update_with(&table, &filter, UpdateExpr::Set(values)).await?;
+                Ok(SqlOutput::Count(updated))
+            }
+            Statement::Delete { table, filter } => {
+                let mut filter = filter.unwrap_or(Query::MatchAll);
+                self.convert_query(&mut filter, &self.sql_columns(&table).await?);
+                Ok(SqlOutput::Count(self.delete(&table, &filter).await?))
+            }
+            Statement::Analyze { table } => {
+                self.analyze(&table).await?;
+                Ok(SqlOutput::Done)
+            }
+        }
+    }
+
+    async fn sql_columns(&self, table: &str) -> Result<Vec<Column>, String> {
+        self.table_columns(table)
+            .await
+            .ok_or_else(|| format!("Table {} not found", table))
+    }
+
+    async fn execute_insert(
+        &self,
+        table: &str,
+        names: Option<Vec<String>>,
+        rows: Vec<Vec<Value>>,
+    ) -> Result<SqlOutput, String> {
+        let columns = self.sql_columns(table).await?;
+        let names = names.unwrap_or_else(|| columns.iter().map(|c| c.name.clone()).collect());
+        let mut maps = Vec::with_capacity(rows.len());
+        for values in rows {
+            if values.len() != names.len() {
+                return Err(format!("Expected {} values, got {}", names.len(), values.len()));
+            }
+            let map: HashMap<String, Value> = names
+                .iter()
+                .zip(values)
+                .map(|(name, value)| match columns.iter().find(|c| c.name == *name) {
+                    Some(column) => (name.clone(), convert(value, column)),
+                    None => (name.clone(), value),
+                })
+                .collect();
+            maps.push(map);
+        }
+        let count = maps.len();
+        if count == 1 {
+            self.insert(table, maps.remove(0)).await?;
+        } else {
+            let mut transaction = Transaction::new();
+            for map in maps {
+                transaction.insert(table.to_string(), map);
+            }
+            self.commit(transaction).await?;
+        }
+        Ok(SqlOutput::Count(count))
+    }
+
+    async fn execute_select(&self, select: Select) -> Result<Vec<Row>, String> {
+        let mut items = select.items.unwrap_or_default();
+        let aggregates = items.iter().filter(|item| matches!(item, SelectItem::Aggregate { .. })).count();
+        if aggregates > 1 {
+            return Err("A select can have only one aggregate".to_string());
+        }
+        if aggregates == 0 && !select.group_by.is_empty() {
+            return Err("GROUP BY needs an aggregate".to_string());
+        }
+        let mut options = SelectOptions::new();
+        options.limit = select.limit;
+        options.offset = select.offset;
+        let mut filter = select.filter;
+
+        if aggregates == 1 {
+            if !select.joins.is_empty() {
+                return Err("Aggregates over joins are not supported".to_string());
+            }
+            let mut names = Vec::new();
+            let mut aggregate = None;
+            for item in items {
+                match item {
+                    SelectItem::Column { name, alias } => {
+                        if !select.group_by.contains(&name) {
+                            return Err(format!("Column {} must be grouped by", name));
+                        }
+                        names.push((name.clone(), alias.unwrap_or(name)));
+                    }
+                    SelectItem::Aggregate { function, column, name } => {
+                        names.push(("result".to_string(), name));
+                        aggregate = Some((function, column));
+                    }
+                }
+            }
+            let Some((function, column)) = aggregate else { unreachable!("counted above") };
+            if let Some(filter) = &mut filter {
+                self.convert_query(filter, &self.table_columns(&select.table).await.unwrap_or_default());
+            }
+            // Sorting is by the names the rows have before renaming.
+            options.order_by = select
+                .order_by
+                .into_iter()
+                .map(|(name, order)| match names.iter().find(|(_, to)| *to == name) {
+                    Some((from, _)) => (from.clone(), order),
+                    None => (name, order),
+                })
+                .collect();
+            let query = Query::Aggregate(AggregateQuery {
+                function,
+                column,
+                filter: filter.map(Box::new),
+                group_by: select.group_by,
+            });
+            let (rows, _) = self.select_with(&select.table, &query, &options).await?;
+            return Ok(rename(rows, &names));
+        }
+
+        let aliased: Vec<(String, String)> = items
+            .iter_mut()
+            .filter_map(|item| match item {
+                SelectItem::Column { name, alias } => Some((name.clone(), alias.take().unwrap_or(name.clone()))),
+                SelectItem::Aggregate { .. } => None,
+            })
+            .collect();
+        options.order_by = select
+            .order_by
+            .into_iter()
+            .map(|(name, order)| match aliased.iter().find(|(_, to)| *to == name) {
+                Some((from, _)) => (from.clone(), order),
+                None => (name, order),
+            })
+            .collect();
+        if !aliased.is_empty() {
+            options.columns = Some(aliased.iter().map(|(from, _)| from.clone()).collect());
+        }
+
+        let rows = if select.joins.is_empty() {
+            let mut filter = filter.unwrap_or(Query::MatchAll);
+            self.convert_query(&mut filter, &self.table_columns(&select.table).await.unwrap_or_default());
+            self.select_with(&select.table, &filter, &options).await?.0
+        } else {
+            let left = select.alias.unwrap_or(select.table.clone());
+            let mut joins = Vec::new();
+            let mut columns = Vec::new();
+            let mut prefixed = |table_columns: Vec<Column>, prefix: &str| {
+                for column in table_columns {
+                    columns.push(Column {
+                        name: format!("{}.{}", prefix, column.name),
+                        ..column
+                    });
+                }
+            };
+            prefixed(self.table_columns(&select.table).await.unwrap_or_default(), &left);
+            for clause in select.joins {
+                let right = clause.alias.unwrap_or(clause.table.clone());
+                // `ON` may name the columns in either order.
+                let (mut on_left, mut on_right) = clause.on;
+                let qualifier = |name: &str| name.split_once('.').map(|(q, _)| q.to_string());
+                if qualifier(&on_left).as_deref() == Some(right.as_str())
+                    && qualifier(&on_right).as_deref() != Some(right.as_str())
+                {
+                    std::mem::swap(&mut on_left, &mut on_right);
+                }
+                let unqualified = |name: &str| name.split_once('.').map_or(name, |(_, c)| c).to_string();
+                // Joins after the first join on a column of the rows so
+                // far, which keep their qualified names.
+                if joins.is_empty() {
+                    on_left = unqualified(&on_left);
+                }
+                joins.push(
+                    Join::new(clause.join_type, &clause.table, (&on_left, &unqualified(&on_right)))
+                        .aliased(&left, &right),
+                );
+                prefixed(self.table_columns(&clause.table).await.unwrap_or_default(), &right);
+            }
+            if let Some(filter) = &mut filter {
+                self.convert_query(filter, &columns);
+            }
+            if joins.len() == 1 {
+                let query = Query::Join(joins.remove(0));
+                self.select_filtered_with(&select.table, &query, filter.as_ref(), &options).await?.0
+            } else {
+                let chain = JoinChain { joins, filter };
+                let rows = self.join_chain_rows(&select.table, &chain).await?;
+                self.arrange_rows(&select.table, &rows, &options)?
+            }
+        };
+        if aliased.iter().any(|(from, to)| from != to) {
+            return Ok(rename(rows, &aliased));
+        }
+        Ok(rows)
+    }
+}
@@ -0,0 +1,225 @@
+//! Column statistics for the query planner.
+//!
+//! `Database::analyze` scans a table and records, per column, the number of
+//! nulls, the number of distinct values and an equi-depth histogram: the
+//! values at evenly spaced ranks, so each bucket between neighbouring bounds
+//! holds about the same number of rows. The planner turns these into the
+//! fraction of rows a condition matches. Statistics describe the table as it
+//! was when analyzed; estimates are scaled to its current row count, and are
+//! only as good as the data hasn't shifted since.
+//!
+//! Each table counts the rows inserted, updated and deleted since it was
+//! last analyzed. With `DatabaseConfig::auto_analyze` set, the write that
+//! takes the count over the threshold analyzes the table again before it
+//! returns. That write pays for a scan of the table; the rest are
+//! unaffected. Tables that were never analyzed count from their creation,
+//! or from when they were loaded. While maintenance is deferred, changes are
+//! counted and the analyze waits for the table's next write after.
+
+use crate::{Condition, Database, Operator, Table, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const BUCKETS: usize = 32;
+
+// Selectivities used for columns without statistics.
+const DEFAULT_EQ: f64 = 0.1;
+const DEFAULT_RANGE: f64 = 1.0 / 3.0;
+// Histograms say nothing about substrings, so patterns always use this.
+const PATTERN_SELECTIVITY: f64 = 0.1;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ColumnStats {
+    pub null_count: usize,
+    pub distinct_count: usize,
+    /// The smallest value, the values at each bucket boundary, then the
+    /// largest value. Empty if the column has no values.
+    pub histogram: Vec<Value>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TableStats {
+    pub row_count: usize,
+    pub columns: HashMap<String, ColumnStats>,
+}
+
+impl ColumnStats {
+    fn compute<'a>(values: impl Iterator<Item = Option<&'a Value>>) -> Self {
+        let mut null_count = 0;
+        let mut sorted: Vec<&Value> = Vec::new();
+        for value in values {
+            match value {
+                None | Some(Value::Null) => null_count += 1,
+                Some(value) => sorted.push(value),
+            }
+        }
+        sorted.sort_unstable();
+        let distinct_count = match sorted.first() {
+            Some(_) => 1 + sorted.windows(2).filter(|w| w[0] != w[1]).count(),
+            None => 0,
+        };
+        let histogram = if sorted.is_empty() {
+            Vec::new()
+        } else {
+            let buckets = BUCKETS.min(sorted.len());
+            (0..=buckets)
+                .map(|i| sorted[(i * (sorted.len() - 1)) / buckets].clone())
+                .collect()
+        };
+        ColumnStats {
+            null_count,
+            distinct_count,
+            histogram,
+        }
+    }
+
+    /// Estimated fraction of the non-null values that are below `value`, or
+    /// at most `value` if `inclusive`.
+    fn fraction_below(&self, value: &Value, inclusive: bool) -> f64 {
+        let bounds = &self.histogram;
+        let (Some(min), Some(max)) = (bounds.first(), bounds.last()) else {
+            return 0.0;
+        };
+        if value < min || (!inclusive && value == min) {
+            return 0.0;
+        }
+        if value > max || (inclusive && value == max) {
+            return 1.0;
+        }
+        // The bucket holding `value`. Runs of equal bounds put it after the
+        // run when counting values up to it, and before when counting below.
+        let i = if inclusive {
+            bounds.partition_point(|b| b <= value)
+        } else {
+            bounds.partition_point(|b| b < value)
+        }
+        .saturating_sub(1);
+        let within = match (&bounds[i], bounds.get(i + 1)) {
+            (low, Some(high)) => interpolate(low, high, value).unwrap_or(0.5),
+            _ => 1.0,
+        };
+        (i as f64 + within) / (bounds.len() - 1) as f64
+    }
+
+    /// Estimated fraction of all rows matching `operator` against `value`.
+    fn selectivity(&self, operator: &Operator, value: &Value, row_count: usize) -> f64 {
+        if row_count == 0 {
+            return 0.0;
+        }
+        let non_null = 1.0 - self.null_count as f64 / row_count as f64;
+        let eq = if self.distinct_count == 0 {
+            0.0
+        } else {
+            non_null / self.distinct_count as f64
+        };
+        match operator {
+            Operator::Eq => eq,
+            Operator::NotEq => non_null - eq,
+            Operator::Lt => non_null * self.fraction_below(value, false),
+            Operator::Lte => non_null * self.fraction_below(value, true),
+            Operator::Gt => non_null * (1.0 - self.fraction_below(value, true)),
+            Operator::Gte => non_null * (1.0 - self.fraction_below(value, false)),
+            Operator::ILike | Operator::Like | Operator::Regex => non_null * PATTERN_SELECTIVITY,
+            Operator::NotLike => non_null * (1.0 - PATTERN_SELECTIVITY),
+        }
+    }
+}
+
+// Where `value` falls between `low` and `high`, from 0 to 1, for values with
+// a numeric position.
+fn interpolate(low: &Value, high: &Value, value: &Value) -> Option<f64> {
+    let position = |v: &Value| match v {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        Value::DateTime(dt) => Some(dt.timestamp_micros() as f64),
+        _ => None,
+    };
+    let (low, high, value) = (position(low)?, position(high)?, position(value)?);
+    (high > low).then(|| ((value - low) / (high - low)).clamp(0.0, 1.0))
+}
+
+impl TableStats {
+    fn compute(table: &Table) -> Self {
+        TableStats {
+            row_count: table.data.len(),
+            columns: table
+                .columns
+                .iter()
+                .map(|c| {
+                    let stats = ColumnStats::compute(table.data.iter().map(|row| row.get(&c.name)));
+                    (c.name.clone(), stats)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Table {
+    fn analyze(&mut self) -> TableStats {
+        let stats = TableStats::compute(self);
+        self.stats = Some(Arc::new(stats.clone()));
+        self.changed_since_analyze = 0;
+        self.plan_epoch = crate::optimizer::next_epoch();
+        stats
+    }
+
+    /// Estimated fraction of rows matching `condition`.
+    pub(crate) fn selectivity(&self, condition: &Condition) -> f64 {
+        let column = self.stats.as_ref().and_then(|s| Some((s, s.columns.get(&condition.column)?)));
+        match (column, &condition.operator) {
+            (Some((stats, column)), operator) => column.selectivity(operator, &condition.value, stats.row_count),
+            (None, Operator::Eq) => DEFAULT_EQ,
+            (None, Operator::NotEq) => 1.0 - DEFAULT_EQ,
+            (None, Operator::ILike | Operator::Like | Operator::Regex) => PATTERN_SELECTIVITY,
+            (None, Operator::NotLike) => 1.0 - PATTERN_SELECTIVITY,
+            (None, _) => DEFAULT_RANGE,
+        }
+    }
+}
+
+impl Database {
+    /// Collects statistics on every column of the table for the planner, and
+    /// returns them. They are kept until the next `analyze`.
+    pub async fn analyze(&self, table_name: &str) -> Result<TableStats, String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let stats = table.analyze();
+        self.publish(&tables, &[table_name]);
+        Ok(stats)
+    }
+
+    // Analyzes the `changed` tables that have crossed
+    // `DatabaseConfig::auto_analyze`. Call before publishing.
+    pub(crate) fn auto_analyze(&self, tables: &mut HashMap<String, Table>, changed: &[&str]) {
+        let Some(threshold) = self.config.auto_analyze else { return };
+        if self.maintenance_deferred() {
+            return;
+        }
+        for name in changed {
+            let Some(table) = tables.get_mut(*name) else { continue };
+            let limit = threshold.min_changes as f64 + threshold.fraction * table.data.len() as f64;
+            if table.changed_since_analyze as f64 > limit {
+                table.analyze();
+            }
+        }
+    }
+
+    /// How many rows the planner expects `condition` to match, from the
+    /// table's statistics and how far off its earlier estimates were.
+    pub async fn estimate_rows(&self, table_name: &str, condition: &Condition) -> Result<f64, String> {
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        Ok(self.query_planner.selectivity(table, condition) * table.data.len() as f64)
+    }
+
+    /// The statistics from the table's last `analyze`, if any.
+    pub async fn table_stats(&self, table_name: &str) -> Option<TableStats> {
+        let tables = self.read_tables_untimed().await;
+        tables.get(table_name)?.stats.as_deref().cloned()
+    }
+}
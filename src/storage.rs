@@ -0,0 +1,153 @@
+//! Where the WAL and snapshots are stored.
+//!
+//! Everything the database persists goes through a `StorageBackend`, addressed
+//! by the same names that used to be file paths: the WAL path given to
+//! `Database::new` and the paths given to `save`, `backup` and `load`.
+//! `FileStorage` keeps them as files and is the default. `MemoryStorage` keeps
+//! them in memory, for tests and for targets without a file system; share one
+//! `Arc<MemoryStorage>` between databases to let them see each other's data.
+//!
+//! Backends are synchronous. The database calls them on tokio's blocking
+//! thread pool when saving, loading and syncing the WAL, so a large snapshot
+//! or a slow fsync only holds up the task waiting for it. WAL appends are
+//! small and are made in place. `FileStorage` replaces a file by renaming a
+//! new one over it, so a snapshot being loaded is never half written.
+//!
+//! wasm32 has no threads, so there backends are called in place, and no
+//! files, so there the default is `MemoryStorage`. `IndexedDbStorage` keeps
+//! data in a browser's IndexedDB.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+pub trait StorageBackend: fmt::Debug + Send + Sync {
+    /// The whole contents of `name`, or `None` if it doesn't exist.
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>>;
+
+    /// Replaces the contents of `name`, creating it if needed.
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Appends to `name`, creating it if needed.
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()>;
+
+    /// Makes earlier writes and appends to `name` durable.
+    fn sync(&self, name: &str) -> io::Result<()>;
+}
+
+/// Runs `f` against `storage` on tokio's blocking thread pool.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) async fn unblock<T, F>(storage: &Arc<dyn StorageBackend>, f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn StorageBackend) -> io::Result<T> + Send + 'static,
+{
+    let storage = Arc::clone(storage);
+    tokio::task::spawn_blocking(move || f(&*storage))
+        .await
+        .map_err(io::Error::other)?
+}
+
+/// Runs `f` against `storage` in place, as wasm32 has no threads.
+#[cfg(target_arch = "wasm32")]
+pub(crate) async fn unblock<T, F>(storage: &Arc<dyn StorageBackend>, f: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&dyn StorageBackend) -> io::Result<T> + Send + 'static,
+{
+    f(&**storage)
+}
+
+#[derive(Debug, Default)]
+pub struct FileStorage {
+    // Files being appended to stay open between appends.
+    appending: Mutex<HashMap<String, File>>,
+}
+
+impl FileStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for FileStorage {
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(name) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    // Written aside and renamed into place, so a concurrent read never sees
+    // half a file. A WAL opened for appending is reopened on the next append.
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let temp = format!("{}.{:016x}.tmp", name, rand::random::<u64>());
+        let result = File::create(&temp).and_then(|mut file| file.write_all(data)).and_then(|()| {
+            let mut appending = self.appending.lock().unwrap();
+            std::fs::rename(&temp, name)?;
+            appending.remove(name);
+            Ok(())
+        });
+        if result.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+        result
+    }
+
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let mut appending = self.appending.lock().unwrap();
+        let file = match appending.entry(name.to_string()) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(File::options().append(true).create(true).open(name)?)
+            }
+        };
+        file.write_all(data)
+    }
+
+    fn sync(&self, name: &str) -> io::Result<()> {
+        match self.appending.lock().unwrap().get(name) {
+            Some(file) => file.sync_all(),
+            None => File::open(name)?.sync_all(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    objects: Mutex<HashMap<String, Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl StorageBackend for MemoryStorage {
+    fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.objects.lock().unwrap().get(name).cloned())
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.objects.lock().unwrap().insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.objects
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    fn sync(&self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//! Filtering a table by the values of another table's rows.
+//!
+//! `Query::InSubquery` keeps the rows whose column equals a value of
+//! `select_column` in the rows of another table matching a subquery, so a
+//! filter like "orders of active customers" is one query rather than a
+//! select and an `Or` of every key it returned. The subquery's values are
+//! gathered once per run of the query, and the outer column's index is used
+//! to look them up when it has one. Values compare as conditions do, so an
+//! integer matches the float equal to it, and nulls match nothing.
+//!
+//! A subquery reads the published tables, as queries do, so inside a write
+//! it sees the tables as of the last write rather than the write's own
+//! changes, and in a transaction it doesn't see the transaction's queued
+//! writes. While maintenance is deferred it reads through the lock when it
+//! can.
+//!
+//! A subquery whose table or column is missing fails the query it is in,
+//! however the query is run, rather than matching nothing. Watches,
+//! materialized views and rollups follow the writes to one table, so their
+//! queries can't contain subqueries.
+
+use crate::{Column, Database, Query, Table, Value};
+use std::collections::HashSet;
+
+/// Whether `query` contains a subquery anywhere.
+pub(crate) fn has_subquery(query: &Query) -> bool {
+    match query {
+        Query::InSubquery { .. } => true,
+        Query::And(queries) | Query::Or(queries) => queries.iter().any(has_subquery),
+        Query::Aggregate(aggregate) => aggregate.filter.as_deref().is_some_and(has_subquery),
+        Query::Traverse(traverse) => has_subquery(&traverse.start),
+        Query::Sample(sample) => has_subquery(&sample.query),
+        Query::MatchAll | Query::Condition(_) | Query::Compare(_) | Query::Join(_) => false,
+    }
+}
+
+impl Database {
+    // Runs `f` on the published `table_name`.
+    pub(crate) fn with_published_table<T>(
+        &self,
+        table_name: &str,
+        f: impl FnOnce(&Table) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let not_found = || format!("Table {} not found", table_name);
+        if self.maintenance_deferred() {
+            if let Ok(tables) = self.tables.try_read() {
+                return f(tables.get(table_name).ok_or_else(not_found)?);
+            }
+        }
+        let snapshot = self.snapshot.load();
+        f(snapshot.get(table_name).ok_or_else(not_found)?)
+    }
+
+    /// The values of `select_column` in the rows of `table_name` matching
+    /// `subquery`, with their numeric twins, as index keys.
+    pub(crate) fn subquery_values(
+        &self,
+        table_name: &str,
+        subquery: &Query,
+        select_column: &str,
+    ) -> Result<HashSet<Value>, String> {
+        self.with_published_table(table_name, |table| {
+            if !table.columns.iter().any(|c| c.name == select_column) {
+                return Err(format!("Column {} not found", select_column));
+            }
+            Ok(self
+                .execute_query(table, subquery)?
+                .into_iter()
+                .filter_map(|i| table.data[i].get(select_column))
+                .filter(|value| !matches!(value, Value::Null))
+                .flat_map(Value::index_keys)
+                .collect())
+        })
+    }
+
+    pub(crate) fn check_subquery(&self, table_name: &str, subquery: &Query, select_column: &str) -> Result<(), String> {
+        if matches!(subquery, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_)) {
+            return Err("A subquery must be a filter query".to_string());
+        }
+        let columns: Vec<Column> = self.with_published_table(table_name, |table| Ok(table.columns.clone()))?;
+        if !columns.iter().any(|c| c.name == select_column) {
+            return Err(format!("Column {} not found", select_column));
+        }
+        self.check_query(subquery, &columns)
+    }
+
+    // Positions of the rows of `table` whose `column` is one of `values`,
+    // in ascending order.
+    pub(crate) fn rows_in(&self, table: &Table, column: &str, values: &HashSet<Value>) -> Vec<usize> {
+        match table.indexes.get(column).filter(|_| !table.indexes_stale) {
+            Some(index) => {
+                table.index_last_used.insert(column.to_string(), self.memory.tick());
+                let mut rows = Vec::new();
+                for value in values {
+                    if let Some(ids) = index.get(value) {
+                        rows.extend(table.positions(ids));
+                    }
+                }
+                rows.sort_unstable();
+                rows.dedup();
+                rows
+            }
+            None => (0..table.data.len())
+                .filter(|i| table.data[*i].get(column).is_some_and(|value| values.contains(value)))
+                .collect(),
+        }
+    }
+}
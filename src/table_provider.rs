@@ -0,0 +1,151 @@
+//! DataFusion integration, enabled with the `datafusion` feature.
+//!
+//! `Database::table_provider` returns a `TableProvider` for a table, so it can
+//! be registered in a `SessionContext` and queried with SQL. Simple
+//! comparisons between a column and a literal, and `AND`s of them, are pushed
+//! down and run as a zapdb query, using its indexes. DataFusion still applies
+//! every filter to the rows that come back, so anything zapdb can't evaluate
+//! is only a missed shortcut.
+
+use crate::record_batch::{arrow_schema, rows_to_batch};
+use crate::{Column, Condition, DataType, Database, Operator, Query, Value};
+use arrow::datatypes::SchemaRef;
+use async_trait::async_trait;
+use chrono::DateTime;
+use datafusion::catalog::memory::MemorySourceConfig;
+use datafusion::catalog::{Session, TableProvider};
+use datafusion::common::{DataFusionError, ScalarValue};
+use datafusion::logical_expr::{BinaryExpr, Expr, Operator as SqlOperator, TableProviderFilterPushDown, TableType};
+use datafusion::physical_plan::ExecutionPlan;
+use std::fmt;
+use std::sync::Arc;
+
+/// A zapdb table as a DataFusion `TableProvider`. Each scan reads the table
+/// as it is at that moment.
+pub struct ZapTable {
+    db: Arc<Database>,
+    table_name: String,
+    columns: Vec<Column>,
+    schema: SchemaRef,
+}
+
+impl fmt::Debug for ZapTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ZapTable").field("table_name", &self.table_name).finish()
+    }
+}
+
+impl Database {
+    pub async fn table_provider(self: &Arc<Self>, table_name: &str) -> Result<ZapTable, String> {
+        let columns = self
+            .table_columns(table_name)
+            .await
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        Ok(ZapTable {
+            db: self.clone(),
+            table_name: table_name.to_string(),
+            schema: Arc::new(arrow_schema(&columns)),
+            columns,
+        })
+    }
+}
+
+#[async_trait]
+impl TableProvider for ZapTable {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn table_type(&self) -> TableType {
+        TableType::Base
+    }
+
+    fn supports_filters_pushdown(
+        &self,
+        filters: &[&Expr],
+    ) -> datafusion::error::Result<Vec<TableProviderFilterPushDown>> {
+        Ok(filters
+            .iter()
+            .map(|filter| match self.to_query(filter) {
+                Some(_) => TableProviderFilterPushDown::Inexact,
+                None => TableProviderFilterPushDown::Unsupported,
+            })
+            .collect())
+    }
+
+    async fn scan(
+        &self,
+        _state: &dyn Session,
+        projection: Option<&Vec<usize>>,
+        filters: &[Expr],
+        limit: Option<usize>,
+    ) -> datafusion::error::Result<Arc<dyn ExecutionPlan>> {
+        let mut queries: Vec<Query> = filters.iter().filter_map(|f| self.to_query(f)).collect();
+        let query = match queries.len() {
+            0 => Query::MatchAll,
+            1 => queries.remove(0),
+            _ => Query::And(queries),
+        };
+        let (mut rows, _) = self
+            .db
+            .select_shared(&self.table_name, &query)
+            .await
+            .map_err(DataFusionError::Execution)?;
+        if let Some(limit) = limit {
+            rows.truncate(limit);
+        }
+        let batch = rows_to_batch(&self.columns, &rows)?;
+        let plan = MemorySourceConfig::try_new_exec(&[vec![batch]], self.schema.clone(), projection.cloned())?;
+        Ok(plan)
+    }
+}
+
+impl ZapTable {
+    // The zapdb query for a filter, if it has one.
+    fn to_query(&self, filter: &Expr) -> Option<Query> {
+        let Expr::BinaryExpr(BinaryExpr { left, op, right }) = filter else {
+            return None;
+        };
+        if *op == SqlOperator::And {
+            return Some(Query::And(vec![self.to_query(left)?, self.to_query(right)?]));
+        }
+        let (column, op, literal) = match (left.as_ref(), right.as_ref()) {
+            (Expr::Column(column), Expr::Literal(literal, _)) => (column, *op, literal),
+            (Expr::Literal(literal, _), Expr::Column(column)) => (column, op.swap()?, literal),
+            _ => return None,
+        };
+        let operator = match op {
+            SqlOperator::Eq => Operator::Eq,
+            SqlOperator::NotEq => Operator::NotEq,
+            SqlOperator::Gt => Operator::Gt,
+            SqlOperator::GtEq => Operator::Gte,
+            SqlOperator::Lt => Operator::Lt,
+            SqlOperator::LtEq => Operator::Lte,
+            _ => return None,
+        };
+        let column = self.columns.iter().find(|c| c.name == column.name)?;
+        let value = match (literal, &column.data_type) {
+            (ScalarValue::Int64(Some(i)), DataType::Integer | DataType::Float) => Value::Integer(*i),
+            (ScalarValue::Float64(Some(f)), DataType::Integer | DataType::Float) => Value::Float(*f),
+            (ScalarValue::Boolean(Some(b)), DataType::Boolean) => Value::Boolean(*b),
+            (ScalarValue::TimestampMicrosecond(Some(us), _), DataType::DateTime) => {
+                Value::DateTime(DateTime::from_timestamp_micros(*us)?)
+            }
+            (
+                ScalarValue::Utf8(Some(s)) | ScalarValue::LargeUtf8(Some(s)) | ScalarValue::Utf8View(Some(s)),
+                DataType::String,
+            ) => Value::String(s.clone()),
+            (ScalarValue::Utf8(Some(s)) | ScalarValue::Utf8View(Some(s)), DataType::Uuid)
+                if matches!(operator, Operator::Eq) =>
+            {
+                Value::Uuid(s.parse().ok()?)
+            }
+            _ => return None,
+        };
+        Some(Query::Condition(Condition {
+            column: column.name.clone(),
+            operator,
+            value,
+        }))
+    }
+}
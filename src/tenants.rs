@@ -0,0 +1,348 @@
+//! Tenants: namespaces of tables with their own key and limits.
+//!
+//! A tenant's tables are ordinary tables named `<tenant>/<table>`, so they
+//! are saved, logged and queried like any other. A `Tenant` handle works with
+//! the unqualified names and can't reach tables outside the namespace; joins
+//! and subqueries are resolved inside it too. Suspending a tenant makes its handle refuse
+//! everything, while the `Database` itself keeps full access.
+//!
+//! The tenant's key encrypts its exports, so a tenant's data can be handed
+//! over or moved without the database key.
+//!
+//! The registry of tenants, with their keys, limits and whether they are
+//! suspended, is logged to the WAL and stored in snapshots, so a suspended
+//! tenant stays suspended after the database is reopened.
+//!
+//! Two parts of per-tenant isolation are not provided:
+//!
+//! - Tenant tables are not encrypted with the tenant's key at rest. The
+//!   database's snapshots and WAL hold them under the database key like
+//!   every other table, and the registry stores the tenant keys under it
+//!   too, so whoever has the database key can read every tenant.
+//! - There are no row-level security defaults, since zapdb has no
+//!   row-level security. The handle's namespace is the only boundary, and
+//!   everyone using a tenant's handle sees all of its rows.
+
+use crate::recovery::{self, RecoveryReport};
+use crate::{
+    begin_transaction, seal_snapshot, unseal_snapshot, Column, Database, JoinColumns, Query, Row, Table, TableLimits,
+    UpdateFn, Value, WalEntry, WriteError,
+};
+use serde::{Deserialize, Serialize};
+use flate2::read::GzDecoder;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+const SEPARATOR: char = '/';
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TenantConfig {
+    /// Encrypts the tenant's exports. The tenant's tables themselves are
+    /// stored under the database key.
+    pub key: [u8; 32],
+    /// Limits applied to each of the tenant's tables.
+    pub limits: TableLimits,
+}
+
+// Leaves the key out, as WAL entries are debug-printed.
+impl fmt::Debug for TenantConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TenantConfig").field("limits", &self.limits).finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct TenantState {
+    config: TenantConfig,
+    suspended: bool,
+}
+
+#[derive(Default)]
+pub(crate) struct Tenants {
+    tenants: RwLock<HashMap<String, TenantState>>,
+}
+
+impl Tenants {
+    // A copy for `Database::fork`.
+    pub(crate) fn fork(&self) -> Self {
+        Tenants {
+            tenants: RwLock::new(self.tenants.read().unwrap().clone()),
+        }
+    }
+
+    pub(crate) fn all(&self) -> HashMap<String, TenantState> {
+        self.tenants.read().unwrap().clone()
+    }
+
+    pub(crate) fn replace(&self, tenants: HashMap<String, TenantState>) {
+        *self.tenants.write().unwrap() = tenants;
+    }
+
+    pub(crate) fn limits_for(&self, table_name: &str) -> Option<TableLimits> {
+        let (tenant, _) = table_name.split_once(SEPARATOR)?;
+        self.tenants.read().unwrap().get(tenant).map(|t| t.config.limits)
+    }
+
+    fn config(&self, name: &str) -> Result<TenantConfig, String> {
+        self.tenants
+            .read()
+            .unwrap()
+            .get(name)
+            .map(|t| t.config.clone())
+            .ok_or_else(|| format!("Tenant {} not found", name))
+    }
+
+    fn check_active(&self, name: &str) -> Result<(), String> {
+        match self.tenants.read().unwrap().get(name) {
+            Some(t) if t.suspended => Err(format!("Tenant {} is suspended", name)),
+            Some(_) => Ok(()),
+            None => Err(format!("Tenant {} not found", name)),
+        }
+    }
+}
+
+/// Access to one tenant's tables, from `Database::tenant`.
+pub struct Tenant<'a> {
+    db: &'a Database,
+    name: String,
+}
+
+impl Database {
+    /// Registers a tenant. Its tables, if any exist from before, become
+    /// reachable through `tenant`.
+    pub async fn create_tenant(&self, name: &str, config: TenantConfig) -> Result<(), String> {
+        self.check_open()?;
+        if name.is_empty() || name.contains(SEPARATOR) {
+            return Err(format!("Invalid tenant name {:?}", name));
+        }
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tenants = self.tenants.tenants.write().unwrap();
+        if tenants.contains_key(name) {
+            return Err(format!("Tenant {} already exists", name));
+        }
+        wal_writer
+            .log(&WalEntry::CreateTenant {
+                name: name.to_string(),
+                config: config.clone(),
+            })
+            .map_err(|e| e.to_string())?;
+        tenants.insert(
+            name.to_string(),
+            TenantState {
+                config,
+                suspended: false,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn tenant(&self, name: &str) -> Result<Tenant<'_>, String> {
+        self.tenants.config(name)?;
+        Ok(Tenant {
+            db: self,
+            name: name.to_string(),
+        })
+    }
+
+    pub fn tenant_names(&self) -> Vec<String> {
+        self.tenants.tenants.read().unwrap().keys().cloned().collect()
+    }
+
+    /// Makes the tenant's handles refuse every operation until
+    /// `resume_tenant`. Nothing is deleted.
+    pub async fn suspend_tenant(&self, name: &str) -> Result<(), String> {
+        self.set_suspended(name, true).await
+    }
+
+    pub async fn resume_tenant(&self, name: &str) -> Result<(), String> {
+        self.set_suspended(name, false).await
+    }
+
+    pub(crate) async fn set_suspended(&self, name: &str, suspended: bool) -> Result<(), String> {
+        self.check_open()?;
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tenants = self.tenants.tenants.write().unwrap();
+        let tenant = tenants
+            .get_mut(name)
+            .ok_or_else(|| format!("Tenant {} not found", name))?;
+        wal_writer
+            .log(&WalEntry::SuspendTenant {
+                name: name.to_string(),
+                suspended,
+            })
+            .map_err(|e| e.to_string())?;
+        tenant.suspended = suspended;
+        Ok(())
+    }
+
+    /// Writes the tenant's tables to `path` as a snapshot encrypted with the
+    /// tenant's key, under their unqualified names. Works while suspended.
+    pub async fn export_tenant(&self, name: &str, path: &str) -> io::Result<()> {
+        self.check_open_io()?;
+        let config = self.tenants.config(name).map_err(io::Error::other)?;
+        let prefix = format!("{}{}", name, SEPARATOR);
+        let tables = self.timed(self.tables.read()).await?;
+        let owned: Vec<(&str, &Table)> = tables
+            .iter()
+            .filter(|(_, t)| !t.ephemeral)
+            .filter_map(|(table_name, t)| Some((table_name.strip_prefix(&prefix)?, t)))
+            .collect();
+        for table in tables.keys().filter(|table_name| table_name.starts_with(&prefix)) {
+            self.check_exportable(table)
+                .map_err(|e| io::Error::new(io::ErrorKind::PermissionDenied, e))?;
+        }
+        let encoded = recovery::encode_snapshot(self.config.codec, &owned, &HashMap::new(), &HashMap::new(), &HashMap::new())?;
+        self.storage.write(path, &seal_snapshot(&config.key, self.config.cipher, &encoded)?)
+    }
+
+    /// Creates the tables in an export at `path`, decrypted with the tenant's
+    /// key, in the tenant's namespace, and inserts their rows in one
+    /// transaction. Fails before creating anything if one of the tables
+    /// already exists.
+    pub async fn import_tenant(&self, name: &str, path: &str) -> io::Result<()> {
+        self.check_open_io()?;
+        let config = self.tenants.config(name).map_err(io::Error::other)?;
+        let buffer = self
+            .storage
+            .read(path)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path)))?;
+        let mut encoded = Vec::new();
+        GzDecoder::new(&unseal_snapshot(&config.key, &buffer)?[..]).read_to_end(&mut encoded)?;
+        let mut report = RecoveryReport::default();
+        let contents = recovery::decode_snapshot(&encoded, &mut report)?;
+        report.into_result()?;
+
+        let tenant = self.tenant(name).map_err(io::Error::other)?;
+        let existing = self.table_names().await;
+        if let Some(table) = contents.tables.keys().find(|t| existing.contains(&tenant.qualify(t))) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("Table {} already exists", tenant.qualify(table)),
+            ));
+        }
+        let mut transaction = begin_transaction();
+        for (table_name, table) in contents.tables {
+            let qualified = tenant.qualify(&table_name);
+            self.create_table(qualified.clone(), table.columns.clone())
+                .await
+                .map_err(io::Error::other)?;
+            if let Some(by) = table.partitioning() {
+                self.partition_table(&qualified, by.clone())
+                    .await
+                    .map_err(io::Error::other)?;
+            }
+            for row in table.data {
+                transaction.insert(qualified.clone(), Arc::unwrap_or_clone(row).into_map());
+            }
+        }
+        self.commit(transaction).await.map_err(io::Error::other)
+    }
+}
+
+impl Tenant<'_> {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name the database knows `table` by.
+    pub fn qualify(&self, table: &str) -> String {
+        format!("{}{}{}", self.name, SEPARATOR, table)
+    }
+
+    fn qualify_query(&self, query: &Query) -> Query {
+        match query {
+            Query::And(queries) => Query::And(queries.iter().map(|q| self.qualify_query(q)).collect()),
+            Query::Or(queries) => Query::Or(queries.iter().map(|q| self.qualify_query(q)).collect()),
+            Query::Join(join) => {
+                let mut join = join.clone();
+                join.target_table = self.qualify(&join.target_table);
+                Query::Join(join)
+            }
+            Query::Traverse(traverse) => {
+                let mut traverse = traverse.clone();
+                traverse.start = Box::new(self.qualify_query(&traverse.start));
+                Query::Traverse(traverse)
+            }
+            Query::Sample(sample) => {
+                let mut sample = sample.clone();
+                sample.query = Box::new(self.qualify_query(&sample.query));
+                Query::Sample(sample)
+            }
+            Query::Aggregate(aggregate) => {
+                let mut aggregate = aggregate.clone();
+                aggregate.filter = aggregate.filter.map(|filter| Box::new(self.qualify_query(&filter)));
+                Query::Aggregate(aggregate)
+            }
+            Query::InSubquery {
+                column,
+                table,
+                subquery,
+                select_column,
+            } => Query::InSubquery {
+                column: column.clone(),
+                table: self.qualify(table),
+                subquery: Box::new(self.qualify_query(subquery)),
+                select_column: select_column.clone(),
+            },
+            other => other.clone(),
+        }
+    }
+
+    fn check_active(&self) -> Result<(), String> {
+        self.db.tenants.check_active(&self.name)
+    }
+
+    pub async fn create_table(&self, name: &str, columns: Vec<Column>) -> Result<Duration, String> {
+        self.check_active()?;
+        self.db.create_table(self.qualify(name), columns).await
+    }
+
+    /// The tenant's tables, by their unqualified names.
+    pub async fn table_names(&self) -> Result<Vec<String>, String> {
+        self.check_active()?;
+        let prefix = self.qualify("");
+        Ok(self
+            .db
+            .table_names()
+            .await
+            .into_iter()
+            .filter_map(|t| t.strip_prefix(&prefix).map(str::to_string))
+            .collect())
+    }
+
+    pub async fn insert(&self, table: &str, row: HashMap<String, Value>) -> Result<Duration, WriteError> {
+        self.check_active()?;
+        self.db.insert(&self.qualify(table), row).await
+    }
+
+    pub async fn select(&self, table: &str, query: &Query) -> Result<(Vec<Row>, Duration), String> {
+        self.check_active()?;
+        let mut query = self.qualify_query(query);
+        // Prefixed columns are named after the tables as the tenant knows them.
+        if let Query::Join(join) = &mut query {
+            if join.columns == JoinColumns::Prefixed {
+                let target = join.target_table.split_once(SEPARATOR).map(|(_, target)| target.to_string());
+                join.columns = JoinColumns::Aliased(table.to_string(), target.unwrap_or_default());
+            }
+        }
+        self.db.select(&self.qualify(table), &query).await
+    }
+
+    pub async fn count(&self, table: &str, query: &Query) -> Result<usize, String> {
+        self.check_active()?;
+        self.db.count(&self.qualify(table), &self.qualify_query(query)).await
+    }
+
+    pub async fn update(&self, table: &str, query: &Query, update_fn: UpdateFn) -> Result<usize, WriteError> {
+        self.check_active()?;
+        self.db.update(&self.qualify(table), &self.qualify_query(query), update_fn).await
+    }
+
+    pub async fn delete(&self, table: &str, query: &Query) -> Result<usize, WriteError> {
+        self.check_active()?;
+        self.db.delete(&self.qualify(table), &self.qualify_query(query)).await
+    }
+}
@@ -0,0 +1,304 @@
+//! Pattern matching, and trigram indexes for it.
+//!
+//! `Operator::ILike` matches strings against a SQL LIKE pattern, ignoring
+//! case, and `Like` minding it. Without help that means testing every row.
+//! A trigram index on the
+//! column maps every run of three characters in each lowercased value to the
+//! rows containing it. A matching value must contain each literal run of the
+//! pattern, and so each of its trigrams, so only rows holding all of them
+//! are tested. Patterns without a literal run of three characters, such as
+//! `%ab%`, still test every row. The index is of lowercased values, so
+//! `Like` uses it too and tests the candidates minding case. `NotLike` and
+//! `Regex` always test every row, or every distinct value of an indexed
+//! column.
+//!
+//! `Regex` patterns are compiled once per thread and kept, so a scan doesn't
+//! compile one per row.
+//!
+//! Like other indexes, trigram indexes are kept up to date on every write,
+//! live only in memory, and can be evicted under a memory limit.
+
+use crate::row_ids::IdList;
+use crate::{Column, Condition, DataType, Database, Operator, Row, RowId, Table, Value};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+type Trigram = [char; 3];
+
+/// Whether `text` matches the LIKE `pattern`: `%` matches any run of
+/// characters, `_` any one character, and `\` makes the next character
+/// literal.
+pub(crate) fn like(pattern: &str, text: &str) -> bool {
+    let pattern = parse_pattern(pattern);
+    let text: Vec<char> = text.chars().collect();
+    // Backtracks to the last `%` on a mismatch.
+    let (mut p, mut t) = (0, 0);
+    let mut retry: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(Token::AnyRun) => {
+                retry = Some((p, t));
+                p += 1;
+            }
+            Some(Token::AnyChar) => {
+                p += 1;
+                t += 1;
+            }
+            Some(Token::Char(c)) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match retry {
+                Some((retry_p, retry_t)) => {
+                    retry = Some((retry_p, retry_t + 1));
+                    p = retry_p + 1;
+                    t = retry_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|token| matches!(token, Token::AnyRun))
+}
+
+pub(crate) fn ilike(pattern: &str, text: &str) -> bool {
+    like(&pattern.to_lowercase(), &text.to_lowercase())
+}
+
+// The cache is emptied rather than grown past this.
+const MAX_CACHED_REGEXES: usize = 256;
+
+thread_local! {
+    static REGEXES: RefCell<HashMap<String, Option<Regex>>> = RefCell::new(HashMap::new());
+}
+
+/// Whether `text` contains a match of the regular expression `pattern`. An
+/// invalid pattern matches nothing; `check_regex` reports it.
+pub(crate) fn regex_matches(pattern: &str, text: &str) -> bool {
+    REGEXES.with_borrow_mut(|regexes| {
+        if !regexes.contains_key(pattern) {
+            if regexes.len() >= MAX_CACHED_REGEXES {
+                regexes.clear();
+            }
+            regexes.insert(pattern.to_string(), Regex::new(pattern).ok());
+        }
+        regexes[pattern].as_ref().is_some_and(|regex| regex.is_match(text))
+    })
+}
+
+pub(crate) fn check_regex(pattern: &str) -> Result<(), String> {
+    Regex::new(pattern)
+        .map(|_| ())
+        .map_err(|e| format!("Invalid regex {:?}: {}", pattern, e))
+}
+
+enum Token {
+    Char(char),
+    AnyChar,
+    AnyRun,
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '%' => Token::AnyRun,
+            '_' => Token::AnyChar,
+            '\\' => Token::Char(chars.next().unwrap_or('\\')),
+            c => Token::Char(c),
+        });
+    }
+    tokens
+}
+
+fn trigrams(chars: &[char]) -> impl Iterator<Item = Trigram> + '_ {
+    chars.windows(3).map(|w| [w[0], w[1], w[2]])
+}
+
+// The trigrams every value matching the lowercased `pattern` contains.
+fn pattern_trigrams(pattern: &str) -> Vec<Trigram> {
+    let mut found = Vec::new();
+    let mut run = Vec::new();
+    for token in parse_pattern(pattern).into_iter().chain([Token::AnyRun]) {
+        match token {
+            Token::Char(c) => run.push(c),
+            Token::AnyChar | Token::AnyRun => {
+                found.extend(trigrams(&run));
+                run.clear();
+            }
+        }
+    }
+    found.sort_unstable();
+    found.dedup();
+    found
+}
+
+fn value_trigrams(text: &str) -> HashSet<Trigram> {
+    let chars: Vec<char> = text.to_lowercase().chars().collect();
+    trigrams(&chars).collect()
+}
+
+// A posting key plus the map's overhead for it.
+const TRIGRAM_ENTRY_SIZE: usize = size_of::<Trigram>() + size_of::<IdList>();
+
+#[derive(Clone, Default)]
+pub(crate) struct TrigramIndex {
+    // Row ids containing each trigram, in ascending order. Persistent, like
+    // the value indexes.
+    postings: imbl::HashMap<Trigram, IdList>,
+    bytes: usize,
+    // Shared with published copies, which are the ones queries use.
+    last_used: Arc<AtomicU64>,
+}
+
+impl TrigramIndex {
+    fn insert(&mut self, id: RowId, text: &str) {
+        for trigram in value_trigrams(text) {
+            let ids = self.postings.entry(trigram).or_insert_with(|| {
+                self.bytes += TRIGRAM_ENTRY_SIZE;
+                IdList::new()
+            });
+            // Usually the newest row, but restored rows keep their old ids.
+            let at = ids.binary_search(&id).unwrap_or_else(|at| at);
+            ids.insert(at, id);
+            self.bytes += size_of::<RowId>();
+        }
+    }
+
+    fn remove(&mut self, id: RowId, text: &str) {
+        for trigram in value_trigrams(text) {
+            let Some(ids) = self.postings.get_mut(&trigram) else { continue };
+            if let Ok(at) = ids.binary_search(&id) {
+                ids.remove(at);
+                self.bytes -= size_of::<RowId>();
+            }
+            if ids.is_empty() {
+                self.postings.remove(&trigram);
+                self.bytes -= TRIGRAM_ENTRY_SIZE;
+            }
+        }
+    }
+
+    // Ids of the rows that may match the lowercased `pattern`, or `None` if
+    // the index can't narrow it down.
+    fn candidates(&self, pattern: &str) -> Option<Vec<RowId>> {
+        let wanted = pattern_trigrams(pattern);
+        if wanted.is_empty() {
+            return None;
+        }
+        let mut lists = Vec::with_capacity(wanted.len());
+        for trigram in &wanted {
+            match self.postings.get(trigram) {
+                Some(ids) => lists.push(ids),
+                None => return Some(Vec::new()),
+            }
+        }
+        lists.sort_by_key(|ids| ids.len());
+        let mut ids: Vec<RowId> = lists[0].iter().copied().collect();
+        for other in &lists[1..] {
+            ids.retain(|id| other.binary_search(id).is_ok());
+        }
+        Some(ids)
+    }
+
+    pub(crate) fn last_used(&self) -> u64 {
+        self.last_used.load(Ordering::Relaxed)
+    }
+}
+
+impl Table {
+    pub(crate) fn build_text_index(&mut self, column: &str) {
+        let mut index = TrigramIndex::default();
+        for (row, id) in self.data.iter().zip(&self.row_ids) {
+            if let Some(Value::String(text)) = row.get(column) {
+                index.insert(*id, text);
+            }
+        }
+        if let Some(previous) = self.text_indexes.get(column) {
+            index.last_used = Arc::clone(&previous.last_used);
+        } else {
+            self.plan_epoch = crate::optimizer::next_epoch();
+        }
+        self.text_indexes.insert(column.to_string(), index);
+    }
+
+    // Returns the estimated bytes freed.
+    pub(crate) fn drop_text_index(&mut self, column: &str) -> usize {
+        self.plan_epoch = crate::optimizer::next_epoch();
+        self.text_indexes.remove(column).map_or(0, |index| index.bytes)
+    }
+
+    pub(crate) fn text_index_bytes(&self) -> usize {
+        self.text_indexes.values().map(|index| index.bytes).sum()
+    }
+
+    pub(crate) fn text_indexed(&self, column: &str) -> bool {
+        self.text_indexes.contains_key(column) && !self.indexes_stale
+    }
+
+    pub(crate) fn text_index_row(&mut self, id: RowId, row: &Row) {
+        for (column, index) in &mut self.text_indexes {
+            if let Some(Value::String(text)) = row.get(column) {
+                index.insert(id, text);
+            }
+        }
+    }
+
+    pub(crate) fn text_unindex_row(&mut self, id: RowId, row: &Row) {
+        for (column, index) in &mut self.text_indexes {
+            if let Some(Value::String(text)) = row.get(column) {
+                index.remove(id, text);
+            }
+        }
+    }
+}
+
+fn check_text_column(columns: &[Column], column_name: &str) -> Result<(), String> {
+    match columns.iter().find(|c| c.name == column_name) {
+        Some(column) if column.data_type == DataType::String => Ok(()),
+        Some(_) => Err(format!("Column {} is not a String column", column_name)),
+        None => Err(format!("Column {} not found", column_name)),
+    }
+}
+
+impl Database {
+    /// Builds a trigram index on a String column, which `ILike` and `Like`
+    /// conditions on the column then use.
+    pub async fn create_text_index(&self, table_name: &str, column_name: &str) -> Result<(), String> {
+        self.check_open()?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        check_text_column(&table.columns, column_name)?;
+        table.build_text_index(column_name);
+        self.publish(&tables, &[table_name]);
+        Ok(())
+    }
+
+    // Positions of the rows matching an `ILike` or `Like` condition, found
+    // through the column's trigram index, or `None` if it can't be used.
+    pub(crate) fn search_text_index(&self, table: &Table, condition: &Condition) -> Option<Vec<usize>> {
+        let (Operator::ILike | Operator::Like, Value::String(pattern)) = (&condition.operator, &condition.value) else {
+            return None;
+        };
+        if !table.text_indexed(&condition.column) {
+            return None;
+        }
+        let index = &table.text_indexes[&condition.column];
+        let ids = index.candidates(&pattern.to_lowercase())?;
+        index.last_used.store(self.memory.tick(), Ordering::Relaxed);
+        Some(
+            table
+                .positions(&ids)
+                .filter(|i| self.evaluate_condition(&table.data[*i], condition))
+                .collect(),
+        )
+    }
+}
+
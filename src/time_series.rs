@@ -0,0 +1,284 @@
+//! Time-series queries: bucketing, downsampling and gap filling.
+//!
+//! `Database::time_series` groups the rows of a table into buckets of fixed
+//! width by a `DateTime` column, aggregates each bucket like a rollup
+//! group, and returns one row per bucket in time order, holding the start
+//! of the bucket under the time column's name and each measure under its
+//! own. Buckets are aligned to the Unix epoch, so `5m` buckets start on the
+//! hour and every five minutes after, whatever time the first row has.
+//! Rows without a time are left out.
+//!
+//! Buckets without rows are left out unless the series says how to fill
+//! them. Gaps are filled between the first and last buckets with rows, or
+//! across the whole of the series' range if it has one.
+
+use crate::materialized::AggregateState;
+use crate::{AggregateFunction, Database, DataType, Measure, Query, Row, RowSchema, Value};
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+// A guard against a range far longer than the buckets are wide.
+const MAX_BUCKETS: i64 = 1_000_000;
+
+/// What goes in the measures of a bucket without rows.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Fill {
+    /// Leave the bucket out.
+    #[default]
+    None,
+    Null,
+    /// The measures of the last bucket with rows, or `Null` before the
+    /// first.
+    Previous,
+    /// Values on a straight line between the buckets with rows either side,
+    /// rounded for integer measures. Measures that aren't numbers, and gaps
+    /// at either end, are `Null`.
+    Linear,
+    /// The same value in every measure, as `Value::Integer(0)` for counts.
+    Value(Value),
+}
+
+/// A bucketed aggregate over a `DateTime` column:
+///
+/// ```ignore
+/// TimeSeries::new("at", "5m")
+///     .measure("requests", AggregateFunction::Count, "id")
+///     .measure("latency", AggregateFunction::Avg, "latency_ms")
+///     .fill(Fill::Linear)
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimeSeries {
+    pub time_column: String,
+    /// The width of each bucket: a whole number followed by `ms`, `s`, `m`,
+    /// `h`, `d` or `w`, as in `5m`.
+    pub bucket: String,
+    pub measures: Vec<Measure>,
+    /// Only rows matching this are aggregated.
+    pub filter: Option<Query>,
+    /// Only rows from the start, inclusive, to the end, exclusive, are
+    /// aggregated, and gaps are filled across the whole range.
+    pub range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    pub fill: Fill,
+}
+
+impl TimeSeries {
+    pub fn new(time_column: &str, bucket: &str) -> Self {
+        TimeSeries {
+            time_column: time_column.to_string(),
+            bucket: bucket.to_string(),
+            measures: Vec::new(),
+            filter: None,
+            range: None,
+            fill: Fill::None,
+        }
+    }
+
+    /// Adds a column named `name` holding `function` over `column`.
+    pub fn measure(mut self, name: &str, function: AggregateFunction, column: &str) -> Self {
+        self.measures.push(Measure {
+            name: name.to_string(),
+            function,
+            column: column.to_string(),
+        });
+        self
+    }
+
+    pub fn filter(mut self, query: Query) -> Self {
+        self.filter = Some(query);
+        self
+    }
+
+    pub fn between(mut self, start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        self.range = Some((start, end));
+        self
+    }
+
+    pub fn fill(mut self, fill: Fill) -> Self {
+        self.fill = fill;
+        self
+    }
+}
+
+// The width of a bucket in milliseconds.
+fn parse_width(width: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid bucket width {:?}", width);
+    let split = width.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (count, unit) = width.split_at(split);
+    let count: i64 = count.parse().map_err(|_| invalid())?;
+    let unit = match unit {
+        "ms" => 1,
+        "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        "w" => 7 * 24 * 60 * 60 * 1000,
+        _ => return Err(invalid()),
+    };
+    match count.checked_mul(unit) {
+        Some(width) if width > 0 => Ok(width),
+        _ => Err(invalid()),
+    }
+}
+
+fn bucket_of(at: &DateTime<Utc>, width: i64) -> i64 {
+    at.timestamp_millis().div_euclid(width) * width
+}
+
+/// The start of the bucket of `width`, such as `5m`, that `at` falls in.
+pub fn time_bucket(width: &str, at: &DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let start = bucket_of(at, parse_width(width)?);
+    DateTime::from_timestamp_millis(start).ok_or_else(|| format!("{} is out of range", at))
+}
+
+// Whether `value` is an integer or a float, and its value as a float.
+fn number(value: &Value) -> Option<(bool, f64)> {
+    match value {
+        Value::Integer(i) => Some((true, *i as f64)),
+        Value::Float(x) => Some((false, *x)),
+        _ => None,
+    }
+}
+
+// Fills the `None` buckets of `buckets`, which has an entry for every
+// bucket in the series.
+fn fill(buckets: &mut [(i64, Option<Vec<Value>>)], fill: &Fill, measures: usize) {
+    let known: Vec<usize> = (0..buckets.len()).filter(|&i| buckets[i].1.is_some()).collect();
+    let mut filled = Vec::new();
+    for i in 0..buckets.len() {
+        if buckets[i].1.is_some() {
+            continue;
+        }
+        let before = known.iter().rev().find(|&&k| k < i).copied();
+        let after = known.iter().find(|&&k| k > i).copied();
+        let values = match fill {
+            Fill::None | Fill::Null => vec![Value::Null; measures],
+            Fill::Value(value) => vec![value.clone(); measures],
+            Fill::Previous => match before {
+                Some(k) => buckets[k].1.clone().unwrap_or_default(),
+                None => vec![Value::Null; measures],
+            },
+            Fill::Linear => match (before, after) {
+                (Some(b), Some(a)) => {
+                    let fraction = (i - b) as f64 / (a - b) as f64;
+                    let (from, to) = (buckets[b].1.as_deref().unwrap_or(&[]), buckets[a].1.as_deref().unwrap_or(&[]));
+                    (0..measures)
+                        .map(|m| match (from.get(m).and_then(number), to.get(m).and_then(number)) {
+                            (Some((integer, from)), Some((_, to))) => {
+                                let value = from + (to - from) * fraction;
+                                if integer {
+                                    Value::Integer(value.round() as i64)
+                                } else {
+                                    Value::Float(value)
+                                }
+                            }
+                            _ => Value::Null,
+                        })
+                        .collect()
+                }
+                _ => vec![Value::Null; measures],
+            },
+        };
+        filled.push((i, values));
+    }
+    for (i, values) in filled {
+        buckets[i].1 = Some(values);
+    }
+}
+
+impl Database {
+    /// Runs `series` over `table_name`, as described in the module docs.
+    pub async fn time_series(&self, table_name: &str, series: &TimeSeries) -> Result<Vec<Row>, String> {
+        self.check_open()?;
+        let width = parse_width(&series.bucket)?;
+        if series.measures.is_empty() {
+            return Err("A time series needs at least one measure".to_string());
+        }
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let column = |name: &str| {
+            self.check_column_readable(table_name, name)?;
+            table
+                .columns
+                .iter()
+                .find(|c| c.name == name)
+                .ok_or_else(|| format!("Column {} not found", name))
+        };
+        if column(&series.time_column)?.data_type != DataType::DateTime {
+            return Err(format!("Column {} is not a DateTime column", series.time_column));
+        }
+        for measure in &series.measures {
+            column(&measure.column)?;
+            if measure.name == series.time_column || series.measures.iter().filter(|m| m.name == measure.name).count() > 1 {
+                return Err(format!("Time series column {} appears more than once", measure.name));
+            }
+        }
+        if let Some(filter) = &series.filter {
+            self.check_query(filter, &table.columns)?;
+            self.check_readable(table_name, filter)?;
+        }
+        let range = series.range.map(|(start, end)| (bucket_of(&start, width), start, end));
+
+        let positions = match &series.filter {
+            Some(filter) => self.execute_query(table, filter)?,
+            None => (0..table.data.len()).collect(),
+        };
+        let mut states: BTreeMap<i64, Vec<AggregateState>> = BTreeMap::new();
+        for i in positions {
+            let row = &table.data[i];
+            let Some(Value::DateTime(at)) = row.get(&series.time_column) else { continue };
+            if range.is_some_and(|(_, start, end)| *at < start || *at >= end) {
+                continue;
+            }
+            let bucket = states
+                .entry(bucket_of(at, width))
+                .or_insert_with(|| series.measures.iter().map(|_| AggregateState::default()).collect());
+            for (measure, state) in series.measures.iter().zip(bucket) {
+                if let Some(value) = row.get(&measure.column) {
+                    state.add(&measure.function, value);
+                }
+            }
+        }
+
+        let results = states.into_iter().map(|(start, states)| {
+            let values = series.measures.iter().zip(&states).map(|(m, s)| s.result(&m.function)).collect();
+            (start, Some(values))
+        });
+        let mut buckets: Vec<(i64, Option<Vec<Value>>)> = if series.fill == Fill::None {
+            results.collect()
+        } else {
+            let mut results: BTreeMap<i64, Option<Vec<Value>>> = results.collect();
+            let bounds = match range {
+                Some((first, _, end)) => Some((first, end.timestamp_millis() - 1)),
+                None => results.keys().next().zip(results.keys().next_back()).map(|(a, b)| (*a, *b)),
+            };
+            let Some((first, last)) = bounds else { return Ok(Vec::new()) };
+            let count = if last < first { 0 } else { (last - first) / width + 1 };
+            if count > MAX_BUCKETS {
+                return Err(format!("A time series can't have more than {} buckets", MAX_BUCKETS));
+            }
+            (0..count)
+                .map(|n| first + n * width)
+                .map(|start| (start, results.remove(&start).flatten()))
+                .collect()
+        };
+        fill(&mut buckets, &series.fill, series.measures.len());
+
+        let names = std::iter::once(series.time_column.clone()).chain(series.measures.iter().map(|m| m.name.clone()));
+        let schema = Arc::new(RowSchema::new(names));
+        let mut rows = Vec::with_capacity(buckets.len());
+        for (start, values) in buckets {
+            let Some(values) = values else { continue };
+            let start = DateTime::from_timestamp_millis(start).ok_or("Bucket start is out of range")?;
+            let mut row = Row::with_schema(schema.clone());
+            row.insert(series.time_column.clone(), Value::DateTime(start));
+            for (measure, value) in series.measures.iter().zip(values) {
+                row.insert(measure.name.clone(), value);
+            }
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+}
@@ -0,0 +1,80 @@
+//! Timeouts for operations that can't get their turn.
+//!
+//! With `DatabaseConfig::operation_timeout`, or a session's
+//! `statement_timeout`, which takes precedence, an operation gives up on
+//! whatever it waits for before its point of no return and fails with a
+//! `Timeout`: `WriteError::Timeout` for writes, an `io::Error` of kind
+//! `TimedOut` for `save`, `load` and the like, and its message elsewhere.
+//!
+//! - Writes, schema changes, index builds and view and rollup refreshes
+//!   time getting through admission and taking their locks. They take them
+//!   before logging or changing anything, and once they hold them they run
+//!   to completion, so one that times out has changed nothing.
+//! - `save` times taking the WAL lock and reading the tables; `load` times
+//!   reading the snapshot and the WAL, before installing either. `backup`
+//!   changes nothing, so it is timed as a whole, writing included.
+//! - Queries read published snapshots and don't wait for anything, except
+//!   for the lock while maintenance is deferred, which is timed.
+//!
+//! Work in memory, such as a scan, never awaits, so a timeout can't
+//! interrupt it once it has started; the limit is on waiting, not on CPU.
+//! Replaying the WAL, and getters with no error to return, such as
+//! `table_names`, aren't timed.
+
+use crate::{Database, Table, WalEntry, WriteError};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::future::Future;
+use std::time::Duration;
+use tokio::sync::RwLockWriteGuard;
+
+/// An operation that gave up waiting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timeout {
+    pub after: Duration,
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Operation timed out after {:?}", self.after)
+    }
+}
+
+impl Error for Timeout {}
+
+impl From<Timeout> for io::Error {
+    fn from(timeout: Timeout) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, timeout)
+    }
+}
+
+impl Database {
+    pub(crate) fn operation_timeout(&self) -> Option<Duration> {
+        self.session().statement_timeout.or(self.config.operation_timeout)
+    }
+
+    /// Waits for `future` for up to the operation timeout.
+    pub(crate) async fn timed<F: Future>(&self, future: F) -> Result<F::Output, Timeout> {
+        match self.operation_timeout() {
+            Some(after) => tokio::time::timeout(after, future).await.map_err(|_| Timeout { after }),
+            None => Ok(future.await),
+        }
+    }
+
+    /// Takes the WAL and table locks, then logs `wal_entry` unless the table
+    /// is ephemeral, and returns the table lock to apply the entry under.
+    pub(crate) async fn log_and_lock(
+        &self,
+        table_name: &str,
+        wal_entry: &WalEntry,
+    ) -> Result<RwLockWriteGuard<'_, HashMap<String, Table>>, WriteError> {
+        let mut wal_writer = self.timed(self.wal_writer.write()).await?;
+        let tables = self.timed(self.tables.write()).await?;
+        if !tables.get(table_name).is_some_and(|t| t.ephemeral) {
+            wal_writer.log(wal_entry).map_err(|e| e.to_string())?;
+        }
+        Ok(tables)
+    }
+}
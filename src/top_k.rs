@@ -0,0 +1,95 @@
+//! The first rows of a query in the order of a column.
+//!
+//! Instead of sorting every matching row, the scan keeps the best `k` seen so
+//! far in a heap whose top is the worst of them, so finding the 10 highest of
+//! a million values takes a million cheap comparisons against the heap's top
+//! and a handful of heap updates, in memory for 10 rows.
+
+use crate::{Database, Query, Row, Value};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+// The `k` smallest items, smallest first.
+pub(crate) fn smallest<T: Ord>(items: impl Iterator<Item = T>, k: usize) -> Vec<T> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut heap = BinaryHeap::with_capacity(k + 1);
+    for item in items {
+        if heap.len() == k {
+            if heap.peek().is_some_and(|worst| item >= *worst) {
+                continue;
+            }
+            heap.pop();
+        }
+        heap.push(item);
+    }
+    heap.into_sorted_vec()
+}
+
+/// The first `k` of `(value, position)` pairs in `order`, with ties in
+/// position order.
+pub(crate) fn top_k<'a>(
+    candidates: impl Iterator<Item = (&'a Value, usize)>,
+    order: SortOrder,
+    k: usize,
+) -> Vec<(&'a Value, usize)> {
+    match order {
+        SortOrder::Ascending => smallest(candidates, k),
+        SortOrder::Descending => smallest(candidates.map(|(v, i)| (Reverse(v), i)), k)
+            .into_iter()
+            .map(|(Reverse(v), i)| (v, i))
+            .collect(),
+    }
+}
+
+impl Database {
+    /// The first `k` rows matching `query` in `order` of `order_column`, like
+    /// `ORDER BY order_column LIMIT k`. Rows with equal values come in
+    /// insertion order; rows without a value in the column are left out.
+    pub async fn select_top(
+        &self,
+        table_name: &str,
+        query: &Query,
+        order_column: &str,
+        order: SortOrder,
+        k: usize,
+    ) -> Result<Vec<Row>, String> {
+        self.check_open()?;
+        if matches!(query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_)) {
+            return Err("Top-k is only supported for filter queries".to_string());
+        }
+        let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+        let table = tables
+            .get(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        if !table.columns.iter().any(|c| c.name == order_column) {
+            return Err(format!("Column {} not found", order_column));
+        }
+        self.check_query(query, &table.columns)?;
+        self.check_readable(table_name, query)?;
+        self.check_column_readable(table_name, order_column)?;
+
+        let optimized_query = self.query_planner.optimize(query.clone(), table);
+        let candidates = self
+            .execute_query(table, &optimized_query)?
+            .into_iter()
+            .filter_map(|i| table.data[i].get(order_column).map(|v| (v, i)))
+            .filter(|(v, _)| **v != Value::Null);
+        Ok(top_k(candidates, order, k)
+            .into_iter()
+            .map(|(_, i)| {
+                let mut row = (*table.data[i]).clone();
+                self.hide_row_columns(table_name, query, &mut row);
+                row
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,86 @@
+//! Recursive queries over a relationship within one table.
+//!
+//! A `Traverse` starts from the rows matching `start`, at depth 0, and steps
+//! from each row to the rows whose `to` column equals its `from` column. With
+//! `from: "id", to: "manager_id"` that walks down an org chart; swapping the
+//! two walks up it. Each row is returned once, at the smallest depth it is
+//! reached, with that depth in an extra `depth` column, so cycles end the
+//! walk instead of looping.
+
+use crate::{Column, Database, Query, Row, RowSchema, Table, Value};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+pub(crate) const DEPTH_COLUMN: &str = "depth";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Traverse {
+    pub start: Box<Query>,
+    pub from: String,
+    pub to: String,
+    /// How many steps to follow. `None` follows until no new rows are reached.
+    pub max_depth: Option<usize>,
+}
+
+impl Database {
+    pub(crate) fn check_traverse(&self, traverse: &Traverse, columns: &[Column]) -> Result<(), String> {
+        for name in [&traverse.from, &traverse.to] {
+            if !columns.iter().any(|c| c.name == *name) {
+                return Err(format!("Column {} not found", name));
+            }
+        }
+        if columns.iter().any(|c| c.name == DEPTH_COLUMN) {
+            return Err(format!("Tables with a {} column can't be traversed", DEPTH_COLUMN));
+        }
+        if matches!(*traverse.start, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_)) {
+            return Err("A traversal must start from a filter query".to_string());
+        }
+        self.check_query(&traverse.start, columns)
+    }
+
+    pub(crate) fn execute_traverse(&self, table: &Table, traverse: &Traverse) -> Result<Vec<Row>, String> {
+        let mut by_target: HashMap<&Value, Vec<usize>> = HashMap::new();
+        for (i, row) in table.data.iter().enumerate() {
+            if let Some(value) = row.get(&traverse.to).filter(|v| !matches!(v, Value::Null)) {
+                by_target.entry(value).or_default().push(i);
+            }
+        }
+
+        let mut level = self.execute_query(table, &traverse.start)?;
+        level.sort_unstable();
+        let mut seen: HashSet<usize> = level.iter().copied().collect();
+        let mut reached = Vec::new();
+        let mut depth = 0;
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            if traverse.max_depth.is_none_or(|max| depth < max) {
+                for &i in &level {
+                    let targets = table.data[i].get(&traverse.from).and_then(|v| by_target.get(v));
+                    for &j in targets.into_iter().flatten() {
+                        if seen.insert(j) {
+                            next.push(j);
+                        }
+                    }
+                }
+            }
+            reached.extend(level.into_iter().map(|i| (i, depth)));
+            next.sort_unstable();
+            level = next;
+            depth += 1;
+        }
+
+        let schema = Arc::new(RowSchema::new(
+            table.schema.names().iter().cloned().chain([DEPTH_COLUMN.to_string()]),
+        ));
+        Ok(reached
+            .into_iter()
+            .map(|(i, depth)| {
+                let mut row = Row::with_schema(schema.clone());
+                row.extend(table.data[i].iter().map(|(k, v)| (k.clone(), v.clone())));
+                row.insert(DEPTH_COLUMN.to_string(), Value::Integer(depth as i64));
+                row
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{Column, Database, DataType, Instant, Query, Row, Value, WriteError};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+impl Value {
+    /// Converts the value into its natural JSON representation. DateTime and
+    /// Uuid values become strings, which is the format their serde impls expect.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::Integer(i) => serde_json::Value::from(*i),
+            Value::String(s) => serde_json::Value::String(s.clone()),
+            Value::Float(f) => serde_json::Value::from(*f),
+            Value::Boolean(b) => serde_json::Value::Bool(*b),
+            Value::DateTime(dt) => serde_json::Value::String(dt.to_rfc3339()),
+            Value::Uuid(u) => serde_json::Value::String(u.to_string()),
+            Value::Json(j) => j.clone(),
+            Value::Null => serde_json::Value::Null,
+            Value::Param(i) => serde_json::json!({ "param": i }),
+        }
+    }
+
+    /// Converts a JSON value into the `Value` variant matching `data_type`.
+    /// Integers are accepted for Float columns; DateTime and Uuid are parsed from strings.
+    pub fn from_json(json: &serde_json::Value, data_type: &DataType) -> Result<Value, String> {
+        if json.is_null() {
+            return Ok(Value::Null);
+        }
+        let value = match data_type {
+            DataType::Integer => json.as_i64().map(Value::Integer),
+            DataType::Float => json.as_f64().map(Value::Float),
+            DataType::String => json.as_str().map(|s| Value::String(s.to_string())),
+            DataType::Boolean => json.as_bool().map(Value::Boolean),
+            DataType::DateTime => json
+                .as_str()
+                .and_then(|s| s.parse::<DateTime<Utc>>().ok())
+                .map(Value::DateTime),
+            DataType::Uuid => json
+                .as_str()
+                .and_then(|s| s.parse().ok())
+                .map(Value::Uuid),
+            DataType::Json => Some(Value::Json(json.clone())),
+        };
+        value.ok_or_else(|| format!("Cannot convert {} to {:?}", json, data_type))
+    }
+}
+
+pub fn row_to_json(row: &Row) -> serde_json::Value {
+    serde_json::Value::Object(
+        row.iter()
+            .map(|(name, value)| (name.clone(), value.to_json()))
+            .collect(),
+    )
+}
+
+pub fn from_row<T: DeserializeOwned>(row: &Row) -> Result<T, String> {
+    serde_json::from_value(row_to_json(row)).map_err(|e| format!("Failed to deserialize row: {}", e))
+}
+
+pub fn to_row<T: Serialize>(
+    value: &T,
+    columns: &[Column],
+) -> Result<HashMap<String, Value>, String> {
+    let json = serde_json::to_value(value).map_err(|e| format!("Failed to serialize row: {}", e))?;
+    let fields = match json {
+        serde_json::Value::Object(fields) => fields,
+        other => return Err(format!("Expected a struct or map, got {}", other)),
+    };
+
+    let mut row = HashMap::new();
+    for (name, field) in fields {
+        let column = columns
+            .iter()
+            .find(|c| c.name == name)
+            .ok_or_else(|| format!("Column {} not found", name))?;
+        let value = Value::from_json(&field, &column.data_type)
+            .map_err(|e| format!("Invalid value for column {}: {}", name, e))?;
+        row.insert(name, value);
+    }
+    Ok(row)
+}
+
+impl Database {
+    pub async fn select_as<T: DeserializeOwned>(
+        &self,
+        table_name: &str,
+        query: &Query,
+    ) -> Result<(Vec<T>, Duration), String> {
+        let start = Instant::now();
+        let (rows, _) = self.select_shared(table_name, query).await?;
+        let results = rows
+            .iter()
+            .map(|row| from_row(row))
+            .collect::<Result<Vec<T>, String>>()?;
+        Ok((results, start.elapsed()))
+    }
+
+    pub async fn insert_struct<T: Serialize>(
+        &self,
+        table_name: &str,
+        value: &T,
+    ) -> Result<Duration, WriteError> {
+        let columns = self
+            .table_columns(table_name)
+            .await
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let row = to_row(value, &columns)?;
+        self.insert(table_name, row).await
+    }
+}
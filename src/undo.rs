@@ -0,0 +1,225 @@
+//! Undo and redo of committed writes.
+//!
+//! With `DatabaseConfig::undo_depth` set, each committed insert, update,
+//! delete or transaction keeps the rows it changed. `undo` puts those rows
+//! back the way they were and `redo` applies them again. Both write exact
+//! row replacements to the WAL, so a reversal survives a restart like any
+//! other write. A new write discards whatever could be redone.
+//!
+//! Reversing a write fails, leaving everything as it was, if a row it touched
+//! has changed since, or if restoring a row would break a constraint.
+
+use crate::watch::{Changes, WatchEvent};
+use crate::{Database, Row, Table, Value, WalEntry};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+pub(crate) struct UndoLog {
+    depth: usize,
+    undo: Mutex<VecDeque<Changes>>,
+    redo: Mutex<Vec<Changes>>,
+}
+
+impl UndoLog {
+    pub(crate) fn new(depth: usize) -> Self {
+        UndoLog {
+            depth,
+            undo: Mutex::new(VecDeque::new()),
+            redo: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        self.depth > 0
+    }
+
+    pub(crate) fn record(&self, changes: &Changes) {
+        if !self.enabled() || changes.is_empty() {
+            return;
+        }
+        let mut undo = self.undo.lock().unwrap();
+        if undo.len() == self.depth {
+            undo.pop_front();
+        }
+        undo.push_back(changes.clone());
+        self.redo.lock().unwrap().clear();
+    }
+}
+
+// One row replacement: remove `old` (if any) and add `new` (if any).
+type Replacement = (String, Option<Row>, Option<Row>);
+
+impl Database {
+    /// Reverses the last `n` committed writes, newest first, and returns how
+    /// many were reversed. Each one is reversed as a whole or not at all.
+    pub async fn undo(&self, n: usize) -> Result<usize, String> {
+        self.check_open()?;
+        let mut undone = 0;
+        while undone < n {
+            let Some(changes) = self.undo_log.undo.lock().unwrap().pop_back() else {
+                break;
+            };
+            let replacements = changes
+                .iter()
+                .rev()
+                .map(|(table, _, event)| match event {
+                    WatchEvent::Insert(row) => (table.clone(), Some(row.clone()), None),
+                    WatchEvent::Update { old, new } => (table.clone(), Some(new.clone()), Some(old.clone())),
+                    WatchEvent::Delete(row) => (table.clone(), None, Some(row.clone())),
+                })
+                .collect();
+            if let Err(e) = self.apply_replacements(replacements).await {
+                self.undo_log.undo.lock().unwrap().push_back(changes);
+                return Err(e);
+            }
+            self.undo_log.redo.lock().unwrap().push(changes);
+            undone += 1;
+        }
+        Ok(undone)
+    }
+
+    /// Applies again the last `n` writes reversed by `undo`.
+    pub async fn redo(&self, n: usize) -> Result<usize, String> {
+        self.check_open()?;
+        let mut redone = 0;
+        while redone < n {
+            let Some(changes) = self.undo_log.redo.lock().unwrap().pop() else {
+                break;
+            };
+            let replacements = changes
+                .iter()
+                .map(|(table, _, event)| match event {
+                    WatchEvent::Insert(row) => (table.clone(), None, Some(row.clone())),
+                    WatchEvent::Update { old, new } => (table.clone(), Some(old.clone()), Some(new.clone())),
+                    WatchEvent::Delete(row) => (table.clone(), Some(row.clone()), None),
+                })
+                .collect();
+            if let Err(e) = self.apply_replacements(replacements).await {
+                self.undo_log.redo.lock().unwrap().push(changes);
+                return Err(e);
+            }
+            self.undo_log.undo.lock().unwrap().push_back(changes);
+            redone += 1;
+        }
+        Ok(redone)
+    }
+
+    async fn apply_replacements(&self, replacements: Vec<Replacement>) -> Result<(), String> {
+        // Same lock order as `commit`.
+        let mut wal_writer = self.timed(self.wal_writer.write()).await.map_err(|e| e.to_string())?;
+        let mut tables = self.timed(self.tables.write()).await.map_err(|e| e.to_string())?;
+        let original_tables = tables.clone();
+        let mut changes = Changes::new();
+        let mut wal_entries = Vec::new();
+        let mut changed: Vec<String> = Vec::new();
+        for (table_name, old, new) in replacements {
+            if !changed.contains(&table_name) {
+                changed.push(table_name.clone());
+            }
+            let old = old.map(|row| row.to_map());
+            let new = new.map(|row| row.to_map());
+            if tables.get(&table_name).is_some_and(|t| !t.ephemeral) {
+                wal_entries.push(WalEntry::Replace {
+                    table_name: table_name.clone(),
+                    old: old.clone(),
+                    new: new.clone(),
+                });
+            }
+            if let Err(e) = self.replace_internal(&mut tables, &table_name, old, new, &mut changes) {
+                *tables = original_tables;
+                return Err(e);
+            }
+        }
+        for entry in &wal_entries {
+            if let Err(e) = wal_writer.log(entry) {
+                *tables = original_tables;
+                return Err(e.to_string());
+            }
+        }
+        let changed: Vec<&str> = changed.iter().map(String::as_str).collect();
+        self.publish_changes(&mut tables, &changed, changes);
+        Ok(())
+    }
+
+    /// Replays a `WalEntry::Replace`.
+    pub(crate) async fn replay_replace(
+        &self,
+        table_name: &str,
+        old: Option<HashMap<String, Value>>,
+        new: Option<HashMap<String, Value>>,
+    ) -> Result<(), String> {
+        let mut tables = self.tables.write().await;
+        let mut changes = Changes::new();
+        self.replace_internal(&mut tables, table_name, old, new, &mut changes)?;
+        self.publish_changes(&mut tables, &[table_name], changes);
+        Ok(())
+    }
+
+    // Removes the first row equal to `old` and inserts `new`, checking `new`
+    // against the table's constraints. When there are both, `new` takes the
+    // place and id of `old`.
+    fn replace_internal(
+        &self,
+        tables: &mut HashMap<String, Table>,
+        table_name: &str,
+        old: Option<HashMap<String, Value>>,
+        new: Option<HashMap<String, Value>>,
+        changes: &mut Changes,
+    ) -> Result<(), String> {
+        self.check_writable(table_name)?;
+        let table = tables
+            .get_mut(table_name)
+            .ok_or_else(|| format!("Table {} not found", table_name))?;
+        let removed = match old {
+            Some(old) => {
+                let position = table
+                    .data
+                    .iter()
+                    .position(|row| row.to_map() == old)
+                    .ok_or_else(|| format!("A row in {} has changed since it was written", table_name))?;
+                let removed = table.remove_rows(&[position]).pop();
+                table.maintain(self.maintenance_deferred());
+                removed
+            }
+            None => None,
+        };
+
+        let mut inserted = Changes::new();
+        if let Some(new) = new {
+            let result = self.insert_internal(tables, table_name, new, &mut inserted);
+            let Some(table) = tables.get_mut(table_name) else {
+                return result.map_err(String::from);
+            };
+            match (result, &removed) {
+                (Err(e), Some((id, row))) => {
+                    table.restore_row(*id, row.clone());
+                    table.maintain(self.maintenance_deferred());
+                    return Err(e.into());
+                }
+                (Err(e), None) => return Err(e.into()),
+                (Ok(()), Some((id, _))) => {
+                    let (_, row) = table.remove_rows(&[table.data.len() - 1]).remove(0);
+                    table.restore_row(*id, row);
+                    table.maintain(self.maintenance_deferred());
+                }
+                (Ok(()), None) => {}
+            }
+        }
+        if self.tracking_changes() {
+            let (id, event) = match (removed, inserted.pop()) {
+                (Some((id, old)), Some((_, _, WatchEvent::Insert(new)))) => (
+                    id,
+                    WatchEvent::Update {
+                        old: (*old).clone(),
+                        new,
+                    },
+                ),
+                (Some((id, old)), _) => (id, WatchEvent::Delete((*old).clone())),
+                (None, Some((_, id, event))) => (id, event),
+                (None, None) => return Ok(()),
+            };
+            changes.push((table_name.to_string(), id, event));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Updates described as data rather than as a function.
+//!
+//! An `UpdateFn` can't be written to the WAL, so updates made with one are
+//! lost on replay. An `UpdateExpr` is logged as it is and applied again on
+//! replay, and it reads and writes each row under the table lock, so
+//! concurrent increments never lose one another's changes.
+
+use crate::watch::Changes;
+use crate::{Database, OperationKind, Query, UpdateFn, Value, WalEntry, WriteError};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub enum UpdateExpr {
+    /// Adds `delta`, an integer or a float, to the column. A missing or null
+    /// value counts as 0.
+    Increment { column: String, delta: Value },
+    /// Sets each column to its value.
+    Set(Vec<(String, Value)>),
+}
+
+impl UpdateExpr {
+    pub(crate) fn apply(&self, row: &mut HashMap<String, Value>) -> Result<(), String> {
+        match self {
+            UpdateExpr::Increment { column, delta } => {
+                let current = row.get(column).unwrap_or(&Value::Null);
+                let sum = match (current, delta) {
+                    (Value::Null, Value::Integer(_) | Value::Float(_)) => delta.clone(),
+                    (Value::Integer(a), Value::Integer(b)) => Value::Integer(
+                        a.checked_add(*b)
+                            .ok_or_else(|| format!("Incrementing column {} overflows", column))?,
+                    ),
+                    (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
+                    (Value::Float(a), Value::Integer(b)) => Value::Float(a + *b as f64),
+                    (current, delta) => {
+                        return Err(format!("Can't add {} to {} in column {}", delta, current, column));
+                    }
+                };
+                row.insert(column.clone(), sum);
+                Ok(())
+            }
+            UpdateExpr::Set(values) => {
+                row.extend(values.iter().cloned());
+                Ok(())
+            }
+        }
+    }
+}
+
+// How `update_internal` changes each row.
+pub(crate) enum RowUpdate<'a> {
+    Fn(UpdateFn),
+    Expr(&'a UpdateExpr),
+}
+
+impl RowUpdate<'_> {
+    pub(crate) fn apply(&self, row: &mut HashMap<String, Value>) -> Result<(), String> {
+        match self {
+            RowUpdate::Fn(update_fn) => {
+                update_fn(row);
+                Ok(())
+            }
+            RowUpdate::Expr(expr) => expr.apply(row),
+        }
+    }
+}
+
+impl Database {
+    /// Adds `delta` to `column` in every row matching `query`, in one step
+    /// that no other write can interleave with, and returns how many rows
+    /// were changed.
+    pub async fn increment(
+        &self,
+        table_name: &str,
+        query: &Query,
+        column: &str,
+        delta: Value,
+    ) -> Result<usize, WriteError> {
+        let expr = UpdateExpr::Increment {
+            column: column.to_string(),
+            delta,
+        };
+        self.check_readable(table_name, query)?;
+        self.check_expr_writable(table_name, &expr)?;
+        self.measured(table_name, OperationKind::Update, async {
+            let _admitted = self.admit(&[table_name]).await?;
+            self.apply_update_expr(table_name, query, &expr).await
+        })
+        .await
+    }
+
+    /// Applies `expr` to every row matching `query` and returns how many
+    /// rows were changed. Unlike `update`, the change is replayed from the
+    /// WAL.
+    pub async fn update_with(&self, table_name: &str, query: &Query, expr: UpdateExpr) -> Result<usize, WriteError> {
+        self.check_readable(table_name, query)?;
+        self.check_expr_writable(table_name, &expr)?;
+        self.measured(table_name, OperationKind::Update, async {
+            let _admitted = self.admit(&[table_name]).await?;
+            self.apply_update_expr(table_name, query, &expr).await
+        })
+        .await
+    }
+
+    // Logs and applies `expr`, without admission, for WAL replay.
+    pub(crate) async fn apply_update_expr(
+        &self,
+        table_name: &str,
+        query: &Query,
+        expr: &UpdateExpr,
+    ) -> Result<usize, WriteError> {
+        self.check_open()?;
+        let wal_entry = WalEntry::UpdateExpr {
+            table_name: table_name.to_string(),
+            query: query.clone(),
+            expr: expr.clone(),
+        };
+        let mut tables = self.log_and_lock(table_name, &wal_entry).await?;
+        let mut changes = Changes::new();
+        let updated = self.update_internal(&mut tables, table_name, query, RowUpdate::Expr(expr), &mut changes)?;
+        self.finish_write(&mut tables, &[table_name], changes);
+        Ok(updated)
+    }
+}
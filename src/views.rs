@@ -0,0 +1,200 @@
+//! Named views: stored queries that can be selected from like tables.
+//!
+//! A view is just its definition; nothing is stored per row. Selecting from a
+//! view runs its query against the base table, combined with the caller's
+//! query. Filter views combine with any filter or aggregate; join and
+//! traversal views can be filtered, with the filter applied to the result
+//! rows.
+//!
+//! Definitions are persisted: creating or dropping a view is logged to the WAL,
+//! and snapshots store them after the tables.
+
+use crate::dependencies;
+use crate::{AggregateQuery, Database, Query, Sample, WalEntry};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct View {
+    pub table: String,
+    pub query: Query,
+}
+
+#[derive(Default)]
+pub(crate) struct NamedViews {
+    views: RwLock<HashMap<String, View>>,
+}
+
+impl NamedViews {
+    pub(crate) fn get(&self, name: &str) -> Option<View> {
+        self.views.read().unwrap().get(name).cloned()
+    }
+
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.views.read().unwrap().contains_key(name)
+    }
+
+    pub(crate) fn all(&self) -> HashMap<String, View> {
+        self.views.read().unwrap().clone()
+    }
+
+    pub(crate) fn replace(&self, views: HashMap<String, View>) {
+        *self.views.write().unwrap() = views;
+    }
+
+    // Views over `table_name`, as their base table or the target of a join.
+    pub(crate) fn depending_on(&self, table_name: &str) -> Vec<String> {
+        let views = self.views.read().unwrap();
+        let mut names: Vec<String> = views
+            .iter()
+            .filter(|(_, view)| {
+                view.table == table_name || matches!(&view.query, Query::Join(join) if join.target_table == table_name)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub(crate) fn remove(&self, name: &str) {
+        self.views.write().unwrap().remove(name);
+    }
+
+    pub(crate) fn rename_table(&self, old: &str, new: &str) {
+        for view in self.views.write().unwrap().values_mut() {
+            if view.table == old {
+                view.table = new.to_string();
+            }
+            dependencies::rename_joined_table(&mut view.query, old, new);
+        }
+    }
+}
+
+// What a query on a view runs as: a query on the base table, plus a filter
+// for the result rows when the view is a join.
+pub(crate) struct ResolvedView {
+    pub(crate) table: String,
+    pub(crate) query: Query,
+    pub(crate) filter: Option<Query>,
+}
+
+impl Database {
+    /// Stores `query` over `table_name` under `name`. The query may be a
+    /// filter, an aggregate, a join or a traversal; the name can't be used by
+    /// a table.
+    pub async fn create_view(&self, name: &str, table_name: &str, query: Query) -> Result<(), String> {
+        self.check_open()?;
+        {
+            let tables = self.read_tables().await.map_err(|e| e.to_string())?;
+            if tables.get(name).is_some() || self.named_views.contains(name) {
+                return Err(format!("Table {} already exists", name));
+            }
+            if tables.get(table_name).is_none() {
+                return Err(format!("Table {} not found", table_name));
+            }
+            if let Query::Join(join) = &query {
+                if tables.get(&join.target_table).is_none() {
+                    return Err(format!("Table {} not found", join.target_table));
+                }
+                join.check_columns(table_name)?;
+            }
+        }
+        let wal_entry = WalEntry::CreateView {
+            name: name.to_string(),
+            table: table_name.to_string(),
+            query: query.clone(),
+        };
+        self.log_wal(table_name, &wal_entry).await?;
+        self.named_views.views.write().unwrap().insert(
+            name.to_string(),
+            View {
+                table: table_name.to_string(),
+                query,
+            },
+        );
+        Ok(())
+    }
+
+    pub async fn drop_view(&self, name: &str) -> Result<(), String> {
+        self.check_open()?;
+        let view = self
+            .named_views
+            .get(name)
+            .ok_or_else(|| format!("View {} not found", name))?;
+        let wal_entry = WalEntry::DropView {
+            name: name.to_string(),
+        };
+        self.log_wal(&view.table, &wal_entry).await?;
+        self.named_views.views.write().unwrap().remove(name);
+        Ok(())
+    }
+
+    pub fn view(&self, name: &str) -> Option<View> {
+        self.named_views.get(name)
+    }
+
+    /// Translates `query` on the view `name` into a query on its base table,
+    /// or returns `None` if `name` isn't a view.
+    pub(crate) fn resolve_view(&self, name: &str, query: &Query) -> Result<Option<ResolvedView>, String> {
+        let view = match self.named_views.get(name) {
+            Some(view) => view,
+            None => return Ok(None),
+        };
+        let resolved = match (&view.query, query) {
+            (Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_), Query::MatchAll) => ResolvedView {
+                table: view.table,
+                query: view.query,
+                filter: None,
+            },
+            (
+                Query::Join(_) | Query::Traverse(_),
+                Query::Condition(_) | Query::Compare(_) | Query::And(_) | Query::Or(_),
+            ) => ResolvedView {
+                table: view.table,
+                query: view.query,
+                filter: Some(query.clone()),
+            },
+            (Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_), _) => {
+                return Err(format!("View {} can only be filtered", name));
+            }
+            (_, Query::Join(_)) => return Err("Joins on views are not supported".to_string()),
+            (_, Query::Traverse(_)) => return Err("Traversals on views are not supported".to_string()),
+            (_, Query::Sample(sample)) => ResolvedView {
+                table: view.table,
+                query: Query::Sample(Sample {
+                    query: Box::new(Query::And(vec![view.query.clone(), (*sample.query).clone()])),
+                    ..sample.clone()
+                }),
+                filter: None,
+            },
+            (_, Query::Aggregate(aggregate)) => {
+                let filter = match &aggregate.filter {
+                    Some(filter) => Query::And(vec![view.query.clone(), (**filter).clone()]),
+                    None => view.query.clone(),
+                };
+                ResolvedView {
+                    table: view.table,
+                    query: Query::Aggregate(AggregateQuery {
+                        function: aggregate.function.clone(),
+                        column: aggregate.column.clone(),
+                        filter: Some(Box::new(filter)),
+                        group_by: aggregate.group_by.clone(),
+                    }),
+                    filter: None,
+                }
+            }
+            (_, Query::MatchAll) => ResolvedView {
+                table: view.table,
+                query: view.query,
+                filter: None,
+            },
+            (_, query) => ResolvedView {
+                table: view.table,
+                query: Query::And(vec![view.query.clone(), query.clone()]),
+                filter: None,
+            },
+        };
+        Ok(Some(resolved))
+    }
+}
@@ -0,0 +1,141 @@
+//! WAL archiving, and rebuilding a database from the archive alone.
+//!
+//! With `DatabaseConfig::wal_archive` set, `save` copies the WAL into the
+//! archive directory as a segment before emptying it. Together the segments
+//! hold every logged write since the database was created, so if the
+//! snapshot is lost or damaged, `Database::rebuild_from_wal` can replay them
+//! into an empty database.
+//!
+//! Entries are numbered across the whole chain by their log sequence number
+//! (LSN), counting from 1. Each segment is named after the LSN of its first
+//! entry, so a segment that is missing, or whose end can't be read, shows
+//! up as a gap before the next one. Replay carries on past gaps and reports
+//! them, as what follows may still be worth having.
+
+use crate::{Database, WalEntries};
+use std::fs;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// What `rebuild_from_wal` replayed.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WalRebuildReport {
+    /// The LSNs of the first and last entries replayed.
+    pub first_lsn: Option<u64>,
+    pub last_lsn: Option<u64>,
+    pub segments: usize,
+    /// Entries replayed.
+    pub entries: u64,
+    /// LSNs missing from the chain.
+    pub gaps: Vec<RangeInclusive<u64>>,
+    /// Segments with an entry that couldn't be decoded, as
+    /// `(segment, LSN, error)`. Nothing after it in the segment is replayed.
+    pub damaged: Vec<(String, u64, String)>,
+}
+
+impl WalRebuildReport {
+    /// Whether the whole chain was replayed.
+    pub fn is_complete(&self) -> bool {
+        self.gaps.is_empty() && self.damaged.is_empty()
+    }
+}
+
+fn segment_name(first_lsn: u64) -> String {
+    format!("{:020}.wal", first_lsn)
+}
+
+// The segments in `dir` with their first LSNs, in order.
+fn segments(dir: &str) -> io::Result<Vec<(u64, String)>> {
+    let mut segments = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if let Some(lsn) = name.strip_suffix(".wal").and_then(|lsn| lsn.parse().ok()) {
+            segments.push((lsn, name));
+        }
+    }
+    segments.sort();
+    Ok(segments)
+}
+
+fn count_entries(wal: &[u8]) -> u64 {
+    WalEntries::new(wal).take_while(|(_, entry)| entry.is_ok()).count() as u64
+}
+
+/// Adds `wal` to the archive in `dir` as the next segment. An empty WAL
+/// adds nothing.
+pub(crate) fn archive(dir: &str, wal: &[u8]) -> io::Result<()> {
+    if count_entries(wal) == 0 {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)?;
+    let next_lsn = match segments(dir)?.pop() {
+        Some((first_lsn, name)) => first_lsn + count_entries(&fs::read(Path::new(dir).join(name))?),
+        None => 1,
+    };
+    // Written under a temporary name so a crash can't leave half a segment.
+    let path = Path::new(dir).join(segment_name(next_lsn));
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, wal)?;
+    fs::File::open(&temporary)?.sync_all()?;
+    fs::rename(&temporary, &path)
+}
+
+impl Database {
+    /// Rebuilds this database from the WAL segments archived in
+    /// `segments_dir`, followed by its own WAL, for when the snapshot is
+    /// lost. The database must have no tables, as when just opened on the
+    /// damaged files, and nothing else should write to it meanwhile.
+    ///
+    /// The WAL's entries are archived first, so afterwards the archive holds
+    /// the whole chain and the WAL is empty. `save` the result to get a
+    /// snapshot again.
+    pub async fn rebuild_from_wal(&self, segments_dir: &str) -> io::Result<WalRebuildReport> {
+        self.check_open_io()?;
+        if self.read_tables().await?.values().any(|table| !table.ephemeral) {
+            return Err(io::Error::other("Can only rebuild from the WAL into an empty database"));
+        }
+        {
+            let mut wal_writer = self.timed(self.wal_writer.write()).await?;
+            if let Some(wal) = self.storage.read(&self.wal_path)? {
+                archive(segments_dir, &wal)?;
+            }
+            wal_writer.truncate()?;
+        }
+
+        let mut report = WalRebuildReport::default();
+        let mut next_lsn = 1;
+        for (first_lsn, name) in segments(segments_dir)? {
+            if first_lsn > next_lsn {
+                report.gaps.push(next_lsn..=first_lsn - 1);
+            }
+            let wal = fs::read(Path::new(segments_dir).join(&name))?;
+            let mut lsn = first_lsn;
+            for (_, entry) in WalEntries::new(&wal) {
+                match entry {
+                    Ok(entry) => {
+                        // Entries that overlap a segment replayed already
+                        // were replayed with it.
+                        if lsn >= next_lsn {
+                            self.apply_wal_entry(entry).await;
+                            report.first_lsn.get_or_insert(lsn);
+                            report.last_lsn = Some(lsn);
+                            report.entries += 1;
+                        }
+                        lsn += 1;
+                    }
+                    Err(e) => {
+                        report.damaged.push((name.clone(), lsn, e.to_string()));
+                        break;
+                    }
+                }
+            }
+            next_lsn = next_lsn.max(lsn);
+            report.segments += 1;
+        }
+
+        // Replay logged every entry again, but they are all archived.
+        self.wal_writer.write().await.truncate()?;
+        Ok(report)
+    }
+}
@@ -0,0 +1,167 @@
+//! Change notifications.
+//!
+//! `Database::watch` registers a query against a table. Every committed write
+//! to that table is checked against the query and matching changes are sent to
+//! the watcher. Events for a transaction are only sent once it commits, and in
+//! the order the writes were applied.
+
+use crate::subquery::has_subquery;
+use crate::{Database, Query, Row, RowId};
+use std::borrow::Borrow;
+use std::sync::Mutex;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WatchEvent {
+    Insert(Row),
+    /// Sent when either the old or the new row matches the query, so watchers
+    /// also learn about rows that stop matching.
+    Update { old: Row, new: Row },
+    Delete(Row),
+}
+
+/// Receives the events for one `Database::watch` call. Dropping it
+/// unsubscribes. Events queue up until received, so keep up with them.
+pub struct Watcher {
+    receiver: UnboundedReceiver<(RowId, WatchEvent)>,
+}
+
+impl Watcher {
+    /// Waits for the next event. Returns `None` once the database is closed.
+    pub async fn recv(&mut self) -> Option<WatchEvent> {
+        self.recv_with_row_id().await.map(|(_, event)| event)
+    }
+
+    pub fn try_recv(&mut self) -> Option<WatchEvent> {
+        self.try_recv_with_row_id().map(|(_, event)| event)
+    }
+
+    /// Like `recv`, along with the id of the row the event is about.
+    pub async fn recv_with_row_id(&mut self) -> Option<(RowId, WatchEvent)> {
+        self.receiver.recv().await
+    }
+
+    pub fn try_recv_with_row_id(&mut self) -> Option<(RowId, WatchEvent)> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+struct Subscription {
+    table: String,
+    query: Query,
+    sender: UnboundedSender<(RowId, WatchEvent)>,
+}
+
+// Changes made by a write, with the ids of the rows they were made to,
+// delivered once the write succeeds.
+pub(crate) type Changes = Vec<(String, RowId, WatchEvent)>;
+
+#[derive(Default)]
+pub(crate) struct Watchers {
+    subscriptions: Mutex<Vec<Subscription>>,
+}
+
+impl Watchers {
+    /// Whether any watcher is registered, so writers can skip building events.
+    pub(crate) fn active(&self) -> bool {
+        !self.subscriptions.lock().unwrap().is_empty()
+    }
+
+    pub(crate) fn close(&self) {
+        self.subscriptions.lock().unwrap().clear();
+    }
+}
+
+impl Database {
+    /// Subscribes to inserts, updates and deletes on `table_name` that match
+    /// `query`. Joins, aggregates and traversals can't be watched, nor can
+    /// subqueries, whose results change with another table's writes.
+    pub async fn watch(&self, table_name: &str, query: Query) -> Result<Watcher, String> {
+        self.check_open()?;
+        if matches!(query, Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_) | Query::Sample(_)) {
+            return Err("Only filter queries can be watched".to_string());
+        }
+        if has_subquery(&query) {
+            return Err("Queries with subqueries can't be watched".to_string());
+        }
+        match self.read_tables().await.map_err(|e| e.to_string())?.get(table_name) {
+            Some(table) => self.check_query(&query, &table.columns)?,
+            None => return Err(format!("Table {} not found", table_name)),
+        }
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.watchers.subscriptions.lock().unwrap().push(Subscription {
+            table: table_name.to_string(),
+            query,
+            sender,
+        });
+        Ok(Watcher { receiver })
+    }
+
+    pub(crate) fn notify(&self, changes: Changes) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut subscriptions = self.watchers.subscriptions.lock().unwrap();
+        subscriptions.retain(|s| !s.sender.is_closed());
+        for (table, id, event) in changes {
+            for subscription in subscriptions.iter().filter(|s| s.table == table) {
+                // Watched queries have no subqueries, so matching can't fail.
+                let matching = |row| self.matches_query(row, &subscription.query).is_ok_and(|m| m);
+                let matches = match &event {
+                    WatchEvent::Insert(row) | WatchEvent::Delete(row) => matching(row),
+                    WatchEvent::Update { old, new } => matching(old) || matching(new),
+                };
+                if matches {
+                    let _ = subscription.sender.send((id, event.clone()));
+                }
+            }
+        }
+    }
+
+    /// Whether `row` matches `query`. Only a subquery can fail.
+    pub(crate) fn matches_query(&self, row: &Row, query: &Query) -> Result<bool, String> {
+        Ok(match query {
+            Query::MatchAll => true,
+            Query::Condition(condition) => self.evaluate_condition(row, condition),
+            Query::Compare(comparison) => self.evaluate_comparison(row, comparison),
+            Query::And(queries) => {
+                for q in queries {
+                    if !self.matches_query(row, q)? {
+                        return Ok(false);
+                    }
+                }
+                true
+            }
+            Query::Or(queries) => {
+                for q in queries {
+                    if self.matches_query(row, q)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            Query::InSubquery {
+                column,
+                table,
+                subquery,
+                select_column,
+            } => {
+                let values = self.subquery_values(table, subquery, select_column)?;
+                row.get(column).is_some_and(|value| values.contains(value))
+            }
+            Query::Join(_) | Query::Aggregate(_) | Query::Traverse(_) | Query::Sample(_) => false,
+        })
+    }
+
+    /// Keeps the rows matching `query`.
+    pub(crate) fn retain_matching<R: Borrow<Row>>(&self, rows: &mut Vec<R>, query: &Query) -> Result<(), String> {
+        let mut kept = Vec::with_capacity(rows.len());
+        for row in rows.drain(..) {
+            if self.matches_query(row.borrow(), query)? {
+                kept.push(row);
+            }
+        }
+        *rows = kept;
+        Ok(())
+    }
+}
@@ -1,3 +1,38 @@
+// Fixtures shared by the test modules. Databases are kept in memory, so
+// tests leave no files behind and can run side by side.
+#[cfg(test)]
+mod fixtures {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use zapdb::{Database, DatabaseConfig, MemoryStorage, StorageBackend, Value};
+
+    /// The name of every fixture database's WAL in its storage.
+    pub(crate) const WAL: &str = "zapdb.wal";
+
+    pub(crate) fn database() -> Database {
+        Database::in_memory([0; 32])
+    }
+
+    /// `database` with `config`, whose storage is replaced.
+    pub(crate) fn database_with(config: DatabaseConfig) -> Database {
+        reopen_with(Arc::new(MemoryStorage::new()), config)
+    }
+
+    /// A new database on `storage`, from another's `storage()`, as though
+    /// that one had been reopened. `load` brings back its tables.
+    pub(crate) fn reopen(storage: Arc<dyn StorageBackend>) -> Database {
+        reopen_with(storage, DatabaseConfig::default())
+    }
+
+    pub(crate) fn reopen_with(storage: Arc<dyn StorageBackend>, config: DatabaseConfig) -> Database {
+        Database::with_config([0; 32], WAL, DatabaseConfig { storage: Some(storage), ..config })
+    }
+
+    /// A row of integer columns.
+    pub(crate) fn row(pairs: &[(&str, i64)]) -> HashMap<String, Value> {
+        pairs.iter().map(|(column, value)| (column.to_string(), Value::Integer(*value))).collect()
+    }
+}
 #[cfg(test)]
 mod test_db;
 #[cfg(test)]
@@ -8,3 +43,153 @@ mod test_joins;
 mod test_new_data_types;
 #[cfg(test)]
 mod test_transactions;
+#[cfg(test)]
+mod test_typed;
+#[cfg(test)]
+mod test_lookup;
+#[cfg(test)]
+mod test_pagination;
+#[cfg(test)]
+mod test_protocol;
+#[cfg(test)]
+mod test_shutdown;
+#[cfg(test)]
+mod test_ephemeral;
+#[cfg(test)]
+mod test_bulk_load;
+#[cfg(test)]
+mod test_memory;
+#[cfg(test)]
+mod test_snapshot_reads;
+#[cfg(test)]
+mod test_dictionary;
+#[cfg(test)]
+mod test_watch;
+#[cfg(test)]
+mod test_materialized_views;
+#[cfg(test)]
+mod test_views;
+#[cfg(test)]
+mod test_scheduler;
+#[cfg(test)]
+mod test_history;
+#[cfg(test)]
+mod test_undo;
+#[cfg(test)]
+mod test_coercion;
+#[cfg(test)]
+mod test_numeric_comparison;
+#[cfg(test)]
+mod test_functions;
+#[cfg(test)]
+mod test_storage;
+#[cfg(all(test, feature = "ffi"))]
+mod test_ffi;
+#[cfg(all(test, feature = "datafusion"))]
+mod test_datafusion;
+#[cfg(test)]
+mod test_codec;
+#[cfg(all(test, feature = "server"))]
+mod test_flight;
+#[cfg(test)]
+mod test_traverse;
+#[cfg(test)]
+mod test_sequences;
+#[cfg(test)]
+mod test_lock;
+#[cfg(test)]
+mod test_recovery;
+#[cfg(test)]
+mod test_limits;
+#[cfg(test)]
+mod test_admission;
+#[cfg(test)]
+mod test_tenants;
+#[cfg(test)]
+mod test_stats;
+#[cfg(test)]
+mod test_row_ids;
+#[cfg(test)]
+mod test_unknown_columns;
+#[cfg(test)]
+mod test_increment;
+#[cfg(test)]
+mod test_partitions;
+#[cfg(test)]
+mod test_sample;
+#[cfg(test)]
+mod test_bundle;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(test)]
+mod test_query_macro;
+#[cfg(test)]
+mod test_pool;
+#[cfg(test)]
+mod test_timeout;
+#[cfg(test)]
+mod test_batch;
+#[cfg(test)]
+mod test_cdc;
+#[cfg(test)]
+mod test_import;
+#[cfg(test)]
+mod test_text_index;
+#[cfg(test)]
+mod test_rollups;
+#[cfg(test)]
+mod test_diff;
+#[cfg(test)]
+mod test_wal_archive;
+#[cfg(test)]
+mod test_compression;
+#[cfg(test)]
+mod test_conflict;
+#[cfg(test)]
+mod test_constraints;
+#[cfg(test)]
+mod test_foreign_keys;
+#[cfg(test)]
+mod test_dependencies;
+#[cfg(test)]
+mod test_time_series;
+#[cfg(test)]
+mod test_join_columns;
+#[cfg(test)]
+mod test_select_options;
+#[cfg(test)]
+mod test_result_limits;
+#[cfg(test)]
+mod test_fork;
+#[cfg(test)]
+mod test_sort_merge_join;
+#[cfg(test)]
+mod test_sql;
+#[cfg(test)]
+mod test_snapshot_encryption;
+#[cfg(test)]
+mod test_query_builder;
+#[cfg(test)]
+mod test_replication;
+#[cfg(test)]
+mod test_prepared;
+#[cfg(test)]
+mod test_schema_file;
+#[cfg(test)]
+mod test_permissions;
+#[cfg(test)]
+mod test_ordered_index;
+#[cfg(test)]
+mod test_savepoints;
+#[cfg(test)]
+mod test_snapshot_roots;
+#[cfg(test)]
+mod test_snapshot_compression;
+#[cfg(test)]
+mod test_join_chain;
+#[cfg(test)]
+mod test_latency;
+#[cfg(test)]
+mod test_estimate;
+#[cfg(test)]
+mod test_subquery;
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{AdmissionConfig, Busy, Column, DataType, Database, DatabaseConfig, Query, Value, WriteError};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn event(id: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row
+    }
+
+    async fn open(wal_path: &str, admission: AdmissionConfig) -> Arc<Database> {
+        let _ = fs::remove_file(wal_path);
+        let config = DatabaseConfig {
+            admission: Some(admission),
+            ..Default::default()
+        };
+        let db = Database::with_config([0; 32], wal_path, config);
+        for table in ["events", "other"] {
+            db.create_table(
+                table.to_string(),
+                vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+            )
+            .await
+            .unwrap();
+        }
+        Arc::new(db)
+    }
+
+    #[tokio::test]
+    async fn test_queue_full() {
+        let wal_path = "test_admission_queue.wal";
+        let db = open(
+            wal_path,
+            AdmissionConfig {
+                max_concurrent_writes: 1,
+                max_queued_writes: 1,
+                ..Default::default()
+            },
+        )
+        .await;
+
+        // Holding the table lock stalls the write that has the permit, so the
+        // next one queues and the one after that is turned away.
+        let lock = db.tables.write().await;
+        let running = tokio::spawn({
+            let db = db.clone();
+            async move { db.insert("events", event(1)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let queued = tokio::spawn({
+            let db = db.clone();
+            async move { db.insert("events", event(2)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let error = db.delete("other", &Query::MatchAll).await.unwrap_err();
+        assert_eq!(error, WriteError::Busy(Busy::QueueFull { limit: 1 }));
+        assert!(error.is_retryable());
+
+        drop(lock);
+        running.await.unwrap().unwrap();
+        queued.await.unwrap().unwrap();
+        assert_eq!(db.count("events", &Query::MatchAll).await.unwrap(), 2);
+        db.insert("events", event(3)).await.unwrap();
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_table_rate() {
+        let wal_path = "test_admission_rate.wal";
+        let db = open(
+            wal_path,
+            AdmissionConfig {
+                max_concurrent_writes: 4,
+                table_rates: HashMap::from([("events".to_string(), 2)]),
+                ..Default::default()
+            },
+        )
+        .await;
+
+        db.insert("events", event(1)).await.unwrap();
+        db.insert("events", event(2)).await.unwrap();
+        let retry_after = match db.insert("events", event(3)).await {
+            Err(WriteError::Busy(Busy::RateLimited { table, retry_after })) => {
+                assert_eq!(table, "events");
+                assert!(retry_after <= Duration::from_millis(500));
+                retry_after
+            }
+            other => panic!("expected RateLimited, got {:?}", other),
+        };
+        // Transactions touching the table are limited too; other tables aren't.
+        let mut transaction = zapdb::begin_transaction();
+        transaction.insert("events".to_string(), event(3));
+        assert!(db.commit(transaction).await.unwrap_err().is_retryable());
+        for i in 0..5 {
+            db.insert("other", event(i)).await.unwrap();
+        }
+
+        tokio::time::sleep(retry_after).await;
+        db.insert("events", event(3)).await.unwrap();
+        assert_eq!(db.count("events", &Query::MatchAll).await.unwrap(), 3);
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
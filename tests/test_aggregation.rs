@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use zapdb::{create_pool, PooledConnection, Column, DataType, Value, Query, AggregateQuery, AggregateFunction};
+    use zapdb::{create_pool, Database, PooledConnection, Column, DataType, Value, Query, AggregateQuery, AggregateFunction};
     use std::collections::HashMap;
 
     async fn setup_db() -> PooledConnection {
@@ -41,11 +41,7 @@ mod tests {
     #[tokio::test]
     async fn test_count() {
         let db = setup_db().await;
-        let query = Query::Aggregate(AggregateQuery {
-            function: AggregateFunction::Count,
-            column: "id".to_string(),
-            filter: None,
-        });
+        let query = Query::Aggregate(AggregateQuery::new(AggregateFunction::Count, "id"));
         let (result, _) = db.select("employees", &query).await.unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].get("result"), Some(&Value::Integer(3)));
@@ -54,11 +50,7 @@ mod tests {
     #[tokio::test]
     async fn test_sum() {
         let db = setup_db().await;
-        let query = Query::Aggregate(AggregateQuery {
-            function: AggregateFunction::Sum,
-            column: "salary".to_string(),
-            filter: None,
-        });
+        let query = Query::Aggregate(AggregateQuery::new(AggregateFunction::Sum, "salary"));
         let (result, _) = db.select("employees", &query).await.unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].get("result"), Some(&Value::Float(180000.0)));
@@ -67,11 +59,7 @@ mod tests {
     #[tokio::test]
     async fn test_avg() {
         let db = setup_db().await;
-        let query = Query::Aggregate(AggregateQuery {
-            function: AggregateFunction::Avg,
-            column: "salary".to_string(),
-            filter: None,
-        });
+        let query = Query::Aggregate(AggregateQuery::new(AggregateFunction::Avg, "salary"));
         let (result, _) = db.select("employees", &query).await.unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].get("result"), Some(&Value::Float(60000.0)));
@@ -80,11 +68,7 @@ mod tests {
     #[tokio::test]
     async fn test_min() {
         let db = setup_db().await;
-        let query = Query::Aggregate(AggregateQuery {
-            function: AggregateFunction::Min,
-            column: "age".to_string(),
-            filter: None,
-        });
+        let query = Query::Aggregate(AggregateQuery::new(AggregateFunction::Min, "age"));
         let (result, _) = db.select("employees", &query).await.unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].get("result"), Some(&Value::Integer(30)));
@@ -93,11 +77,7 @@ mod tests {
     #[tokio::test]
     async fn test_max() {
         let db = setup_db().await;
-        let query = Query::Aggregate(AggregateQuery {
-            function: AggregateFunction::Max,
-            column: "age".to_string(),
-            filter: None,
-        });
+        let query = Query::Aggregate(AggregateQuery::new(AggregateFunction::Max, "age"));
         let (result, _) = db.select("employees", &query).await.unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].get("result"), Some(&Value::Integer(40)));
@@ -111,13 +91,115 @@ mod tests {
             operator: zapdb::Operator::Eq,
             value: Value::Integer(30),
         });
-        let query = Query::Aggregate(AggregateQuery {
-            function: AggregateFunction::Count,
-            column: "id".to_string(),
-            filter: Some(Box::new(filter)),
-        });
+        let query = Query::Aggregate(AggregateQuery::new(AggregateFunction::Count, "id").filtered(filter));
         let (result, _) = db.select("employees", &query).await.unwrap();
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].get("result"), Some(&Value::Integer(2)));
     }
+
+    #[tokio::test]
+    async fn test_count_distinct() {
+        let db = setup_db().await;
+        for function in [AggregateFunction::CountDistinct, AggregateFunction::ApproxCountDistinct] {
+            let query = Query::Aggregate(AggregateQuery::new(function, "age"));
+            let (result, _) = db.select("employees", &query).await.unwrap();
+            assert_eq!(result[0].get("result"), Some(&Value::Integer(2)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_approx_count_distinct_large() {
+        let db = Database::new([0; 32], "test_approx_count_distinct.wal");
+        db.create_ephemeral_table(
+            "visits".to_string(),
+            vec![Column::new("visitor".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        db.with_deferred_maintenance(async |db: &Database| {
+            for i in 0..50_000 {
+                let mut row = HashMap::new();
+                row.insert("visitor".to_string(), Value::Integer(i % 20_000));
+                db.insert("visits", row).await.unwrap();
+            }
+        })
+        .await;
+
+        let count = |function| Query::Aggregate(AggregateQuery::new(function, "visitor"));
+        let (exact, _) = db.select("visits", &count(AggregateFunction::CountDistinct)).await.unwrap();
+        assert_eq!(exact[0].get("result"), Some(&Value::Integer(20_000)));
+        let (approx, _) = db.select("visits", &count(AggregateFunction::ApproxCountDistinct)).await.unwrap();
+        let approx = approx[0].get_i64("result").unwrap();
+        assert!((approx - 20_000).abs() < 600, "estimated {}", approx);
+
+        let _ = std::fs::remove_file("test_approx_count_distinct.wal");
+    }
+
+    #[tokio::test]
+    async fn test_group_by() {
+        let wal_path = "test_group_by.wal";
+        let _ = std::fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "staff".to_string(),
+            vec![
+                Column::new("department".to_string(), DataType::String, vec![]),
+                Column::new("level".to_string(), DataType::Integer, vec![]),
+                Column::new("salary".to_string(), DataType::Float, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        let staff = [
+            ("ops", 1, Value::Float(40.0)),
+            ("dev", 1, Value::Float(50.0)),
+            ("ops", 2, Value::Float(60.0)),
+            ("dev", 1, Value::Float(70.0)),
+            ("ops", 1, Value::Null),
+        ];
+        for (department, level, salary) in staff {
+            let mut row = HashMap::new();
+            row.insert("department".to_string(), Value::String(department.to_string()));
+            row.insert("level".to_string(), Value::Integer(level));
+            row.insert("salary".to_string(), salary);
+            db.insert("staff", row).await.unwrap();
+        }
+        let grouped =
+            |function, group_by: &[&str]| Query::Aggregate(AggregateQuery::new(function, "salary").grouped_by(group_by));
+
+        let (rows, _) = db.select("staff", &grouped(AggregateFunction::Avg, &["department"])).await.unwrap();
+        let averages: Vec<(&str, f64)> = rows
+            .iter()
+            .map(|r| (r.get_str("department").unwrap(), r.get_f64("result").unwrap()))
+            .collect();
+        assert_eq!(averages, vec![("ops", 50.0), ("dev", 60.0)]);
+
+        let (rows, _) = db
+            .select("staff", &grouped(AggregateFunction::Count, &["department", "level"]))
+            .await
+            .unwrap();
+        let counts: Vec<(&str, i64, i64)> = rows
+            .iter()
+            .map(|r| {
+                (r.get_str("department").unwrap(), r.get_i64("level").unwrap(), r.get_i64("result").unwrap())
+            })
+            .collect();
+        assert_eq!(counts, vec![("ops", 1, 2), ("dev", 1, 2), ("ops", 2, 1)]);
+
+        let mut filtered = grouped(AggregateFunction::Max, &["level"]);
+        if let Query::Aggregate(aggregate) = &mut filtered {
+            aggregate.filter = Some(Box::new(zapdb::query!(department == "ops")));
+        }
+        let (rows, _) = db.select("staff", &filtered).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].get_f64("result"), Some(40.0));
+
+        assert!(db.select("staff", &grouped(AggregateFunction::Sum, &["team"])).await.is_err());
+        assert!(db
+            .create_materialized_view("by_department", "staff", grouped(AggregateFunction::Sum, &["department"]))
+            .await
+            .is_err());
+
+        let _ = std::fs::remove_file(wal_path);
+    }
 }
@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{query, Column, Constraint, DataType, Database, Query, Value, WriteBatch};
+
+    fn row(id: i64, name: &str) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("name".to_string(), Value::String(name.to_string())),
+        ])
+    }
+
+    async fn create_users(db: &Database) {
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_flush_applies_each_write_and_logs_them() {
+        let wal_path = "test_flush_applies_each_write_and_logs_them.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        create_users(&db).await;
+
+        let mut batch = WriteBatch::new();
+        for id in 1..=4 {
+            batch.insert("users", row(id, "user"));
+        }
+        batch.insert("users", row(1, "duplicate"));
+        batch.update("users", query!(id <= 2), |row| {
+            row.insert("name".to_string(), Value::String("early".to_string()));
+        });
+        batch.delete("users", query!(id == 4));
+        assert_eq!(batch.len(), 7);
+
+        let results = db.flush(&mut batch).await.unwrap();
+        assert!(batch.is_empty());
+        assert_eq!(results.len(), 7);
+        assert!(results[..4].iter().all(|r| r == &Ok(1)));
+        // The duplicate fails on its own; the writes after it still apply.
+        assert!(results[4].is_err());
+        assert_eq!(results[5], Ok(2));
+        assert_eq!(results[6], Ok(1));
+
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 3);
+        assert_eq!(db.count("users", &query!(name == "early")).await.unwrap(), 2);
+        // Indexes are brought up to date once the batch is done.
+        assert_eq!(db.count("users", &query!(id == 3)).await.unwrap(), 1);
+        assert!(db.flush(&mut batch).await.unwrap().is_empty());
+
+        let reopened = Database::new([0; 32], wal_path);
+        reopened.load("missing.zap").await.unwrap();
+        assert_eq!(reopened.count("users", &Query::MatchAll).await.unwrap(), 3);
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{create_pool, Column, DataType, Value, Query, Condition, Operator, Database};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::time::Duration;
+
+    fn age_is(age: i64) -> Query {
+        Query::Condition(Condition {
+            column: "age".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(age),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_deferred_maintenance() {
+        let wal_path = "test_deferred_maintenance.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_index("users", "age").await.unwrap();
+
+        let inserted = db
+            .with_deferred_maintenance(async |db: &Database| {
+                for i in 0..500 {
+                    let mut row = HashMap::new();
+                    row.insert("id".to_string(), Value::Integer(i));
+                    row.insert("age".to_string(), Value::Integer(i % 50));
+                    db.insert("users", row).await.unwrap();
+                }
+                db.delete("users", &age_is(0)).await.unwrap();
+                // The age index is stale here, so this must fall back to a scan.
+                db.count("users", &age_is(1)).await.unwrap()
+            })
+            .await;
+        assert_eq!(inserted, 10);
+
+        assert!(db.verify_integrity().await);
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 490);
+        assert_eq!(db.count("users", &age_is(0)).await.unwrap(), 0);
+        let (rows, _) = db.select("users", &age_is(49)).await.unwrap();
+        assert_eq!(rows.len(), 10);
+        assert!(rows.iter().all(|r| r.get_i64("age") == Some(49)));
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_deferred_maintenance_ends_when_cancelled() {
+        let db = Database::in_memory([0; 32]);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_index("users", "age").await.unwrap();
+
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(50),
+            db.with_deferred_maintenance(async |db: &Database| {
+                let mut row = HashMap::new();
+                row.insert("id".to_string(), Value::Integer(1));
+                row.insert("age".to_string(), Value::Integer(30));
+                db.insert("users", row).await.unwrap();
+                std::future::pending::<()>().await;
+            }),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        // Reads are back on the published snapshot, which has the write, so
+        // a held table lock doesn't hold them up.
+        let locked = db.tables.write().await;
+        let count = tokio::time::timeout(Duration::from_secs(1), db.count("users", &age_is(30))).await;
+        assert_eq!(count.expect("the read waited on the lock").unwrap(), 1);
+        drop(locked);
+        assert!(db.verify_integrity().await);
+    }
+
+    #[tokio::test]
+    async fn test_deferred_maintenance_finished_by_next_write() {
+        let db = Database::in_memory([0; 32]);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        let insert = async |db: &Database, id: i64| {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("age".to_string(), Value::Integer(30));
+            db.insert("users", row).await.unwrap();
+        };
+
+        let mut deferred = Box::pin(db.with_deferred_maintenance(async |db: &Database| {
+            insert(db, 1).await;
+            std::future::pending::<()>().await;
+        }));
+        assert!(tokio::time::timeout(Duration::from_millis(50), &mut deferred).await.is_err());
+        // Dropped while the table lock is held elsewhere, so the mode can't
+        // be finished on the spot.
+        let locked = db.tables.write().await;
+        drop(deferred);
+        drop(locked);
+        assert_eq!(db.count("users", &age_is(30)).await.unwrap(), 1);
+
+        insert(&db, 2).await;
+        let locked = db.tables.write().await;
+        let count = tokio::time::timeout(Duration::from_secs(1), db.count("users", &age_is(30))).await;
+        assert_eq!(count.expect("the read waited on the lock").unwrap(), 2);
+        drop(locked);
+        assert!(db.verify_integrity().await);
+    }
+}
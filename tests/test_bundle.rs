@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use zapdb::{Column, Condition, DataType, Database, DatabaseConfig, MemoryStorage, Operator, Query, StorageBackend, Value};
+
+    fn open(storage: &Arc<MemoryStorage>, wal_path: &str) -> Database {
+        let config = DatabaseConfig {
+            storage: Some(storage.clone()),
+            ..Default::default()
+        };
+        Database::with_config([0; 32], wal_path, config)
+    }
+
+    async fn create(db: &Database, table: &str, count: i64) {
+        db.create_table(
+            table.to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        for id in 0..count {
+            db.insert(table, HashMap::from([("id".to_string(), Value::Integer(id))]))
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bundle_round_trip() {
+        let storage = Arc::new(MemoryStorage::new());
+        let source = open(&storage, "source.wal");
+        create(&source, "users", 10).await;
+        create(&source, "orders", 3).await;
+        create(&source, "secrets", 1).await;
+        let filters = HashMap::from([(
+            "users".to_string(),
+            Query::Condition(Condition {
+                column: "id".to_string(),
+                operator: Operator::Lt,
+                value: Value::Integer(4),
+            }),
+        )]);
+        let key = [9; 32];
+        let manifest = source
+            .export_bundle(&["users", "orders"], &filters, "out.bundle", &key)
+            .await
+            .unwrap();
+        assert_eq!(
+            manifest.tables.iter().map(|t| (t.name.as_str(), t.rows)).collect::<Vec<_>>(),
+            vec![("users", 4), ("orders", 3)]
+        );
+
+        let target = open(&storage, "target.wal");
+        assert!(target.import_bundle("out.bundle", &[0; 32]).await.is_err());
+        assert_eq!(target.import_bundle("out.bundle", &key).await.unwrap(), manifest);
+        let mut names = target.table_names().await;
+        names.sort();
+        assert_eq!(names, vec!["orders".to_string(), "users".to_string()]);
+        assert_eq!(target.count("users", &Query::MatchAll).await.unwrap(), 4);
+
+        // Importing again would overwrite the tables.
+        let err = target.import_bundle("out.bundle", &key).await.unwrap_err();
+        assert_eq!(err.to_string(), "Table users already exists");
+        assert_eq!(target.count("users", &Query::MatchAll).await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_bundle_rejects_a_tampered_file() {
+        let storage = Arc::new(MemoryStorage::new());
+        let source = open(&storage, "source.wal");
+        create(&source, "users", 2).await;
+        source
+            .export_bundle(&["users"], &HashMap::new(), "out.bundle", &[9; 32])
+            .await
+            .unwrap();
+        let mut bundle = storage.read("out.bundle").unwrap().unwrap();
+        bundle[20] ^= 1;
+        storage.write("out.bundle", &bundle).unwrap();
+
+        let target = open(&storage, "target.wal");
+        assert!(target.import_bundle("out.bundle", &[9; 32]).await.is_err());
+        assert!(target.table_names().await.is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use zapdb::{
+        query, ChangeRecord, ChangeSink, Column, DataType, Database, SinkFuture, SinkOptions, Value,
+        WatchEvent, CHECKPOINT_TABLE,
+    };
+
+    // Collects what it publishes, failing the first `failures` batches.
+    #[derive(Clone, Default)]
+    struct Collect {
+        published: Arc<Mutex<Vec<ChangeRecord>>>,
+        failures: Arc<Mutex<usize>>,
+    }
+
+    impl ChangeSink for Collect {
+        fn publish<'a>(&'a mut self, changes: &'a [ChangeRecord]) -> SinkFuture<'a> {
+            Box::pin(async move {
+                let mut failures = self.failures.lock().unwrap();
+                if *failures > 0 {
+                    *failures -= 1;
+                    return Err("unavailable".to_string());
+                }
+                self.published.lock().unwrap().extend_from_slice(changes);
+                Ok(())
+            })
+        }
+    }
+
+    fn row(id: i64) -> HashMap<String, Value> {
+        HashMap::from([("id".to_string(), Value::Integer(id))])
+    }
+
+    // Waits until the sink has accepted and checkpointed up to `position`.
+    async fn wait_for(db: &Database, name: &str, position: u64) {
+        for _ in 0..200 {
+            if db.sink_status(name).unwrap().position >= position {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("{} never reached position {}", name, position);
+    }
+
+    #[tokio::test]
+    async fn test_sink_retries_and_checkpoints() {
+        let wal_path = "test_sink_retries_and_checkpoints.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Arc::new(Database::new([0; 32], wal_path));
+        db.create_table(
+            "events".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+
+        let sink = Collect {
+            failures: Arc::new(Mutex::new(2)),
+            ..Default::default()
+        };
+        let options = SinkOptions {
+            batch_size: 10,
+            retry_delay: Duration::from_millis(1),
+        };
+        db.attach_sink("audit", "events", sink.clone(), options.clone()).await.unwrap();
+        db.insert("events", row(1)).await.unwrap();
+        db.insert("events", row(2)).await.unwrap();
+        db.delete("events", &query!(id == 1)).await.unwrap();
+        wait_for(&db, "audit", 3).await;
+
+        let published = sink.published.lock().unwrap().clone();
+        assert_eq!(published.iter().map(|c| c.position).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(matches!(published[2].event, WatchEvent::Delete(_)));
+        assert_eq!(published[0].to_json()["after"]["id"], 1);
+        assert_eq!(published[2].to_json()["operation"], "delete");
+
+        let status = db.sink_status("audit").unwrap();
+        assert_eq!(status.failures, 2);
+        assert_eq!(status.last_error.as_deref(), Some("unavailable"));
+        let checkpoint = db.first(CHECKPOINT_TABLE, &query!(sink == "audit")).await.unwrap().unwrap();
+        assert_eq!(checkpoint.get_i64("position"), Some(3));
+        assert!(db.detach_sink("audit"));
+        assert!(db.sink_status("audit").is_none());
+
+        // After a restart, positions carry on from the checkpoint.
+        let reopened = Arc::new(Database::new([0; 32], wal_path));
+        reopened.load("missing.zap").await.unwrap();
+        let sink = Collect::default();
+        reopened.attach_sink("audit", "events", sink.clone(), options).await.unwrap();
+        reopened.insert("events", row(3)).await.unwrap();
+        wait_for(&reopened, "audit", 4).await;
+        assert_eq!(sink.published.lock().unwrap()[0].position, 4);
+
+        reopened.close(None).await.unwrap();
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use std::fs;
+    use uuid::Uuid;
+    use zapdb::{Codec, Column, DataType, Database, DatabaseConfig, Query, Value};
+
+    fn open(wal_path: &str, codec: Codec) -> Database {
+        let config = DatabaseConfig {
+            codec,
+            ..Default::default()
+        };
+        Database::with_config([0; 32], wal_path, config)
+    }
+
+    fn event(id: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("name".to_string(), Value::String(format!("event{}", id)));
+        row.insert("score".to_string(), Value::Float(id as f64 / 2.0));
+        row.insert("at".to_string(), Value::DateTime(Utc::now()));
+        row.insert("ref".to_string(), Value::Uuid(Uuid::new_v4()));
+        row
+    }
+
+    async fn create_events(db: &Database) {
+        db.create_table(
+            "events".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+                Column::new("score".to_string(), DataType::Float, vec![]),
+                Column::new("at".to_string(), DataType::DateTime, vec![]),
+                Column::new("ref".to_string(), DataType::Uuid, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_codecs_round_trip() {
+        for (codec, name) in [
+            (Codec::Bincode, "bincode"),
+            (Codec::Postcard, "postcard"),
+            (Codec::MessagePack, "msgpack"),
+        ] {
+            let wal_path = format!("test_codec_{}.wal", name);
+            let db_path = format!("test_codec_{}.zap", name);
+            let db = open(&wal_path, codec);
+            create_events(&db).await;
+            let rows: Vec<_> = (0..5).map(event).collect();
+            for row in &rows {
+                db.insert("events", row.clone()).await.unwrap();
+            }
+            db.save(&db_path).await.unwrap();
+            db.insert("events", event(5)).await.unwrap();
+
+            // Snapshot plus WAL.
+            let restored = open(&wal_path, codec);
+            restored.load(&db_path).await.unwrap();
+            let (loaded, _) = restored.select("events", &Query::MatchAll).await.unwrap();
+            assert_eq!(loaded.len(), 6, "{:?}", codec);
+            for row in &rows {
+                assert!(loaded.iter().any(|r| r.to_map() == *row), "{:?}", codec);
+            }
+
+            let _ = fs::remove_file(&wal_path);
+            let _ = fs::remove_file(&db_path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_codec_change() {
+        let wal_path = "test_codec_change.wal";
+        let db_path = "test_codec_change.zap";
+        let db = open(wal_path, Codec::Bincode);
+        create_events(&db).await;
+        db.insert("events", event(0)).await.unwrap();
+        db.save(db_path).await.unwrap();
+        db.insert("events", event(1)).await.unwrap();
+
+        // Files written with another codec are still read, and the existing
+        // WAL keeps its codec until the next save.
+        let db = open(wal_path, Codec::Postcard);
+        db.load(db_path).await.unwrap();
+        db.insert("events", event(2)).await.unwrap();
+        assert_eq!(db.count("events", &Query::MatchAll).await.unwrap(), 3);
+
+        db.save(db_path).await.unwrap();
+        db.insert("events", event(3)).await.unwrap();
+        let db = open(wal_path, Codec::MessagePack);
+        db.load(db_path).await.unwrap();
+        assert_eq!(db.count("events", &Query::MatchAll).await.unwrap(), 4);
+
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(db_path);
+    }
+}
@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use chrono::{TimeZone, Utc};
+    use zapdb::{Coercion, Column, DataType, Database, DatabaseConfig, Query, Value};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    const ID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+    async fn setup(coercion: Coercion) -> Database {
+        let config = DatabaseConfig {
+            coercion,
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        db.create_table(
+            "readings".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Uuid, vec![]),
+                Column::new("value".to_string(), DataType::Float, vec![]),
+                Column::new("count".to_string(), DataType::Integer, vec![]),
+                Column::new("at".to_string(), DataType::DateTime, vec![]),
+                Column::new("label".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    fn reading(value: Value, count: Value, label: Value) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::String(ID.to_string()));
+        row.insert("value".to_string(), value);
+        row.insert("count".to_string(), count);
+        row.insert("at".to_string(), Value::String("2024-01-01T12:00:00Z".to_string()));
+        row.insert("label".to_string(), label);
+        row
+    }
+
+    #[test]
+    fn test_coerce_value() {
+        assert_eq!(
+            Value::Integer(5).coerce(&DataType::Float, Coercion::Strict),
+            Value::Integer(5)
+        );
+        assert_eq!(
+            Value::Integer(5).coerce(&DataType::Float, Coercion::Lossless),
+            Value::Float(5.0)
+        );
+        assert_eq!(
+            Value::Integer(i64::MAX).coerce(&DataType::Float, Coercion::Lossless),
+            Value::Integer(i64::MAX)
+        );
+        assert_eq!(
+            Value::Float(2.0).coerce(&DataType::Integer, Coercion::Lossless),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            Value::Float(2.5).coerce(&DataType::Integer, Coercion::Lossless),
+            Value::Float(2.5)
+        );
+        assert_eq!(
+            Value::Float(2.5).coerce(&DataType::Integer, Coercion::Lossy),
+            Value::Integer(2)
+        );
+        assert_eq!(
+            Value::String(" 42 ".to_string()).coerce(&DataType::Integer, Coercion::Lossy),
+            Value::Integer(42)
+        );
+        assert_eq!(
+            Value::Boolean(true).coerce(&DataType::String, Coercion::Lossy),
+            Value::String("true".to_string())
+        );
+        assert_eq!(
+            Value::String("not a uuid".to_string()).coerce(&DataType::Uuid, Coercion::Lossy),
+            Value::String("not a uuid".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_coercion_on_insert() {
+        let strict = setup(Coercion::Strict).await;
+        let row = reading(Value::Integer(5), Value::Integer(1), Value::String("a".to_string()));
+        assert!(strict.insert("readings", row.clone()).await.is_err());
+        drop(strict);
+
+        let db = setup(Coercion::Lossless).await;
+        db.insert("readings", row).await.unwrap();
+        let (rows, _) = db.select("readings", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows[0].get("value"), Some(&Value::Float(5.0)));
+        assert_eq!(rows[0].get("id"), Some(&Value::Uuid(Uuid::parse_str(ID).unwrap())));
+        assert_eq!(
+            rows[0].get("at"),
+            Some(&Value::DateTime(Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap()))
+        );
+
+        // Lossy conversions are still refused.
+        let row = reading(Value::Float(1.0), Value::Float(1.5), Value::String("b".to_string()));
+        assert!(db.insert("readings", row).await.is_err());
+        let row = reading(Value::Float(1.0), Value::Integer(1), Value::Integer(7));
+        assert!(db.insert("readings", row).await.is_err());
+
+        // Updates are coerced too.
+        db.update("readings", &Query::MatchAll, |row| {
+            row.insert("count".to_string(), Value::Float(3.0));
+        })
+        .await
+        .unwrap();
+        let (rows, _) = db.select("readings", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows[0].get("count"), Some(&Value::Integer(3)));
+        drop(db);
+
+        let db = setup(Coercion::Lossy).await;
+        let row = reading(Value::String("1.5".to_string()), Value::Float(1.5), Value::Integer(7));
+        db.insert("readings", row).await.unwrap();
+        let (rows, _) = db.select("readings", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows[0].get("value"), Some(&Value::Float(1.5)));
+        assert_eq!(rows[0].get("count"), Some(&Value::Integer(1)));
+        assert_eq!(rows[0].get_str("label"), Some("7"));
+    }
+}
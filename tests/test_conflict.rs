@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{
+        begin_transaction, query, Column, Constraint, DataType, Database, InsertOutcome, OnConflict, Query, Value,
+        WriteBatch,
+    };
+
+    fn user(id: i64, email: &str, name: &str) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("email".to_string(), Value::String(email.to_string())),
+            ("name".to_string(), Value::String(name.to_string())),
+        ])
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("email".to_string(), DataType::String, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![Constraint::NotNull]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.insert("users", user(1, "alice@example.com", "Alice")).await.unwrap();
+        db.insert("users", user(2, "bob@example.com", "Bob")).await.unwrap();
+        db
+    }
+
+    async fn names(db: &Database) -> Vec<String> {
+        let (rows, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        rows.iter().map(|row| row.get_str("name").unwrap().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_insert_on_conflict_modes() {
+        let db = setup().await;
+
+        let clash = user(1, "carol@example.com", "Carol");
+        assert!(db.insert_on_conflict("users", clash.clone(), OnConflict::Error).await.is_err());
+        let outcome = db.insert_on_conflict("users", clash.clone(), OnConflict::Ignore).await.unwrap();
+        assert_eq!(outcome, InsertOutcome::Ignored);
+        assert_eq!(names(&db).await, vec!["Alice", "Bob"]);
+
+        let outcome = db.insert_on_conflict("users", clash, OnConflict::Replace).await.unwrap();
+        assert_eq!(outcome, InsertOutcome::Replaced(1));
+        assert_eq!(names(&db).await, vec!["Bob", "Carol"]);
+
+        // A row clashing with two rows on different columns replaces both.
+        let outcome = db
+            .insert_on_conflict("users", user(2, "carol@example.com", "Dave"), OnConflict::Replace)
+            .await
+            .unwrap();
+        assert_eq!(outcome, InsertOutcome::Replaced(2));
+        assert_eq!(names(&db).await, vec!["Dave"]);
+
+        let outcome = db
+            .insert_on_conflict("users", user(3, "erin@example.com", "Erin"), OnConflict::Ignore)
+            .await
+            .unwrap();
+        assert_eq!(outcome, InsertOutcome::Inserted);
+
+        // Other constraints still fail, and a failed replace keeps the rows.
+        let mut nameless = user(3, "frank@example.com", "Frank");
+        nameless.insert("name".to_string(), Value::Null);
+        assert!(db.insert_on_conflict("users", nameless, OnConflict::Replace).await.is_err());
+        assert_eq!(names(&db).await, vec!["Dave", "Erin"]);
+        let (rows, _) = db.select("users", &query!(id == 3)).await.unwrap();
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_insert_on_conflict_in_batches_and_transactions() {
+        let db = setup().await;
+
+        let mut batch = WriteBatch::new();
+        batch.insert_on_conflict("users", user(1, "alice@example.com", "Alice"), OnConflict::Ignore);
+        batch.insert_on_conflict("users", user(3, "carol@example.com", "Carol"), OnConflict::Ignore);
+        batch.insert_on_conflict("users", user(2, "bob@example.com", "Robert"), OnConflict::Replace);
+        let results: Vec<usize> = db.flush(&mut batch).await.unwrap().into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, vec![0, 1, 1]);
+        assert_eq!(names(&db).await, vec!["Alice", "Carol", "Robert"]);
+
+        let mut transaction = begin_transaction();
+        transaction.insert_on_conflict("users".to_string(), user(1, "alice@example.com", "Alicia"), OnConflict::Replace);
+        transaction.insert("users".to_string(), user(3, "carol@example.com", "Carol"));
+        assert!(db.commit(transaction).await.is_err());
+        assert_eq!(names(&db).await, vec!["Alice", "Carol", "Robert"]);
+    }
+
+    #[tokio::test]
+    async fn test_insert_on_conflict_is_replayed_from_the_wal() {
+        let db = setup().await;
+        db.insert_on_conflict("users", user(1, "alicia@example.com", "Alicia"), OnConflict::Replace)
+            .await
+            .unwrap();
+        db.insert_on_conflict("users", user(2, "robert@example.com", "Robert"), OnConflict::Ignore)
+            .await
+            .unwrap();
+
+        let recovered = fixtures::reopen(db.storage());
+        recovered.load("test_insert_on_conflict_replayed.zap").await.unwrap();
+        assert_eq!(names(&recovered).await, vec!["Bob", "Alicia"]);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{query, Column, Constraint, ConstraintError, DataType, Database, Value, WriteError};
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "teams".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique])],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("email".to_string(), DataType::String, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![Constraint::NotNull]),
+                Column::new(
+                    "team".to_string(),
+                    DataType::Integer,
+                    vec![Constraint::ForeignKey { table: "teams".to_string(), column: "id".to_string() }],
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        db.insert("teams", HashMap::from([("id".to_string(), Value::Integer(1))])).await.unwrap();
+        db.insert("users", user("alice@example.com", Some("Alice"), 1)).await.unwrap();
+        db.insert("users", user("bob@example.com", Some("Bob"), 1)).await.unwrap();
+        db
+    }
+
+    fn user(email: &str, name: Option<&str>, team: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::from([
+            ("email".to_string(), Value::String(email.to_string())),
+            ("team".to_string(), Value::Integer(team)),
+        ]);
+        if let Some(name) = name {
+            row.insert("name".to_string(), Value::String(name.to_string()));
+        }
+        row
+    }
+
+    fn constraint_error(result: Result<impl std::fmt::Debug, WriteError>) -> ConstraintError {
+        match result {
+            Err(WriteError::Constraint(e)) => *e,
+            other => panic!("expected a constraint error, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_constraint_errors_name_the_offending_value() {
+        let db = setup().await;
+
+        let e = constraint_error(db.insert("users", user("alice@example.com", Some("Al"), 1)).await);
+        assert_eq!(
+            e,
+            ConstraintError {
+                table: "users".to_string(),
+                column: "email".to_string(),
+                constraint: Constraint::Unique,
+                value: Value::String("alice@example.com".to_string()),
+            }
+        );
+        assert_eq!(e.to_string(), "Column email must be unique");
+
+        let e = constraint_error(db.insert("users", user("carol@example.com", None, 1)).await);
+        assert_eq!((e.column.as_str(), &e.constraint, &e.value), ("name", &Constraint::NotNull, &Value::Null));
+
+        let e = constraint_error(db.insert("users", user("carol@example.com", Some("Carol"), 7)).await);
+        assert_eq!(e.column, "team");
+        assert_eq!(e.value, Value::Integer(7));
+        assert_eq!(e.constraint, Constraint::ForeignKey { table: "teams".to_string(), column: "id".to_string() });
+
+        // Updates report the same way.
+        let e = constraint_error(
+            db.update("users", &query!(name == "Bob"), |row| {
+                row.insert("email".to_string(), Value::String("alice@example.com".to_string()));
+            })
+            .await,
+        );
+        assert_eq!((e.table.as_str(), e.column.as_str()), ("users", "email"));
+        assert_eq!(e.value, Value::String("alice@example.com".to_string()));
+    }
+}
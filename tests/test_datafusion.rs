@@ -0,0 +1,64 @@
+#[cfg(all(test, feature = "datafusion"))]
+mod tests {
+    use datafusion::arrow::array::{Array, Int64Array, StringArray};
+    use datafusion::prelude::SessionContext;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use zapdb::{Column, Constraint, DataType, Database, Value};
+
+    #[tokio::test]
+    async fn test_datafusion_sql() {
+        let db = Arc::new(Database::in_memory([0; 32]));
+        db.create_table(
+            "orders".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("customer".to_string(), DataType::String, vec![]),
+                Column::new("total".to_string(), DataType::Float, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, customer, total) in [(1, "alice", 10.0), (2, "bob", 5.5), (3, "alice", 2.5), (4, "carol", 7.0)] {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("customer".to_string(), Value::String(customer.to_string()));
+            row.insert("total".to_string(), Value::Float(total));
+            db.insert("orders", row).await.unwrap();
+        }
+
+        let ctx = SessionContext::new();
+        ctx.register_table("orders", Arc::new(db.table_provider("orders").await.unwrap()))
+            .unwrap();
+
+        let batches = ctx
+            .sql("SELECT customer, COUNT(*) AS n FROM orders WHERE id > 1 GROUP BY customer ORDER BY customer")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let batch = &batches[0];
+        let customers = batch.column(0).as_any().downcast_ref::<StringArray>().unwrap();
+        let counts = batch.column(1).as_any().downcast_ref::<Int64Array>().unwrap();
+        let result: Vec<(&str, i64)> = (0..batch.num_rows()).map(|i| (customers.value(i), counts.value(i))).collect();
+        assert_eq!(result, vec![("alice", 1), ("bob", 1), ("carol", 1)]);
+
+        // Filters zapdb can't evaluate are still applied by DataFusion.
+        let batches = ctx
+            .sql("SELECT id FROM orders WHERE customer = 'alice' AND total * 2 > 10 ORDER BY id")
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        let ids = batches[0].column(0).as_any().downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(ids.values().to_vec(), vec![1]);
+        assert_eq!(ids.null_count(), 0);
+
+        // Scans see later writes.
+        db.delete("orders", &zapdb::Query::MatchAll).await.unwrap();
+        let batches = ctx.sql("SELECT * FROM orders").await.unwrap().collect().await.unwrap();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 0);
+    }
+}
@@ -3,6 +3,7 @@ mod tests {
     use zapdb::{create_pool, Column, DataType, Value, Query, Constraint};
     use std::collections::HashMap;
     use std::fs;
+    use std::sync::Arc;
 
     #[tokio::test]
     async fn test_not_null_constraint() {
@@ -77,6 +78,50 @@ mod tests {
         assert!(db.insert("posts", post_row).await.is_err());
     }
 
+    #[tokio::test]
+    async fn test_create_table_checks_schema() {
+        let wal_path = "test_create_table_checks_schema.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique])],
+        )
+        .await
+        .unwrap();
+
+        let fk = |table: &str, column: &str| Constraint::ForeignKey { table: table.to_string(), column: column.to_string() };
+        let columns = vec![
+            Column::new("id".to_string(), DataType::Integer, vec![Constraint::NotNull, Constraint::NotNull]),
+            Column::new("id".to_string(), DataType::String, vec![]),
+            Column::new("author".to_string(), DataType::String, vec![fk("users", "id")]),
+            Column::new("editor".to_string(), DataType::Integer, vec![fk("users", "name")]),
+            Column::new("group".to_string(), DataType::Integer, vec![fk("groups", "id")]),
+            Column::new("parent".to_string(), DataType::Integer, vec![fk("posts", "parent")]),
+        ];
+        let error = db.create_table("posts".to_string(), columns).await.unwrap_err();
+        for problem in [
+            "column id has the same constraint twice",
+            "column id is declared more than once",
+            "column author is String but references users.id, which is Integer",
+            "column editor references users.name, which doesn't exist",
+            "column group references table groups, which doesn't exist",
+            "column parent references itself",
+        ] {
+            assert!(error.contains(problem), "{} is missing {:?}", error, problem);
+        }
+        assert!(!db.table_names().await.contains(&"posts".to_string()));
+
+        // A table may refer to its own columns.
+        let columns = vec![
+            Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+            Column::new("parent".to_string(), DataType::Integer, vec![fk("posts", "id")]),
+        ];
+        db.create_table("posts".to_string(), columns).await.unwrap();
+
+        let _ = fs::remove_file(wal_path);
+    }
+
     #[tokio::test]
     async fn test_save_load_with_compression_and_integrity_check() {
         let key = [0u8; 32];
@@ -169,4 +214,79 @@ mod tests {
         let _ = fs::remove_file(db_path);
         let _ = fs::remove_file(wal_path);
     }
+
+    #[tokio::test]
+    async fn test_select_shared_rows() {
+        let wal_path = "test_select_shared.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(1));
+        row.insert("age".to_string(), Value::Integer(30));
+        db.insert("users", row).await.unwrap();
+
+        let (first, _) = db.select_shared("users", &Query::MatchAll).await.unwrap();
+        let (second, _) = db.select_shared("users", &Query::MatchAll).await.unwrap();
+        assert!(Arc::ptr_eq(&first[0], &second[0]));
+
+        db.update("users", &Query::MatchAll, |row| {
+            row.insert("age".to_string(), Value::Integer(31));
+        })
+        .await
+        .unwrap();
+
+        // Rows handed out before the update keep their old contents.
+        assert_eq!(first[0].get("age"), Some(&Value::Integer(30)));
+        let (users, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(users[0].get("age"), Some(&Value::Integer(31)));
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_rows_share_schema() {
+        let db_path = "test_rows_share_schema.zap";
+        let wal_path = "test_rows_share_schema.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        for i in 0..3 {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(i));
+            row.insert("name".to_string(), Value::String(format!("user{}", i)));
+            db.insert("users", row).await.unwrap();
+        }
+        db.save(db_path).await.unwrap();
+
+        let new_pool = create_pool([0; 32], wal_path).unwrap();
+        let new_db = new_pool.get().unwrap();
+        new_db.load(db_path).await.unwrap();
+        let (rows, _) = new_db.select_shared("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(Arc::ptr_eq(rows[0].schema(), rows[2].schema()));
+        assert_eq!(rows[1].get_str("name"), Some("user1"));
+        assert!(new_db.verify_integrity().await);
+
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_file(wal_path);
+    }
 }
@@ -0,0 +1,132 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures::{self, row};
+    use zapdb::{
+        query, AggregateFunction, Column, Constraint, DataType, Database, Dependent, Join, JoinType, OnDependents,
+        Query, Rollup,
+    };
+
+    // teams <- users (foreign key), with views, a materialized view, a rollup
+    // over the materialized view, and a join view from users to teams.
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "teams".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique])],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new(
+                    "team".to_string(),
+                    DataType::Integer,
+                    vec![Constraint::ForeignKey { table: "teams".to_string(), column: "id".to_string() }],
+                ),
+                Column::new(
+                    "manager".to_string(),
+                    DataType::Integer,
+                    vec![Constraint::ForeignKey { table: "users".to_string(), column: "id".to_string() }],
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        db.insert("teams", row(&[("id", 1)])).await.unwrap();
+        db.insert("users", row(&[("id", 1), ("team", 1)])).await.unwrap();
+        db.insert("users", row(&[("id", 2), ("team", 1), ("manager", 1)])).await.unwrap();
+
+        db.create_view("big_teams", "teams", query!(id > 0)).await.unwrap();
+        let join = Query::Join(Join::new(JoinType::Inner, "teams", ("team", "id")));
+        db.create_view("user_teams", "users", join).await.unwrap();
+        db.create_materialized_view("team_ids", "teams", Query::MatchAll).await.unwrap();
+        db.create_rollup("team_count", Rollup::new("team_ids").measure("teams", AggregateFunction::Count, "id"))
+            .await
+            .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_restrict_lists_what_blocks() {
+        let db = setup().await;
+        let expected = vec![
+            Dependent::ForeignKey { table: "users".to_string(), column: "team".to_string() },
+            Dependent::View("big_teams".to_string()),
+            Dependent::View("user_teams".to_string()),
+            Dependent::MaterializedView("team_ids".to_string()),
+        ];
+        assert_eq!(db.dependents("teams").await, expected);
+        assert_eq!(db.dependents("team_ids").await, vec![Dependent::Rollup("team_count".to_string())]);
+        // A foreign key to the table itself doesn't count.
+        assert_eq!(db.dependents("users").await, vec![Dependent::View("user_teams".to_string())]);
+
+        let error = db.drop_table("teams", OnDependents::Restrict).await.unwrap_err();
+        assert_eq!(
+            error,
+            "Table teams is used by foreign key users.team, view big_teams, view user_teams, materialized view team_ids"
+        );
+        assert!(db.rename_table("teams", "squads", OnDependents::Restrict).await.is_err());
+        assert_eq!(db.count("teams", &Query::MatchAll).await.unwrap(), 1);
+        assert!(db.drop_table("team_ids", OnDependents::Cascade).await.is_err());
+
+        assert!(db.drop_table("missing", OnDependents::Restrict).await.is_err());
+        db.create_table("scratch".to_string(), vec![Column::new("id".to_string(), DataType::Integer, vec![])])
+            .await
+            .unwrap();
+        assert_eq!(db.drop_table("scratch", OnDependents::Restrict).await.unwrap(), vec![]);
+        assert!(!db.table_names().await.contains(&"scratch".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_drop_cascades() {
+        let db = setup().await;
+        let dropped = db.drop_table("teams", OnDependents::Cascade).await.unwrap();
+        assert_eq!(dropped.len(), 5);
+        assert!(dropped.contains(&Dependent::Rollup("team_count".to_string())));
+
+        assert_eq!(db.table_names().await, vec!["users"]);
+        assert!(db.view("big_teams").is_none());
+        assert!(db.view("user_teams").is_none());
+        // The foreign key is gone, so any team will do; the one to users stays.
+        db.insert("users", row(&[("id", 3), ("team", 42)])).await.unwrap();
+        assert!(db.insert("users", row(&[("id", 4), ("manager", 42)])).await.is_err());
+
+        // Replaying the WAL drops the same things.
+        let replayed = fixtures::reopen(db.storage());
+        replayed.load("test_dependencies_drop.zap").await.unwrap();
+        assert_eq!(replayed.table_names().await, vec!["users"]);
+        assert!(replayed.view("big_teams").is_none());
+        assert_eq!(replayed.count("users", &Query::MatchAll).await.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rename_cascades() {
+        let db = setup().await;
+        let updated = db.rename_table("teams", "squads", OnDependents::Cascade).await.unwrap();
+        assert_eq!(updated.len(), 4);
+        assert_eq!(db.table_names().await, vec!["squads", "team_count", "team_ids", "users"]);
+
+        assert_eq!(db.view("big_teams").unwrap().table, "squads");
+        assert_eq!(db.count("user_teams", &Query::MatchAll).await.unwrap(), 2);
+        assert!(db.insert("users", row(&[("id", 3), ("team", 2)])).await.is_err());
+        db.insert("squads", row(&[("id", 2)])).await.unwrap();
+        db.insert("users", row(&[("id", 3), ("team", 2)])).await.unwrap();
+        assert_eq!(db.count("team_ids", &Query::MatchAll).await.unwrap(), 2);
+        assert_eq!(db.dependents("teams").await, vec![]);
+
+        // A table's foreign keys to itself follow it.
+        db.drop_view("user_teams").await.unwrap();
+        db.rename_table("users", "members", OnDependents::Restrict).await.unwrap();
+        assert!(db.insert("members", row(&[("id", 4), ("manager", 42)])).await.is_err());
+        db.insert("members", row(&[("id", 4), ("manager", 3)])).await.unwrap();
+        assert!(db.rename_table("members", "squads", OnDependents::Cascade).await.is_err());
+
+        let replayed = fixtures::reopen(db.storage());
+        replayed.load("test_dependencies_rename.zap").await.unwrap();
+        assert_eq!(replayed.table_names().await, vec!["members", "squads"]);
+        assert_eq!(replayed.view("big_teams").unwrap().table, "squads");
+        assert_eq!(replayed.count("members", &Query::MatchAll).await.unwrap(), 4);
+    }
+}
@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{Column, Condition, DataType, Database, Operator, Query, Value};
+    use std::collections::HashMap;
+
+    const COUNTRIES: [&str; 3] = ["United Kingdom", "United States", "New Zealand"];
+
+    fn country_is(operator: Operator, country: &str) -> Query {
+        Query::Condition(Condition {
+            column: "country".to_string(),
+            operator,
+            value: Value::String(country.to_string()),
+        })
+    }
+
+    async fn setup(encode: bool) -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("country".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        if encode {
+            db.enable_dictionary_encoding("users", "country").await.unwrap();
+        }
+        for i in 0..300 {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(i));
+            row.insert(
+                "country".to_string(),
+                Value::String(COUNTRIES[i as usize % 3].to_string()),
+            );
+            db.insert("users", row).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_encoded_queries() {
+        let db = setup(true).await;
+        assert_eq!(db.dictionary_size("users", "country").await, Some(3));
+        assert_eq!(db.dictionary_size("users", "id").await, None);
+
+        let uk = country_is(Operator::Eq, "United Kingdom");
+        assert_eq!(db.count("users", &uk).await.unwrap(), 100);
+        let not_uk = country_is(Operator::NotEq, "United Kingdom");
+        assert_eq!(db.count("users", &not_uk).await.unwrap(), 200);
+        let unknown = country_is(Operator::Eq, "France");
+        assert_eq!(db.count("users", &unknown).await.unwrap(), 0);
+        let after_n = country_is(Operator::Gt, "O");
+        assert_eq!(db.count("users", &after_n).await.unwrap(), 200);
+
+        let row = db.get("users", &Value::Integer(4)).await.unwrap().unwrap();
+        assert_eq!(row.get_str("country"), Some("United States"));
+
+        db.update("users", &uk, |row| {
+            row.insert("country".to_string(), Value::String("France".to_string()));
+        })
+        .await
+        .unwrap();
+        assert_eq!(db.count("users", &unknown).await.unwrap(), 100);
+        assert_eq!(db.dictionary_size("users", "country").await, Some(4));
+        assert!(db.verify_integrity().await);
+
+        // Re-enabling rebuilds the dictionary from the rows.
+        db.enable_dictionary_encoding("users", "country").await.unwrap();
+        assert_eq!(db.dictionary_size("users", "country").await, Some(3));
+        assert_eq!(db.count("users", &unknown).await.unwrap(), 100);
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_encoding_saves_memory() {
+        let plain = setup(false).await;
+        let encoded = setup(true).await;
+        let plain_bytes = plain.memory_stats().await.data_bytes;
+        let encoded_bytes = encoded.memory_stats().await.data_bytes;
+        assert!(encoded_bytes < plain_bytes);
+
+        let (plain_rows, _) = plain.select("users", &Query::MatchAll).await.unwrap();
+        let (encoded_rows, _) = encoded.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(plain_rows, encoded_rows);
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_encoding_requires_string_column() {
+        let db = setup(false).await;
+        assert!(db.enable_dictionary_encoding("users", "id").await.is_err());
+        assert!(db.enable_dictionary_encoding("users", "missing").await.is_err());
+        assert!(db.enable_dictionary_encoding("missing", "country").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_encoded_table_round_trips() {
+        let db_path = "test_dictionary_round_trip.zap";
+        let db = setup(true).await;
+        db.save(db_path).await.unwrap();
+
+        let reopened = fixtures::reopen(db.storage());
+        reopened.load(db_path).await.unwrap();
+        let uk = country_is(Operator::Eq, "United Kingdom");
+        assert_eq!(reopened.count("users", &uk).await.unwrap(), 100);
+        assert_eq!(reopened.dictionary_size("users", "country").await, None);
+    }
+}
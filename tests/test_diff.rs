@@ -0,0 +1,110 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{query, Column, Constraint, DataType, Database, Value};
+
+    fn user(id: i64, name: &str) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("name".to_string(), Value::String(name.to_string())),
+        ])
+    }
+
+    async fn create_table(db: &Database, name: &str) {
+        db.create_table(
+            name.to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    fn cleanup(paths: &[&str]) {
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_diff_reports_row_and_table_changes() {
+        let paths = ["test_diff_reports_changes.wal", "test_diff_reports_changes_a.db", "test_diff_reports_changes_b.db"];
+        cleanup(&paths);
+        let db = Database::new([0; 32], paths[0]);
+        create_table(&db, "users").await;
+        create_table(&db, "teams").await;
+        for (id, name) in [(1, "Alice"), (2, "Bob"), (3, "Carol")] {
+            db.insert("users", user(id, name)).await.unwrap();
+        }
+        db.insert("teams", user(1, "Core")).await.unwrap();
+        db.save(paths[1]).await.unwrap();
+
+        db.delete("users", &query!(id == 2)).await.unwrap();
+        db.update("users", &query!(id == 3), |row| {
+            row.insert("name".to_string(), Value::String("Caroline".to_string()));
+        })
+        .await
+        .unwrap();
+        db.insert("users", user(4, "Dave")).await.unwrap();
+        create_table(&db, "projects").await;
+        db.insert("projects", user(1, "Launch")).await.unwrap();
+        db.save(paths[2]).await.unwrap();
+
+        let diff = db.diff(paths[1], paths[2]).await.unwrap();
+        assert_eq!(diff.added_tables, vec!["projects"]);
+        assert!(diff.removed_tables.is_empty());
+        assert_eq!(diff.unchanged_tables, 1);
+        assert!(!diff.tables.contains_key("teams"));
+
+        let users = &diff.tables["users"];
+        assert!(!users.columns_changed);
+        assert_eq!(users.removed.len(), 1);
+        assert_eq!(users.removed[0].1.get_str("name"), Some("Bob"));
+        assert_eq!(users.added.len(), 1);
+        assert_eq!(users.added[0].1.get_str("name"), Some("Dave"));
+        assert_eq!(users.changed.len(), 1);
+        let (_, before, after) = &users.changed[0];
+        assert_eq!(before.get_str("name"), Some("Carol"));
+        assert_eq!(after.get_str("name"), Some("Caroline"));
+        assert_eq!(diff.tables["projects"].added.len(), 1);
+
+        // The other way round, added and removed swap.
+        let reverse = db.diff(paths[2], paths[1]).await.unwrap();
+        assert_eq!(reverse.removed_tables, vec!["projects"]);
+        assert_eq!(reverse.tables["users"].added[0].1.get_str("name"), Some("Bob"));
+
+        assert!(db.diff(paths[1], paths[1]).await.unwrap().is_empty());
+        assert!(db.diff(paths[1], "test_diff_missing.db").await.is_err());
+
+        cleanup(&paths);
+    }
+
+    #[tokio::test]
+    async fn test_diff_current_compares_with_live_tables() {
+        let paths = ["test_diff_current.wal", "test_diff_current.db"];
+        cleanup(&paths);
+        let db = Database::new([0; 32], paths[0]);
+        create_table(&db, "users").await;
+        db.insert("users", user(1, "Alice")).await.unwrap();
+        db.save(paths[1]).await.unwrap();
+
+        let diff = db.diff_current(paths[1]).await.unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.unchanged_tables, 1);
+
+        db.insert("users", user(2, "Bob")).await.unwrap();
+        let diff = db.diff_current(paths[1]).await.unwrap();
+        assert_eq!(diff.tables["users"].added.len(), 1);
+        assert!(diff.tables["users"].removed.is_empty());
+
+        // Snapshots from a database with another key can't be read.
+        let other = Database::new([1; 32], "test_diff_current_other.wal");
+        assert!(other.diff_current(paths[1]).await.is_err());
+
+        cleanup(&paths);
+        cleanup(&["test_diff_current_other.wal"]);
+    }
+}
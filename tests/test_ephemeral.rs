@@ -0,0 +1,51 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{create_pool, Column, DataType, Value, Query};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn row(id: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_table() {
+        let db_path = "test_ephemeral.zap";
+        let wal_path = "test_ephemeral.wal";
+        let _ = fs::remove_file(wal_path);
+        let columns = vec![Column::new("id".to_string(), DataType::Integer, vec![])];
+
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table("users".to_string(), columns.clone()).await.unwrap();
+        db.create_ephemeral_table("scratch".to_string(), columns).await.unwrap();
+        db.insert("users", row(1)).await.unwrap();
+        db.insert("scratch", row(1)).await.unwrap();
+        db.insert("scratch", row(2)).await.unwrap();
+        db.delete("scratch", &Query::MatchAll).await.unwrap();
+        db.insert("scratch", row(3)).await.unwrap();
+
+        // Nothing about the scratch table reaches the WAL.
+        let recovered_pool = create_pool([0; 32], wal_path).unwrap();
+        let recovered = recovered_pool.get().unwrap();
+        recovered.load("test_ephemeral_missing.zap").await.unwrap();
+        assert_eq!(recovered.count("users", &Query::MatchAll).await.unwrap(), 1);
+        assert!(recovered.select("scratch", &Query::MatchAll).await.is_err());
+
+        // Nor the snapshot, but reloading keeps the live scratch table.
+        db.save(db_path).await.unwrap();
+        db.load(db_path).await.unwrap();
+        assert_eq!(db.count("scratch", &Query::MatchAll).await.unwrap(), 1);
+
+        let new_pool = create_pool([0; 32], wal_path).unwrap();
+        let new_db = new_pool.get().unwrap();
+        new_db.load(db_path).await.unwrap();
+        assert_eq!(new_db.count("users", &Query::MatchAll).await.unwrap(), 1);
+        assert!(new_db.select("scratch", &Query::MatchAll).await.is_err());
+
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_file(wal_path);
+    }
+}
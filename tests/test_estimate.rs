@@ -0,0 +1,91 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{
+        AggregateFunction, AggregateQuery, Column, Condition, DataType, Database, Join, JoinType, Operator, Query, Value,
+    };
+
+    fn eq(column: &str, value: Value) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator: Operator::Eq,
+            value,
+        })
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("status".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "orders".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("user_id".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for id in 0..100 {
+            let status = ["active", "idle", "banned", "new"][id as usize % 4];
+            let user = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("status".to_string(), Value::String(status.to_string())),
+            ]);
+            db.insert("users", user).await.unwrap();
+        }
+        for id in 0..200 {
+            let order = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("user_id".to_string(), Value::Integer(id % 100)),
+            ]);
+            db.insert("orders", order).await.unwrap();
+        }
+        db.create_index("users", "id").await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_estimate_filters() {
+        let db = setup().await;
+
+        let all = db.estimate("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(all.rows, 100.0);
+        assert!(all.bytes > 0.0);
+
+        // Counted from the index.
+        let one = db.estimate("users", &eq("id", Value::Integer(7))).await.unwrap();
+        assert_eq!(one.rows, 1.0);
+        assert_eq!(one.bytes, all.bytes / 100.0);
+
+        db.analyze("users").await.unwrap();
+        let active = db.estimate("users", &eq("status", Value::String("active".to_string()))).await.unwrap();
+        assert!((15.0..=35.0).contains(&active.rows), "{:?}", active);
+        let either = Query::Or(vec![eq("id", Value::Integer(7)), eq("id", Value::Integer(8))]);
+        assert!((db.estimate("users", &either).await.unwrap().rows - 2.0).abs() < 0.1);
+
+        assert!(db.estimate("missing", &Query::MatchAll).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_estimate_joins_and_aggregates() {
+        let db = setup().await;
+        db.analyze("orders").await.unwrap();
+
+        let join = Query::Join(Join::new(JoinType::Inner, "orders", ("id", "user_id")));
+        let estimate = db.estimate("users", &join).await.unwrap();
+        assert!((150.0..=250.0).contains(&estimate.rows), "{:?}", estimate);
+
+        let by_status = Query::Aggregate(AggregateQuery::new(AggregateFunction::Count, "*").grouped_by(&["status"]));
+        db.analyze("users").await.unwrap();
+        assert_eq!(db.estimate("users", &by_status).await.unwrap().rows, 4.0);
+    }
+}
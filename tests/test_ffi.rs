@@ -0,0 +1,57 @@
+#[cfg(all(test, feature = "ffi"))]
+mod tests {
+    use zapdb::ffi::*;
+    use std::ffi::{CStr, CString};
+    use std::fs;
+    use std::ptr;
+
+    fn c(s: &str) -> CString {
+        CString::new(s).unwrap()
+    }
+
+    #[test]
+    fn test_ffi_round_trip() {
+        let wal_path = "test_ffi_round_trip.wal";
+        let db_path = "test_ffi_round_trip.zap";
+        let key = [0u8; 32];
+        unsafe {
+            let db = zapdb_open(key.as_ptr(), c(wal_path).as_ptr());
+            assert!(!db.is_null());
+
+            let columns = c(r#"[{"name": "id", "type": "integer", "constraints": ["unique"]},
+                               {"name": "name", "type": "string"}]"#);
+            assert_eq!(zapdb_create_table(db, c("users").as_ptr(), columns.as_ptr()), 0);
+            for row in [r#"{"id": 1, "name": "Alice"}"#, r#"{"id": 2, "name": "Bob"}"#] {
+                assert_eq!(zapdb_insert(db, c("users").as_ptr(), c(row).as_ptr()), 0);
+            }
+
+            // Failures are reported through zapdb_last_error.
+            let duplicate = c(r#"{"id": 1, "name": "Again"}"#);
+            assert_eq!(zapdb_insert(db, c("users").as_ptr(), duplicate.as_ptr()), -1);
+            let error = CStr::from_ptr(zapdb_last_error()).to_str().unwrap();
+            assert_eq!(error, "Column id must be unique");
+            assert!(zapdb_query(db, c("missing").as_ptr(), c("\"match_all\"").as_ptr()).is_null());
+
+            let query = c(r#"{"condition": {"column": "id", "op": "eq", "value": 2}}"#);
+            let result = zapdb_query(db, c("users").as_ptr(), query.as_ptr());
+            assert_eq!(zapdb_result_count(result), 1);
+            let row: serde_json::Value =
+                serde_json::from_str(CStr::from_ptr(zapdb_result_row(result, 0)).to_str().unwrap()).unwrap();
+            assert_eq!(row["name"], "Bob");
+            assert!(zapdb_result_row(result, 1).is_null());
+            zapdb_result_free(result);
+
+            assert_eq!(zapdb_delete(db, c("users").as_ptr(), query.as_ptr()), 1);
+            assert_eq!(zapdb_close(db, c(db_path).as_ptr()), 0);
+
+            let db = zapdb_open(key.as_ptr(), c(wal_path).as_ptr());
+            assert_eq!(zapdb_load(db, c(db_path).as_ptr()), 0);
+            let result = zapdb_query(db, c("users").as_ptr(), c("\"match_all\"").as_ptr());
+            assert_eq!(zapdb_result_count(result), 1);
+            zapdb_result_free(result);
+            assert_eq!(zapdb_close(db, ptr::null()), 0);
+        }
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(db_path);
+    }
+}
@@ -0,0 +1,90 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use zapdb::{Column, Condition, Constraint, DataType, Database, Operator, Query, Value};
+
+    async fn blog(wal_path: &str) -> Database {
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "posts".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new(
+                    "author_id".to_string(),
+                    DataType::Integer,
+                    vec![Constraint::ForeignKey {
+                        table: "users".to_string(),
+                        column: "id".to_string(),
+                    }],
+                ),
+                Column::new("title".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_load_fixtures() {
+        let wal_path = "test_load_fixtures.wal";
+        let db = blog(wal_path).await;
+        // Posts sort before users but reference them, so users load first.
+        let fixtures = r#"{
+            "posts": [
+                {"id": 10, "author_id": {"$ref": "users.bob.id"}, "title": "Hello"},
+                {"id": 11, "author_id": {"$ref": "users.alice.id"}, "title": "Again"}
+            ],
+            "users": [
+                {"$name": "alice", "id": 1, "name": "Alice"},
+                {"$name": "bob", "id": 2, "name": "Bob"}
+            ]
+        }"#;
+        assert_eq!(db.load_fixtures(fixtures.as_bytes()).await.unwrap(), 4);
+
+        let by_bob = Query::Condition(Condition {
+            column: "author_id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(2),
+        });
+        let (rows, _) = db.select("posts", &by_bob).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get("title"), Some(&Value::String("Hello".to_string())));
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 2);
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_load_fixtures_errors() {
+        let wal_path = "test_load_fixtures_errors.wal";
+        let db = blog(wal_path).await;
+
+        let cases = [
+            (r#"{"users": [{"id": {"$ref": "users.carol.id"}}]}"#, "Fixture users.carol not found"),
+            (r#"{"users": [{"id": {"$ref": "users"}}]}"#, "Reference users is not <table>.<name>.<column>"),
+            (r#"{"users": [{"id": "one"}]}"#, "users.id: Cannot convert \"one\" to Integer"),
+            (r#"{"comments": []}"#, "Table comments not found"),
+        ];
+        for (json, expected) in cases {
+            let err = db.load_fixtures(json.as_bytes()).await.unwrap_err();
+            assert_eq!(err.to_string(), expected);
+        }
+
+        // A failing row leaves out the rows before it too.
+        let fixtures = r#"{"users": [{"id": 1}, {"id": 1}]}"#;
+        assert!(db.load_fixtures(fixtures.as_bytes()).await.is_err());
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 0);
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(all(test, feature = "server"))]
+mod tests {
+    use arrow::array::{Array, Int32Array, Int64Array, StringArray};
+    use arrow::datatypes::{DataType as ArrowType, Field, Schema};
+    use arrow::record_batch::RecordBatch;
+    use arrow_flight::encode::FlightDataEncoderBuilder;
+    use arrow_flight::{FlightClient, FlightDescriptor, Ticket};
+    use futures::{stream, TryStreamExt};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tonic::transport::Channel;
+    use zapdb::{Column, DataType, Database};
+
+    async fn connect(port: u16) -> FlightClient {
+        for _ in 0..50 {
+            if let Ok(channel) = Channel::from_shared(format!("http://127.0.0.1:{}", port))
+                .unwrap()
+                .connect()
+                .await
+            {
+                return FlightClient::new(channel);
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Flight server did not start");
+    }
+
+    #[tokio::test]
+    async fn test_flight_put_and_get() {
+        let db = Arc::new(Database::in_memory([0; 32]));
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let server = db.clone();
+        tokio::spawn(async move { server.serve_flight(([127, 0, 0, 1], port).into()).await });
+        let mut client = connect(port).await;
+
+        // Int32 ids are cast to the Integer column.
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", ArrowType::Int32, false),
+            Field::new("name", ArrowType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int32Array::from_iter_values(0..1000)),
+                Arc::new(StringArray::from_iter((0..1000).map(|i| (i % 10 != 0).then(|| format!("user{}", i))))),
+            ],
+        )
+        .unwrap();
+        let data = FlightDataEncoderBuilder::new()
+            .with_flight_descriptor(Some(FlightDescriptor::new_path(vec!["users".to_string()])))
+            .build(stream::iter(vec![Ok(batch.clone()), Ok(batch.slice(0, 10))]));
+        let results: Vec<_> = client.do_put(data).await.unwrap().try_collect().await.unwrap();
+        assert_eq!(results.last().unwrap().app_metadata, "1010".as_bytes());
+        assert_eq!(db.count("users", &zapdb::Query::MatchAll).await.unwrap(), 1010);
+
+        let info = client
+            .get_flight_info(FlightDescriptor::new_path(vec!["users".to_string()]))
+            .await
+            .unwrap();
+        assert_eq!(info.total_records, 1010);
+
+        let ticket = Ticket::new(r#"{"table": "users", "query": {"condition": {"column": "id", "op": "lt", "value": 5}}}"#);
+        let batches: Vec<RecordBatch> = client.do_get(ticket).await.unwrap().try_collect().await.unwrap();
+        let rows: usize = batches.iter().map(|b| b.num_rows()).sum();
+        assert_eq!(rows, 10);
+        let batch = &batches[0];
+        let ids = batch.column_by_name("id").unwrap().as_any().downcast_ref::<Int64Array>().unwrap();
+        assert!(ids.values().iter().all(|id| *id < 5));
+        let names = batch.column_by_name("name").unwrap();
+        assert_eq!(names.null_count(), 2);
+
+        let missing = client.do_get(Ticket::new(r#"{"table": "missing"}"#)).await;
+        assert!(missing.is_err());
+    }
+}
@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures::{self, row};
+    use zapdb::{query, Column, Constraint, DataType, Database, IndexInfo, Value, WriteError};
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "teams".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("code".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        assert_eq!(db.indexes("teams").await, Some(vec![]));
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new(
+                    "team".to_string(),
+                    DataType::Integer,
+                    vec![Constraint::ForeignKey { table: "teams".to_string(), column: "code".to_string() }],
+                ),
+                Column::new(
+                    "manager".to_string(),
+                    DataType::Integer,
+                    vec![Constraint::ForeignKey { table: "users".to_string(), column: "id".to_string() }],
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_referenced_columns_are_indexed() {
+        let db = setup().await;
+        for code in 0..100 {
+            db.insert("teams", row(&[("id", code), ("code", code * 10)])).await.unwrap();
+        }
+        db.insert("users", row(&[("id", 1), ("team", 50)])).await.unwrap();
+        db.insert("users", row(&[("id", 2), ("team", 990), ("manager", 1)])).await.unwrap();
+        assert!(matches!(
+            db.insert("users", row(&[("id", 3), ("team", 55)])).await,
+            Err(WriteError::Constraint(_))
+        ));
+        assert!(db.insert("users", row(&[("id", 3), ("manager", 7)])).await.is_err());
+
+        // The index follows updates and deletes of the parent.
+        db.delete("teams", &query!(code == 990)).await.unwrap();
+        assert!(db.insert("users", row(&[("id", 3), ("team", 990)])).await.is_err());
+        db.update("teams", &query!(id == 5), |row| {
+            row.insert("code".to_string(), Value::Integer(55));
+        })
+        .await
+        .unwrap();
+        db.insert("users", row(&[("id", 3), ("team", 55)])).await.unwrap();
+        assert!(db.update("users", &query!(id == 3), |row| {
+            row.insert("team".to_string(), Value::Integer(50_000));
+        })
+        .await
+        .is_err());
+
+        let teams = db.indexes("teams").await.unwrap();
+        assert_eq!(teams.len(), 1);
+        let IndexInfo { column, bytes, unique, referenced_by } = &teams[0];
+        assert_eq!(column, "code");
+        assert!(*bytes > 0);
+        assert!(!unique);
+        assert_eq!(referenced_by, &vec![("users".to_string(), "team".to_string())]);
+        let users = db.indexes("users").await.unwrap();
+        assert_eq!(users[0].column, "id");
+        assert!(users[0].unique);
+        assert_eq!(users[0].referenced_by, vec![("users".to_string(), "manager".to_string())]);
+        assert_eq!(db.indexes("missing").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_referenced_columns_are_indexed_on_load() {
+        let db_path = "test_referenced_columns_load.zap";
+        let db = setup().await;
+        db.insert("teams", row(&[("id", 1), ("code", 10)])).await.unwrap();
+        db.save(db_path).await.unwrap();
+
+        let loaded = fixtures::reopen(db.storage());
+        loaded.load(db_path).await.unwrap();
+        let columns: Vec<String> = loaded.indexes("teams").await.unwrap().into_iter().map(|i| i.column).collect();
+        assert_eq!(columns, vec!["code", "id"]);
+        loaded.insert("users", row(&[("id", 1), ("team", 10)])).await.unwrap();
+        assert!(loaded.insert("users", row(&[("id", 2), ("team", 11)])).await.is_err());
+    }
+}
@@ -0,0 +1,73 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{query, Column, Constraint, DataType, Database, Query, Value};
+
+    fn user(id: i64, name: &str) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("name".to_string(), Value::String(name.to_string())),
+        ])
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.insert("users", user(1, "Alice")).await.unwrap();
+        db.insert("users", user(2, "Bob")).await.unwrap();
+        db.create_view("alices", "users", query!(name == "Alice")).await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_fork_is_isolated_from_the_original() {
+        let db = setup().await;
+        let fork = db.fork().await.unwrap();
+
+        let (rows, _) = fork.select("alices", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        // Constraints and their indexes come along.
+        assert!(fork.insert("users", user(1, "Again")).await.is_err());
+
+        fork.insert("users", user(3, "Carol")).await.unwrap();
+        fork.update("users", &query!(id == 1), |row| {
+            row.insert("name".to_string(), Value::String("Alicia".to_string()));
+        })
+        .await
+        .unwrap();
+        fork.delete("users", &query!(id == 2)).await.unwrap();
+        db.insert("users", user(4, "Dave")).await.unwrap();
+
+        let names = |rows: Vec<zapdb::Row>| -> Vec<String> {
+            rows.iter().map(|r| r.get_str("name").unwrap().to_string()).collect()
+        };
+        let (original, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(names(original), ["Alice", "Bob", "Dave"]);
+        let (forked, _) = fork.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(names(forked), ["Alicia", "Carol"]);
+
+        // The fork's WAL is its own, in its own storage.
+        let wal = db.storage().read(fixtures::WAL).unwrap().unwrap();
+        let reloaded = fixtures::reopen(db.storage());
+        reloaded.load("test_fork_is_isolated.zap").await.unwrap();
+        let (rows, _) = reloaded.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert!(fork.storage().read(fixtures::WAL).unwrap().is_some_and(|fork_wal| fork_wal != wal));
+    }
+
+    #[tokio::test]
+    async fn test_fork_of_a_closed_database_fails() {
+        let db = setup().await;
+        db.close(None).await.unwrap();
+        assert!(db.fork().await.is_err());
+    }
+}
@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{Column, Comparison, DataType, Database, Expr, Operator, Query, Value};
+    use std::collections::HashMap;
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "posts".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("title".to_string(), DataType::String, vec![]),
+                Column::new("likes".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, title, likes) in [(1, "Hello", 3), (2, "A longer title", 10), (3, "Hi", 0)] {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("title".to_string(), Value::String(title.to_string()));
+            row.insert("likes".to_string(), Value::Integer(likes));
+            db.insert("posts", row).await.unwrap();
+        }
+        db.register_function("score", vec![DataType::String, DataType::Integer], DataType::Float, |args| {
+            match args {
+                [Value::String(title), Value::Integer(likes)] => {
+                    Ok(Value::Float(*likes as f64 / title.len() as f64))
+                }
+                _ => Ok(Value::Null),
+            }
+        });
+        db
+    }
+
+    fn score_above(threshold: f64) -> Query {
+        Query::Compare(Comparison {
+            left: Expr::call("score", vec![Expr::column("title"), Expr::column("likes")]),
+            operator: Operator::Gt,
+            right: Expr::Literal(Value::Float(threshold)),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_functions_in_queries() {
+        let db = setup().await;
+
+        let (rows, _) = db.select("posts", &score_above(0.5)).await.unwrap();
+        let mut ids: Vec<i64> = rows.iter().map(|r| r.get_i64("id").unwrap()).collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(db.count("posts", &score_above(0.65)).await.unwrap(), 1);
+        assert_eq!(db.delete("posts", &score_above(0.65)).await.unwrap(), 1);
+
+        let projected = db
+            .project(
+                "posts",
+                &Query::MatchAll,
+                &[
+                    ("id", Expr::column("id")),
+                    ("score", Expr::call("score", vec![Expr::column("title"), Expr::column("likes")])),
+                ],
+            )
+            .await
+            .unwrap();
+        let scores: Vec<(i64, f64)> = projected
+            .iter()
+            .map(|r| (r.get_i64("id").unwrap(), r.get_f64("score").unwrap()))
+            .collect();
+        assert!(scores.contains(&(1, 0.6)));
+        assert!(scores.contains(&(3, 0.0)));
+
+        // The JSON protocol round-trips expressions.
+        let json = score_above(0.5).to_json().to_string();
+        let columns = db.table_columns("posts").await.unwrap();
+        let parsed = Query::from_json(&json, &columns).unwrap();
+        assert_eq!(db.count("posts", &parsed).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_functions_are_checked() {
+        let db = setup().await;
+        let compare = |left: Expr, right: Expr| {
+            Query::Compare(Comparison {
+                left,
+                operator: Operator::Eq,
+                right,
+            })
+        };
+
+        let unknown = compare(Expr::call("nope", vec![]), Expr::Literal(Value::Null));
+        let err = db.select("posts", &unknown).await.unwrap_err();
+        assert_eq!(err, "Function nope not found");
+
+        let arity = compare(Expr::call("score", vec![Expr::column("title")]), Expr::Literal(Value::Null));
+        let err = db.select("posts", &arity).await.unwrap_err();
+        assert_eq!(err, "Function score takes 2 arguments, got 1");
+
+        let types = compare(
+            Expr::call("score", vec![Expr::column("likes"), Expr::column("likes")]),
+            Expr::Literal(Value::Null),
+        );
+        let err = db.count("posts", &types).await.unwrap_err();
+        assert_eq!(err, "Argument 1 of score must be String, got Integer");
+
+        let mismatch = compare(Expr::column("title"), Expr::column("likes"));
+        let err = db.delete("posts", &mismatch).await.unwrap_err();
+        assert_eq!(err.to_string(), "Cannot compare String with Integer");
+
+        let missing = compare(Expr::column("missing"), Expr::Literal(Value::Integer(1)));
+        assert!(db.watch("posts", missing).await.is_err());
+
+        // A function returning the wrong type fails the projection.
+        db.register_function("bad", vec![], DataType::Integer, |_| Ok(Value::Boolean(true)));
+        let err = db
+            .project("posts", &Query::MatchAll, &[("x", Expr::call("bad", vec![]))])
+            .await
+            .unwrap_err();
+        assert_eq!(err, "Function bad returned Boolean, expected Integer");
+    }
+}
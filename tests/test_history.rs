@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{
+        begin_transaction, Column, Condition, Constraint, DataType, Database, HistoryOperation,
+        Operator, Query, Value,
+    };
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn user(id: i64, name: &str) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("name".to_string(), Value::String(name.to_string()));
+        row
+    }
+
+    fn by_id(id: i64) -> Query {
+        Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(id),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_row_history() {
+        let wal_path = "test_row_history.wal";
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        assert!(db.history("users", &Value::Integer(1)).is_err());
+        assert!(db.enable_history("missing").await.is_err());
+
+        // Changes made before history is enabled aren't recorded.
+        db.insert("users", user(1, "Alice")).await.unwrap();
+        db.enable_history("users").await.unwrap();
+        db.insert("users", user(2, "Bob")).await.unwrap();
+        db.update("users", &by_id(1), |row| {
+            row.insert("name".to_string(), Value::String("Alicia".to_string()));
+        })
+        .await
+        .unwrap();
+        db.delete("users", &by_id(2)).await.unwrap();
+
+        let alice = db.history("users", &Value::Integer(1)).unwrap();
+        assert_eq!(alice.len(), 1);
+        assert_eq!(alice[0].operation, HistoryOperation::Update);
+        assert_eq!(alice[0].before.as_ref().unwrap().get_str("name"), Some("Alice"));
+        assert_eq!(alice[0].after.as_ref().unwrap().get_str("name"), Some("Alicia"));
+
+        let bob = db.history("users", &Value::Integer(2)).unwrap();
+        let operations: Vec<_> = bob.iter().map(|e| e.operation).collect();
+        assert_eq!(operations, vec![HistoryOperation::Insert, HistoryOperation::Delete]);
+        assert!(bob[0].before.is_none());
+        assert!(bob[1].after.is_none());
+        assert!(bob[0].timestamp <= bob[1].timestamp);
+        assert!(db.history("users", &Value::Integer(3)).unwrap().is_empty());
+
+        // Failed transactions leave no trace.
+        let mut transaction = begin_transaction();
+        transaction.insert("users".to_string(), user(3, "Carol"));
+        transaction.insert("users".to_string(), user(1, "Duplicate"));
+        assert!(db.commit(transaction).await.is_err());
+        assert!(db.history("users", &Value::Integer(3)).unwrap().is_empty());
+
+        // Changing the key records the update under both keys.
+        db.update("users", &by_id(1), |row| {
+            row.insert("id".to_string(), Value::Integer(10));
+        })
+        .await
+        .unwrap();
+        assert_eq!(db.history("users", &Value::Integer(1)).unwrap().len(), 2);
+        assert_eq!(db.history("users", &Value::Integer(10)).unwrap().len(), 1);
+
+        assert!(db.disable_history("users"));
+        assert!(db.history("users", &Value::Integer(1)).is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
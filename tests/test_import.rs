@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod tests {
+    use futures::stream;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use zapdb::{query, Column, Constraint, DataType, Database, ImportOptions, OnRowError, Query, Value};
+
+    fn employee(id: i64, manager: Option<i64>) -> HashMap<String, Value> {
+        let mut row = HashMap::from([("id".to_string(), Value::Integer(id))]);
+        if let Some(manager) = manager {
+            row.insert("manager_id".to_string(), Value::Integer(manager));
+        }
+        row
+    }
+
+    async fn create_employees(db: &Database) {
+        db.create_table(
+            "employees".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new(
+                    "manager_id".to_string(),
+                    DataType::Integer,
+                    vec![Constraint::ForeignKey {
+                        table: "employees".to_string(),
+                        column: "id".to_string(),
+                    }],
+                ),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_import_orders_rows_after_the_rows_they_refer_to() {
+        let db = Database::in_memory([0; 32]);
+        create_employees(&db).await;
+
+        // Reports come before their managers.
+        let rows = vec![employee(3, Some(2)), employee(2, Some(1)), employee(4, Some(1)), employee(1, None)];
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&batches);
+        let options = ImportOptions {
+            batch_size: 2,
+            ..Default::default()
+        }
+        .on_progress(move |progress| seen.lock().unwrap().push(progress.clone()));
+        let report = db.import("employees", stream::iter(rows), options).await.unwrap();
+
+        assert_eq!(report.inserted, 4);
+        assert_eq!(db.count("employees", &Query::MatchAll).await.unwrap(), 4);
+        let batches = batches.lock().unwrap();
+        let last = batches.last().unwrap();
+        assert_eq!((last.read, last.inserted, last.failed, last.waiting), (4, 4, 0, 0));
+        assert!(batches.iter().all(|p| p.inserted <= p.read));
+    }
+
+    #[tokio::test]
+    async fn test_import_error_policies() {
+        let rows = || stream::iter(vec![employee(1, None), employee(1, None), employee(2, Some(9)), employee(3, Some(1))]);
+
+        let db = Database::in_memory([0; 32]);
+        create_employees(&db).await;
+        let options = ImportOptions {
+            on_error: OnRowError::Collect,
+            ..Default::default()
+        };
+        let report = db.import("employees", rows(), options).await.unwrap();
+        assert_eq!((report.inserted, report.failed), (2, 2));
+        let failed: Vec<usize> = report.errors.iter().map(|e| e.index).collect();
+        assert_eq!(failed, vec![1, 2]);
+        assert_eq!(report.errors[1].row, employee(2, Some(9)));
+        assert_eq!(db.count("employees", &query!(id == 3)).await.unwrap(), 1);
+
+        let db = Database::in_memory([0; 32]);
+        create_employees(&db).await;
+        let options = ImportOptions {
+            on_error: OnRowError::Skip,
+            ..Default::default()
+        };
+        let report = db.import("employees", rows(), options).await.unwrap();
+        assert_eq!((report.inserted, report.failed), (2, 2));
+        assert!(report.errors.is_empty());
+
+        // Failing writes nothing from the batch with the bad row.
+        let db = Database::in_memory([0; 32]);
+        create_employees(&db).await;
+        let options = ImportOptions {
+            batch_size: 1,
+            ..Default::default()
+        };
+        assert!(db.import("employees", rows(), options).await.is_err());
+        assert_eq!(db.count("employees", &Query::MatchAll).await.unwrap(), 1);
+    }
+}
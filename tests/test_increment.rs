@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{Column, Condition, DataType, Database, Operator, Query, Value};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "pages".to_string(),
+            vec![
+                Column::new("path".to_string(), DataType::String, vec![]),
+                Column::new("hits".to_string(), DataType::Integer, vec![]),
+                Column::new("score".to_string(), DataType::Float, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for path in ["/", "/about"] {
+            let mut row = HashMap::new();
+            row.insert("path".to_string(), Value::String(path.to_string()));
+            row.insert("hits".to_string(), Value::Integer(0));
+            db.insert("pages", row).await.unwrap();
+        }
+        db
+    }
+
+    fn page(path: &str) -> Query {
+        Query::Condition(Condition {
+            column: "path".to_string(),
+            operator: Operator::Eq,
+            value: Value::String(path.to_string()),
+        })
+    }
+
+    async fn get(db: &Database, path: &str, column: &str) -> Option<Value> {
+        let (rows, _) = db.select("pages", &page(path)).await.unwrap();
+        rows[0].get(column).cloned()
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_increments() {
+        let db = Arc::new(setup().await);
+        let tasks: Vec<_> = (0..8)
+            .map(|_| {
+                let db = db.clone();
+                tokio::spawn(async move {
+                    for _ in 0..25 {
+                        db.increment("pages", &page("/"), "hits", Value::Integer(1)).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(get(&db, "/", "hits").await, Some(Value::Integer(200)));
+        assert_eq!(get(&db, "/about", "hits").await, Some(Value::Integer(0)));
+    }
+
+    #[tokio::test]
+    async fn test_increment_values() {
+        let db = setup().await;
+
+        // A missing value counts as 0, and integers add to floats.
+        db.increment("pages", &page("/"), "score", Value::Float(1.5)).await.unwrap();
+        db.increment("pages", &page("/"), "score", Value::Integer(2)).await.unwrap();
+        assert_eq!(get(&db, "/", "score").await, Some(Value::Float(3.5)));
+
+        db.increment("pages", &page("/"), "hits", Value::Integer(i64::MAX)).await.unwrap();
+        assert!(db.increment("pages", &page("/"), "hits", Value::Integer(1)).await.is_err());
+        assert!(db.increment("pages", &page("/"), "hits", Value::Float(0.5)).await.is_err());
+        assert!(db
+            .increment("pages", &page("/"), "path", Value::Integer(1))
+            .await
+            .is_err());
+        assert_eq!(get(&db, "/", "hits").await, Some(Value::Integer(i64::MAX)));
+
+        let updated = db
+            .increment("pages", &Query::MatchAll, "hits", Value::Integer(-1))
+            .await
+            .unwrap();
+        assert_eq!(updated, 2);
+    }
+
+    #[tokio::test]
+    async fn test_increments_are_replayed() {
+        let db = setup().await;
+        for _ in 0..3 {
+            db.increment("pages", &page("/about"), "hits", Value::Integer(2)).await.unwrap();
+        }
+        let storage = db.storage();
+        drop(db);
+
+        let restored = fixtures::reopen(storage);
+        restored.load("test_increments_are_replayed.zap").await.unwrap();
+        assert_eq!(get(&restored, "/about", "hits").await, Some(Value::Integer(6)));
+    }
+}
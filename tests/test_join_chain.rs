@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{Column, Condition, DataType, Database, Join, JoinChain, JoinType, Operator, Query, SqlOutput, Value};
+
+    fn row(values: &[(&str, Value)]) -> HashMap<String, Value> {
+        values.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    async fn create(db: &Database, name: &str, columns: &[(&str, DataType)]) {
+        let columns = columns
+            .iter()
+            .map(|(name, data_type)| Column::new(name.to_string(), data_type.clone(), vec![]))
+            .collect();
+        db.create_table(name.to_string(), columns).await.unwrap();
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        create(&db, "users", &[("id", DataType::Integer), ("name", DataType::String)]).await;
+        create(&db, "posts", &[("id", DataType::Integer), ("user_id", DataType::Integer), ("title", DataType::String)]).await;
+        create(&db, "comments", &[("id", DataType::Integer), ("post_id", DataType::Integer), ("body", DataType::String)]).await;
+        for (id, name) in [(1, "Alice"), (2, "Bob"), (3, "Carol")] {
+            db.insert("users", row(&[("id", Value::Integer(id)), ("name", Value::String(name.to_string()))]))
+                .await
+                .unwrap();
+        }
+        for (id, user_id, title) in [(101, 1, "Hello"), (102, 2, "Again"), (103, 1, "Third")] {
+            let post = row(&[
+                ("id", Value::Integer(id)),
+                ("user_id", Value::Integer(user_id)),
+                ("title", Value::String(title.to_string())),
+            ]);
+            db.insert("posts", post).await.unwrap();
+        }
+        for (id, post_id, body) in [(1001, 101, "Nice"), (1002, 101, "Agreed"), (1003, 102, "Hm")] {
+            let comment = row(&[
+                ("id", Value::Integer(id)),
+                ("post_id", Value::Integer(post_id)),
+                ("body", Value::String(body.to_string())),
+            ]);
+            db.insert("comments", comment).await.unwrap();
+        }
+        db
+    }
+
+    fn strings<'a>(rows: &'a [zapdb::Row], column: &str) -> Vec<Option<&'a str>> {
+        rows.iter().map(|r| r.get_str(column)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_join_users_posts_comments() {
+        let db = setup().await;
+
+        let chain = JoinChain::new()
+            .join(Join::new(JoinType::Inner, "posts", ("id", "user_id")).prefixed())
+            .join(Join::new(JoinType::Inner, "comments", ("posts.id", "post_id")).prefixed());
+        let (rows, _) = db.select_joins("users", &chain).await.unwrap();
+        assert_eq!(strings(&rows, "users.name"), [Some("Alice"), Some("Alice"), Some("Bob")]);
+        assert_eq!(strings(&rows, "posts.title"), [Some("Hello"), Some("Hello"), Some("Again")]);
+        assert_eq!(strings(&rows, "comments.body"), [Some("Nice"), Some("Agreed"), Some("Hm")]);
+
+        // Left joins keep the users without posts and the posts without
+        // comments.
+        let chain = JoinChain::new()
+            .join(Join::new(JoinType::Left, "posts", ("id", "user_id")).prefixed())
+            .join(Join::new(JoinType::Left, "comments", ("posts.id", "post_id")).prefixed())
+            .filter(Query::Condition(Condition {
+                column: "comments.body".to_string(),
+                operator: Operator::Eq,
+                value: Value::Null,
+            }));
+        let (rows, _) = db.select_joins("users", &chain).await.unwrap();
+        assert_eq!(strings(&rows, "users.name"), [Some("Alice"), Some("Carol")]);
+        assert_eq!(strings(&rows, "posts.title"), [Some("Third"), None]);
+    }
+
+    #[tokio::test]
+    async fn test_join_chain_errors_and_sql() {
+        let db = setup().await;
+
+        let missing = JoinChain::new()
+            .join(Join::new(JoinType::Inner, "posts", ("id", "user_id")).prefixed())
+            .join(Join::new(JoinType::Inner, "comments", ("id", "post_id")).prefixed());
+        assert!(db.select_joins("users", &missing).await.is_err());
+        let twice = JoinChain::new()
+            .join(Join::new(JoinType::Inner, "posts", ("id", "user_id")).prefixed())
+            .join(Join::new(JoinType::Inner, "posts", ("posts.id", "id")).prefixed());
+        assert!(db.select_joins("users", &twice).await.is_err());
+        assert!(db.select_joins("users", &JoinChain::new()).await.is_err());
+
+        let SqlOutput::Rows(rows) = db
+            .execute_sql(
+                "SELECT u.name, c.body FROM users u JOIN posts p ON u.id = p.user_id \
+                 JOIN comments c ON c.post_id = p.id WHERE p.title = 'Hello' ORDER BY c.body",
+            )
+            .await
+            .unwrap()
+        else {
+            panic!("expected rows");
+        };
+        assert_eq!(strings(&rows, "u.name"), [Some("Alice"), Some("Alice")]);
+        assert_eq!(strings(&rows, "c.body"), [Some("Agreed"), Some("Nice")]);
+    }
+}
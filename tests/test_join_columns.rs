@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{Column, Condition, DataType, Database, Expr, Join, JoinColumns, JoinType, Operator, Query, Value};
+
+    fn row(values: &[(&str, Value)]) -> HashMap<String, Value> {
+        values.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    fn condition(column: &str, operator: Operator, value: i64) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value: Value::Integer(value),
+        })
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+                Column::new("manager".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "posts".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("user_id".to_string(), DataType::Integer, vec![]),
+                Column::new("title".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, name, manager) in [(1, "Alice", Value::Null), (2, "Bob", Value::Integer(1)), (3, "Carol", Value::Integer(1))] {
+            db.insert(
+                "users",
+                row(&[("id", Value::Integer(id)), ("name", Value::String(name.to_string())), ("manager", manager)]),
+            )
+            .await
+            .unwrap();
+        }
+        for (id, user_id, title) in [(101, 1, "Hello"), (102, 2, "Again"), (103, 1, "Third")] {
+            db.insert(
+                "posts",
+                row(&[
+                    ("id", Value::Integer(id)),
+                    ("user_id", Value::Integer(user_id)),
+                    ("title", Value::String(title.to_string())),
+                ]),
+            )
+            .await
+            .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_prefixed_join_keeps_both_ids() {
+        let db = setup().await;
+
+        // Merged, the post's id overwrites the user's.
+        let merged = Join::new(JoinType::Inner, "posts", ("id", "user_id"));
+        let (rows, _) = db.select("users", &Query::Join(merged)).await.unwrap();
+        assert_eq!(rows[0].get_i64("id"), Some(101));
+
+        let prefixed = Join::new(JoinType::Inner, "posts", ("id", "user_id")).prefixed();
+        let (rows, _) = db.select("users", &Query::Join(prefixed)).await.unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].get_i64("users.id"), Some(1));
+        assert_eq!(rows[0].get_i64("posts.id"), Some(101));
+        assert_eq!(rows[0].get_str("users.name"), Some("Alice"));
+        assert_eq!(rows[0].get("id"), None);
+
+        // Padding doesn't overwrite the preserved table's columns either.
+        let left = Join::new(JoinType::Left, "posts", ("id", "user_id")).prefixed();
+        let (rows, _) = db.select("users", &Query::Join(left)).await.unwrap();
+        let carol = rows.iter().find(|r| r.get_str("users.name") == Some("Carol")).unwrap();
+        assert_eq!(carol.get_i64("users.id"), Some(3));
+        assert_eq!(carol.get("posts.id"), Some(&Value::Null));
+    }
+
+    #[tokio::test]
+    async fn test_self_join_needs_aliases() {
+        let db = setup().await;
+
+        let prefixed = Join::new(JoinType::Inner, "users", ("manager", "id")).prefixed();
+        assert!(db.select("users", &Query::Join(prefixed)).await.is_err());
+
+        let aliased = Join::new(JoinType::Inner, "users", ("manager", "id")).aliased("report", "boss");
+        let (rows, _) = db.select("users", &Query::Join(aliased)).await.unwrap();
+        let pairs: Vec<(&str, &str)> = rows
+            .iter()
+            .map(|r| (r.get_str("report.name").unwrap(), r.get_str("boss.name").unwrap()))
+            .collect();
+        assert_eq!(pairs, vec![("Bob", "Alice"), ("Carol", "Alice")]);
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_projections_use_prefixed_names() {
+        let db = setup().await;
+        let join = Join::new(JoinType::Inner, "posts", ("id", "user_id")).prefixed();
+
+        db.create_view("user_posts", "users", Query::Join(join.clone())).await.unwrap();
+        let filter = Query::And(vec![condition("users.id", Operator::Eq, 1), condition("posts.id", Operator::Gt, 101)]);
+        let (rows, _) = db.select("user_posts", &filter).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get_str("posts.title"), Some("Third"));
+
+        let projected = db
+            .project(
+                "users",
+                &Query::Join(join.clone()),
+                &[
+                    ("author", Expr::Column("users.name".to_string())),
+                    ("post", Expr::Column("posts.id".to_string())),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(projected[1].get_str("author"), Some("Alice"));
+        assert_eq!(projected[1].get_i64("post"), Some(103));
+        let unknown = db
+            .project("users", &Query::Join(join.clone()), &[("id", Expr::Column("id".to_string()))])
+            .await;
+        assert!(unknown.is_err());
+
+        db.create_materialized_view("user_posts_mv", "users", Query::Join(join)).await.unwrap();
+        let columns: Vec<String> =
+            db.table_columns("user_posts_mv").await.unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(columns, ["users.id", "users.name", "users.manager", "posts.id", "posts.user_id", "posts.title"]);
+        assert_eq!(db.count("user_posts_mv", &condition("posts.id", Operator::Eq, 102)).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_join_columns_round_trip_through_json() {
+        let join = Query::Join(Join::new(JoinType::Left, "posts", ("id", "user_id")).aliased("u", "p"));
+        let json = join.to_json().to_string();
+        let columns = vec![Column::new("id".to_string(), DataType::Integer, vec![])];
+        match Query::from_json(&json, &columns).unwrap() {
+            Query::Join(join) => assert_eq!(join.columns, JoinColumns::Aliased("u".to_string(), "p".to_string())),
+            other => panic!("expected a join, got {:?}", other),
+        }
+        let json = r#"{"join": {"type": "inner", "table": "posts", "on": ["id", "user_id"], "columns": "sideways"}}"#;
+        assert!(Query::from_json(json, &columns).is_err());
+    }
+}
@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use zapdb::{create_pool, PooledConnection, Column, DataType, Value, Query, Join, JoinType};
+    use zapdb::{create_pool, PooledConnection, Column, DataType, Value, Query, Join, JoinMethod, JoinType};
     use std::collections::HashMap;
 
     async fn setup_db() -> PooledConnection {
@@ -64,11 +64,7 @@ mod tests {
     async fn test_inner_join() {
         let db = setup_db().await;
 
-        let join = Join {
-            join_type: JoinType::Inner,
-            target_table: "posts".to_string(),
-            on_condition: ("id".to_string(), "user_id".to_string()),
-        };
+        let join = Join::new(JoinType::Inner, "posts", ("id", "user_id"));
 
         let (results, _) = db.select("users", &Query::Join(join)).await.unwrap();
         assert_eq!(results.len(), 3);
@@ -78,11 +74,7 @@ mod tests {
     async fn test_left_join() {
         let db = setup_db().await;
 
-        let join = Join {
-            join_type: JoinType::Left,
-            target_table: "posts".to_string(),
-            on_condition: ("id".to_string(), "user_id".to_string()),
-        };
+        let join = Join::new(JoinType::Left, "posts", ("id", "user_id"));
 
         let (results, _) = db.select("users", &Query::Join(join)).await.unwrap();
         assert_eq!(results.len(), 4);
@@ -98,13 +90,117 @@ mod tests {
         post4.insert("title".to_string(), Value::String("Post 4".to_string()));
         db.insert("posts", post4).await.unwrap();
 
-        let join = Join {
-            join_type: JoinType::Right,
-            target_table: "posts".to_string(),
-            on_condition: ("id".to_string(), "user_id".to_string()),
-        };
+        let join = Join::new(JoinType::Right, "posts", ("id", "user_id"));
 
         let (results, _) = db.select("users", &Query::Join(join)).await.unwrap();
         assert_eq!(results.len(), 4);
     }
+
+    #[tokio::test]
+    async fn test_join_strategies_agree() {
+        let pool = create_pool([0; 32], "test_joins_strategies.wal").unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "customers".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("region".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "orders".to_string(),
+            vec![
+                Column::new("order_id".to_string(), DataType::Integer, vec![]),
+                Column::new("customer".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for id in 0..5 {
+            let mut customer = HashMap::new();
+            customer.insert("id".to_string(), Value::Integer(id));
+            customer.insert("region".to_string(), Value::String(format!("r{}", id)));
+            db.insert("customers", customer).await.unwrap();
+        }
+        for order_id in 0..200 {
+            let mut order = HashMap::new();
+            order.insert("order_id".to_string(), Value::Integer(order_id));
+            order.insert("customer".to_string(), Value::Integer(order_id % 7));
+            db.insert("orders", order).await.unwrap();
+        }
+
+        let joins = |join_type: JoinType| {
+            Query::Join(Join::new(join_type, "orders", ("id", "customer")))
+        };
+        let mut before = Vec::new();
+        for join_type in [JoinType::Inner, JoinType::Left, JoinType::Right] {
+            before.push(db.select("customers", &joins(join_type)).await.unwrap().0);
+        }
+        db.create_index("orders", "customer").await.unwrap();
+        db.create_index("customers", "id").await.unwrap();
+        for (join_type, before) in [JoinType::Inner, JoinType::Left, JoinType::Right].into_iter().zip(before) {
+            let (after, _) = db.select("customers", &joins(join_type)).await.unwrap();
+            assert_eq!(before, after);
+        }
+
+        let (inner, _) = db.select("customers", &joins(JoinType::Inner)).await.unwrap();
+        assert_eq!(inner.len(), 29 * 4 + 28);
+        let (right, _) = db.select("customers", &joins(JoinType::Right)).await.unwrap();
+        assert_eq!(right.len(), 200);
+        assert_eq!(right[5].get("order_id"), Some(&Value::Integer(5)));
+        assert_eq!(right[5].get("region"), Some(&Value::Null));
+        let _ = std::fs::remove_file("test_joins_strategies.wal");
+    }
+
+    #[tokio::test]
+    async fn test_explain_join_probes_index() {
+        let pool = create_pool([0; 32], "test_joins_explain.wal").unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "customers".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "orders".to_string(),
+            vec![
+                Column::new("order_id".to_string(), DataType::Integer, vec![]),
+                Column::new("customer".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for id in 0..3 {
+            db.insert("customers", HashMap::from([("id".to_string(), Value::Integer(id))])).await.unwrap();
+        }
+        // A customer row without an id joins the orders without a customer.
+        db.insert("customers", HashMap::new()).await.unwrap();
+        for order_id in 0..100 {
+            let mut order = HashMap::from([("order_id".to_string(), Value::Integer(order_id))]);
+            if order_id % 10 != 0 {
+                order.insert("customer".to_string(), Value::Integer(order_id % 4));
+            }
+            db.insert("orders", order).await.unwrap();
+        }
+
+        let join = Join::new(JoinType::Inner, "orders", ("id", "customer"));
+        let hashed = db.explain_join("customers", &join, None).await.unwrap();
+        assert_eq!(hashed.method, JoinMethod::Hash);
+        assert_eq!(hashed.rows, (4, 100));
+        let (before, _) = db.select("customers", &Query::Join(join.clone())).await.unwrap();
+
+        db.create_index("orders", "customer").await.unwrap();
+        let probed = db.explain_join("customers", &join, None).await.unwrap();
+        assert_eq!(probed.method, JoinMethod::IndexLookup);
+        assert_eq!(probed.driving_table, "customers");
+        assert_eq!(probed.probed_table, "orders");
+        assert_eq!(probed.index.as_deref(), Some("customer"));
+        let (after, _) = db.select("customers", &Query::Join(join)).await.unwrap();
+        assert_eq!(before, after);
+        assert_eq!(after.len(), 65 + 10);
+        let _ = std::fs::remove_file("test_joins_explain.wal");
+    }
 }
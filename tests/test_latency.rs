@@ -0,0 +1,55 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{Column, Condition, DataType, Database, OperationKind, Operator, Query, Value};
+
+    #[tokio::test]
+    async fn test_latency_stats_per_table_and_kind() {
+        let wal_path = "test_latency_stats.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "events".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        for id in 0..20 {
+            db.insert("events", HashMap::from([("id".to_string(), Value::Integer(id))]))
+                .await
+                .unwrap();
+        }
+        let recent = Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Gte,
+            value: Value::Integer(15),
+        });
+        for _ in 0..5 {
+            db.select("events", &recent).await.unwrap();
+        }
+        db.delete("events", &recent).await.unwrap();
+        // Failures aren't timed.
+        assert!(db.insert("missing", HashMap::new()).await.is_err());
+
+        let stats = db.latency_stats();
+        let kinds: Vec<(&str, OperationKind, u64)> =
+            stats.iter().map(|s| (s.table.as_str(), s.kind, s.count)).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("events", OperationKind::Select, 5),
+                ("events", OperationKind::Insert, 20),
+                ("events", OperationKind::Delete, 1),
+            ]
+        );
+        for s in &stats {
+            assert!(s.p50 <= s.p95 && s.p95 <= s.p99 && s.p99 <= s.max, "{:?}", s);
+            assert!(s.throughput > 0.0);
+        }
+
+        db.reset_latency_stats();
+        assert!(db.latency_stats().is_empty());
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{Column, DataType, Database, DatabaseConfig, LimitError, Query, TableLimits, Value, WriteError};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn event(id: i64, payload: &str) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("payload".to_string(), Value::String(payload.to_string()));
+        row
+    }
+
+    async fn open(wal_path: &str, limits: TableLimits) -> Database {
+        let _ = fs::remove_file(wal_path);
+        let config = DatabaseConfig {
+            table_limits: HashMap::from([("events".to_string(), limits)]),
+            ..Default::default()
+        };
+        let db = Database::with_config([0; 32], wal_path, config);
+        for table in ["events", "other"] {
+            db.create_table(
+                table.to_string(),
+                vec![
+                    Column::new("id".to_string(), DataType::Integer, vec![]),
+                    Column::new("payload".to_string(), DataType::String, vec![]),
+                ],
+            )
+            .await
+            .unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_row_limits() {
+        let wal_path = "test_limits_rows.wal";
+        let limits = TableLimits {
+            max_rows: Some(2),
+            max_row_bytes: Some(1024),
+            ..Default::default()
+        };
+        let db = open(wal_path, limits).await;
+
+        let large = "x".repeat(2048);
+        match db.insert("events", event(0, &large)).await {
+            Err(WriteError::Limit(LimitError::RowTooLarge { table, size, limit })) => {
+                assert_eq!(table, "events");
+                assert!(size > 2048);
+                assert_eq!(limit, 1024);
+            }
+            other => panic!("expected RowTooLarge, got {:?}", other),
+        }
+
+        db.insert("events", event(1, "a")).await.unwrap();
+        db.insert("events", event(2, "b")).await.unwrap();
+        assert_eq!(
+            db.insert("events", event(3, "c")).await.unwrap_err(),
+            WriteError::Limit(LimitError::TooManyRows {
+                table: "events".to_string(),
+                limit: 2
+            })
+        );
+        assert_eq!(db.count("events", &Query::MatchAll).await.unwrap(), 2);
+
+        // Other tables are unaffected, and limits hold inside transactions.
+        for i in 0..5 {
+            db.insert("other", event(i, &large)).await.unwrap();
+        }
+        let mut transaction = zapdb::begin_transaction();
+        transaction.insert("events".to_string(), event(4, "d"));
+        assert!(matches!(
+            db.commit(transaction).await.unwrap_err(),
+            WriteError::Limit(LimitError::TooManyRows { limit: 2, .. })
+        ));
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_byte_limit() {
+        let wal_path = "test_limits_bytes.wal";
+        let limits = TableLimits {
+            max_bytes: Some(4096),
+            ..Default::default()
+        };
+        let db = open(wal_path, limits).await;
+
+        let payload = "x".repeat(1000);
+        let mut inserted = 0;
+        let error = loop {
+            match db.insert("events", event(inserted, &payload)).await {
+                Ok(_) => inserted += 1,
+                Err(e) => break e,
+            }
+        };
+        assert!(matches!(error, WriteError::Limit(LimitError::TooManyBytes { limit: 4096, .. })));
+        assert_eq!(inserted, 3);
+        assert!(db.memory_stats().await.data_bytes <= 4096);
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
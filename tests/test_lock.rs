@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{Database, DatabaseConfig, OpenError};
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_second_open_is_refused_until_close() {
+        let wal_path = "test_lock_second_open.wal";
+        let lock_path = "test_lock_second_open.wal.lock";
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(lock_path);
+
+        let db = Database::open([0; 32], wal_path, DatabaseConfig::default()).unwrap();
+        assert_eq!(fs::read_to_string(lock_path).unwrap(), std::process::id().to_string());
+        match Database::open([0; 32], wal_path, DatabaseConfig::default()) {
+            Err(OpenError::InUse { pid }) => assert_eq!(pid, Some(std::process::id())),
+            other => panic!("expected InUse, got {:?}", other.err()),
+        }
+
+        // The file only names the owner; the lock holds without it.
+        fs::write(lock_path, "").unwrap();
+        match Database::open([0; 32], wal_path, DatabaseConfig::default()) {
+            Err(OpenError::InUse { pid }) => assert_eq!(pid, None),
+            other => panic!("expected InUse, got {:?}", other.err()),
+        }
+
+        db.close(None).await.unwrap();
+        let db = Database::open([0; 32], wal_path, DatabaseConfig::default()).unwrap();
+        drop(db);
+        let db = Database::open([0; 32], wal_path, DatabaseConfig::default()).unwrap();
+        drop(db);
+
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(lock_path);
+    }
+
+    #[tokio::test]
+    async fn test_stale_lock_is_taken_over() {
+        let wal_path = "test_lock_stale.wal";
+        let lock_path = "test_lock_stale.wal.lock";
+        let _ = fs::remove_file(wal_path);
+
+        // A file left by a process that has exited isn't locked, whatever
+        // it holds.
+        fs::write(lock_path, "4194305").unwrap();
+        let db = Database::open([0; 32], wal_path, DatabaseConfig::default()).unwrap();
+        assert_eq!(fs::read_to_string(lock_path).unwrap(), std::process::id().to_string());
+        drop(db);
+
+        fs::write(lock_path, "not a pid").unwrap();
+        let db = Database::open([0; 32], wal_path, DatabaseConfig::default()).unwrap();
+        drop(db);
+
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(lock_path);
+    }
+}
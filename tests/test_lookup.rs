@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{create_pool, PooledConnection, Column, DataType, Value, Query, Condition, Operator, Constraint};
+    use std::collections::HashMap;
+    use std::fs;
+
+    async fn setup_db(wal_path: &str) -> PooledConnection {
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        let columns = vec![
+            Column::new("name".to_string(), DataType::String, vec![]),
+            Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+            Column::new("age".to_string(), DataType::Integer, vec![]),
+        ];
+        db.create_table("users".to_string(), columns).await.unwrap();
+
+        for (id, name, age) in [(1, "Alice", 30), (2, "Bob", 25), (3, "Charlie", 30)] {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("name".to_string(), Value::String(name.to_string()));
+            row.insert("age".to_string(), Value::Integer(age));
+            db.insert("users", row).await.unwrap();
+        }
+        db
+    }
+
+    fn age_is(age: i64) -> Query {
+        Query::Condition(Condition {
+            column: "age".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(age),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_get() {
+        let wal_path = "test_lookup_get.wal";
+        let db = setup_db(wal_path).await;
+
+        let user = db.get("users", &Value::Integer(2)).await.unwrap().unwrap();
+        assert_eq!(user.get("name"), Some(&Value::String("Bob".to_string())));
+        assert!(db.get("users", &Value::Integer(4)).await.unwrap().is_none());
+        assert!(db.get("missing", &Value::Integer(1)).await.is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_exists_count_first() {
+        let wal_path = "test_lookup_helpers.wal";
+        let db = setup_db(wal_path).await;
+
+        assert!(db.exists("users", &age_is(25)).await.unwrap());
+        assert!(!db.exists("users", &age_is(99)).await.unwrap());
+        assert_eq!(db.count("users", &age_is(30)).await.unwrap(), 2);
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 3);
+
+        let first = db.first("users", &age_is(30)).await.unwrap().unwrap();
+        assert_eq!(first.get("name"), Some(&Value::String("Alice".to_string())));
+        assert!(db.first("users", &age_is(99)).await.unwrap().is_none());
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_or_returns_rows_once_in_order() {
+        let wal_path = "test_lookup_or.wal";
+        let db = setup_db(wal_path).await;
+        let ids = |rows: Vec<zapdb::Row>| rows.iter().map(|r| r.get_i64("id").unwrap()).collect::<Vec<_>>();
+        let id_is = |id: i64| {
+            Query::Condition(Condition {
+                column: "id".to_string(),
+                operator: Operator::Eq,
+                value: Value::Integer(id),
+            })
+        };
+        let name_is = |name: &str| {
+            Query::Condition(Condition {
+                column: "name".to_string(),
+                operator: Operator::Eq,
+                value: Value::String(name.to_string()),
+            })
+        };
+
+        let indexed = Query::Or(vec![id_is(3), age_is(30), age_is(25), id_is(1)]);
+        let scanned = Query::Or(vec![name_is("Charlie"), age_is(30)]);
+        let nested = Query::Or(vec![Query::And(vec![age_is(30), id_is(3)]), id_is(2), id_is(3)]);
+        for index_age in [false, true] {
+            if index_age {
+                db.create_index("users", "age").await.unwrap();
+            }
+            assert_eq!(ids(db.select("users", &indexed).await.unwrap().0), vec![1, 2, 3]);
+            assert_eq!(ids(db.select("users", &scanned).await.unwrap().0), vec![1, 3]);
+            assert_eq!(ids(db.select("users", &nested).await.unwrap().0), vec![2, 3]);
+        }
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_index_scans_keep_insertion_order() {
+        let wal_path = "test_lookup_order.wal";
+        let db = setup_db(wal_path).await;
+        for (id, age) in [(4, 41), (5, 19), (6, 27), (7, 30)] {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("name".to_string(), Value::String(format!("user{}", id)));
+            row.insert("age".to_string(), Value::Integer(age));
+            db.insert("users", row).await.unwrap();
+        }
+        db.create_index("users", "age").await.unwrap();
+        let ids = |rows: Vec<zapdb::Row>| rows.iter().map(|r| r.get_i64("id").unwrap()).collect::<Vec<_>>();
+        let older_than = |age: i64| {
+            Query::Condition(Condition {
+                column: "age".to_string(),
+                operator: Operator::Gt,
+                value: Value::Integer(age),
+            })
+        };
+
+        for _ in 0..5 {
+            assert_eq!(ids(db.select("users", &older_than(20)).await.unwrap().0), vec![1, 2, 3, 4, 6, 7]);
+            assert_eq!(ids(db.select("users", &age_is(30)).await.unwrap().0), vec![1, 3, 7]);
+        }
+        let _ = fs::remove_file(wal_path);
+    }
+}
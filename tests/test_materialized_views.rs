@@ -0,0 +1,153 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{
+        AggregateFunction, AggregateQuery, Column, Condition, DataType, Database, Join, JoinType,
+        Operator, Query, Value,
+    };
+    use std::collections::HashMap;
+
+    fn user(id: i64, age: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("age".to_string(), Value::Integer(age));
+        row
+    }
+
+    fn condition(column: &str, operator: Operator, value: i64) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value: Value::Integer(value),
+        })
+    }
+
+    fn aggregate(function: AggregateFunction) -> Query {
+        Query::Aggregate(AggregateQuery::new(function, "age"))
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, age) in [(1, 15), (2, 30), (3, 45)] {
+            db.insert("users", user(id, age)).await.unwrap();
+        }
+        db
+    }
+
+    async fn result(db: &Database, view: &str) -> Value {
+        let (rows, _) = db.select(view, &Query::MatchAll).await.unwrap();
+        rows[0].get("result").unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn test_filter_view_is_maintained() {
+        let db = setup().await;
+        db.create_materialized_view("adults", "users", condition("age", Operator::Gte, 18))
+            .await
+            .unwrap();
+        assert_eq!(db.count("adults", &Query::MatchAll).await.unwrap(), 2);
+
+        db.insert("users", user(4, 50)).await.unwrap();
+        db.insert("users", user(5, 10)).await.unwrap();
+        assert_eq!(db.count("adults", &Query::MatchAll).await.unwrap(), 3);
+
+        db.update("users", &condition("id", Operator::Eq, 1), |row| {
+            row.insert("age".to_string(), Value::Integer(20));
+        })
+        .await
+        .unwrap();
+        db.delete("users", &condition("id", Operator::Eq, 2)).await.unwrap();
+
+        // The view is indexable and queryable like a table.
+        db.create_index("adults", "id").await.unwrap();
+        let (rows, _) = db.select("adults", &condition("id", Operator::Eq, 1)).await.unwrap();
+        assert_eq!(rows[0].get_i64("age"), Some(20));
+        let mut ids: Vec<i64> = db
+            .select("adults", &Query::MatchAll)
+            .await
+            .unwrap()
+            .0
+            .iter()
+            .filter_map(|r| r.get_i64("id"))
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3, 4]);
+        assert!(db.verify_integrity().await);
+
+        assert!(db.insert("adults", user(9, 99)).await.is_err());
+        assert!(db.delete("adults", &Query::MatchAll).await.is_err());
+
+        db.drop_materialized_view("adults").await.unwrap();
+        assert!(db.select("adults", &Query::MatchAll).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_aggregate_views_are_maintained() {
+        let db = setup().await;
+        db.create_materialized_view("user_count", "users", aggregate(AggregateFunction::Count))
+            .await
+            .unwrap();
+        db.create_materialized_view("age_sum", "users", aggregate(AggregateFunction::Sum))
+            .await
+            .unwrap();
+        db.create_materialized_view("age_avg", "users", aggregate(AggregateFunction::Avg))
+            .await
+            .unwrap();
+        db.create_materialized_view("age_max", "users", aggregate(AggregateFunction::Max))
+            .await
+            .unwrap();
+        assert_eq!(result(&db, "user_count").await, Value::Integer(3));
+        assert_eq!(result(&db, "age_max").await, Value::Integer(45));
+
+        db.insert("users", user(4, 60)).await.unwrap();
+        db.delete("users", &condition("id", Operator::Eq, 1)).await.unwrap();
+        assert_eq!(result(&db, "user_count").await, Value::Integer(3));
+        assert_eq!(result(&db, "age_sum").await, Value::Float(135.0));
+        assert_eq!(result(&db, "age_avg").await, Value::Float(45.0));
+        assert_eq!(result(&db, "age_max").await, Value::Integer(60));
+
+        // Removing the maximum forces a recompute.
+        db.delete("users", &condition("id", Operator::Eq, 4)).await.unwrap();
+        assert_eq!(result(&db, "age_max").await, Value::Integer(45));
+        assert_eq!(result(&db, "age_sum").await, Value::Float(75.0));
+    }
+
+    #[tokio::test]
+    async fn test_join_view_and_load() {
+        let db_path = "test_join_view.zap";
+        let db = setup().await;
+        db.create_table(
+            "posts".to_string(),
+            vec![
+                Column::new("post_id".to_string(), DataType::Integer, vec![]),
+                Column::new("user_id".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        let join = Query::Join(Join::new(JoinType::Inner, "posts", ("id", "user_id")));
+        db.create_materialized_view("user_posts", "users", join).await.unwrap();
+        assert_eq!(db.count("user_posts", &Query::MatchAll).await.unwrap(), 0);
+
+        let mut post = HashMap::new();
+        post.insert("post_id".to_string(), Value::Integer(100));
+        post.insert("user_id".to_string(), Value::Integer(2));
+        db.insert("posts", post).await.unwrap();
+        assert_eq!(db.count("user_posts", &Query::MatchAll).await.unwrap(), 1);
+
+        db.save(db_path).await.unwrap();
+        db.insert("users", user(4, 20)).await.unwrap();
+        db.load(db_path).await.unwrap();
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 4);
+        assert_eq!(db.count("user_posts", &Query::MatchAll).await.unwrap(), 1);
+    }
+}
@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{Column, Condition, DataType, Database, DatabaseConfig, Operator, Query, Value};
+    use std::collections::HashMap;
+
+    fn user(id: i64, name: &str) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("name".to_string(), Value::String(name.to_string()));
+        row
+    }
+
+    async fn create_users(db: &Database) {
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_memory_accounting() {
+        let db = fixtures::database();
+        create_users(&db).await;
+
+        let empty = db.memory_stats().await;
+        assert_eq!(empty.used_bytes(), 0);
+        assert_eq!(empty.pressure(), 0.0);
+
+        for i in 0..10 {
+            db.insert("users", user(i, "someone")).await.unwrap();
+        }
+        let stats = db.memory_stats().await;
+        assert!(stats.data_bytes > 0);
+        assert_eq!(stats.index_bytes, 0);
+
+        db.create_index("users", "id").await.unwrap();
+        assert!(db.memory_stats().await.index_bytes > 0);
+
+        db.delete("users", &Query::MatchAll).await.unwrap();
+        let stats = db.memory_stats().await;
+        assert_eq!(stats.data_bytes, 0);
+        assert_eq!(stats.index_bytes, 0);
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit_evicts_cold_indexes() {
+        let probe = fixtures::database();
+        create_users(&probe).await;
+        probe.insert("users", user(0, "someone")).await.unwrap();
+        let row_bytes = probe.memory_stats().await.data_bytes;
+
+        // Room for the rows and one index, but not both.
+        let config = DatabaseConfig {
+            memory_limit: Some(row_bytes * 25),
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        create_users(&db).await;
+        db.create_index("users", "id").await.unwrap();
+        db.create_index("users", "name").await.unwrap();
+
+        // Use the id index so the name index is the colder one.
+        let by_id = Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(1),
+        });
+        for i in 0..15 {
+            db.insert("users", user(i, "someone")).await.unwrap();
+            db.count("users", &by_id).await.unwrap();
+        }
+        let stats = db.memory_stats().await;
+        assert_eq!(stats.evicted_indexes, 1);
+        assert!(stats.used_bytes() <= row_bytes * 25);
+        assert_eq!(db.count("users", &by_id).await.unwrap(), 1);
+
+        let by_name = Query::Condition(Condition {
+            column: "name".to_string(),
+            operator: Operator::Eq,
+            value: Value::String("someone".to_string()),
+        });
+        assert_eq!(db.count("users", &by_name).await.unwrap(), 15);
+    }
+
+    #[tokio::test]
+    async fn test_memory_limit_rejects_writes() {
+        let config = DatabaseConfig {
+            memory_limit: Some(1024),
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        create_users(&db).await;
+
+        let mut inserted = 0;
+        let err = loop {
+            match db.insert("users", user(inserted, "someone")).await {
+                Ok(_) => inserted += 1,
+                Err(e) => break e,
+            }
+        };
+        assert!(err.to_string().contains("Memory limit"));
+        assert!(inserted > 0);
+
+        let stats = db.memory_stats().await;
+        assert_eq!(stats.rejected_writes, 1);
+        assert!(stats.used_bytes() <= 1024);
+        assert!(stats.pressure() <= 1.0);
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), inserted as usize);
+    }
+}
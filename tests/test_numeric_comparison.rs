@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{Column, Condition, DataType, Database, Operator, Query, Value};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn condition(column: &str, operator: Operator, value: Value) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value,
+        })
+    }
+
+    async fn ids(db: &Database, query: Query) -> Vec<i64> {
+        let (rows, _) = db.select("people", &query).await.unwrap();
+        let mut ids: Vec<i64> = rows.iter().map(|r| r.get_i64("id").unwrap()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_value_ordering() {
+        assert!(Value::Integer(30) > Value::Float(29.5));
+        assert!(Value::Float(29.5) < Value::Integer(30));
+        assert!(Value::Integer(29) < Value::Float(29.5));
+        assert_eq!(
+            Value::Integer(5).partial_cmp(&Value::Float(5.0)),
+            Some(std::cmp::Ordering::Equal)
+        );
+        // No rounding through f64: 2^53 + 1 is greater than the float 2^53.
+        assert!(Value::Integer((1 << 53) + 1) > Value::Float((1u64 << 53) as f64));
+        assert!(Value::Integer(i64::MAX) < Value::Float(1e19));
+        assert!(Value::Integer(i64::MIN) > Value::Float(-1e19));
+        assert_eq!(Value::Integer(1).partial_cmp(&Value::Float(f64::NAN)), None);
+        assert_eq!(Value::Integer(1).partial_cmp(&Value::String("1".to_string())), None);
+    }
+
+    #[tokio::test]
+    async fn test_cross_type_conditions() {
+        let wal_path = "test_cross_type_conditions.wal";
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "people".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+                Column::new("score".to_string(), DataType::Float, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, age, score) in [(1, 25, 1.0), (2, 30, 2.5), (3, 35, 3.0)] {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("age".to_string(), Value::Integer(age));
+            row.insert("score".to_string(), Value::Float(score));
+            db.insert("people", row).await.unwrap();
+        }
+
+        // The same results with and without indexes.
+        for indexed in [false, true] {
+            if indexed {
+                db.create_index("people", "age").await.unwrap();
+                db.create_index("people", "score").await.unwrap();
+            }
+            assert_eq!(ids(&db, condition("age", Operator::Gt, Value::Float(29.5))).await, vec![2, 3]);
+            assert_eq!(ids(&db, condition("age", Operator::Lte, Value::Float(30.0))).await, vec![1, 2]);
+            assert_eq!(ids(&db, condition("age", Operator::Eq, Value::Float(30.0))).await, vec![2]);
+            assert!(ids(&db, condition("age", Operator::Eq, Value::Float(30.5))).await.is_empty());
+            assert_eq!(ids(&db, condition("age", Operator::NotEq, Value::Float(30.0))).await, vec![1, 3]);
+            assert_eq!(ids(&db, condition("score", Operator::Eq, Value::Integer(3))).await, vec![3]);
+            assert_eq!(ids(&db, condition("score", Operator::Lt, Value::Integer(3))).await, vec![1, 2]);
+            assert!(ids(&db, condition("age", Operator::Gt, Value::Float(f64::NAN))).await.is_empty());
+        }
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
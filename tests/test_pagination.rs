@@ -0,0 +1,131 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{create_pool, Column, DataType, Value, Query, Condition, Operator, SortOrder};
+    use std::collections::HashMap;
+    use std::fs;
+
+    #[tokio::test]
+    async fn test_select_page() {
+        let wal_path = "test_select_page.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "events".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("kind".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        // Insert out of order so the page order comes from the cursor column.
+        for id in [5, 1, 4, 2, 3, 6, 7] {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            let kind = if id == 6 { "skip" } else { "keep" };
+            row.insert("kind".to_string(), Value::String(kind.to_string()));
+            db.insert("events", row).await.unwrap();
+        }
+
+        let query = Query::Condition(Condition {
+            column: "kind".to_string(),
+            operator: Operator::Eq,
+            value: Value::String("keep".to_string()),
+        });
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = db.select_page("events", &query, "id", cursor.as_ref(), 2).await.unwrap();
+            assert!(page.rows.len() <= 2);
+            seen.extend(page.rows.iter().map(|r| r.get("id").cloned().unwrap()));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+            // Rows inserted behind the cursor don't shift later pages.
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(0));
+            row.insert("kind".to_string(), Value::String("keep".to_string()));
+            db.insert("events", row).await.unwrap();
+        }
+
+        let expected: Vec<Value> = [1, 2, 3, 4, 5, 7].into_iter().map(Value::Integer).collect();
+        assert_eq!(seen, expected);
+        assert!(db.select_page("events", &query, "missing", None, 2).await.is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_select_top() {
+        let wal_path = "test_select_top.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "employees".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("salary".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for id in 0..100 {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            // Salaries repeat, and one employee has none.
+            if id != 50 {
+                row.insert("salary".to_string(), Value::Integer((id * 37) % 20));
+            }
+            db.insert("employees", row).await.unwrap();
+        }
+
+        let ids = |rows: Vec<zapdb::Row>| -> Vec<(i64, i64)> {
+            rows.iter()
+                .map(|r| (r.get_i64("salary").unwrap(), r.get_i64("id").unwrap()))
+                .collect()
+        };
+        let (all, _) = db.select("employees", &Query::MatchAll).await.unwrap();
+        let mut expected: Vec<(i64, i64)> = all
+            .iter()
+            .filter_map(|r| Some((r.get_i64("salary")?, r.get_i64("id").unwrap())))
+            .collect();
+        expected.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+
+        let top = db
+            .select_top("employees", &Query::MatchAll, "salary", SortOrder::Descending, 10)
+            .await
+            .unwrap();
+        assert_eq!(ids(top), expected[..10]);
+
+        let low = Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Lt,
+            value: Value::Integer(10),
+        });
+        let bottom = db
+            .select_top("employees", &low, "salary", SortOrder::Ascending, 3)
+            .await
+            .unwrap();
+        assert_eq!(ids(bottom), vec![(0, 0), (2, 6), (5, 5)]);
+
+        let everything = db
+            .select_top("employees", &Query::MatchAll, "salary", SortOrder::Descending, 1000)
+            .await
+            .unwrap();
+        assert_eq!(ids(everything), expected);
+        assert!(db
+            .select_top("employees", &Query::MatchAll, "salary", SortOrder::Ascending, 0)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .select_top("employees", &Query::MatchAll, "missing", SortOrder::Ascending, 1)
+            .await
+            .is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,151 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use chrono::{TimeZone, Utc};
+    use zapdb::{
+        Column, Condition, DataType, Database, Operator, Partition, Partitioning, Period, Query, Value,
+    };
+    use std::collections::HashMap;
+
+    fn at(month: u32, day: u32) -> Value {
+        Value::DateTime(Utc.with_ymd_and_hms(2024, month, day, 12, 0, 0).unwrap())
+    }
+
+    fn event(id: i64, month: u32, day: u32) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("at".to_string(), at(month, day));
+        row
+    }
+
+    fn condition(column: &str, operator: Operator, value: Value) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value,
+        })
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "events".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("at".to_string(), DataType::DateTime, vec![]),
+                Column::new("region".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn ids(db: &Database, query: &Query) -> Vec<i64> {
+        let (rows, _) = db.select("events", query).await.unwrap();
+        rows.iter().map(|r| r.get_i64("id").unwrap()).collect()
+    }
+
+    // The partition of a month, keyed by its first day.
+    fn month(month: u32, rows: usize) -> Partition {
+        Partition {
+            key: Value::DateTime(Utc.with_ymd_and_hms(2024, month, 1, 0, 0, 0).unwrap()),
+            rows,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_time_partitions() {
+        let snapshot_path = "test_time_partitions.zap";
+        let db = setup().await;
+        db.insert("events", event(1, 1, 5)).await.unwrap();
+        let by_month = Partitioning::Time {
+            column: "at".to_string(),
+            period: Period::Month,
+        };
+        db.partition_table("events", by_month.clone()).await.unwrap();
+        for (id, (m, d)) in [(2, (3, 1)), (3, (1, 31)), (4, (2, 14)), (5, (3, 31))] {
+            db.insert("events", event(id, m, d)).await.unwrap();
+        }
+        assert_eq!(
+            db.partitions("events").await.unwrap(),
+            vec![month(1, 2), month(2, 1), month(3, 2)]
+        );
+
+        // Pruned scans find the same rows, in insertion order.
+        assert_eq!(ids(&db, &condition("at", Operator::Gte, at(2, 1))).await, vec![2, 4, 5]);
+        assert_eq!(ids(&db, &condition("at", Operator::Lt, at(2, 14))).await, vec![1, 3]);
+        assert_eq!(ids(&db, &condition("at", Operator::Eq, at(3, 31))).await, vec![5]);
+        assert_eq!(ids(&db, &condition("at", Operator::NotEq, at(3, 31))).await, vec![1, 2, 3, 4]);
+
+        // Moving a row moves it between partitions.
+        db.update("events", &condition("id", Operator::Eq, Value::Integer(4)), |row| {
+            row.insert("at".to_string(), Value::Null);
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            db.partitions("events").await.unwrap(),
+            vec![
+                Partition {
+                    key: Value::Null,
+                    rows: 1
+                },
+                month(1, 2),
+                month(3, 2)
+            ]
+        );
+
+        assert_eq!(db.drop_partition("events", &month(1, 0).key).await.unwrap(), 2);
+        assert_eq!(db.drop_partition("events", &month(1, 0).key).await.unwrap(), 0);
+        assert_eq!(ids(&db, &Query::MatchAll).await, vec![2, 4, 5]);
+
+        // The partitioning is saved, and drops are replayed.
+        db.save(snapshot_path).await.unwrap();
+        db.drop_partition("events", &Value::Null).await.unwrap();
+        let storage = db.storage();
+        drop(db);
+        let restored = fixtures::reopen(storage);
+        restored.load(snapshot_path).await.unwrap();
+        assert_eq!(restored.partitions("events").await.unwrap(), vec![month(3, 2)]);
+        assert_eq!(ids(&restored, &condition("at", Operator::Gt, at(3, 1))).await, vec![5]);
+    }
+
+    #[tokio::test]
+    async fn test_key_partitions() {
+        let db = setup().await;
+        let by_region = Partitioning::Key {
+            column: "region".to_string(),
+        };
+        db.partition_table("events", by_region).await.unwrap();
+        for (id, region) in [(1, "eu"), (2, "us"), (3, "eu"), (4, "ap")] {
+            let mut row = event(id, 1, 1);
+            row.insert("region".to_string(), Value::String(region.to_string()));
+            db.insert("events", row).await.unwrap();
+        }
+        let eu = condition("region", Operator::Eq, Value::String("eu".to_string()));
+        assert_eq!(ids(&db, &eu).await, vec![1, 3]);
+        let keys: Vec<Value> = db.partitions("events").await.unwrap().into_iter().map(|p| p.key).collect();
+        assert_eq!(keys, vec![Value::String("ap".to_string()), Value::String("eu".to_string()), Value::String("us".to_string())]);
+
+        // A partition goes away with its last row.
+        db.delete("events", &condition("id", Operator::Eq, Value::Integer(4))).await.unwrap();
+        assert_eq!(db.partitions("events").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_partitioning_errors() {
+        let db = setup().await;
+        assert!(db.partitions("events").await.is_err());
+        let by_id = Partitioning::Time {
+            column: "id".to_string(),
+            period: Period::Day,
+        };
+        assert!(db.partition_table("events", by_id).await.is_err());
+        let missing = Partitioning::Key {
+            column: "missing".to_string(),
+        };
+        assert!(db.partition_table("events", missing).await.is_err());
+        assert!(db.drop_partition("events", &Value::Integer(1)).await.is_err());
+    }
+}
@@ -0,0 +1,199 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{
+        Column, Condition, DataType, Database, DatabaseConfig, Expr, Join, JoinType, Operator, Query, RolePermissions,
+        SessionContext, UpdateExpr, Value,
+    };
+
+    async fn setup() -> Database {
+        let config = DatabaseConfig {
+            roles: HashMap::from([(
+                "support".to_string(),
+                RolePermissions::new().hide("users", &["password_hash"]).read_only("users", &["email"]),
+            )]),
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("email".to_string(), DataType::String, vec![]),
+                Column::new("password_hash".to_string(), DataType::String, vec![]),
+                Column::new("visits".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "orders".to_string(),
+            vec![
+                Column::new("user_id".to_string(), DataType::Integer, vec![]),
+                Column::new("total".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        let user = HashMap::from([
+            ("id".to_string(), Value::Integer(1)),
+            ("email".to_string(), Value::String("a@example.com".to_string())),
+            ("password_hash".to_string(), Value::String("secret".to_string())),
+            ("visits".to_string(), Value::Integer(0)),
+        ]);
+        db.insert("users", user).await.unwrap();
+        let order = HashMap::from([("user_id".to_string(), Value::Integer(1)), ("total".to_string(), Value::Integer(20))]);
+        db.insert("orders", order).await.unwrap();
+        db
+    }
+
+    fn act_as(db: &Database, role: Option<&str>) {
+        db.set_session(SessionContext {
+            role: role.map(|r| r.to_string()),
+            ..Default::default()
+        });
+    }
+
+    fn by_id(id: i64) -> Query {
+        Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(id),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_hidden_columns_are_left_out() {
+        let db = setup().await;
+        act_as(&db, Some("support"));
+
+        let (rows, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].get("password_hash").is_none());
+        assert!(rows[0].get("email").is_some());
+
+        let join = Query::Join(Join::new(JoinType::Inner, "orders", ("id", "user_id")).prefixed());
+        let (rows, _) = db.select("users", &join).await.unwrap();
+        assert!(rows[0].get("users.password_hash").is_none());
+        assert!(rows[0].get("orders.total").is_some());
+
+        // Filtering or projecting on a hidden column would give it away.
+        let by_hash = Query::Condition(Condition {
+            column: "password_hash".to_string(),
+            operator: Operator::Eq,
+            value: Value::String("secret".to_string()),
+        });
+        assert!(db.select("users", &by_hash).await.is_err());
+        let projected = db
+            .project("users", &Query::MatchAll, &[("hash", Expr::column("password_hash"))])
+            .await;
+        assert!(projected.is_err());
+        assert!(db
+            .export_bundle(&["users"], &HashMap::new(), "test_hidden_columns.bundle", &[9; 32])
+            .await
+            .is_err());
+
+        act_as(&db, None);
+        let (rows, _) = db.select("users", &by_hash).await.unwrap();
+        assert_eq!(rows[0].get("password_hash"), Some(&Value::String("secret".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_protected_columns_are_not_written() {
+        let db = setup().await;
+        act_as(&db, Some("support"));
+
+        let row = HashMap::from([
+            ("id".to_string(), Value::Integer(2)),
+            ("email".to_string(), Value::String("b@example.com".to_string())),
+        ]);
+        assert!(db.insert("users", row).await.is_err());
+        let set_email = UpdateExpr::Set(vec![("email".to_string(), Value::String("c@example.com".to_string()))]);
+        assert!(db.update_with("users", &by_id(1), set_email).await.is_err());
+
+        // Update functions don't see hidden columns, and can't change
+        // protected ones.
+        let rejected = db
+            .update(
+                "users",
+                &by_id(1),
+                |row| {
+                    row.insert("email".to_string(), Value::String("c@example.com".to_string()));
+                },
+            )
+            .await;
+        assert!(rejected.is_err());
+        db.update(
+            "users",
+            &by_id(1),
+            |row| {
+                assert!(!row.contains_key("password_hash"));
+                row.insert("visits".to_string(), Value::Integer(1));
+            },
+        )
+        .await
+        .unwrap();
+        db.increment("users", &by_id(1), "visits", Value::Integer(1)).await.unwrap();
+
+        act_as(&db, None);
+        let (rows, _) = db.select("users", &by_id(1)).await.unwrap();
+        assert_eq!(rows[0].get("visits"), Some(&Value::Integer(2)));
+        assert_eq!(rows[0].get("email"), Some(&Value::String("a@example.com".to_string())));
+        assert_eq!(rows[0].get("password_hash"), Some(&Value::String("secret".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_join_filters_cant_read_hidden_columns() {
+        let db = setup().await;
+        let join = Query::Join(Join::new(JoinType::Inner, "orders", ("id", "user_id")));
+        db.create_view("uo", "users", join.clone()).await.unwrap();
+        act_as(&db, Some("support"));
+
+        // Whether a filter on the hidden column matches would give its
+        // value away, through a view or a SQL join alike.
+        let by_hash = Query::Condition(Condition {
+            column: "password_hash".to_string(),
+            operator: Operator::Eq,
+            value: Value::String("secret".to_string()),
+        });
+        assert!(db.select("uo", &by_hash).await.is_err());
+        let (rows, _) = db.select("uo", &by_id(1)).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].get("password_hash").is_none());
+        let sql = "SELECT u.id FROM users u JOIN orders o ON u.id = o.user_id WHERE u.password_hash = 'secret'";
+        assert!(db.execute_sql(sql).await.is_err());
+        assert!(db
+            .execute_sql("SELECT u.id FROM users u JOIN orders o ON u.id = o.user_id WHERE o.total = 20")
+            .await
+            .is_ok());
+
+        // Nor can the role copy the column into a table it can read.
+        assert!(db.create_materialized_view("copy", "users", Query::MatchAll).await.is_err());
+        assert!(db.create_materialized_view("joined", "orders", Query::MatchAll).await.is_ok());
+
+        act_as(&db, None);
+        let (rows, _) = db.select("uo", &by_hash).await.unwrap();
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_history_leaves_out_hidden_columns() {
+        let db = setup().await;
+        db.enable_history("users").await.unwrap();
+        db.update_with("users", &by_id(1), UpdateExpr::Set(vec![("visits".to_string(), Value::Integer(3))]))
+            .await
+            .unwrap();
+
+        act_as(&db, Some("support"));
+        let history = db.history("users", &Value::Integer(1)).unwrap();
+        assert_eq!(history.len(), 1);
+        let after = history[0].after.as_ref().unwrap();
+        assert!(after.get("password_hash").is_none());
+        assert_eq!(after.get("visits"), Some(&Value::Integer(3)));
+
+        act_as(&db, None);
+        let history = db.history("users", &Value::Integer(1)).unwrap();
+        assert!(history[0].after.as_ref().unwrap().get("password_hash").is_some());
+    }
+}
@@ -0,0 +1,63 @@
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use zapdb::{create_pool_with_options, create_pool_with_session, DatabaseConfig, PoolConfig, SessionContext};
+
+    #[test]
+    fn test_pool_session_context() {
+        let session = SessionContext {
+            role: Some("reader".to_string()),
+            statement_timeout: Some(Duration::from_secs(5)),
+        };
+        let pool = create_pool_with_session([0; 32], "test_pool_session_context.wal", DatabaseConfig::default(), session.clone())
+            .unwrap();
+
+        let conn = pool.get().unwrap();
+        assert_eq!(conn.session(), session);
+        conn.set_session(SessionContext {
+            role: Some("admin".to_string()),
+            ..session.clone()
+        });
+        assert_eq!(conn.session().role.as_deref(), Some("admin"));
+        drop(conn);
+
+        // Every checkout starts from the pool's context again.
+        let conns: Vec<_> = (0..3).map(|_| pool.get().unwrap()).collect();
+        assert!(conns.iter().all(|conn| conn.session() == session));
+
+        drop(conns);
+        drop(pool);
+        let _ = std::fs::remove_file("test_pool_session_context.wal");
+    }
+
+    #[tokio::test]
+    async fn test_pool_limits_and_metrics() {
+        let wal_path = "test_pool_limits_and_metrics.wal";
+        let options = PoolConfig {
+            max_connections: 2,
+            acquire_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let pool = create_pool_with_options([0; 32], wal_path, DatabaseConfig::default(), options).unwrap();
+        assert_eq!(pool.max_connections(), 2);
+
+        let first = pool.get().unwrap();
+        let second = pool.get().unwrap();
+        assert!(pool.get().is_err());
+        let metrics = pool.metrics();
+        assert_eq!((metrics.connections, metrics.in_use, metrics.idle), (2, 2, 0));
+        assert_eq!((metrics.acquired, metrics.timeouts, metrics.waiters), (2, 1, 0));
+
+        // A connection closed while checked out is replaced on the next checkout.
+        first.close(None).await.unwrap();
+        drop(first);
+        drop(second);
+        for _ in 0..2 {
+            assert!(!pool.get().unwrap().is_closed());
+        }
+        assert_eq!(pool.metrics().in_use, 0);
+
+        drop(pool);
+        let _ = std::fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{col, Column, Condition, DataType, Database, Operator, Query, Value};
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, name, age) in [(1, "Alice", 34), (2, "Bob", 27), (3, "Carol", 41), (4, "Dave", 19)] {
+            let row = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("name".to_string(), Value::String(name.to_string())),
+                ("age".to_string(), Value::Integer(age)),
+            ]);
+            db.insert("users", row).await.unwrap();
+        }
+        db
+    }
+
+    fn names(rows: &[zapdb::Row]) -> Vec<&str> {
+        let mut names: Vec<&str> = rows.iter().map(|r| r.get_str("name").unwrap()).collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_execute_prepared() {
+        let db = setup().await;
+        let prepared = db
+            .prepare("users", col("age").gte(Value::Param(0)).and(col("name").ne(Value::Param(1))))
+            .await
+            .unwrap();
+        assert_eq!(prepared.param_count(), 2);
+
+        let (rows, _) = db
+            .execute_prepared(&prepared, &[Value::Integer(30), Value::String("Carol".to_string())])
+            .await
+            .unwrap();
+        assert_eq!(names(&rows), ["Alice"]);
+        let (rows, _) = db
+            .execute_prepared(&prepared, &[Value::Integer(20), Value::String("Dave".to_string())])
+            .await
+            .unwrap();
+        assert_eq!(names(&rows), ["Alice", "Bob", "Carol"]);
+
+        // A new index retires the plan; the next run plans again.
+        db.create_index("users", "name").await.unwrap();
+        let (rows, _) = db
+            .execute_prepared(&prepared, &[Value::Integer(0), Value::String("Bob".to_string())])
+            .await
+            .unwrap();
+        assert_eq!(names(&rows), ["Alice", "Carol", "Dave"]);
+
+        assert!(db.execute_prepared(&prepared, &[Value::Integer(30)]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unbound_and_invalid_params() {
+        let db = setup().await;
+
+        // A template can't be run without its values.
+        let template = col("age").eq(Value::Param(0));
+        assert!(db.select("users", &template).await.is_err());
+
+        let pattern = Query::Condition(Condition {
+            column: "name".to_string(),
+            operator: Operator::Regex,
+            value: Value::Param(0),
+        });
+        let prepared = db.prepare("users", pattern).await.unwrap();
+        let (rows, _) = db.execute_prepared(&prepared, &[Value::String("^[AB]".to_string())]).await.unwrap();
+        assert_eq!(names(&rows), ["Alice", "Bob"]);
+        assert!(db.execute_prepared(&prepared, &[Value::String("(".to_string())]).await.is_err());
+
+        assert!(db.prepare("missing", Query::MatchAll).await.is_err());
+    }
+}
@@ -0,0 +1,148 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{create_pool, Column, DataType, Value, Query, Condition, Operator, Params};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn columns() -> Vec<Column> {
+        vec![
+            Column::new("id".to_string(), DataType::Integer, vec![]),
+            Column::new("name".to_string(), DataType::String, vec![]),
+            Column::new("salary".to_string(), DataType::Float, vec![]),
+        ]
+    }
+
+    #[test]
+    fn test_query_from_json() {
+        let json = r#"{"and": [
+            {"condition": {"column": "salary", "op": "gte", "value": 50000}},
+            {"condition": {"column": "name", "op": "eq", "value": "Alice"}}
+        ]}"#;
+        let query = Query::from_json(json, &columns()).unwrap();
+        match &query {
+            Query::And(queries) => match &queries[0] {
+                Query::Condition(condition) => assert_eq!(condition.value, Value::Float(50000.0)),
+                other => panic!("unexpected query {:?}", other),
+            },
+            other => panic!("unexpected query {:?}", other),
+        }
+
+        // Round trip through the serialized form.
+        let reparsed = Query::from_json(&query.to_json().to_string(), &columns()).unwrap();
+        assert_eq!(reparsed.to_json(), query.to_json());
+
+        let aggregate = r#"{"aggregate": {"function": "avg", "column": "salary", "filter": "match_all"}}"#;
+        assert!(Query::from_json(aggregate, &columns()).is_ok());
+
+        let sample = Query::from_json(r#"{"sample": {"query": "match_all", "fraction": 0.1, "seed": 7}}"#, &columns()).unwrap();
+        let reparsed = Query::from_json(&sample.to_json().to_string(), &columns()).unwrap();
+        assert_eq!(reparsed.to_json(), sample.to_json());
+    }
+
+    #[test]
+    fn test_query_from_json_with_params() {
+        let template = r#"{"and": [
+            {"condition": {"column": "name", "op": "eq", "param": "name"}},
+            {"compare": {"left": {"column": "salary"}, "op": "gt", "right": {"param": "min_salary"}}}
+        ]}"#;
+        // Input that would break out of a string if written into the JSON is
+        // just a value here.
+        let name = r#"Alice"}}, "match_all", {"condition": {"column": "id", "op": "gt", "value": 0"#;
+        let params = Params::from([
+            ("name".to_string(), Value::String(name.to_string())),
+            ("min_salary".to_string(), Value::Integer(50000)),
+        ]);
+        let query = Query::from_json_with_params(template, &columns(), &params).unwrap();
+        match &query {
+            Query::And(queries) => match &queries[0] {
+                Query::Condition(condition) => assert_eq!(condition.value, Value::String(name.to_string())),
+                other => panic!("unexpected query {:?}", other),
+            },
+            other => panic!("unexpected query {:?}", other),
+        }
+
+        let cases = [
+            (r#"{"condition": {"column": "id", "op": "eq", "param": "missing"}}"#, "missing parameter missing"),
+            (r#"{"condition": {"column": "id", "op": "eq", "param": "name"}}"#, "expected Integer, got String"),
+            (r#"{"condition": {"column": "id", "op": "eq", "value": 1}}"#, "literal values aren't allowed"),
+            (r#"{"condition": {"column": "id", "op": "eq", "value": 1, "param": "name"}}"#, "one of value or param"),
+        ];
+        for (json, expected) in cases {
+            let err = Query::from_json_with_params(json, &columns(), &params).unwrap_err();
+            assert!(err.contains(expected), "{} -> {}", json, err);
+        }
+        // Params aren't bound outside a parameterized query.
+        let err = Query::from_json(template, &columns()).unwrap_err();
+        assert!(err.contains("missing parameter name"), "{}", err);
+    }
+
+    #[test]
+    fn test_query_from_json_validation() {
+        let cases = [
+            (r#"{"condition": {"column": "age", "op": "eq", "value": 1}}"#, "column age not found"),
+            (r#"{"condition": {"column": "id", "op": "between", "value": 1}}"#, "unknown operator"),
+            (r#"{"condition": {"column": "id", "op": "eq", "value": "one"}}"#, "$.condition.value"),
+            (r#"{"or": [{"condition": {"column": "id", "op": "eq", "value": 1, "extra": 2}}]}"#, "$.or[0].condition: unknown field extra"),
+            (r#"{"condition": {}, "and": []}"#, "single key"),
+            (r#"{"select": {}}"#, "unknown query type"),
+            (r#"{"sample": {"query": "match_all", "rows": 1, "fraction": 0.5, "seed": 1}}"#, "one of fraction or rows"),
+            (r#"{"aggregate": {"function": "sum", "column": "*"}}"#, "column * not found"),
+        ];
+        for (json, expected) in cases {
+            let err = Query::from_json(json, &columns()).unwrap_err();
+            assert!(err.contains(expected), "{} -> {}", json, err);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_database_query_from_json() {
+        let wal_path = "test_query_from_json.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table("users".to_string(), columns()).await.unwrap();
+        db.create_table(
+            "posts".to_string(),
+            vec![Column::new("user_id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(1));
+        row.insert("name".to_string(), Value::String("Alice".to_string()));
+        db.insert("users", row).await.unwrap();
+
+        let query = db
+            .query_from_json("users", r#"{"condition": {"column": "name", "op": "eq", "value": "Alice"}}"#)
+            .await
+            .unwrap();
+        let (rows, _) = db.select("users", &query).await.unwrap();
+        assert_eq!(rows.len(), 1);
+
+        let count = db
+            .query_from_json("users", r#"{"aggregate": {"function": "count", "column": "*"}}"#)
+            .await
+            .unwrap();
+        let (rows, _) = db.select("users", &count).await.unwrap();
+        assert_eq!(rows[0].get("result"), Some(&Value::Integer(1)));
+
+        let join = r#"{"join": {"type": "left", "table": "posts", "on": ["id", "user_id"]}}"#;
+        assert!(db.query_from_json("users", join).await.is_ok());
+        let bad_join = r#"{"join": {"type": "left", "table": "posts", "on": ["id", "author_id"]}}"#;
+        assert!(db.query_from_json("users", bad_join).await.is_err());
+        let missing_table = r#"{"join": {"type": "left", "table": "comments", "on": ["id", "user_id"]}}"#;
+        assert!(db.query_from_json("users", missing_table).await.is_err());
+
+        let condition = Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::NotEq,
+            value: Value::Integer(1),
+        });
+        assert_eq!(
+            condition.to_json(),
+            serde_json::json!({"condition": {"column": "id", "op": "not_eq", "value": 1}})
+        );
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{col, query, Column, Condition, DataType, Database, Operator, Query, QueryBuilder, Value};
+
+    fn condition(column: &str, operator: Operator, value: Value) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value,
+        })
+    }
+
+    #[test]
+    fn test_builder_makes_flat_queries() {
+        let built = QueryBuilder::table("users")
+            .filter(col("age").gte(25))
+            .and(col("name").eq("Alice"))
+            .and(col("score").lt(0.5).or(col("admin").eq(true)))
+            .build();
+        let expected = Query::And(vec![
+            condition("age", Operator::Gte, Value::Integer(25)),
+            condition("name", Operator::Eq, Value::String("Alice".to_string())),
+            Query::Or(vec![
+                condition("score", Operator::Lt, Value::Float(0.5)),
+                condition("admin", Operator::Eq, Value::Boolean(true)),
+            ]),
+        ]);
+        assert_eq!(format!("{:?}", built), format!("{:?}", expected));
+        assert_eq!(
+            format!("{:?}", col("age").gte(25).and(col("name").eq("Alice"))),
+            format!("{:?}", query!(age >= 25 && name == "Alice"))
+        );
+
+        // Nothing to filter on matches everything, and `or` with it too.
+        assert_eq!(format!("{:?}", QueryBuilder::table("users").build()), "MatchAll");
+        assert_eq!(
+            format!("{:?}", QueryBuilder::table("users").filter(col("id").eq(1)).or(Query::MatchAll).build()),
+            "MatchAll"
+        );
+        let ors = col("a").eq(1).or(col("b").eq(2)).or(col("c").eq(3).or(col("d").eq(4)));
+        assert!(matches!(ors, Query::Or(ref queries) if queries.len() == 4));
+    }
+
+    #[tokio::test]
+    async fn test_fetch() {
+        let wal_path = "test_query_builder_fetch.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, name, age) in [(1, "Alice", 34), (2, "Bob", 27), (3, "Carol", 41), (4, "Dave", 19), (5, "Erin", 30)] {
+            let row = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("name".to_string(), Value::String(name.to_string())),
+                ("age".to_string(), Value::Integer(age)),
+            ]);
+            db.insert("users", row).await.unwrap();
+        }
+
+        let builder = QueryBuilder::table("users")
+            .filter(col("age").gte(25))
+            .and(col("name").ne("Erin"))
+            .order_by_desc("age")
+            .limit(2)
+            .columns(&["name"]);
+        let (rows, _) = db.fetch(&builder).await.unwrap();
+        let names: Vec<&str> = rows.iter().map(|r| r.get_str("name").unwrap()).collect();
+        assert_eq!(names, ["Carol", "Alice"]);
+        assert_eq!(rows[0].get("age"), None);
+
+        let builder = QueryBuilder::table("users").filter(col("id").is_in([2, 4, 5])).order_by("name").offset(1);
+        let (rows, _) = db.fetch(&builder).await.unwrap();
+        let names: Vec<&str> = rows.iter().map(|r| r.get_str("name").unwrap()).collect();
+        assert_eq!(names, ["Dave", "Erin"]);
+
+        let (rows, _) = db.select("users", &col("age").between(27, 30)).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        let (rows, _) = db.select("users", &col("name").like("_a%")).await.unwrap();
+        assert_eq!(rows.len(), 2);
+        let (rows, _) = db.select("users", &col("id").is_in(Vec::<i64>::new())).await.unwrap();
+        assert!(rows.is_empty());
+
+        assert!(db.fetch(&QueryBuilder::table("users").order_by("missing")).await.is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,39 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{query, Condition, Operator, Query, Value};
+
+    fn condition(column: &str, operator: Operator, value: Value) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value,
+        })
+    }
+
+    #[test]
+    fn test_query_macro() {
+        assert_eq!(format!("{:?}", query!()), format!("{:?}", Query::MatchAll));
+        assert_eq!(
+            format!("{:?}", query!(age >= 25)),
+            format!("{:?}", condition("age", Operator::Gte, Value::Integer(25)))
+        );
+
+        let name = "Alice";
+        let expected = Query::Or(vec![
+            Query::And(vec![
+                condition("age", Operator::Gt, Value::Integer(-5)),
+                condition("name", Operator::Eq, Value::String("Alice".to_string())),
+            ]),
+            condition("score", Operator::Lte, Value::Float(0.5)),
+            Query::And(vec![
+                Query::Or(vec![
+                    condition("admin", Operator::Eq, Value::Boolean(true)),
+                    condition("id", Operator::NotEq, Value::Integer(1)),
+                ]),
+                condition("id", Operator::Lt, Value::Integer(10)),
+            ]),
+        ]);
+        let query = query!(age > -5 && name == name || score <= 0.5 || (admin == true || id != 1) && id < 10);
+        assert_eq!(format!("{:?}", query), format!("{:?}", expected));
+    }
+}
@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod tests {
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::{Aes256Gcm, Nonce};
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use hkdf::Hkdf;
+    use sha2::Sha256;
+    use zapdb::{Column, DataType, Database, DatabaseConfig, MemoryStorage, Query, Skipped, StorageBackend, Value};
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::sync::Arc;
+
+    const WAL: &str = "test_recovery.wal";
+    const SNAPSHOT: &str = "test_recovery.zap";
+
+    fn open(storage: &Arc<MemoryStorage>) -> Database {
+        let config = DatabaseConfig {
+            storage: Some(storage.clone()),
+            ..Default::default()
+        };
+        Database::with_config([0; 32], WAL, config)
+    }
+
+    async fn create(db: &Database, table: &str, column: &str, values: &[&str]) {
+        db.create_table(
+            table.to_string(),
+            vec![Column::new(column.to_string(), DataType::String, vec![])],
+        )
+        .await
+        .unwrap();
+        for value in values {
+            let mut row = HashMap::new();
+            row.insert(column.to_string(), Value::String(value.to_string()));
+            db.insert(table, row).await.unwrap();
+        }
+    }
+
+    // Decrypts a snapshot small enough for one chunk.
+    fn unseal(buffer: &[u8]) -> Vec<u8> {
+        let (header, chunk) = buffer.split_at(4 + 2 + 32 + 4);
+        let mut key = [0; 32];
+        let mut info = b"zapdb snapshot chunk ".to_vec();
+        info.extend(0u64.to_le_bytes());
+        Hkdf::<Sha256>::new(Some(&header[6..38]), &[0; 32]).expand(&info, &mut key).unwrap();
+        let aad = [header, &0u32.to_le_bytes()].concat();
+        let payload = Payload { msg: &chunk[4..], aad: &aad };
+        Aes256Gcm::new(&key.into()).decrypt(Nonce::from_slice(&[0; 12]), payload).unwrap()
+    }
+
+    // Overwrites the bincode length prefix in front of `marker` in the
+    // snapshot, so whatever holds it no longer decodes. It is written back
+    // in the format from before snapshots were chunked, which still loads.
+    fn corrupt_snapshot(storage: &MemoryStorage, markers: &[&str]) {
+        let cipher = Aes256Gcm::new((&[0u8; 32]).into());
+        let compressed = unseal(&storage.read(SNAPSHOT).unwrap().unwrap());
+        let mut data = Vec::new();
+        GzDecoder::new(&compressed[..]).read_to_end(&mut data).unwrap();
+
+        for marker in markers {
+            let at = data.windows(marker.len()).position(|w| w == marker.as_bytes()).unwrap();
+            data[at - 8..at].copy_from_slice(&[0xff; 8]);
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut buffer = [1; 12].to_vec();
+        buffer.extend(cipher.encrypt(Nonce::from_slice(&buffer[..12]), compressed.as_slice()).unwrap());
+        storage.write(SNAPSHOT, &buffer).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_load_with_recovery() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage);
+        create(&db, "users", "name", &["alice", "corrupt_row", "carol"]).await;
+        create(&db, "broken", "corrupt_column", &["x"]).await;
+        db.create_sequence("ids", 1, 1).await.unwrap();
+        db.save(SNAPSHOT).await.unwrap();
+        corrupt_snapshot(&storage, &["corrupt_row", "corrupt_column"]);
+
+        // A plain load refuses the snapshot and loads nothing.
+        let db = open(&storage);
+        assert!(db.load(SNAPSHOT).await.is_err());
+        assert!(db.table_names().await.is_empty());
+
+        let db = open(&storage);
+        let report = db.load_with_recovery(SNAPSHOT).await.unwrap();
+        assert_eq!(report.skipped.len(), 2);
+        assert!(report
+            .skipped
+            .iter()
+            .any(|s| matches!(s, Skipped::Row { table, position: 1, .. } if table == "users")));
+        assert!(report
+            .skipped
+            .iter()
+            .any(|s| matches!(s, Skipped::Table { name: Some(name), .. } if name == "broken")));
+
+        assert_eq!(db.table_names().await, vec!["users".to_string()]);
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 2);
+        assert_eq!(db.next_val("ids").await.unwrap(), 1);
+        let mut row = HashMap::new();
+        row.insert("name".to_string(), Value::String("dave".to_string()));
+        db.insert("users", row).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_recovery_stops_at_a_bad_wal_entry() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage);
+        create(&db, "users", "name", &["alice"]).await;
+        let good = storage.read(WAL).unwrap().unwrap();
+        let mut wal = good.clone();
+        wal.extend([0xff; 16]);
+        storage.write(WAL, &wal).unwrap();
+
+        let db = open(&storage);
+        assert!(db.load(SNAPSHOT).await.is_err());
+
+        storage.write(WAL, &wal).unwrap();
+        let db = open(&storage);
+        let report = db.load_with_recovery(SNAPSHOT).await.unwrap();
+        assert_eq!(
+            report.skipped.iter().map(|s| match s {
+                Skipped::Wal { offset, .. } => *offset,
+                other => panic!("unexpected {}", other),
+            }).collect::<Vec<_>>(),
+            vec![good.len()]
+        );
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_verify_files() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage);
+        create(&db, "users", "name", &["alice", "corrupt_row", "carol"]).await;
+        db.backup(SNAPSHOT).await.unwrap();
+        assert!(db.verify_files(SNAPSHOT).await.unwrap().is_clean());
+
+        corrupt_snapshot(&storage, &["corrupt_row"]);
+        let mut wal = storage.read(WAL).unwrap().unwrap();
+        let good = wal.len();
+        wal.extend([0xff; 16]);
+        storage.write(WAL, &wal).unwrap();
+        let report = db.verify_files(SNAPSHOT).await.unwrap();
+        assert_eq!(report.skipped.len(), 2);
+        assert!(matches!(&report.skipped[0], Skipped::Row { table, position: 1, .. } if table == "users"));
+        assert!(matches!(report.skipped[1], Skipped::Wal { offset, .. } if offset == good));
+        // The live tables are untouched.
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 3);
+
+        // Tampering with the ciphertext fails authentication.
+        let mut buffer = storage.read(SNAPSHOT).unwrap().unwrap();
+        let last = buffer.len() - 1;
+        buffer[last] ^= 1;
+        storage.write(SNAPSHOT, &buffer).unwrap();
+        assert!(db.verify_files(SNAPSHOT).await.is_err());
+        assert!(db.verify_files("missing.zap").await.is_err());
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::Arc;
+    use zapdb::{query, Column, Constraint, DataType, Database, Query, Value};
+
+    fn user(id: i64, name: &str) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("name".to_string(), Value::String(name.to_string())),
+        ])
+    }
+
+    async fn names(db: &Database) -> Vec<String> {
+        let (rows, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        let mut names: Vec<String> = rows.iter().map(|r| r.get_str("name").unwrap().to_string()).collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_replica_follows_the_primary() {
+        let (primary_wal, replica_wal) = ("test_replication_primary.wal", "test_replication_replica.wal");
+        let _ = fs::remove_file(primary_wal);
+        let _ = fs::remove_file(replica_wal);
+        let primary = Database::new([0; 32], primary_wal);
+        primary
+            .create_table(
+                "users".to_string(),
+                vec![
+                    Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                    Column::new("name".to_string(), DataType::String, vec![]),
+                ],
+            )
+            .await
+            .unwrap();
+        primary.insert("users", user(1, "Alice")).await.unwrap();
+        primary.insert("users", user(2, "Bob")).await.unwrap();
+
+        let replica = Arc::new(Database::new([0; 32], replica_wal));
+        let replication = primary.replicate_to(Arc::clone(&replica)).await.unwrap();
+        assert_eq!(names(&replica).await, ["Alice", "Bob"]);
+        // The replica can index what the primary doesn't.
+        replica.create_index("users", "name").await.unwrap();
+
+        primary.insert("users", user(3, "Carol")).await.unwrap();
+        primary.execute_sql("UPDATE users SET name = 'Bobby' WHERE id = 2").await.unwrap();
+        primary.delete("users", &query!(id == 1)).await.unwrap();
+        primary
+            .create_table("notes".to_string(), vec![Column::new("text".to_string(), DataType::String, vec![])])
+            .await
+            .unwrap();
+        replication.caught_up().await;
+        assert_eq!(replication.lag(), 0);
+        assert_eq!(names(&replica).await, ["Bobby", "Carol"]);
+        let (rows, _) = replica.select("users", &query!(name == "Carol")).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert!(replica.select("notes", &Query::MatchAll).await.is_ok());
+
+        // Once stopped, the replica keeps what it had.
+        assert!(replication.is_running());
+        replication.stop();
+        primary.insert("users", user(4, "Dave")).await.unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(names(&replica).await, ["Bobby", "Carol"]);
+
+        let _ = fs::remove_file(primary_wal);
+        let _ = fs::remove_file(replica_wal);
+    }
+
+    #[tokio::test]
+    async fn test_replica_must_be_empty() {
+        let (primary_wal, replica_wal) = ("test_replication_empty_primary.wal", "test_replication_empty_replica.wal");
+        let _ = fs::remove_file(primary_wal);
+        let _ = fs::remove_file(replica_wal);
+        let primary = Database::new([0; 32], primary_wal);
+        let replica = Arc::new(Database::new([0; 32], replica_wal));
+        replica
+            .create_table("users".to_string(), vec![Column::new("id".to_string(), DataType::Integer, vec![])])
+            .await
+            .unwrap();
+        assert!(primary.replicate_to(Arc::clone(&replica)).await.is_err());
+
+        let _ = fs::remove_file(primary_wal);
+        let _ = fs::remove_file(replica_wal);
+    }
+}
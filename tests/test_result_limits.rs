@@ -0,0 +1,88 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{
+        query, Column, DataType, Database, DatabaseConfig, Expr, Query, ResultLimitError, ResultLimits, SelectError,
+        SelectOptions, SortOrder, Value,
+    };
+
+    async fn setup(result_limits: ResultLimits) -> Database {
+        let config = DatabaseConfig {
+            result_limits,
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        db.create_table(
+            "events".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("payload".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for id in 0..20 {
+            let row = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("payload".to_string(), Value::String("x".repeat(100))),
+            ]);
+            db.insert("events", row).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_selects_over_the_row_limit_fail() {
+        let limits = ResultLimits {
+            max_rows: Some(5),
+            ..Default::default()
+        };
+        let db = setup(limits).await;
+
+        let err = db.select("events", &Query::MatchAll).await.unwrap_err();
+        assert_eq!(err, "Query on events returns more than 5 rows");
+        assert!(db.project("events", &Query::MatchAll, &[("id", Expr::Column("id".to_string()))]).await.is_err());
+        let (rows, _) = db.select("events", &query!(id < 5)).await.unwrap();
+        assert_eq!(rows.len(), 5);
+
+        let err = db.select_with("events", &Query::MatchAll, &SelectOptions::new()).await.unwrap_err();
+        assert_eq!(
+            err,
+            SelectError::Limit(ResultLimitError::TooManyRows {
+                table: "events".to_string(),
+                limit: 5,
+            })
+        );
+        // The select's own limit comes first.
+        let options = SelectOptions::new().order_by("id", SortOrder::Descending).limit(5);
+        let (rows, _) = db.select_with("events", &Query::MatchAll, &options).await.unwrap();
+        assert_eq!(rows[0].get_i64("id"), Some(19));
+
+        // Counting isn't capped.
+        assert_eq!(db.count("events", &Query::MatchAll).await.unwrap(), 20);
+        assert!(db.first("events", &Query::MatchAll).await.unwrap().is_some());
+
+        let missing = db.select_with("missing", &Query::MatchAll, &SelectOptions::new()).await.unwrap_err();
+        assert!(matches!(missing, SelectError::Failed(_)));
+    }
+
+    #[tokio::test]
+    async fn test_truncating_keeps_the_rows_that_fit() {
+        let limits = ResultLimits {
+            max_rows: Some(10),
+            max_bytes: Some(1000),
+            truncate: true,
+        };
+        let db = setup(limits).await;
+
+        let (rows, _) = db.select("events", &Query::MatchAll).await.unwrap();
+        let bytes: usize = rows.iter().map(|r| r.estimated_size()).sum();
+        assert!(!rows.is_empty() && rows.len() < 10, "{} rows", rows.len());
+        assert!(bytes <= 1000);
+        assert_eq!(rows[0].get_i64("id"), Some(0));
+
+        let (rows, _) = db.select("events", &query!(id < 3)).await.unwrap();
+        assert_eq!(rows.len(), 3);
+    }
+}
@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+    use zapdb::{query, AggregateFunction, Column, DataType, Database, Period, Query, Rollup, Row, Value};
+
+    fn at(day: u32, hour: u32) -> Value {
+        Value::DateTime(Utc.with_ymd_and_hms(2024, 3, day, hour, 0, 0).unwrap())
+    }
+
+    fn event(id: i64, kind: &str, day: u32, hour: u32, amount: i64) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("kind".to_string(), Value::String(kind.to_string())),
+            ("at".to_string(), at(day, hour)),
+            ("amount".to_string(), Value::Integer(amount)),
+        ])
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "events".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("kind".to_string(), DataType::String, vec![]),
+                Column::new("at".to_string(), DataType::DateTime, vec![]),
+                Column::new("amount".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    fn daily() -> Rollup {
+        Rollup::new("events")
+            .group_by("kind")
+            .group_by_period("at", Period::Day)
+            .measure("events", AggregateFunction::Count, "id")
+            .measure("total", AggregateFunction::Sum, "amount")
+            .measure("largest", AggregateFunction::Max, "amount")
+    }
+
+    // `(kind, day, events, total, largest)` for each row of the rollup.
+    async fn groups(db: &Database, name: &str) -> Vec<(String, Value, i64, f64, i64)> {
+        let (rows, _) = db.select(name, &Query::MatchAll).await.unwrap();
+        let mut groups: Vec<_> = rows
+            .iter()
+            .map(|row: &Row| {
+                (
+                    row.get_str("kind").unwrap().to_string(),
+                    row.get("at").unwrap().clone(),
+                    row.get_i64("events").unwrap(),
+                    row.get_f64("total").unwrap(),
+                    row.get_i64("largest").unwrap(),
+                )
+            })
+            .collect();
+        groups.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        groups
+    }
+
+    #[tokio::test]
+    async fn test_rollup_is_maintained_on_write() {
+        let db = setup().await;
+        db.insert("events", event(1, "click", 1, 9, 5)).await.unwrap();
+        db.insert("events", event(2, "click", 1, 17, 7)).await.unwrap();
+        db.create_rollup("daily", daily()).await.unwrap();
+        assert_eq!(groups(&db, "daily").await, vec![("click".to_string(), at(1, 0), 2, 12.0, 7)]);
+
+        db.insert("events", event(3, "click", 2, 8, 1)).await.unwrap();
+        db.insert("events", event(4, "view", 1, 10, 3)).await.unwrap();
+        assert_eq!(
+            groups(&db, "daily").await,
+            vec![
+                ("click".to_string(), at(1, 0), 2, 12.0, 7),
+                ("click".to_string(), at(2, 0), 1, 1.0, 1),
+                ("view".to_string(), at(1, 0), 1, 3.0, 3),
+            ]
+        );
+
+        // Removing the maximum recomputes the group from the source.
+        db.delete("events", &query!(id == 2)).await.unwrap();
+        // An update moves a row between groups; the emptied group goes.
+        db.update("events", &query!(id == 3), |row| {
+            row.insert("kind".to_string(), Value::String("view".to_string()));
+            row.insert("at".to_string(), Value::DateTime(Utc.with_ymd_and_hms(2024, 3, 1, 23, 59, 0).unwrap()));
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            groups(&db, "daily").await,
+            vec![
+                ("click".to_string(), at(1, 0), 1, 5.0, 5),
+                ("view".to_string(), at(1, 0), 2, 4.0, 3),
+            ]
+        );
+
+        // The rollup matches one recomputed from scratch.
+        db.create_rollup("daily_again", daily()).await.unwrap();
+        assert_eq!(groups(&db, "daily").await, groups(&db, "daily_again").await);
+    }
+
+    #[tokio::test]
+    async fn test_rollup_filter_and_transactions() {
+        let db = setup().await;
+        let rollup = daily().filter(query!(amount >= 5));
+        db.create_rollup("large", rollup).await.unwrap();
+
+        let mut transaction = zapdb::begin_transaction();
+        transaction.insert("events".to_string(), event(1, "click", 1, 9, 5));
+        transaction.insert("events".to_string(), event(2, "click", 1, 10, 2));
+        transaction.insert("events".to_string(), event(3, "click", 1, 11, 9));
+        db.commit(transaction).await.unwrap();
+        assert_eq!(groups(&db, "large").await, vec![("click".to_string(), at(1, 0), 2, 14.0, 9)]);
+
+        db.delete("events", &Query::MatchAll).await.unwrap();
+        assert!(groups(&db, "large").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rollups_are_read_only_and_validated() {
+        let db = setup().await;
+        db.create_rollup("daily", daily()).await.unwrap();
+
+        let row = HashMap::from([("kind".to_string(), Value::String("click".to_string()))]);
+        assert!(db.insert("daily", row).await.is_err());
+        assert!(db.create_rollup("daily", daily()).await.is_err());
+        assert!(db.create_rollup("none", Rollup::new("events").group_by("kind")).await.is_err());
+        let by_kind_period = Rollup::new("events")
+            .group_by_period("kind", Period::Day)
+            .measure("events", AggregateFunction::Count, "id");
+        assert!(db.create_rollup("bad_period", by_kind_period).await.is_err());
+        let clashing = Rollup::new("events")
+            .group_by("kind")
+            .measure("kind", AggregateFunction::Count, "id");
+        assert!(db.create_rollup("clashing", clashing).await.is_err());
+        let missing = Rollup::new("events").measure("n", AggregateFunction::Count, "missing");
+        assert!(db.create_rollup("missing", missing).await.is_err());
+
+        db.drop_rollup("daily").await.unwrap();
+        assert!(db.select("daily", &Query::MatchAll).await.is_err());
+        assert!(db.drop_rollup("daily").await.is_err());
+    }
+}
@@ -0,0 +1,139 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{
+        Column, Condition, DataType, Database, DatabaseConfig, Operator, Query, Value, WatchEvent,
+    };
+    use std::collections::HashMap;
+
+    fn user(id: i64, age: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("age".to_string(), Value::Integer(age));
+        row
+    }
+
+    fn by(column: &str, operator: Operator, value: i64) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value: Value::Integer(value),
+        })
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_index("users", "age").await.unwrap();
+        db
+    }
+
+    async fn ids(db: &Database, query: &Query) -> Vec<i64> {
+        let (rows, _) = db.select("users", query).await.unwrap();
+        rows.iter().map(|r| r.get_i64("id").unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_row_ids_survive_deletes() {
+        let db = setup().await;
+        let mut watcher = db.watch("users", Query::MatchAll).await.unwrap();
+        for i in 0..10 {
+            db.insert("users", user(i, i % 3)).await.unwrap();
+        }
+        let inserted: Vec<u64> = (0..10)
+            .map(|_| watcher.try_recv_with_row_id().unwrap().0)
+            .collect();
+        assert_eq!(inserted, (0..10).collect::<Vec<u64>>());
+
+        // The index keeps finding the remaining rows after rows before them
+        // are gone.
+        db.delete("users", &by("id", Operator::Lt, 4)).await.unwrap();
+        assert_eq!(ids(&db, &by("age", Operator::Eq, 1)).await, vec![4, 7]);
+        assert_eq!(ids(&db, &by("age", Operator::Gte, 2)).await, vec![5, 8]);
+        let deleted: Vec<u64> = (0..4)
+            .map(|_| watcher.try_recv_with_row_id().unwrap().0)
+            .collect();
+        assert_eq!(deleted, vec![0, 1, 2, 3]);
+
+        // Updates keep the id, and new rows get ids never used before.
+        db.update("users", &by("id", Operator::Eq, 7), |row| {
+            row.insert("age".to_string(), Value::Integer(2));
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            watcher.try_recv_with_row_id(),
+            Some((7, WatchEvent::Update { .. }))
+        ));
+        db.insert("users", user(10, 1)).await.unwrap();
+        assert!(matches!(watcher.try_recv_with_row_id(), Some((10, WatchEvent::Insert(_)))));
+        assert_eq!(ids(&db, &by("age", Operator::Eq, 1)).await, vec![4, 10]);
+        assert_eq!(ids(&db, &by("age", Operator::Eq, 2)).await, vec![5, 7, 8]);
+    }
+
+    #[tokio::test]
+    async fn test_row_ids_are_saved() {
+        let snapshot_path = "test_row_ids_are_saved.zap";
+        let db = setup().await;
+        for i in 0..5 {
+            db.insert("users", user(i, 20)).await.unwrap();
+        }
+        db.delete("users", &by("id", Operator::Lt, 2)).await.unwrap();
+        db.save(snapshot_path).await.unwrap();
+        let storage = db.storage();
+        drop(db);
+
+        let restored = fixtures::reopen(storage);
+        restored.load(snapshot_path).await.unwrap();
+        let mut watcher = restored.watch("users", Query::MatchAll).await.unwrap();
+        restored.delete("users", &by("id", Operator::Eq, 3)).await.unwrap();
+        restored.insert("users", user(5, 20)).await.unwrap();
+        assert!(matches!(watcher.try_recv_with_row_id(), Some((3, WatchEvent::Delete(_)))));
+        assert!(matches!(watcher.try_recv_with_row_id(), Some((5, WatchEvent::Insert(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_undo_keeps_row_ids() {
+        let config = DatabaseConfig {
+            undo_depth: 10,
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_index("users", "age").await.unwrap();
+        for i in 0..4 {
+            db.insert("users", user(i, 30)).await.unwrap();
+        }
+        db.update("users", &by("id", Operator::Eq, 1), |row| {
+            row.insert("age".to_string(), Value::Integer(40));
+        })
+        .await
+        .unwrap();
+
+        let mut watcher = db.watch("users", Query::MatchAll).await.unwrap();
+        db.undo(1).await.unwrap();
+        assert!(matches!(
+            watcher.try_recv_with_row_id(),
+            Some((1, WatchEvent::Update { .. }))
+        ));
+        assert_eq!(ids(&db, &Query::MatchAll).await, vec![0, 1, 2, 3]);
+        assert_eq!(ids(&db, &by("age", Operator::Eq, 30)).await, vec![0, 1, 2, 3]);
+        assert!(ids(&db, &by("age", Operator::Eq, 40)).await.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{Column, Condition, DataType, Database, Operator, Query, Row, Sample, SampleSize, Value};
+
+    async fn numbers(wal_path: &str, count: i64) -> Database {
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "numbers".to_string(),
+            vec![Column::new("n".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        for n in 0..count {
+            db.insert("numbers", HashMap::from([("n".to_string(), Value::Integer(n))]))
+                .await
+                .unwrap();
+        }
+        db
+    }
+
+    fn sample(size: SampleSize, seed: u64) -> Query {
+        Query::Sample(Sample {
+            query: Box::new(Query::Condition(Condition {
+                column: "n".to_string(),
+                operator: Operator::Gte,
+                value: Value::Integer(100),
+            })),
+            size,
+            seed,
+        })
+    }
+
+    fn ns(rows: &[Row]) -> Vec<i64> {
+        rows.iter()
+            .map(|row| match row.get("n") {
+                Some(Value::Integer(n)) => *n,
+                other => panic!("unexpected value {:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sample_is_reproducible() {
+        let wal_path = "test_sample_is_reproducible.wal";
+        let db = numbers(wal_path, 1000).await;
+
+        let (rows, _) = db.select("numbers", &sample(SampleSize::Rows(50), 7)).await.unwrap();
+        let first = ns(&rows);
+        assert_eq!(first.len(), 50);
+        assert!(first.windows(2).all(|w| w[0] < w[1]));
+        assert!(first.iter().all(|n| *n >= 100));
+
+        let (rows, _) = db.select("numbers", &sample(SampleSize::Rows(50), 7)).await.unwrap();
+        assert_eq!(ns(&rows), first);
+        let (rows, _) = db.select("numbers", &sample(SampleSize::Rows(50), 8)).await.unwrap();
+        assert_ne!(ns(&rows), first);
+
+        let (rows, _) = db.select("numbers", &sample(SampleSize::Fraction(0.25), 7)).await.unwrap();
+        assert_eq!(rows.len(), 225);
+        let (rows, _) = db.select("numbers", &sample(SampleSize::Rows(5000), 7)).await.unwrap();
+        assert_eq!(rows.len(), 900);
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_sample_rejects_bad_fraction() {
+        let wal_path = "test_sample_rejects_bad_fraction.wal";
+        let db = numbers(wal_path, 10).await;
+
+        let err = db.select("numbers", &sample(SampleSize::Fraction(1.5), 7)).await.unwrap_err();
+        assert_eq!(err, "A sample fraction must be from 0 to 1, not 1.5");
+        assert!(db.watch("numbers", sample(SampleSize::Rows(1), 7)).await.is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
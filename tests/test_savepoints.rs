@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{create_pool, Column, Constraint, DataType, Database, Query, Value, WriteError};
+
+    async fn create_users(db: &Database) {
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![Constraint::NotNull]),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    fn user(id: i64, name: &str) -> HashMap<String, Value> {
+        HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("name".to_string(), Value::String(name.to_string())),
+        ])
+    }
+
+    async fn ids(db: &Database) -> Vec<i64> {
+        let (rows, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        let mut ids: Vec<i64> = rows.iter().map(|r| r.get_i64("id").unwrap()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn test_transaction_closure() {
+        let wal_path = "test_transaction_closure.wal";
+        let _ = fs::remove_file(wal_path);
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        create_users(&db).await;
+
+        let committed: Result<usize, WriteError> = db
+            .transaction(async |tx| {
+                tx.insert("users".to_string(), user(1, "Alice"));
+                // A failed savepoint only discards its own writes.
+                let nested: Result<(), String> = tx
+                    .savepoint(async |tx| {
+                        tx.insert("users".to_string(), user(2, "Bob"));
+                        tx.savepoint(async |tx| {
+                            tx.insert("users".to_string(), user(3, "Carol"));
+                            Ok::<(), String>(())
+                        })
+                        .await?;
+                        Err("changed my mind".to_string())
+                    })
+                    .await;
+                assert!(nested.is_err());
+                tx.savepoint(async |tx| {
+                    tx.insert("users".to_string(), user(4, "Dave"));
+                    Ok::<(), String>(())
+                })
+                .await?;
+                Ok(tx.len())
+            })
+            .await;
+        assert_eq!(committed.unwrap(), 2);
+        assert_eq!(ids(&db).await, vec![1, 4]);
+
+        let rolled_back: Result<(), String> = db
+            .transaction(async |tx| {
+                tx.insert("users".to_string(), user(5, "Erin"));
+                Err("abort".to_string())
+            })
+            .await;
+        assert!(rolled_back.is_err());
+
+        // A commit that fails is returned as the closure's error.
+        let failed: Result<(), WriteError> = db
+            .transaction(async |tx| {
+                tx.insert("users".to_string(), user(6, "Frank"));
+                tx.insert("users".to_string(), user(1, "Alice again"));
+                Ok(())
+            })
+            .await;
+        assert!(failed.is_err());
+        assert_eq!(ids(&db).await, vec![1, 4]);
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_panic_writes_nothing() {
+        let wal_path = "test_transaction_panic_writes_nothing.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = std::sync::Arc::new(Database::new([0; 32], wal_path));
+        create_users(&db).await;
+
+        let handle = std::sync::Arc::clone(&db);
+        let task = tokio::spawn(async move {
+            let _: Result<(), WriteError> = handle
+                .transaction(async |tx| {
+                    tx.insert("users".to_string(), user(1, "Alice"));
+                    panic!("lost connection to the payment provider");
+                })
+                .await;
+        });
+        assert!(task.await.unwrap_err().is_panic());
+        assert!(ids(&db).await.is_empty());
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
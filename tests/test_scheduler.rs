@@ -0,0 +1,118 @@
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use zapdb::{Column, DataType, Database, Job, Query, Schedule, Value};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_cron_schedule() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 10, 7, 30).unwrap();
+
+        let every_quarter = Schedule::cron("*/15 * * * *").unwrap();
+        assert_eq!(
+            every_quarter.next_after(start),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 10, 15, 0).unwrap())
+        );
+
+        // 2024-01-01 is a Monday, so the next Sunday 03:30 is the 7th.
+        let weekly = Schedule::cron("30 3 * * 0").unwrap();
+        assert_eq!(
+            weekly.next_after(start),
+            Some(Utc.with_ymd_and_hms(2024, 1, 7, 3, 30, 0).unwrap())
+        );
+
+        let leap_day = Schedule::cron("0 0 29 2 *").unwrap();
+        assert_eq!(
+            leap_day.next_after(start),
+            Some(Utc.with_ymd_and_hms(2024, 2, 29, 0, 0, 0).unwrap())
+        );
+        assert_eq!(Schedule::cron("0 0 30 2 *").unwrap().next_after(start), None);
+
+        assert!(Schedule::cron("* * * *").is_err());
+        assert!(Schedule::cron("60 * * * *").is_err());
+        assert!(Schedule::cron("*/0 * * * *").is_err());
+        assert!(Schedule::cron("10-5 * * * *").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_jobs() {
+        let db_path = "test_scheduled_jobs.zap";
+        let wal_path = "test_scheduled_jobs.wal";
+        let db = Arc::new(Database::new([0; 32], wal_path));
+        db.create_table(
+            "users".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(1));
+        db.insert("users", row).await.unwrap();
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = runs.clone();
+        db.schedule(
+            "count",
+            Schedule::every(Duration::from_millis(10)),
+            Job::custom(move |_db| {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            }),
+        )
+        .unwrap();
+        db.schedule(
+            "fail",
+            Schedule::every(Duration::from_millis(10)),
+            Job::custom(|_db| async { Err("boom".to_string()) }),
+        )
+        .unwrap();
+        db.schedule(
+            "save",
+            Schedule::every(Duration::from_millis(10)),
+            Job::Save(db_path.to_string()),
+        )
+        .unwrap();
+        assert_eq!(db.job_names(), vec!["count", "fail", "save"]);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(runs.load(Ordering::SeqCst) >= 2);
+        let status = db.job_status("fail").unwrap();
+        assert!(status.failures >= 1);
+        assert_eq!(status.last_error.as_deref(), Some("boom"));
+        assert!(db.job_status("save").unwrap().runs >= 1);
+
+        let restored = Database::new([0; 32], "test_scheduled_jobs_restored.wal");
+        restored.load(db_path).await.unwrap();
+        let (users, _) = restored.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(users.len(), 1);
+
+        // Cancelled jobs stop running.
+        assert!(db.cancel_job("count"));
+        assert!(!db.cancel_job("count"));
+        assert!(db.job_status("count").is_none());
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let stopped_at = runs.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), stopped_at);
+
+        // So do all jobs once the database is closed.
+        db.close(None).await.unwrap();
+        let saves = db.job_status("save").unwrap().runs;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(db.job_status("save").unwrap().runs, saves);
+        assert!(db
+            .schedule("late", Schedule::every(Duration::from_secs(1)), Job::SyncWal)
+            .is_err());
+
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file("test_scheduled_jobs_restored.wal");
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use zapdb::{Column, Constraint, DataType, Database, SchemaChange, SchemaDefinition};
+
+    const SCHEMA: &str = r#"{
+        "tables": [
+            {
+                "name": "posts",
+                "columns": [
+                    {"name": "id", "data_type": "Integer", "constraints": ["Unique"]},
+                    {"name": "author", "data_type": "Integer", "constraints": [{"ForeignKey": {"table": "users", "column": "id"}}]},
+                    {"name": "body", "data_type": "String", "constraints": []}
+                ],
+                "indexes": ["author"],
+                "text_indexes": ["body"]
+            },
+            {
+                "name": "users",
+                "columns": [
+                    {"name": "id", "data_type": "Integer", "constraints": ["Unique", "NotNull"]},
+                    {"name": "name", "data_type": "String", "constraints": []}
+                ],
+                "indexes": ["id", "name"]
+            }
+        ]
+    }"#;
+
+    #[tokio::test]
+    async fn test_apply_schema() {
+        let wal_path = "test_apply_schema.wal";
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file("test_apply_schema_copy.wal");
+        let db = Database::new([0; 32], wal_path);
+        let schema = SchemaDefinition::from_json(SCHEMA).unwrap();
+
+        let changes: Vec<String> = db.diff_schema(&schema).await.unwrap().iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                "create table users (id Integer unique not null, name String)",
+                "create table posts (id Integer unique, author Integer references users(id), body String)",
+                "create index on posts (author)",
+                "create text index on posts (body)",
+                "create index on users (name)",
+            ]
+        );
+        assert_eq!(db.apply_schema(&schema).await.unwrap().len(), 5);
+        assert!(db.diff_schema(&schema).await.unwrap().is_empty());
+        assert!(db.apply_schema(&schema).await.unwrap().is_empty());
+
+        // What comes out goes back in.
+        let exported = db.export_schema().await.unwrap();
+        assert_eq!(exported.tables.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["posts", "users"]);
+        let copy = Database::new([0; 32], "test_apply_schema_copy.wal");
+        copy.apply_schema(&SchemaDefinition::from_json(&exported.to_json()).unwrap()).await.unwrap();
+        assert_eq!(copy.export_schema().await.unwrap(), exported);
+
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file("test_apply_schema_copy.wal");
+    }
+
+    #[tokio::test]
+    async fn test_altered_tables_are_refused() {
+        let wal_path = "test_schema_altered.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        db.create_table("users".to_string(), vec![Column::new("id".to_string(), DataType::Integer, vec![])])
+            .await
+            .unwrap();
+
+        let mut schema = db.export_schema().await.unwrap();
+        schema.tables[0].columns = vec![
+            Column::new("id".to_string(), DataType::String, vec![Constraint::Unique]),
+            Column::new("email".to_string(), DataType::String, vec![]),
+        ];
+        schema.tables[0].indexes = vec!["email".to_string()];
+        let changes = db.diff_schema(&schema).await.unwrap();
+        assert!(matches!(changes[0], SchemaChange::AlterTable { .. }));
+        assert_eq!(
+            changes[0].to_string(),
+            "alter table users: change column id Integer to id String unique, add column email String"
+        );
+
+        let error = db.apply_schema(&schema).await.unwrap_err();
+        assert!(error.starts_with("Tables can't be altered"), "{}", error);
+        // Nothing was applied, not even the index.
+        assert!(db.export_schema().await.unwrap().tables[0].indexes.is_empty());
+
+        schema.tables[0].indexes = vec!["missing".to_string()];
+        assert!(db.diff_schema(&schema).await.is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
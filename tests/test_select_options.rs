@@ -0,0 +1,127 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{query, Column, DataType, Database, Join, JoinType, Query, SelectOptions, SortOrder, Value};
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "employees".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("department".to_string(), DataType::String, vec![]),
+                Column::new("salary".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        let employees = [
+            (1, "ops", Value::Integer(50)),
+            (2, "dev", Value::Integer(70)),
+            (3, "ops", Value::Integer(60)),
+            (4, "dev", Value::Null),
+            (5, "dev", Value::Integer(70)),
+            (6, "ops", Value::Integer(40)),
+        ];
+        for (id, department, salary) in employees {
+            let row = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("department".to_string(), Value::String(department.to_string())),
+                ("salary".to_string(), salary),
+            ]);
+            db.insert("employees", row).await.unwrap();
+        }
+        db
+    }
+
+    fn ids(rows: &[zapdb::Row]) -> Vec<i64> {
+        rows.iter().map(|r| r.get_i64("id").unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_order_by_several_columns_with_offset_and_limit() {
+        let db = setup().await;
+        let by_department = SelectOptions::new()
+            .order_by("department", SortOrder::Ascending)
+            .order_by("salary", SortOrder::Descending);
+
+        let (rows, _) = db.select_with("employees", &Query::MatchAll, &by_department).await.unwrap();
+        // Nulls come first in descending order, and ties keep insertion order.
+        assert_eq!(ids(&rows), vec![4, 2, 5, 3, 1, 6]);
+
+        let page = by_department.clone().offset(2).limit(3);
+        let (rows, _) = db.select_with("employees", &Query::MatchAll, &page).await.unwrap();
+        assert_eq!(ids(&rows), vec![5, 3, 1]);
+
+        let ascending = SelectOptions::new().order_by("salary", SortOrder::Ascending).limit(10);
+        let (rows, _) = db.select_with("employees", &query!(department == "dev"), &ascending).await.unwrap();
+        assert_eq!(ids(&rows), vec![2, 5, 4]);
+
+        // Without an order, rows come in insertion order.
+        let (rows, _) = db
+            .select_with("employees", &Query::MatchAll, &SelectOptions::new().offset(4).limit(5))
+            .await
+            .unwrap();
+        assert_eq!(ids(&rows), vec![5, 6]);
+
+        let missing = SelectOptions::new().order_by("age", SortOrder::Ascending);
+        assert!(db.select_with("employees", &Query::MatchAll, &missing).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_order_by_on_joins() {
+        let db = setup().await;
+        db.create_table(
+            "departments".to_string(),
+            vec![
+                Column::new("name".to_string(), DataType::String, vec![]),
+                Column::new("floor".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (name, floor) in [("ops", 1), ("dev", 2)] {
+            let row = HashMap::from([
+                ("name".to_string(), Value::String(name.to_string())),
+                ("floor".to_string(), Value::Integer(floor)),
+            ]);
+            db.insert("departments", row).await.unwrap();
+        }
+        let join = Query::Join(Join::new(JoinType::Inner, "departments", ("department", "name")));
+        let options = SelectOptions::new()
+            .order_by("floor", SortOrder::Descending)
+            .order_by("id", SortOrder::Descending)
+            .limit(2);
+        let (rows, _) = db.select_with("employees", &join, &options).await.unwrap();
+        assert_eq!(ids(&rows), vec![5, 4]);
+
+        let projected = options.columns(&["id", "floor"]);
+        let (rows, _) = db.select_with("employees", &join, &projected).await.unwrap();
+        assert_eq!(rows[0].keys().collect::<Vec<_>>(), ["id", "floor"]);
+    }
+
+    #[tokio::test]
+    async fn test_select_columns() {
+        let db = setup().await;
+
+        let (rows, _) = db
+            .select_columns("employees", &query!(department == "ops"), &["salary", "id"])
+            .await
+            .unwrap();
+        assert_eq!(ids(&rows), vec![1, 3, 6]);
+        assert_eq!(rows[0].keys().collect::<Vec<_>>(), ["salary", "id"]);
+        assert_eq!(rows[0].get("department"), None);
+
+        // Sorting may use a column that isn't returned.
+        let options = SelectOptions::new()
+            .order_by("salary", SortOrder::Descending)
+            .limit(1)
+            .columns(&["id"]);
+        let (rows, _) = db.select_with("employees", &Query::MatchAll, &options).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].len(), 1);
+
+        assert!(db.select_columns("employees", &Query::MatchAll, &["id", "age"]).await.is_err());
+    }
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use zapdb::Database;
+
+    #[tokio::test]
+    async fn test_sequences() {
+        let wal_path = "test_sequences.wal";
+        let db = Database::new([0; 32], wal_path);
+        db.create_sequence("order_ids", 1000, 1).await.unwrap();
+        db.create_sequence("countdown", 10, -5).await.unwrap();
+        assert_eq!(
+            db.create_sequence("order_ids", 1, 1).await.unwrap_err(),
+            "Sequence order_ids already exists"
+        );
+        assert!(db.create_sequence("stuck", 1, 0).await.is_err());
+
+        assert_eq!(db.next_val("order_ids").await.unwrap(), 1000);
+        assert_eq!(db.next_val("order_ids").await.unwrap(), 1001);
+        assert_eq!(db.peek_val("order_ids"), Some(1002));
+        assert_eq!(db.next_val("countdown").await.unwrap(), 10);
+        assert_eq!(db.next_val("countdown").await.unwrap(), 5);
+        assert!(db.next_val("missing").await.is_err());
+
+        db.create_sequence("last", i64::MAX - 1, 1).await.unwrap();
+        assert_eq!(db.next_val("last").await.unwrap(), i64::MAX - 1);
+        assert_eq!(db.next_val("last").await.unwrap_err(), "Sequence last is exhausted");
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_sequences_persist() {
+        let wal_path = "test_sequences_persist.wal";
+        let db_path = "test_sequences_persist.zap";
+        let db = Database::new([0; 32], wal_path);
+        db.create_sequence("ids", 1, 1).await.unwrap();
+        db.create_sequence("dropped", 1, 1).await.unwrap();
+        db.next_val("ids").await.unwrap();
+
+        // Recovered from the WAL alone.
+        let recovered = Database::new([0; 32], wal_path);
+        recovered.load(db_path).await.unwrap();
+        assert_eq!(recovered.peek_val("ids"), Some(2));
+
+        // From a snapshot plus the WAL written after it.
+        db.save(db_path).await.unwrap();
+        db.next_val("ids").await.unwrap();
+        db.drop_sequence("dropped").await.unwrap();
+        let restored = Database::new([0; 32], wal_path);
+        restored.load(db_path).await.unwrap();
+        assert_eq!(restored.next_val("ids").await.unwrap(), 3);
+        assert_eq!(restored.sequence_names(), vec!["ids".to_string()]);
+
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(db_path);
+    }
+}
@@ -0,0 +1,59 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{create_pool, Column, DataType, Value, Query};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_close() {
+        let db_path = "test_close.zap";
+        let wal_path = "test_close.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(1));
+        db.insert("users", row.clone()).await.unwrap();
+
+        let stopped = Arc::new(AtomicBool::new(true));
+        let flag = stopped.clone();
+        db.spawn_background(async move {
+            loop {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                flag.store(false, Ordering::SeqCst);
+            }
+        });
+
+        db.close(Some(db_path)).await.unwrap();
+        assert!(db.is_closed());
+        // Closing again is harmless.
+        db.close(None).await.unwrap();
+
+        stopped.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(stopped.load(Ordering::SeqCst));
+
+        assert!(db.insert("users", row).await.is_err());
+        assert!(db.select("users", &Query::MatchAll).await.is_err());
+        assert!(db.save(db_path).await.is_err());
+
+        // The final snapshot is usable.
+        let new_pool = create_pool([0; 32], wal_path).unwrap();
+        let new_db = new_pool.get().unwrap();
+        new_db.load(db_path).await.unwrap();
+        let (users, _) = new_db.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(users.len(), 1);
+
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{Column, DataType, Database, DatabaseConfig, Query, SnapshotCompression, Value};
+
+    const DICTIONARY: SnapshotCompression = SnapshotCompression::ZstdDictionary {
+        level: 3,
+        samples: 500,
+        dictionary_bytes: 1024,
+    };
+
+    fn open(wal_path: &str, snapshot_compression: SnapshotCompression) -> Database {
+        let config = DatabaseConfig {
+            snapshot_compression,
+            ..Default::default()
+        };
+        Database::with_config([0; 32], wal_path, config)
+    }
+
+    async fn fill(db: &Database, rows: i64) {
+        db.create_table(
+            "events".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("kind".to_string(), DataType::String, vec![]),
+                Column::new("email".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for id in 0..rows {
+            let row = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("kind".to_string(), Value::String(["login", "logout", "purchase"][id as usize % 3].to_string())),
+                ("email".to_string(), Value::String(format!("user{}@example.com", id * 7919 % 500))),
+            ]);
+            db.insert("events", row).await.unwrap();
+        }
+    }
+
+    fn cleanup(paths: &[&str]) {
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_compressions_round_trip() {
+        let modes = [
+            ("gzip", SnapshotCompression::Gzip),
+            ("zstd", SnapshotCompression::Zstd { level: 3 }),
+            ("dictionary", DICTIONARY),
+        ];
+        let mut sizes = HashMap::new();
+        for (name, compression) in modes {
+            let wal_path = format!("test_snapshot_compression_{}.wal", name);
+            let db_path = format!("test_snapshot_compression_{}.zap", name);
+            cleanup(&[&wal_path, &db_path]);
+            let db = open(&wal_path, compression);
+            fill(&db, 1000).await;
+            db.save(&db_path).await.unwrap();
+            sizes.insert(name, fs::metadata(&db_path).unwrap().len());
+
+            // Read back under the default configuration.
+            let reader = open(&format!("test_snapshot_compression_{}_reader.wal", name), SnapshotCompression::Gzip);
+            reader.load(&db_path).await.unwrap();
+            let (rows, _) = reader.select("events", &Query::MatchAll).await.unwrap();
+            assert_eq!(rows.len(), 1000);
+            assert!(rows.iter().any(|row| row.get("email") == Some(&Value::String("user419@example.com".to_string()))));
+            cleanup(&[&wal_path, &db_path, &format!("test_snapshot_compression_{}_reader.wal", name)]);
+        }
+        assert!(sizes["dictionary"] < sizes["gzip"], "{:?}", sizes);
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_falls_back_with_few_rows() {
+        let paths = ["test_snapshot_compression_few.wal", "test_snapshot_compression_few.zap"];
+        cleanup(&paths);
+        let db = open(paths[0], DICTIONARY);
+        fill(&db, 3).await;
+        db.save(paths[1]).await.unwrap();
+        db.load(paths[1]).await.unwrap();
+        assert_eq!(db.select("events", &Query::MatchAll).await.unwrap().0.len(), 3);
+        cleanup(&paths);
+    }
+}
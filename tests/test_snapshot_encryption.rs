@@ -0,0 +1,176 @@
+#[cfg(test)]
+mod tests {
+    use aes_gcm::aead::{Aead, KeyInit, Payload};
+    use aes_gcm::{Aes256Gcm, Nonce};
+    use hkdf::Hkdf;
+    use sha2::Sha256;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use zapdb::{Cipher, Column, DataType, Database, DatabaseConfig, MemoryStorage, Query, StorageBackend, Value};
+
+    // Magic, version, cipher, salt and chunk count.
+    const HEADER_LEN: usize = 4 + 2 + 32 + 4;
+    const SALT: std::ops::Range<usize> = 6..38;
+
+    fn open(storage: &Arc<MemoryStorage>, key: [u8; 32]) -> Database {
+        open_with(storage, key, Cipher::default())
+    }
+
+    fn open_with(storage: &Arc<MemoryStorage>, key: [u8; 32], cipher: Cipher) -> Database {
+        let config = DatabaseConfig {
+            storage: Some(storage.clone()),
+            cipher,
+            ..Default::default()
+        };
+        Database::with_config(key, "test_snapshot_encryption.wal", config)
+    }
+
+    // Enough hard-to-compress text for several chunks.
+    async fn fill(db: &Database) {
+        db.create_table(
+            "blobs".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("body".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        let mut state: u64 = 42;
+        for id in 0..400 {
+            let body: String = (0..256)
+                .map(|_| {
+                    state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                    format!("{:016x}", state)
+                })
+                .collect();
+            let row = HashMap::from([("id".to_string(), Value::Integer(id)), ("body".to_string(), Value::String(body))]);
+            db.insert("blobs", row).await.unwrap();
+        }
+    }
+
+    // The offsets and lengths of the chunks, which are each prefixed by
+    // their length.
+    fn chunks(snapshot: &[u8]) -> Vec<(usize, usize)> {
+        let mut chunks = Vec::new();
+        let mut at = HEADER_LEN;
+        while at < snapshot.len() {
+            let len = u32::from_le_bytes(snapshot[at..at + 4].try_into().unwrap()) as usize;
+            chunks.push((at, 4 + len));
+            at += 4 + len;
+        }
+        chunks
+    }
+
+    #[tokio::test]
+    async fn test_chunked_snapshot_round_trips() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage, [7; 32]);
+        fill(&db).await;
+        db.save("chunked.zap").await.unwrap();
+
+        let snapshot = storage.read("chunked.zap").unwrap().unwrap();
+        assert_eq!(&snapshot[..4], b"ZAPS");
+        assert!(chunks(&snapshot).len() > 2);
+
+        let restored = open(&storage, [7; 32]);
+        restored.load("chunked.zap").await.unwrap();
+        assert_eq!(restored.count("blobs", &Query::MatchAll).await.unwrap(), 400);
+        let (original, _) = db.select("blobs", &Query::MatchAll).await.unwrap();
+        let (loaded, _) = restored.select("blobs", &Query::MatchAll).await.unwrap();
+        assert_eq!(original.last().unwrap().get("body"), loaded.last().unwrap().get("body"));
+
+        // Every save picks a new salt, so no chunk key is used twice.
+        db.save("again.zap").await.unwrap();
+        let again = storage.read("again.zap").unwrap().unwrap();
+        assert_ne!(snapshot[SALT], again[SALT]);
+
+        assert!(open(&storage, [8; 32]).load("chunked.zap").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reordered_or_dropped_chunks_fail_to_load() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage, [7; 32]);
+        fill(&db).await;
+        db.save("chunked.zap").await.unwrap();
+        let snapshot = storage.read("chunked.zap").unwrap().unwrap();
+        let chunks = chunks(&snapshot);
+
+        // The first two chunks are full, so swapping them keeps the layout.
+        let ((first, len), (second, _)) = (chunks[0], chunks[1]);
+        let mut swapped = snapshot[..first].to_vec();
+        swapped.extend(&snapshot[second..second + len]);
+        swapped.extend(&snapshot[first..first + len]);
+        swapped.extend(&snapshot[second + len..]);
+        storage.write("swapped.zap", &swapped).unwrap();
+        assert!(open(&storage, [7; 32]).load("swapped.zap").await.is_err());
+
+        let (last, _) = *chunks.last().unwrap();
+        storage.write("truncated.zap", &snapshot[..last]).unwrap();
+        assert!(open(&storage, [7; 32]).load("truncated.zap").await.is_err());
+
+        // Nor can a chunk count be lowered to match.
+        let mut recounted = snapshot[..last].to_vec();
+        recounted[HEADER_LEN - 4..HEADER_LEN].copy_from_slice(&(chunks.len() as u32 - 1).to_le_bytes());
+        storage.write("recounted.zap", &recounted).unwrap();
+        assert!(open(&storage, [7; 32]).load("recounted.zap").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_from_before_chunking_still_load() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage, [7; 32]);
+        fill(&db).await;
+        db.save("chunked.zap").await.unwrap();
+        let snapshot = storage.read("chunked.zap").unwrap().unwrap();
+
+        // Decrypt each chunk as the module docs describe...
+        let header = &snapshot[..HEADER_LEN];
+        let mut compressed = Vec::new();
+        for (index, (at, len)) in chunks(&snapshot).into_iter().enumerate() {
+            let mut info = b"zapdb snapshot chunk ".to_vec();
+            info.extend((index as u64).to_le_bytes());
+            let mut key = [0; 32];
+            Hkdf::<Sha256>::new(Some(&header[SALT]), &[7; 32]).expand(&info, &mut key).unwrap();
+            let mut aad = header.to_vec();
+            aad.extend((index as u32).to_le_bytes());
+            let payload = Payload { msg: &snapshot[at + 4..at + len], aad: &aad };
+            compressed.extend(Aes256Gcm::new(&key.into()).decrypt(Nonce::from_slice(&[0; 12]), payload).unwrap());
+        }
+
+        // ...and seal it the old way, under one nonce and the database key.
+        let nonce = [3; 12];
+        let mut legacy = nonce.to_vec();
+        legacy.extend(Aes256Gcm::new(&[7; 32].into()).encrypt(Nonce::from_slice(&nonce), &compressed[..]).unwrap());
+        storage.write("legacy.zap", &legacy).unwrap();
+
+        let restored = open(&storage, [7; 32]);
+        restored.load("legacy.zap").await.unwrap();
+        assert_eq!(restored.count("blobs", &Query::MatchAll).await.unwrap(), 400);
+    }
+
+    #[tokio::test]
+    async fn test_snapshots_record_their_cipher() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open_with(&storage, [7; 32], Cipher::ChaCha20Poly1305);
+        fill(&db).await;
+        db.save("chacha.zap").await.unwrap();
+        let snapshot = storage.read("chacha.zap").unwrap().unwrap();
+        assert_eq!(snapshot[4..6], [2, 1]);
+
+        // Whatever cipher the reader is configured with.
+        let restored = open_with(&storage, [7; 32], Cipher::Aes256Gcm);
+        restored.load("chacha.zap").await.unwrap();
+        assert_eq!(restored.count("blobs", &Query::MatchAll).await.unwrap(), 400);
+        restored.save("aes.zap").await.unwrap();
+        assert_eq!(storage.read("aes.zap").unwrap().unwrap()[4..6], [2, 0]);
+
+        // An unknown cipher fails rather than being guessed at.
+        let mut unknown = snapshot.clone();
+        unknown[5] = 9;
+        storage.write("unknown.zap", &unknown).unwrap();
+        let error = open(&storage, [7; 32]).load("unknown.zap").await.unwrap_err();
+        assert!(error.to_string().contains("Unknown cipher 9"), "{}", error);
+    }
+}
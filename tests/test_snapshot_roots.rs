@@ -0,0 +1,80 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{Column, Constraint, DataType, Database, Query, SnapshotError, Value};
+
+    async fn create_table(db: &Database, name: &str) {
+        db.create_table(
+            name.to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+    }
+
+    fn cleanup(paths: &[&str]) {
+        for path in paths {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_verified_checks_roots() {
+        let paths = [
+            "test_load_verified_leader.wal",
+            "test_load_verified_follower.wal",
+            "test_load_verified.db",
+        ];
+        cleanup(&paths);
+        let leader = Database::new([0; 32], paths[0]);
+        create_table(&leader, "users").await;
+        create_table(&leader, "teams").await;
+        for (id, name) in [(1, "Alice"), (2, "Bob")] {
+            let row = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("name".to_string(), Value::String(name.to_string())),
+            ]);
+            leader.insert("users", row).await.unwrap();
+        }
+        let roots = leader.backup_with_roots(paths[2]).await.unwrap();
+        assert_eq!(roots, leader.table_roots().await);
+        assert_eq!(roots["teams"], [0; 32]);
+
+        let follower = Database::new([0; 32], paths[1]);
+        let mut tampered = roots.clone();
+        tampered.insert("users".to_string(), [7; 32]);
+        match follower.load_verified(paths[2], &tampered).await {
+            Err(SnapshotError::RootMismatch { table, expected, actual }) => {
+                assert_eq!(table, "users");
+                assert_eq!(expected, [7; 32]);
+                assert_eq!(actual, roots["users"]);
+            }
+            other => panic!("expected a root mismatch, got {:?}", other),
+        }
+        // Nothing was loaded.
+        assert!(follower.select("users", &Query::MatchAll).await.is_err());
+
+        let mut extra = roots.clone();
+        extra.insert("orders".to_string(), [0; 32]);
+        assert!(matches!(
+            follower.load_verified(paths[2], &extra).await,
+            Err(SnapshotError::MissingTable(table)) if table == "orders"
+        ));
+        let mut partial = roots.clone();
+        partial.remove("teams");
+        assert!(matches!(
+            follower.load_verified(paths[2], &partial).await,
+            Err(SnapshotError::UnexpectedTable(table)) if table == "teams"
+        ));
+
+        follower.load_verified(paths[2], &roots).await.unwrap();
+        let (users, _) = follower.select("users", &Query::MatchAll).await.unwrap();
+        assert_eq!(users.len(), 2);
+        assert_eq!(follower.table_roots().await, roots);
+        cleanup(&paths);
+    }
+}
@@ -0,0 +1,104 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{Column, DataType, Database, DatabaseConfig, Join, JoinType, Query, Value};
+
+    async fn setup(config: DatabaseConfig) -> Database {
+        let storage = std::sync::Arc::new(zapdb::MemoryStorage::new());
+        let db = Database::with_config([0; 32], "test_sort_merge_join.wal", DatabaseConfig { storage: Some(storage), ..config });
+        for (table, key) in [("orders", "customer"), ("customers", "code")] {
+            db.create_table(
+                table.to_string(),
+                vec![
+                    Column::new("id".to_string(), DataType::Integer, vec![]),
+                    Column::new(key.to_string(), DataType::String, vec![]),
+                ],
+            )
+            .await
+            .unwrap();
+        }
+        // Keys repeat on both sides, and some are null. They are long, so
+        // each side sorts in more than one run.
+        let key = |i: i64, modulus: i64| match i % 37 {
+            0 => Value::Null,
+            _ => Value::String(format!("{:0>200}", i % modulus)),
+        };
+        for i in 0..400 {
+            let row = HashMap::from([("id".to_string(), Value::Integer(i)), ("customer".to_string(), key(i, 150))]);
+            db.insert("orders", row).await.unwrap();
+        }
+        for i in 0..300 {
+            let row = HashMap::from([("id".to_string(), Value::Integer(i)), ("code".to_string(), key(i, 200))]);
+            db.insert("customers", row).await.unwrap();
+        }
+        db
+    }
+
+    async fn joins(db: &Database) -> Vec<Vec<zapdb::Row>> {
+        let mut results = Vec::new();
+        for join_type in [JoinType::Inner, JoinType::Left, JoinType::Right] {
+            let join = Join::new(join_type, "customers", ("customer", "code")).prefixed();
+            let (rows, _) = db.select("orders", &Query::Join(join)).await.unwrap();
+            results.push(rows);
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn test_spilled_join_matches_hash_join() {
+        let spill_dir = "test_sort_merge_join_spill";
+        let _ = fs::remove_dir_all(spill_dir);
+        fs::create_dir(spill_dir).unwrap();
+
+        let expected = joins(&setup(DatabaseConfig::default()).await).await;
+        assert!(expected[0].len() > 400);
+
+        let config = DatabaseConfig {
+            join_memory_limit: Some(1000),
+            spill_dir: Some(spill_dir.to_string()),
+            ..Default::default()
+        };
+        let db = setup(config).await;
+        assert_eq!(joins(&db).await, expected);
+        assert_eq!(db.memory_stats().await.spilled_joins, 3);
+        assert_eq!(fs::read_dir(spill_dir).unwrap().count(), 0);
+
+        // Under the budget, the join is hashed in memory.
+        let roomy = DatabaseConfig {
+            join_memory_limit: Some(1 << 30),
+            spill_dir: Some(spill_dir.to_string()),
+            ..Default::default()
+        };
+        let db = setup(roomy).await;
+        assert_eq!(joins(&db).await, expected);
+        assert_eq!(db.memory_stats().await.spilled_joins, 0);
+
+        fs::remove_dir_all(spill_dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unwritable_spill_dir_falls_back_to_memory() {
+        let expected = joins(&setup(DatabaseConfig::default()).await).await;
+        let config = DatabaseConfig {
+            join_memory_limit: Some(1000),
+            spill_dir: Some("test_sort_merge_join_missing/nested".to_string()),
+            ..Default::default()
+        };
+        let db = setup(config).await;
+        assert_eq!(joins(&db).await, expected);
+        assert_eq!(db.memory_stats().await.spilled_joins, 0);
+    }
+
+    #[tokio::test]
+    async fn test_no_spill_dir_keeps_joins_in_memory() {
+        let expected = joins(&setup(DatabaseConfig::default()).await).await;
+        let config = DatabaseConfig {
+            join_memory_limit: Some(1000),
+            ..Default::default()
+        };
+        let db = setup(config).await;
+        assert_eq!(joins(&db).await, expected);
+        assert_eq!(db.memory_stats().await.spilled_joins, 0);
+    }
+}
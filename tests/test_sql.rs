@@ -0,0 +1,193 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{Database, DataType, Params, Row, SqlOutput, Value};
+
+    async fn rows(db: &Database, sql: &str) -> Vec<Row> {
+        match db.execute_sql(sql).await.unwrap() {
+            SqlOutput::Rows(rows) => rows,
+            other => panic!("expected rows, got {:?}", other),
+        }
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.execute_sql("CREATE TABLE users (id INTEGER PRIMARY KEY, name VARCHAR(32) NOT NULL, age INT)")
+            .await
+            .unwrap();
+        db.execute_sql(
+            "create table orders (id integer primary key, user_id integer references users (id), total float, placed timestamp)",
+        )
+        .await
+        .unwrap();
+        let inserted = db
+            .execute_sql("INSERT INTO users VALUES (1, 'Alice', 34), (2, 'Bob', 27), (3, 'Carol', 41), (4, 'O''Brien', NULL)")
+            .await
+            .unwrap();
+        assert_eq!(inserted, SqlOutput::Count(4));
+        db.execute_sql(
+            "INSERT INTO orders (id, user_id, total, placed) VALUES
+                (10, 1, 20, '2024-01-05T10:00:00Z'),
+                (11, 1, 5.5, '2024-02-01T10:00:00Z'),
+                (12, 2, 12.25, '2024-02-03T10:00:00Z');",
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_create_insert_and_select() {
+        let db = setup().await;
+
+        let columns = db.table_columns("orders").await.unwrap();
+        assert_eq!(columns[3].data_type, DataType::DateTime);
+        let users = rows(&db, "SELECT * FROM users WHERE age >= 30 AND name <> 'Carol'").await;
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0].get_str("name"), Some("Alice"));
+
+        // The integer total and the timestamp strings were converted.
+        let orders = rows(&db, "SELECT id FROM orders WHERE total < 15 OR placed < '2024-01-31T00:00:00Z' ORDER BY id DESC").await;
+        let ids: Vec<i64> = orders.iter().map(|r| r.get_i64("id").unwrap()).collect();
+        assert_eq!(ids, [12, 11, 10]);
+
+        let named = rows(&db, "select name as who, age from users where name ilike '%o%' order by who limit 2 offset 1").await;
+        let names: Vec<&str> = named.iter().map(|r| r.get_str("who").unwrap()).collect();
+        assert_eq!(names, ["Carol", "O'Brien"]);
+        assert_eq!(named[1].get("age"), Some(&Value::Null));
+        assert_eq!(named[0].get("name"), None);
+
+        let picked = rows(&db, "SELECT id FROM users WHERE id IN (1, 3) OR age BETWEEN 25 AND 30").await;
+        assert_eq!(picked.len(), 3);
+        let flipped = rows(&db, "SELECT id FROM users WHERE 30 < age").await;
+        assert_eq!(flipped.len(), 2);
+
+        // Constraints came from the statement.
+        assert!(db.execute_sql("INSERT INTO users (id, name) VALUES (1, 'Again')").await.is_err());
+        assert!(db.execute_sql("INSERT INTO orders (id, user_id) VALUES (13, 9)").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_update_and_delete() {
+        let db = setup().await;
+
+        let updated = db.execute_sql("UPDATE users SET age = 28, name = 'Robert' WHERE name = 'Bob'").await.unwrap();
+        assert_eq!(updated, SqlOutput::Count(1));
+        let bob = rows(&db, "SELECT name, age FROM users WHERE id = 2").await;
+        assert_eq!(bob[0].get_str("name"), Some("Robert"));
+        assert_eq!(bob[0].get_i64("age"), Some(28));
+
+        assert_eq!(db.execute_sql("ANALYZE users").await.unwrap(), SqlOutput::Done);
+        assert_eq!(db.table_stats("users").await.unwrap().columns["age"].distinct_count, 3);
+
+        let deleted = db.execute_sql("DELETE FROM orders WHERE user_id = 1").await.unwrap();
+        assert_eq!(deleted, SqlOutput::Count(2));
+        assert_eq!(rows(&db, "SELECT * FROM orders").await.len(), 1);
+
+        // Updates are replayed from the WAL.
+        let recovered = fixtures::reopen(db.storage());
+        recovered.load("test_sql_update_and_delete.zap").await.unwrap();
+        let bob = rows(&recovered, "SELECT name FROM users WHERE id = 2").await;
+        assert_eq!(bob[0].get_str("name"), Some("Robert"));
+    }
+
+    #[tokio::test]
+    async fn test_joins_and_aggregates() {
+        let db = setup().await;
+
+        let joined = rows(
+            &db,
+            "SELECT u.name, o.total FROM users u JOIN orders o ON o.user_id = u.id WHERE o.total > 10 ORDER BY o.total",
+        )
+        .await;
+        let pairs: Vec<(&str, f64)> =
+            joined.iter().map(|r| (r.get_str("u.name").unwrap(), r.get_f64("o.total").unwrap())).collect();
+        assert_eq!(pairs, [("Bob", 12.25), ("Alice", 20.0)]);
+
+        let left = rows(&db, "SELECT * FROM users LEFT JOIN orders ON users.id = orders.user_id").await;
+        assert_eq!(left.len(), 5);
+
+        let count = rows(&db, "SELECT COUNT(*) FROM users WHERE age > 30").await;
+        assert_eq!(count[0].get_i64("count(*)"), Some(2));
+
+        let totals = rows(&db, "SELECT user_id, SUM(total) AS spent FROM orders GROUP BY user_id ORDER BY spent DESC").await;
+        let totals: Vec<(i64, f64)> =
+            totals.iter().map(|r| (r.get_i64("user_id").unwrap(), r.get_f64("spent").unwrap())).collect();
+        assert_eq!(totals, [(1, 25.5), (2, 12.25)]);
+
+        assert!(db.execute_sql("SELECT name, COUNT(*) FROM users").await.is_err());
+        assert!(db.execute_sql("SELECT COUNT(*) FROM users u JOIN orders o ON u.id = o.user_id").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_syntax_errors_name_the_position() {
+        let db = setup().await;
+
+        let error = db.execute_sql("SELECT * FROM users WHERE age >").await.unwrap_err();
+        assert_eq!(error, "Expected a value at the end of the statement");
+        let error = db.execute_sql("SELECT * FROM users ORDER age").await.unwrap_err();
+        assert_eq!(error, "Expected BY at position 26, found age");
+        assert!(db.execute_sql("DROP TABLE users").await.is_err());
+        assert!(db.execute_sql("ANALYZE missing").await.is_err());
+        assert!(db.execute_sql("SELECT * FROM users WHERE name = 'open").await.is_err());
+        assert!(db.execute_sql("SELECT * FROM missing").await.is_err());
+        assert!(db.execute_sql("SELECT nope FROM users").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_parameters() {
+        let db = setup().await;
+        let select = |sql: &'static str, params: Vec<Value>| {
+            let db = &db;
+            async move {
+                match db.execute_sql_with_params(sql, &params).await.unwrap() {
+                    SqlOutput::Rows(rows) => rows,
+                    other => panic!("expected rows, got {:?}", other),
+                }
+            }
+        };
+
+        let found = select("SELECT name FROM users WHERE age > ? AND age < ?", vec![Value::Integer(30), Value::Integer(40)]).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get("name"), Some(&Value::String("Alice".to_string())));
+
+        // Input is bound as a value, however it is written.
+        let input = Value::String("x' OR '1'='1".to_string());
+        assert!(select("SELECT * FROM users WHERE name = ?", vec![input.clone()]).await.is_empty());
+        db.execute_sql_with_params("INSERT INTO users (id, name) VALUES (?, ?)", &[Value::Integer(5), input.clone()])
+            .await
+            .unwrap();
+        assert_eq!(select("SELECT id FROM users WHERE name = ?", vec![input]).await.len(), 1);
+
+        let params = Params::from([("name".to_string(), Value::String("Bob".to_string())), ("age".to_string(), Value::Integer(28))]);
+        let updated = db
+            .execute_sql_with_named_params("UPDATE users SET age = :age WHERE name = :name OR name = :name", &params)
+            .await
+            .unwrap();
+        assert_eq!(updated, SqlOutput::Count(1));
+        assert_eq!(rows(&db, "SELECT age FROM users WHERE id = 2").await[0].get("age"), Some(&Value::Integer(28)));
+        let found = select(
+            "SELECT id FROM users WHERE id IN (SELECT user_id FROM orders WHERE total > ?)",
+            vec![Value::Float(10.0)],
+        )
+        .await;
+        assert_eq!(found.len(), 2);
+
+        // A statement with parameters can't also carry literals, and the
+        // parameters must all be given.
+        let error = db
+            .execute_sql_with_params("SELECT * FROM users WHERE name = 'x' OR '1'='1' AND age = ?", &[Value::Integer(1)])
+            .await
+            .unwrap_err();
+        assert!(error.contains("literal values aren't allowed"), "{}", error);
+        let error = db.execute_sql_with_params("SELECT * FROM users WHERE age = ?", &[]).await.unwrap_err();
+        assert_eq!(error, "Expected 1 parameters, got 0");
+        let error = db
+            .execute_sql_with_named_params("DELETE FROM users WHERE id = :id", &Params::new())
+            .await
+            .unwrap_err();
+        assert_eq!(error, "Missing parameter id");
+        assert!(db.execute_sql_with_params("SELECT * FROM users WHERE id = ? OR id = :id", &[]).await.is_err());
+        assert!(db.execute_sql("SELECT * FROM users WHERE id = ?").await.is_err());
+    }
+}
@@ -0,0 +1,195 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{AutoAnalyze, Column, Condition, DataType, Database, DatabaseConfig, Operator, Query, Value};
+    use std::collections::HashMap;
+    use std::fs;
+
+    fn condition(column: &str, operator: Operator, value: Value) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value,
+        })
+    }
+
+    async fn open(wal_path: &str) -> Database {
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "readings".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("sensor".to_string(), DataType::String, vec![]),
+                Column::new("value".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for id in 0..1000 {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("sensor".to_string(), Value::String(format!("s{}", id % 4)));
+            if id % 10 != 0 {
+                row.insert("value".to_string(), Value::Integer(id));
+            }
+            db.insert("readings", row).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_analyze() {
+        let wal_path = "test_stats_analyze.wal";
+        let db = open(wal_path).await;
+        assert_eq!(db.table_stats("readings").await, None);
+
+        let stats = db.analyze("readings").await.unwrap();
+        assert_eq!(stats.row_count, 1000);
+        assert_eq!(db.table_stats("readings").await, Some(stats.clone()));
+
+        let sensor = &stats.columns["sensor"];
+        assert_eq!(sensor.null_count, 0);
+        assert_eq!(sensor.distinct_count, 4);
+
+        let value = &stats.columns["value"];
+        assert_eq!(value.null_count, 100);
+        assert_eq!(value.distinct_count, 900);
+        assert_eq!(value.histogram.first(), Some(&Value::Integer(1)));
+        assert_eq!(value.histogram.last(), Some(&Value::Integer(999)));
+        assert!(value.histogram.windows(2).all(|w| w[0] <= w[1]));
+
+        assert!(db.analyze("missing").await.is_err());
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_planned_results_unchanged() {
+        let wal_path = "test_stats_planned.wal";
+        let db = open(wal_path).await;
+        db.create_index("readings", "sensor").await.unwrap();
+
+        let query = Query::And(vec![
+            condition("value", Operator::Gte, Value::Integer(100)),
+            condition("sensor", Operator::Eq, Value::String("s1".to_string())),
+            condition("value", Operator::Lt, Value::Integer(200)),
+        ]);
+        let (before, _) = db.select("readings", &query).await.unwrap();
+        db.analyze("readings").await.unwrap();
+        let (after, _) = db.select("readings", &query).await.unwrap();
+
+        let ids = |rows: &[zapdb::Row]| {
+            let mut ids: Vec<Value> = rows.iter().map(|r| r.get("id").unwrap().clone()).collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(before.len(), 25);
+        assert_eq!(ids(&before), ids(&after));
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_cached_plans_follow_changes() {
+        let wal_path = "test_stats_plan_cache.wal";
+        let db = open(wal_path).await;
+
+        // Queries of the same shape share a plan; each must still get its
+        // own rows, before and after the indexes and statistics change.
+        let query = |sensor: &str, below: i64| {
+            Query::And(vec![
+                condition("value", Operator::Lt, Value::Integer(below)),
+                condition("sensor", Operator::Eq, Value::String(sensor.to_string())),
+            ])
+        };
+        let expected = |sensor: i64, below: i64| (1..below).filter(|id| id % 4 == sensor && id % 10 != 0).count();
+        for step in 0..3 {
+            match step {
+                1 => db.create_index("readings", "sensor").await.unwrap(),
+                2 => drop(db.analyze("readings").await.unwrap()),
+                _ => {}
+            }
+            for (sensor, below) in [(1, 100), (2, 500), (3, 40)] {
+                let (rows, _) = db.select("readings", &query(&format!("s{}", sensor), below)).await.unwrap();
+                assert_eq!(rows.len(), expected(sensor, below));
+            }
+        }
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_estimates_learn_from_queries() {
+        let wal_path = "test_stats_feedback.wal";
+        let db = open(wal_path).await;
+        let sensor = Condition {
+            column: "sensor".to_string(),
+            operator: Operator::Eq,
+            value: Value::String("s1".to_string()),
+        };
+        let low = Condition {
+            column: "value".to_string(),
+            operator: Operator::Lt,
+            value: Value::Integer(100),
+        };
+
+        // Without statistics, equality guesses a tenth of the rows and a
+        // range a third.
+        assert_eq!(db.estimate_rows("readings", &sensor).await.unwrap().round(), 100.0);
+        assert_eq!(db.estimate_rows("readings", &low).await.unwrap().round(), 333.0);
+        for _ in 0..20 {
+            db.select("readings", &Query::Condition(sensor.clone())).await.unwrap();
+            db.select("readings", &Query::Condition(low.clone())).await.unwrap();
+        }
+        let estimate = db.estimate_rows("readings", &sensor).await.unwrap();
+        assert!((200.0..300.0).contains(&estimate), "{}", estimate);
+        let estimate = db.estimate_rows("readings", &low).await.unwrap();
+        assert!((70.0..120.0).contains(&estimate), "{}", estimate);
+
+        assert!(db.estimate_rows("missing", &sensor).await.is_err());
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_auto_analyze() {
+        let wal_path = "test_stats_auto_analyze.wal";
+        let _ = fs::remove_file(wal_path);
+        let config = DatabaseConfig {
+            auto_analyze: Some(AutoAnalyze {
+                min_changes: 10,
+                fraction: 0.5,
+            }),
+            ..Default::default()
+        };
+        let db = Database::with_config([0; 32], wal_path, config);
+        db.create_table(
+            "readings".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        let insert = |id: i64| {
+            let row = HashMap::from([("id".to_string(), Value::Integer(id))]);
+            db.insert("readings", row)
+        };
+
+        // Over 10 changes plus half the rows: with only inserts, at 21 rows.
+        for id in 0..20 {
+            insert(id).await.unwrap();
+        }
+        assert_eq!(db.table_stats("readings").await, None);
+        insert(20).await.unwrap();
+        assert_eq!(db.table_stats("readings").await.unwrap().row_count, 21);
+
+        // The count starts again.
+        for id in 21..31 {
+            insert(id).await.unwrap();
+        }
+        assert_eq!(db.table_stats("readings").await.unwrap().row_count, 21);
+
+        // Deletes count too, one per row.
+        db.delete("readings", &condition("id", Operator::Lt, Value::Integer(25))).await.unwrap();
+        let stats = db.table_stats("readings").await.unwrap();
+        assert_eq!(stats.row_count, 6);
+        assert_eq!(stats.columns["id"].distinct_count, 6);
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{
+        Column, DataType, Database, DatabaseConfig, MemoryStorage, Query, StorageBackend, Value,
+    };
+    use std::collections::HashMap;
+    use std::io;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn open(storage: &Arc<MemoryStorage>) -> Database {
+        let config = DatabaseConfig {
+            storage: Some(storage.clone()),
+            ..Default::default()
+        };
+        Database::with_config([0; 32], "test_memory_storage.wal", config)
+    }
+
+    async fn user_count(db: &Database) -> usize {
+        db.count("users", &Query::MatchAll).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage);
+        db.create_table(
+            "users".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        for id in 0..3 {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            db.insert("users", row).await.unwrap();
+        }
+
+        // Nothing touches the file system.
+        assert!(!Path::new("test_memory_storage.wal").exists());
+        let wal_len = storage.read("test_memory_storage.wal").unwrap().unwrap().len();
+        assert!(wal_len > 0);
+
+        // A second database on the same storage recovers from the WAL...
+        let replica = open(&storage);
+        replica.load("snapshot.zap").await.unwrap();
+        assert_eq!(user_count(&replica).await, 3);
+
+        // ...and from a snapshot, which truncates the WAL.
+        db.save("snapshot.zap").await.unwrap();
+        assert!(!Path::new("snapshot.zap").exists());
+        assert!(storage.read("test_memory_storage.wal").unwrap().unwrap().len() < wal_len);
+        let restored = open(&storage);
+        restored.load("snapshot.zap").await.unwrap();
+        assert_eq!(user_count(&restored).await, 3);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_database() {
+        let db = Database::in_memory([0; 32]);
+        db.create_table(
+            "users".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        db.save("users.zap").await.unwrap();
+        assert!(!Path::new("zapdb.wal").exists());
+        assert!(!Path::new("users.zap").exists());
+
+        // Another database can be opened on the same storage.
+        let config = DatabaseConfig {
+            storage: Some(db.storage()),
+            ..Default::default()
+        };
+        let reopened = Database::with_config([0; 32], "zapdb.wal", config);
+        reopened.load("users.zap").await.unwrap();
+        assert_eq!(reopened.table_names().await, vec!["users"]);
+    }
+
+    // Storage whose whole-object reads and writes take a while, like a big
+    // snapshot on a slow disk.
+    #[derive(Debug, Default)]
+    struct SlowStorage(MemoryStorage);
+
+    impl StorageBackend for SlowStorage {
+        fn read(&self, name: &str) -> io::Result<Option<Vec<u8>>> {
+            std::thread::sleep(Duration::from_millis(200));
+            self.0.read(name)
+        }
+
+        fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+            std::thread::sleep(Duration::from_millis(200));
+            self.0.write(name, data)
+        }
+
+        fn append(&self, name: &str, data: &[u8]) -> io::Result<()> {
+            self.0.append(name, data)
+        }
+
+        fn sync(&self, name: &str) -> io::Result<()> {
+            self.0.sync(name)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_save_and_load_leave_the_executor_free() {
+        let config = DatabaseConfig {
+            storage: Some(Arc::new(SlowStorage::default())),
+            ..Default::default()
+        };
+        let db = Database::with_config([0; 32], "test_slow_storage.wal", config);
+        db.create_table(
+            "users".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+
+        // The test runs on one thread, so the ticker only ticks while the
+        // storage is called somewhere else.
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let ticker = tokio::spawn({
+            let ticks = ticks.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+        db.save("slow.zap").await.unwrap();
+        let saved = ticks.load(Ordering::Relaxed);
+        assert!(saved > 5, "only {} ticks during save", saved);
+        db.load("slow.zap").await.unwrap();
+        assert!(ticks.load(Ordering::Relaxed) > saved + 5);
+        ticker.abort();
+        assert_eq!(db.table_names().await, vec!["users"]);
+    }
+}
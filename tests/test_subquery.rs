@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use std::collections::HashMap;
+    use zapdb::{Column, Condition, DataType, Database, Join, JoinChain, JoinType, Operator, Query, SqlOutput, Value};
+
+    fn eq(column: &str, value: Value) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator: Operator::Eq,
+            value,
+        })
+    }
+
+    fn active_customers() -> Query {
+        Query::InSubquery {
+            column: "customer_id".to_string(),
+            table: "customers".to_string(),
+            subquery: Box::new(eq("status", Value::String("active".to_string()))),
+            select_column: "id".to_string(),
+        }
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "customers".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("status".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "orders".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("customer_id".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, status) in [(1, "active"), (2, "closed"), (3, "active")] {
+            let customer = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("status".to_string(), Value::String(status.to_string())),
+            ]);
+            db.insert("customers", customer).await.unwrap();
+        }
+        let orders = [
+            (10, Value::Integer(1)),
+            (11, Value::Integer(2)),
+            (12, Value::Integer(3)),
+            (13, Value::Null),
+        ];
+        for (id, customer_id) in orders {
+            let order = HashMap::from([
+                ("id".to_string(), Value::Integer(id)),
+                ("customer_id".to_string(), customer_id),
+            ]);
+            db.insert("orders", order).await.unwrap();
+        }
+        db
+    }
+
+    fn ids(rows: &[zapdb::Row]) -> Vec<Value> {
+        rows.iter().map(|row| row.get("id").unwrap().clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_in_subquery() {
+        let db = setup().await;
+
+        let (rows, _) = db.select("orders", &active_customers()).await.unwrap();
+        assert_eq!(ids(&rows), vec![Value::Integer(10), Value::Integer(12)]);
+
+        // The same through the outer column's index.
+        db.create_index("orders", "customer_id").await.unwrap();
+        let (rows, _) = db.select("orders", &active_customers()).await.unwrap();
+        assert_eq!(ids(&rows), vec![Value::Integer(10), Value::Integer(12)]);
+
+        let both = Query::And(vec![active_customers(), eq("id", Value::Integer(12))]);
+        let (rows, _) = db.select("orders", &both).await.unwrap();
+        assert_eq!(ids(&rows), vec![Value::Integer(12)]);
+
+        assert_eq!(db.delete("orders", &active_customers()).await.unwrap(), 2);
+        let (rows, _) = db.select("orders", &Query::MatchAll).await.unwrap();
+        assert_eq!(ids(&rows), vec![Value::Integer(11), Value::Integer(13)]);
+    }
+
+    #[tokio::test]
+    async fn test_in_subquery_errors() {
+        let db = setup().await;
+
+        let missing_table = Query::InSubquery {
+            column: "customer_id".to_string(),
+            table: "missing".to_string(),
+            subquery: Box::new(Query::MatchAll),
+            select_column: "id".to_string(),
+        };
+        assert!(db.select("orders", &missing_table).await.is_err());
+        let missing_column = Query::InSubquery {
+            column: "customer_id".to_string(),
+            table: "customers".to_string(),
+            subquery: Box::new(Query::MatchAll),
+            select_column: "missing".to_string(),
+        };
+        assert!(db.select("orders", &missing_column).await.is_err());
+        assert!(db.delete("orders", &missing_table).await.is_err());
+        assert!(db.update("orders", &missing_table, |_| {}).await.is_err());
+
+        // Join filters aren't checked before they run, but still fail.
+        let chain = JoinChain::new()
+            .join(Join::new(JoinType::Inner, "customers", ("customer_id", "id")).prefixed())
+            .filter(missing_table.clone());
+        assert!(db.select_joins("orders", &chain).await.is_err());
+
+        assert!(db.watch("orders", active_customers()).await.is_err());
+        assert!(db.create_materialized_view("active_orders", "orders", active_customers()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_in_subquery_json_and_sql() {
+        let db = setup().await;
+
+        let json = active_customers().to_json().to_string();
+        assert_eq!(db.query_from_json("orders", &json).await.unwrap().to_json(), active_customers().to_json());
+        let columns = db.table_columns("orders").await.unwrap();
+        assert!(Query::from_json(&json, &columns).is_err());
+
+        let SqlOutput::Rows(rows) = db
+            .execute_sql("SELECT id FROM orders WHERE customer_id IN (SELECT id FROM customers WHERE status = 'active')")
+            .await
+            .unwrap()
+        else {
+            panic!("expected rows");
+        };
+        assert_eq!(ids(&rows), vec![Value::Integer(10), Value::Integer(12)]);
+    }
+}
@@ -0,0 +1,162 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{
+        Column, Condition, DataType, Database, Join, JoinType, LimitError, MemoryStorage, Operator, Query, StorageBackend,
+        TableLimits, TenantConfig, Value, WriteError,
+    };
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn open(storage: &Arc<MemoryStorage>) -> Database {
+        let config = zapdb::DatabaseConfig {
+            storage: Some(storage.clone()),
+            ..Default::default()
+        };
+        Database::with_config([0; 32], "test_tenants.wal", config)
+    }
+
+    fn user(id: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row
+    }
+
+    fn users() -> Vec<Column> {
+        vec![Column::new("id".to_string(), DataType::Integer, vec![])]
+    }
+
+    #[tokio::test]
+    async fn test_tenant_isolation() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage);
+        for (name, key) in [("acme", [1; 32]), ("globex", [2; 32])] {
+            let config = TenantConfig {
+                key,
+                limits: TableLimits {
+                    max_rows: Some(3),
+                    ..Default::default()
+                },
+            };
+            db.create_tenant(name, config).await.unwrap();
+        }
+        assert!(db.create_tenant("a/b", TenantConfig { key: [0; 32], limits: TableLimits::default() }).await.is_err());
+
+        let acme = db.tenant("acme").unwrap();
+        let globex = db.tenant("globex").unwrap();
+        acme.create_table("users", users()).await.unwrap();
+        globex.create_table("users", users()).await.unwrap();
+        db.create_table("users".to_string(), users()).await.unwrap();
+        for id in 0..3 {
+            acme.insert("users", user(id)).await.unwrap();
+        }
+        globex.insert("users", user(10)).await.unwrap();
+
+        assert_eq!(acme.count("users", &Query::MatchAll).await.unwrap(), 3);
+        assert_eq!(globex.count("users", &Query::MatchAll).await.unwrap(), 1);
+        assert_eq!(db.count("users", &Query::MatchAll).await.unwrap(), 0);
+        assert_eq!(db.count("acme/users", &Query::MatchAll).await.unwrap(), 3);
+        assert_eq!(acme.table_names().await.unwrap(), vec!["users".to_string()]);
+
+        // Quotas are per tenant.
+        assert!(matches!(
+            acme.insert("users", user(3)).await,
+            Err(WriteError::Limit(LimitError::TooManyRows { limit: 3, .. }))
+        ));
+
+        // Joins stay inside the namespace.
+        let join = Query::Join(Join::new(JoinType::Inner, "users", ("id", "id")));
+        assert_eq!(globex.select("users", &join).await.unwrap().0.len(), 1);
+
+        // So do subqueries, even when they name another tenant's table.
+        let in_users = |table: &str| Query::InSubquery {
+            column: "id".to_string(),
+            table: table.to_string(),
+            subquery: Box::new(Query::MatchAll),
+            select_column: "id".to_string(),
+        };
+        assert_eq!(globex.select("users", &in_users("users")).await.unwrap().0.len(), 1);
+        assert!(globex.select("users", &in_users("acme/users")).await.is_err());
+        assert!(globex.count("users", &in_users("acme/users")).await.is_err());
+
+        db.suspend_tenant("acme").await.unwrap();
+        assert!(acme.count("users", &Query::MatchAll).await.is_err());
+        assert!(acme.insert("users", user(4)).await.is_err());
+        assert_eq!(globex.count("users", &Query::MatchAll).await.unwrap(), 1);
+        db.resume_tenant("acme").await.unwrap();
+        let by_id = Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(1),
+        });
+        assert_eq!(acme.delete("users", &by_id).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_export() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage);
+        let config = TenantConfig {
+            key: [7; 32],
+            limits: TableLimits::default(),
+        };
+        db.create_tenant("acme", config.clone()).await.unwrap();
+        let acme = db.tenant("acme").unwrap();
+        acme.create_table("users", users()).await.unwrap();
+        for id in 0..5 {
+            acme.insert("users", user(id)).await.unwrap();
+        }
+        db.suspend_tenant("acme").await.unwrap();
+        db.export_tenant("acme", "acme.export").await.unwrap();
+
+        // The export is readable with the tenant's key only.
+        let other = Database::in_memory([0; 32]);
+        other.create_tenant("acme", TenantConfig { key: [8; 32], ..config.clone() }).await.unwrap();
+        other.storage().write("acme.export", &storage.read("acme.export").unwrap().unwrap()).unwrap();
+        assert!(other.import_tenant("acme", "acme.export").await.is_err());
+
+        let other = Database::in_memory([0; 32]);
+        other.create_tenant("moved", config).await.unwrap();
+        other.storage().write("acme.export", &storage.read("acme.export").unwrap().unwrap()).unwrap();
+        other.import_tenant("moved", "acme.export").await.unwrap();
+        let moved = other.tenant("moved").unwrap();
+        assert_eq!(moved.count("users", &Query::MatchAll).await.unwrap(), 5);
+        assert!(other.import_tenant("moved", "acme.export").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tenants_persist() {
+        let storage = Arc::new(MemoryStorage::new());
+        let db = open(&storage);
+        let config = TenantConfig {
+            key: [7; 32],
+            limits: TableLimits {
+                max_rows: Some(1),
+                ..Default::default()
+            },
+        };
+        db.create_tenant("acme", config.clone()).await.unwrap();
+        db.create_tenant("globex", config).await.unwrap();
+        db.tenant("acme").unwrap().create_table("users", users()).await.unwrap();
+        db.suspend_tenant("acme").await.unwrap();
+
+        // From the WAL.
+        let reopened = open(&storage);
+        reopened.load("test_tenants.zap").await.unwrap();
+        let mut names = reopened.tenant_names();
+        names.sort();
+        assert_eq!(names, vec!["acme".to_string(), "globex".to_string()]);
+        assert!(reopened.tenant("acme").unwrap().count("users", &Query::MatchAll).await.is_err());
+
+        // From a snapshot, limits included.
+        db.resume_tenant("acme").await.unwrap();
+        db.save("test_tenants.zap").await.unwrap();
+        let reopened = open(&storage);
+        reopened.load("test_tenants.zap").await.unwrap();
+        let acme = reopened.tenant("acme").unwrap();
+        acme.insert("users", user(1)).await.unwrap();
+        assert!(matches!(
+            acme.insert("users", user(2)).await,
+            Err(WriteError::Limit(LimitError::TooManyRows { limit: 1, .. }))
+        ));
+    }
+}
@@ -0,0 +1,171 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{query, Column, Condition, Constraint, DataType, Database, Operator, Query, Value};
+
+    fn ilike(column: &str, pattern: &str) -> Query {
+        matching(column, Operator::ILike, pattern)
+    }
+
+    fn matching(column: &str, operator: Operator, pattern: &str) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value: Value::String(pattern.to_string()),
+        })
+    }
+
+    async fn create_products(db: &Database, names: &[&str]) {
+        db.create_table(
+            "products".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, name) in names.iter().enumerate() {
+            let row = HashMap::from([
+                ("id".to_string(), Value::Integer(id as i64)),
+                ("name".to_string(), Value::String(name.to_string())),
+            ]);
+            db.insert("products", row).await.unwrap();
+        }
+    }
+
+    async fn names(db: &Database, query: &Query) -> Vec<String> {
+        let (rows, _) = db.select("products", query).await.unwrap();
+        rows.iter().map(|row| row.get_str("name").unwrap().to_string()).collect()
+    }
+
+    const NAMES: [&str; 6] = [
+        "Red Apple",
+        "Green APPLE pie",
+        "Pineapple",
+        "Banana",
+        "apple_juice",
+        "Grape",
+    ];
+
+    #[tokio::test]
+    async fn test_ilike_matches_with_and_without_text_index() {
+        let wal_path = "test_ilike_matches_with_and_without_text_index.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        create_products(&db, &NAMES).await;
+
+        let patterns = ["%apple%", "apple%", "%APPLE", "%ap_le%", "%pp%", "%", "%apple\\_j%", "%kiwi%", "banana"];
+        let mut unindexed = Vec::new();
+        for pattern in patterns {
+            unindexed.push(names(&db, &ilike("name", pattern)).await);
+        }
+        assert_eq!(unindexed[0], vec!["Red Apple", "Green APPLE pie", "Pineapple", "apple_juice"]);
+        assert_eq!(unindexed[1], vec!["apple_juice"]);
+        assert_eq!(unindexed[2], vec!["Red Apple", "Pineapple"]);
+        assert_eq!(unindexed[5].len(), NAMES.len());
+        assert_eq!(unindexed[6], vec!["apple_juice"]);
+        assert!(unindexed[7].is_empty());
+        assert_eq!(unindexed[8], vec!["Banana"]);
+
+        db.create_text_index("products", "name").await.unwrap();
+        assert!(db.memory_stats().await.index_bytes > 0);
+        for (pattern, expected) in patterns.iter().zip(&unindexed) {
+            assert_eq!(&names(&db, &ilike("name", pattern)).await, expected, "{}", pattern);
+        }
+
+        // Combined with other conditions, the indexed condition is still exact.
+        let query = Query::And(vec![ilike("name", "%apple%"), query!(id >= 2)]);
+        assert_eq!(names(&db, &query).await, vec!["Pineapple", "apple_juice"]);
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_like_not_like_and_regex() {
+        let wal_path = "test_like_not_like_and_regex.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        create_products(&db, &NAMES).await;
+
+        let cases = [
+            (Operator::Like, "%apple%", vec!["Pineapple", "apple_juice"]),
+            (Operator::Like, "%Apple", vec!["Red Apple"]),
+            (Operator::NotLike, "%apple%", vec!["Red Apple", "Green APPLE pie", "Banana", "Grape"]),
+            (Operator::Regex, "^(Red|Green) ", vec!["Red Apple", "Green APPLE pie"]),
+            (Operator::Regex, "(?i)apple$", vec!["Red Apple", "Pineapple"]),
+            (Operator::Regex, "an", vec!["Banana"]),
+        ];
+        // Scanned, through the trigram index, and through a hash index.
+        for step in 0..3 {
+            match step {
+                1 => db.create_text_index("products", "name").await.unwrap(),
+                2 => db.create_index("products", "name").await.unwrap(),
+                _ => {}
+            }
+            for (operator, pattern, expected) in &cases {
+                let query = matching("name", operator.clone(), pattern);
+                assert_eq!(&names(&db, &query).await, expected, "{:?} {}", operator, pattern);
+            }
+        }
+
+        // Values that aren't strings match none of them.
+        for operator in [Operator::Like, Operator::NotLike, Operator::Regex] {
+            assert!(names(&db, &matching("id", operator, "%")).await.is_empty());
+        }
+        let invalid = db.select("products", &matching("name", Operator::Regex, "(unclosed")).await;
+        assert!(invalid.unwrap_err().contains("Invalid regex"));
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_text_index_is_maintained_on_write() {
+        let wal_path = "test_text_index_is_maintained_on_write.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        create_products(&db, &NAMES).await;
+        db.create_text_index("products", "name").await.unwrap();
+
+        let row = HashMap::from([
+            ("id".to_string(), Value::Integer(10)),
+            ("name".to_string(), Value::String("Toffee Apple".to_string())),
+        ]);
+        db.insert("products", row).await.unwrap();
+        db.delete("products", &query!(id == 0)).await.unwrap();
+        db.update("products", &query!(id == 3), |row| {
+            row.insert("name".to_string(), Value::String("Banana apple smoothie".to_string()));
+        })
+        .await
+        .unwrap();
+        db.update("products", &query!(id == 2), |row| {
+            row.insert("name".to_string(), Value::String("Pineapple chunks".to_string()));
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(
+            names(&db, &ilike("name", "%apple%")).await,
+            vec!["Green APPLE pie", "Pineapple chunks", "Banana apple smoothie", "apple_juice", "Toffee Apple"]
+        );
+        assert_eq!(names(&db, &ilike("name", "%banana%")).await, vec!["Banana apple smoothie"]);
+        assert!(names(&db, &ilike("name", "red%")).await.is_empty());
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_create_text_index_requires_a_string_column() {
+        let wal_path = "test_create_text_index_requires_a_string_column.wal";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::new([0; 32], wal_path);
+        create_products(&db, &NAMES).await;
+
+        assert!(db.create_text_index("products", "id").await.is_err());
+        assert!(db.create_text_index("products", "missing").await.is_err());
+        assert!(db.create_text_index("missing", "name").await.is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,95 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use chrono::{DateTime, Duration, TimeZone, Utc};
+    use std::collections::HashMap;
+    use zapdb::{query, time_bucket, AggregateFunction, Column, DataType, Database, Fill, Row, TimeSeries, Value};
+
+    fn at(minute: i64, second: i64) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap() + Duration::minutes(minute) + Duration::seconds(second)
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "metrics".to_string(),
+            vec![
+                Column::new("at".to_string(), DataType::DateTime, vec![]),
+                Column::new("host".to_string(), DataType::String, vec![]),
+                Column::new("cpu".to_string(), DataType::Float, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        // Readings in the buckets at 12:00, 12:05 and 12:20.
+        for (minute, second, host, cpu) in [(0, 10, "a", 10.0), (3, 0, "b", 30.0), (5, 0, "a", 50.0), (24, 59, "a", 80.0)] {
+            let row = HashMap::from([
+                ("at".to_string(), Value::DateTime(at(minute, second))),
+                ("host".to_string(), Value::String(host.to_string())),
+                ("cpu".to_string(), Value::Float(cpu)),
+            ]);
+            db.insert("metrics", row).await.unwrap();
+        }
+        db.insert("metrics", HashMap::from([("cpu".to_string(), Value::Float(99.0))])).await.unwrap();
+        db
+    }
+
+    fn column(rows: &[Row], name: &str) -> Vec<Value> {
+        rows.iter().map(|row| row.get(name).cloned().unwrap()).collect()
+    }
+
+    fn series() -> TimeSeries {
+        TimeSeries::new("at", "5m")
+            .measure("readings", AggregateFunction::Count, "cpu")
+            .measure("cpu", AggregateFunction::Avg, "cpu")
+    }
+
+    #[tokio::test]
+    async fn test_time_series_buckets_and_fills() {
+        let db = setup().await;
+
+        let rows = db.time_series("metrics", &series()).await.unwrap();
+        assert_eq!(column(&rows, "at"), vec![Value::DateTime(at(0, 0)), Value::DateTime(at(5, 0)), Value::DateTime(at(20, 0))]);
+        assert_eq!(column(&rows, "readings"), vec![Value::Integer(2), Value::Integer(1), Value::Integer(1)]);
+        assert_eq!(column(&rows, "cpu"), vec![Value::Float(20.0), Value::Float(50.0), Value::Float(80.0)]);
+
+        let rows = db.time_series("metrics", &series().fill(Fill::Linear)).await.unwrap();
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[2].get("at"), Some(&Value::DateTime(at(10, 0))));
+        assert_eq!(column(&rows, "cpu")[1..4], [Value::Float(50.0), Value::Float(60.0), Value::Float(70.0)]);
+        assert_eq!(column(&rows, "readings")[2], Value::Integer(1));
+
+        let rows = db.time_series("metrics", &series().fill(Fill::Previous)).await.unwrap();
+        assert_eq!(column(&rows, "cpu")[2..4], [Value::Float(50.0), Value::Float(50.0)]);
+        let rows = db.time_series("metrics", &series().fill(Fill::Value(Value::Integer(0)))).await.unwrap();
+        assert_eq!(column(&rows, "readings"), vec![2, 1, 0, 0, 1].into_iter().map(Value::Integer).collect::<Vec<_>>());
+
+        // A range restricts the rows and fills gaps across all of it.
+        let range = series().between(at(-10, 0), at(10, 0)).fill(Fill::Null);
+        let rows = db.time_series("metrics", &range).await.unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(column(&rows, "cpu"), vec![Value::Null, Value::Null, Value::Float(20.0), Value::Float(50.0)]);
+
+        let filtered = series().filter(query!(host == "a"));
+        let rows = db.time_series("metrics", &filtered).await.unwrap();
+        assert_eq!(column(&rows, "cpu")[0], Value::Float(10.0));
+    }
+
+    #[tokio::test]
+    async fn test_time_series_rejects_bad_definitions() {
+        let db = setup().await;
+        let bucket = |width| TimeSeries::new("at", width).measure("n", AggregateFunction::Count, "cpu");
+        assert!(db.time_series("metrics", &bucket("5")).await.is_err());
+        assert!(db.time_series("metrics", &bucket("0m")).await.is_err());
+        assert!(db.time_series("metrics", &bucket("5y")).await.is_err());
+        assert!(db.time_series("metrics", &TimeSeries::new("at", "5m")).await.is_err());
+        let on_host = TimeSeries::new("host", "5m").measure("n", AggregateFunction::Count, "cpu");
+        assert!(db.time_series("metrics", &on_host).await.is_err());
+        let huge = bucket("1ms").between(at(0, 0), at(60 * 24 * 365, 0)).fill(Fill::Null);
+        assert!(db.time_series("metrics", &huge).await.is_err());
+
+        assert_eq!(time_bucket("15m", &at(29, 59)).unwrap(), at(15, 0));
+        assert_eq!(time_bucket("1h", &at(-1, 0)).unwrap(), at(-60, 0));
+        assert!(time_bucket("soon", &at(0, 0)).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use std::time::Duration;
+    use zapdb::{Column, DataType, Database, DatabaseConfig, Query, SessionContext, Timeout, Value, WriteError};
+
+    fn row(id: i64) -> HashMap<String, Value> {
+        HashMap::from([("id".to_string(), Value::Integer(id))])
+    }
+
+    #[tokio::test]
+    async fn test_write_times_out_waiting_for_the_lock() {
+        let wal_path = "test_write_times_out_waiting_for_the_lock.wal";
+        let _ = fs::remove_file(wal_path);
+        let config = DatabaseConfig {
+            operation_timeout: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let db = Database::with_config([0; 32], wal_path, config.clone());
+        db.create_table(
+            "items".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+
+        let stuck = db.tables.write().await;
+        let err = db.insert("items", row(1)).await.unwrap_err();
+        assert_eq!(err, WriteError::Timeout(Timeout { after: Duration::from_millis(50) }));
+        assert!(err.is_retryable());
+
+        // The session's timeout takes precedence.
+        db.set_session(SessionContext {
+            statement_timeout: Some(Duration::from_millis(10)),
+            ..Default::default()
+        });
+        let err = db.insert("items", row(2)).await.unwrap_err();
+        assert_eq!(err, WriteError::Timeout(Timeout { after: Duration::from_millis(10) }));
+        drop(stuck);
+
+        db.insert("items", row(3)).await.unwrap();
+        assert_eq!(db.count("items", &Query::MatchAll).await.unwrap(), 1);
+
+        // Nothing was logged for the writes that timed out.
+        let reopened = Database::with_config([0; 32], wal_path, config);
+        reopened.load("missing.zap").await.unwrap();
+        assert_eq!(reopened.count("items", &Query::MatchAll).await.unwrap(), 1);
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_other_operations_time_out() {
+        let wal_path = "test_other_operations_time_out.wal";
+        let snapshot_path = "test_other_operations_time_out.zap";
+        let _ = fs::remove_file(wal_path);
+        let db = Database::with_config(
+            [0; 32],
+            wal_path,
+            DatabaseConfig {
+                operation_timeout: Some(Duration::from_millis(20)),
+                ..Default::default()
+            },
+        );
+        db.create_table(
+            "items".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        db.insert("items", row(1)).await.unwrap();
+        db.create_materialized_view("all_items", "items", Query::MatchAll).await.unwrap();
+
+        let stuck = db.tables.write().await;
+        let err = db.save(snapshot_path).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+        assert!(db.backup(snapshot_path).await.is_err());
+        assert!(db.create_index("items", "id").await.is_err());
+        assert!(db.refresh_materialized_view("all_items").await.is_err());
+        assert!(db.analyze("items").await.is_err());
+        // Queries don't wait for writers.
+        assert_eq!(db.count("items", &Query::MatchAll).await.unwrap(), 1);
+        drop(stuck);
+
+        // While maintenance is deferred they read through the lock, so they
+        // can time out too.
+        let result = db
+            .with_deferred_maintenance(async |db| {
+                let stuck = db.tables.write().await;
+                let result = db.count("items", &Query::MatchAll).await;
+                drop(stuck);
+                result
+            })
+            .await;
+        assert!(result.unwrap_err().contains("timed out"));
+
+        db.save(snapshot_path).await.unwrap();
+        let reopened = Database::new([0; 32], wal_path);
+        reopened.load(snapshot_path).await.unwrap();
+        assert_eq!(reopened.count("items", &Query::MatchAll).await.unwrap(), 1);
+
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(snapshot_path);
+    }
+}
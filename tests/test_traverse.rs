@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{Column, Condition, DataType, Database, Operator, Query, Traverse, Value};
+
+    fn employee(id: i64, manager: Option<i64>) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("manager_id".to_string(), manager.map_or(Value::Null, Value::Integer));
+        row
+    }
+
+    async fn org_chart(wal_path: &str) -> Database {
+        let db = Database::new([0; 32], wal_path);
+        db.create_table(
+            "employees".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("manager_id".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        //        1
+        //      /   \
+        //     2     3
+        //    / \     \
+        //   4   5     6
+        //             |
+        //             7
+        for (id, manager) in [(1, None), (2, Some(1)), (3, Some(1)), (4, Some(2)), (5, Some(2)), (6, Some(3)), (7, Some(6))] {
+            db.insert("employees", employee(id, manager)).await.unwrap();
+        }
+        db
+    }
+
+    fn id_is(id: i64) -> Box<Query> {
+        Box::new(Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(id),
+        }))
+    }
+
+    fn ids_and_depths(rows: &[zapdb::Row]) -> Vec<(i64, i64)> {
+        rows.iter()
+            .map(|row| match (row.get("id"), row.get("depth")) {
+                (Some(Value::Integer(id)), Some(Value::Integer(depth))) => (*id, *depth),
+                other => panic!("unexpected row {:?}", other),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_traverse_down_and_up() {
+        let wal_path = "test_traverse_down_and_up.wal";
+        let db = org_chart(wal_path).await;
+
+        let reports = Query::Traverse(Traverse {
+            start: id_is(2),
+            from: "id".to_string(),
+            to: "manager_id".to_string(),
+            max_depth: None,
+        });
+        let (rows, _) = db.select("employees", &reports).await.unwrap();
+        assert_eq!(ids_and_depths(&rows), vec![(2, 0), (4, 1), (5, 1)]);
+
+        let direct_reports_of_ceo = Query::Traverse(Traverse {
+            start: id_is(1),
+            from: "id".to_string(),
+            to: "manager_id".to_string(),
+            max_depth: Some(1),
+        });
+        assert_eq!(db.count("employees", &direct_reports_of_ceo).await.unwrap(), 3);
+
+        let chain_of_command = Query::Traverse(Traverse {
+            start: id_is(7),
+            from: "manager_id".to_string(),
+            to: "id".to_string(),
+            max_depth: None,
+        });
+        let (rows, _) = db.select("employees", &chain_of_command).await.unwrap();
+        assert_eq!(ids_and_depths(&rows), vec![(7, 0), (6, 1), (3, 2), (1, 3)]);
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_traverse_cycles_and_errors() {
+        let wal_path = "test_traverse_cycles.wal";
+        let db = org_chart(wal_path).await;
+        // Make 1 report to 7, closing a loop.
+        db.delete("employees", &id_is(1)).await.unwrap();
+        db.insert("employees", employee(1, Some(7))).await.unwrap();
+
+        let everyone = Query::Traverse(Traverse {
+            start: id_is(3),
+            from: "id".to_string(),
+            to: "manager_id".to_string(),
+            max_depth: None,
+        });
+        let (rows, _) = db.select("employees", &everyone).await.unwrap();
+        assert_eq!(rows.len(), 7);
+        assert_eq!(ids_and_depths(&rows)[..4], [(3, 0), (6, 1), (7, 2), (1, 3)]);
+
+        let bad_column = Query::Traverse(Traverse {
+            start: id_is(3),
+            from: "id".to_string(),
+            to: "boss".to_string(),
+            max_depth: None,
+        });
+        assert_eq!(
+            db.select("employees", &bad_column).await.unwrap_err(),
+            "Column boss not found"
+        );
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_traverse_json_and_views() {
+        let wal_path = "test_traverse_json.wal";
+        let db = org_chart(wal_path).await;
+        let json = r#"{"traverse": {"start": {"condition": {"column": "id", "op": "eq", "value": 3}},
+                                    "from": "id", "to": "manager_id", "max_depth": 1}}"#;
+        let query = db.query_from_json("employees", json).await.unwrap();
+        assert_eq!(db.count("employees", &query).await.unwrap(), 2);
+        assert_eq!(
+            db.query_from_json("employees", &query.to_json().to_string()).await.unwrap().to_json(),
+            query.to_json()
+        );
+
+        db.create_view("team_3", "employees", query).await.unwrap();
+        let deep = Query::Condition(Condition {
+            column: "depth".to_string(),
+            operator: Operator::Gt,
+            value: Value::Integer(0),
+        });
+        let (rows, _) = db.select("team_3", &deep).await.unwrap();
+        assert_eq!(ids_and_depths(&rows), vec![(6, 1)]);
+
+        let _ = fs::remove_file(wal_path);
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(test)]
+mod tests {
+    use zapdb::{create_pool, Column, DataType, Value, Query, Condition, Operator, Row};
+    use serde::{Deserialize, Serialize};
+    use std::collections::HashMap;
+    use std::fs;
+    use chrono::{DateTime, Utc};
+    use uuid::Uuid;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct User {
+        id: i64,
+        name: String,
+        score: f64,
+        active: bool,
+        created_at: DateTime<Utc>,
+        uuid: Uuid,
+        nickname: Option<String>,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Product {
+        id: i64,
+        name: String,
+        price: f64,
+    }
+
+    #[tokio::test]
+    async fn test_select_as() {
+        let wal_path = "test_select_as.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+                Column::new("score".to_string(), DataType::Float, vec![]),
+                Column::new("active".to_string(), DataType::Boolean, vec![]),
+                Column::new("created_at".to_string(), DataType::DateTime, vec![]),
+                Column::new("uuid".to_string(), DataType::Uuid, vec![]),
+                Column::new("nickname".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let now = Utc::now();
+        let uuid = Uuid::new_v4();
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(1));
+        row.insert("name".to_string(), Value::String("Alice".to_string()));
+        row.insert("score".to_string(), Value::Float(9.5));
+        row.insert("active".to_string(), Value::Boolean(true));
+        row.insert("created_at".to_string(), Value::DateTime(now));
+        row.insert("uuid".to_string(), Value::Uuid(uuid));
+        row.insert("nickname".to_string(), Value::Null);
+        db.insert("users", row).await.unwrap();
+
+        let query = Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(1),
+        });
+        let (users, _) = db.select_as::<User>("users", &query).await.unwrap();
+        assert_eq!(
+            users,
+            vec![User {
+                id: 1,
+                name: "Alice".to_string(),
+                score: 9.5,
+                active: true,
+                created_at: now,
+                uuid,
+                nickname: None,
+            }]
+        );
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[tokio::test]
+    async fn test_insert_struct() {
+        let wal_path = "test_insert_struct.wal";
+        let pool = create_pool([0; 32], wal_path).unwrap();
+        let db = pool.get().unwrap();
+        db.create_table(
+            "products".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+                Column::new("price".to_string(), DataType::Float, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let product = Product { id: 1, name: "Widget".to_string(), price: 3.0 };
+        db.insert_struct("products", &product).await.unwrap();
+
+        let (rows, _) = db.select("products", &Query::MatchAll).await.unwrap();
+        assert_eq!(rows[0].get("price"), Some(&Value::Float(3.0)));
+        let (products, _) = db.select_as::<Product>("products", &Query::MatchAll).await.unwrap();
+        assert_eq!(products, vec![product]);
+
+        #[derive(Serialize)]
+        struct Unknown {
+            id: i64,
+            colour: String,
+        }
+        let result = db.insert_struct("products", &Unknown { id: 2, colour: "red".to_string() }).await;
+        assert!(result.is_err());
+
+        let _ = fs::remove_file(wal_path);
+    }
+
+    #[test]
+    fn test_row_accessors() {
+        let now = Utc::now();
+        let row: Row = vec![
+            ("id".to_string(), Value::Integer(7)),
+            ("name".to_string(), Value::String("Alice".to_string())),
+            ("created_at".to_string(), Value::DateTime(now)),
+            ("nickname".to_string(), Value::Null),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(row.get_i64("id"), Some(7));
+        assert_eq!(row.get_str("name"), Some("Alice"));
+        assert_eq!(row.get_datetime("created_at"), Some(now));
+        assert_eq!(row.get_str("id"), None);
+        assert_eq!(row.get_str("nickname"), None);
+        assert_eq!(row.get_i64("missing"), None);
+
+        assert_eq!(row.try_get::<i64>("id"), Ok(7));
+        assert_eq!(row.try_get::<Option<String>>("nickname"), Ok(None));
+        assert!(row.try_get::<String>("id").is_err());
+        assert!(row.try_get::<i64>("missing").is_err());
+
+        // Rows still behave like the underlying map.
+        assert_eq!(row.get("id"), Some(&Value::Integer(7)));
+        assert_eq!(row.len(), 4);
+    }
+
+    #[test]
+    fn test_row_derefs_to_map() {
+        fn names(map: &HashMap<String, Value>) -> usize {
+            map.len()
+        }
+        let mut row: Row = vec![("id".to_string(), Value::Integer(7))].into_iter().collect();
+        assert_eq!(names(&row), 1);
+        assert_eq!(row.get_key_value("id"), Some((&"id".to_string(), &Value::Integer(7))));
+        assert_eq!(row["id"], Value::Integer(7));
+
+        // The map follows changes to the row.
+        row.insert("name".to_string(), Value::String("Alice".to_string()));
+        assert_eq!(names(&row), 2);
+        *row.get_mut("id").unwrap() = Value::Integer(8);
+        let expected = HashMap::from([
+            ("id".to_string(), Value::Integer(8)),
+            ("name".to_string(), Value::String("Alice".to_string())),
+        ]);
+        assert_eq!(*row, expected);
+    }
+}
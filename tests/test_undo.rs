@@ -0,0 +1,129 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{
+        begin_transaction, Column, Condition, Constraint, DataType, Database, DatabaseConfig,
+        Operator, Query, Value,
+    };
+    use std::collections::HashMap;
+
+    fn user(id: i64, name: &str) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("name".to_string(), Value::String(name.to_string()));
+        row
+    }
+
+    fn by_id(id: i64) -> Query {
+        Query::Condition(Condition {
+            column: "id".to_string(),
+            operator: Operator::Eq,
+            value: Value::Integer(id),
+        })
+    }
+
+    async fn setup(undo_depth: usize) -> Database {
+        let config = DatabaseConfig {
+            undo_depth,
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![Constraint::Unique]),
+                Column::new("name".to_string(), DataType::String, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    async fn names(db: &Database) -> Vec<String> {
+        let (rows, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        let mut names: Vec<String> = rows
+            .iter()
+            .map(|r| r.get_str("name").unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[tokio::test]
+    async fn test_undo_and_redo() {
+        let db = setup(10).await;
+        db.insert("users", user(1, "Alice")).await.unwrap();
+        db.insert("users", user(2, "Bob")).await.unwrap();
+        db.update("users", &by_id(1), |row| {
+            row.insert("name".to_string(), Value::String("Alicia".to_string()));
+        })
+        .await
+        .unwrap();
+        db.delete("users", &by_id(2)).await.unwrap();
+        assert_eq!(names(&db).await, vec!["Alicia"]);
+
+        assert_eq!(db.undo(2).await.unwrap(), 2);
+        assert_eq!(names(&db).await, vec!["Alice", "Bob"]);
+        assert_eq!(db.redo(1).await.unwrap(), 1);
+        assert_eq!(names(&db).await, vec!["Alicia", "Bob"]);
+
+        // A transaction is undone as a whole.
+        let mut transaction = begin_transaction();
+        transaction.insert("users".to_string(), user(3, "Carol"));
+        transaction.delete("users".to_string(), by_id(1));
+        db.commit(transaction).await.unwrap();
+        assert_eq!(names(&db).await, vec!["Bob", "Carol"]);
+        assert_eq!(db.undo(1).await.unwrap(), 1);
+        assert_eq!(names(&db).await, vec!["Alicia", "Bob"]);
+
+        // A new write discards what could be redone.
+        db.insert("users", user(4, "Dave")).await.unwrap();
+        assert_eq!(db.redo(1).await.unwrap(), 0);
+
+        // Undoing more than was recorded stops at the oldest write.
+        assert_eq!(db.undo(100).await.unwrap(), 4);
+        assert!(names(&db).await.is_empty());
+        assert_eq!(db.get("users", &Value::Integer(1)).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_undo_limits() {
+        let db = setup(2).await;
+        for (id, name) in [(1, "Alice"), (2, "Bob"), (3, "Carol")] {
+            db.insert("users", user(id, name)).await.unwrap();
+        }
+        // Only the last two writes are kept.
+        assert_eq!(db.undo(3).await.unwrap(), 2);
+        assert_eq!(names(&db).await, vec!["Alice"]);
+
+        // Rows changed behind the database's back can't be restored, and a
+        // failed undo can be retried.
+        db.insert("users", user(2, "Bob")).await.unwrap();
+        db.tables.write().await.clear();
+        assert!(db.undo(1).await.is_err());
+
+        // Without an undo depth nothing is recorded.
+        let db = setup(0).await;
+        db.insert("users", user(1, "Alice")).await.unwrap();
+        assert_eq!(db.undo(1).await.unwrap(), 0);
+        assert_eq!(names(&db).await, vec!["Alice"]);
+    }
+
+    #[tokio::test]
+    async fn test_undo_is_logged() {
+        let db = setup(10).await;
+        db.insert("users", user(1, "Alice")).await.unwrap();
+        db.insert("users", user(2, "Bob")).await.unwrap();
+        db.delete("users", &by_id(1)).await.unwrap();
+        db.undo(1).await.unwrap();
+        db.undo(1).await.unwrap();
+        assert_eq!(names(&db).await, vec!["Alice"]);
+        let storage = db.storage();
+        drop(db);
+
+        let restored = fixtures::reopen(storage);
+        restored.load("test_undo_is_logged.zap").await.unwrap();
+        assert_eq!(names(&restored).await, vec!["Alice"]);
+    }
+}
@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{Column, DataType, Database, DatabaseConfig, Query, UnknownColumns, Value};
+    use std::collections::HashMap;
+
+    async fn setup(unknown_columns: UnknownColumns) -> Database {
+        let config = DatabaseConfig {
+            unknown_columns,
+            ..Default::default()
+        };
+        let db = fixtures::database_with(config);
+        db.create_table(
+            "users".to_string(),
+            vec![Column::new("id".to_string(), DataType::Integer, vec![])],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    fn user(id: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("nickname".to_string(), Value::String("al".to_string()));
+        row.insert("age".to_string(), Value::Integer(30));
+        row
+    }
+
+    async fn columns(db: &Database) -> Vec<Vec<String>> {
+        let (rows, _) = db.select("users", &Query::MatchAll).await.unwrap();
+        rows.iter()
+            .map(|r| {
+                let mut names: Vec<String> = r.to_map().into_keys().collect();
+                names.sort();
+                names
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_unknown_columns_kept_by_default() {
+        let db = setup(UnknownColumns::Keep).await;
+        db.insert("users", user(1)).await.unwrap();
+        assert_eq!(columns(&db).await, vec![vec!["age", "id", "nickname"]]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_columns_rejected() {
+        let db = setup(UnknownColumns::Reject).await;
+        let error = db.insert("users", user(1)).await.unwrap_err();
+        assert!(error.to_string().contains("age, nickname"), "{}", error);
+
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(2));
+        db.insert("users", row).await.unwrap();
+        let result = db
+            .update("users", &Query::MatchAll, |row| {
+                row.insert("age".to_string(), Value::Integer(3));
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(columns(&db).await, vec![vec!["id"]]);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_columns_stripped() {
+        let db = setup(UnknownColumns::Strip).await;
+        db.insert("users", user(1)).await.unwrap();
+        db.update("users", &Query::MatchAll, |row| {
+            row.insert("age".to_string(), Value::Integer(3));
+        })
+        .await
+        .unwrap();
+        assert_eq!(columns(&db).await, vec![vec!["id"]]);
+    }
+}
@@ -0,0 +1,145 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{
+        AggregateFunction, AggregateQuery, Column, Condition, DataType, Database, Join, JoinType,
+        Operator, Query, Value,
+    };
+    use std::collections::HashMap;
+
+    fn condition(column: &str, operator: Operator, value: i64) -> Query {
+        Query::Condition(Condition {
+            column: column.to_string(),
+            operator,
+            value: Value::Integer(value),
+        })
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db.create_table(
+            "posts".to_string(),
+            vec![
+                Column::new("post_id".to_string(), DataType::Integer, vec![]),
+                Column::new("user_id".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        for (id, age) in [(1, 15), (2, 30), (3, 45)] {
+            let mut row = HashMap::new();
+            row.insert("id".to_string(), Value::Integer(id));
+            row.insert("age".to_string(), Value::Integer(age));
+            db.insert("users", row).await.unwrap();
+        }
+        for (post_id, user_id) in [(10, 1), (11, 2), (12, 2)] {
+            let mut row = HashMap::new();
+            row.insert("post_id".to_string(), Value::Integer(post_id));
+            row.insert("user_id".to_string(), Value::Integer(user_id));
+            db.insert("posts", row).await.unwrap();
+        }
+        db
+    }
+
+    #[tokio::test]
+    async fn test_filter_view() {
+        let db = setup().await;
+        db.create_view("adults", "users", condition("age", Operator::Gte, 18))
+            .await
+            .unwrap();
+
+        assert_eq!(db.count("adults", &Query::MatchAll).await.unwrap(), 2);
+        let over_40 = condition("age", Operator::Gt, 40);
+        let (rows, _) = db.select("adults", &over_40).await.unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].get_i64("id"), Some(3));
+        let first = db.first("adults", &Query::MatchAll).await.unwrap().unwrap();
+        assert!(first.get_i64("age").unwrap() >= 18);
+
+        let sum = Query::Aggregate(AggregateQuery::new(AggregateFunction::Sum, "age"));
+        let (rows, _) = db.select("adults", &sum).await.unwrap();
+        assert_eq!(rows[0].get("result"), Some(&Value::Float(75.0)));
+
+        assert!(db.create_table("adults".to_string(), vec![]).await.is_err());
+        assert!(db.create_view("users", "users", Query::MatchAll).await.is_err());
+        assert!(db.create_view("other", "missing", Query::MatchAll).await.is_err());
+
+        db.drop_view("adults").await.unwrap();
+        assert!(db.select("adults", &Query::MatchAll).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_join_view() {
+        let db = setup().await;
+        let join = Query::Join(Join::new(JoinType::Inner, "posts", ("id", "user_id")));
+        db.create_view("user_posts", "users", join).await.unwrap();
+
+        assert_eq!(db.count("user_posts", &Query::MatchAll).await.unwrap(), 3);
+        let (rows, _) = db
+            .select("user_posts", &condition("age", Operator::Eq, 30))
+            .await
+            .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|r| r.get_i64("user_id") == Some(2)));
+
+        let count = Query::Aggregate(AggregateQuery::new(AggregateFunction::Count, "id"));
+        assert!(db.select("user_posts", &count).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_outer_join_view() {
+        let db = setup().await;
+        db.create_index("users", "age").await.unwrap();
+        let join = Query::Join(Join::new(JoinType::Left, "posts", ("id", "user_id")));
+        db.create_view("user_posts", "users", join).await.unwrap();
+
+        let ids = |rows: &[zapdb::Row]| {
+            rows.iter()
+                .map(|r| (r.get_i64("id"), r.get_i64("post_id")))
+                .collect::<Vec<_>>()
+        };
+        // On the preserved side: users without posts are still padded.
+        let (rows, _) = db
+            .select("user_posts", &condition("age", Operator::Gte, 30))
+            .await
+            .unwrap();
+        assert_eq!(ids(&rows), vec![(Some(2), Some(11)), (Some(2), Some(12)), (Some(3), None)]);
+
+        // On the padded side: the padded rows don't match.
+        let filter = Query::And(vec![
+            condition("post_id", Operator::Gte, 11),
+            condition("age", Operator::Lt, 40),
+        ]);
+        let (rows, _) = db.select("user_posts", &filter).await.unwrap();
+        assert_eq!(ids(&rows), vec![(Some(2), Some(11)), (Some(2), Some(12))]);
+    }
+
+    #[tokio::test]
+    async fn test_views_are_persisted() {
+        let db_path = "test_views_persisted.zap";
+        let db = setup().await;
+        db.create_view("adults", "users", condition("age", Operator::Gte, 18))
+            .await
+            .unwrap();
+        db.save(db_path).await.unwrap();
+        db.create_view("minors", "users", condition("age", Operator::Lt, 18))
+            .await
+            .unwrap();
+
+        // One view comes from the snapshot, the other from the WAL.
+        let reopened = fixtures::reopen(db.storage());
+        reopened.load(db_path).await.unwrap();
+        assert_eq!(reopened.count("adults", &Query::MatchAll).await.unwrap(), 2);
+        assert_eq!(reopened.count("minors", &Query::MatchAll).await.unwrap(), 1);
+        assert!(reopened.view("adults").is_some());
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+    use zapdb::{query, Column, DataType, Database, DatabaseConfig, Query, Value};
+
+    fn archiving(wal_path: &str, dir: &str) -> Database {
+        let config = DatabaseConfig {
+            wal_archive: Some(dir.to_string()),
+            ..Default::default()
+        };
+        Database::with_config([0; 32], wal_path, config)
+    }
+
+    fn item(id: i64) -> HashMap<String, Value> {
+        HashMap::from([("id".to_string(), Value::Integer(id))])
+    }
+
+    async fn ids(db: &Database) -> Vec<i64> {
+        let (rows, _) = db.select("items", &Query::MatchAll).await.unwrap();
+        rows.iter().map(|row| row.get_i64("id").unwrap()).collect()
+    }
+
+    // Writes three segments of entries 1-3, 4-5 and 6-7, and leaves entry 8
+    // in the WAL.
+    async fn write_history(wal_path: &str, db_path: &str, dir: &str) {
+        let db = archiving(wal_path, dir);
+        db.create_table("items".to_string(), vec![Column::new("id".to_string(), DataType::Integer, vec![])])
+            .await
+            .unwrap();
+        db.insert("items", item(1)).await.unwrap();
+        db.insert("items", item(2)).await.unwrap();
+        db.save(db_path).await.unwrap();
+        db.insert("items", item(3)).await.unwrap();
+        db.delete("items", &query!(id == 1)).await.unwrap();
+        db.save(db_path).await.unwrap();
+        db.insert("items", item(4)).await.unwrap();
+        db.insert("items", item(5)).await.unwrap();
+        db.save(db_path).await.unwrap();
+        // Saving again with nothing new adds no segment.
+        db.save(db_path).await.unwrap();
+        db.insert("items", item(6)).await.unwrap();
+    }
+
+    fn cleanup(wal_path: &str, db_path: &str, dir: &str) {
+        let _ = fs::remove_file(wal_path);
+        let _ = fs::remove_file(db_path);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_from_wal_archive() {
+        let (wal_path, db_path, dir) = ("test_rebuild_from_wal.wal", "test_rebuild_from_wal.db", "test_rebuild_from_wal_segments");
+        cleanup(wal_path, db_path, dir);
+        write_history(wal_path, db_path, dir).await;
+        assert_eq!(fs::read_dir(dir).unwrap().count(), 3);
+
+        // The snapshot is lost.
+        fs::remove_file(db_path).unwrap();
+        let db = archiving(wal_path, dir);
+        let report = db.rebuild_from_wal(dir).await.unwrap();
+        assert!(report.is_complete());
+        assert_eq!(report.first_lsn, Some(1));
+        assert_eq!(report.last_lsn, Some(8));
+        assert_eq!(report.entries, 8);
+        // The WAL's entry was archived as a fourth segment.
+        assert_eq!(report.segments, 4);
+        assert_eq!(ids(&db).await, vec![2, 3, 4, 5, 6]);
+
+        // The rebuilt database saves and loads like any other.
+        db.save(db_path).await.unwrap();
+        let reopened = Database::new([0; 32], wal_path);
+        reopened.load(db_path).await.unwrap();
+        assert_eq!(ids(&reopened).await, vec![2, 3, 4, 5, 6]);
+
+        // Only an empty database can be rebuilt.
+        assert!(reopened.rebuild_from_wal(dir).await.is_err());
+
+        cleanup(wal_path, db_path, dir);
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_from_wal_reports_gaps() {
+        let (wal_path, db_path, dir) = ("test_rebuild_gaps.wal", "test_rebuild_gaps.db", "test_rebuild_gaps_segments");
+        cleanup(wal_path, db_path, dir);
+        write_history(wal_path, db_path, dir).await;
+        fs::remove_file(format!("{}/{:020}.wal", dir, 4)).unwrap();
+
+        let db = archiving(wal_path, dir);
+        let report = db.rebuild_from_wal(dir).await.unwrap();
+        assert!(!report.is_complete());
+        assert_eq!(report.gaps, vec![4..=5]);
+        assert_eq!(report.entries, 6);
+        assert_eq!(report.last_lsn, Some(8));
+        // The lost segment held the insert of 3 and the delete of 1.
+        assert_eq!(ids(&db).await, vec![1, 2, 4, 5, 6]);
+
+        cleanup(wal_path, db_path, dir);
+    }
+}
@@ -0,0 +1,100 @@
+#[cfg(test)]
+mod tests {
+    use crate::fixtures;
+    use zapdb::{
+        begin_transaction, Column, Condition, DataType, Database, Operator, Query, Value,
+        WatchEvent,
+    };
+    use std::collections::HashMap;
+
+    fn user(id: i64, age: i64) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert("id".to_string(), Value::Integer(id));
+        row.insert("age".to_string(), Value::Integer(age));
+        row
+    }
+
+    fn adults() -> Query {
+        Query::Condition(Condition {
+            column: "age".to_string(),
+            operator: Operator::Gte,
+            value: Value::Integer(18),
+        })
+    }
+
+    async fn setup() -> Database {
+        let db = fixtures::database();
+        db.create_table(
+            "users".to_string(),
+            vec![
+                Column::new("id".to_string(), DataType::Integer, vec![]),
+                Column::new("age".to_string(), DataType::Integer, vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn test_watch_filters_events() {
+        let db = setup().await;
+        let mut watcher = db.watch("users", adults()).await.unwrap();
+
+        db.insert("users", user(1, 30)).await.unwrap();
+        db.insert("users", user(2, 10)).await.unwrap();
+        match watcher.try_recv() {
+            Some(WatchEvent::Insert(row)) => assert_eq!(row.get_i64("id"), Some(1)),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(watcher.try_recv().is_none());
+
+        // A row that stops matching is still reported.
+        db.update("users", &Query::MatchAll, |row| {
+            row.insert("age".to_string(), Value::Integer(12));
+        })
+        .await
+        .unwrap();
+        match watcher.recv().await {
+            Some(WatchEvent::Update { old, new }) => {
+                assert_eq!(old.get_i64("age"), Some(30));
+                assert_eq!(new.get_i64("age"), Some(12));
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(watcher.try_recv().is_none());
+
+        db.delete("users", &Query::MatchAll).await.unwrap();
+        assert!(watcher.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_watch_transactions() {
+        let db = setup().await;
+        let mut watcher = db.watch("users", Query::MatchAll).await.unwrap();
+
+        let mut transaction = begin_transaction();
+        transaction.insert("users".to_string(), user(1, 20));
+        transaction.insert("missing".to_string(), user(2, 20));
+        assert!(db.commit(transaction).await.is_err());
+        assert!(watcher.try_recv().is_none());
+
+        let mut transaction = begin_transaction();
+        transaction.insert("users".to_string(), user(1, 20));
+        transaction.delete("users".to_string(), Query::MatchAll);
+        db.commit(transaction).await.unwrap();
+        assert!(matches!(watcher.try_recv(), Some(WatchEvent::Insert(_))));
+        assert!(matches!(watcher.try_recv(), Some(WatchEvent::Delete(_))));
+
+        db.close(None).await.unwrap();
+        assert!(watcher.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_watch_rejects_invalid_queries() {
+        let db = setup().await;
+        assert!(db.watch("missing", Query::MatchAll).await.is_err());
+        let aggregate = Query::Aggregate(zapdb::AggregateQuery::new(zapdb::AggregateFunction::Count, "id"));
+        assert!(db.watch("users", aggregate).await.is_err());
+    }
+}